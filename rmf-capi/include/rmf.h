@@ -0,0 +1,214 @@
+/* C interface to the Rust Memory Forensics Toolkit */
+
+#ifndef RMF_H
+#define RMF_H
+
+/* Generated by cbindgen from rmf-capi/src/lib.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Version of this C interface; bumped on incompatible changes
+ */
+#define RMF_CAPI_VERSION 1
+
+/**
+ * Paging scheme used for virtual address translation
+ */
+typedef enum RmfArch {
+  RMF_ARCH_X86_64 = 0,
+  RMF_ARCH_X86_PAE = 1,
+  RMF_ARCH_X86 = 2,
+  RMF_ARCH_RISCV_SV39 = 3,
+  RMF_ARCH_RISCV_SV48 = 4,
+} RmfArch;
+
+/**
+ * Result of every `rmf_*` call
+ */
+typedef enum RmfStatus {
+  RMF_STATUS_OK = 0,
+  /**
+   * A required pointer argument was NULL
+   */
+  RMF_STATUS_NULL_ARGUMENT = 1,
+  /**
+   * A string argument was not valid UTF-8 or another argument was invalid
+   */
+  RMF_STATUS_INVALID_ARGUMENT = 2,
+  /**
+   * The dump could not be opened or parsed
+   */
+  RMF_STATUS_IO = 3,
+  /**
+   * The virtual address is not mapped
+   */
+  RMF_STATUS_UNMAPPED = 4,
+  /**
+   * The physical range lies outside the image
+   */
+  RMF_STATUS_OUT_OF_RANGE = 5,
+  /**
+   * No plugin with that name is registered
+   */
+  RMF_STATUS_NOT_FOUND = 6,
+  /**
+   * Rust code panicked; the image should not be used further
+   */
+  RMF_STATUS_INTERNAL = 7,
+} RmfStatus;
+
+/**
+ * Findings returned by `rmf_plugin_run`
+ */
+typedef struct RmfFindings RmfFindings;
+
+/**
+ * An open memory image
+ */
+typedef struct RmfImage RmfImage;
+
+/**
+ * One plugin finding; strings are owned by the `RmfFindings` they came from
+ */
+typedef struct RmfFinding {
+  uint64_t addr;
+  uint8_t confidence;
+  const char *plugin;
+  const char *description;
+  /**
+   * Details as a JSON object
+   */
+  const char *details_json;
+  /**
+   * Stable content-derived ID
+   */
+  const char *id;
+} RmfFinding;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Version of the rmf engine as a static NUL-terminated string
+ */
+const char *rmf_version(void);
+
+/**
+ * Message describing the last error on this thread, or NULL
+ *
+ * The pointer stays valid until the next failing call on the same thread.
+ */
+const char *rmf_last_error(void);
+
+/**
+ * Open a memory dump; free the result with `rmf_image_close`
+ *
+ * # Safety
+ * `path` must be a NUL-terminated string and `out` a valid pointer.
+ */
+enum RmfStatus rmf_image_open(const char *path, struct RmfImage **out);
+
+/**
+ * Close an image opened with `rmf_image_open`; NULL is ignored
+ *
+ * # Safety
+ * `image` must come from `rmf_image_open` and not be used afterwards.
+ */
+void rmf_image_close(struct RmfImage *image);
+
+/**
+ * Size of the image's physical address space in bytes
+ *
+ * # Safety
+ * `image` must be NULL or a valid image.
+ */
+uint64_t rmf_image_size(const struct RmfImage *image);
+
+/**
+ * Set the paging scheme and DTB/CR3 used by virtual reads and translation
+ *
+ * `arch` is an `RmfArch` value; any other value fails with
+ * `RMF_STATUS_INVALID_ARGUMENT`.
+ *
+ * # Safety
+ * `image` must be a valid image.
+ */
+enum RmfStatus rmf_image_set_dtb(struct RmfImage *image, uint32_t arch, uint64_t dtb);
+
+/**
+ * Translate a virtual address to a physical one
+ *
+ * # Safety
+ * `image` must be a valid image and `phys_out` a valid pointer.
+ */
+enum RmfStatus rmf_translate(const struct RmfImage *image, uint64_t virt, uint64_t *phys_out);
+
+/**
+ * Read `len` bytes of physical memory into `buf`
+ *
+ * # Safety
+ * `image` must be a valid image and `buf` must hold `len` bytes.
+ */
+enum RmfStatus rmf_read_physical(const struct RmfImage *image,
+                                 uint64_t phys,
+                                 uint8_t *buf,
+                                 size_t len);
+
+/**
+ * Read `len` bytes of virtual memory into `buf`; fails if any page is unmapped
+ *
+ * # Safety
+ * `image` must be a valid image and `buf` must hold `len` bytes.
+ */
+enum RmfStatus rmf_read_virtual(const struct RmfImage *image,
+                                uint64_t virt,
+                                uint8_t *buf,
+                                size_t len);
+
+/**
+ * Run a built-in plugin; free the result with `rmf_findings_free`
+ *
+ * # Safety
+ * `image` must be a valid image, `name` a NUL-terminated string and `out` a valid pointer.
+ */
+enum RmfStatus rmf_plugin_run(const struct RmfImage *image,
+                              const char *name,
+                              struct RmfFindings **out);
+
+/**
+ * Number of findings in a result set
+ *
+ * # Safety
+ * `findings` must be NULL or come from `rmf_plugin_run`.
+ */
+size_t rmf_findings_count(const struct RmfFindings *findings);
+
+/**
+ * Copy out the finding at `index`
+ *
+ * # Safety
+ * `findings` must come from `rmf_plugin_run` and `out` be a valid pointer.
+ */
+enum RmfStatus rmf_findings_get(const struct RmfFindings *findings,
+                                size_t index,
+                                struct RmfFinding *out);
+
+/**
+ * Free a result set; NULL is ignored
+ *
+ * # Safety
+ * `findings` must come from `rmf_plugin_run` and not be used afterwards.
+ */
+void rmf_findings_free(struct RmfFindings *findings);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* RMF_H */
@@ -0,0 +1,65 @@
+// Thin wrapper over the wasm-bindgen output in ../pkg (built with wasm-pack).
+// Addresses are accepted as Number or BigInt and returned as BigInt.
+import init, { Image, version } from "../pkg/rmf_wasm.js";
+
+let ready = null;
+
+/** Load the WebAssembly module once; pass a URL or bytes outside bundlers */
+export function initialize(source) {
+  ready ??= init(source);
+  return ready;
+}
+
+const big = (value) => BigInt(value);
+
+export class MemoryImage {
+  #image;
+
+  constructor(image) {
+    this.#image = image;
+  }
+
+  /** Parse a dump from an ArrayBuffer, Uint8Array or Node Buffer */
+  static async open(bytes, source) {
+    await initialize(source);
+    const data = bytes instanceof Uint8Array ? bytes : new Uint8Array(bytes);
+    return new MemoryImage(new Image(data));
+  }
+
+  get size() {
+    return this.#image.size();
+  }
+
+  /** Format, size, physical runs and minidump modules */
+  get info() {
+    return JSON.parse(this.#image.infoJson());
+  }
+
+  /** arch: "x64", "pae", "x86", "riscv" or "riscv-sv48" */
+  setDtb(dtb, arch = "x64") {
+    this.#image.setDtb(arch, big(dtb));
+    return this;
+  }
+
+  /** Physical address for `va`, or null when unmapped */
+  translate(va) {
+    return this.#image.translate(big(va)) ?? null;
+  }
+
+  /** Uint8Array of physical memory, or null outside the image */
+  readPhysical(pa, length) {
+    return this.#image.readPhysical(big(pa), length) ?? null;
+  }
+
+  /** Uint8Array of virtual memory, or null when any page is unmapped */
+  readVirtual(va, length) {
+    return this.#image.readVirtual(big(va), length) ?? null;
+  }
+
+  /** Release the WebAssembly-side copy of the image */
+  close() {
+    this.#image.free();
+  }
+}
+
+export { version };
@@ -0,0 +1,142 @@
+//! WebAssembly bindings for the rmf parsing core
+//!
+//! Exposes container format detection, physical/virtual reads and address
+//! translation over an image held in memory. Build with
+//! `wasm-pack build rmf-wasm --target web` (or `--target nodejs`) and use
+//! the wrapper in `js/index.js`. Images are copied into WebAssembly
+//! memory, so this is meant for small images (well under 4 GB).
+
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+
+use rmf::loader::image_from_bytes;
+use rmf::{Architecture, MemoryImage};
+
+fn parse_arch(arch: &str) -> Option<Architecture> {
+    Some(match arch {
+        "x64" | "x86_64" => Architecture::X86_64,
+        "pae" => Architecture::X86Pae,
+        "x86" => Architecture::X86,
+        "riscv" | "sv39" => Architecture::RiscvSv39,
+        "riscv-sv48" | "sv48" => Architecture::RiscvSv48,
+        _ => return None,
+    })
+}
+
+/// A memory image loaded from a byte buffer
+#[wasm_bindgen]
+pub struct Image {
+    inner: MemoryImage,
+}
+
+impl Image {
+    /// Parse dump contents, detecting raw, ELF core and minidump containers
+    pub fn parse(bytes: Vec<u8>) -> Result<Image, String> {
+        image_from_bytes(bytes)
+            .map(|inner| Image { inner })
+            .map_err(|e| format!("{:#}", e))
+    }
+
+    /// Select the paging scheme and DTB for translation
+    pub fn configure(&mut self, arch: &str, dtb: u64) -> Result<(), String> {
+        let arch = parse_arch(arch).ok_or_else(|| format!("unknown architecture '{}'", arch))?;
+        self.inner.set_arch(arch).set_cr3(dtb);
+        Ok(())
+    }
+
+    /// Container details as a JSON document
+    pub fn info_json(&self) -> String {
+        let info = &self.inner.info;
+        let runs: Vec<_> = self.inner.runs().iter()
+            .map(|r| json!({ "start": r.start, "length": r.length }))
+            .collect();
+        let modules: Vec<_> = info.user.iter()
+            .flat_map(|u| &u.modules)
+            .map(|m| json!({ "base": m.base, "size": m.size, "name": m.name }))
+            .collect();
+        json!({
+            "format": format!("{:?}", info.format),
+            "size": self.inner.size(),
+            "dtb": info.dtb,
+            "runs": runs,
+            "modules": modules,
+        })
+        .to_string()
+    }
+}
+
+#[wasm_bindgen]
+impl Image {
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: Vec<u8>) -> Result<Image, JsError> {
+        Image::parse(bytes).map_err(|e| JsError::new(&e))
+    }
+
+    /// Size of the physical address space in bytes
+    pub fn size(&self) -> u64 {
+        self.inner.size() as u64
+    }
+
+    /// Set the paging scheme (`x64`, `pae`, `x86`, `riscv`, `riscv-sv48`) and DTB/CR3
+    #[wasm_bindgen(js_name = setDtb)]
+    pub fn set_dtb(&mut self, arch: &str, dtb: u64) -> Result<(), JsError> {
+        self.configure(arch, dtb).map_err(|e| JsError::new(&e))
+    }
+
+    /// Translate a virtual address; `undefined` when it is not mapped
+    pub fn translate(&self, virt: u64) -> Option<u64> {
+        self.inner.virt_to_phys(virt)
+    }
+
+    /// Read physical memory; `undefined` when the range is outside the image
+    #[wasm_bindgen(js_name = readPhysical)]
+    pub fn read_physical(&self, phys: u64, len: usize) -> Option<Vec<u8>> {
+        self.inner.get_bytes(phys as usize, len).map(|b| b.to_vec())
+    }
+
+    /// Read virtual memory; `undefined` when any page is unmapped
+    #[wasm_bindgen(js_name = readVirtual)]
+    pub fn read_virtual(&self, virt: u64, len: usize) -> Option<Vec<u8>> {
+        self.inner.read_virt(virt, len)
+    }
+
+    /// Format, size, physical runs and (for minidumps) modules as JSON
+    #[wasm_bindgen(js_name = infoJson)]
+    pub fn info_json_js(&self) -> String {
+        self.info_json()
+    }
+}
+
+/// Version of the rmf engine
+#[wasm_bindgen]
+pub fn version() -> String {
+    env!("CARGO_PKG_VERSION").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_translate_and_read() {
+        let mut data = vec![0u8; 64 * 1024];
+        // PML4 0x1000 -> PDPT 0x2000 -> PD 0x3000 -> PT 0x4000 -> page 0x5000
+        for (table, next) in [(0x1000, 0x2000u64), (0x2000, 0x3000), (0x3000, 0x4000), (0x4000, 0x5000)] {
+            data[table..table + 8].copy_from_slice(&(next | 1).to_le_bytes());
+        }
+        data[0x5000..0x5004].copy_from_slice(b"WASM");
+
+        let mut image = Image::parse(data).unwrap();
+        assert_eq!(image.size(), 64 * 1024);
+        assert_eq!(image.translate(0), None);
+        assert!(image.configure("arm", 0x1000).is_err());
+        image.configure("x64", 0x1000).unwrap();
+        assert_eq!(image.translate(0x10), Some(0x5010));
+        assert_eq!(image.read_virtual(0, 4).as_deref(), Some(&b"WASM"[..]));
+        assert_eq!(image.read_physical(64 * 1024, 1), None);
+
+        let info: serde_json::Value = serde_json::from_str(&image.info_json()).unwrap();
+        assert_eq!(info["format"], "Raw");
+        assert_eq!(info["dtb"], 0x1000);
+    }
+}
@@ -0,0 +1,224 @@
+//! External actions on findings and extracted files
+//!
+//! rmf often sits at the start of a larger pipeline: extracted PEs go to
+//! capa or a sandbox, findings to a ticketing script. An action is a
+//! command template run once per finding or once per file written by an
+//! extraction command, such as `capa --json {path}`. Templates are split
+//! into arguments before placeholders are filled in, and commands run
+//! without a shell, so values taken from the dump cannot inject arguments
+//! or shell syntax. At most `jobs` commands run at once.
+//!
+//! Finding placeholders are `{dump}`, `{plugin}`, `{id}`, `{addr}`,
+//! `{confidence}`, `{desc}` and every detail key, e.g. `{rule}`; file
+//! placeholders are `{dump}`, `{path}`, `{name}` and `{dir}`. Placeholders
+//! a finding has no value for are replaced with nothing.
+
+use anyhow::{bail, Result};
+use colored::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::limits::ResourceLimits;
+use crate::plugin::Finding;
+
+/// Commands running at once when no limit is set
+const DEFAULT_JOBS: usize = 4;
+
+/// Commands to run on findings and extracted files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Actions {
+    on_finding: Vec<Vec<String>>,
+    on_file: Vec<Vec<String>>,
+    jobs: Option<usize>,
+}
+
+static ACTIONS: Mutex<Actions> = Mutex::new(Actions { on_finding: Vec::new(), on_file: Vec::new(), jobs: None });
+
+/// One command that was run
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionRun {
+    pub command: Vec<String>,
+    /// Exit code, or `None` when it could not be started or was killed
+    pub status: Option<i32>,
+    pub error: Option<String>,
+}
+
+impl ActionRun {
+    pub fn succeeded(&self) -> bool {
+        self.status == Some(0)
+    }
+}
+
+impl Actions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `template` for every finding
+    pub fn on_finding(mut self, template: &str) -> Result<Self> {
+        self.on_finding.push(split_template(template)?);
+        Ok(self)
+    }
+
+    /// Run `template` for every file an extraction command writes
+    pub fn on_file(mut self, template: &str) -> Result<Self> {
+        self.on_file.push(split_template(template)?);
+        Ok(self)
+    }
+
+    /// Run at most `jobs` commands at once
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs.max(1));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.on_finding.is_empty() && self.on_file.is_empty()
+    }
+
+    /// Use these actions for the rest of the process
+    pub fn apply(self) {
+        *ACTIONS.lock().unwrap() = self;
+    }
+
+    /// The commands for each finding of a scan of `dump`
+    pub fn finding_commands(&self, dump: &Path, findings: &[Finding]) -> Vec<Vec<String>> {
+        findings.iter().flat_map(|finding| {
+            let mut values: BTreeMap<&str, String> = finding.details.iter().map(|(k, v)| (k.as_str(), v.clone())).collect();
+            values.insert("dump", dump.display().to_string());
+            values.insert("plugin", finding.plugin.clone());
+            values.insert("id", finding.id());
+            values.insert("addr", format!("0x{:X}", finding.addr));
+            values.insert("confidence", finding.confidence.to_string());
+            values.insert("desc", finding.desc.clone());
+            self.on_finding.iter().map(move |template| render(template, &values)).collect::<Vec<_>>()
+        }).collect()
+    }
+
+    /// The commands for each file extracted from `dump`
+    pub fn file_commands(&self, dump: &Path, files: &[PathBuf]) -> Vec<Vec<String>> {
+        files.iter().flat_map(|file| {
+            let values = BTreeMap::from([
+                ("dump", dump.display().to_string()),
+                ("path", file.display().to_string()),
+                ("name", file.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned())),
+                ("dir", file.parent().map_or(String::new(), |d| d.display().to_string())),
+            ]);
+            self.on_file.iter().map(move |template| render(template, &values)).collect::<Vec<_>>()
+        }).collect()
+    }
+
+    /// Run `commands`, at most `jobs` at once, in the order given
+    pub fn run(&self, commands: Vec<Vec<String>>) -> Vec<ActionRun> {
+        if commands.is_empty() {
+            return Vec::new();
+        }
+        let jobs = ResourceLimits::new().threads_for(commands.len().min(self.jobs.unwrap_or(DEFAULT_JOBS)));
+        let next = AtomicUsize::new(0);
+        let runs: Vec<Mutex<Option<ActionRun>>> = commands.iter().map(|_| Mutex::new(None)).collect();
+        std::thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(command) = commands.get(index) else { break };
+                    *runs[index].lock().unwrap() = Some(run_command(command));
+                });
+            }
+        });
+        runs.into_iter().filter_map(|run| run.into_inner().unwrap()).collect()
+    }
+}
+
+/// Split a command template into arguments: whitespace separates them, and
+/// single or double quotes keep whitespace inside one
+pub fn split_template(template: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in template.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        bail!("unterminated quote in action '{}'", template);
+    }
+    args.extend(current);
+    if args.is_empty() {
+        bail!("empty action command");
+    }
+    Ok(args)
+}
+
+/// Fill the `{name}` placeholders of each argument from `values`
+pub fn render(template: &[String], values: &BTreeMap<&str, String>) -> Vec<String> {
+    template.iter().map(|arg| {
+        let mut out = String::new();
+        let mut rest = arg.as_str();
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            match rest[open..].find('}') {
+                Some(close) => {
+                    let key = &rest[open + 1..open + close];
+                    out.push_str(values.get(key).map_or("", |v| v.as_str()));
+                    rest = &rest[open + close + 1..];
+                }
+                None => {
+                    out.push_str(&rest[open..]);
+                    rest = "";
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }).collect()
+}
+
+fn run_command(command: &[String]) -> ActionRun {
+    match Command::new(&command[0]).args(&command[1..]).status() {
+        Ok(status) => ActionRun { command: command.to_vec(), status: status.code(), error: None },
+        Err(e) => ActionRun { command: command.to_vec(), status: None, error: Some(e.to_string()) },
+    }
+}
+
+fn report(kind: &str, runs: &[ActionRun]) {
+    if runs.is_empty() {
+        return;
+    }
+    let failed: Vec<&ActionRun> = runs.iter().filter(|run| !run.succeeded()).collect();
+    println!("{} {} actions on {} ({} failed)",
+        "Ran".bright_green(), runs.len().to_string().bright_yellow(), kind, failed.len());
+    for run in failed {
+        let reason = run.error.clone().unwrap_or_else(|| match run.status {
+            Some(code) => format!("exit status {}", code),
+            None => "killed by a signal".to_string(),
+        });
+        println!("  {} {}: {}", "Failed:".bright_red(), run.command.join(" "), reason);
+    }
+}
+
+/// Run the configured finding actions on the findings of a scan of `dump`
+pub fn run_for_findings(dump: &Path, findings: &[Finding]) -> Vec<ActionRun> {
+    let actions = ACTIONS.lock().unwrap().clone();
+    let runs = actions.run(actions.finding_commands(dump, findings));
+    report("findings", &runs);
+    runs
+}
+
+/// Run the configured file actions on files extracted from `dump`
+pub fn run_for_files(dump: &Path, files: &[PathBuf]) -> Vec<ActionRun> {
+    let actions = ACTIONS.lock().unwrap().clone();
+    let runs = actions.run(actions.file_commands(dump, files));
+    report("extracted files", &runs);
+    runs
+}
@@ -1 +1,4 @@
+pub mod riscv;
+pub mod x86;
 pub mod x86_64;
+pub mod x86_pae;
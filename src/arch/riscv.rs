@@ -0,0 +1,82 @@
+//! RISC-V Sv39/Sv48 paging structures
+//!
+//! Both schemes use 4KB pages and 512-entry tables of 64-bit PTEs; Sv39
+//! walks 3 levels over a 39-bit address space and Sv48 walks 4 levels over
+//! 48 bits. A valid PTE with any of R/W/X set is a leaf, so higher levels
+//! map 2MB/1GB/512GB superpages.
+
+pub use super::x86_64::PAGE_SIZE;
+
+/// satp.MODE values
+pub const SATP_MODE_SV39: u64 = 8;
+pub const SATP_MODE_SV48: u64 = 9;
+
+/// Accept either a root table address or a raw satp value as the DTB
+pub fn root_table(dtb: u64) -> u64 {
+    match dtb >> 60 {
+        SATP_MODE_SV39 | SATP_MODE_SV48 => (dtb & 0xFFF_FFFF_FFFF) << 12,
+        _ => dtb & !0xFFF,
+    }
+}
+
+/// RISC-V page table entry
+pub struct PageTableEntry(u64);
+
+impl PageTableEntry {
+    pub fn new(value: u64) -> Self {
+        PageTableEntry(value)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (self.0 & 0x1) == 0x1
+    }
+
+    /// R, W or X set: the entry maps a page rather than the next table
+    pub fn is_leaf(&self) -> bool {
+        (self.0 & 0xE) != 0
+    }
+
+    /// W without R is reserved and faults
+    pub fn is_reserved(&self) -> bool {
+        (self.0 & 0x6) == 0x4
+    }
+
+    /// Physical address from the PPN field (bits 53:10)
+    pub fn get_physical_address(&self) -> u64 {
+        ((self.0 >> 10) & 0xFFF_FFFF_FFFF) << 12
+    }
+
+    pub fn flags(&self) -> u64 {
+        self.0 & 0x3FF
+    }
+}
+
+/// Virtual address split for an Sv39/Sv48 walk
+pub struct VirtualAddress(u64);
+
+impl VirtualAddress {
+    pub fn new(addr: u64) -> Self {
+        VirtualAddress(addr)
+    }
+
+    pub fn addr(&self) -> u64 {
+        self.0
+    }
+
+    /// Addresses must be sign-extended from the top bit of the scheme
+    pub fn is_canonical(&self, levels: usize) -> bool {
+        let bits = 12 + 9 * levels as u32;
+        let upper = (self.0 as i64) >> (bits - 1);
+        upper == 0 || upper == -1
+    }
+
+    /// Extract VPN[level] (9 bits each, starting at bit 12)
+    pub fn get_vpn(&self, level: usize) -> usize {
+        ((self.0 >> (12 + 9 * level)) & 0x1FF) as usize
+    }
+
+    /// Offset within a page mapped at `level` (0 = 4KB, 1 = 2MB, 2 = 1GB, ...)
+    pub fn get_page_offset(&self, level: usize) -> u64 {
+        self.0 & ((1u64 << (12 + 9 * level)) - 1)
+    }
+}
@@ -0,0 +1,100 @@
+//! Classic 32-bit x86 (non-PAE) paging structures
+//!
+//! A 2-level walk: CR3 points to a 1024-entry page directory of 32-bit
+//! entries, each pointing to a 1024-entry page table. With CR4.PSE, page
+//! directory entries with the PS bit map 4MB pages (PSE-36 extends their
+//! base beyond 4GB).
+
+pub use super::x86_64::PAGE_SIZE;
+
+/// CR3 holds the page directory address in bits 31:12
+pub fn page_directory_base(cr3: u64) -> u64 {
+    cr3 & 0xFFFF_F000
+}
+
+/// Page Directory entry
+pub struct PDEntry(u32);
+
+impl PDEntry {
+    pub fn new(value: u32) -> Self {
+        PDEntry(value)
+    }
+
+    pub fn is_present(&self) -> bool {
+        (self.0 & 0x1) == 0x1
+    }
+
+    pub fn is_page_size_4mb(&self) -> bool {
+        // PS bit (Page Size bit, bit 7) is set for 4MB pages
+        (self.0 & 0x80) == 0x80
+    }
+
+    /// Page table address for a regular entry
+    pub fn get_physical_address(&self) -> u64 {
+        (self.0 & 0xFFFF_F000) as u64
+    }
+
+    /// 4MB page base: bits 31:22, plus PSE-36 bits 39:32 from bits 20:13
+    pub fn get_large_page_address(&self) -> u64 {
+        let high = ((self.0 >> 13) & 0xFF) as u64;
+        (self.0 & 0xFFC0_0000) as u64 | (high << 32)
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.0 & 0xFFF
+    }
+}
+
+/// Page Table entry
+pub struct PTEntry(u32);
+
+impl PTEntry {
+    pub fn new(value: u32) -> Self {
+        PTEntry(value)
+    }
+
+    pub fn is_present(&self) -> bool {
+        (self.0 & 0x1) == 0x1
+    }
+
+    pub fn get_physical_address(&self) -> u64 {
+        (self.0 & 0xFFFF_F000) as u64
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.0 & 0xFFF
+    }
+}
+
+/// 32-bit virtual address split for a 2-level walk
+pub struct VirtualAddress(u32);
+
+impl VirtualAddress {
+    pub fn new(addr: u32) -> Self {
+        VirtualAddress(addr)
+    }
+
+    pub fn addr(&self) -> u32 {
+        self.0
+    }
+
+    /// Extract PD index (bits 22-31)
+    pub fn get_pd_index(&self) -> usize {
+        (self.0 >> 22) as usize
+    }
+
+    /// Extract PT index (bits 12-21)
+    pub fn get_pt_index(&self) -> usize {
+        ((self.0 >> 12) & 0x3FF) as usize
+    }
+
+    /// Extract page offset (bits 0-11)
+    pub fn get_page_offset(&self) -> usize {
+        (self.0 & 0xFFF) as usize
+    }
+
+    /// Extract 4MB page offset (bits 0-21)
+    pub fn get_large_page_offset(&self) -> usize {
+        (self.0 & 0x3F_FFFF) as usize
+    }
+}
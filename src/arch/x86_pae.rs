@@ -0,0 +1,70 @@
+//! 32-bit x86 PAE (Physical Address Extension) paging structures
+//!
+//! PAE uses a 3-level walk: CR3 points to a 32-byte aligned PDPT with four
+//! 64-bit entries, followed by 512-entry page directories and page tables.
+//! PD entries with the PS bit map 2MB pages.
+
+pub use super::x86_64::{PDEntry, PTEntry, PAGE_SIZE};
+
+/// Number of entries in the PAE page directory pointer table
+pub const PDPT_ENTRIES: usize = 4;
+
+/// PAE CR3 holds the PDPT address in bits 31:5
+pub fn pdpt_base(cr3: u64) -> u64 {
+    cr3 & 0xFFFF_FFE0
+}
+
+/// Page Directory Pointer Table entry (no large page support under PAE)
+pub struct PDPTEntry(u64);
+
+impl PDPTEntry {
+    pub fn new(value: u64) -> Self {
+        PDPTEntry(value)
+    }
+
+    pub fn is_present(&self) -> bool {
+        (self.0 & 0x1) == 0x1
+    }
+
+    pub fn get_physical_address(&self) -> u64 {
+        self.0 & 0x000F_FFFF_FFFF_F000
+    }
+}
+
+/// 32-bit virtual address split for a PAE walk
+pub struct VirtualAddress(u32);
+
+impl VirtualAddress {
+    pub fn new(addr: u32) -> Self {
+        VirtualAddress(addr)
+    }
+
+    pub fn addr(&self) -> u32 {
+        self.0
+    }
+
+    /// Extract PDPT index (bits 30-31)
+    pub fn get_pdpt_index(&self) -> usize {
+        ((self.0 >> 30) & 0x3) as usize
+    }
+
+    /// Extract PD index (bits 21-29)
+    pub fn get_pd_index(&self) -> usize {
+        ((self.0 >> 21) & 0x1FF) as usize
+    }
+
+    /// Extract PT index (bits 12-20)
+    pub fn get_pt_index(&self) -> usize {
+        ((self.0 >> 12) & 0x1FF) as usize
+    }
+
+    /// Extract page offset (bits 0-11)
+    pub fn get_page_offset(&self) -> usize {
+        (self.0 & 0xFFF) as usize
+    }
+
+    /// Extract 2MB page offset (bits 0-20)
+    pub fn get_large_page_offset(&self) -> usize {
+        (self.0 & 0x1F_FFFF) as usize
+    }
+}
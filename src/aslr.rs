@@ -0,0 +1,203 @@
+//! Address space layout (ASLR) report
+//!
+//! Collects where a process's image, modules, heaps, stacks and TEBs were
+//! placed and flags layouts that ASLR should not produce: images at their
+//! linker-default base and regions that overlap each other. Either is a
+//! common side effect of manual mapping or exploitation.
+
+use anyhow::{bail, Result};
+use colored::*;
+use prettytable::{format, row, Table};
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::loader::load_memory_image;
+use crate::paging::MemoryImage;
+
+/// Preferred bases the linker assigns when an image opts out of ASLR
+const DEFAULT_IMAGE_BASES: &[u64] = &[0x40_0000, 0x1000_0000, 0x1_4000_0000, 0x1_8000_0000];
+
+/// x64 TEB/PEB offsets
+const TEB_STACK_BASE: u64 = 0x08;
+const TEB_STACK_LIMIT: u64 = 0x10;
+const TEB_PEB: u64 = 0x60;
+const PEB_IMAGE_BASE: u64 = 0x10;
+const PEB_NUMBER_OF_HEAPS: u64 = 0xE8;
+const PEB_PROCESS_HEAPS: u64 = 0xF0;
+/// Heap segments are at least this large; used to size heap regions
+const HEAP_RESERVE: u64 = 0x10_0000;
+const MAX_HEAPS: u64 = 64;
+
+/// Kind of a region in the layout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegionKind {
+    Image,
+    Module,
+    Heap,
+    Stack,
+    Teb,
+}
+
+impl fmt::Display for RegionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            RegionKind::Image => "Image",
+            RegionKind::Module => "Module",
+            RegionKind::Heap => "Heap",
+            RegionKind::Stack => "Stack",
+            RegionKind::Teb => "TEB",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// A placed region of the process address space
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutRegion {
+    pub kind: RegionKind,
+    pub base: u64,
+    pub size: u64,
+    pub label: String,
+}
+
+impl LayoutRegion {
+    fn end(&self) -> u64 {
+        self.base + self.size
+    }
+}
+
+/// An ASLR anomaly in a process layout
+#[derive(Debug, Clone, PartialEq)]
+pub enum AslrFlag {
+    /// A module loaded at its linker-default base
+    NotRandomized { label: String, base: u64 },
+    /// Two regions share address space
+    Overlap { first: String, second: String },
+}
+
+impl fmt::Display for AslrFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AslrFlag::NotRandomized { label, base } => write!(f, "{} at default base 0x{:X}", label, base),
+            AslrFlag::Overlap { first, second } => write!(f, "{} overlaps {}", first, second),
+        }
+    }
+}
+
+/// Layout of one process's address space
+#[derive(Debug, Clone, Default)]
+pub struct AslrLayout {
+    pub pid: Option<u32>,
+    pub name: String,
+    pub regions: Vec<LayoutRegion>,
+}
+
+impl AslrLayout {
+    /// Build the layout of a user-mode dump from its module/thread lists and PEB
+    pub fn from_user_dump(img: &MemoryImage) -> Option<Self> {
+        let user = img.info.user.as_ref()?;
+        let mut layout = AslrLayout::default();
+
+        let peb = user.threads.iter()
+            .find_map(|t| img.read_virt_u64(t.teb + TEB_PEB))
+            .filter(|&peb| peb != 0);
+        let image_base = peb.and_then(|peb| img.read_virt_u64(peb + PEB_IMAGE_BASE))
+            .or_else(|| user.modules.first().map(|m| m.base));
+
+        for module in &user.modules {
+            let kind = if Some(module.base) == image_base { RegionKind::Image } else { RegionKind::Module };
+            let label = module.name.rsplit(['\\', '/']).next().unwrap_or(&module.name).to_string();
+            if kind == RegionKind::Image {
+                layout.name = label.clone();
+            }
+            layout.regions.push(LayoutRegion { kind, base: module.base, size: module.size, label });
+        }
+
+        if let Some(peb) = peb {
+            let count = img.read_virt_u64(peb + PEB_NUMBER_OF_HEAPS).unwrap_or(0) & 0xFFFF_FFFF;
+            if let Some(heaps) = img.read_virt_u64(peb + PEB_PROCESS_HEAPS) {
+                for i in 0..count.min(MAX_HEAPS) {
+                    if let Some(heap) = img.read_virt_u64(heaps + i * 8).filter(|&h| h != 0) {
+                        let label = format!("heap {}", i);
+                        layout.regions.push(LayoutRegion { kind: RegionKind::Heap, base: heap, size: HEAP_RESERVE, label });
+                    }
+                }
+            }
+        }
+
+        for thread in &user.threads {
+            // Prefer the live stack bounds from the TEB over the captured stack range
+            let (base, size) = match (img.read_virt_u64(thread.teb + TEB_STACK_BASE), img.read_virt_u64(thread.teb + TEB_STACK_LIMIT)) {
+                (Some(high), Some(low)) if low < high => (low, high - low),
+                _ => (thread.stack_base, thread.stack_size),
+            };
+            if size > 0 {
+                let label = format!("stack {}", thread.id);
+                layout.regions.push(LayoutRegion { kind: RegionKind::Stack, base, size, label });
+            }
+            if thread.teb != 0 {
+                let label = format!("TEB {}", thread.id);
+                layout.regions.push(LayoutRegion { kind: RegionKind::Teb, base: thread.teb, size: 0x1000, label });
+            }
+        }
+
+        layout.regions.sort_by_key(|r| r.base);
+        Some(layout)
+    }
+
+    /// Flag modules at default bases and overlapping regions
+    pub fn analyze(&self) -> Vec<AslrFlag> {
+        let mut flags = Vec::new();
+
+        for region in &self.regions {
+            if matches!(region.kind, RegionKind::Image | RegionKind::Module) && DEFAULT_IMAGE_BASES.contains(&region.base) {
+                flags.push(AslrFlag::NotRandomized { label: region.label.clone(), base: region.base });
+            }
+        }
+
+        // Regions are sorted by base, so compare each with the ones starting inside it
+        for (i, first) in self.regions.iter().enumerate() {
+            for second in self.regions[i + 1..].iter().take_while(|r| r.base < first.end()) {
+                flags.push(AslrFlag::Overlap { first: first.label.clone(), second: second.label.clone() });
+            }
+        }
+
+        flags
+    }
+}
+
+/// Print the ASLR layout of a process dump and its anomalies
+pub fn report_aslr(dump_path: PathBuf) -> Result<()> {
+    let memory_image = load_memory_image(&dump_path)?;
+    let layout = match AslrLayout::from_user_dump(&memory_image) {
+        Some(layout) => layout,
+        None => bail!("{} is not a user-mode process dump", dump_path.display()),
+    };
+    let flags = layout.analyze();
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"Kind", bFg->"Base", bFg->"End", bFg->"Region"]);
+    for region in &layout.regions {
+        table.add_row(row![
+            region.kind,
+            Fy->format!("0x{:016X}", region.base),
+            format!("0x{:016X}", region.end()),
+            Fc->region.label
+        ]);
+    }
+
+    println!("{} {}", "Address space layout of".bright_green(), layout.name.bright_yellow().bold());
+    table.printstd();
+
+    if flags.is_empty() {
+        println!("\n{}", "No ASLR anomalies found".bright_green());
+    } else {
+        println!("\n{} {}", "ASLR anomalies:".bright_red().bold(), flags.len());
+        for flag in &flags {
+            println!("  {} {}", "!".bright_red(), flag);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+//! Module baselines from a golden image
+//!
+//! Fleet triage compares every host against a known-good build. A baseline
+//! records, per module name, a hash of the module's code and its file
+//! version, taken from the memory of a golden image; extraction on other
+//! hosts flags modules whose code hash deviates. A changed hash with an
+//! unchanged version is what patched or trojanized system binaries look
+//! like, while a different version usually means a missing or extra update.
+//!
+//! The hash only covers executable, non-writable sections, which hold the
+//! same bytes on every host. Absolute pointers into the module itself are
+//! rewritten as offsets first, so the hash does not depend on where the
+//! module was loaded. Modules with code pages that were not resident are
+//! left unverified rather than reported as changed.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::pe::{pe_headers, SECTION_HEADER_SIZE};
+
+/// IMAGE_SCN_MEM_EXECUTE and IMAGE_SCN_MEM_WRITE
+const SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+/// `VS_FIXEDFILEINFO.dwSignature`
+const VERSION_SIGNATURE: [u8; 4] = 0xFEEF_04BDu32.to_le_bytes();
+
+/// What a baseline records about one module
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleFingerprint {
+    pub size: u64,
+    /// Hex SHA-256 of the normalized code sections; `None` when a code page
+    /// was not resident
+    pub code_sha256: Option<String>,
+    /// `FileVersion` from the version resource
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+impl ModuleFingerprint {
+    /// Fingerprint a module image as mapped at `base`; pages that were not
+    /// resident must be zero-filled
+    pub fn of_image(image: &[u8], base: u64) -> Self {
+        ModuleFingerprint {
+            size: image.len() as u64,
+            code_sha256: code_hash(image, base),
+            version: file_version(image),
+        }
+    }
+}
+
+/// Hash of the executable, non-writable sections with pointers into the
+/// image rewritten relative to `base`
+pub fn code_hash(image: &[u8], base: u64) -> Option<String> {
+    let (_, sections, count) = pe_headers(image).ok()?;
+    let mut hasher = Sha256::new();
+    let mut hashed = false;
+    for index in 0..count {
+        let header = sections + index * SECTION_HEADER_SIZE;
+        let characteristics = u32_at(image, header + 36)?;
+        if characteristics & SCN_MEM_EXECUTE == 0 || characteristics & SCN_MEM_WRITE != 0 {
+            continue;
+        }
+        let rva = u32_at(image, header + 12)? as usize;
+        let size = u32_at(image, header + 8)? as usize;
+        let code = image.get(rva..rva.checked_add(size)?.min(image.len()))?;
+        // Code pages are never entirely zero; a zero page was not resident
+        let first_page = rva & !0xFFF;
+        if image[first_page..(rva + code.len()).min(image.len())].chunks(0x1000).any(|page| page.iter().all(|&b| b == 0)) {
+            return None;
+        }
+        hasher.update(normalize_pointers(code, base, image.len() as u64));
+        hashed = true;
+    }
+    hashed.then(|| hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Replace every 8-byte value pointing into `base..base + size` with its
+/// offset from `base`, undoing the loader's relocations
+fn normalize_pointers(code: &[u8], base: u64, size: u64) -> Vec<u8> {
+    let mut out = code.to_vec();
+    let mut i = 0;
+    while i + 8 <= out.len() {
+        let value = u64::from_le_bytes(out[i..i + 8].try_into().unwrap());
+        if value >= base && value - base < size {
+            out[i..i + 8].copy_from_slice(&(value - base).to_le_bytes());
+            i += 8;
+        } else {
+            i += 1;
+        }
+    }
+    out
+}
+
+/// `FileVersion` of the first VS_FIXEDFILEINFO in the image, as `a.b.c.d`
+pub fn file_version(image: &[u8]) -> Option<String> {
+    let at = image.windows(4).position(|w| w == VERSION_SIGNATURE)?;
+    let (ms, ls) = (u32_at(image, at + 8)?, u32_at(image, at + 12)?);
+    Some(format!("{}.{}.{}.{}", ms >> 16, ms & 0xFFFF, ls >> 16, ls & 0xFFFF))
+}
+
+/// How a module compares with the baseline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    /// Same code hash
+    Match,
+    /// The code differs but the version does not: patched or trojanized
+    Modified,
+    /// A different version with different code
+    VersionChanged { baseline: Option<String>, found: Option<String> },
+    /// The baseline has no module of that name
+    NotInBaseline,
+    /// The code hash could not be computed on one side
+    Unverified,
+}
+
+impl Drift {
+    /// Whether the module should be looked at
+    pub fn is_deviation(&self) -> bool {
+        matches!(self, Drift::Modified | Drift::VersionChanged { .. })
+    }
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::Match => f.write_str("match"),
+            Drift::Modified => f.write_str("MODIFIED"),
+            Drift::VersionChanged { baseline, found } => write!(f, "version {} -> {}",
+                baseline.as_deref().unwrap_or("?"), found.as_deref().unwrap_or("?")),
+            Drift::NotInBaseline => f.write_str("not in baseline"),
+            Drift::Unverified => f.write_str("unverified"),
+        }
+    }
+}
+
+/// Fingerprints of the modules of a golden image, by lower-case name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub modules: BTreeMap<String, ModuleFingerprint>,
+}
+
+impl Baseline {
+    pub fn insert(&mut self, name: &str, fingerprint: ModuleFingerprint) {
+        self.modules.insert(name.to_lowercase(), fingerprint);
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("{} is not a module baseline", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Compare a module of another image with its baseline entry
+    pub fn compare(&self, name: &str, fingerprint: &ModuleFingerprint) -> Drift {
+        let Some(expected) = self.modules.get(&name.to_lowercase()) else { return Drift::NotInBaseline };
+        match (&expected.code_sha256, &fingerprint.code_sha256) {
+            (Some(a), Some(b)) if a == b => Drift::Match,
+            (Some(_), Some(_)) if expected.version == fingerprint.version => Drift::Modified,
+            (Some(_), Some(_)) => Drift::VersionChanged { baseline: expected.version.clone(), found: fingerprint.version.clone() },
+            _ => Drift::Unverified,
+        }
+    }
+}
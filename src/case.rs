@@ -0,0 +1,580 @@
+//! Case directories and finding triage
+//!
+//! A case is a directory shared by the analysts working an investigation.
+//! `run-plugin --case` records the findings of each run in it, and analysts
+//! mark findings as confirmed, false positive or needing review. Triage
+//! decisions are appended to a log rather than overwritten, so several
+//! analysts can review the same case and every decision keeps its author;
+//! a finding's state is the most recent decision, and findings whose
+//! reviewers currently disagree are flagged as disputed.
+//!
+//! The case manifest records how each analysed dump was acquired, so the
+//! crash or hibernation time in its header anchors the case's timeline.
+//!
+//! Writes take an advisory lock on the case directory so concurrent runs do
+//! not overwrite each other. Analysts working on copies of a case combine
+//! them with a merge, which adds the other copy's findings and decisions
+//! without dropping any.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use prettytable::{format, row, Table};
+use serde::{Deserialize, Serialize};
+use std::collections::{btree_map, BTreeMap};
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::formats::acquisition::AcquisitionMetadata;
+use crate::plugin::Finding;
+
+/// Findings recorded by `run-plugin --case`, keyed by ID
+pub const FINDINGS_FILE: &str = "findings.json";
+
+/// Append-only log of triage decisions, one JSON record per line
+pub const TRIAGE_FILE: &str = "triage.jsonl";
+
+/// Dumps analysed in the case and their acquisition metadata
+pub const MANIFEST_FILE: &str = "case.json";
+
+/// Advisory lock held while a case is written, naming its holder
+pub const LOCK_FILE: &str = ".lock";
+
+/// How long to wait for another writer to release the lock
+const LOCK_WAIT: Duration = Duration::from_secs(2);
+
+/// Environment variable naming the analyst recording triage decisions
+pub const ANALYST_ENV: &str = "RMF_ANALYST";
+
+/// Review outcome of a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TriageState {
+    Confirmed,
+    NeedsReview,
+    FalsePositive,
+}
+
+impl fmt::Display for TriageState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TriageState::Confirmed => "confirmed",
+            TriageState::NeedsReview => "needs-review",
+            TriageState::FalsePositive => "false-positive",
+        })
+    }
+}
+
+/// One analyst's decision about one finding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriageRecord {
+    pub finding: String,
+    pub state: TriageState,
+    pub analyst: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// RFC 3339 time the decision was recorded
+    pub time: String,
+}
+
+/// A finding as recorded in the case, with the dump it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaseFinding {
+    pub id: String,
+    pub dump: String,
+    pub plugin: String,
+    pub addr: u64,
+    pub desc: String,
+    pub confidence: u8,
+    pub details: BTreeMap<String, String>,
+}
+
+impl CaseFinding {
+    pub fn new(finding: &Finding, dump: &Path) -> Self {
+        CaseFinding {
+            id: finding.id(),
+            dump: dump.display().to_string(),
+            plugin: finding.plugin.clone(),
+            addr: finding.addr,
+            desc: finding.desc.clone(),
+            confidence: finding.confidence,
+            details: finding.details.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        }
+    }
+}
+
+/// Dumps analysed in a case
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CaseManifest {
+    /// Acquisition metadata of each dump, keyed by path
+    pub dumps: BTreeMap<String, AcquisitionMetadata>,
+}
+
+/// Current triage of one finding
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriageStatus {
+    /// The most recent decision
+    pub latest: TriageRecord,
+    /// Every decision, oldest first
+    pub history: Vec<TriageRecord>,
+}
+
+impl TriageStatus {
+    pub fn state(&self) -> TriageState {
+        self.latest.state
+    }
+
+    /// Whether analysts' most recent decisions disagree
+    pub fn disputed(&self) -> bool {
+        let mut by_analyst: BTreeMap<&str, TriageState> = BTreeMap::new();
+        for record in &self.history {
+            by_analyst.insert(&record.analyst, record.state);
+        }
+        by_analyst.values().any(|&state| state != self.latest.state)
+    }
+}
+
+/// Analyst name from `$RMF_ANALYST`, falling back to the login name
+pub fn default_analyst() -> String {
+    [ANALYST_ENV, "USER", "USERNAME"].iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// Held lock on a case directory, released when dropped
+#[derive(Debug)]
+pub struct CaseLock {
+    path: PathBuf,
+}
+
+impl Drop for CaseLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// What a merge added to a case
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergeSummary {
+    pub findings_added: usize,
+    pub dumps_added: usize,
+    pub decisions_added: usize,
+    /// Findings whose reviewers disagree after the merge
+    pub disputed: Vec<String>,
+}
+
+/// A case directory
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub dir: PathBuf,
+}
+
+impl Case {
+    /// Open the case at `dir`, creating the directory if needed
+    pub fn open(dir: &Path) -> Result<Self> {
+        if dir.exists() && !dir.is_dir() {
+            bail!("{} is not a case directory", dir.display());
+        }
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create case directory {}", dir.display()))?;
+        Ok(Case { dir: dir.to_path_buf() })
+    }
+
+    /// Findings recorded in the case, keyed by ID
+    pub fn findings(&self) -> Result<BTreeMap<String, CaseFinding>> {
+        let path = self.dir.join(FINDINGS_FILE);
+        if !path.is_file() {
+            return Ok(BTreeMap::new());
+        }
+        let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("{} is not a case findings file", path.display()))
+    }
+
+    /// Take the case's advisory lock, waiting briefly for another writer
+    pub fn lock(&self) -> Result<CaseLock> {
+        let path = self.dir.join(LOCK_FILE);
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    // Constructed first so a failed write still releases the lock
+                    let lock = CaseLock { path: path.clone() };
+                    let holder = format!("{} (pid {}) since {}\n", default_analyst(), std::process::id(), now());
+                    file.write_all(holder.as_bytes()).with_context(|| format!("Failed to write {}", path.display()))?;
+                    return Ok(lock);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if start.elapsed() >= LOCK_WAIT {
+                        let holder = std::fs::read_to_string(&path).unwrap_or_default();
+                        bail!("Case {} is locked by {} (if the holder has exited, run `rmf case unlock`)",
+                            self.dir.display(), holder.trim());
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                Err(e) => return Err(e).with_context(|| format!("Failed to create {}", path.display())),
+            }
+        }
+    }
+
+    /// Remove a stale lock; returns whether there was one
+    pub fn unlock(&self) -> Result<bool> {
+        let path = self.dir.join(LOCK_FILE);
+        if !path.exists() {
+            return Ok(false);
+        }
+        std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        Ok(true)
+    }
+
+    fn write_findings(&self, findings: &BTreeMap<String, CaseFinding>) -> Result<()> {
+        let path = self.dir.join(FINDINGS_FILE);
+        let json = serde_json::to_string_pretty(findings)?;
+        std::fs::write(&path, json + "\n").with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The case manifest; empty before any dump is recorded
+    pub fn manifest(&self) -> Result<CaseManifest> {
+        let path = self.dir.join(MANIFEST_FILE);
+        if !path.is_file() {
+            return Ok(CaseManifest::default());
+        }
+        let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("{} is not a case manifest", path.display()))
+    }
+
+    fn write_manifest(&self, manifest: &CaseManifest) -> Result<()> {
+        let path = self.dir.join(MANIFEST_FILE);
+        let json = serde_json::to_string_pretty(manifest)?;
+        std::fs::write(&path, json + "\n").with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Record how `dump` was acquired, replacing an earlier record of it
+    pub fn record_dump(&self, dump: &Path, acquisition: &AcquisitionMetadata) -> Result<()> {
+        let _lock = self.lock()?;
+        let mut manifest = self.manifest()?;
+        manifest.dumps.insert(dump.display().to_string(), acquisition.clone());
+        self.write_manifest(&manifest)
+    }
+
+    /// Add findings from `dump`, replacing earlier records with the same ID
+    pub fn record_findings(&self, dump: &Path, findings: &[Finding]) -> Result<()> {
+        let _lock = self.lock()?;
+        let mut recorded = self.findings()?;
+        for finding in findings {
+            let finding = CaseFinding::new(finding, dump);
+            recorded.insert(finding.id.clone(), finding);
+        }
+        self.write_findings(&recorded)
+    }
+
+    /// Every triage decision, in the order recorded
+    pub fn triage_log(&self) -> Result<Vec<TriageRecord>> {
+        let path = self.dir.join(TRIAGE_FILE);
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                serde_json::from_str(line).with_context(|| format!("{}:{}: bad triage record", path.display(), number + 1))
+            })
+            .collect()
+    }
+
+    fn append_triage(&self, records: &[TriageRecord]) -> Result<()> {
+        let path = self.dir.join(TRIAGE_FILE);
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut lines = String::new();
+        for record in records {
+            lines += &(serde_json::to_string(record)? + "\n");
+        }
+        file.write_all(lines.as_bytes()).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Append a decision to the triage log
+    pub fn triage(&self, record: &TriageRecord) -> Result<()> {
+        let _lock = self.lock()?;
+        self.append_triage(std::slice::from_ref(record))
+    }
+
+    /// Triage of every finding that has a decision, keyed by finding ID
+    ///
+    /// Decisions are ordered by time, so records merged from another copy
+    /// of the case take effect in the order they were made.
+    pub fn triage_status(&self) -> Result<BTreeMap<String, TriageStatus>> {
+        let mut log = self.triage_log()?;
+        log.sort_by(|a, b| a.time.cmp(&b.time));
+        let mut status: BTreeMap<String, TriageStatus> = BTreeMap::new();
+        for record in log {
+            match status.get_mut(&record.finding) {
+                Some(entry) => {
+                    entry.latest = record.clone();
+                    entry.history.push(record);
+                }
+                None => {
+                    status.insert(record.finding.clone(), TriageStatus { latest: record.clone(), history: vec![record] });
+                }
+            }
+        }
+        Ok(status)
+    }
+}
+
+impl Case {
+    /// Add the findings and triage decisions of `other`, a copy of this case
+    /// another analyst worked on; findings and decisions already present are kept
+    pub fn merge(&self, other: &Case) -> Result<MergeSummary> {
+        let _lock = self.lock()?;
+        let _other_lock = other.lock()?;
+        let mut summary = MergeSummary::default();
+
+        let mut findings = self.findings()?;
+        for (id, finding) in other.findings()? {
+            if let btree_map::Entry::Vacant(entry) = findings.entry(id) {
+                entry.insert(finding);
+                summary.findings_added += 1;
+            }
+        }
+        if summary.findings_added > 0 {
+            self.write_findings(&findings)?;
+        }
+
+        let mut manifest = self.manifest()?;
+        for (dump, acquisition) in other.manifest()?.dumps {
+            if let btree_map::Entry::Vacant(entry) = manifest.dumps.entry(dump) {
+                entry.insert(acquisition);
+                summary.dumps_added += 1;
+            }
+        }
+        if summary.dumps_added > 0 {
+            self.write_manifest(&manifest)?;
+        }
+
+        let log = self.triage_log()?;
+        let mut added: Vec<TriageRecord> = other.triage_log()?.into_iter().filter(|r| !log.contains(r)).collect();
+        added.sort_by(|a, b| a.time.cmp(&b.time));
+        added.dedup();
+        summary.decisions_added = added.len();
+        self.append_triage(&added)?;
+
+        summary.disputed = self.triage_status()?.into_iter()
+            .filter(|(_, status)| status.disputed())
+            .map(|(id, _)| id)
+            .collect();
+        Ok(summary)
+    }
+}
+
+/// Merge the case at `from` into the case at `into`
+pub fn merge_cases(into: PathBuf, from: PathBuf) -> Result<()> {
+    if !from.is_dir() {
+        bail!("{} is not a case directory", from.display());
+    }
+    let (case, other) = (Case::open(&into)?, Case::open(&from)?);
+    let summary = case.merge(&other)?;
+    println!("{} {} into {}: {} findings, {} triage decisions and {} dumps added",
+        "Merged".bright_green(), from.display().to_string().bright_cyan(), into.display().to_string().bright_cyan(),
+        summary.findings_added.to_string().bright_yellow(), summary.decisions_added.to_string().bright_yellow(),
+        summary.dumps_added.to_string().bright_yellow());
+
+    if !summary.disputed.is_empty() {
+        let status = case.triage_status()?;
+        println!("{} {} findings have conflicting decisions:", "Disputed:".bright_red(), summary.disputed.len());
+        for id in &summary.disputed {
+            let decisions: Vec<String> = status[id].history.iter().map(|r| format!("{} {}", r.analyst, r.state)).collect();
+            println!("  {} {}", id.bright_yellow(), decisions.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Remove the lock of the case at `dir` left behind by an interrupted run
+pub fn unlock_case(dir: PathBuf) -> Result<()> {
+    let case = Case::open(&dir)?;
+    let holder = std::fs::read_to_string(case.dir.join(LOCK_FILE)).unwrap_or_default();
+    if case.unlock()? {
+        println!("{} (was held by {})", "Lock removed".bright_green(), holder.trim());
+    } else {
+        println!("{}", "Case is not locked".bright_yellow());
+    }
+    Ok(())
+}
+
+/// Record a triage decision for `finding_id` in the case at `dir`
+pub fn triage_finding(dir: PathBuf, finding_id: String, state: TriageState, note: Option<String>, analyst: Option<String>) -> Result<()> {
+    let id = finding_id.to_ascii_lowercase();
+    if id.len() != 16 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!("'{}' is not a finding ID (16 hex digits, as in the ID column)", finding_id);
+    }
+    let case = Case::open(&dir)?;
+    let findings = case.findings()?;
+    if !findings.contains_key(&id) {
+        println!("{} finding {} is not recorded in this case (run-plugin --case records findings)",
+            "Warning:".bright_yellow(), id);
+    }
+
+    let record = TriageRecord {
+        finding: id.clone(),
+        state,
+        analyst: analyst.unwrap_or_else(default_analyst),
+        note,
+        time: now(),
+    };
+    case.triage(&record)?;
+    println!("{} {} as {} ({})", "Marked".bright_green(), id.bright_yellow(), state.to_string().bright_cyan(), record.analyst);
+
+    if let Some(status) = case.triage_status()?.get(&id).filter(|s| s.disputed()) {
+        println!("{} other analysts disagree:", "Disputed:".bright_red());
+        for record in &status.history {
+            println!("  {} {} {}", record.time, record.analyst, record.state);
+        }
+    }
+    Ok(())
+}
+
+fn state_label(status: Option<&TriageStatus>) -> String {
+    match status {
+        Some(status) if status.disputed() => format!("{} (disputed)", status.state()),
+        Some(status) => status.state().to_string(),
+        None => "untriaged".to_string(),
+    }
+}
+
+/// List the case's findings with their triage state; `history` also prints every decision
+pub fn list_triage(dir: PathBuf, history: bool) -> Result<()> {
+    let case = Case::open(&dir)?;
+    let findings = case.findings()?;
+    let status = case.triage_status()?;
+
+    let mut ids: Vec<&String> = findings.keys().chain(status.keys().filter(|id| !findings.contains_key(*id))).collect();
+    ids.sort_by_key(|id| (findings.get(*id).map(|f| (f.addr, f.plugin.clone())), *id));
+    if ids.is_empty() {
+        println!("{}", "No findings recorded in this case".bright_yellow());
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![b->"ID", b->"Plugin", b->"Address", b->"State", b->"Analyst", b->"Description"]);
+    for id in &ids {
+        let finding = findings.get(*id);
+        let entry = status.get(*id);
+        table.add_row(row![
+            id,
+            finding.map_or("-", |f| f.plugin.as_str()),
+            finding.map_or("-".to_string(), |f| format!("0x{:08X}", f.addr)),
+            state_label(entry),
+            entry.map_or("-", |s| s.latest.analyst.as_str()),
+            finding.map_or("(not recorded)", |f| f.desc.as_str())
+        ]);
+    }
+    table.printstd();
+
+    if history {
+        for (id, entry) in &status {
+            println!("\n{} {}", "Finding".bright_green(), id.bright_yellow());
+            for record in &entry.history {
+                println!("  {} {:<14} {} {}", record.time, record.state.to_string(), record.analyst, record.note.as_deref().unwrap_or(""));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Markdown report of the case's findings grouped by triage state
+///
+/// Untriaged findings are listed with those needing review; false positives
+/// are listed last so the report shows why they were dismissed.
+pub fn case_report(case: &Case) -> Result<String> {
+    let findings = case.findings()?;
+    let status = case.triage_status()?;
+    let name = case.dir.file_name().map_or(case.dir.display().to_string(), |n| n.to_string_lossy().into_owned());
+
+    let mut groups: BTreeMap<TriageState, Vec<&CaseFinding>> = BTreeMap::new();
+    for finding in findings.values() {
+        let state = status.get(&finding.id).map_or(TriageState::NeedsReview, |s| s.state());
+        groups.entry(state).or_default().push(finding);
+    }
+    for group in groups.values_mut() {
+        group.sort_by_key(|f| (f.addr, f.plugin.clone(), f.id.clone()));
+    }
+    let count = |state| groups.get(&state).map_or(0, Vec::len);
+    let untriaged = findings.keys().filter(|id| !status.contains_key(*id)).count();
+
+    let mut out = format!("# Case report: {}\n\n", name);
+    out += &format!("| Findings | Confirmed | Needs review | Untriaged | False positive |\n|---|---|---|---|---|\n| {} | {} | {} | {} | {} |\n",
+        findings.len(),
+        count(TriageState::Confirmed),
+        count(TriageState::NeedsReview) - untriaged,
+        untriaged,
+        count(TriageState::FalsePositive));
+
+    let manifest = case.manifest()?;
+    if !manifest.dumps.is_empty() {
+        out += "\n## Acquisition\n\n| Dump | Container | Tool | Captured |\n|---|---|---|---|\n";
+        for (dump, acquisition) in &manifest.dumps {
+            let field = |value: &Option<String>| value.as_deref().map_or("-".to_string(), escape_cell);
+            let captured = acquisition.captured_at().map_or("-".to_string(), |t| format!("{} ({})", t.time, t.anchor));
+            out += &format!("| {} | {} | {} | {} |\n", escape_cell(dump), field(&acquisition.container), field(&acquisition.tool), captured);
+        }
+    }
+
+    for (state, title) in [
+        (TriageState::Confirmed, "Confirmed"),
+        (TriageState::NeedsReview, "Needs review"),
+        (TriageState::FalsePositive, "False positives"),
+    ] {
+        let Some(group) = groups.get(&state) else { continue };
+        out += &format!("\n## {}\n\n| ID | Plugin | Address | Confidence | Description | Dump | Triage |\n|---|---|---|---|---|---|---|\n", title);
+        for finding in group {
+            let triage = match status.get(&finding.id) {
+                Some(entry) => {
+                    let mut text = format!("{} ({})", entry.latest.analyst, entry.latest.time);
+                    if let Some(note) = &entry.latest.note {
+                        text += &format!(": {}", note);
+                    }
+                    if entry.disputed() {
+                        text += " **disputed**";
+                    }
+                    text
+                }
+                None => "untriaged".to_string(),
+            };
+            out += &format!("| {} | {} | {} | {}% | {} | {} | {} |\n",
+                finding.id,
+                finding.plugin,
+                finding.details.get("va").cloned().unwrap_or_else(|| format!("0x{:08X}", finding.addr)),
+                finding.confidence,
+                escape_cell(&finding.desc),
+                escape_cell(&finding.dump),
+                escape_cell(&triage));
+        }
+    }
+    Ok(out)
+}
+
+/// Write the case report to `output`, or print it
+pub fn report_case(dir: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let case = Case::open(&dir)?;
+    let report = case_report(&case)?;
+    match output {
+        Some(path) => {
+            std::fs::write(&path, report).with_context(|| format!("Failed to write {}", path.display()))?;
+            println!("{} {}", "Report written to".bright_green(), path.display().to_string().bright_cyan());
+        }
+        None => print!("{}", report),
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+//! Container awareness for Linux memory images
+//!
+//! Container runtimes place each container's processes in a dedicated cgroup
+//! whose path embeds the 64-character container ID. Recognising those paths
+//! lets us tie processes and findings back to docker/containerd/CRI-O/podman
+//! workloads and scope analysis to a single container.
+
+use std::fmt;
+
+use crate::plugin::Finding;
+use crate::processes::Process;
+
+/// Container runtime that owns a cgroup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContainerRuntime {
+    Docker,
+    Containerd,
+    CriO,
+    Podman,
+    Runc,
+}
+
+impl fmt::Display for ContainerRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Containerd => "containerd",
+            ContainerRuntime::CriO => "cri-o",
+            ContainerRuntime::Podman => "podman",
+            ContainerRuntime::Runc => "runc",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A container identified from cgroup or runtime state data
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerRef {
+    pub id: String,
+    pub runtime: ContainerRuntime,
+    /// Kubernetes pod UID when the cgroup lives under `kubepods`
+    pub pod_uid: Option<String>,
+}
+
+impl ContainerRef {
+    /// The 12-character short ID shown by `docker ps`
+    pub fn short_id(&self) -> &str {
+        &self.id[..12]
+    }
+}
+
+/// Length of a full container ID
+pub const CONTAINER_ID_LEN: usize = 64;
+
+fn is_container_id(s: &str) -> bool {
+    s.len() == CONTAINER_ID_LEN && s.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase())
+}
+
+/// Extract a pod UID from a `kubepods` cgroup component such as
+/// `kubepods-besteffort-pod1234_5678.slice` or `pod1234-5678`
+fn pod_uid(component: &str) -> Option<String> {
+    let start = component.rfind("pod")? + 3;
+    let rest = component[start..].trim_end_matches(".slice");
+    if rest.len() < 32 {
+        return None;
+    }
+    Some(rest.replace('_', "-"))
+}
+
+/// Parse a cgroup path (cgroupfs or systemd driver) into a container reference
+pub fn parse_cgroup_path(path: &str) -> Option<ContainerRef> {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    let kubernetes = components.iter().any(|c| c.starts_with("kubepods"));
+    let pod = components.iter().filter(|c| c.contains("pod")).find_map(|c| pod_uid(c));
+
+    for (i, component) in components.iter().enumerate().rev() {
+        let name = component.trim_end_matches(".scope");
+
+        // systemd cgroup driver: <runtime>-<id>.scope
+        let prefixed = [
+            ("docker-", ContainerRuntime::Docker),
+            ("cri-containerd-", ContainerRuntime::Containerd),
+            ("crio-", ContainerRuntime::CriO),
+            ("libpod-", ContainerRuntime::Podman),
+        ];
+        for (prefix, runtime) in prefixed {
+            if let Some(id) = name.strip_prefix(prefix) {
+                if is_container_id(id) {
+                    return Some(ContainerRef { id: id.to_string(), runtime, pod_uid: pod });
+                }
+            }
+        }
+
+        // cgroupfs driver: bare ID under a runtime-named parent
+        if is_container_id(name) {
+            let parent = if i > 0 { components[i - 1] } else { "" };
+            let runtime = if parent == "docker" {
+                ContainerRuntime::Docker
+            } else if parent.starts_with("libpod") || parent == "machine.slice" {
+                ContainerRuntime::Podman
+            } else if kubernetes {
+                ContainerRuntime::Containerd
+            } else {
+                ContainerRuntime::Runc
+            };
+            return Some(ContainerRef { id: name.to_string(), runtime, pod_uid: pod });
+        }
+    }
+
+    None
+}
+
+/// Restricts results to a single container
+///
+/// A finding is in scope when it carries a matching `container_id` detail, or
+/// when its `pid` detail belongs to a process attributed to the container.
+pub struct ContainerScope {
+    id: String,
+    pids: Vec<u32>,
+}
+
+impl ContainerScope {
+    /// Build a scope for a full or abbreviated container ID
+    pub fn new(id: &str, processes: &[Process]) -> Self {
+        let id = id.to_lowercase();
+        let pids = processes
+            .iter()
+            .filter(|p| p.container_id.as_deref().is_some_and(|c| c.starts_with(&id)))
+            .map(|p| p.pid)
+            .collect();
+        Self { id, pids }
+    }
+
+    /// PIDs of the processes attributed to this container
+    pub fn pids(&self) -> &[u32] {
+        &self.pids
+    }
+
+    pub fn contains(&self, finding: &Finding) -> bool {
+        if let Some(container) = finding.details.get("container_id") {
+            return container.starts_with(&self.id);
+        }
+        finding
+            .details
+            .get("pid")
+            .and_then(|pid| pid.parse::<u32>().ok())
+            .is_some_and(|pid| self.pids.contains(&pid))
+    }
+}
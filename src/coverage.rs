@@ -0,0 +1,183 @@
+//! Analysis coverage tracking
+//!
+//! When a `CoverageMap` is attached to a `MemoryImage`, every physical read
+//! is recorded against the plugin currently running. The `coverage` report
+//! uses this to show how much of the image each plugin examined and where
+//! the large unexamined regions are.
+
+use anyhow::Result;
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::loader::load_memory_image;
+use crate::plugin::get_plugin_registry;
+
+/// Granularity of coverage tracking
+pub const COVERAGE_PAGE_SIZE: usize = 4096;
+
+/// Pages examined by each plugin, as one bitmap per plugin
+#[derive(Debug)]
+pub struct CoverageMap {
+    pages: usize,
+    state: Mutex<CoverageState>,
+}
+
+#[derive(Debug, Default)]
+struct CoverageState {
+    current: Option<usize>,
+    plugins: Vec<(String, Vec<u64>)>,
+}
+
+impl CoverageMap {
+    pub fn new(image_size: usize) -> Self {
+        Self { pages: image_size.div_ceil(COVERAGE_PAGE_SIZE), state: Mutex::default() }
+    }
+
+    /// Number of pages in the tracked image
+    pub fn total_pages(&self) -> usize {
+        self.pages
+    }
+
+    /// Attribute subsequent reads to `plugin`
+    pub fn begin(&self, plugin: &str) {
+        let mut state = self.state.lock().unwrap();
+        let index = match state.plugins.iter().position(|(name, _)| name == plugin) {
+            Some(index) => index,
+            None => {
+                state.plugins.push((plugin.to_string(), vec![0; self.pages.div_ceil(64)]));
+                state.plugins.len() - 1
+            }
+        };
+        state.current = Some(index);
+    }
+
+    /// Stop attributing reads; reads outside a plugin are not recorded
+    pub fn end(&self) {
+        self.state.lock().unwrap().current = None;
+    }
+
+    /// Record a read of `len` bytes at physical `offset`
+    pub(crate) fn record(&self, offset: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let Some(current) = state.current else { return };
+        let bitmap = &mut state.plugins[current].1;
+        let last = ((offset + len - 1) / COVERAGE_PAGE_SIZE).min(self.pages.saturating_sub(1));
+        for page in offset / COVERAGE_PAGE_SIZE..=last {
+            bitmap[page / 64] |= 1 << (page % 64);
+        }
+    }
+
+    /// Pages examined per plugin, in the order the plugins first ran
+    pub fn plugins(&self) -> Vec<(String, usize)> {
+        let state = self.state.lock().unwrap();
+        state.plugins.iter()
+            .map(|(name, bitmap)| (name.clone(), bitmap.iter().map(|w| w.count_ones() as usize).sum()))
+            .collect()
+    }
+
+    fn union(&self) -> Vec<u64> {
+        let state = self.state.lock().unwrap();
+        let mut union = vec![0u64; self.pages.div_ceil(64)];
+        for (_, bitmap) in &state.plugins {
+            union.iter_mut().zip(bitmap).for_each(|(u, w)| *u |= w);
+        }
+        union
+    }
+
+    /// Pages examined by at least one plugin
+    pub fn examined_pages(&self) -> usize {
+        self.union().iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Unexamined physical ranges as `(start, length)`, largest first
+    pub fn unexamined_ranges(&self) -> Vec<(u64, u64)> {
+        let union = self.union();
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for page in 0..=self.pages {
+            let examined = page == self.pages || union[page / 64] & (1 << (page % 64)) != 0;
+            match (examined, start) {
+                (false, None) => start = Some(page),
+                (true, Some(first)) => {
+                    ranges.push(((first * COVERAGE_PAGE_SIZE) as u64, ((page - first) * COVERAGE_PAGE_SIZE) as u64));
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        ranges.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranges
+    }
+}
+
+fn share(pages: usize, total: usize) -> String {
+    if total == 0 {
+        return "0.0%".to_string();
+    }
+    format!("{:.1}%", pages as f64 * 100.0 / total as f64)
+}
+
+/// Run plugins with coverage tracking and report what was examined
+pub fn report_coverage(dump_path: PathBuf, plugins: Vec<String>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    let coverage = memory_image.track_coverage();
+
+    let registry = get_plugin_registry();
+    let registry = registry.read().unwrap();
+    let names = if plugins.is_empty() {
+        let mut names: Vec<String> = registry.list_plugins().into_iter().map(|(name, _, _)| name).collect();
+        names.sort();
+        names
+    } else {
+        plugins
+    };
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {msg}"
+    )?.progress_chars("#>-"));
+
+    for name in &names {
+        let Some(plugin) = registry.get(name) else {
+            println!("{} {}", "Unknown plugin, skipped:".bright_red(), name);
+            continue;
+        };
+        coverage.begin(name);
+        progress.reset();
+        progress.set_message(format!("Running {}", name));
+        let findings = plugin.scan(&memory_image, &progress);
+        coverage.end();
+        progress.println(format!("{} {} ({} findings)", "Finished".bright_green(), name.bright_yellow(), findings.len()));
+    }
+    progress.finish_and_clear();
+
+    let total = coverage.total_pages();
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"Plugin", bFg->"Pages Examined", bFg->"Coverage"]);
+    for (name, pages) in coverage.plugins() {
+        table.add_row(row![name, pages, share(pages, total)]);
+    }
+    let examined = coverage.examined_pages();
+    table.add_row(row![b->"(any plugin)", b->examined, b->share(examined, total)]);
+    table.printstd();
+
+    let ranges = coverage.unexamined_ranges();
+    println!("\n{} {} pages ({}) in {} ranges",
+        "Unexamined:".bright_green(),
+        (total - examined).to_string().bright_yellow(),
+        share(total - examined, total),
+        ranges.len()
+    );
+    for (start, length) in ranges.iter().take(10) {
+        println!("  0x{:012X} - 0x{:012X} ({} KB)", start, start + length, length / 1024);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+//! x86-64 disassembly
+//!
+//! Decodes 64-bit code to Intel syntax with capstone, to preview code found
+//! in memory and to follow the trampolines inline hooks patch into
+//! functions. Each instruction records the destination of a relative call
+//! or jump, its RIP-relative memory operand and its immediate. A byte that
+//! does not start an instruction is shown as `db` and decoding resumes
+//! after it; without the `disasm` feature every byte is shown that way.
+
+use std::fmt;
+
+#[cfg(feature = "disasm")]
+use capstone::arch::x86::{ArchMode, ArchSyntax, X86OperandType, X86Reg};
+#[cfg(feature = "disasm")]
+use capstone::arch::{BuildsCapstone, BuildsCapstoneSyntax, DetailsArchInsn};
+#[cfg(feature = "disasm")]
+use capstone::{Capstone, InsnGroupType};
+
+/// The architectural limit on an instruction's length
+const MAX_LENGTH: usize = 15;
+
+/// One decoded instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub address: u64,
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    pub operands: String,
+    /// Destination of a relative call or jump
+    pub target: Option<u64>,
+    /// Address of a RIP-relative memory operand
+    pub memory: Option<u64>,
+    /// Immediate operand, sign-extended to 64 bits
+    pub immediate: Option<u64>,
+}
+
+impl Instruction {
+    /// Mnemonic and operands
+    pub fn text(&self) -> String {
+        if self.operands.is_empty() {
+            self.mnemonic.clone()
+        } else {
+            format!("{} {}", self.mnemonic, self.operands)
+        }
+    }
+
+    /// Whether this is a `db` for a byte that did not decode
+    pub fn is_unknown(&self) -> bool {
+        self.mnemonic == "db"
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        write!(f, "{:#x}  {:<24} {}", self.address, bytes.join(" "), self.text())
+    }
+}
+
+#[cfg(feature = "disasm")]
+thread_local! {
+    /// A capstone handle cannot be shared between threads
+    static CAPSTONE: Option<Capstone> = Capstone::new()
+        .x86()
+        .mode(ArchMode::Mode64)
+        .syntax(ArchSyntax::Intel)
+        .detail(true)
+        .build()
+        .ok();
+}
+
+/// The instruction capstone decodes at the start of `code`
+#[cfg(feature = "disasm")]
+fn decode_instruction(code: &[u8], address: u64) -> Option<Instruction> {
+    CAPSTONE.with(|capstone| {
+        let capstone = capstone.as_ref()?;
+        let decoded = capstone.disasm_count(code, address, 1).ok()?;
+        let insn = decoded.iter().next()?;
+        let detail = capstone.insn_detail(insn).ok()?;
+        let branch = detail.groups().iter()
+            .any(|group| [InsnGroupType::CS_GRP_JUMP, InsnGroupType::CS_GRP_CALL].contains(&(group.0 as u32)));
+        let next = address.wrapping_add(insn.len() as u64);
+
+        let (mut target, mut memory, mut immediate) = (None, None, None);
+        for operand in detail.arch_detail().x86()?.operands() {
+            match operand.op_type {
+                // Capstone resolves relative branches to their destination
+                X86OperandType::Imm(value) if branch => target = Some(value as u64),
+                X86OperandType::Imm(value) => immediate = Some(value as u64),
+                X86OperandType::Mem(mem) if mem.base().0 as u32 == X86Reg::X86_REG_RIP => {
+                    memory = Some(next.wrapping_add(mem.disp() as u64));
+                }
+                _ => {}
+            }
+        }
+        Some(Instruction {
+            address,
+            bytes: insn.bytes().to_vec(),
+            mnemonic: insn.mnemonic()?.to_string(),
+            operands: insn.op_str().unwrap_or_default().to_string(),
+            target,
+            memory,
+            immediate,
+        })
+    })
+}
+
+#[cfg(not(feature = "disasm"))]
+fn decode_instruction(_code: &[u8], _address: u64) -> Option<Instruction> {
+    None
+}
+
+/// Decode the instruction at the start of `code`, which sits at `address`;
+/// None only when `code` is empty
+pub fn decode(code: &[u8], address: u64) -> Option<Instruction> {
+    let first = *code.first()?;
+    decode_instruction(&code[..code.len().min(MAX_LENGTH)], address).or_else(|| Some(Instruction {
+        address,
+        bytes: vec![first],
+        mnemonic: "db".to_string(),
+        operands: format!("{:#04x}", first),
+        target: None,
+        memory: None,
+        immediate: None,
+    }))
+}
+
+/// Decode up to `max` instructions from the start of `code`
+pub fn disassemble(code: &[u8], address: u64, max: usize) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut at = 0;
+    while instructions.len() < max {
+        let Some(instruction) = decode(&code[at..], address.wrapping_add(at as u64)) else { break };
+        at += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+    instructions
+}
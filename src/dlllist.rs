@@ -0,0 +1,216 @@
+//! DLLs loaded by Windows processes
+//!
+//! The loader links every module of a process into three lists hanging off
+//! `PEB.Ldr`: load order, memory order and initialization order (which the
+//! main executable is never on). Injection tools that hide a DLL usually
+//! unlink it from some of the lists but not all, and reflectively loaded
+//! DLLs are on none of them while their image stays mapped; walking every
+//! list and the process's image VADs exposes both.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::time::SystemTime;
+#[cfg(not(target_arch = "wasm32"))]
+use pager::Pager;
+
+use crate::kdbg::OsContext;
+use crate::loader::load_memory_image;
+use crate::paging::MemoryImage;
+use crate::pe::module_name;
+use crate::processes::{filetime_to_system, format_time, windows_finder, Process, ProcessFinder, WindowsProcessFinder};
+use crate::symbols::SymbolStore;
+use crate::vad::{VadKind, VadRegion};
+
+/// x64 PEB offsets
+const PEB_IMAGE_BASE: u64 = 0x10;
+const PEB_LDR: u64 = 0x18;
+
+/// LIST_ENTRY heads in PEB_LDR_DATA and links in LDR_DATA_TABLE_ENTRY, in
+/// load, memory and initialization order; both use the same offsets
+/// relative to the first list
+const LDR_DATA_LISTS: [u64; 3] = [0x10, 0x20, 0x30];
+const LDR_ENTRY_LINKS: [u64; 3] = [0x00, 0x10, 0x20];
+
+/// Names of the lists, in the order of `LoadedDll::lists`
+pub const LDR_LISTS: [&str; 3] = ["load order", "memory order", "init order"];
+
+/// x64 LDR_DATA_TABLE_ENTRY offsets, Windows 8 and later
+const LDR_ENTRY_DLL_BASE: u64 = 0x30;
+const LDR_ENTRY_SIZE_OF_IMAGE: u64 = 0x40;
+const LDR_ENTRY_FULL_NAME: u64 = 0x48;
+const LDR_ENTRY_BASE_NAME: u64 = 0x58;
+const LDR_ENTRY_LOAD_TIME: u64 = 0x100;
+
+/// Upper bound on list entries, guarding against corrupted links
+const MAX_LDR_ENTRIES: usize = 4096;
+
+/// A module on the loader lists of a process
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedDll {
+    /// Virtual address of the LDR_DATA_TABLE_ENTRY
+    pub entry: u64,
+    pub base: u64,
+    pub size: u64,
+    pub name: String,
+    pub path: Option<String>,
+    pub load_time: Option<SystemTime>,
+    /// On the load, memory and initialization order lists
+    pub lists: [bool; 3],
+    /// The process's main executable, which is never on the init order list
+    pub is_image: bool,
+}
+
+impl LoadedDll {
+    /// Names of the lists the entry should be on but is not
+    pub fn missing_from(&self) -> Vec<&'static str> {
+        (0..3).filter(|&i| !(self.lists[i] || i == 2 && self.is_image))
+            .map(|i| LDR_LISTS[i])
+            .collect()
+    }
+}
+
+/// Loader entries and image mappings of one process
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DllList {
+    /// In load order, then entries found only on the other lists
+    pub dlls: Vec<LoadedDll>,
+    /// Image VADs whose base no loader entry claims
+    pub unlisted_images: Vec<VadRegion>,
+}
+
+/// Entry addresses on the list whose head is at `head`, or `None` when the
+/// head is unreadable or was never initialized
+fn walk_list(img: &MemoryImage, head: u64, link_offset: u64) -> Option<Vec<u64>> {
+    let mut link = img.read_virt_u64(head).filter(|&l| l != 0)?;
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    while link != head && link != 0 && seen.insert(link) && seen.len() <= MAX_LDR_ENTRIES {
+        entries.push(link - link_offset);
+        link = match img.read_virt_u64(link) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Some(entries)
+}
+
+/// Walk the three loader lists of the PEB at `peb`; `img` must translate
+/// through the process's page tables
+pub fn loaded_dlls(img: &MemoryImage, peb: u64) -> Vec<LoadedDll> {
+    let Some(ldr) = img.read_virt_u64(peb + PEB_LDR).filter(|&l| l != 0) else { return Vec::new() };
+    let image_base = img.read_virt_u64(peb + PEB_IMAGE_BASE).unwrap_or(0);
+
+    // Lists that cannot be read at all say nothing about their entries
+    let lists: Vec<Option<Vec<u64>>> = (0..3)
+        .map(|i| walk_list(img, ldr + LDR_DATA_LISTS[i], LDR_ENTRY_LINKS[i]))
+        .collect();
+    let mut order: Vec<u64> = Vec::new();
+    for entries in lists.iter().flatten() {
+        for &entry in entries {
+            if !order.contains(&entry) {
+                order.push(entry);
+            }
+        }
+    }
+
+    order.into_iter().filter_map(|entry| {
+        let base = img.read_virt_u64(entry + LDR_ENTRY_DLL_BASE)?;
+        let path = img.read_unicode_string(entry + LDR_ENTRY_FULL_NAME);
+        let name = img.read_unicode_string(entry + LDR_ENTRY_BASE_NAME)
+            .or_else(|| path.as_ref().map(|p| p.rsplit('\\').next().unwrap_or(p).to_string()))
+            .or_else(|| module_name(img, base))
+            .unwrap_or_else(|| format!("module_{:X}", base));
+        let on = |i: usize| lists[i].as_ref().is_none_or(|entries| entries.contains(&entry));
+        Some(LoadedDll {
+            entry,
+            base,
+            size: img.read_virt_u32(entry + LDR_ENTRY_SIZE_OF_IMAGE).unwrap_or(0) as u64,
+            name,
+            path,
+            load_time: img.read_virt_u64(entry + LDR_ENTRY_LOAD_TIME).and_then(filetime_to_system),
+            lists: [on(0), on(1), on(2)],
+            is_image: base == image_base,
+        })
+    }).collect()
+}
+
+/// Loader entries of `process` and its image VADs no entry accounts for;
+/// the image must use the kernel DTB
+pub fn process_dlls(img: &MemoryImage, finder: &WindowsProcessFinder, process: &Process) -> DllList {
+    let peb = finder.process_context(img, process.virtual_address).map_or(0, |ctx| ctx.peb);
+    let Some(space) = process.address_space(img).filter(|_| peb != 0) else { return DllList::default() };
+    let dlls = loaded_dlls(&space, peb);
+    let unlisted_images = finder.vads(img, process).into_iter()
+        .filter(|vad| vad.kind == VadKind::Image && !dlls.iter().any(|dll| dll.base == vad.start))
+        .collect();
+    DllList { dlls, unlisted_images }
+}
+
+/// List the DLLs of every process, or only of `pid`, and flag modules
+/// unlinked from a loader list or mapped without a loader entry
+pub fn list_dlls(dump_path: PathBuf, dtb: u64, pid: Option<u32>, symbols: Option<SymbolStore>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    memory_image.set_cr3(dtb);
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let os = OsContext::find(&memory_image, &progress).context("No KDBG block found; cannot walk the process list")?;
+    let finder = windows_finder(&memory_image, os, symbols.as_ref());
+    let processes: Vec<Process> = finder.find_processes(&memory_image, &progress)?
+        .into_iter()
+        .filter(|p| pid.is_none_or(|pid| p.pid == pid))
+        .collect();
+    progress.finish_and_clear();
+    if let (Some(pid), true) = (pid, processes.is_empty()) {
+        bail!("Process {} is not on the active process list", pid);
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"PID", bFg->"Process", bFg->"Base", bFg->"Size", bFg->"Name", bFg->"Path", bFg->"Load Time"]);
+    let mut suspicious: BTreeMap<(u32, u64), String> = BTreeMap::new();
+    for process in &processes {
+        let list = process_dlls(&memory_image, &finder, process);
+        for dll in &list.dlls {
+            table.add_row(row![
+                process.pid,
+                process.name,
+                format!("0x{:X}", dll.base),
+                format!("0x{:X}", dll.size),
+                dll.name,
+                dll.path.as_deref().unwrap_or("-"),
+                dll.load_time.map_or("-".to_string(), format_time)
+            ]);
+            let missing = dll.missing_from();
+            if !missing.is_empty() {
+                suspicious.insert((process.pid, dll.base), format!("{} at 0x{:X} in {} (PID {}) is missing from the {} list",
+                    dll.name.bright_yellow(), dll.base, process.name, process.pid, missing.join(" and ")));
+            }
+        }
+        for vad in &list.unlisted_images {
+            suspicious.insert((process.pid, vad.start), format!("image {} at 0x{:X} in {} (PID {}) is on no loader list",
+                vad.file.as_deref().unwrap_or("(unnamed)").bright_yellow(), vad.start, process.name, process.pid));
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if table.len() > 20 {
+        Pager::new().setup();
+    }
+
+    println!("{} {} modules in {} processes", "Found".bright_green(), table.len().to_string().bright_yellow(), processes.len());
+    table.printstd();
+    if suspicious.is_empty() {
+        println!("\n{}", "Every module is on all loader lists".bright_green());
+    }
+    for line in suspicious.values() {
+        println!("{} {}", "Suspicious:".bright_red(), line);
+    }
+    Ok(())
+}
@@ -0,0 +1,351 @@
+//! .NET assemblies in process memory
+//!
+//! A process runs managed code once the CLR is loaded: `mscorwks.dll` for
+//! .NET Framework 2 to 3.5, `clr.dll` from 4, `coreclr.dll` for .NET Core
+//! and .NET 5 on. Every assembly it loads is a PE image whose COM
+//! descriptor directory points at a CLI header and `BSJB` metadata. Those
+//! loaded from disk are image views of their file; those passed to
+//! `Assembly.Load(byte[])`, as loader malware does with its payload, are
+//! copied into private memory in file layout, usually inside a byte array
+//! on the managed heap, so private and pagefile-backed regions are searched
+//! for them at every array-aligned offset. An assembly is named by its
+//! Module table, the file name it was compiled to, and identified by its
+//! MVID, which stays the same however it is packed on disk.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dlllist::process_dlls;
+use crate::kdbg::OsContext;
+use crate::loader::load_memory_image;
+use crate::modules::read_pages;
+use crate::paging::MemoryImage;
+use crate::pe::{read_u16, read_u32, read_u64, rebuild_pe, ExportIndex, PeHeaders};
+use crate::plugin::has_pe_header;
+use crate::processes::{windows_finder, Process, ProcessAddressSpace, ProcessFinder, WindowsProcessFinder};
+use crate::symbols::SymbolStore;
+use crate::vad::VadKind;
+
+/// Data directory of the CLI header
+const COM_DESCRIPTOR_DIRECTORY: usize = 14;
+const CLI_HEADER_SIZE: u32 = 0x48;
+const METADATA_SIGNATURE: &[u8] = b"BSJB";
+/// Longest metadata version string, and streams read of the metadata root
+const MAX_VERSION_LEN: usize = 0x100;
+const MAX_STREAMS: u16 = 8;
+/// CLI header flags
+const COMIMAGE_FLAGS_ILONLY: u32 = 0x1;
+const COMIMAGE_FLAGS_32BITREQUIRED: u32 = 0x2;
+const COMIMAGE_FLAGS_STRONGNAMESIGNED: u32 = 0x8;
+const COMIMAGE_FLAGS_NATIVE_ENTRYPOINT: u32 = 0x10;
+const PAGE_SIZE: u64 = 0x1000;
+/// Byte arrays on the managed heap start 8-byte aligned
+const ARRAY_ALIGN: usize = 8;
+/// Regions searched for assemblies, and assemblies read, up to this size
+const MAX_REGION_SIZE: u64 = 0x1000_0000;
+const MAX_ASSEMBLY_SIZE: u64 = 0x400_0000;
+
+/// The CLR a process has loaded, by the DLL names of its modules
+pub fn clr_runtime<'a>(modules: impl IntoIterator<Item = &'a str>) -> Option<&'static str> {
+    let mut runtime = None;
+    for module in modules {
+        let found = match module.to_lowercase().as_str() {
+            "coreclr.dll" => ".NET Core",
+            "clr.dll" => ".NET Framework 4",
+            "mscorwks.dll" | "mscorsvr.dll" => ".NET Framework 2",
+            "mscoree.dll" => "mscoree (shim only)",
+            _ => continue,
+        };
+        // The shim is loaded with every runtime
+        if runtime.is_none() || runtime == Some("mscoree (shim only)") {
+            runtime = Some(found);
+        }
+    }
+    runtime
+}
+
+/// A GUID in its usual text form
+pub fn format_guid(guid: &[u8; 16]) -> String {
+    format!("{{{:08X}-{:04X}-{:04X}-{}-{}}}",
+        u32::from_le_bytes(guid[0..4].try_into().unwrap()),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        guid[8..10].iter().map(|b| format!("{:02X}", b)).collect::<String>(),
+        guid[10..].iter().map(|b| format!("{:02X}", b)).collect::<String>())
+}
+
+fn bounded_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(&bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())]).to_string()
+}
+
+/// What the CLI header and metadata of an assembly say
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClrHeader {
+    pub runtime_version: (u16, u16),
+    pub flags: u32,
+    pub entry_point_token: u32,
+    /// Version of the runtime the metadata was built for, e.g. `v4.0.30319`
+    pub metadata_version: String,
+    /// Name in the Module table
+    pub module_name: Option<String>,
+    /// Module version ID
+    pub mvid: Option<[u8; 16]>,
+}
+
+impl ClrHeader {
+    /// Parse the CLI header and metadata of the image at `image[0]`, laid
+    /// out as `mapped` or as a file; `None` unless it is an assembly
+    pub fn parse(image: &[u8], mapped: bool) -> Option<ClrHeader> {
+        let headers = PeHeaders::parse(image)?;
+        let &(rva, size) = headers.directories.get(COM_DESCRIPTOR_DIRECTORY)?;
+        if rva == 0 || size < CLI_HEADER_SIZE {
+            return None;
+        }
+        let cli = headers.offset(rva, mapped)?;
+        let root = headers.offset(read_u32(image, cli + 8)?, mapped)?;
+        if image.get(root..root + 4)? != METADATA_SIGNATURE {
+            return None;
+        }
+        let version_len = read_u32(image, root + 12)? as usize;
+        if version_len > MAX_VERSION_LEN {
+            return None;
+        }
+        let metadata_version = bounded_string(image.get(root + 16..root + 16 + version_len)?);
+
+        // Stream headers: offset from the root, size, and a padded name
+        let mut streams = Vec::new();
+        let count = read_u16(image, root + 16 + version_len + 2)?;
+        let mut at = root + 16 + version_len + 4;
+        for _ in 0..count.min(MAX_STREAMS) {
+            let (offset, size) = (read_u32(image, at)? as usize, read_u32(image, at + 4)? as usize);
+            let name = bounded_string(image.get(at + 8..(at + 40).min(image.len()))?);
+            at += 8 + (name.len() + 1).next_multiple_of(4);
+            streams.push((name, root + offset, size));
+        }
+        let stream = |wanted: &str| streams.iter().find(|(name, ..)| name == wanted).and_then(|&(_, start, size)| image.get(start..start.checked_add(size)?));
+
+        let mut header = ClrHeader {
+            runtime_version: (read_u16(image, cli + 4)?, read_u16(image, cli + 6)?),
+            flags: read_u32(image, cli + 16)?,
+            entry_point_token: read_u32(image, cli + 20)?,
+            metadata_version,
+            module_name: None,
+            mvid: None,
+        };
+        // The Module table comes first, so its row needs no other table's size
+        let Some(tables) = stream("#~").or_else(|| stream("#-")) else { return Some(header) };
+        let heap_sizes = *tables.get(6)?;
+        let valid = read_u64(tables, 8)?;
+        if valid & 1 == 0 {
+            return Some(header);
+        }
+        let row = 24 + 4 * valid.count_ones() as usize;
+        let index = |offset: usize, wide: bool| if wide { read_u32(tables, offset).map(|i| i as usize) } else { read_u16(tables, offset).map(|i| i as usize) };
+        let (string_wide, guid_wide) = (heap_sizes & 1 != 0, heap_sizes & 2 != 0);
+        let name = index(row + 2, string_wide)?;
+        let mvid = index(row + 2 + if string_wide { 4 } else { 2 }, guid_wide)?;
+        header.module_name = stream("#Strings").and_then(|strings| strings.get(name..)).map(bounded_string).filter(|name| !name.is_empty());
+        header.mvid = stream("#GUID").filter(|_| mvid != 0).and_then(|guids| guids.get((mvid - 1) * 16..mvid * 16)).and_then(|guid| guid.try_into().ok());
+        Some(header)
+    }
+
+    /// Names of the flags that are set
+    pub fn flag_names(&self) -> Vec<&'static str> {
+        [
+            (COMIMAGE_FLAGS_ILONLY, "il_only"),
+            (COMIMAGE_FLAGS_32BITREQUIRED, "32bit_required"),
+            (COMIMAGE_FLAGS_STRONGNAMESIGNED, "strong_name_signed"),
+            (COMIMAGE_FLAGS_NATIVE_ENTRYPOINT, "native_entrypoint"),
+        ].into_iter().filter(|&(flag, _)| self.flags & flag != 0).map(|(_, name)| name).collect()
+    }
+}
+
+/// Where an assembly was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssemblySource {
+    /// An image view of its file
+    Image,
+    /// Private or pagefile-backed memory, as `Assembly.Load(byte[])` leaves it
+    Memory,
+}
+
+impl std::fmt::Display for AssemblySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssemblySource::Image => write!(f, "image"),
+            AssemblySource::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// An assembly in a process's address space
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assembly {
+    pub base: u64,
+    pub size: u64,
+    /// Laid out as loaded rather than as a file
+    pub mapped: bool,
+    pub source: AssemblySource,
+    /// File of the image view
+    pub file: Option<String>,
+    pub clr: ClrHeader,
+}
+
+impl Assembly {
+    /// The module name, or the file name of its image view
+    pub fn name(&self) -> String {
+        self.clr.module_name.clone()
+            .or_else(|| self.file.as_deref().and_then(|file| file.rsplit('\\').next()).map(String::from))
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    /// The bytes of the assembly, mapped or as a file as found
+    pub fn read(&self, space: &MemoryImage) -> Vec<u8> {
+        read_pages(space, self.base, self.size).0
+    }
+}
+
+/// The assembly at `base` if there is one, read no further than `limit`
+fn assembly_at(space: &MemoryImage, base: u64, limit: u64, source: AssemblySource) -> Option<Assembly> {
+    // The page after the headers may not be resident
+    let rest_of_page = PAGE_SIZE - base % PAGE_SIZE;
+    let header = space.read_virt(base, PAGE_SIZE.min(limit - base) as usize)
+        .or_else(|| space.read_virt(base, rest_of_page.min(limit - base) as usize))?;
+    if !has_pe_header(&header) {
+        return None;
+    }
+    let headers = PeHeaders::parse(&header)?;
+    headers.directories.get(COM_DESCRIPTOR_DIRECTORY).filter(|&&(rva, _)| rva != 0)?;
+    let file_size = headers.sections.iter().map(|s| s.raw_offset as u64 + s.raw_size as u64).max().unwrap_or(0).max(headers.headers_size as u64);
+    // Image views are mapped; anything else is tried as a file first
+    let layouts = match source {
+        AssemblySource::Image => vec![(true, headers.image_size as u64)],
+        AssemblySource::Memory => vec![(false, file_size), (true, headers.image_size as u64)],
+    };
+    layouts.into_iter()
+        .filter(|&(_, size)| size != 0 && size <= MAX_ASSEMBLY_SIZE && size <= limit - base)
+        .find_map(|(mapped, size)| {
+            let clr = ClrHeader::parse(&read_pages(space, base, size).0, mapped)?;
+            Some(Assembly { base, size, mapped, source, file: None, clr })
+        })
+}
+
+/// The assemblies in the address space of `process`: image views with a
+/// CLI header, and assemblies anywhere in its other regions
+pub fn process_assemblies(img: &MemoryImage, finder: &WindowsProcessFinder, process: &Process) -> Vec<Assembly> {
+    let Some(space) = process.address_space(img) else { return Vec::new() };
+    let mut assemblies = Vec::new();
+    for vad in finder.vads(img, process).into_iter().filter(|vad| vad.size() <= MAX_REGION_SIZE) {
+        let limit = vad.end + 1;
+        if vad.kind == VadKind::Image {
+            if let Some(assembly) = assembly_at(&space, vad.start, limit, AssemblySource::Image) {
+                assemblies.push(Assembly { file: vad.file.clone(), ..assembly });
+            }
+            continue;
+        }
+        let mut page = vad.start;
+        while page < limit {
+            let found = space.read_virt(page, PAGE_SIZE as usize).and_then(|data| {
+                (0..data.len()).step_by(ARRAY_ALIGN)
+                    .filter(|&offset| data[offset..].starts_with(b"MZ"))
+                    .find_map(|offset| assembly_at(&space, page + offset as u64, limit, AssemblySource::Memory))
+            });
+            page = match found {
+                Some(assembly) => {
+                    let next = ((assembly.base + assembly.size) & !(PAGE_SIZE - 1)).max(page + PAGE_SIZE);
+                    assemblies.push(assembly);
+                    next
+                }
+                None => page + PAGE_SIZE,
+            };
+        }
+    }
+    assemblies
+}
+
+/// Write `assembly` of process `pid` into `output` as a file a decompiler
+/// loads, rebuilding a mapped one into file layout
+pub fn write_assembly(space: &ProcessAddressSpace, pid: u32, assembly: &Assembly, output: &Path) -> Result<PathBuf> {
+    let data = assembly.read(space);
+    let data = if assembly.mapped { rebuild_pe(&data, assembly.base, &ExportIndex::default()).unwrap_or(data) } else { data };
+    let path = output.join(format!("pid.{}.{}.0x{:x}", pid, assembly.name().replace(['/', '\\', ':'], "_"), assembly.base));
+    fs::write(&path, data).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// List the CLR and assemblies of every process, or only of `pid`, and
+/// write the assemblies into `output` when one is given
+pub fn list_assemblies(dump_path: PathBuf, dtb: u64, pid: Option<u32>, output: Option<PathBuf>, symbols: Option<SymbolStore>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    memory_image.set_cr3(dtb);
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let os = OsContext::find(&memory_image, &progress).context("No KDBG block found; cannot walk the process list")?;
+    let finder = windows_finder(&memory_image, os, symbols.as_ref());
+    let processes: Vec<Process> = finder.find_processes(&memory_image, &progress)?
+        .into_iter()
+        .filter(|p| pid.is_none_or(|pid| p.pid == pid))
+        .collect();
+    if let (Some(pid), true) = (pid, processes.is_empty()) {
+        bail!("Process {} is not on the active process list", pid);
+    }
+    if let Some(output) = &output {
+        fs::create_dir_all(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"PID", bFg->"Process", bFg->"Runtime", bFg->"Base", bFg->"Size", bFg->"Module", bFg->"MVID", bFg->"Source", bFg->"File"]);
+    let (mut count, mut suspicious, mut files) = (0, Vec::new(), Vec::new());
+    progress.set_length(processes.len() as u64);
+    for (index, process) in processes.iter().enumerate() {
+        progress.set_position(index as u64 + 1);
+        progress.set_message(format!("Searching {} (PID {}) for assemblies", process.name, process.pid));
+        let dlls = process_dlls(&memory_image, &finder, process).dlls;
+        let runtime = clr_runtime(dlls.iter().map(|dll| dll.name.as_str()));
+        let assemblies = process_assemblies(&memory_image, &finder, process);
+        let Some(space) = process.address_space(&memory_image) else { continue };
+        for assembly in &assemblies {
+            let file = match &output {
+                Some(output) => {
+                    let path = write_assembly(&space, process.pid, assembly, output)?;
+                    files.push(path.clone());
+                    path.display().to_string()
+                }
+                None => assembly.file.clone().unwrap_or_else(|| "-".to_string()),
+            };
+            table.add_row(row![
+                process.pid,
+                process.name,
+                runtime.unwrap_or("-"),
+                format!("0x{:X}", assembly.base),
+                format!("0x{:X}", assembly.size),
+                assembly.name(),
+                assembly.clr.mvid.as_ref().map_or("-".to_string(), format_guid),
+                assembly.source,
+                file
+            ]);
+            if assembly.source == AssemblySource::Memory {
+                suspicious.push(format!("{} at 0x{:X} in {} (PID {}) was loaded from memory, not from a file",
+                    assembly.name().bright_yellow(), assembly.base, process.name, process.pid));
+            }
+        }
+        count += assemblies.len();
+    }
+    progress.finish_and_clear();
+
+    println!("{} {} assemblies in {} processes", "Found".bright_green(), count.to_string().bright_yellow(), processes.len());
+    table.printstd();
+    for line in &suspicious {
+        println!("{} {}", "Suspicious:".bright_red(), line);
+    }
+    crate::actions::run_for_files(&dump_path, &files);
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! Discovery of the page table root in dumps that do not record one
+//!
+//! Raw dumps carry no CPU state, so the DTB has to be found by scanning for
+//! pages that look like an x86_64 PML4. Every process has its own PML4 but
+//! they all share the kernel half, so a candidate is scored by the share of
+//! its present kernel-half entries that lead to a sane PDPT: inside the
+//! image, not marked as a large page, and with every present entry of its
+//! own pointing back inside the image. Windows maps the PML4 into itself,
+//! so a self-referencing entry breaks ties between equal scores.
+
+use indicatif::ProgressBar;
+use std::fmt;
+
+use crate::arch::x86_64::{PDPTEntry, PML4Entry, PAGE_SIZE};
+use crate::paging::{Architecture, MemoryImage};
+
+/// Candidates tried, best first, before giving up on a dump
+pub const MAX_DTB_ATTEMPTS: usize = 8;
+
+/// Candidates scoring below this percentage are discarded
+pub const MIN_DTB_SCORE: f64 = 50.0;
+
+/// First PML4 index of the kernel half of the address space
+const KERNEL_HALF: usize = 256;
+
+/// A page that may be the PML4 of the dumped system
+#[derive(Debug, Clone, PartialEq)]
+pub struct DtbCandidate {
+    pub dtb: u64,
+    /// Present kernel-half PML4 entries
+    pub kernel_entries: usize,
+    /// Kernel-half entries leading to a sane PDPT
+    pub valid_entries: usize,
+    /// Kernel-half PML4 index mapping the table onto itself
+    pub self_ref: Option<usize>,
+}
+
+impl DtbCandidate {
+    /// Percentage of present kernel-half entries that translate sanely
+    pub fn score(&self) -> f64 {
+        self.valid_entries as f64 * 100.0 / self.kernel_entries as f64
+    }
+}
+
+impl fmt::Display for DtbCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:X} (score {:.0}%, {}/{} kernel entries", self.dtb, self.score(), self.valid_entries, self.kernel_entries)?;
+        if let Some(index) = self.self_ref {
+            write!(f, ", self-map 0x{:X}", index)?;
+        }
+        write!(f, ")")
+    }
+}
+
+fn entries(page: &[u8]) -> impl Iterator<Item = u64> + '_ {
+    page.chunks_exact(8).map(|e| u64::from_le_bytes(e.try_into().unwrap()))
+}
+
+/// A present PDPT at `table` whose present entries all stay inside the image
+fn sane_pdpt(img: &MemoryImage, table: u64) -> bool {
+    let Some(page) = img.get_bytes(table as usize, PAGE_SIZE) else { return false };
+    let mut present = 0;
+    for entry in entries(page).map(PDPTEntry::new).filter(PDPTEntry::is_present) {
+        let frame = entry.get_physical_address();
+        // 1GB pages must be aligned to their size; tables must be readable
+        let sane = if entry.is_page_size_1gb() {
+            frame & 0x3FFF_FFFF == 0 && (frame as usize) < img.size()
+        } else {
+            img.get_bytes(frame as usize, PAGE_SIZE).is_some()
+        };
+        if !sane {
+            return false;
+        }
+        present += 1;
+    }
+    present > 0
+}
+
+/// Score the page at `dtb` as an x86_64 PML4; `None` without kernel-half entries
+pub fn score_dtb(img: &MemoryImage, dtb: u64) -> Option<DtbCandidate> {
+    let dtb = dtb & !(PAGE_SIZE as u64 - 1);
+    let page = img.get_bytes(dtb as usize, PAGE_SIZE)?;
+    let mut candidate = DtbCandidate { dtb, kernel_entries: 0, valid_entries: 0, self_ref: None };
+    for (index, value) in entries(page).enumerate().skip(KERNEL_HALF) {
+        let entry = PML4Entry::new(value);
+        if !entry.is_present() {
+            continue;
+        }
+        candidate.kernel_entries += 1;
+        let frame = entry.get_physical_address();
+        if frame == dtb {
+            candidate.self_ref.get_or_insert(index);
+            candidate.valid_entries += 1;
+        } else if value & 0x80 == 0 && sane_pdpt(img, frame) {
+            candidate.valid_entries += 1;
+        }
+    }
+    (candidate.kernel_entries > 0).then_some(candidate)
+}
+
+/// Pages of the image that look like an x86_64 PML4, best first
+///
+/// Other architectures are not scanned and return nothing.
+pub fn find_dtb_candidates(img: &MemoryImage, progress: &ProgressBar) -> Vec<DtbCandidate> {
+    if img.info.arch != Architecture::X86_64 {
+        return Vec::new();
+    }
+    progress.set_length(img.size() as u64);
+    progress.set_message("Scanning for page table roots");
+
+    let mut candidates = Vec::new();
+    for run in img.runs() {
+        let first = run.start.next_multiple_of(PAGE_SIZE as u64);
+        for dtb in (first..run.end()).step_by(PAGE_SIZE) {
+            progress.set_position(dtb);
+            if let Some(candidate) = score_dtb(img, dtb).filter(|c| c.score() >= MIN_DTB_SCORE) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.score().total_cmp(&a.score())
+            .then(b.self_ref.is_some().cmp(&a.self_ref.is_some()))
+            .then(b.valid_entries.cmp(&a.valid_entries))
+            .then(a.dtb.cmp(&b.dtb))
+    });
+    candidates
+}
+
+/// Translate `va` with each candidate in turn, leaving the image on the
+/// first that maps it; returns that candidate's index and the physical address
+pub fn translate_with_candidates(img: &mut MemoryImage, candidates: &[DtbCandidate], va: u64) -> Option<(usize, u64)> {
+    candidates.iter().enumerate().find_map(|(index, candidate)| {
+        img.set_cr3(candidate.dtb);
+        img.virt_to_phys(va).map(|pa| (index, pa))
+    })
+}
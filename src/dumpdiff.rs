@@ -0,0 +1,364 @@
+//! Comparison of two captures of the same host
+//!
+//! `rmf diff` snapshots each capture: its processes, including the exited
+//! ones left in pool, kernel modules, network endpoints, the findings of
+//! selected plugins, and a [`ProcessSnapshot`] of every running process.
+//! The later capture is then compared with the earlier one. Processes are
+//! matched by PID and start time, so a reused PID shows up as one process
+//! terminating and another being created. For processes running in both
+//! captures, new code outside every loaded module is reported as injected,
+//! as `diff-proc` does for a single process.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::kdbg::OsContext;
+use crate::limits::{parallel_map, ResourceLimits};
+use crate::loader::load_memory_image;
+use crate::modules::{find_kernel_modules, KernelModule};
+use crate::output::Record;
+use crate::paging::MemoryImage;
+use crate::plugin::{format_endpoint, get_plugin_registry, Finding, MemoryPlugin, NetworkScanner};
+use crate::procdiff::{LoadedModule, ProcessDiff, ProcessSnapshot};
+use crate::processes::{merge_remnants, windows_finder, Process, ProcessFinder};
+
+/// Identity of a process across captures
+pub type ProcessKey = (u32, SystemTime);
+
+fn process_key(process: &Process) -> ProcessKey {
+    (process.pid, process.start_time)
+}
+
+/// A network endpoint and the process owning it
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Connection {
+    pub protocol: String,
+    pub local: String,
+    /// Peer of a connected endpoint
+    pub remote: Option<String>,
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+}
+
+impl Connection {
+    /// The endpoint a `netscan` finding describes
+    pub fn from_finding(finding: &Finding) -> Option<Self> {
+        let endpoint = |addr: &str, port: &str| -> Option<String> {
+            let addr: IpAddr = finding.details.get(addr)?.parse().ok()?;
+            Some(format_endpoint(addr, finding.details.get(port)?.parse().ok()?))
+        };
+        Some(Connection {
+            protocol: finding.details.get("protocol")?.clone(),
+            local: endpoint("local_addr", "local_port")?,
+            remote: endpoint("remote_addr", "remote_port"),
+            pid: finding.details.get("pid").and_then(|pid| pid.parse().ok()),
+            process: finding.details.get("process").cloned(),
+        })
+    }
+}
+
+impl std::fmt::Display for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.protocol, self.local)?;
+        if let Some(remote) = &self.remote {
+            write!(f, " -> {}", remote)?;
+        }
+        if let (Some(pid), Some(process)) = (self.pid, &self.process) {
+            write!(f, " by {} ({})", pid, process)?;
+        }
+        Ok(())
+    }
+}
+
+/// State of a host in one capture
+#[derive(Debug, Clone, Default)]
+pub struct HostSnapshot {
+    /// Active processes followed by the exited ones pool scanning found
+    pub processes: Vec<Process>,
+    pub modules: Vec<KernelModule>,
+    pub connections: BTreeSet<Connection>,
+    pub findings: Vec<Finding>,
+    /// Snapshots of the processes on the active process list
+    pub process_snapshots: BTreeMap<ProcessKey, ProcessSnapshot>,
+}
+
+impl HostSnapshot {
+    /// Snapshot a Windows capture and run `plugins` on it; the image must use the kernel DTB
+    pub fn capture(img: &MemoryImage, plugins: &[&dyn MemoryPlugin], progress: &ProgressBar) -> Result<Self> {
+        img.info.dtb.context("A kernel DTB is required (use --dtb)")?;
+        let os = OsContext::find(img, progress).context("No KDBG block found")?;
+        let modules = find_kernel_modules(img, Some(&os), progress).into_iter().map(|found| found.module).collect();
+        let finder = windows_finder(img, os, None);
+        let mut processes = finder.find_processes(img, progress)?;
+
+        let mut process_snapshots = BTreeMap::new();
+        progress.set_message("Snapshotting processes");
+        for process in &processes {
+            let Some(ctx) = finder.process_context(img, process.virtual_address) else { continue };
+            let Some(space) = process.address_space(img) else { continue };
+            process_snapshots.insert(
+                process_key(process),
+                ProcessSnapshot::from_address_space(&space, process.pid, &process.name, &ctx),
+            );
+        }
+        merge_remnants(&mut processes, finder.scan_remnants(img, progress)?);
+
+        let connections = NetworkScanner.scan(img, progress).iter().filter_map(Connection::from_finding).collect();
+        let mut findings = Vec::new();
+        for plugin in plugins {
+            progress.set_message(format!("Running {}", plugin.name()));
+            findings.extend(plugin.scan(img, progress));
+        }
+
+        Ok(HostSnapshot { processes, modules, connections, findings, process_snapshots })
+    }
+
+    /// Changes from this snapshot to a `later` one of the same host
+    pub fn diff(&self, later: &HostSnapshot) -> HostDiff {
+        let before: BTreeMap<ProcessKey, &Process> = self.processes.iter().map(|p| (process_key(p), p)).collect();
+        let after: BTreeMap<ProcessKey, &Process> = later.processes.iter().map(|p| (process_key(p), p)).collect();
+        let running = |process: &Process| process.exit_time.is_none();
+
+        let mut diff = HostDiff {
+            processes_created: later.processes.iter()
+                .filter(|p| !before.contains_key(&process_key(p)))
+                .cloned()
+                .collect(),
+            ..Default::default()
+        };
+        // Running before and either gone or exited since; a process that both
+        // started and exited in between is created and terminated
+        for process in self.processes.iter().filter(|p| running(p)) {
+            match after.get(&process_key(process)) {
+                None => diff.processes_terminated.push(process.clone()),
+                Some(later) if !running(later) => diff.processes_terminated.push((*later).clone()),
+                _ => {}
+            }
+        }
+        diff.processes_terminated.extend(later.processes.iter()
+            .filter(|p| !running(p) && !before.contains_key(&process_key(p)))
+            .cloned());
+
+        let bases = |modules: &[KernelModule]| -> BTreeSet<(u64, String)> {
+            modules.iter().map(|m| (m.base, m.name.clone())).collect()
+        };
+        let (old_modules, new_modules) = (bases(&self.modules), bases(&later.modules));
+        diff.modules_loaded = later.modules.iter().filter(|m| !old_modules.contains(&(m.base, m.name.clone()))).cloned().collect();
+        diff.modules_unloaded = self.modules.iter().filter(|m| !new_modules.contains(&(m.base, m.name.clone()))).cloned().collect();
+
+        diff.connections_opened = later.connections.difference(&self.connections).cloned().collect();
+        diff.connections_closed = self.connections.difference(&later.connections).cloned().collect();
+
+        // Pool addresses differ between captures; what a finding says does not
+        let describe = |findings: &[Finding]| -> BTreeSet<(String, String)> {
+            findings.iter().map(|f| (f.plugin.clone(), f.desc.clone())).collect()
+        };
+        let (old_findings, new_findings) = (describe(&self.findings), describe(&later.findings));
+        diff.findings_new = later.findings.iter().filter(|f| !old_findings.contains(&(f.plugin.clone(), f.desc.clone()))).cloned().collect();
+        diff.findings_gone = self.findings.iter().filter(|f| !new_findings.contains(&(f.plugin.clone(), f.desc.clone()))).cloned().collect();
+
+        for (key, old) in &self.process_snapshots {
+            let Some(new) = later.process_snapshots.get(key) else { continue };
+            let changes = old.diff(new);
+            if !changes.injected.is_empty() || !changes.modules_loaded.is_empty() || !changes.modules_unloaded.is_empty() {
+                diff.processes_changed.push((new.clone(), changes));
+            }
+        }
+        diff
+    }
+}
+
+/// Differences between two captures of a host
+#[derive(Debug, Clone, Default)]
+pub struct HostDiff {
+    /// Processes in the later capture only, running or not
+    pub processes_created: Vec<Process>,
+    /// Processes that were running and exited or disappeared, as last seen
+    pub processes_terminated: Vec<Process>,
+    pub modules_loaded: Vec<KernelModule>,
+    pub modules_unloaded: Vec<KernelModule>,
+    pub connections_opened: Vec<Connection>,
+    pub connections_closed: Vec<Connection>,
+    /// Findings of the selected plugins only the later capture has
+    pub findings_new: Vec<Finding>,
+    pub findings_gone: Vec<Finding>,
+    /// Processes running in both captures that loaded or unloaded DLLs, or
+    /// gained code outside every module
+    pub processes_changed: Vec<(ProcessSnapshot, ProcessDiff)>,
+}
+
+impl HostDiff {
+    pub fn is_empty(&self) -> bool {
+        self.records().is_empty()
+    }
+
+    /// Every difference as a record
+    pub fn records(&self) -> Vec<DiffRecord> {
+        let record = |change: &str, kind: &str, pid: Option<u32>, name: &str, address: Option<u64>, description: String| DiffRecord {
+            change: change.to_string(),
+            kind: kind.to_string(),
+            pid,
+            name: name.to_string(),
+            address: address.map(|addr| format!("0x{:X}", addr)),
+            description,
+        };
+        let mut records = Vec::new();
+        for (change, processes) in [("+", &self.processes_created), ("-", &self.processes_terminated)] {
+            for p in processes {
+                let description = match p.exit_time {
+                    Some(exit) => format!("PPID {}, exited {}", p.ppid, chrono::DateTime::<chrono::Utc>::from(exit).format("%Y-%m-%d %H:%M:%S")),
+                    None => format!("PPID {}, running", p.ppid),
+                };
+                records.push(record(change, "process", Some(p.pid), &p.name, None, description));
+            }
+        }
+        for (change, modules) in [("+", &self.modules_loaded), ("-", &self.modules_unloaded)] {
+            for m in modules {
+                let description = m.path.clone().unwrap_or_else(|| format!("{} bytes", m.size));
+                records.push(record(change, "kernel_module", None, &m.name, Some(m.base), description));
+            }
+        }
+        for (change, connections) in [("+", &self.connections_opened), ("-", &self.connections_closed)] {
+            for c in connections {
+                records.push(record(change, "connection", c.pid, c.process.as_deref().unwrap_or(""), None, c.to_string()));
+            }
+        }
+        for (change, findings) in [("+", &self.findings_new), ("-", &self.findings_gone)] {
+            for f in findings {
+                records.push(record(change, "finding", f.details.get("pid").and_then(|pid| pid.parse().ok()), &f.plugin, Some(f.addr), f.desc.clone()));
+            }
+        }
+        for (process, changes) in &self.processes_changed {
+            let dlls = changes.modules_loaded.iter().map(|m| ("+", m)).chain(changes.modules_unloaded.iter().map(|m| ("-", m)));
+            for (change, LoadedModule { base, name, .. }) in dlls {
+                records.push(record(change, "dll", Some(process.pid), &process.name, Some(*base), name.clone()));
+            }
+            for &(start, size) in &changes.injected {
+                let description = format!("{} bytes of new code outside any module", size);
+                records.push(record("+", "injected", Some(process.pid), &process.name, Some(start), description));
+            }
+        }
+        records
+    }
+}
+
+/// A difference as `diff --format json|csv` reports it
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffRecord {
+    /// `+` for what the later capture has, `-` for what it lost
+    pub change: String,
+    /// `process`, `kernel_module`, `connection`, `finding`, `dll` or `injected`
+    pub kind: String,
+    pub pid: Option<u32>,
+    /// Process, module or plugin name
+    pub name: String,
+    pub address: Option<String>,
+    pub description: String,
+}
+
+impl Record for DiffRecord {
+    const SCHEMA: &'static str = "rmf.diff";
+    const TABLE: &'static str = "diffs";
+    const COLUMNS: &'static [&'static str] = &["change", "kind", "pid", "name", "address", "description"];
+}
+
+/// Snapshot both captures, running `plugins` on each, and print what changed between them
+pub fn report_dump_diff(before: PathBuf, after: PathBuf, dtbs: [Option<u64>; 2], plugins: Vec<String>) -> Result<()> {
+    let registry = get_plugin_registry();
+    let registry = registry.read().unwrap();
+    let mut selected = Vec::new();
+    for name in &plugins {
+        match registry.get(name) {
+            Some(plugin) => selected.push(plugin),
+            None => crate::status!("{} {}", "Unknown plugin, skipped:".bright_red(), name),
+        }
+    }
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+
+    let captures = [(before, dtbs[0]), (after, dtbs[1])];
+    let snapshots = parallel_map(&captures, ResourceLimits::new().threads_for(captures.len()), |(path, dtb)| {
+        let mut memory_image = load_memory_image(path)?;
+        if memory_image.info.user.is_some() {
+            bail!("{} is a user-mode dump; diff needs kernel captures", path.display());
+        }
+        if let Some(dtb) = dtb {
+            memory_image.set_cr3(*dtb);
+        }
+        HostSnapshot::capture(&memory_image, &selected, &progress)
+            .with_context(|| format!("Failed to snapshot {}", path.display()))
+    }).into_iter().collect::<Result<Vec<_>>>()?;
+    progress.finish_and_clear();
+
+    let (old, new) = (&snapshots[0], &snapshots[1]);
+    let diff = old.diff(new);
+    if crate::output::structured() {
+        return crate::output::emit(&captures[1].0, &diff.records());
+    }
+
+    let running = |snapshot: &HostSnapshot| snapshot.processes.iter().filter(|p| p.exit_time.is_none()).count();
+    println!("{} {} -> {}", "Comparing".bright_green(), captures[0].0.display().to_string().bright_cyan(), captures[1].0.display().to_string().bright_cyan());
+    println!("  {:<18} {} -> {}", "Processes", running(old), running(new));
+    println!("  {:<18} {} -> {}", "Kernel modules", old.modules.len(), new.modules.len());
+    println!("  {:<18} {} -> {}", "Connections", old.connections.len(), new.connections.len());
+    println!("  {:<18} {} -> {}", "Findings", old.findings.len(), new.findings.len());
+
+    if diff.is_empty() {
+        println!("\n{}", "No changes between the captures".bright_green());
+        return Ok(());
+    }
+
+    if !diff.processes_created.is_empty() || !diff.processes_terminated.is_empty() {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(row![bFg->"Change", bFg->"PID", bFg->"PPID", bFg->"Name", bFg->"Started (UTC)", bFg->"Exited (UTC)"]);
+        let time = |time: SystemTime| chrono::DateTime::<chrono::Utc>::from(time).format("%Y-%m-%d %H:%M:%S").to_string();
+        for (change, processes) in [("created", &diff.processes_created), ("terminated", &diff.processes_terminated)] {
+            for p in processes {
+                let change = if change == "created" { change.bright_green() } else { change.bright_red() };
+                table.add_row(row![change, p.pid, p.ppid, Fy->p.name, time(p.start_time), p.exit_time.map_or("-".to_string(), time)]);
+            }
+        }
+        println!("\n{}", "Processes:".bright_green());
+        table.printstd();
+    }
+
+    let sections = [("Kernel modules:", "kernel_module"), ("Connections:", "connection"), ("Findings:", "finding"), ("DLLs:", "dll")];
+    let records = diff.records();
+    for (title, kind) in sections {
+        let changes: Vec<&DiffRecord> = records.iter().filter(|r| r.kind == kind).collect();
+        if changes.is_empty() {
+            continue;
+        }
+        println!("\n{}", title.bright_green());
+        for r in changes {
+            let change = if r.change == "+" { "+".bright_green() } else { "-".bright_red() };
+            let owner = match (kind, r.pid) {
+                ("dll", Some(pid)) => format!("{} (PID {}) ", r.name, pid),
+                ("kernel_module", _) => format!("{} ", r.name),
+                _ => String::new(),
+            };
+            let address = r.address.as_ref().map_or(String::new(), |addr| format!("{} ", addr));
+            println!("  {} {}{}{}", change, owner, address, r.description);
+        }
+    }
+
+    for (process, changes) in &diff.processes_changed {
+        for (start, size) in &changes.injected {
+            println!("{} {} (PID {}) 0x{:012X}-0x{:012X} ({} bytes of new code outside any module)",
+                "Injected:".bright_red(), process.name.bright_yellow(), process.pid, start, start + size, size);
+        }
+    }
+
+    Ok(())
+}
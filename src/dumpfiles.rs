@@ -0,0 +1,342 @@
+//! Cached file contents of Windows dumps
+//!
+//! Files a system had open are often still in memory when it is captured,
+//! including ones deleted from disk since. Every open file has a FILE_OBJECT
+//! in pool memory, found by pool tag, whose SectionObjectPointer leads to up
+//! to three copies of its contents:
+//!
+//! - the data section: a CONTROL_AREA whose subsections hold prototype PTEs
+//!   for the file's pages, in file order (`.dat`)
+//! - the shared cache map: the cache manager's 256 KiB views of the file,
+//!   each described by a VACB with its file offset (`.vacb`)
+//! - the image section: the file as mapped for execution (`.img`)
+//!
+//! Pages that were not resident are written as zeroes and counted in the
+//! manifest written next to the files. Files are written through
+//! [`ChunkedExtractor`], so a run that was interrupted continues where it
+//! stopped. Offsets are for Windows 10 x64.
+
+use anyhow::{Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use regex::Regex;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::extract::ChunkedExtractor;
+use crate::loader::load_memory_image;
+use crate::modules::{read_pages, unicode_string_at};
+use crate::paging::MemoryImage;
+use crate::processes::scan_pool_tags;
+
+/// Pool tags for FILE_OBJECT, with and without the protected-allocation bit
+const FILE_POOL_TAGS: [&[u8]; 2] = [b"File", b"Fil\xe5"];
+const POOL_HEADER_SIZE: usize = 0x10;
+const OBJECT_HEADER_SIZE: usize = 0x30;
+const MAX_OPTIONAL_HEADERS: usize = 0x90;
+
+/// FILE_OBJECT offsets
+const IO_TYPE_FILE: u16 = 5;
+const FILE_OBJECT_SIZE: usize = 0xD8;
+const FILE_SECTION_POINTERS: usize = 0x28;
+const FILE_NAME: usize = 0x58;
+
+/// SECTION_OBJECT_POINTERS offsets
+const DATA_SECTION_OBJECT: u64 = 0x0;
+const SHARED_CACHE_MAP: u64 = 0x8;
+const IMAGE_SECTION_OBJECT: u64 = 0x10;
+
+/// The first SUBSECTION follows its CONTROL_AREA
+const CONTROL_AREA_SIZE: u64 = 0x80;
+/// SUBSECTION offsets
+const SUBSECTION_CONTROL_AREA: u64 = 0x0;
+const SUBSECTION_BASE: u64 = 0x8;
+const SUBSECTION_NEXT: u64 = 0x10;
+const SUBSECTION_STARTING_SECTOR: u64 = 0x24;
+const SUBSECTION_PTES: u64 = 0x2C;
+const SECTOR_SIZE: u64 = 0x200;
+const MAX_SUBSECTIONS: usize = 0x1000;
+
+/// SHARED_CACHE_MAP offsets
+const CACHE_NTC_SHARED_CACHE_MAP: u16 = 0x2FF;
+const SCM_FILE_SIZE: u64 = 0x8;
+const SCM_SECTION_SIZE: u64 = 0x18;
+const SCM_VACBS: u64 = 0x68;
+/// VACB offsets; each VACB maps this much of the file
+const VACB_BASE_ADDRESS: u64 = 0x0;
+const VACB_SHARED_CACHE_MAP: u64 = 0x8;
+const VACB_FILE_OFFSET: u64 = 0x10;
+const VACB_MAPPING_GRANULARITY: u64 = 0x40000;
+
+/// PTE bits of a page that is resident or on a transition list
+const PTE_VALID: u64 = 1;
+const PTE_TRANSITION: u64 = 1 << 11;
+const PTE_PROTOTYPE: u64 = 1 << 10;
+const PTE_PFN_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Files larger than this are not reconstructed
+const MAX_FILE_SIZE: u64 = 1 << 30;
+const KERNEL_VA_START: u64 = 0xFFFF_8000_0000_0000;
+
+/// A FILE_OBJECT found in pool memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileObject {
+    /// Physical address of the object body
+    pub addr: u64,
+    pub name: String,
+    /// Virtual address of the SECTION_OBJECT_POINTERS, or 0
+    pub section_pointers: u64,
+}
+
+/// Validate and decode a FILE_OBJECT body at physical address `addr`
+fn parse_file_object(img: &MemoryImage, addr: usize) -> Option<FileObject> {
+    let body = img.get_bytes(addr, FILE_OBJECT_SIZE)?;
+    let u16_at = |off: usize| u16::from_le_bytes(body[off..off + 2].try_into().unwrap());
+    if u16_at(0) != IO_TYPE_FILE || u16_at(2) as usize != FILE_OBJECT_SIZE {
+        return None;
+    }
+    let section_pointers = u64::from_le_bytes(body[FILE_SECTION_POINTERS..FILE_SECTION_POINTERS + 8].try_into().unwrap());
+    if section_pointers != 0 && section_pointers < KERNEL_VA_START {
+        return None;
+    }
+    let name = unicode_string_at(img, body, FILE_NAME)?;
+    Some(FileObject { addr: addr as u64, name, section_pointers })
+}
+
+/// FILE_OBJECTs in pool memory with a name, in address order
+pub fn scan_file_objects(img: &MemoryImage, progress: &ProgressBar) -> Vec<FileObject> {
+    scan_pool_tags(img, progress, &FILE_POOL_TAGS).into_iter()
+        .filter_map(|(header, _)| {
+            let first = header as usize + POOL_HEADER_SIZE + OBJECT_HEADER_SIZE;
+            (0..=MAX_OPTIONAL_HEADERS).step_by(0x10).find_map(|extra| parse_file_object(img, first + extra))
+        })
+        .collect()
+}
+
+/// Which copy of a file's contents was reconstructed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSource {
+    DataSection,
+    SharedCacheMap,
+    ImageSection,
+}
+
+impl FileSource {
+    fn extension(&self) -> &'static str {
+        match self {
+            FileSource::DataSection => "dat",
+            FileSource::SharedCacheMap => "vacb",
+            FileSource::ImageSection => "img",
+        }
+    }
+}
+
+/// File contents rebuilt from memory, with non-resident pages zeroed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedFile {
+    pub source: FileSource,
+    pub data: Vec<u8>,
+    pub resident_pages: usize,
+}
+
+impl CachedFile {
+    pub fn total_pages(&self) -> usize {
+        self.data.len().div_ceil(0x1000)
+    }
+}
+
+/// Physical address of the page a prototype PTE refers to, when resident
+fn prototype_page(pte: u64) -> Option<u64> {
+    let resident = pte & PTE_VALID != 0 || (pte & PTE_TRANSITION != 0 && pte & PTE_PROTOTYPE == 0);
+    resident.then_some(pte & PTE_PFN_MASK)
+}
+
+/// Rebuild the file behind the CONTROL_AREA at `control_area` from the
+/// prototype PTEs of its subsections, at their file offsets
+fn read_control_area(img: &MemoryImage, control_area: u64, source: FileSource) -> Option<CachedFile> {
+    let mut pages: Vec<(u64, u64)> = Vec::new();
+    let mut subsection = control_area + CONTROL_AREA_SIZE;
+    for _ in 0..MAX_SUBSECTIONS {
+        if img.read_virt_u64(subsection + SUBSECTION_CONTROL_AREA) != Some(control_area) {
+            break;
+        }
+        let (Some(base), Some(ptes), Some(sector)) = (
+            img.read_virt_u64(subsection + SUBSECTION_BASE),
+            img.read_virt_u32(subsection + SUBSECTION_PTES),
+            img.read_virt_u32(subsection + SUBSECTION_STARTING_SECTOR),
+        ) else { break };
+        // Image sections are laid out as mapped, one subsection after another
+        let start = match source {
+            FileSource::ImageSection => pages.last().map_or(0, |&(offset, _)| offset + 0x1000),
+            _ => sector as u64 * SECTOR_SIZE,
+        };
+        for index in 0..ptes as u64 {
+            if start + index * 0x1000 >= MAX_FILE_SIZE {
+                break;
+            }
+            let pte = img.read_virt_u64(base + index * 8).unwrap_or(0);
+            pages.push((start + index * 0x1000, pte));
+        }
+        match img.read_virt_u64(subsection + SUBSECTION_NEXT) {
+            Some(next) if next != 0 => subsection = next,
+            _ => break,
+        }
+    }
+
+    let size = pages.iter().map(|&(offset, _)| offset + 0x1000).max()?;
+    let mut data = vec![0u8; size as usize];
+    let mut resident_pages = 0;
+    for (offset, pte) in pages {
+        let Some(bytes) = prototype_page(pte).and_then(|pa| img.get_bytes(pa as usize, 0x1000)) else { continue };
+        data[offset as usize..offset as usize + 0x1000].copy_from_slice(bytes);
+        resident_pages += 1;
+    }
+    (resident_pages > 0).then_some(CachedFile { source, data, resident_pages })
+}
+
+/// Rebuild a file from the views of its SHARED_CACHE_MAP at `scm`
+fn read_shared_cache_map(img: &MemoryImage, scm: u64) -> Option<CachedFile> {
+    if img.read_virt(scm, 2).map(|b| u16::from_le_bytes([b[0], b[1]])) != Some(CACHE_NTC_SHARED_CACHE_MAP) {
+        return None;
+    }
+    let file_size = img.read_virt_u64(scm + SCM_FILE_SIZE)?;
+    let section_size = img.read_virt_u64(scm + SCM_SECTION_SIZE)?.max(file_size);
+    if file_size == 0 || file_size > MAX_FILE_SIZE {
+        return None;
+    }
+    let vacbs = img.read_virt_u64(scm + SCM_VACBS)?;
+
+    let mut data = vec![0u8; file_size as usize];
+    let mut resident_pages = 0;
+    for index in 0..section_size.div_ceil(VACB_MAPPING_GRANULARITY) {
+        let Some(vacb) = img.read_virt_u64(vacbs + index * 8).filter(|&vacb| vacb != 0) else { continue };
+        if img.read_virt_u64(vacb + VACB_SHARED_CACHE_MAP) != Some(scm) {
+            continue;
+        }
+        let (Some(base), Some(offset)) = (img.read_virt_u64(vacb + VACB_BASE_ADDRESS), img.read_virt_u64(vacb + VACB_FILE_OFFSET)) else { continue };
+        // The low bits of the offset hold the VACB's active count
+        let offset = offset & !(VACB_MAPPING_GRANULARITY - 1);
+        if base == 0 || offset >= file_size {
+            continue;
+        }
+        let len = VACB_MAPPING_GRANULARITY.min(file_size - offset);
+        let (view, missing) = read_pages(img, base, len);
+        data[offset as usize..(offset + len) as usize].copy_from_slice(&view);
+        resident_pages += len.div_ceil(0x1000) as usize - missing;
+    }
+    (resident_pages > 0).then_some(CachedFile { source: FileSource::SharedCacheMap, data, resident_pages })
+}
+
+/// Every copy of the contents of `file` still in memory; the image must use
+/// the kernel DTB
+pub fn cached_files(img: &MemoryImage, file: &FileObject) -> Vec<CachedFile> {
+    if file.section_pointers == 0 {
+        return Vec::new();
+    }
+    let pointer = |offset: u64| img.read_virt_u64(file.section_pointers + offset).filter(|&p| p >= KERNEL_VA_START);
+    let mut files = Vec::new();
+    if let Some(cached) = pointer(DATA_SECTION_OBJECT).and_then(|ca| read_control_area(img, ca, FileSource::DataSection)) {
+        files.push(cached);
+    }
+    if let Some(cached) = pointer(SHARED_CACHE_MAP).and_then(|scm| read_shared_cache_map(img, scm)) {
+        files.push(cached);
+    }
+    if let Some(cached) = pointer(IMAGE_SECTION_OBJECT).and_then(|ca| read_control_area(img, ca, FileSource::ImageSection)) {
+        files.push(cached);
+    }
+    files
+}
+
+/// One file written by `dump_files`, as recorded in its manifest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManifestEntry {
+    pub file_object: String,
+    pub name: String,
+    pub source: FileSource,
+    pub path: PathBuf,
+    pub size: u64,
+    pub resident_pages: usize,
+    pub total_pages: usize,
+    pub sha256: String,
+}
+
+/// Name of the manifest `dump_files` writes into the output directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Write the cached contents of every file object whose name matches
+/// `pattern` into `output`, with a manifest; the image must use the kernel DTB
+pub fn dump_file_objects(img: &MemoryImage, pattern: Option<&Regex>, output: &Path, progress: &ProgressBar) -> Result<Vec<ManifestEntry>> {
+    fs::create_dir_all(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let objects: Vec<FileObject> = scan_file_objects(img, progress).into_iter()
+        .filter(|file| pattern.is_none_or(|p| p.is_match(&file.name)))
+        .collect();
+
+    let mut manifest = Vec::new();
+    for file in &objects {
+        let base_name = file.name.rsplit('\\').next().unwrap_or_default().replace(['/', ':'], "_");
+        for cached in cached_files(img, file) {
+            let path = output.join(format!("file.0x{:x}.{}.{}", file.addr, base_name, cached.source.extension()));
+            let summary = ChunkedExtractor::new(&path).write_all(&cached.data, &ProgressBar::hidden())
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            manifest.push(ManifestEntry {
+                file_object: format!("0x{:X}", file.addr),
+                name: file.name.clone(),
+                source: cached.source,
+                path,
+                size: cached.data.len() as u64,
+                resident_pages: cached.resident_pages,
+                total_pages: cached.total_pages(),
+                sha256: summary.sha256,
+            });
+        }
+    }
+    let manifest_path = output.join(MANIFEST_FILE);
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    Ok(manifest)
+}
+
+/// Reconstruct the files matching `pattern` from memory into `output`
+pub fn dump_files(dump_path: PathBuf, dtb: u64, pattern: Option<&str>, output: PathBuf) -> Result<()> {
+    let regex = pattern.map(|p| Regex::new(&format!("(?i){}", p)))
+        .transpose()
+        .context("Invalid --regex pattern")?;
+    let mut memory_image = load_memory_image(&dump_path)?;
+    memory_image.set_cr3(dtb);
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    progress.set_message("Scanning pool memory for file objects");
+    let manifest = dump_file_objects(&memory_image, regex.as_ref(), &output, &progress)?;
+    progress.finish_and_clear();
+    if manifest.is_empty() {
+        println!("{} {}", "No cached contents found for".bright_yellow(),
+            pattern.map_or("any file".to_string(), |p| format!("files matching {}", p)));
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"File Object", bFg->"Source", bFg->"Size", bFg->"Resident", bFg->"Name", bFg->"File"]);
+    for entry in &manifest {
+        table.add_row(row![
+            entry.file_object,
+            entry.path.extension().and_then(|e| e.to_str()).unwrap_or_default(),
+            format!("0x{:X}", entry.size),
+            format!("{}/{}", entry.resident_pages, entry.total_pages),
+            entry.name,
+            entry.path.display()
+        ]);
+    }
+    println!("{} {} files to {} (manifest: {})", "Wrote".bright_green(), manifest.len().to_string().bright_yellow(),
+        output.display().to_string().bright_cyan(), MANIFEST_FILE);
+    table.printstd();
+    let paths: Vec<PathBuf> = manifest.into_iter().map(|entry| entry.path).collect();
+    crate::actions::run_for_files(&dump_path, &paths);
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! Several memory images opened together
+//!
+//! `DumpSet` is the library entry point for batch and correlation work:
+//! images are loaded in parallel, per-image analysis results that several
+//! consumers need (KDBG location, freed memory map) are computed once and
+//! shared, and a plugin can be run across every image concurrently. The
+//! number of images worked on at once follows the set's `ResourceLimits`.
+
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::freed::FreedMemory;
+use crate::kdbg::OsContext;
+use crate::limits::{parallel_map, ResourceLimits};
+use crate::loader::load_memory_image;
+use crate::paging::MemoryImage;
+use crate::plugin::{get_plugin_registry, scan_with_provenance, Finding, MemoryPlugin};
+
+/// One image of a `DumpSet` with its lazily computed analysis state
+#[derive(Debug)]
+pub struct Dump {
+    pub path: PathBuf,
+    pub image: MemoryImage,
+    os: OnceLock<Option<OsContext>>,
+    freed: OnceLock<FreedMemory>,
+}
+
+impl Dump {
+    pub fn new(path: PathBuf, image: MemoryImage) -> Self {
+        Self { path, image, os: OnceLock::new(), freed: OnceLock::new() }
+    }
+
+    /// KDBG-derived kernel globals, located on first use
+    pub fn os_context(&self) -> Option<&OsContext> {
+        self.os.get_or_init(|| OsContext::find(&self.image, &ProgressBar::hidden())).as_ref()
+    }
+
+    /// Freed pool blocks and transition pages, scanned on first use
+    pub fn freed(&self) -> &FreedMemory {
+        self.freed.get_or_init(|| FreedMemory::scan(&self.image, &ProgressBar::hidden()))
+    }
+}
+
+/// Findings a plugin produced on one image of the set
+#[derive(Debug)]
+pub struct DumpFindings<'a> {
+    pub dump: &'a Dump,
+    pub findings: Vec<Finding>,
+}
+
+/// A set of memory images analysed together
+#[derive(Debug, Default)]
+pub struct DumpSet {
+    dumps: Vec<Dump>,
+    limits: ResourceLimits,
+}
+
+impl DumpSet {
+    /// Load every image in parallel, keeping the order of `paths`
+    pub fn open<P: AsRef<Path> + Sync>(paths: &[P]) -> Result<Self> {
+        Self::open_with_limits(paths, ResourceLimits::default())
+    }
+
+    /// Load every image in parallel within `limits`, keeping the order of `paths`
+    pub fn open_with_limits<P: AsRef<Path> + Sync>(paths: &[P], limits: ResourceLimits) -> Result<Self> {
+        let loaded = parallel_map(paths, limits.threads_for(paths.len()), |path| {
+            let path = path.as_ref().to_path_buf();
+            let image = load_memory_image(&path)
+                .with_context(|| format!("Failed to load {}", path.display()))?;
+            Ok(Dump::new(path, image))
+        });
+
+        Ok(Self { dumps: loaded.into_iter().collect::<Result<_>>()?, limits })
+    }
+
+    /// Limit the concurrency of later plugin runs
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Add an image that was loaded or configured by the caller
+    pub fn push(&mut self, path: PathBuf, image: MemoryImage) -> &mut Self {
+        self.dumps.push(Dump::new(path, image));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.dumps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dumps.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Dump> {
+        self.dumps.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Dump> {
+        self.dumps.iter()
+    }
+
+    /// Run one plugin on every image concurrently; results follow set order
+    /// and each image's findings carry their provenance and are sorted
+    pub fn run_plugin(&self, plugin: &dyn MemoryPlugin) -> Vec<DumpFindings<'_>> {
+        parallel_map(&self.dumps, self.limits.threads_for(self.dumps.len()), |dump| {
+            let findings = scan_with_provenance(plugin, &dump.image, &ProgressBar::hidden());
+            DumpFindings { dump, findings }
+        })
+    }
+
+    /// Run a plugin from the global registry by name
+    pub fn run_named(&self, name: &str) -> Result<Vec<DumpFindings<'_>>> {
+        let registry = get_plugin_registry();
+        let registry = registry.read().unwrap();
+        let plugin = registry.get(name).with_context(|| format!("Plugin '{}' not found", name))?;
+        Ok(self.run_plugin(plugin))
+    }
+}
+
+impl<'a> IntoIterator for &'a DumpSet {
+    type Item = &'a Dump;
+    type IntoIter = std::slice::Iter<'a, Dump>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.dumps.iter()
+    }
+}
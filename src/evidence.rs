@@ -0,0 +1,178 @@
+//! Evidence protection
+//!
+//! Dumps are evidence and rmf must never change them; compliance procedures
+//! want that enforced by the tool rather than promised by it. Every dump is
+//! opened through `open_evidence`, which opens it read-only, checks the
+//! access mode of the descriptor it got, and records the file's size and
+//! modification time, plus its SHA-256 when hashing is enabled.
+//! `verify_session` runs when a command finishes and fails if any dump it
+//! opened has changed since.
+//!
+//! With `require_readonly`, dumps the current user could write to are
+//! refused, so analysis only runs on write-protected copies or read-only
+//! mounts. Note that root can write to files whatever their mode bits.
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// How dumps are protected for the rest of the process
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EvidencePolicy {
+    require_readonly: bool,
+    verify_hash: bool,
+}
+
+static POLICY: Mutex<EvidencePolicy> = Mutex::new(EvidencePolicy { require_readonly: false, verify_hash: false });
+
+/// Dumps opened so far, checked again by `verify_session`
+static OPENED: Mutex<Vec<EvidenceRecord>> = Mutex::new(Vec::new());
+
+impl EvidencePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Refuse dumps the current user can write to
+    pub fn require_readonly(mut self, require: bool) -> Self {
+        self.require_readonly = require;
+        self
+    }
+
+    /// Hash each dump when it is opened and again at the end of the session
+    pub fn verify_hash(mut self, verify: bool) -> Self {
+        self.verify_hash = verify;
+        self
+    }
+
+    /// Apply the policy to every dump opened from now on
+    pub fn apply(self) {
+        *POLICY.lock().unwrap() = self;
+    }
+}
+
+/// State of a dump when it was opened
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvidenceRecord {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    /// Hex SHA-256, when hashing is enabled
+    pub sha256: Option<String>,
+}
+
+impl EvidenceRecord {
+    /// Record the current state of `path`, hashing it if `hash` is set
+    pub fn capture(path: &Path, hash: bool) -> Result<Self> {
+        let metadata = std::fs::metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(EvidenceRecord {
+            path: path.to_path_buf(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            sha256: if hash { Some(file_sha256(path)?) } else { None },
+        })
+    }
+
+    /// How the file differs from the recorded state; empty when unchanged
+    pub fn changes(&self) -> Vec<String> {
+        let current = match EvidenceRecord::capture(&self.path, self.sha256.is_some()) {
+            Ok(current) => current,
+            Err(_) => return vec!["the file can no longer be read".to_string()],
+        };
+        let mut changes = Vec::new();
+        if current.len != self.len {
+            changes.push(format!("size changed from {} to {} bytes", self.len, current.len));
+        }
+        if current.modified != self.modified {
+            changes.push("modification time changed".to_string());
+        }
+        if current.sha256 != self.sha256 {
+            changes.push(format!("SHA-256 changed from {} to {}",
+                self.sha256.as_deref().unwrap_or("-"), current.sha256.as_deref().unwrap_or("-")));
+        }
+        changes
+    }
+}
+
+/// Hex SHA-256 of a file's contents
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Whether the current user could open `path` for writing
+#[cfg(unix)]
+pub fn is_writable(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else { return true };
+    // SAFETY: access only reads the NUL-terminated path
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_writable(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|m| !m.permissions().readonly())
+}
+
+/// Fail unless `file` was opened without write access
+#[cfg(unix)]
+fn assert_read_only(file: &File, path: &Path) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+    // SAFETY: F_GETFL only reads the flags of a descriptor we own
+    let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+    if flags < 0 || flags & libc::O_ACCMODE != libc::O_RDONLY {
+        bail!("{} was not opened read-only; refusing to use it as evidence", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn assert_read_only(_file: &File, _path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Open a dump read-only under the current policy and record its state
+pub fn open_evidence(path: &Path) -> Result<File> {
+    let policy = *POLICY.lock().unwrap();
+    if policy.require_readonly && is_writable(path) {
+        bail!("{} is writable and --require-readonly is set; analyse a write-protected copy or a read-only mount",
+            path.display());
+    }
+    let file = OpenOptions::new().read(true).open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    assert_read_only(&file, path)?;
+
+    let mut opened = OPENED.lock().unwrap();
+    if !opened.iter().any(|record| record.path == path) {
+        opened.push(EvidenceRecord::capture(path, policy.verify_hash)?);
+    }
+    Ok(file)
+}
+
+/// Check every dump opened this session against its recorded state
+pub fn verify_session() -> Result<Vec<EvidenceRecord>> {
+    let opened = OPENED.lock().unwrap().clone();
+    let changed: Vec<String> = opened.iter()
+        .filter_map(|record| {
+            let changes = record.changes();
+            (!changes.is_empty()).then(|| format!("{}: {}", record.path.display(), changes.join(", ")))
+        })
+        .collect();
+    if !changed.is_empty() {
+        bail!("Evidence changed during analysis:\n  {}", changed.join("\n  "));
+    }
+    Ok(opened)
+}
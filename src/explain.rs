@@ -0,0 +1,350 @@
+//! Field-by-field explanations of well-known structures
+//!
+//! `rmf explain` looks up the structure an address falls in and prints
+//! every field with its offset, decoded value and what it is for, marking
+//! the field holding the address. It is meant for teaching: point it at an
+//! EPROCESS from `list-procs`, a module base from `modules` or an entry of
+//! a page walk from `translate` and it shows what the bytes mean.
+//!
+//! Each structure is a `KnownStructure` in `KNOWN_STRUCTURES` with a
+//! function that recognizes it around a physical address.
+
+use anyhow::Result;
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use std::path::PathBuf;
+
+use crate::kdbg::OsContext;
+use crate::loader::load_memory_image;
+use crate::paging::{Architecture, MemoryImage};
+use crate::processes::{merge_remnants, windows_finder, Process, ProcessFinder, WindowsProcessFinder};
+
+/// How the bytes of a field are decoded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FieldKind {
+    U16,
+    U32,
+    U64,
+    Pointer,
+    /// `LIST_ENTRY` of two pointers
+    ListEntry,
+    /// `EX_FAST_REF`: a pointer with a reference count in its low 4 bits
+    FastRef,
+    /// 100ns intervals since 1601
+    FileTime,
+    /// NUL-padded ASCII of the given length
+    Ascii(usize),
+}
+
+impl FieldKind {
+    fn size(self) -> usize {
+        match self {
+            FieldKind::U16 => 2,
+            FieldKind::U32 => 4,
+            FieldKind::U64 | FieldKind::Pointer | FieldKind::FastRef | FieldKind::FileTime => 8,
+            FieldKind::ListEntry => 16,
+            FieldKind::Ascii(len) => len,
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> String {
+        let int = |range: std::ops::Range<usize>| bytes[range].iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64);
+        match self {
+            FieldKind::U16 | FieldKind::U32 | FieldKind::U64 => {
+                let value = int(0..bytes.len());
+                format!("0x{:X} ({})", value, value)
+            }
+            FieldKind::Pointer => format!("0x{:X}", int(0..8)),
+            FieldKind::ListEntry => format!("Flink 0x{:X}, Blink 0x{:X}", int(0..8), int(8..16)),
+            FieldKind::FastRef => format!("object 0x{:X}, refs {}", int(0..8) & !0xF, int(0..8) & 0xF),
+            FieldKind::FileTime => match int(0..8) {
+                0 => "0 (not set)".to_string(),
+                ticks => chrono::DateTime::from_timestamp((ticks / 10_000_000) as i64 - 11_644_473_600, 0)
+                    .map_or_else(|| format!("0x{:X}", ticks), |t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+            },
+            FieldKind::Ascii(_) => {
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                format!("\"{}\"", String::from_utf8_lossy(&bytes[..end]))
+            }
+        }
+    }
+}
+
+/// A field of a structure layout
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StructField {
+    pub(crate) name: &'static str,
+    pub(crate) offset: usize,
+    pub(crate) kind: FieldKind,
+    pub(crate) about: &'static str,
+}
+
+impl StructField {
+    pub(crate) fn new(name: &'static str, offset: usize, kind: FieldKind, about: &'static str) -> Self {
+        StructField { name, offset, kind, about }
+    }
+}
+
+/// One decoded field of an explained structure
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplainedField {
+    /// Offset from the start of the structure
+    pub offset: u64,
+    pub size: u64,
+    pub name: String,
+    pub value: String,
+    pub about: String,
+}
+
+/// A structure found around an address, decoded field by field
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// Structure name, e.g. `EPROCESS`
+    pub structure: &'static str,
+    /// What this instance is, e.g. the process it describes
+    pub title: String,
+    /// Physical address of the structure
+    pub base: u64,
+    pub fields: Vec<ExplainedField>,
+}
+
+impl Explanation {
+    /// Decode `fields` of the structure at physical address `base`; fields
+    /// outside the image are left out
+    fn decode(img: &MemoryImage, structure: &'static str, title: String, base: u64, fields: &[StructField]) -> Self {
+        let fields = fields.iter().filter_map(|field| {
+            let size = field.kind.size();
+            let bytes = img.get_bytes(base as usize + field.offset, size)?;
+            Some(ExplainedField {
+                offset: field.offset as u64,
+                size: size as u64,
+                name: field.name.to_string(),
+                value: field.kind.decode(bytes),
+                about: field.about.to_string(),
+            })
+        }).collect();
+        Explanation { structure, title, base, fields }
+    }
+
+    /// The field holding physical address `pa`
+    pub fn field_at(&self, pa: u64) -> Option<&ExplainedField> {
+        self.fields.iter().find(|f| (self.base + f.offset..self.base + f.offset + f.size).contains(&pa))
+    }
+}
+
+/// What `explain` can recognize an address against
+pub struct ExplainContext<'a> {
+    pub img: &'a MemoryImage,
+    pub finder: WindowsProcessFinder,
+    /// Processes found by walking the process list and scanning pool memory
+    pub processes: Vec<Process>,
+}
+
+/// A structure `explain` knows how to find and decode
+pub struct KnownStructure {
+    pub name: &'static str,
+    pub about: &'static str,
+    recognize: fn(&ExplainContext, u64) -> Option<Explanation>,
+}
+
+/// Every structure `explain` recognizes, tried in order
+pub const KNOWN_STRUCTURES: [KnownStructure; 3] = [
+    KnownStructure { name: "EPROCESS", about: "Windows kernel process object", recognize: explain_eprocess },
+    KnownStructure { name: "PE header", about: "DOS, NT and section headers of a loaded image", recognize: explain_pe_header },
+    KnownStructure { name: "Page table entry", about: "x86_64 PML4E, PDPTE, PDE or PTE under the DTB", recognize: explain_page_table_entry },
+];
+
+/// Every known structure holding physical address `pa`
+pub fn explain(ctx: &ExplainContext, pa: u64) -> Vec<Explanation> {
+    KNOWN_STRUCTURES.iter().filter_map(|known| (known.recognize)(ctx, pa)).collect()
+}
+
+fn explain_eprocess(ctx: &ExplainContext, pa: u64) -> Option<Explanation> {
+    let size = ctx.finder.eprocess_size() as u64;
+    let process = ctx.processes.iter().find(|p| (p.virtual_address..p.virtual_address + size).contains(&pa))?;
+    let title = format!("{} (PID {})", process.name, process.pid);
+    Some(Explanation::decode(ctx.img, "EPROCESS", title, process.virtual_address, &ctx.finder.eprocess_fields()))
+}
+
+fn explain_pe_header(ctx: &ExplainContext, pa: u64) -> Option<Explanation> {
+    let base = pa & !0xFFF;
+    let page = ctx.img.get_bytes(base as usize, 0x1000)?;
+    let u16_at = |off: usize| page.get(off..off + 2).map(|b| u16::from_le_bytes(b.try_into().unwrap()));
+    let u32_at = |off: usize| page.get(off..off + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+    if &page[..2] != b"MZ" {
+        return None;
+    }
+    let nt = u32_at(0x3C)? as usize;
+    if nt + 0x18 > page.len() || &page[nt..nt + 4] != b"PE\0\0" {
+        return None;
+    }
+    let optional = nt + 0x18;
+    let pe32_plus = u16_at(optional)? == 0x20B;
+    let sections = optional + u16_at(nt + 0x14)? as usize;
+    let section_count = u16_at(nt + 6)? as usize;
+
+    use FieldKind::*;
+    let mut fields = vec![
+        StructField::new("e_magic", 0, U16, "DOS signature \"MZ\""),
+        StructField::new("e_lfanew", 0x3C, U32, "Offset of the NT headers"),
+        StructField::new("Signature", nt, U32, "NT signature \"PE\\0\\0\""),
+        StructField::new("Machine", nt + 4, U16, "Target CPU: 0x8664 x64, 0x14C x86"),
+        StructField::new("NumberOfSections", nt + 6, U16, "Entries in the section table"),
+        StructField::new("TimeDateStamp", nt + 8, U32, "Link time, seconds since 1970"),
+        StructField::new("SizeOfOptionalHeader", nt + 0x14, U16, "Bytes between the file header and the section table"),
+        StructField::new("Characteristics", nt + 0x16, U16, "Image flags: 0x2 executable, 0x2000 DLL"),
+        StructField::new("Magic", optional, U16, "Optional header format: 0x10B PE32, 0x20B PE32+"),
+        StructField::new("AddressOfEntryPoint", optional + 0x10, U32, "RVA where execution starts"),
+        if pe32_plus {
+            StructField::new("ImageBase", optional + 0x18, Pointer, "Preferred load address")
+        } else {
+            StructField::new("ImageBase", optional + 0x1C, U32, "Preferred load address")
+        },
+        StructField::new("SectionAlignment", optional + 0x20, U32, "Alignment of sections in memory"),
+        StructField::new("SizeOfImage", optional + 0x38, U32, "Bytes the loaded image spans"),
+        StructField::new("SizeOfHeaders", optional + 0x3C, U32, "Bytes of headers before the first section"),
+        StructField::new("Subsystem", optional + 0x44, U16, "2 GUI, 3 console, 1 native (drivers)"),
+        StructField::new("DllCharacteristics", optional + 0x46, U16, "Mitigations: 0x40 ASLR, 0x100 DEP"),
+    ];
+    for index in 0..section_count.min(16) {
+        fields.push(StructField::new("Section Name", sections + index * 40, Ascii(8), "Section header: name, then size and RVA"));
+    }
+    let title = format!("image header at physical 0x{:X}", base);
+    Some(Explanation::decode(ctx.img, "PE header", title, base, &fields))
+}
+
+/// The page table under the DTB containing the page at `target`: its level
+/// (4 for the PML4) and the first virtual address it maps
+fn find_table(img: &MemoryImage, table: u64, level: u32, va_base: u64, target: u64, seen: &mut std::collections::HashSet<u64>) -> Option<(u32, u64)> {
+    if table == target {
+        return Some((level, va_base));
+    }
+    if level == 1 || !seen.insert(table) {
+        return None;
+    }
+    let entries = img.get_bytes(table as usize, 0x1000)?;
+    let shift = 12 + 9 * (level - 1);
+    entries.chunks_exact(8).enumerate().find_map(|(index, entry)| {
+        let entry = u64::from_le_bytes(entry.try_into().unwrap());
+        // Only present entries pointing at a lower table, not at a large page
+        if entry & 1 == 0 || (level < 4 && entry & 0x80 != 0) {
+            return None;
+        }
+        let mut va = va_base | (index as u64) << shift;
+        if level == 4 && index >= 256 {
+            va |= 0xFFFF_0000_0000_0000;
+        }
+        find_table(img, entry & 0x000F_FFFF_FFFF_F000, level - 1, va, target, seen)
+    })
+}
+
+fn explain_page_table_entry(ctx: &ExplainContext, pa: u64) -> Option<Explanation> {
+    let dtb = ctx.img.info.dtb.filter(|_| ctx.img.info.arch == Architecture::X86_64)? & 0x000F_FFFF_FFFF_F000;
+    let (level, va_base) = find_table(ctx.img, dtb, 4, 0, pa & !0xFFF, &mut std::collections::HashSet::new())?;
+    let entry_addr = pa & !7;
+    let index = (entry_addr & 0xFFF) / 8;
+    let value = ctx.img.read_u64(entry_addr as usize)?;
+    let shift = 12 + 9 * (level - 1);
+    let mut va = va_base | index << shift;
+    if level == 4 && index >= 256 {
+        va |= 0xFFFF_0000_0000_0000;
+    }
+    let name = ["PTE", "PDE", "PDPTE", "PML4E"][level as usize - 1];
+    let large = (level == 2 || level == 3) && value & 0x80 != 0;
+    let bit = |n: u32| (value >> n & 1).to_string();
+    // Each row covers the byte of the entry holding its bits
+    let rows: [(&str, String, &str); 11] = [
+        ("P (bit 0)", bit(0), "Present; clear means any access faults"),
+        ("RW (bit 1)", bit(1), "Writable"),
+        ("US (bit 2)", bit(2), "User-mode code may access it"),
+        ("PWT (bit 3)", bit(3), "Write-through caching"),
+        ("PCD (bit 4)", bit(4), "Caching disabled"),
+        ("A (bit 5)", bit(5), "Accessed since the OS last cleared it"),
+        ("D (bit 6)", bit(6), "Dirty: written to (leaf entries)"),
+        (if level == 1 { "PAT (bit 7)" } else { "PS (bit 7)" }, bit(7),
+            if level == 1 { "Page attribute table index" } else { "Maps a large page instead of a table" }),
+        ("G (bit 8)", bit(8), "Global: kept in the TLB across CR3 switches"),
+        ("Frame (bits 12-51)", format!("0x{:X}", value & 0x000F_FFFF_FFFF_F000),
+            if level == 1 || large { "Physical page mapped" } else { "Physical address of the next table" }),
+        ("NX (bit 63)", bit(63), "No-execute"),
+    ];
+    let bytes = [(0, 1), (0, 1), (0, 1), (0, 1), (0, 1), (0, 1), (0, 1), (0, 1), (1, 1), (1, 6), (7, 1)];
+    let fields = rows.into_iter().zip(bytes).map(|((field, value, about), (offset, size))| ExplainedField {
+        offset,
+        size,
+        name: field.to_string(),
+        value,
+        about: about.to_string(),
+    }).collect();
+    Some(Explanation {
+        structure: "Page table entry",
+        title: format!("{} {} = 0x{:016X}, maps VA 0x{:X} ({} KB)", name, index, value, va, (1u64 << shift) / 1024),
+        base: entry_addr,
+        fields,
+    })
+}
+
+/// Print what lies at `address` in the dump: virtual when a DTB is given
+/// (unless `physical`), physical otherwise
+pub fn explain_address(dump_path: PathBuf, address: u64, dtb: Option<u64>, physical: bool) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    if let Some(dtb) = dtb {
+        memory_image.set_cr3(dtb);
+    }
+    let pa = match dtb {
+        Some(_) if !physical => {
+            let pa = memory_image.virt_to_phys(address)
+                .ok_or_else(|| anyhow::anyhow!("0x{:X} is not mapped under the DTB; use --physical for a physical address", address))?;
+            println!("{} 0x{:X} -> physical 0x{:X}", "Virtual address".bright_green(), address, pa);
+            pa
+        }
+        _ => address,
+    };
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    // Walk the process list when the kernel is found, and carve pool for the rest
+    let os = memory_image.info.dtb.and_then(|_| OsContext::find(&memory_image, &progress));
+    let (finder, mut processes) = match os {
+        Some(os) => {
+            let finder = windows_finder(&memory_image, os, None);
+            let processes = finder.find_processes(&memory_image, &progress).unwrap_or_default();
+            (finder, processes)
+        }
+        None => (WindowsProcessFinder::new(), Vec::new()),
+    };
+    merge_remnants(&mut processes, finder.scan_remnants(&memory_image, &progress)?);
+    progress.finish_and_clear();
+
+    let ctx = ExplainContext { img: &memory_image, finder, processes };
+    let explanations = explain(&ctx, pa);
+    if explanations.is_empty() {
+        println!("{} physical 0x{:X} is not inside a known structure. Known structures:", "Unrecognized:".bright_red(), pa);
+        for known in &KNOWN_STRUCTURES {
+            println!("  {:<18} {}", known.name.bright_yellow(), known.about);
+        }
+        return Ok(());
+    }
+
+    for explanation in &explanations {
+        println!("\n{} {} at physical 0x{:X}: {}", "Structure:".bright_green(), explanation.structure.bright_yellow(),
+            explanation.base, explanation.title);
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(row![bFg->"", bFg->"Offset", bFg->"Field", bFg->"Value", bFg->"Description"]);
+        let holding = explanation.field_at(pa);
+        for field in &explanation.fields {
+            let offset = format!("+0x{:X}", field.offset);
+            if holding == Some(field) {
+                table.add_row(row![bFy->"=>", bFy->offset, bFy->field.name, bFy->field.value, field.about]);
+            } else {
+                table.add_row(row!["", offset, field.name, field.value, field.about]);
+            }
+        }
+        table.printstd();
+    }
+    Ok(())
+}
@@ -0,0 +1,187 @@
+//! Chunked, resumable extraction of large memory regions
+//!
+//! Extraction commands write their output through `ChunkedExtractor`, which
+//! copies a region in fixed-size chunks and records a SHA-256 per chunk in a
+//! sidecar `<output>.part.json` manifest after each chunk reaches disk. If a
+//! run is interrupted (a network-mounted dump going away is the usual
+//! cause), the next run re-verifies the chunks already written against the
+//! manifest and continues from the first one that is missing or damaged.
+//! On success the manifest is renamed to `<output>.chunks.json` so the
+//! output can be re-verified later; running the command again skips outputs
+//! that still match it.
+
+use anyhow::{bail, Context, Result};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Default chunk size: large enough to keep manifests small, small enough to lose little on resume
+pub const DEFAULT_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Per-output record of the chunks written so far
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    pub total_len: u64,
+    pub chunk_size: u64,
+    /// Hex SHA-256 of each completed chunk, in order
+    pub chunks: Vec<String>,
+}
+
+/// Result of an extraction run
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractSummary {
+    pub bytes_written: u64,
+    pub chunks_written: usize,
+    pub chunks_resumed: usize,
+    pub sha256: String,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn sidecar(output: &Path, suffix: &str) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Writes a region to disk chunk by chunk, resuming interrupted runs
+pub struct ChunkedExtractor {
+    output: PathBuf,
+    chunk_size: u64,
+}
+
+impl ChunkedExtractor {
+    pub fn new(output: impl Into<PathBuf>) -> Self {
+        Self { output: output.into(), chunk_size: DEFAULT_CHUNK_SIZE }
+    }
+
+    pub fn chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Path of the in-progress manifest
+    pub fn partial_manifest(&self) -> PathBuf {
+        sidecar(&self.output, ".part.json")
+    }
+
+    /// Path of the manifest kept next to a completed output
+    pub fn final_manifest(&self) -> PathBuf {
+        sidecar(&self.output, ".chunks.json")
+    }
+
+    /// Count the leading chunks of an earlier run that still match their checksums
+    fn verified_prefix(&self, manifest: &ChunkManifest) -> Result<usize> {
+        let mut file = match File::open(&self.output) {
+            Ok(file) => file,
+            Err(_) => return Ok(0),
+        };
+        let mut buf = vec![0u8; self.chunk_size as usize];
+        for (i, expected) in manifest.chunks.iter().enumerate() {
+            let len = self.chunk_size.min(manifest.total_len - i as u64 * self.chunk_size) as usize;
+            if file.read_exact(&mut buf[..len]).is_err() || sha256_hex(&buf[..len]) != *expected {
+                return Ok(i);
+            }
+        }
+        Ok(manifest.chunks.len())
+    }
+
+    fn save_manifest(&self, manifest: &ChunkManifest) -> Result<()> {
+        // Write then rename so a crash never leaves a truncated manifest
+        let part = self.partial_manifest();
+        let tmp = sidecar(&part, ".tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(manifest)?)?;
+        fs::rename(&tmp, &part)?;
+        Ok(())
+    }
+
+    /// Copy `total_len` bytes produced by `read_chunk(offset, len)` to the output
+    ///
+    /// `read_chunk` must return exactly `len` bytes; unreadable memory should be
+    /// zero-filled by the caller so offsets stay stable across runs.
+    pub fn extract<F>(&self, total_len: u64, progress: &ProgressBar, mut read_chunk: F) -> Result<ExtractSummary>
+    where
+        F: FnMut(u64, usize) -> Result<Vec<u8>>,
+    {
+        let mut manifest = ChunkManifest { total_len, chunk_size: self.chunk_size, chunks: Vec::new() };
+
+        // Pick up where an earlier run with the same geometry stopped, or
+        // keep an output an earlier run completed
+        let mut resumed = 0;
+        let previous = [self.partial_manifest(), self.final_manifest()].into_iter()
+            .find_map(|path| fs::read(&path).ok().map(|data| (path, data)));
+        if let Some((path, data)) = previous {
+            let previous: ChunkManifest = serde_json::from_slice(&data)
+                .with_context(|| format!("Corrupt extraction manifest {}", path.display()))?;
+            if previous.total_len == total_len && previous.chunk_size == self.chunk_size {
+                resumed = self.verified_prefix(&previous)?;
+                manifest.chunks = previous.chunks[..resumed].to_vec();
+            }
+        }
+
+        let mut file = OpenOptions::new().create(true).write(true).truncate(false).open(&self.output)
+            .with_context(|| format!("Failed to open {}", self.output.display()))?;
+        let start = (resumed as u64 * self.chunk_size).min(total_len);
+        file.set_len(start)?;
+        file.seek(SeekFrom::Start(start))?;
+
+        progress.set_length(total_len);
+        progress.set_position(start);
+        if resumed > 0 {
+            progress.set_message(format!("Resuming {} after {} verified chunks", self.output.display(), resumed));
+        }
+
+        let mut offset = start;
+        while offset < total_len {
+            let len = self.chunk_size.min(total_len - offset) as usize;
+            let data = read_chunk(offset, len)
+                .with_context(|| format!("Failed to read chunk at offset 0x{:X}", offset))?;
+            if data.len() != len {
+                bail!("Short read at offset 0x{:X}: expected {} bytes, got {}", offset, len, data.len());
+            }
+
+            file.write_all(&data)?;
+            file.sync_data()?;
+            manifest.chunks.push(sha256_hex(&data));
+            self.save_manifest(&manifest)?;
+
+            offset += len as u64;
+            progress.set_position(offset);
+        }
+
+        // Hash the finished file so callers can record it alongside their evidence
+        let mut hasher = Sha256::new();
+        let mut reader = File::open(&self.output)?;
+        let mut buf = vec![0u8; 1 << 20];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        let sha256: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+
+        if !self.partial_manifest().exists() {
+            self.save_manifest(&manifest)?;
+        }
+        fs::rename(self.partial_manifest(), self.final_manifest())?;
+        progress.finish_with_message(format!("Wrote {} bytes to {}", total_len, self.output.display()));
+
+        Ok(ExtractSummary {
+            bytes_written: total_len - start,
+            chunks_written: manifest.chunks.len() - resumed,
+            chunks_resumed: resumed,
+            sha256,
+        })
+    }
+    /// Write `data` already in memory chunk by chunk
+    pub fn write_all(&self, data: &[u8], progress: &ProgressBar) -> Result<ExtractSummary> {
+        self.extract(data.len() as u64, progress, |offset, len| Ok(data[offset as usize..offset as usize + len].to_vec()))
+    }
+}
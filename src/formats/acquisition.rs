@@ -0,0 +1,156 @@
+//! Acquisition metadata recorded in dump headers
+//!
+//! Crash dumps record when the system crashed and hibernation files when
+//! it went to sleep; minidumps carry the time they were written. These
+//! times anchor a timeline: process start times and file timestamps found
+//! in memory can be read relative to the moment memory was captured.
+//! Acquisition tools that wrap the dump (LiME, AFF4 imagers) or leave
+//! their name in its first pages are reported as well; `loader` adds the
+//! VirtualBox version of `dumpvmcore` cores.
+//!
+//! Only the leading bytes of the dump file are read.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Bytes at the start of the dump searched for tool markers
+const MARKER_WINDOW: usize = 0x10000;
+
+/// Names acquisition tools leave near the start of their output
+const TOOL_MARKERS: [(&[u8], &str); 6] = [
+    (b"winpmem", "WinPmem"),
+    (b"WinPmem", "WinPmem"),
+    (b"DumpIt", "DumpIt"),
+    (b"Magnet RAM Capture", "Magnet RAM Capture"),
+    (b"FTK Imager", "FTK Imager"),
+    (b"Belkasoft", "Belkasoft RAM Capturer"),
+];
+
+/// What an acquisition timestamp marks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeAnchor {
+    /// `SystemTime` of a crash dump header
+    CrashTime,
+    /// `SystemTime` of a hibernation file header
+    HibernationTime,
+    /// `TimeDateStamp` of a minidump header
+    DumpWritten,
+}
+
+impl fmt::Display for TimeAnchor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeAnchor::CrashTime => "Crash time",
+            TimeAnchor::HibernationTime => "Hibernation time",
+            TimeAnchor::DumpWritten => "Dump written",
+        })
+    }
+}
+
+/// One timestamp recorded by the dump
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AcquisitionTime {
+    pub anchor: TimeAnchor,
+    /// RFC 3339, UTC
+    pub time: String,
+}
+
+/// Where a dump came from and when memory was captured
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AcquisitionMetadata {
+    /// Container identified from the header, e.g. `Windows crash dump (64-bit)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    /// Acquisition tool whose header or marker was found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub times: Vec<AcquisitionTime>,
+    /// Bug check code and parameters of a crash dump
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bugcheck: Option<String>,
+}
+
+impl AcquisitionMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == AcquisitionMetadata::default()
+    }
+
+    /// The time memory was captured, if the dump records one
+    pub fn captured_at(&self) -> Option<&AcquisitionTime> {
+        self.times.first()
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// RFC 3339 form of a FILETIME (100ns intervals since 1601); `None` when unset
+pub fn filetime_to_rfc3339(filetime: u64) -> Option<String> {
+    let seconds = (filetime / 10_000_000).checked_sub(11_644_473_600)?;
+    unix_to_rfc3339(seconds)
+}
+
+fn unix_to_rfc3339(seconds: u64) -> Option<String> {
+    let time = chrono::DateTime::from_timestamp(i64::try_from(seconds).ok()?, 0)?;
+    Some(time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+/// Read the acquisition metadata from the start of a dump file
+pub fn parse(data: &[u8]) -> AcquisitionMetadata {
+    let mut metadata = AcquisitionMetadata::default();
+    let time = |anchor, time: Option<String>| time.map(|time| AcquisitionTime { anchor, time });
+
+    match data.get(..8) {
+        // DUMP_HEADER64: BugCheckCode at 0x38, parameters after it, SystemTime at 0xFA8
+        Some(b"PAGEDU64") => {
+            metadata.times.extend(time(TimeAnchor::CrashTime, u64_at(data, 0xFA8).and_then(filetime_to_rfc3339)));
+            metadata.container = Some("Windows crash dump (64-bit)".to_string());
+            metadata.bugcheck = u32_at(data, 0x38).map(|code| {
+                let params: Vec<String> = (0..4).filter_map(|i| u64_at(data, 0x40 + i * 8)).map(|p| format!("0x{:X}", p)).collect();
+                format!("0x{:X} ({})", code, params.join(", "))
+            });
+        }
+        // DUMP_HEADER32: BugCheckCode at 0x20
+        Some(b"PAGEDUMP") => {
+            metadata.container = Some("Windows crash dump (32-bit)".to_string());
+            metadata.bugcheck = u32_at(data, 0x20).map(|code| format!("0x{:X}", code));
+        }
+        // PO_MEMORY_IMAGE: SystemTime at 0x20 on x64
+        Some(header) if [b"hibr", b"HIBR", b"wake", b"WAKE", b"RSTR"].iter().any(|sig| header.starts_with(*sig)) => {
+            metadata.times.extend(time(TimeAnchor::HibernationTime, u64_at(data, 0x20).and_then(filetime_to_rfc3339)));
+            let resumed = header[..4].eq_ignore_ascii_case(b"wake");
+            metadata.container = Some(if resumed { "Windows hibernation file (resumed)" } else { "Windows hibernation file" }.to_string());
+        }
+        // MINIDUMP_HEADER: TimeDateStamp at 0x10, seconds since 1970
+        Some(header) if header.starts_with(b"MDMP") => {
+            metadata.times.extend(time(TimeAnchor::DumpWritten, u32_at(data, 0x10).filter(|&t| t != 0).and_then(|t| unix_to_rfc3339(t as u64))));
+            metadata.container = Some("Windows minidump".to_string());
+        }
+        // lime_mem_range_header, magic 0x4C694D45
+        Some(header) if header.starts_with(b"EMiL") => {
+            metadata.container = Some("LiME".to_string());
+            metadata.tool = Some("LiME".to_string());
+        }
+        Some(header) if header.starts_with(b"PK\x03\x04") && contains(&data[..data.len().min(MARKER_WINDOW)], b"aff4") => {
+            metadata.container = Some("AFF4 image".to_string());
+        }
+        _ => {}
+    }
+
+    if metadata.tool.is_none() {
+        let window = &data[..data.len().min(MARKER_WINDOW)];
+        metadata.tool = TOOL_MARKERS.iter().find(|(marker, _)| contains(window, marker)).map(|(_, tool)| tool.to_string());
+    }
+    metadata
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
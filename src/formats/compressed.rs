@@ -0,0 +1,94 @@
+//! Transparent decompression of compressed memory dumps
+//!
+//! Acquisition tools commonly ship gzip, zstd or lz4 compressed images. The
+//! stream is inflated into an unlinked temporary file which is then memory
+//! mapped, so the rest of the toolkit sees an ordinary dump.
+
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+
+/// Compression wrappers recognised by the loader
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Lz4 => "lz4",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+const LZ4_FRAME_MAGIC: &[u8] = &[0x04, 0x22, 0x4D, 0x18];
+
+/// Identify a compression wrapper from the first bytes of a file
+pub fn detect(header: &[u8]) -> Option<Compression> {
+    if header.starts_with(ZSTD_MAGIC) {
+        Some(Compression::Zstd)
+    } else if header.starts_with(LZ4_FRAME_MAGIC) {
+        Some(Compression::Lz4)
+    } else if header.starts_with(GZIP_MAGIC) {
+        Some(Compression::Gzip)
+    } else {
+        None
+    }
+}
+
+/// Read the magic bytes of an open file and rewind it
+pub fn detect_file(file: &mut File) -> Result<Option<Compression>> {
+    let mut header = [0u8; 4];
+    let read = file.read(&mut header)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(detect(&header[..read]))
+}
+
+/// Reader adapter reporting compressed bytes consumed to a progress bar
+struct ProgressReader<R> {
+    inner: R,
+    progress: ProgressBar,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.inc(n as u64);
+        Ok(n)
+    }
+}
+
+/// Stream-decompress `file` into an unlinked temporary file (honours `TMPDIR`)
+pub fn decompress(file: File, compression: Compression, progress: &ProgressBar) -> Result<File> {
+    let compressed_len = file.metadata()?.len();
+    progress.set_length(compressed_len);
+    progress.set_position(0);
+    progress.set_message(format!("Decompressing {} dump", compression));
+
+    let reader = BufReader::new(ProgressReader { inner: file, progress: progress.clone() });
+    let mut output = tempfile::tempfile().context("Failed to create temporary file for decompression")?;
+
+    let written = match compression {
+        Compression::Gzip => io::copy(&mut flate2::read::MultiGzDecoder::new(reader), &mut output),
+        #[cfg(not(target_arch = "wasm32"))]
+        Compression::Zstd => io::copy(&mut zstd::stream::read::Decoder::with_buffer(reader)?, &mut output),
+        #[cfg(target_arch = "wasm32")]
+        Compression::Zstd => anyhow::bail!("zstd decompression is not available on this target"),
+        Compression::Lz4 => io::copy(&mut lz4_flex::frame::FrameDecoder::new(reader), &mut output),
+    }
+    .with_context(|| format!("Failed to decompress {} stream", compression))?;
+
+    output.sync_all()?;
+    progress.set_message(format!("Decompressed {} bytes", written));
+    Ok(output)
+}
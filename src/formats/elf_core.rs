@@ -0,0 +1,168 @@
+//! ELF core dump parsing (VirtualBox `dumpvmcore` and generic physical cores)
+//!
+//! VirtualBox writes an ELF64 `ET_CORE` file whose `PT_LOAD` segments carry
+//! guest physical memory (`p_paddr`) and whose `PT_NOTE` segment holds a
+//! `VBCORE` descriptor followed by one `VBCPU` note per virtual CPU.
+
+use anyhow::{bail, Context, Result};
+use scroll::{Pread, LE};
+
+use crate::paging::{CpuState, PhysicalRun};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_CORE: u16 = 4;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+
+/// Note types written by DBGFCoreWrite
+const NT_VBOXCORE: u32 = 0xb00;
+const NT_VBOXCPU: u32 = 0xb01;
+/// `DBGFCOREDESCRIPTOR::u32Magic`
+const DBGFCORE_MAGIC: u32 = 0xc01a_c0de;
+
+/// Offsets inside `DBGFCORECPU`
+const CPU_RIP: usize = 0x80;
+const CPU_RSP: usize = 0x88;
+const CPU_CR0: usize = 0x130;
+const CPU_CR3: usize = 0x140;
+const CPU_CR4: usize = 0x148;
+
+/// `DBGFCOREDESCRIPTOR` contents
+#[derive(Debug, Clone, PartialEq)]
+pub struct VBoxCoreDescriptor {
+    pub format_version: u32,
+    pub vbox_version: u32,
+    pub vbox_revision: u32,
+    pub cpu_count: u32,
+}
+
+impl VBoxCoreDescriptor {
+    /// Render the packed VirtualBox version as `major.minor.build`
+    pub fn version_string(&self) -> String {
+        format!(
+            "{}.{}.{} r{}",
+            self.vbox_version >> 24,
+            (self.vbox_version >> 16) & 0xFF,
+            self.vbox_version & 0xFFFF,
+            self.vbox_revision
+        )
+    }
+}
+
+/// Result of parsing an ELF core file
+#[derive(Debug, Default)]
+pub struct ElfCore {
+    pub runs: Vec<PhysicalRun>,
+    pub cpus: Vec<CpuState>,
+    pub vbox: Option<VBoxCoreDescriptor>,
+}
+
+/// Check whether the data looks like a little-endian ELF64 core file
+pub fn is_elf_core(data: &[u8]) -> bool {
+    data.len() >= 0x40
+        && data.starts_with(ELF_MAGIC)
+        && data[4] == ELFCLASS64
+        && data[5] == ELFDATA2LSB
+        && data.pread_with::<u16>(0x10, LE).ok() == Some(ET_CORE)
+}
+
+fn align(value: usize, to: usize) -> usize {
+    (value + to - 1) & !(to - 1)
+}
+
+/// Parse the program headers and notes of an ELF64 core file
+pub fn parse(data: &[u8]) -> Result<ElfCore> {
+    if !is_elf_core(data) {
+        bail!("Not a little-endian ELF64 core file");
+    }
+
+    let phoff: u64 = data.pread_with(0x20, LE)?;
+    let phentsize: u16 = data.pread_with(0x36, LE)?;
+    let phnum: u16 = data.pread_with(0x38, LE)?;
+
+    let mut core = ElfCore::default();
+
+    for i in 0..phnum as usize {
+        let ph = (i as u64).checked_mul(phentsize as u64)
+            .and_then(|offset| offset.checked_add(phoff))
+            .and_then(|ph| data.get(usize::try_from(ph).ok()?..))
+            .with_context(|| format!("Program header {} lies outside the file", i))?;
+        let p_type: u32 = ph.pread_with(0, LE)
+            .with_context(|| format!("Truncated program header {}", i))?;
+        let p_offset: u64 = ph.pread_with(0x08, LE)?;
+        let p_paddr: u64 = ph.pread_with(0x18, LE)?;
+        let p_filesz: u64 = ph.pread_with(0x20, LE)?;
+
+        match p_type {
+            PT_LOAD if p_offset.checked_add(p_filesz).is_none() || p_paddr.checked_add(p_filesz).is_none() => {
+                bail!("Program header {} describes a segment past the end of the address space", i);
+            }
+            PT_LOAD if p_filesz > 0 => core.runs.push(PhysicalRun {
+                start: p_paddr,
+                length: p_filesz,
+                file_offset: p_offset,
+                segment: 0,
+            }),
+            PT_NOTE => {
+                let start = p_offset as usize;
+                let end = start.saturating_add(p_filesz as usize).min(data.len());
+                parse_notes(&data[start.min(end)..end], &mut core);
+            }
+            _ => {}
+        }
+    }
+
+    if core.runs.is_empty() {
+        bail!("ELF core contains no PT_LOAD segments");
+    }
+
+    Ok(core)
+}
+
+/// Walk the note segment collecting the VirtualBox descriptor and CPU states
+fn parse_notes(notes: &[u8], core: &mut ElfCore) {
+    let mut offset = 0;
+    while offset + 12 <= notes.len() {
+        let (Ok(namesz), Ok(descsz), Ok(note_type)) = (
+            notes.pread_with::<u32>(offset, LE),
+            notes.pread_with::<u32>(offset + 4, LE),
+            notes.pread_with::<u32>(offset + 8, LE),
+        ) else {
+            break;
+        };
+
+        // DBGFCoreWrite stores n_namesz without the terminator and pads both
+        // name and descriptor to 8 bytes
+        let name_len = align(namesz as usize + 1, 8);
+        let desc_start = offset + 12 + name_len;
+        let Some(desc) = notes.get(desc_start..desc_start + descsz as usize) else {
+            break;
+        };
+
+        match note_type {
+            NT_VBOXCORE if desc.pread_with::<u32>(0, LE).ok() == Some(DBGFCORE_MAGIC) => {
+                core.vbox = Some(VBoxCoreDescriptor {
+                    format_version: desc.pread_with(4, LE).unwrap_or(0),
+                    vbox_version: desc.pread_with(12, LE).unwrap_or(0),
+                    vbox_revision: desc.pread_with(16, LE).unwrap_or(0),
+                    cpu_count: desc.pread_with(20, LE).unwrap_or(0),
+                });
+            }
+            NT_VBOXCPU if desc.len() >= CPU_CR4 + 8 => {
+                let reg = |off: usize| desc.pread_with::<u64>(off, LE).unwrap_or(0);
+                core.cpus.push(CpuState {
+                    rip: reg(CPU_RIP),
+                    rsp: reg(CPU_RSP),
+                    cr0: reg(CPU_CR0),
+                    cr3: reg(CPU_CR3),
+                    cr4: reg(CPU_CR4),
+                });
+            }
+            _ => {}
+        }
+
+        offset = desc_start + align(descsz as usize, 8);
+    }
+}
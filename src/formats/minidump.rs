@@ -0,0 +1,148 @@
+//! Microsoft minidump (`.dmp` written by `MiniDumpWriteDump`) parsing
+//!
+//! Memory comes from `Memory64ListStream` (full dumps, one contiguous data
+//! block) or `MemoryListStream` (per-range RVAs). Module and thread lists are
+//! decoded so the image can be analysed as a single user-mode process.
+
+use anyhow::{bail, Context, Result};
+use scroll::{Pread, LE};
+
+use crate::paging::PhysicalRun;
+use crate::usermode::{UserModule, UserRegion, UserSpace, UserThread};
+
+const MINIDUMP_SIGNATURE: &[u8] = b"MDMP";
+
+/// `MINIDUMP_STREAM_TYPE` values
+const THREAD_LIST_STREAM: u32 = 3;
+const MODULE_LIST_STREAM: u32 = 4;
+const MEMORY_LIST_STREAM: u32 = 5;
+const MEMORY64_LIST_STREAM: u32 = 9;
+
+const DIRECTORY_SIZE: usize = 12;
+const THREAD_SIZE: usize = 48;
+const MODULE_SIZE: usize = 108;
+const MEMORY_DESCRIPTOR_SIZE: usize = 16;
+
+/// `CONTEXT` register offsets for AMD64 and x86 threads
+const AMD64_CONTEXT_SIZE: u32 = 0x4d0;
+const AMD64_RSP: usize = 0x98;
+const AMD64_RIP: usize = 0xf8;
+const X86_EIP: usize = 0xb8;
+const X86_ESP: usize = 0xc4;
+
+/// Result of parsing a minidump
+#[derive(Debug, Default)]
+pub struct Minidump {
+    pub runs: Vec<PhysicalRun>,
+    pub user: UserSpace,
+}
+
+pub fn is_minidump(data: &[u8]) -> bool {
+    data.starts_with(MINIDUMP_SIGNATURE)
+}
+
+/// Read a `MINIDUMP_STRING` (u32 byte length followed by UTF-16LE)
+fn read_string(data: &[u8], rva: usize) -> Option<String> {
+    let len: u32 = data.pread_with(rva, LE).ok()?;
+    let bytes = data.get(rva + 4..rva + 4 + len as usize)?;
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+pub fn parse(data: &[u8]) -> Result<Minidump> {
+    if !is_minidump(data) {
+        bail!("Not a minidump");
+    }
+
+    let stream_count: u32 = data.pread_with(8, LE)?;
+    let directory: u32 = data.pread_with(12, LE)?;
+
+    let mut dump = Minidump::default();
+    let mut ranges: Vec<(u64, u64, u64)> = Vec::new(); // (va, size, file offset)
+
+    for i in 0..stream_count as usize {
+        let entry = directory as usize + i * DIRECTORY_SIZE;
+        let stream_type: u32 = data.pread_with(entry, LE)
+            .with_context(|| format!("Truncated stream directory entry {}", i))?;
+        let rva = data.pread_with::<u32>(entry + 8, LE)? as usize;
+
+        match stream_type {
+            MEMORY64_LIST_STREAM => {
+                let count: u64 = data.pread_with(rva, LE)?;
+                let mut file_offset: u64 = data.pread_with(rva + 8, LE)?;
+                for j in 0..count as usize {
+                    let desc = rva + 16 + j * 16;
+                    let va: u64 = data.pread_with(desc, LE)?;
+                    let size: u64 = data.pread_with(desc + 8, LE)?;
+                    if va.checked_add(size).is_none() {
+                        bail!("Memory descriptor {} describes a range past the end of the address space", j);
+                    }
+                    ranges.push((va, size, file_offset));
+                    file_offset = file_offset.checked_add(size)
+                        .with_context(|| format!("Memory descriptor {} runs past the end of the address space", j))?;
+                }
+            }
+            MEMORY_LIST_STREAM => {
+                let count: u32 = data.pread_with(rva, LE)?;
+                for j in 0..count as usize {
+                    let desc = rva + 4 + j * MEMORY_DESCRIPTOR_SIZE;
+                    let va: u64 = data.pread_with(desc, LE)?;
+                    let size: u32 = data.pread_with(desc + 8, LE)?;
+                    let offset: u32 = data.pread_with(desc + 12, LE)?;
+                    ranges.push((va, size as u64, offset as u64));
+                }
+            }
+            MODULE_LIST_STREAM => {
+                let count: u32 = data.pread_with(rva, LE)?;
+                for j in 0..count as usize {
+                    let module = rva + 4 + j * MODULE_SIZE;
+                    let name_rva: u32 = data.pread_with(module + 20, LE)?;
+                    dump.user.modules.push(UserModule {
+                        base: data.pread_with(module, LE)?,
+                        size: data.pread_with::<u32>(module + 8, LE)? as u64,
+                        timestamp: data.pread_with(module + 16, LE)?,
+                        name: read_string(data, name_rva as usize).unwrap_or_default(),
+                    });
+                }
+            }
+            THREAD_LIST_STREAM => {
+                let count: u32 = data.pread_with(rva, LE)?;
+                for j in 0..count as usize {
+                    let entry = rva + 4 + j * THREAD_SIZE;
+                    let mut thread = UserThread {
+                        id: data.pread_with(entry, LE)?,
+                        teb: data.pread_with(entry + 16, LE)?,
+                        stack_base: data.pread_with(entry + 24, LE)?,
+                        stack_size: data.pread_with::<u32>(entry + 32, LE)? as u64,
+                        ..Default::default()
+                    };
+                    let ctx_size: u32 = data.pread_with(entry + 40, LE)?;
+                    let ctx = data.pread_with::<u32>(entry + 44, LE)? as usize;
+                    if ctx_size >= AMD64_CONTEXT_SIZE {
+                        thread.rip = data.pread_with(ctx + AMD64_RIP, LE).unwrap_or(0);
+                        thread.rsp = data.pread_with(ctx + AMD64_RSP, LE).unwrap_or(0);
+                    } else {
+                        thread.rip = data.pread_with::<u32>(ctx + X86_EIP, LE).unwrap_or(0) as u64;
+                        thread.rsp = data.pread_with::<u32>(ctx + X86_ESP, LE).unwrap_or(0) as u64;
+                    }
+                    dump.user.threads.push(thread);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Pack the captured ranges back to back, ordered by virtual address
+    ranges.sort_by_key(|&(va, _, _)| va);
+    let mut packed = 0;
+    for (va, size, file_offset) in ranges {
+        if size == 0 || file_offset.checked_add(size).is_none_or(|end| end > data.len() as u64) {
+            continue;
+        }
+        dump.runs.push(PhysicalRun { start: packed, length: size, file_offset, segment: 0 });
+        dump.user.regions.push(UserRegion { va, offset: packed, size });
+        packed += size;
+    }
+
+    Ok(dump)
+}
@@ -0,0 +1,9 @@
+//! Parsers for memory dump container formats
+//!
+//! Each parser inspects the mapped dump file and describes how physical
+//! memory is laid out inside it, so `loader` can build a `MemoryImage`.
+
+pub mod acquisition;
+pub mod compressed;
+pub mod elf_core;
+pub mod minidump;
@@ -0,0 +1,186 @@
+//! Freed-memory (slack) detection
+//!
+//! Scanners walk all of physical memory, so they also hit data that the OS
+//! has already released. This module identifies such regions so findings in
+//! them can be left out by default or, with `--include-freed`, kept and
+//! tagged with their provenance.
+//!
+//! Two kinds of freed memory are recognised:
+//!
+//! * Free Windows pool blocks, found by walking the pool header chain of
+//!   every page and taking blocks whose `PoolType` is zero.
+//! * Pages referenced only by transition PTEs: no longer mapped, but their
+//!   contents are still resident in physical memory.
+
+use indicatif::ProgressBar;
+
+use crate::arch::x86_64::PAGE_SIZE;
+use crate::paging::{Architecture, MemoryImage};
+use crate::plugin::Finding;
+
+const POOL_HEADER_SIZE: usize = 0x10;
+
+/// Software bits of a non-present x86_64 Windows PTE
+const PTE_PRESENT: u64 = 1 << 0;
+const PTE_LARGE: u64 = 1 << 7;
+const PTE_PROTOTYPE: u64 = 1 << 10;
+const PTE_TRANSITION: u64 = 1 << 11;
+const PTE_FRAME_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Why a region is considered freed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FreedSource {
+    Pool,
+    Transition,
+}
+
+impl FreedSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FreedSource::Pool => "free_pool",
+            FreedSource::Transition => "transition_page",
+        }
+    }
+}
+
+/// A freed physical range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FreedRange {
+    pub start: u64,
+    pub end: u64,
+    pub source: FreedSource,
+}
+
+/// Freed regions of a memory image, sorted by start address
+#[derive(Debug, Default)]
+pub struct FreedMemory {
+    ranges: Vec<FreedRange>,
+}
+
+impl FreedMemory {
+    /// Locate free pool blocks and transition pages in the image
+    pub fn scan(img: &MemoryImage, progress: &ProgressBar) -> Self {
+        let size = img.size();
+        progress.set_length(size as u64);
+        progress.set_message("Locating freed memory");
+
+        let mut ranges = Vec::new();
+        for page in (0..size).step_by(PAGE_SIZE) {
+            if page % 0x10_0000 == 0 {
+                progress.set_position(page as u64);
+            }
+            if let Some(data) = img.get_bytes(page, PAGE_SIZE) {
+                free_pool_blocks(data, page as u64, &mut ranges);
+            }
+        }
+        transition_pages(img, &mut ranges);
+
+        ranges.sort_by_key(|r| r.start);
+        progress.finish_with_message(format!("Found {} freed regions", ranges.len()));
+        Self { ranges }
+    }
+
+    pub fn ranges(&self) -> &[FreedRange] {
+        &self.ranges
+    }
+
+    /// The freed range containing a physical address, if any
+    pub fn lookup(&self, addr: u64) -> Option<&FreedRange> {
+        // No range is longer than a page, so only the last few can contain addr
+        let idx = self.ranges.partition_point(|r| r.start <= addr);
+        self.ranges[..idx]
+            .iter()
+            .rev()
+            .take_while(|r| r.start + PAGE_SIZE as u64 > addr)
+            .find(|r| addr < r.end)
+    }
+
+    /// Drop findings that lie in freed memory, or tag them when `include` is set
+    pub fn apply(&self, findings: &mut Vec<Finding>, include: bool) {
+        findings.retain_mut(|finding| match self.lookup(finding.addr) {
+            None => true,
+            Some(_) if !include => false,
+            Some(range) => {
+                finding.details.insert("provenance".to_string(), "freed".to_string());
+                finding.details.insert("freed_source".to_string(), range.source.as_str().to_string());
+                true
+            }
+        });
+    }
+}
+
+/// Walk the pool header chain of one page and record its free blocks
+///
+/// A page only counts when the chain is consistent from start to end: every
+/// block's `PreviousSize` matches the block before it and the sizes add up to
+/// exactly one page. This rejects almost all non-pool data.
+fn free_pool_blocks(page: &[u8], base: u64, out: &mut Vec<FreedRange>) {
+    let mut free = Vec::new();
+    let mut blocks = 0;
+    let mut offset = 0;
+    let mut previous = 0u8;
+
+    while offset < page.len() {
+        let header = match page.get(offset..offset + POOL_HEADER_SIZE) {
+            Some(header) => header,
+            None => return,
+        };
+        let (prev_size, block_size, pool_type) = (header[0], header[2], header[3]);
+        let tag = &header[4..8];
+        let block_len = block_size as usize * POOL_HEADER_SIZE;
+
+        if block_size == 0
+            || prev_size != previous
+            || offset + block_len > page.len()
+            || !tag[..3].iter().all(|c| c.is_ascii_graphic() || *c == b' ')
+        {
+            return;
+        }
+        if pool_type == 0 {
+            free.push((offset + POOL_HEADER_SIZE, offset + block_len));
+        }
+
+        previous = block_size;
+        offset += block_len;
+        blocks += 1;
+    }
+
+    // A lone block proves little about the page being pool memory
+    if blocks > 1 {
+        out.extend(free.into_iter().map(|(start, end)| FreedRange {
+            start: base + start as u64,
+            end: base + end as u64,
+            source: FreedSource::Pool,
+        }));
+    }
+}
+
+/// Record pages referenced by transition PTEs in the kernel's page tables
+fn transition_pages(img: &MemoryImage, out: &mut Vec<FreedRange>) {
+    let dtb = match img.info.dtb {
+        Some(dtb) if img.info.arch == Architecture::X86_64 && img.info.user.is_none() => dtb,
+        _ => return,
+    };
+
+    let table = |base: u64| -> Vec<u64> {
+        img.get_bytes(base as usize, PAGE_SIZE)
+            .map(|t| t.chunks_exact(8).map(|e| u64::from_le_bytes(e.try_into().unwrap())).collect())
+            .unwrap_or_default()
+    };
+    let next_level = |entry: u64| (entry & PTE_PRESENT != 0 && entry & PTE_LARGE == 0).then_some(entry & PTE_FRAME_MASK);
+
+    for pdpt in table(dtb & PTE_FRAME_MASK).into_iter().filter_map(|e| (e & PTE_PRESENT != 0).then_some(e & PTE_FRAME_MASK)) {
+        for pd in table(pdpt).into_iter().filter_map(next_level) {
+            for pt in table(pd).into_iter().filter_map(next_level) {
+                for pte in table(pt) {
+                    if pte & (PTE_PRESENT | PTE_PROTOTYPE) == 0 && pte & PTE_TRANSITION != 0 {
+                        let frame = pte & PTE_FRAME_MASK;
+                        if (frame as usize) < img.size() {
+                            out.push(FreedRange { start: frame, end: frame + PAGE_SIZE as u64, source: FreedSource::Transition });
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
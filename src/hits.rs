@@ -0,0 +1,137 @@
+//! Sparse maps of scan hits
+//!
+//! A full scan of a large dump is slow, but the hits of a fast scan tend to
+//! cluster in a few pages. A `HitMap` records which fixed-size blocks of the
+//! physical address space held a hit, without the findings themselves, and
+//! saves them as a short text file of `start:end` intervals that other
+//! tools can read as easily as rmf. A later pass loads only those intervals
+//! (`--only-hits`) and parses them in detail, at their original addresses.
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::paging::AddressRange;
+use crate::plugin::Finding;
+
+/// Default block size: one page
+pub const DEFAULT_GRANULARITY: u64 = 0x1000;
+
+/// First line of a hit map file
+const HEADER: &str = "# rmf hits v1";
+
+/// Blocks of the physical address space that held at least one hit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HitMap {
+    granularity: u64,
+    /// Indexes of the blocks with hits
+    blocks: BTreeSet<u64>,
+}
+
+impl Default for HitMap {
+    fn default() -> Self {
+        HitMap::new(DEFAULT_GRANULARITY)
+    }
+}
+
+impl HitMap {
+    /// An empty map of `granularity`-byte blocks
+    pub fn new(granularity: u64) -> Self {
+        HitMap { granularity: granularity.max(1), blocks: BTreeSet::new() }
+    }
+
+    /// Map the addresses of `findings`
+    pub fn from_findings(findings: &[Finding], granularity: u64) -> Self {
+        let mut map = HitMap::new(granularity);
+        for finding in findings {
+            map.insert(finding.addr, 1);
+        }
+        map
+    }
+
+    pub fn granularity(&self) -> u64 {
+        self.granularity
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Mark the blocks covering `len` bytes at `addr`
+    pub fn insert(&mut self, addr: u64, len: u64) {
+        let last = addr.saturating_add(len.max(1) - 1) / self.granularity;
+        self.blocks.extend(addr / self.granularity..=last);
+    }
+
+    pub fn contains(&self, addr: u64) -> bool {
+        self.blocks.contains(&(addr / self.granularity))
+    }
+
+    /// Runs of adjacent blocks as address ranges, in address order
+    pub fn ranges(&self) -> Vec<AddressRange> {
+        let mut ranges: Vec<AddressRange> = Vec::new();
+        for &block in &self.blocks {
+            let start = block * self.granularity;
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = start + self.granularity,
+                _ => ranges.push(AddressRange { start, end: start + self.granularity }),
+            }
+        }
+        ranges
+    }
+
+    /// Bytes covered by the map
+    pub fn covered(&self) -> u64 {
+        self.blocks.len() as u64 * self.granularity
+    }
+
+    /// The ranges grown by `context` bytes on each side, merging the ones
+    /// that meet, so structures straddling a block edge are read whole
+    pub fn ranges_with_context(&self, context: u64) -> Vec<AddressRange> {
+        let mut ranges: Vec<AddressRange> = Vec::new();
+        for range in self.ranges() {
+            let (start, end) = (range.start.saturating_sub(context), range.end.saturating_add(context));
+            match ranges.last_mut() {
+                Some(last) if last.end >= start => last.end = end,
+                _ => ranges.push(AddressRange { start, end }),
+            }
+        }
+        ranges
+    }
+
+    /// Write the map as one `start:end` interval per line
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut text = format!("{}\n# granularity 0x{:X}\n", HEADER, self.granularity);
+        for range in self.ranges() {
+            text.push_str(&format!("0x{:X}:0x{:X}\n", range.start, range.end));
+        }
+        fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Read a map written by `save`; interval files from other tools need
+    /// no header and may use any block size
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let granularity = text.lines()
+            .find_map(|line| line.strip_prefix("# granularity "))
+            .map(|value| u64::from_str_radix(value.trim().trim_start_matches("0x"), 16))
+            .transpose()
+            .with_context(|| format!("{}: bad granularity", path.display()))?
+            .unwrap_or(DEFAULT_GRANULARITY);
+        let mut map = HitMap::new(granularity);
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let range: AddressRange = line.parse()
+                .with_context(|| format!("{}:{}: bad interval", path.display(), number + 1))?;
+            map.insert(range.start, range.len());
+        }
+        if map.is_empty() {
+            bail!("{} holds no hits", path.display());
+        }
+        Ok(map)
+    }
+}
@@ -0,0 +1,344 @@
+//! Matching indicators of compromise against memory
+//!
+//! `rmf ioc` reads a JSON list of IP addresses, domains, file names, mutex
+//! names and hashes, searches all of physical memory for each of them as
+//! ASCII and UTF-16LE text, and reports every hit. Indicators match without
+//! regard to case, and only as whole tokens: `1.2.3.4` does not match in
+//! `1.2.3.45`, nor `evil.exe` in `notevil.exe`, while a domain matches its
+//! subdomains. Hashes match where their hex digits appear as text, as in
+//! logs, scripts and tool output; the hash of a file in memory is not
+//! computed, as a mapped image differs from the file on disk anyway.
+//!
+//! With the kernel's DTB, the user-mode page tables of every process are
+//! walked so each hit names the processes that map its page, and the
+//! virtual address there.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use regex::bytes::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::kdbg::OsContext;
+use crate::loader::load_memory_image;
+use crate::paging::MemoryImage;
+use crate::plugin::{narrow_utf16, TextEncoding};
+use crate::processes::{windows_finder, ProcessFinder};
+use crate::scan_util::{chunks, CHUNK_SIZE};
+use crate::symbols::SymbolStore;
+
+/// Highest user-mode address; pages above are the kernel's, shared by all
+const USER_LIMIT: u64 = 0x0000_8000_0000_0000;
+/// Bytes of text shown either side of a hit
+const CONTEXT: usize = 24;
+
+/// The indicators to search for, as read from `--iocs`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IocSet {
+    pub ips: Vec<String>,
+    pub domains: Vec<String>,
+    #[serde(alias = "files")]
+    pub filenames: Vec<String>,
+    pub mutexes: Vec<String>,
+    pub hashes: Vec<String>,
+}
+
+/// What an indicator is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IocKind {
+    Ip,
+    Domain,
+    Filename,
+    Mutex,
+    Hash,
+}
+
+impl std::fmt::Display for IocKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IocKind::Ip => write!(f, "ip"),
+            IocKind::Domain => write!(f, "domain"),
+            IocKind::Filename => write!(f, "filename"),
+            IocKind::Mutex => write!(f, "mutex"),
+            IocKind::Hash => write!(f, "hash"),
+        }
+    }
+}
+
+impl IocKind {
+    /// Whether `c` continues a token of this kind, so an indicator next to
+    /// it is part of something longer
+    fn continues(self, c: u8) -> bool {
+        match self {
+            IocKind::Ip => c.is_ascii_digit() || c == b'.',
+            IocKind::Domain => c.is_ascii_alphanumeric() || c == b'-',
+            IocKind::Filename | IocKind::Mutex => c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.'),
+            IocKind::Hash => c.is_ascii_hexdigit(),
+        }
+    }
+}
+
+impl IocSet {
+    /// Read and check the indicators in the JSON file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let iocs: IocSet = serde_json::from_str(&text).with_context(|| format!("{} is not an IOC list", path.display()))?;
+        iocs.validate()?;
+        Ok(iocs)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.indicators().next().is_none() {
+            bail!("The IOC list has no indicators (ips, domains, filenames, mutexes, hashes)");
+        }
+        for ip in &self.ips {
+            ip.parse::<IpAddr>().with_context(|| format!("'{}' is not an IP address", ip))?;
+        }
+        for hash in &self.hashes {
+            if !matches!(hash.len(), 32 | 40 | 64) || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                bail!("'{}' is not an MD5, SHA-1 or SHA-256 hash in hex", hash);
+            }
+        }
+        if let Some((kind, empty)) = self.indicators().find(|(_, value)| value.trim().is_empty()) {
+            bail!("Empty {} in the IOC list: '{}'", kind, empty);
+        }
+        Ok(())
+    }
+
+    /// Every indicator with its kind
+    pub fn indicators(&self) -> impl Iterator<Item = (IocKind, &String)> {
+        let kinds = [(IocKind::Ip, &self.ips), (IocKind::Domain, &self.domains), (IocKind::Filename, &self.filenames), (IocKind::Mutex, &self.mutexes), (IocKind::Hash, &self.hashes)];
+        kinds.into_iter().flat_map(|(kind, values)| values.iter().map(move |value| (kind, value)))
+    }
+}
+
+/// The indicators compiled into one search
+pub struct IocMatcher {
+    regex: Regex,
+    /// Kind and indicator as given, by lower-case text
+    indicators: HashMap<Vec<u8>, (IocKind, String)>,
+    /// Bytes of the longest indicator
+    longest: usize,
+}
+
+/// A hit in a buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IocMatch {
+    pub offset: usize,
+    pub len: usize,
+    pub kind: IocKind,
+    pub indicator: String,
+    pub encoding: TextEncoding,
+    /// The hit with text around it, non-printable bytes as `.`
+    pub context: String,
+}
+
+impl IocMatcher {
+    pub fn new(iocs: &IocSet) -> Result<Self> {
+        let mut indicators = HashMap::new();
+        for (kind, value) in iocs.indicators() {
+            indicators.entry(value.to_ascii_lowercase().into_bytes()).or_insert((kind, value.clone()));
+        }
+        // Longer indicators first, so a domain wins over its parent
+        let mut alternatives: Vec<&Vec<u8>> = indicators.keys().collect();
+        alternatives.sort_by_key(|text| std::cmp::Reverse(text.len()));
+        let pattern = alternatives.iter().map(|text| regex::escape(&String::from_utf8_lossy(text))).collect::<Vec<_>>().join("|");
+        let regex = RegexBuilder::new(&format!("(?i){}", pattern)).unicode(false).size_limit(1 << 26).build()
+            .context("Too many indicators to search for at once")?;
+        let longest = indicators.keys().map(Vec::len).max().unwrap_or(0);
+        Ok(IocMatcher { regex, indicators, longest })
+    }
+
+    /// Bytes a hit can span, as UTF-16
+    pub fn span(&self) -> usize {
+        self.longest * 2 + 2
+    }
+
+    /// Hits in `data` read as `encoding` would be, by offset in `data`
+    fn find_in(&self, data: &[u8], encoding: TextEncoding) -> Vec<IocMatch> {
+        let mut found = Vec::new();
+        for m in self.regex.find_iter(data) {
+            let Some(&(kind, ref indicator)) = self.indicators.get(&m.as_bytes().to_ascii_lowercase()) else { continue };
+            let before = m.start().checked_sub(1).is_some_and(|at| kind.continues(data[at]));
+            // A trailing dot ends a sentence, unless a name goes on after it
+            let after = match data.get(m.end()) {
+                Some(b'.') => data.get(m.end() + 1).is_some_and(|&c| c.is_ascii_alphanumeric()),
+                Some(&c) => kind.continues(c),
+                None => false,
+            };
+            if before || after {
+                continue;
+            }
+            let shown = &data[m.start().saturating_sub(CONTEXT)..(m.end() + CONTEXT).min(data.len())];
+            let context = shown.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+            found.push(IocMatch { offset: m.start(), len: m.len(), kind, indicator: indicator.clone(), encoding, context });
+        }
+        found
+    }
+
+    /// Hits in `data`, as ASCII and as UTF-16LE, by offset
+    pub fn find(&self, data: &[u8]) -> Vec<IocMatch> {
+        let mut matches = self.find_in(data, TextEncoding::Ascii);
+        for alignment in 0..2 {
+            matches.extend(self.find_in(&narrow_utf16(data, alignment), TextEncoding::Utf16Le).into_iter().map(|m| {
+                IocMatch { offset: alignment + m.offset * 2, len: m.len * 2, ..m }
+            }));
+        }
+        matches.sort_by_key(|m| (m.offset, m.encoding == TextEncoding::Utf16Le));
+        matches
+    }
+}
+
+/// A process mapping a physical page, and where
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageOwner {
+    pub pid: u32,
+    pub process: String,
+    /// Virtual address of the hit in the process
+    pub va: u64,
+}
+
+/// Which processes map each physical page, from their user-mode page tables
+#[derive(Debug, Default)]
+pub struct PageOwners {
+    /// Mappings by physical start: size, process index and virtual address
+    pages: BTreeMap<u64, Vec<(u64, usize, u64)>>,
+    processes: Vec<(u32, String)>,
+}
+
+impl PageOwners {
+    /// Walk the page tables of the Windows processes of the kernel whose DTB
+    /// `img` translates through
+    pub fn windows(img: &MemoryImage, symbols: Option<&SymbolStore>, progress: &ProgressBar) -> Result<Self> {
+        let os = OsContext::find(img, progress).context("No KDBG block found; cannot walk the process list")?;
+        let finder = windows_finder(img, os, symbols);
+        let mut owners = PageOwners::default();
+        for process in finder.find_processes(img, progress)? {
+            let Some(space) = process.address_space(img) else { continue };
+            owners.add(process.pid, &process.name, space.mappings().into_iter().filter(|m| m.va < USER_LIMIT).map(|m| (m.va, m.pa, m.size)));
+        }
+        Ok(owners)
+    }
+
+    /// Record that process `pid` maps each `(va, pa, size)` of `mappings`
+    pub fn add(&mut self, pid: u32, name: &str, mappings: impl IntoIterator<Item = (u64, u64, u64)>) {
+        let index = self.processes.len();
+        self.processes.push((pid, name.to_string()));
+        for (va, pa, size) in mappings {
+            self.pages.entry(pa).or_default().push((size, index, va));
+        }
+    }
+
+    /// Processes that map physical address `pa`, by PID
+    pub fn owners(&self, pa: u64) -> Vec<PageOwner> {
+        // Large pages start below the page of `pa`; none is larger than 1 GiB
+        let mut owners: Vec<PageOwner> = self.pages.range(pa.saturating_sub(0x4000_0000)..=pa).flat_map(|(&start, mappings)| {
+            mappings.iter().filter(move |&&(size, ..)| pa < start + size).map(move |&(_, index, va)| {
+                let (pid, name) = &self.processes[index];
+                PageOwner { pid: *pid, process: name.clone(), va: va + (pa - start) }
+            })
+        }).collect();
+        owners.sort_by_key(|owner| (owner.pid, owner.va));
+        owners.dedup();
+        owners
+    }
+}
+
+/// A hit in physical memory
+#[derive(Debug, Clone, Serialize)]
+pub struct IocHit {
+    pub kind: IocKind,
+    pub indicator: String,
+    pub physical: u64,
+    pub encoding: String,
+    pub context: String,
+    /// Empty when no process maps the page, or processes were not walked
+    pub processes: Vec<PageOwner>,
+}
+
+/// Every hit of `matcher` in physical memory, attributed with `owners`
+pub fn scan_iocs(img: &MemoryImage, matcher: &IocMatcher, owners: &PageOwners, progress: &ProgressBar) -> Vec<IocHit> {
+    let mut hits = Vec::new();
+    for window in chunks(img, CHUNK_SIZE, matcher.span()).with_progress(progress) {
+        for found in matcher.find(window.data).into_iter().filter(|m| window.owns(m.offset)) {
+            let physical = window.addr(found.offset);
+            hits.push(IocHit {
+                kind: found.kind,
+                indicator: found.indicator,
+                physical,
+                encoding: found.encoding.to_string(),
+                context: found.context,
+                processes: owners.owners(physical),
+            });
+        }
+    }
+    hits
+}
+
+/// Search a dump for the indicators in `iocs_path` and report every hit
+pub fn report_iocs(dump_path: PathBuf, iocs_path: &Path, dtb: Option<u64>, output: Option<PathBuf>, symbols: Option<SymbolStore>) -> Result<()> {
+    let iocs = IocSet::load(iocs_path)?;
+    let matcher = IocMatcher::new(&iocs)?;
+    let mut memory_image = load_memory_image(&dump_path)?;
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let owners = match dtb {
+        Some(dtb) => {
+            memory_image.set_cr3(dtb);
+            progress.set_message("Walking process page tables");
+            PageOwners::windows(&memory_image, symbols.as_ref(), &progress)?
+        }
+        None => PageOwners::default(),
+    };
+    progress.set_message(format!("Searching for {} indicators", iocs.indicators().count()));
+    let hits = scan_iocs(&memory_image, &matcher, &owners, &progress);
+    progress.finish_and_clear();
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"Kind", bFg->"Indicator", bFg->"Physical", bFg->"Encoding", bFg->"PID", bFg->"Process", bFg->"Virtual", bFg->"Context"]);
+    for hit in &hits {
+        let pids: Vec<String> = hit.processes.iter().map(|owner| owner.pid.to_string()).collect();
+        let names: Vec<&str> = hit.processes.iter().map(|owner| owner.process.as_str()).collect();
+        let vas: Vec<String> = hit.processes.iter().map(|owner| format!("0x{:X}", owner.va)).collect();
+        let or_dash = |values: String| if values.is_empty() { "-".to_string() } else { values };
+        table.add_row(row![
+            hit.kind,
+            hit.indicator,
+            format!("0x{:X}", hit.physical),
+            hit.encoding,
+            or_dash(pids.join(",")),
+            or_dash(names.join(",")),
+            or_dash(vas.join(",")),
+            hit.context
+        ]);
+    }
+    table.printstd();
+
+    let mut counts: BTreeMap<(IocKind, &str), usize> = BTreeMap::new();
+    for hit in &hits {
+        *counts.entry((hit.kind, hit.indicator.as_str())).or_default() += 1;
+    }
+    println!("\n{} hits of {} of {} indicators", hits.len(), counts.len(), iocs.indicators().count());
+    for ((kind, indicator), count) in &counts {
+        println!("  {} {}: {} hits", kind, indicator.bright_red(), count);
+    }
+    if dtb.is_none() {
+        println!("{}", "No --dtb given; hits are not attributed to processes".yellow());
+    }
+    if let Some(output) = output {
+        fs::write(&output, serde_json::to_string_pretty(&hits)?).with_context(|| format!("Failed to write {}", output.display()))?;
+        println!("Hits written to {}", output.display());
+    }
+    Ok(())
+}
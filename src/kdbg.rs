@@ -0,0 +1,270 @@
+//! Windows kernel debugger data block (KDBG) scanner
+//!
+//! `_KDDEBUGGER_DATA64` records the kernel base, the heads of the active
+//! process and loaded module lists, the CID handle table and the per-CPU
+//! processor blocks. Before Windows 8 it sits in memory as
+//! plaintext with a `KDBG` owner tag. Later kernels keep it encoded and
+//! only decode a copy in `KdCopyDataBlock`; that function is located in
+//! executable kernel pages and its RIP-relative operands give the keys.
+
+use anyhow::Result;
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::path::PathBuf;
+
+use crate::loader::load_memory_image;
+use crate::paging::MemoryImage;
+
+/// Owner tag in the KDBG header
+pub const KDBG_TAG: &[u8; 4] = b"KDBG";
+
+// Offsets in _KDDEBUGGER_DATA64
+const OWNER_TAG: usize = 0x10;
+const HEADER_SIZE: usize = 0x14;
+const KERN_BASE: usize = 0x18;
+const PS_LOADED_MODULE_LIST: usize = 0x48;
+const PS_ACTIVE_PROCESS_HEAD: usize = 0x50;
+const PSP_CID_TABLE: usize = 0x58;
+const KI_PROCESSOR_BLOCK: usize = 0x218;
+/// Bytes needed to read every required field
+const KDBG_PREFIX_LEN: usize = 0x60;
+/// Bytes read to include the optional fields too
+const KDBG_READ_LEN: usize = 0x220;
+
+/// List heads must lie inside the kernel image
+const MAX_KERNEL_IMAGE: u64 = 0x0400_0000;
+
+/// Kernel globals located through the KDBG block
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsContext {
+    /// Physical address of the block, when it was found by a physical scan
+    pub kdbg_phys: Option<u64>,
+    /// Virtual address of the block, when it was found through code
+    pub kdbg_va: Option<u64>,
+    pub kernel_base: u64,
+    pub ps_active_process_head: u64,
+    pub ps_loaded_module_list: u64,
+    /// Address of the `PspCidTable` pointer, when the block records a plausible one
+    pub psp_cid_table: Option<u64>,
+    /// Address of `KiProcessorBlock`, the array of each CPU's KPRCB, when
+    /// the block is long enough to record it
+    pub ki_processor_block: Option<u64>,
+    /// Size recorded in the KDBG header
+    pub block_size: u32,
+    /// Whether the block had to be decoded (Windows 8 and later)
+    pub encoded: bool,
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn is_kernel_va(va: u64) -> bool {
+    va >= 0xFFFF_8000_0000_0000
+}
+
+impl OsContext {
+    /// Validate a plaintext (or decoded) KDBG block
+    pub fn from_block(block: &[u8]) -> Option<Self> {
+        if block.len() < KDBG_PREFIX_LEN || &block[OWNER_TAG..OWNER_TAG + 4] != KDBG_TAG {
+            return None;
+        }
+        let block_size = u32::from_le_bytes(block[HEADER_SIZE..HEADER_SIZE + 4].try_into().unwrap());
+        if !(KDBG_PREFIX_LEN as u32..=0x1000).contains(&block_size) {
+            return None;
+        }
+
+        let kernel_base = u64_at(block, KERN_BASE);
+        let ps_loaded_module_list = u64_at(block, PS_LOADED_MODULE_LIST);
+        let ps_active_process_head = u64_at(block, PS_ACTIVE_PROCESS_HEAD);
+        let psp_cid_table = u64_at(block, PSP_CID_TABLE);
+        let ki_processor_block = (block.len() >= KDBG_READ_LEN && block_size as usize >= KDBG_READ_LEN)
+            .then(|| u64_at(block, KI_PROCESSOR_BLOCK));
+        let in_image = |va: u64| va > kernel_base && va - kernel_base < MAX_KERNEL_IMAGE;
+        if !is_kernel_va(kernel_base) || kernel_base & 0xFFF != 0
+            || !in_image(ps_loaded_module_list) || !in_image(ps_active_process_head)
+        {
+            return None;
+        }
+
+        Some(OsContext {
+            kdbg_phys: None,
+            kdbg_va: None,
+            kernel_base,
+            ps_active_process_head,
+            ps_loaded_module_list,
+            psp_cid_table: in_image(psp_cid_table).then_some(psp_cid_table),
+            ki_processor_block: ki_processor_block.filter(|&va| in_image(va)),
+            block_size,
+            encoded: false,
+        })
+    }
+
+    /// Locate the KDBG block, trying a plaintext scan before decoding
+    pub fn find(img: &MemoryImage, progress: &ProgressBar) -> Option<Self> {
+        find_plaintext(img, progress).or_else(|| find_encoded(img, progress))
+    }
+}
+
+/// Keys `KdCopyDataBlock` uses to decode the block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KdbgKeys {
+    pub wait_never: u64,
+    pub wait_always: u64,
+    /// Address of `KdpDataBlockEncoded`, mixed into every qword
+    pub encoded_flag_va: u64,
+}
+
+impl KdbgKeys {
+    /// Decode a block the way `KdCopyDataBlock` does, one qword at a time
+    pub fn decode(&self, encoded: &[u8]) -> Vec<u8> {
+        encoded.chunks_exact(8)
+            .flat_map(|q| {
+                let value = u64::from_le_bytes(q.try_into().unwrap());
+                let value = (value ^ self.wait_never).rotate_left((self.wait_never & 0xFF) as u32);
+                ((value ^ self.encoded_flag_va).swap_bytes() ^ self.wait_always).to_le_bytes()
+            })
+            .collect()
+    }
+}
+
+fn find_plaintext(img: &MemoryImage, progress: &ProgressBar) -> Option<OsContext> {
+    let size = img.size();
+    let chunk_size = 0x10000;
+
+    progress.set_length(size as u64);
+    progress.set_message("Scanning for KDBG");
+
+    for chunk_start in (0..size).step_by(chunk_size) {
+        progress.set_position(chunk_start as u64);
+        // Overlap chunks so a block straddling the boundary is still seen
+        let len = (chunk_size + KDBG_READ_LEN).min(size - chunk_start);
+        let Some(chunk) = img.get_bytes(chunk_start, len) else { continue };
+
+        for pos in chunk.windows(4).enumerate().filter(|(_, w)| w == KDBG_TAG).map(|(i, _)| i) {
+            if pos < OWNER_TAG || pos - OWNER_TAG >= chunk_size {
+                continue;
+            }
+            let start = pos - OWNER_TAG;
+            if let Some(mut ctx) = chunk.get(start..).and_then(OsContext::from_block) {
+                ctx.kdbg_phys = Some((chunk_start + start) as u64);
+                return Some(ctx);
+            }
+        }
+    }
+    None
+}
+
+/// RIP-relative `mov`/`lea` of a 64-bit register: REX.W, opcode, modrm 00 reg 101
+fn rip_relative(code: &[u8], pos: usize, opcode: u8) -> Option<i32> {
+    let insn = code.get(pos..pos + 7)?;
+    if (insn[0] == 0x48 || insn[0] == 0x4C) && insn[1] == opcode && insn[2] & 0xC7 == 0x05 {
+        return Some(i32::from_le_bytes(insn[3..7].try_into().unwrap()));
+    }
+    None
+}
+
+/// Recognise `KdCopyDataBlock` at `pos` and return the keys and block address
+fn match_copy_data_block(img: &MemoryImage, code: &[u8], pos: usize, va: u64) -> Option<(KdbgKeys, u64)> {
+    // cmp byte ptr [rip+disp], 0
+    let cmp = code.get(pos..pos + 7)?;
+    if cmp[0] != 0x80 || cmp[1] != 0x3D || cmp[6] != 0 {
+        return None;
+    }
+    let target = |next: usize, disp: i32| (va + next as u64).wrapping_add(disp as i64 as u64);
+    let encoded_flag_va = target(pos + 7, i32::from_le_bytes(cmp[2..6].try_into().unwrap()));
+
+    // mov reg, [KiWaitNever] ... lea reg, [KdDebuggerDataBlock] ... mov reg, [KiWaitAlways]
+    let mut loads = Vec::new();
+    let mut block_va = None;
+    for at in pos + 7..(pos + 0x60).min(code.len()) {
+        if let Some(disp) = rip_relative(code, at, 0x8B) {
+            loads.push(target(at + 7, disp));
+        } else if let Some(disp) = rip_relative(code, at, 0x8D) {
+            block_va.get_or_insert(target(at + 7, disp));
+        }
+        if loads.len() == 2 && block_va.is_some() {
+            break;
+        }
+    }
+    if loads.len() < 2 {
+        return None;
+    }
+
+    let keys = KdbgKeys {
+        wait_never: img.read_virt_u64(loads[0])?,
+        wait_always: img.read_virt_u64(loads[1])?,
+        encoded_flag_va,
+    };
+    Some((keys, block_va?))
+}
+
+fn find_encoded(img: &MemoryImage, progress: &ProgressBar) -> Option<OsContext> {
+    let code: Vec<_> = img.mappings().into_iter()
+        .filter(|m| m.executable && is_kernel_va(m.va))
+        .collect();
+
+    progress.set_length(code.len() as u64);
+    progress.set_message("Searching kernel code for KdCopyDataBlock");
+
+    for (i, mapping) in code.iter().enumerate() {
+        progress.set_position(i as u64);
+        let Some(bytes) = img.get_bytes(mapping.pa as usize, mapping.size as usize) else { continue };
+
+        for pos in bytes.windows(2).enumerate().filter(|(_, w)| w == &[0x80, 0x3D]).map(|(i, _)| i) {
+            let Some((keys, block_va)) = match_copy_data_block(img, bytes, pos, mapping.va) else { continue };
+            let Some(block) = img.read_virt(block_va, KDBG_READ_LEN).or_else(|| img.read_virt(block_va, KDBG_PREFIX_LEN)) else { continue };
+
+            // KdpDataBlockEncoded is cleared when encoding is disabled
+            let flag = img.virt_to_phys(keys.encoded_flag_va)
+                .and_then(|pa| img.get_bytes(pa as usize, 1))
+                .map_or(0, |b| b[0]);
+            let (block, encoded) = if flag != 0 { (keys.decode(&block), true) } else { (block, false) };
+            if let Some(mut ctx) = OsContext::from_block(&block) {
+                ctx.kdbg_va = Some(block_va);
+                ctx.encoded = encoded;
+                return Some(ctx);
+            }
+        }
+    }
+    None
+}
+
+/// Print the KDBG block and the kernel globals it points to
+pub fn report_kdbg(dump_path: PathBuf, dtb: Option<u64>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    if let Some(dtb) = dtb {
+        memory_image.set_cr3(dtb);
+    }
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let ctx = OsContext::find(&memory_image, &progress);
+    progress.finish_and_clear();
+
+    let Some(ctx) = ctx else {
+        println!("{}", "No KDBG block found".bright_red());
+        if memory_image.info.dtb.is_none() {
+            println!("Encoded blocks (Windows 8+) can only be decoded with a DTB (use --dtb)");
+        }
+        return Ok(());
+    };
+
+    let location = match (ctx.kdbg_va, ctx.kdbg_phys) {
+        (Some(va), _) => format!("0x{:X} (virtual)", va),
+        (None, Some(pa)) => format!("0x{:X} (physical)", pa),
+        (None, None) => "-".to_string(),
+    };
+    println!("{} {}", "KDBG:".bright_green(), location.bright_yellow());
+    println!("  {:<22} {}", "Encoded", if ctx.encoded { "yes" } else { "no" });
+    println!("  {:<22} 0x{:X}", "Block size", ctx.block_size);
+    println!("  {:<22} 0x{:X}", "Kernel base", ctx.kernel_base);
+    println!("  {:<22} 0x{:X}", "PsActiveProcessHead", ctx.ps_active_process_head);
+    println!("  {:<22} 0x{:X}", "PsLoadedModuleList", ctx.ps_loaded_module_list);
+    println!("  {:<22} {}", "PspCidTable", ctx.psp_cid_table.map_or("-".to_string(), |va| format!("0x{:X}", va)));
+    println!("  {:<22} {}", "KiProcessorBlock", ctx.ki_processor_block.map_or("-".to_string(), |va| format!("0x{:X}", va)));
+
+    Ok(())
+}
@@ -1,13 +1,57 @@
+pub mod actions;
+pub mod allowlist;
 pub mod arch;
+pub mod aslr;
+pub mod baseline;
+pub mod case;
+pub mod containers;
+pub mod dlllist;
+pub mod dotnet;
+pub mod disasm;
+pub mod coverage;
+pub mod dtb;
+pub mod dumpdiff;
+pub mod dumpfiles;
+pub mod dumpset;
+pub mod evidence;
+pub mod explain;
+pub mod extract;
+pub mod formats;
+pub mod freed;
+pub mod hits;
+pub mod ioc;
+pub mod kdbg;
+pub mod limits;
+pub mod linux_profile;
 pub mod loader;
 pub mod paging;
+pub mod pe;
 pub mod processes;
 pub mod modules;
+pub mod netscan;
+pub mod osinfo;
+pub mod output;
 pub mod plugin;
+pub mod procdiff;
+pub mod procdump;
+pub mod progress;
+pub mod psxview;
+pub mod registry;
+#[cfg(feature = "sqlite")]
+pub mod resultsdb;
+pub mod scan_util;
+pub mod signatures;
+pub mod stats;
+pub mod symbols;
+pub mod timeline;
+pub mod token;
+pub mod usermode;
+pub mod vad;
 
 // Re-export commonly used types
-pub use paging::{MemoryImage, MemoryImageInfo, Architecture, PageTableType};
+pub use paging::{MemoryImage, MemoryImageInfo, Architecture, PageTableType, ImageFormat, PhysicalRun, CpuState, Segment};
 pub use plugin::{Finding, MemoryPlugin};
+pub use dumpset::{Dump, DumpSet};
 
 #[cfg(test)]
 mod tests {
@@ -44,20 +88,21 @@ mod tests {
         assert_eq!(bytes.unwrap().len(), 16, "Should read exactly 16 bytes");
     }
 
-    #[test]
-    fn test_virt_to_phys_translation() {
-        let path = test_dump_path();
-        let memory_image = load_memory_image(&path).unwrap();
-        
-        // Test basic virtual to physical translation
-        let virt_addr = 0x7FFFFFFF1000;
-        let phys_addr = memory_image.virt_to_phys(virt_addr);
-        
-        assert!(phys_addr.is_some(), "Virtual address translation failed");
-        assert_eq!(phys_addr.unwrap() & 0xFFF, virt_addr & 0xFFF, 
-                   "Page offset should be preserved in translation");
-    }
-    
     // Include advanced paging tests
     mod paging_tests;
+
+    // Include built-in plugin tests
+    mod plugin_tests;
+
+    // Include dump format tests
+    mod format_tests;
+
+    // Include process discovery tests
+    mod process_tests;
+
+    // Include region extraction tests
+    mod extract_tests;
+
+    // Include registry hive tests
+    mod registry_tests;
 }
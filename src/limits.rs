@@ -0,0 +1,191 @@
+//! Resource limits for running on shared analysis servers
+//!
+//! `ResourceLimits::apply` sets process-wide limits before analysis starts.
+//! The memory cap is an `RLIMIT_DATA` on heap and anonymous allocations;
+//! dumps are read through file-backed mappings, which it does not count, so
+//! large images still open. The I/O priority is set with `ioprio_set`
+//! (Linux only). The thread cap bounds every parallel stage of the engine:
+//! loading and scanning the images of a `DumpSet` and the two captures
+//! compared by `diff-proc`.
+
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Thread cap set by `ResourceLimits::apply`; 0 when unlimited
+static MAX_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// I/O scheduling class for the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Only use the disk when no other process needs it
+    Idle,
+    /// Normal scheduling at a level from 0 (highest) to 7 (lowest)
+    BestEffort(u8),
+}
+
+impl FromStr for IoPriority {
+    type Err = anyhow::Error;
+
+    /// `idle`, `best-effort` or `best-effort:<0-7>`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "idle" => Ok(IoPriority::Idle),
+            None if s == "best-effort" => Ok(IoPriority::BestEffort(4)),
+            Some(("best-effort", level)) => match level.parse::<u8>() {
+                Ok(level) if level <= 7 => Ok(IoPriority::BestEffort(level)),
+                _ => bail!("best-effort level must be 0-7, got '{}'", level),
+            },
+            _ => bail!("unknown I/O priority '{}' (use idle, best-effort or best-effort:<0-7>)", s),
+        }
+    }
+}
+
+impl fmt::Display for IoPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IoPriority::Idle => write!(f, "idle"),
+            IoPriority::BestEffort(level) => write!(f, "best-effort:{}", level),
+        }
+    }
+}
+
+/// Parse a byte count with an optional K/M/G/T suffix (powers of 1024)
+pub fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let (digits, shift) = match s.char_indices().last() {
+        Some((at, c)) if c.is_ascii_alphabetic() => {
+            let shift = match c.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => bail!("unknown size suffix in '{}' (use K, M, G or T)", s),
+            };
+            (&s[..at], shift)
+        }
+        _ => (s, 0),
+    };
+    let value: u64 = digits.trim().parse().with_context(|| format!("'{}' is not a size", s))?;
+    value.checked_mul(1 << shift).with_context(|| format!("size '{}' is too large", s))
+}
+
+/// Limits on the threads, memory and I/O priority of an analysis
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResourceLimits {
+    max_threads: Option<usize>,
+    max_memory: Option<u64>,
+    io_priority: Option<IoPriority>,
+}
+
+impl ResourceLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run at most `threads` analysis threads at once
+    pub fn max_threads(mut self, threads: usize) -> Self {
+        self.max_threads = Some(threads.max(1));
+        self
+    }
+
+    /// Cap heap and anonymous memory at `bytes`
+    pub fn max_memory(mut self, bytes: u64) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    pub fn io_priority(mut self, priority: IoPriority) -> Self {
+        self.io_priority = Some(priority);
+        self
+    }
+
+    /// Threads to use for `jobs` parallel tasks: the cap set here, else the
+    /// process-wide one, else one per task
+    pub fn threads_for(&self, jobs: usize) -> usize {
+        let cap = self.max_threads.or_else(global_max_threads).unwrap_or(usize::MAX);
+        jobs.min(cap).max(1)
+    }
+
+    /// Apply the limits to the whole process
+    pub fn apply(&self) -> Result<()> {
+        if let Some(threads) = self.max_threads {
+            MAX_THREADS.store(threads, Ordering::Relaxed);
+        }
+        if let Some(bytes) = self.max_memory {
+            set_memory_limit(bytes)?;
+        }
+        if let Some(priority) = self.io_priority {
+            set_io_priority(priority)?;
+        }
+        Ok(())
+    }
+}
+
+/// Thread cap applied to the process, if any
+pub fn global_max_threads() -> Option<usize> {
+    match MAX_THREADS.load(Ordering::Relaxed) {
+        0 => None,
+        threads => Some(threads),
+    }
+}
+
+#[cfg(unix)]
+fn set_memory_limit(bytes: u64) -> Result<()> {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    // SAFETY: getrlimit and setrlimit only access the struct passed by reference
+    if unsafe { libc::getrlimit(libc::RLIMIT_DATA, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to read the memory limit");
+    }
+    // Lower the soft limit only, never above the hard limit set by the administrator
+    limit.rlim_cur = (bytes as libc::rlim_t).min(limit.rlim_max);
+    if unsafe { libc::setrlimit(libc::RLIMIT_DATA, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to set the memory limit");
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_memory_limit(_bytes: u64) -> Result<()> {
+    bail!("--max-memory is not supported on this platform")
+}
+
+#[cfg(target_os = "linux")]
+fn set_io_priority(priority: IoPriority) -> Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    let value = match priority {
+        IoPriority::BestEffort(level) => (2 << IOPRIO_CLASS_SHIFT) | level as libc::c_int,
+        IoPriority::Idle => 3 << IOPRIO_CLASS_SHIFT,
+    };
+    // SAFETY: ioprio_set takes only integer arguments; 0 selects the calling process
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, value) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to set the I/O priority");
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_io_priority(_priority: IoPriority) -> Result<()> {
+    bail!("--io-priority is only supported on Linux")
+}
+
+/// Apply `f` to every item on at most `threads` threads, keeping item order
+pub(crate) fn parallel_map<'a, T: Sync, R: Send>(items: &'a [T], threads: usize, f: impl Fn(&'a T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..threads.min(items.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else { break };
+                let result = f(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().map(|r| r.expect("worker panicked")).collect()
+}
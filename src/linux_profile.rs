@@ -0,0 +1,248 @@
+//! Linux kernel profiles built from DWARF debug information
+//!
+//! There is no symbol server for Linux: structure layouts depend on the
+//! kernel's configuration and compiler, so they are read from the debug
+//! information of the exact build, either a `vmlinux` with debug info or a
+//! module's debuginfo compiled against the same headers. Symbol addresses
+//! come from the ELF symbol table or a `System.map`. A profile is saved as
+//! JSON named after the kernel release so the release in a dump's banner
+//! selects it.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::symbols::KernelTypes;
+
+/// Structures whose layout is recorded in a profile
+pub const LINUX_STRUCTS: &[&str] = &[
+    "task_struct", "mm_struct", "cred", "list_head",
+    "files_struct", "fdtable", "file", "socket", "sock_common",
+    "neigh_table", "neigh_hash_table", "neighbour", "net_device",
+    "css_set", "cgroup", "kernfs_node",
+];
+
+/// Kernel symbols whose addresses are recorded in a profile
+pub const LINUX_SYMBOLS: &[&str] = &["init_task", "linux_banner", "swapper_pg_dir", "init_top_pgt", "_text", "arp_tbl", "nd_tbl"];
+
+/// Offsets and symbols of one Linux kernel build
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinuxProfile {
+    /// Kernel release, e.g. `5.15.0-91-generic`
+    pub release: String,
+    pub symbols: BTreeMap<String, u64>,
+    pub types: KernelTypes,
+}
+
+/// File name of the profile for a kernel release
+pub fn profile_file_name(release: &str) -> String {
+    format!("linux-{}.json", release)
+}
+
+impl LinuxProfile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("{} is not a Linux profile", path.display()))
+    }
+
+    /// Load the profile at `path`, or the one matching `release` when `path` is a directory
+    pub fn find(path: &Path, release: Option<&str>) -> Result<Self> {
+        if !path.is_dir() {
+            return Self::load(path);
+        }
+        let Some(release) = release else {
+            bail!("No kernel release to select a profile from {}", path.display());
+        };
+        Self::load(&path.join(profile_file_name(release)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n").with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn file_name(&self) -> String {
+        profile_file_name(&self.release)
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<u64> {
+        self.symbols.get(name).copied()
+    }
+}
+
+/// Addresses of `LINUX_SYMBOLS` listed in a `System.map`
+pub fn parse_system_map(text: &str) -> BTreeMap<String, u64> {
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let addr = u64::from_str_radix(parts.next()?, 16).ok()?;
+            let name = parts.nth(1)?;
+            LINUX_SYMBOLS.contains(&name).then(|| (name.to_string(), addr))
+        })
+        .collect()
+}
+
+/// Kernel release named by a banner or a module's `vermagic` in raw file contents
+#[cfg(feature = "symbols")]
+fn find_release(data: &[u8]) -> Option<String> {
+    use crate::osinfo::LinuxBanner;
+
+    let find = |needle: &[u8]| data.windows(needle.len()).position(|w| w == needle);
+    let string_at = |at: usize| {
+        let end = data[at..].iter().position(|&b| b == 0 || b == b'\n').map_or(data.len(), |e| at + e);
+        String::from_utf8_lossy(&data[at..end]).into_owned()
+    };
+
+    if let Some(at) = find(b"Linux version ") {
+        if let Some(banner) = LinuxBanner::parse(&string_at(at)) {
+            return Some(banner.release);
+        }
+    }
+    // .modinfo: "vermagic=5.15.0-91-generic SMP mod_unload modversions"
+    let at = find(b"vermagic=")? + b"vermagic=".len();
+    string_at(at).split_whitespace().next().map(str::to_string)
+}
+
+/// Record the members of the structure at `offset`, flattening anonymous
+/// structs and unions into their parent at `base`
+#[cfg(feature = "symbols")]
+fn collect_members<R: gimli::Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+    offset: gimli::UnitOffset<R::Offset>,
+    base: u64,
+    fields: &mut BTreeMap<String, u64>,
+) -> Result<()> {
+    let mut tree = unit.entries_tree(Some(offset))?;
+    let mut children = tree.root()?.children();
+    while let Some(child) = children.next()? {
+        let entry = child.entry();
+        if entry.tag() != gimli::DW_TAG_member {
+            continue;
+        }
+        // Union members have no location; bitfields may only give a bit offset
+        let location = match entry.attr_value(gimli::DW_AT_data_member_location)? {
+            Some(value) => value.udata_value(),
+            None => entry.attr_value(gimli::DW_AT_data_bit_offset)?.and_then(|v| v.udata_value()).map(|bits| bits / 8),
+        }.unwrap_or(0);
+
+        match entry.attr_value(gimli::DW_AT_name)? {
+            Some(name) => {
+                let name = dwarf.attr_string(unit, name)?.to_string_lossy()?.into_owned();
+                fields.entry(name).or_insert(base + location);
+            }
+            None => {
+                if let Some(gimli::AttributeValue::UnitRef(inner)) = entry.attr_value(gimli::DW_AT_type)? {
+                    collect_members(dwarf, unit, inner, base + location, fields)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Layouts of `LINUX_STRUCTS`, taken from the first complete definition of each
+#[cfg(feature = "symbols")]
+fn types_from_dwarf<R: gimli::Reader>(dwarf: &gimli::Dwarf<R>) -> Result<KernelTypes> {
+    use crate::symbols::StructLayout;
+
+    let mut types = KernelTypes::default();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            if entry.tag() != gimli::DW_TAG_structure_type || entry.attr(gimli::DW_AT_declaration)?.is_some() {
+                continue;
+            }
+            let Some(name) = entry.attr_value(gimli::DW_AT_name)? else { continue };
+            let name = dwarf.attr_string(&unit, name)?.to_string_lossy()?.into_owned();
+            if !LINUX_STRUCTS.contains(&name.as_str()) || types.structs.contains_key(&name) {
+                continue;
+            }
+            let Some(size) = entry.attr_value(gimli::DW_AT_byte_size)?.and_then(|v| v.udata_value()) else { continue };
+
+            let mut layout = StructLayout { size, fields: BTreeMap::new() };
+            collect_members(dwarf, &unit, entry.offset(), 0, &mut layout.fields)?;
+            types.structs.insert(name, layout);
+        }
+        if types.structs.len() == LINUX_STRUCTS.len() {
+            break;
+        }
+    }
+    Ok(types)
+}
+
+/// Build a profile from an ELF file with DWARF debug information
+///
+/// `system_map` supplies symbol addresses when the ELF is not `vmlinux`;
+/// the release is read from the ELF's banner or `vermagic` unless given.
+#[cfg(feature = "symbols")]
+pub fn profile_from_elf(data: &[u8], system_map: Option<&str>, release: Option<String>) -> Result<LinuxProfile> {
+    use object::{Object, ObjectSection, ObjectSymbol};
+    use std::borrow::Cow;
+
+    let file = object::File::parse(data).context("Not an ELF file")?;
+    let endian = if file.is_little_endian() { gimli::RunTimeEndian::Little } else { gimli::RunTimeEndian::Big };
+    let sections = gimli::DwarfSections::load(|id| -> Result<Cow<[u8]>> {
+        Ok(match file.section_by_name(id.name()) {
+            Some(section) => section.uncompressed_data()?,
+            None => Cow::Borrowed(&[]),
+        })
+    })?;
+    let dwarf = sections.borrow(|section| gimli::EndianSlice::new(section, endian));
+
+    let types = types_from_dwarf(&dwarf)?;
+    if !types.structs.contains_key("task_struct") {
+        bail!("No task_struct in the debug information (is the file stripped?)");
+    }
+
+    let mut symbols: BTreeMap<String, u64> = file.symbols()
+        .filter_map(|sym| {
+            let name = sym.name().ok()?;
+            LINUX_SYMBOLS.contains(&name).then(|| (name.to_string(), sym.address()))
+        })
+        .collect();
+    if let Some(map) = system_map {
+        symbols.extend(parse_system_map(map));
+    }
+
+    let release = match release.or_else(|| find_release(data)) {
+        Some(release) => release,
+        None => bail!("Cannot tell the kernel release from the ELF; pass --release"),
+    };
+    Ok(LinuxProfile { release, symbols, types })
+}
+
+#[cfg(not(feature = "symbols"))]
+pub fn profile_from_elf(_data: &[u8], _system_map: Option<&str>, _release: Option<String>) -> Result<LinuxProfile> {
+    bail!("Cannot read DWARF: rmf was built without the `symbols` feature")
+}
+
+/// Build a profile from `debug_file` and write it to `output` (a file or directory)
+pub fn build_linux_profile(debug_file: PathBuf, system_map: Option<PathBuf>, release: Option<String>, output: PathBuf) -> Result<()> {
+    let data = std::fs::read(&debug_file).with_context(|| format!("Failed to read {}", debug_file.display()))?;
+    let map = system_map
+        .map(|path| std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display())))
+        .transpose()?;
+    let profile = profile_from_elf(&data, map.as_deref(), release)?;
+
+    let path = if output.is_dir() { output.join(profile.file_name()) } else { output };
+    profile.save(&path)?;
+
+    println!("{} {}", "Kernel release:".bright_green(), profile.release.bright_yellow());
+    for (name, layout) in &profile.types.structs {
+        println!("  {:<16} size 0x{:X}, {} fields", name, layout.size, layout.fields.len());
+    }
+    for name in LINUX_SYMBOLS {
+        let addr = profile.symbol(name).map_or("-".to_string(), |a| format!("0x{:X}", a));
+        println!("  {:<16} {}", name, addr);
+    }
+    if profile.symbol("init_task").is_none() {
+        println!("{} no init_task address; pass the kernel's System.map with --system-map", "Warning:".bright_yellow());
+    }
+    println!("{} {}", "Profile written to".bright_green(), path.display());
+    Ok(())
+}
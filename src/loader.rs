@@ -1,9 +1,45 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
-use memmap2::MmapOptions;
-use std::{fs::File, path::PathBuf};
-use crate::paging::MemoryImage;
+use memmap2::{Mmap, MmapOptions};
+use std::{path::{Path, PathBuf}, sync::Mutex};
+use crate::evidence;
+use crate::hits::HitMap;
+use crate::formats::{acquisition, compressed, compressed::Compression, elf_core, minidump};
+use crate::formats::acquisition::AcquisitionMetadata;
+use crate::paging::{AddressRange, ImageFormat, MemoryImage, Segment};
+
+/// Slice of the physical address space every loaded dump is restricted to
+static LOAD_RANGE: Mutex<Option<AddressRange>> = Mutex::new(None);
+
+/// Restrict every dump loaded from now on to `range`, e.g. one physical run
+/// of a huge image; addresses keep their values in the full dump
+pub fn set_load_range(range: Option<AddressRange>) {
+    *LOAD_RANGE.lock().unwrap() = range;
+}
+
+static LOAD_HITS: Mutex<Option<HitMap>> = Mutex::new(None);
+
+/// Restrict every dump loaded from now on to the blocks of a hit map from
+/// an earlier scan, plus one block of context on each side
+pub fn set_load_hits(hits: Option<HitMap>) {
+    *LOAD_HITS.lock().unwrap() = hits;
+}
+
+fn apply_load_range(image: &mut MemoryImage) -> Result<()> {
+    let size = image.size();
+    if let Some(range) = *LOAD_RANGE.lock().unwrap() {
+        if image.restrict_to(range).runs().is_empty() {
+            bail!("Range {} holds no data of the dump (0x0-0x{:X})", range, size);
+        }
+    }
+    if let Some(hits) = LOAD_HITS.lock().unwrap().as_ref() {
+        if image.restrict_to_ranges(&hits.ranges_with_context(hits.granularity())).runs().is_empty() {
+            bail!("No hit of the hit map falls in the data of the dump (0x0-0x{:X})", size);
+        }
+    }
+    Ok(())
+}
 
 pub fn display_banner() {
     let banner = "
@@ -23,37 +59,161 @@ pub fn display_banner() {
     println!("    {}", separator);
 }
 
-pub fn load_memory_image(path: &PathBuf) -> Result<MemoryImage> {
-    // Show a progress bar when opening large memory dumps
+fn spinner() -> crate::progress::Progress {
     let progress = ProgressBar::new_spinner();
     progress.set_style(
         ProgressStyle::with_template("{spinner:.green} {msg}")
             .unwrap()
             .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈"),
     );
-    progress.set_message(format!("Opening memory dump {}", path.display()));
+    crate::progress::attach(progress)
+}
+
+/// Open and map a dump file, inflating compressed acquisitions into a temp file
+fn map_dump_file(path: &Path, progress: &ProgressBar) -> Result<(Mmap, Option<Compression>)> {
+    let mut file = evidence::open_evidence(path)?;
+    let compression = compressed::detect_file(&mut file)?;
+    if let Some(kind) = compression {
+        file = compressed::decompress(file, kind, progress)?;
+    }
     
-    // Open the file and create memory map
-    let file = File::open(path)?;
-    progress.set_message("Memory mapping the file...");
+    // Create memory map
+    progress.set_message(format!("Memory mapping {}...", path.display()));
     let mmap = unsafe { MmapOptions::new().map(&file)? };
+    Ok((mmap, compression))
+}
+
+pub fn load_memory_image(path: &Path) -> Result<MemoryImage> {
+    // Show a progress bar when opening large memory dumps
+    let progress = spinner();
+    progress.set_message(format!("Opening memory dump {}", path.display()));
+    
+    let (mmap, compression) = map_dump_file(path, &progress)?;
     
-    // Create a MemoryImage from the memory map
     progress.finish_with_message(format!(
-        "Successfully mapped {} bytes from {}", 
+        "Successfully mapped {} bytes from {}{}", 
         mmap.len(), 
-        path.display()
+        path.display(),
+        compression.map(|c| format!(" ({} decompressed)", c)).unwrap_or_default()
+    ));
+    
+    let mut image = image_from_bytes(mmap)?;
+    image.info.compression = compression;
+    apply_load_range(&mut image)?;
+    Ok(image)
+}
+
+/// The segment number of `path`: the last run of digits in its file name,
+/// with the rest of the path so segments of different dumps don't interleave
+fn segment_number(path: &Path) -> (PathBuf, u128) {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let end = name.rfind(|c: char| c.is_ascii_digit()).map_or(0, |i| i + 1);
+    let start = name[..end].rfind(|c: char| !c.is_ascii_digit()).map_or(0, |i| i + 1);
+    let number = name[start..end].parse().unwrap_or(0);
+    (path.with_file_name(format!("{}{}", &name[..start], &name[end..])), number)
+}
+
+/// Resolve a segment glob (e.g. `dump.0*`) to its files in segment order
+pub fn segment_paths(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut paths = glob::glob(pattern)
+        .with_context(|| format!("Invalid segment pattern '{}'", pattern))?
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|p| p.is_file());
+    // Sort on the segment number, not lexically: dump.10 follows dump.9
+    paths.sort_by_cached_key(|p| segment_number(p));
+
+    if paths.is_empty() {
+        bail!("No dump segments match '{}'", pattern);
+    }
+    if let Some(pair) = paths.windows(2).find(|pair| segment_number(&pair[0]) == segment_number(&pair[1])) {
+        bail!("Segments '{}' and '{}' have the same number", pair[0].display(), pair[1].display());
+    }
+    Ok(paths)
+}
+
+/// Load a raw dump split across several files as one physical address space
+pub fn load_segmented_image(pattern: &str) -> Result<MemoryImage> {
+    let paths = segment_paths(pattern)?;
+    let progress = spinner();
+
+    let mut segments = Vec::with_capacity(paths.len());
+    let mut compression = None;
+    for path in &paths {
+        let (mmap, kind) = map_dump_file(path, &progress)?;
+        compression = compression.or(kind);
+        segments.push(mmap);
+    }
+
+    let mut image = MemoryImage::from_segments(segments);
+    progress.finish_with_message(format!(
+        "Successfully mapped {} bytes from {} segments{}",
+        image.size(),
+        paths.len(),
+        compression.map(|c| format!(" ({} decompressed)", c)).unwrap_or_default()
     ));
+    image.info.compression = compression;
+    apply_load_range(&mut image)?;
+    Ok(image)
+}
+
+/// Build an image from dump contents, detecting the container format
+pub fn image_from_bytes(data: impl Into<Segment>) -> Result<MemoryImage> {
+    let data = data.into();
+    let mut metadata = acquisition::parse(&data);
+    if elf_core::is_elf_core(&data) {
+        let core = elf_core::parse(&data)?;
+        let format = if core.vbox.is_some() { ImageFormat::VBoxElf } else { ImageFormat::ElfCore };
+        if let Some(vbox) = &core.vbox {
+            metadata.tool = Some(format!("VirtualBox {} (dumpvmcore)", vbox.version_string()));
+        }
+        let mut image = MemoryImage::with_runs(data, core.runs, format);
+        image.set_cpus(core.cpus);
+        image.info.acquisition = metadata;
+        return Ok(image);
+    }
+    
+    if minidump::is_minidump(&data) {
+        let dump = minidump::parse(&data)?;
+        let mut image = MemoryImage::with_runs(data, dump.runs, ImageFormat::Minidump);
+        image.info.user = Some(dump.user);
+        image.info.acquisition = metadata;
+        return Ok(image);
+    }
     
-    Ok(MemoryImage::new(mmap))
+    let mut image = MemoryImage::new(data);
+    image.info.acquisition = metadata;
+    Ok(image)
 }
 
-pub fn load_dump(path: PathBuf) -> Result<()> {
+/// Print the dump's acquisition metadata, if it records any
+pub fn print_acquisition(metadata: &AcquisitionMetadata) {
+    if metadata.is_empty() {
+        return;
+    }
+    println!("{}", "Acquisition:".bright_green());
+    if let Some(container) = &metadata.container {
+        println!("  {:<18} {}", "Container", container.bright_yellow());
+    }
+    if let Some(tool) = &metadata.tool {
+        println!("  {:<18} {}", "Tool", tool.bright_yellow());
+    }
+    for time in &metadata.times {
+        println!("  {:<18} {}", time.anchor.to_string(), time.time.bright_cyan());
+    }
+    if let Some(bugcheck) = &metadata.bugcheck {
+        println!("  {:<18} {}", "Bug check", bugcheck);
+    }
+}
+
+pub fn load_dump(path: Option<PathBuf>, segments: Option<String>) -> Result<()> {
     // Load the memory image
-    let memory_image = load_memory_image(&path)?;
+    let (memory_image, path_str) = match (segments, path) {
+        (Some(pattern), _) => (load_segmented_image(&pattern)?, pattern),
+        (None, Some(path)) => (load_memory_image(&path)?, path.display().to_string()),
+        (None, None) => bail!("Either a dump path or --segments is required"),
+    };
     
     let size_str = format!("{}", memory_image.size());
-    let path_str = format!("{}", path.display());
     
     println!("{} {} {} {}",
         "Mapped".bright_green(),
@@ -62,6 +222,44 @@ pub fn load_dump(path: PathBuf) -> Result<()> {
         path_str.bright_cyan().underline()
     );
     
+    if let Some(range) = memory_image.info.range {
+        let bytes: u64 = memory_image.runs().iter().map(|r| r.length).sum();
+        println!("{} {} ({} bytes of data, addresses as in the full dump)",
+            "Restricted to:".bright_green(), range.to_string().bright_yellow(), bytes);
+    }
+    if let Some(hits) = LOAD_HITS.lock().unwrap().as_ref() {
+        let bytes: u64 = memory_image.runs().iter().map(|r| r.length).sum();
+        println!("{} {} ranges ({} bytes of data, addresses as in the full dump)",
+            "Restricted to hits:".bright_green(), hits.ranges().len().to_string().bright_yellow(), bytes);
+    }
+    if let Some(compression) = memory_image.info.compression {
+        println!("{} {}", "Decompressed from:".bright_green(), compression.to_string().bright_yellow());
+    }
+    if memory_image.info.format != ImageFormat::Raw {
+        println!("{} {:?} ({} physical runs)",
+            "Container format:".bright_green(),
+            memory_image.info.format,
+            memory_image.runs().len().to_string().bright_yellow()
+        );
+    }
+    if let Some(user) = &memory_image.info.user {
+        println!("{} {} regions, {} modules, {} threads",
+            "User-mode process dump:".bright_green(),
+            user.regions.len().to_string().bright_yellow(),
+            user.modules.len().to_string().bright_yellow(),
+            user.threads.len().to_string().bright_yellow()
+        );
+    }
+    print_acquisition(&memory_image.info.acquisition);
+    for (i, cpu) in memory_image.info.cpus.iter().enumerate() {
+        println!("{} {} CR3={} RIP={}",
+            "CPU".bright_green(),
+            i,
+            format!("0x{:X}", cpu.cr3).bright_yellow(),
+            format!("0x{:X}", cpu.rip).bright_cyan()
+        );
+    }
+    
     // Print first 16 bytes in hex with colorized output
     print!("{} ", "First 16 bytes:".bright_green());
     
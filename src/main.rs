@@ -1,14 +1,11 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::{Parser, Subcommand, ValueEnum};
 use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
-mod arch;
-mod loader;
-mod paging;
-mod processes;
-mod modules;
-mod plugin;
+use rmf::{actions, allowlist::Allowlist, aslr, baseline, case, coverage, dlllist, dotnet, dtb, dumpdiff, dumpfiles, evidence, explain, hits, ioc, kdbg, limits, linux_profile, loader, osinfo, output, paging, processes, procdiff, progress, psxview, registry, modules, netscan, plugin, procdump, stats, symbols, timeline, usermode, vad, Architecture};
 
 /// Supported memory dump formats
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -36,12 +33,130 @@ enum OSType {
     Auto,
 }
 
+/// Paging scheme used to translate virtual addresses
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ArchType {
+    /// x86_64 4-level paging
+    X64,
+    /// 32-bit x86 with PAE
+    Pae,
+    /// 32-bit x86 without PAE
+    X86,
+    /// RISC-V Sv39
+    Riscv,
+    /// RISC-V Sv48
+    RiscvSv48,
+}
+
+impl From<ArchType> for Architecture {
+    fn from(arch: ArchType) -> Self {
+        match arch {
+            ArchType::X64 => Architecture::X86_64,
+            ArchType::Pae => Architecture::X86Pae,
+            ArchType::X86 => Architecture::X86,
+            ArchType::Riscv => Architecture::RiscvSv39,
+            ArchType::RiscvSv48 => Architecture::RiscvSv48,
+        }
+    }
+}
+
 /// Rust Memory Forensics Toolkit (rmf)
 #[derive(Parser)]
 #[command(name = "rmf", about = "Rust Memory Forensics Toolkit", version = "0.1.0")]
 struct Cli {
     #[command(subcommand)]
     cmd: Commands,
+    
+    /// Run at most this many analysis threads at once
+    #[arg(long, global = true)]
+    max_threads: Option<usize>,
+    
+    /// Cap heap memory, e.g. 4G (memory-mapped dumps do not count against it)
+    #[arg(long, global = true, value_parser = limits::parse_size)]
+    max_memory: Option<u64>,
+    
+    /// I/O scheduling class: idle, best-effort or best-effort:<0-7> (Linux)
+    #[arg(long, global = true)]
+    io_priority: Option<limits::IoPriority>,
+    
+    /// Only load this physical range of the dump, start:end in hex (end exclusive)
+    #[arg(long, global = true)]
+    range: Option<paging::AddressRange>,
+    
+    /// Only load the ranges of a hit map written by run-plugin --hits (or any start:end interval file)
+    #[arg(long, global = true)]
+    only_hits: Option<PathBuf>,
+    
+    /// Refuse to analyse dumps the current user could write to
+    #[arg(long, global = true)]
+    require_readonly: bool,
+    
+    /// Hash each dump when it is opened and fail if the hash differs at the end
+    #[arg(long, global = true)]
+    verify_hash: bool,
+    
+    /// Run this command for every finding, e.g. "notify.sh {plugin} {addr} {desc}" (repeatable)
+    #[arg(long, global = true)]
+    on_finding: Vec<String>,
+    
+    /// Run this command for every extracted file, e.g. "capa --json {path}" (repeatable)
+    #[arg(long, global = true)]
+    on_file: Vec<String>,
+    
+    /// Run at most this many action commands at once (default 4)
+    #[arg(long, global = true)]
+    action_jobs: Option<usize>,
+    
+    /// How to report progress of long scans
+    #[arg(long, global = true, value_enum, default_value_t = ProgressArg::Bar)]
+    progress: ProgressArg,
+    
+    /// Report results of list-procs, run-plugin, translate, extract-modules, timeline and diff as text, json or csv
+    #[arg(long, global = true, value_enum, default_value_t = OutputArg::Text)]
+    format: OutputArg,
+    
+    /// Write json or csv records to this file instead of stdout; a .db file collects them in a results database
+    #[arg(long, global = true)]
+    output_file: Option<PathBuf>,
+}
+
+/// Progress output format
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ProgressArg {
+    /// Animated progress bars
+    Bar,
+    /// JSON lines on stderr (stage, bytes done, rate, ETA)
+    Json,
+}
+
+impl From<ProgressArg> for progress::ProgressFormat {
+    fn from(format: ProgressArg) -> Self {
+        match format {
+            ProgressArg::Bar => progress::ProgressFormat::Bar,
+            ProgressArg::Json => progress::ProgressFormat::Json,
+        }
+    }
+}
+
+/// Result output format
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputArg {
+    /// Colored tables
+    Text,
+    /// One JSON document with the schema name, version and records
+    Json,
+    /// A header row and one row per record
+    Csv,
+}
+
+impl From<OutputArg> for output::OutputFormat {
+    fn from(format: OutputArg) -> Self {
+        match format {
+            OutputArg::Text => output::OutputFormat::Text,
+            OutputArg::Json => output::OutputFormat::Json,
+            OutputArg::Csv => output::OutputFormat::Csv,
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -49,11 +164,16 @@ enum Commands {
     /// Map a memory dump and display basic info
     Load {
         /// Path to the raw memory dump file
-        path: PathBuf,
+        #[arg(required_unless_present = "segments")]
+        path: Option<PathBuf>,
+        
+        /// Glob matching the parts of a split raw dump (e.g. "dump.0*")
+        #[arg(long, conflicts_with = "path")]
+        segments: Option<String>,
         
         /// Format of the memory dump
-        #[arg(short, long, value_enum, default_value_t = DumpFormat::Raw)]
-        format: DumpFormat,
+        #[arg(short = 'f', long, value_enum, default_value_t = DumpFormat::Raw)]
+        dump_format: DumpFormat,
     },
     
     /// List processes in a memory dump
@@ -68,9 +188,249 @@ enum Commands {
         /// Directory Table Base / CR3 value (hex)
         #[arg(short, long)]
         dtb: Option<String>,
+        
+        /// Also carve process structures from pool/slab memory to recover exited processes
+        #[arg(long)]
+        scan_pool: bool,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
+        
+        /// Linux profile JSON, or a directory of profiles to pick from by kernel release
+        #[arg(long)]
+        profile: Option<PathBuf>,
+        
+        /// Show the parent/child hierarchy and flag orphaned or unexpected parentage
+        #[arg(long)]
+        tree: bool,
+        
+        /// Also print each process's working directory and environment variables (Windows)
+        #[arg(long)]
+        env: bool,
+        
+        /// Also print each process's token: user and group SIDs and enabled privileges (Windows)
+        #[arg(long)]
+        tokens: bool,
+    },
+    
+    /// Scan pool memory for EPROCESS structures and report terminated and unlinked processes
+    Psscan {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Directory Table Base / CR3 value (hex); needed to compare with the active process list
+        #[arg(short, long)]
+        dtb: Option<String>,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
+    },
+    
+    /// Compare the process list with pool, thread and CID table views to find hidden processes
+    Psxview {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
+    },
+    
+    /// List the threads of each process and flag those starting outside any loaded module
+    Threads {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Only list the threads of this process
+        #[arg(long)]
+        pid: Option<u32>,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
+    },
+    
+    /// List each process's virtual address descriptors: range, type, protection and mapped file
+    Vadinfo {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Only list the regions of this process
+        #[arg(long)]
+        pid: Option<u32>,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
+    },
+    
+    /// List each process's DLLs from the PEB loader lists and flag unlinked or unlisted modules
+    Dlllist {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Only list the DLLs of this process
+        #[arg(long)]
+        pid: Option<u32>,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
+    },
+    
+    /// List the .NET runtime and assemblies of each process, flagging those loaded from memory
+    Dotnet {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Only search this process
+        #[arg(long)]
+        pid: Option<u32>,
+        
+        /// Write the assemblies to this directory, rebuilt into file layout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
+    },
+    
+    /// Write a process's memory regions, or its rebuilt executable, to a directory
+    DumpProcess {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Process to dump
+        #[arg(long)]
+        pid: u32,
+        
+        /// Output directory
+        #[arg(short, long)]
+        output: PathBuf,
+        
+        /// What to write
+        #[arg(long, value_enum, default_value_t = DumpModeArg::Regions)]
+        mode: DumpModeArg,
+        
+        /// With --mode pe, write the image as mapped, without rebuilding it
+        #[arg(long)]
+        raw: bool,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
+    },
+    
+    /// Reconstruct files cached in memory from their file objects' sections and cache maps
+    DumpFiles {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Only files whose name matches this regular expression (case-insensitive)
+        #[arg(long)]
+        regex: Option<String>,
+        
+        /// Output directory
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    
+    /// List kernel modules from PsLoadedModuleList and MmLd pool allocations, flagging unlinked drivers
+    Modscan {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Directory Table Base / CR3 value (hex) of the kernel
+        #[arg(short, long)]
+        dtb: Option<String>,
+    },
+    
+    /// List network connections (or with --arp the neighbor cache) from tcpip.sys on Windows, kernel structures on Linux
+    Netscan {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Operating system type
+        #[arg(short, long, value_enum, default_value_t = OSType::Windows)]
+        os: OSType,
+        
+        /// Directory Table Base / CR3 value (hex) of the kernel
+        #[arg(short, long)]
+        dtb: Option<String>,
+        
+        /// Linux profile JSON, or a directory of profiles to pick from by kernel release
+        #[arg(long)]
+        profile: Option<PathBuf>,
+        
+        /// List the ARP and IPv6 neighbor cache instead of connections
+        #[arg(long)]
+        arp: bool,
     },
     
-    /// Extract loaded modules from a memory dump
+    /// Extract kernel modules, including unlinked ones found in pool, from a memory dump
     ExtractModules {
         /// Path to the memory dump file
         dump: PathBuf,
@@ -78,9 +438,81 @@ enum Commands {
         /// Output directory for extracted modules
         output: PathBuf,
         
-        /// Only extract modules matching this pattern
+        /// Only extract modules whose name or path matches this glob pattern, e.g. "*.sys"
         #[arg(short, long)]
         pattern: Option<String>,
+        
+        /// Only extract modules whose name or path matches this regex (case-insensitive)
+        #[arg(long)]
+        regex: Option<String>,
+        
+        /// Extract the DLLs of this process instead of kernel modules
+        #[arg(long)]
+        pid: Option<u32>,
+        
+        /// Flag modules whose code differs from this baseline of a golden image
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+        
+        /// Save the code hashes and versions of the extracted modules as a baseline
+        #[arg(long)]
+        save_baseline: Option<PathBuf>,
+        
+        /// Write module bodies as mapped, without rebuilding them into PE files
+        #[arg(long)]
+        raw: bool,
+        
+        /// Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: Option<String>,
+    },
+    
+    /// Search memory for IPs, domains, file names, mutexes and hashes from an IOC list, naming the processes that map each hit
+    Ioc {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// JSON file of indicators: {"ips": [], "domains": [], "filenames": [], "mutexes": [], "hashes": []}
+        #[arg(long)]
+        iocs: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex); needed to attribute hits to processes
+        #[arg(short, long)]
+        dtb: Option<String>,
+        
+        /// Also write the hits to this file as JSON
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
+    },
+    
+    /// Timeline of process, thread, network, registry key, Shimcache/Amcache and prefetch timestamps
+    Timeline {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex); needed for threads and registry keys
+        #[arg(short, long)]
+        dtb: Option<String>,
+        
+        /// Write the timeline to this file: a mactime bodyfile, or CSV for a .csv name
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Symbol cache directory; fetches the kernel PDB for exact structure offsets
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long, requires = "symbols")]
+        offline: bool,
     },
     
     /// Run a memory analysis plugin
@@ -91,11 +523,204 @@ enum Commands {
         /// Name of the plugin to run
         plugin: String,
         
-        /// Export findings to this file (CSV format)
+        /// Export findings to this file: CSV, JSON / JSON lines for a .json / .jsonl name, or a .db results database
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Only report findings belonging to this container (full or short ID)
+        #[arg(long)]
+        container: Option<String>,
+        
+        /// Linux profile JSON, or a directory of profiles, for attributing processes to --container by their cgroups
+        /// and ssh_keys findings to the ssh-agent/sshd tasks holding them
+        #[arg(long)]
+        profile: Option<PathBuf>,
+        
+        /// Also report findings in freed pool blocks and transition pages
+        #[arg(long)]
+        include_freed: bool,
+        
+        /// Case allowlist of known-benign findings (repeatable; the global one always applies)
+        #[arg(long)]
+        allowlist: Vec<PathBuf>,
+        
+        /// List the findings the allowlists suppressed
+        #[arg(long)]
+        show_suppressed: bool,
+        
+        /// Case directory: record the findings there and show their triage state
+        #[arg(long)]
+        case: Option<PathBuf>,
+        
+        /// Only write the 4 KiB blocks holding findings to this hit map, for a later --only-hits pass
+        #[arg(long)]
+        hits: Option<PathBuf>,
+        
+        /// Print the secrets plugins such as lsadump redact by default
+        #[arg(long)]
+        reveal: bool,
+        
+        /// YARA rule file for the yara plugin
+        #[arg(long)]
+        rules: Option<PathBuf>,
+        
+        /// With --rules, also scan the virtual memory of each process
+        #[arg(long)]
+        processes: bool,
+        
+        /// Signature file (TOML) for the signature_scan plugin
+        #[arg(long)]
+        signatures: Option<PathBuf>,
+        
+        /// Plugin setting such as min_string_len=12 (repeatable; list-plugins shows each plugin's parameters)
+        #[arg(long = "set", value_name = "KEY=VALUE", value_parser = plugin::parse_setting)]
+        settings: Vec<(String, String)>,
+    },
+    
+    /// Run several plugins, reporting the quick structure walks while heavy scans continue
+    Analyze {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Plugins to run (comma-separated, default: all)
+        #[arg(short, long, value_delimiter = ',')]
+        plugins: Vec<String>,
+        
+        /// Print the plugin pipeline, scan passes and requirements without running it
+        #[arg(long)]
+        plan: bool,
+        
+        /// Export the findings of every plugin to this file: CSV, JSON / JSON lines for a .json / .jsonl name, or a .db results database
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
     
+    /// Show the modules and threads of a user-mode process dump (minidump)
+    UserInfo {
+        /// Path to the process dump file
+        dump: PathBuf,
+    },
+    
+    /// Report the ASLR layout of a user-mode process dump and flag anomalies
+    Aslr {
+        /// Path to the process dump file
+        dump: PathBuf,
+    },
+    
+    /// Report the composition of physical memory and how much of it is identified
+    Stats {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: Option<String>,
+    },
+    
+    /// Run plugins and report which parts of physical memory they examined
+    Coverage {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Plugins to run (comma-separated, default: all)
+        #[arg(short, long, value_delimiter = ',')]
+        plugins: Vec<String>,
+    },
+    
+    /// Identify the operating system and kernel version of a dump
+    Osinfo {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: Option<String>,
+    },
+    
+    /// Locate the Windows KDBG block and the kernel globals it records
+    Kdbg {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: Option<String>,
+    },
+    
+    /// Identify the kernel PDB, fetch it and print the structure offsets it defines
+    Symbols {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Symbol cache directory
+        #[arg(long, default_value = "symbols")]
+        cache: PathBuf,
+        
+        /// Symbol server URL
+        #[arg(long, default_value = symbols::DEFAULT_SYMBOL_SERVER)]
+        server: String,
+        
+        /// Only use PDBs already in the symbol cache
+        #[arg(long)]
+        offline: bool,
+    },
+    
+    /// Build and manage OS profiles
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+    
+    /// Read registry hives loaded in a Windows dump
+    Reg {
+        #[command(subcommand)]
+        command: RegCommand,
+    },
+    
+    /// Compare one process between two captures of the same system
+    DiffProc {
+        /// Earlier memory dump
+        before: PathBuf,
+        
+        /// Later memory dump
+        after: PathBuf,
+        
+        /// Process ID to compare
+        #[arg(long)]
+        pid: u32,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+    },
+    
+    /// Compare processes, modules, connections and plugin findings between two captures of the same host
+    Diff {
+        /// Earlier memory dump
+        dump_a: PathBuf,
+        
+        /// Later memory dump
+        dump_b: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex), or one per dump separated by a comma
+        #[arg(short, long, value_delimiter = ',', num_args = 1..=2)]
+        dtb: Vec<String>,
+        
+        /// Plugins whose findings to compare (comma-separated)
+        #[arg(short, long, value_delimiter = ',', default_value = "malfind")]
+        plugins: Vec<String>,
+    },
+    
+    /// Triage findings and report on a case directory
+    Case {
+        #[command(subcommand)]
+        command: CaseCommand,
+    },
+    
     /// List available plugins
     ListPlugins,
     
@@ -104,13 +729,29 @@ enum Commands {
         /// Path to the memory dump file
         dump: PathBuf,
         
-        /// Type of scan to perform (strings, pe, urls, etc.)
+        /// Type of scan to perform (strings, pe, elf, macho, regex, bytes, signatures)
         #[arg(short, long, default_value = "strings")]
         scan_type: String,
         
+        /// Regular expression for the regex scan type, or byte signature such as "4D 5A ?? ?? 90" for bytes
+        #[arg(short, long)]
+        pattern: Option<String>,
+        
+        /// Signature file (TOML) for the signatures scan type
+        #[arg(long)]
+        signatures: Option<PathBuf>,
+        
         /// Minimum match length for string scans
         #[arg(short, long, default_value_t = 8)]
         min_length: usize,
+        
+        /// Also report findings in freed pool blocks and transition pages
+        #[arg(long)]
+        include_freed: bool,
+        
+        /// Only write the 4 KiB blocks holding hits to this hit map, for a later --only-hits pass
+        #[arg(long)]
+        hits: Option<PathBuf>,
     },
     
     /// Translate virtual memory addresses to physical
@@ -124,6 +765,187 @@ enum Commands {
         /// Directory Table Base / CR3 value (hex)
         #[arg(short, long)]
         dtb: Option<String>,
+        
+        /// Paging scheme of the dumped system
+        #[arg(short, long, value_enum, default_value_t = ArchType::X64)]
+        arch: ArchType,
+    },
+    
+    /// Print the structure an address falls in (EPROCESS, PE header, page table entry) field by field
+    Explain {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Address to explain (hex); virtual when --dtb is given
+        address: String,
+        
+        /// Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: Option<String>,
+        
+        /// Treat the address as physical even with --dtb
+        #[arg(long)]
+        physical: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// Extract task_struct/mm_struct offsets from a kernel's DWARF into a profile JSON
+    BuildLinux {
+        /// vmlinux with debug info, or module debuginfo built against the same kernel
+        debug_file: PathBuf,
+        
+        /// System.map with the kernel's symbol addresses
+        #[arg(long)]
+        system_map: Option<PathBuf>,
+        
+        /// Kernel release, when the debug file does not record it
+        #[arg(long)]
+        release: Option<String>,
+        
+        /// Output file, or directory for linux-<release>.json
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+}
+
+/// Output of dump-process
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DumpModeArg {
+    /// One raw file per VAD region, unresident pages zero-filled
+    Regions,
+    /// The main executable rebuilt into a PE file
+    Pe,
+}
+
+impl DumpModeArg {
+    fn with_raw(self, raw: bool) -> procdump::DumpMode {
+        match self {
+            DumpModeArg::Regions => procdump::DumpMode::Regions,
+            DumpModeArg::Pe => procdump::DumpMode::Pe { raw },
+        }
+    }
+}
+
+/// Review outcome of a finding
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum TriageArg {
+    /// The finding is a real indicator
+    Confirmed,
+    /// The finding is benign or a misdetection
+    FalsePositive,
+    /// The finding needs another look
+    NeedsReview,
+}
+
+impl From<TriageArg> for case::TriageState {
+    fn from(state: TriageArg) -> Self {
+        match state {
+            TriageArg::Confirmed => case::TriageState::Confirmed,
+            TriageArg::FalsePositive => case::TriageState::FalsePositive,
+            TriageArg::NeedsReview => case::TriageState::NeedsReview,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum RegCommand {
+    /// List the loaded hives, or a key's subkeys and values
+    List {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Key to list, e.g. 'HKLM\SOFTWARE\Microsoft\Windows\CurrentVersion\Run'
+        #[arg(short, long)]
+        key: Option<String>,
+    },
+    
+    /// Rebuild every loaded hive from memory into a hive file
+    DumpHive {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+        
+        /// Output directory
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    
+    /// Shimcache and Amcache entries as a timeline of binaries present or run
+    Timeline {
+        /// Path to the memory dump file
+        dump: PathBuf,
+        
+        /// Kernel Directory Table Base / CR3 value (hex)
+        #[arg(short, long)]
+        dtb: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CaseCommand {
+    /// Mark a finding as confirmed, false-positive or needs-review
+    Triage {
+        /// Case directory
+        case: PathBuf,
+        
+        /// Finding ID, as shown in the ID column
+        id: String,
+        
+        /// Review outcome
+        #[arg(value_enum)]
+        state: TriageArg,
+        
+        /// Reason for the decision
+        #[arg(short, long)]
+        note: Option<String>,
+        
+        /// Analyst name (default: $RMF_ANALYST or the login name)
+        #[arg(long)]
+        analyst: Option<String>,
+    },
+    
+    /// List the case's findings with their triage state
+    Findings {
+        /// Case directory
+        case: PathBuf,
+        
+        /// Also print every analyst's decisions
+        #[arg(long)]
+        history: bool,
+    },
+    
+    /// Add the findings and triage decisions of another copy of the case
+    Merge {
+        /// Case directory to merge into
+        case: PathBuf,
+        
+        /// Copy of the case another analyst worked on
+        from: PathBuf,
+    },
+    
+    /// Remove a lock left behind by an interrupted run
+    Unlock {
+        /// Case directory
+        case: PathBuf,
+    },
+    
+    /// Generate a Markdown report of the case grouped by triage state
+    Report {
+        /// Case directory
+        case: PathBuf,
+        
+        /// Write the report to this file instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 }
 
@@ -132,6 +954,75 @@ fn parse_hex_address(addr_str: &str) -> Result<u64> {
     Ok(u64::from_str_radix(cleaned, 16)?)
 }
 
+/// Print each table entry read by a page walk, `!pte` style
+fn print_page_walk(walk: &paging::PageWalk) {
+    for step in &walk.steps {
+        let size = match step.page_size {
+            Some(size) if step.leaf && size >= 1 << 20 => format!("{}MB page", size >> 20),
+            Some(size) if step.leaf => format!("{}KB page", size >> 10),
+            _ => String::new(),
+        };
+        println!("  {:<6} [{:>3X}] at 0x{:<12X} = {}  {:<24} {}",
+            step.level.bright_cyan(),
+            step.index,
+            step.entry_addr,
+            format!("0x{:016X}", step.value).bright_yellow(),
+            step.flags,
+            size.bright_green()
+        );
+    }
+}
+
+/// Print the walk of a translation, then its result and the bytes there
+fn print_translation(memory_image: &paging::MemoryImage, walk: &paging::PageWalk) {
+    print_page_walk(walk);
+    match walk.pa {
+        Some(phys_addr) => {
+            println!("{} {} {} {}",
+                "Virtual address".bright_green(),
+                format!("0x{:X}", walk.va).bright_yellow(),
+                "translates to physical address".bright_green(),
+                format!("0x{:X}", phys_addr).bright_cyan()
+            );
+            
+            // Display memory at that location
+            if let Some(bytes) = memory_image.get_bytes(phys_addr as usize, 16) {
+                println!("{}", "Memory contents:".bright_green());
+                print!("  ");
+                for (i, byte) in bytes.iter().enumerate() {
+                    let byte_str = format!("{:02X}", byte);
+                    let colored_byte = if i % 2 == 0 {
+                        byte_str.bright_yellow()
+                    } else {
+                        byte_str.bright_cyan()
+                    };
+                    
+                    print!("{} ", colored_byte);
+                }
+                println!();
+                
+                // Also show as ASCII
+                print!("  ");
+                for &byte in bytes {
+                    if (32..=126).contains(&byte) {
+                        print!("{} ", (byte as char).to_string().bright_green());
+                    } else {
+                        print!("{} ", ".".bright_red());
+                    }
+                }
+                println!();
+            }
+        },
+        None => {
+            println!("{} {}{}", 
+                "Could not translate virtual address".bright_red(),
+                format!("0x{:X}", walk.va).bright_yellow(),
+                walk.fault.as_ref().map_or(String::new(), |fault| format!(": {}", fault))
+            );
+        },
+    }
+}
+
 fn main() -> Result<()> {
     // Enable colors in Windows terminals
     #[cfg(target_os = "windows")]
@@ -140,43 +1031,243 @@ fn main() -> Result<()> {
     // Always enable colors
     colored::control::set_override(true);
 
-    loader::display_banner();
     // Initialize built-in plugins so they are available for commands
     plugin::init_plugins();
     let cli = Cli::parse();
+    output::set_output_format(cli.format.into(), cli.output_file.clone());
+    if !output::structured() {
+        loader::display_banner();
+    }
+    
+    // Apply resource limits before any dump is opened
+    let mut resource_limits = limits::ResourceLimits::new();
+    if let Some(threads) = cli.max_threads {
+        resource_limits = resource_limits.max_threads(threads);
+    }
+    if let Some(bytes) = cli.max_memory {
+        resource_limits = resource_limits.max_memory(bytes);
+    }
+    if let Some(priority) = cli.io_priority {
+        resource_limits = resource_limits.io_priority(priority);
+    }
+    resource_limits.apply()?;
+    progress::set_progress_format(cli.progress.into());
+    loader::set_load_range(cli.range);
+    loader::set_load_hits(cli.only_hits.as_deref().map(hits::HitMap::load).transpose()?);
+    evidence::EvidencePolicy::new()
+        .require_readonly(cli.require_readonly)
+        .verify_hash(cli.verify_hash)
+        .apply();
+    let mut actions = actions::Actions::new();
+    for template in &cli.on_finding {
+        actions = actions.on_finding(template)?;
+    }
+    for template in &cli.on_file {
+        actions = actions.on_file(template)?;
+    }
+    if let Some(jobs) = cli.action_jobs {
+        actions = actions.jobs(jobs);
+    }
+    actions.apply();
     
     match cli.cmd {
-        Commands::Load { path, format } => {
-            println!("Loading memory dump in {} format", match format {
+        Commands::Load { path, segments, dump_format } => {
+            println!("Loading memory dump in {} format", match dump_format {
                 DumpFormat::Raw => "Raw".bright_green(),
                 DumpFormat::Crashdump => "Windows Crashdump".bright_green(),
                 DumpFormat::Vmem => "VMware".bright_green(),
                 DumpFormat::Profile => "Volatility Profile".bright_green(),
             });
-            loader::load_dump(path)?
+            loader::load_dump(path, segments)?
         },
         
-        Commands::ListProcs { dump, os, dtb } => {
-            if let Some(dtb_str) = dtb {
-                let dtb_val = parse_hex_address(&dtb_str)?;
-                println!("Using DTB/CR3: {}", format!("0x{:X}", dtb_val).bright_yellow());
-                // In a real implementation, we'd set the DTB in the memory image
+        Commands::ListProcs { dump, os, dtb, scan_pool, symbols, offline, profile, tree, env, tokens } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            if let Some(dtb_val) = dtb {
+                rmf::status!("Using DTB/CR3: {}", format!("0x{:X}", dtb_val).bright_yellow());
             }
-            processes::list_processes(dump)?
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            let os_type = match os {
+                OSType::Windows => "windows",
+                OSType::Linux => "linux",
+                OSType::MacOS => "macos",
+                OSType::Auto => "auto",
+            };
+            let options = processes::ListOptions { scan_pool, tree, env, tokens };
+            processes::list_processes(dump, os_type, dtb, store, profile, options)?
+        },
+        
+        Commands::Psscan { dump, dtb, symbols, offline } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            processes::psscan(dump, dtb, store)?
+        },
+        
+        Commands::Psxview { dump, dtb, symbols, offline } => {
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            psxview::report_psxview(dump, parse_hex_address(&dtb)?, store)?
+        },
+        
+        Commands::Threads { dump, dtb, pid, symbols, offline } => {
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            processes::list_threads(dump, parse_hex_address(&dtb)?, pid, store)?
+        },
+        
+        Commands::Vadinfo { dump, dtb, pid, symbols, offline } => {
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            vad::list_vads(dump, parse_hex_address(&dtb)?, pid, store)?
+        },
+        
+        Commands::Dlllist { dump, dtb, pid, symbols, offline } => {
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            dlllist::list_dlls(dump, parse_hex_address(&dtb)?, pid, store)?
+        },
+        
+        Commands::Dotnet { dump, dtb, pid, output, symbols, offline } => {
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            dotnet::list_assemblies(dump, parse_hex_address(&dtb)?, pid, output, store)?
+        },
+        
+        Commands::DumpProcess { dump, dtb, pid, output, mode, raw, symbols, offline } => {
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            procdump::dump_process(dump, parse_hex_address(&dtb)?, pid, output, mode.with_raw(raw), store)?
+        },
+        
+        Commands::DumpFiles { dump, dtb, regex, output } => {
+            dumpfiles::dump_files(dump, parse_hex_address(&dtb)?, regex.as_deref(), output)?
+        },
+        
+        Commands::Ioc { dump, iocs, dtb, output, symbols, offline } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            ioc::report_iocs(dump, &iocs, dtb, output, store)?
         },
         
-        Commands::ExtractModules { dump, output, pattern } => {
-            if let Some(pat) = pattern {
-                println!("Extracting modules matching: {}", pat.bright_yellow());
+        Commands::Timeline { dump, dtb, output, symbols, offline } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            let store = symbols.map(|dir| symbols::SymbolStore::new(dir).offline(offline));
+            timeline::report_timeline(dump, dtb, output, store)?
+        },
+        
+        Commands::Modscan { dump, dtb } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            modules::modscan(dump, dtb)?
+        },
+        
+        Commands::Netscan { dump, os, dtb, profile, arp } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            let os_type = match os {
+                OSType::Windows => "windows",
+                OSType::Linux => "linux",
+                OSType::MacOS => "macos",
+                OSType::Auto => "auto",
+            };
+            netscan::netscan(dump, os_type, dtb, profile, arp)?
+        },
+        
+        Commands::ExtractModules { dump, output, pattern, regex, pid, dtb, baseline, save_baseline, raw } => {
+            for pat in pattern.iter().chain(&regex) {
+                rmf::status!("Extracting modules matching: {}", pat.bright_yellow());
             }
-            modules::extract_modules(dump, output)?
+            if let Some(pid) = pid {
+                rmf::status!("Extracting the DLLs of process {}", pid.to_string().bright_yellow());
+            }
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            let options = modules::ExtractOptions {
+                pattern,
+                regex,
+                pid,
+                baseline: baseline.as_deref().map(baseline::Baseline::load).transpose()?,
+                save_baseline,
+                raw,
+            };
+            modules::extract_modules(dump, output, dtb, options)?
         },
         
-        Commands::RunPlugin { dump, plugin, output } => {
+        Commands::RunPlugin { dump, plugin, output, container, profile, include_freed, allowlist, show_suppressed, case, hits, reveal, rules: yara_rules, processes, signatures, settings } => {
             if let Some(out_path) = &output {
-                println!("Will export findings to: {}", out_path.display().to_string().bright_cyan());
+                rmf::status!("Will export findings to: {}", out_path.display().to_string().bright_cyan());
+            }
+            let mut rules = Allowlist::load_global()?;
+            for path in &allowlist {
+                rules.extend(Allowlist::load(path)?);
+            }
+            let options = plugin::RunOptions {
+                export: output,
+                container,
+                profile,
+                include_freed,
+                allowlist: rules,
+                show_suppressed,
+                case,
+                hits,
+                reveal,
+                rules: yara_rules,
+                processes,
+                signatures,
+                settings: settings.into_iter().collect(),
+            };
+            plugin::run_plugin(dump, plugin, options)?
+        },
+        
+        Commands::Analyze { dump, plugins, plan, output } => plugin::analyze(dump, plugins, plan, output)?,
+        
+        Commands::UserInfo { dump } => usermode::list_user_space(dump)?,
+        
+        Commands::Aslr { dump } => aslr::report_aslr(dump)?,
+        
+        Commands::Stats { dump, dtb } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            stats::print_stats(dump, dtb)?
+        },
+        
+        Commands::Coverage { dump, plugins } => coverage::report_coverage(dump, plugins)?,
+        
+        Commands::Osinfo { dump, dtb } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            osinfo::report_osinfo(dump, dtb)?
+        },
+        
+        Commands::Kdbg { dump, dtb } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            kdbg::report_kdbg(dump, dtb)?
+        },
+        
+        Commands::Symbols { dump, dtb, cache, server, offline } => {
+            let store = symbols::SymbolStore::new(cache).server(server).offline(offline);
+            symbols::report_symbols(dump, parse_hex_address(&dtb)?, store)?
+        },
+        
+        Commands::Profile { command } => match command {
+            ProfileCommand::BuildLinux { debug_file, system_map, release, output } => {
+                linux_profile::build_linux_profile(debug_file, system_map, release, output)?
+            }
+        },
+        
+        Commands::Reg { command } => match command {
+            RegCommand::List { dump, dtb, key } => registry::list_registry(dump, parse_hex_address(&dtb)?, key)?,
+            RegCommand::DumpHive { dump, dtb, output } => registry::dump_hives(dump, parse_hex_address(&dtb)?, output)?,
+            RegCommand::Timeline { dump, dtb } => registry::report_execution_timeline(dump, parse_hex_address(&dtb)?)?,
+        },
+        
+        Commands::Diff { dump_a, dump_b, dtb, plugins } => {
+            let dtbs = dtb.iter().map(|d| parse_hex_address(d)).collect::<Result<Vec<_>>>()?;
+            let dtb_a = dtbs.first().copied();
+            dumpdiff::report_dump_diff(dump_a, dump_b, [dtb_a, dtbs.get(1).copied().or(dtb_a)], plugins)?
+        },
+        
+        Commands::DiffProc { before, after, pid, dtb } => {
+            procdiff::report_process_diff(before, after, pid, parse_hex_address(&dtb)?)?
+        },
+        
+        Commands::Case { command } => match command {
+            CaseCommand::Triage { case: dir, id, state, note, analyst } => {
+                case::triage_finding(dir, id, state.into(), note, analyst)?
             }
-            plugin::run_plugin(dump, plugin, output)?
+            CaseCommand::Findings { case: dir, history } => case::list_triage(dir, history)?,
+            CaseCommand::Merge { case: dir, from } => case::merge_cases(dir, from)?,
+            CaseCommand::Unlock { case: dir } => case::unlock_case(dir)?,
+            CaseCommand::Report { case: dir, output } => case::report_case(dir, output)?,
         },
         
         Commands::ListPlugins => {
@@ -196,29 +1287,71 @@ fn main() -> Result<()> {
                         desc.bright_white(),
                         version.bright_blue()
                     );
+                    let parameters = registry.get(&name).map(|p| p.parameters()).unwrap_or_default();
+                    if !parameters.is_empty() {
+                        let parameters: Vec<String> = parameters.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+                        println!("      parameters: {}", parameters.join(", "));
+                    }
                 }
             }
         },
         
-        Commands::Scan { dump, scan_type, min_length } => {
-            println!("Scanning memory dump for {} with minimum length {}", 
-                scan_type.bright_yellow(),
-                min_length.to_string().bright_cyan()
-            );
-            
-            // For now, just run the appropriate plugin
-            let plugin_name = match scan_type.as_str() {
-                "strings" => "string_carve",
-                "pe" => "pe_scanner",
-                _ => "string_carve",  // Default to string carving
-            };
-            
-            plugin::run_plugin(dump, plugin_name.to_string(), None)?
+        Commands::Scan { dump, scan_type, min_length, include_freed, hits, pattern, signatures } => {
+            if scan_type == "regex" || scan_type == "bytes" {
+                let pattern = pattern.with_context(|| format!("--scan-type {} needs --pattern", scan_type))?;
+                rmf::status!("Scanning memory dump for {} {}", scan_type, pattern.bright_yellow());
+                let scanner = if scan_type == "regex" {
+                    plugin::PatternScanner::regex(&pattern)?
+                } else {
+                    plugin::PatternScanner::bytes(&pattern)?
+                };
+                let options = plugin::RunOptions {
+                    include_freed,
+                    allowlist: Allowlist::load_global()?,
+                    hits,
+                    ..Default::default()
+                };
+                plugin::run_scanner(dump, &scanner, options)?
+            } else if scan_type == "signatures" {
+                let path = signatures.context("--scan-type signatures needs --signatures")?;
+                let scanner = plugin::SignatureScanner::load(&path)?;
+                rmf::status!("Scanning memory dump for {} signatures from {}", scanner.len().to_string().bright_yellow(), path.display().to_string().bright_cyan());
+                let options = plugin::RunOptions {
+                    include_freed,
+                    allowlist: Allowlist::load_global()?,
+                    hits,
+                    ..Default::default()
+                };
+                plugin::run_scanner(dump, &scanner, options)?
+            } else {
+                rmf::status!("Scanning memory dump for {} with minimum length {}", 
+                    scan_type.bright_yellow(),
+                    min_length.to_string().bright_cyan()
+                );
+                
+                let (plugin_name, settings) = match scan_type.as_str() {
+                    "pe" => ("pe_scanner", HashMap::new()),
+                    "elf" => ("elf_scanner", HashMap::new()),
+                    "macho" => ("macho_scanner", HashMap::new()),
+                    // Default to string carving
+                    _ => ("string_carve", HashMap::from([("min_string_len".to_string(), min_length.to_string())])),
+                };
+                
+                let options = plugin::RunOptions {
+                    include_freed,
+                    allowlist: Allowlist::load_global()?,
+                    hits,
+                    settings,
+                    ..Default::default()
+                };
+                plugin::run_plugin(dump, plugin_name.to_string(), options)?
+            }
         },
         
-        Commands::Translate { dump, address, dtb } => {
+        Commands::Translate { dump, address, dtb, arch } => {
             // Load the memory image
             let mut memory_image = loader::load_memory_image(&dump)?;
+            memory_image.set_arch(arch.into());
             
             // Parse the virtual address
             let virt_addr = parse_hex_address(&address)?;
@@ -229,53 +1362,48 @@ fn main() -> Result<()> {
                 memory_image.set_cr3(dtb_val);
             }
             
-            // Translate the address
-            match memory_image.virt_to_phys(virt_addr) {
-                Some(phys_addr) => {
-                    println!("{} {} {} {}",
-                        "Virtual address".bright_green(),
-                        format!("0x{:X}", virt_addr).bright_yellow(),
-                        "translates to physical address".bright_green(),
-                        format!("0x{:X}", phys_addr).bright_cyan()
-                    );
-                    
-                    // Display memory at that location
-                    if let Some(bytes) = memory_image.get_bytes(phys_addr as usize, 16) {
-                        println!("{}", "Memory contents:".bright_green());
-                        print!("  ");
-                        for (i, byte) in bytes.iter().enumerate() {
-                            let byte_str = format!("{:02X}", byte);
-                            let colored_byte = if i % 2 == 0 {
-                                byte_str.bright_yellow()
-                            } else {
-                                byte_str.bright_cyan()
-                            };
-                            
-                            print!("{} ", colored_byte);
-                        }
-                        println!();
-                        
-                        // Also show as ASCII
-                        print!("  ");
-                        for &byte in bytes {
-                            if byte >= 32 && byte <= 126 {
-                                print!("{} ", (byte as char).to_string().bright_green());
-                            } else {
-                                print!("{} ", ".".bright_red());
-                            }
-                        }
-                        println!();
-                    }
-                },
-                None => {
-                    println!("{} {}", 
-                        "Could not translate virtual address".bright_red(),
-                        format!("0x{:X}", virt_addr).bright_yellow()
-                    );
-                },
+            let class = paging::classify_address(memory_image.info.arch, virt_addr);
+            rmf::status!("{} {}", "Address class:".bright_green(), class.to_string().bright_yellow());
+            
+            // Without a DTB, try the page table roots found in the dump, best first
+            let searchable = class != paging::AddressClass::NonCanonical && memory_image.info.user.is_none();
+            if memory_image.info.dtb.is_none() && searchable {
+                let progress = progress::attach(ProgressBar::new(100));
+                progress.set_style(ProgressStyle::with_template(
+                    "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+                )?.progress_chars("#>-"));
+                let candidates = dtb::find_dtb_candidates(&memory_image, &progress);
+                progress.finish_and_clear();
+                rmf::status!("{} {}", "DTB candidates found:".bright_green(), candidates.len().to_string().bright_yellow());
+                let found = dtb::translate_with_candidates(&mut memory_image, &candidates, virt_addr);
+                match found {
+                    Some((index, _)) => rmf::status!("{} {} (candidate {} of {})",
+                        "Using DTB".bright_green(), candidates[index].to_string().bright_yellow(), index + 1, candidates.len()),
+                    None if !candidates.is_empty() => rmf::status!("{}", "No DTB candidate maps this address".bright_red()),
+                    None => {}
+                }
+            }
+            
+            // Show every entry of the walk, then the result
+            let walk = memory_image.page_walk(virt_addr);
+            if output::structured() {
+                output::emit(&dump, &[paging::TranslationRecord::new(&memory_image, &walk)])?;
+            } else {
+                print_translation(&memory_image, &walk);
             }
         },
+        
+        Commands::Explain { dump, address, dtb, physical } => {
+            let dtb = dtb.map(|d| parse_hex_address(&d)).transpose()?;
+            explain::explain_address(dump, parse_hex_address(&address)?, dtb, physical)?
+        },
     }
     
+    // Evidence must be exactly as it was before the command ran
+    for record in evidence::verify_session()? {
+        if let Some(sha256) = record.sha256 {
+            rmf::status!("{} {} (SHA-256 {})", "Evidence unchanged:".bright_green(), record.path.display(), sha256);
+        }
+    }
     Ok(())
 }
@@ -1,64 +1,513 @@
-use anyhow::Result;
+//! Kernel modules
+//!
+//! Loaded drivers are linked into `PsLoadedModuleList`, and each loader
+//! entry is its own `MmLd` pool allocation. Rootkits that hide a driver
+//! unlink its entry but leave the allocation, so scanning pool memory for
+//! the tag finds them; entries of drivers that were unloaded since turn up
+//! the same way while their memory has not been reused.
+//!
+//! `extract_modules` writes the bodies of kernel modules, or of the DLLs
+//! on a process's loader lists, selected by name or path.
+
+use anyhow::{bail, Context, Result};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::{path::PathBuf, fs::{self, File}, io::Write};
+use prettytable::{cell, format, row, Table};
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{path::{Path, PathBuf}, fs::{self, File}, io::Write};
+use crate::baseline::{Baseline, Drift, ModuleFingerprint};
+use crate::dlllist::process_dlls;
+use crate::dumpfiles::MANIFEST_FILE;
+use crate::kdbg::OsContext;
 use crate::loader::load_memory_image;
+use crate::pe::{module_name, rebuild_pe, ExportIndex, PeHashes};
+use crate::processes::{scan_pool_tags, windows_finder, ProcessAddressSpace, ProcessFinder, POOL_HEADER_SIZE};
+use crate::output::Record;
+use crate::MemoryImage;
+
+// Offsets in the x64 LDR_DATA_TABLE_ENTRY
+const LDR_DLL_BASE: u64 = 0x30;
+const LDR_SIZE_OF_IMAGE: u64 = 0x40;
+const LDR_FULL_NAME: u64 = 0x48;
+const LDR_BASE_NAME: u64 = 0x58;
+
+/// Pool tag of kernel loader entries
+const MODULE_POOL_TAGS: [&[u8]; 1] = [b"MmLd"];
+
+/// Bytes of a loader entry decoded from pool, up to the end of BaseDllName
+const LDR_ENTRY_SIZE: usize = 0x68;
+
+/// Lowest kernel-mode address on x64
+const KERNEL_SPACE: u64 = 0xFFFF_8000_0000_0000;
+
+/// Upper bound on a driver image, guarding against garbage entries
+const MAX_MODULE_SIZE: u64 = 0x1000_0000;
+
+/// A kernel module from PsLoadedModuleList
+#[derive(Debug, Clone, PartialEq)]
+pub struct KernelModule {
+    pub base: u64,
+    pub size: u64,
+    pub name: String,
+    pub path: Option<String>,
+}
+
+impl KernelModule {
+    pub fn contains(&self, va: u64) -> bool {
+        va >= self.base && va - self.base < self.size
+    }
+}
+
+/// The module whose image holds `va`
+pub fn module_containing(modules: &[KernelModule], va: u64) -> Option<&KernelModule> {
+    modules.iter().find(|module| module.contains(va))
+}
+
+/// Walk PsLoadedModuleList in load order
+pub fn list_kernel_modules(img: &MemoryImage, os: &OsContext) -> Vec<KernelModule> {
+    let mut modules = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut entry = img.read_virt_u64(os.ps_loaded_module_list).unwrap_or(0);
+
+    while entry != os.ps_loaded_module_list && entry != 0 && seen.insert(entry) {
+        let base = img.read_virt_u64(entry + LDR_DLL_BASE).unwrap_or(0);
+        let size = img.read_virt_u32(entry + LDR_SIZE_OF_IMAGE).unwrap_or(0) as u64;
+        if base != 0 && size != 0 {
+            let path = img.read_unicode_string(entry + LDR_FULL_NAME);
+            let name = img.read_unicode_string(entry + LDR_BASE_NAME)
+                .or_else(|| module_name(img, base))
+                .unwrap_or_else(|| format!("module_{:X}.sys", base));
+            modules.push(KernelModule { base, size, name, path });
+        }
+        entry = img.read_virt_u64(entry).unwrap_or(0);
+    }
+    modules
+}
+
+/// Read `size` bytes at `base` page by page, zero-filling pages that are not
+/// resident; returns the data and the number of missing pages
+pub(crate) fn read_pages(img: &MemoryImage, base: u64, size: u64) -> (Vec<u8>, usize) {
+    let mut data = vec![0u8; size as usize];
+    let mut missing = 0;
+    for (i, page) in data.chunks_mut(0x1000).enumerate() {
+        match img.read_virt(base + (i * 0x1000) as u64, page.len()) {
+            Some(bytes) => page.copy_from_slice(&bytes),
+            None => missing += 1,
+        }
+    }
+    (data, missing)
+}
+
+/// A kernel module and where it was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundModule {
+    pub module: KernelModule,
+    /// On PsLoadedModuleList
+    pub listed: bool,
+    /// Has an `MmLd` allocation in pool memory
+    pub pooled: bool,
+}
 
-pub fn extract_modules(dump_path: PathBuf, output_path: PathBuf) -> Result<()> {
-    println!("{} {} {} {}",
+impl FoundModule {
+    /// In pool but not on the loaded module list: hidden or unloaded
+    pub fn is_unlinked(&self) -> bool {
+        self.pooled && !self.listed
+    }
+}
+
+/// Decode a UNICODE_STRING from raw bytes, reading its buffer through the
+/// kernel page tables
+pub(crate) fn unicode_string_at(img: &MemoryImage, data: &[u8], offset: usize) -> Option<String> {
+    let length = u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?) as usize;
+    let buffer = u64::from_le_bytes(data.get(offset + 8..offset + 16)?.try_into().ok()?);
+    if length == 0 || buffer == 0 {
+        return None;
+    }
+    img.read_utf16_string(img.virt_to_phys(buffer)? as usize, length)
+}
+
+/// Decode the loader entry at physical address `entry`, rejecting
+/// allocations that do not look like a loaded kernel image
+fn read_pool_entry(img: &MemoryImage, entry: u64) -> Option<KernelModule> {
+    let data = img.get_bytes(entry as usize, LDR_ENTRY_SIZE)?;
+    let u64_at = |off: u64| u64::from_le_bytes(data[off as usize..off as usize + 8].try_into().unwrap());
+    let base = u64_at(LDR_DLL_BASE);
+    let size = u64_at(LDR_SIZE_OF_IMAGE) & 0xFFFF_FFFF;
+    if base < KERNEL_SPACE || base & 0xFFF != 0 || size == 0 || size > MAX_MODULE_SIZE || u64_at(0) < KERNEL_SPACE {
+        return None;
+    }
+    // A paged out name is recovered from the image's export directory
+    let name = unicode_string_at(img, data, LDR_BASE_NAME as usize).or_else(|| module_name(img, base))?;
+    if !name.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+        return None;
+    }
+    Some(KernelModule { base, size, name, path: unicode_string_at(img, data, LDR_FULL_NAME as usize) })
+}
+
+/// Scan pool memory for `MmLd` allocations holding loader entries; the
+/// image must use the kernel DTB to resolve names
+pub fn scan_kernel_modules(img: &MemoryImage, progress: &ProgressBar) -> Vec<KernelModule> {
+    let mut modules: Vec<KernelModule> = scan_pool_tags(img, progress, &MODULE_POOL_TAGS).into_iter()
+        .filter(|&(_, block_size)| block_size == 0 || block_size >= POOL_HEADER_SIZE + LDR_ENTRY_SIZE)
+        .filter_map(|(header, _)| read_pool_entry(img, header + POOL_HEADER_SIZE as u64))
+        .collect();
+    modules.sort_by_key(|m| m.base);
+    modules.dedup_by_key(|m| m.base);
+    modules
+}
+
+/// Modules on PsLoadedModuleList, in load order, followed by the ones only
+/// pool scanning found, by base address
+pub fn find_kernel_modules(img: &MemoryImage, os: Option<&OsContext>, progress: &ProgressBar) -> Vec<FoundModule> {
+    let scanned = scan_kernel_modules(img, progress);
+    let mut found: Vec<FoundModule> = os.map(|os| list_kernel_modules(img, os)).unwrap_or_default().into_iter()
+        .map(|module| FoundModule { pooled: scanned.iter().any(|m| m.base == module.base), listed: true, module })
+        .collect();
+    for module in scanned {
+        if !found.iter().any(|f| f.module.base == module.base) {
+            found.push(FoundModule { module, listed: false, pooled: true });
+        }
+    }
+    found
+}
+
+/// A module body written by `extract_modules`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedModule {
+    pub path: PathBuf,
+    /// Pages written as zeroes because they were not resident
+    pub missing_pages: usize,
+    pub fingerprint: ModuleFingerprint,
+    /// SHA-256 of the file written
+    pub sha256: String,
+    /// Hashes of the body as mapped, if its headers are resident
+    pub hashes: Option<PeHashes>,
+}
+
+/// An entry of the manifest `extract_modules` writes with the bodies
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleManifestEntry {
+    pub name: String,
+    pub base: String,
+    pub size: u64,
+    pub path: PathBuf,
+    pub missing_pages: usize,
+    pub sha256: String,
+    #[serde(flatten)]
+    pub hashes: PeHashes,
+}
+
+/// An extracted module as `extract-modules --format json|csv` reports it
+#[derive(Debug, Clone, Serialize)]
+pub struct ModuleRecord {
+    pub name: String,
+    /// Full path from the loader entry
+    pub module_path: Option<String>,
+    pub base: String,
+    pub size: u64,
+    /// On PsLoadedModuleList
+    pub listed: bool,
+    pub pooled: bool,
+    pub unlinked: bool,
+    /// File the body was written to
+    pub file: PathBuf,
+    pub missing_pages: usize,
+    pub version: Option<String>,
+    pub sha256: String,
+    pub imphash: Option<String>,
+    pub rich_header_hash: Option<String>,
+    /// Comparison with `--baseline`, when one was given
+    pub baseline: Option<String>,
+}
+
+impl Record for ModuleRecord {
+    const SCHEMA: &'static str = "rmf.module";
+    const TABLE: &'static str = "modules";
+    const COLUMNS: &'static [&'static str] = &[
+        "name", "module_path", "base", "size", "listed", "pooled", "unlinked", "file",
+        "missing_pages", "version", "sha256", "imphash", "rich_header_hash", "baseline",
+    ];
+}
+
+impl ModuleRecord {
+    pub fn new(found: &FoundModule, extracted: &ExtractedModule, drift: Option<&Drift>) -> ModuleRecord {
+        let hashes = extracted.hashes.clone().unwrap_or_default();
+        ModuleRecord {
+            name: found.module.name.clone(),
+            module_path: found.module.path.clone(),
+            base: format!("0x{:X}", found.module.base),
+            size: found.module.size,
+            listed: found.listed,
+            pooled: found.pooled,
+            unlinked: found.is_unlinked(),
+            file: extracted.path.clone(),
+            missing_pages: extracted.missing_pages,
+            version: extracted.fingerprint.version.clone(),
+            sha256: extracted.sha256.clone(),
+            imphash: hashes.imphash,
+            rich_header_hash: hashes.rich_header_hash,
+            baseline: drift.map(|drift| drift.to_string()),
+        }
+    }
+}
+
+/// Which modules to extract: those whose name or path matches a glob
+/// pattern and a regex, both without regard to case
+#[derive(Debug, Clone, Default)]
+pub struct ModuleFilter {
+    glob: Option<glob::Pattern>,
+    regex: Option<Regex>,
+}
+
+impl ModuleFilter {
+    pub fn new(pattern: Option<&str>, regex: Option<&str>) -> Result<Self> {
+        Ok(ModuleFilter {
+            glob: pattern.map(glob::Pattern::new).transpose().context("Invalid --pattern")?,
+            regex: regex.map(|r| Regex::new(&format!("(?i){}", r))).transpose().context("Invalid --regex")?,
+        })
+    }
+
+    pub fn matches(&self, module: &KernelModule) -> bool {
+        let options = glob::MatchOptions { case_sensitive: false, ..Default::default() };
+        let names = || std::iter::once(module.name.as_str()).chain(module.path.as_deref());
+        self.glob.as_ref().is_none_or(|glob| names().any(|name| glob.matches_with(name, options)))
+            && self.regex.as_ref().is_none_or(|regex| names().any(|name| regex.is_match(name)))
+    }
+}
+
+/// What `extract_modules` extracts and compares
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Only modules whose name or path matches this glob pattern
+    pub pattern: Option<String>,
+    /// Only modules whose name or path matches this regex
+    pub regex: Option<String>,
+    /// Extract the DLLs of this process instead of kernel modules
+    pub pid: Option<u32>,
+    /// Flag modules whose code deviates from this baseline
+    pub baseline: Option<Baseline>,
+    /// Write the fingerprints of the extracted modules here, as a baseline
+    pub save_baseline: Option<PathBuf>,
+    /// Write bodies as mapped instead of rebuilt into PE files
+    pub raw: bool,
+}
+
+/// Write the body of each module into `output_path`, rebuilt into a PE
+/// file with its imports named from `exports` unless that is `None`; a body
+/// whose headers are not resident is written as mapped
+fn write_module_bodies(img: &MemoryImage, modules: &[FoundModule], output_path: &Path, exports: Option<&ExportIndex>, progress: &ProgressBar) -> Result<Vec<ExtractedModule>> {
+    progress.set_length(modules.len() as u64);
+    let mut written = Vec::new();
+    for (i, found) in modules.iter().enumerate() {
+        let module = &found.module;
+        progress.set_position(i as u64 + 1);
+        progress.set_message(format!("Extracting {}", module.name));
+
+        let (data, missing) = read_pages(img, module.base, module.size);
+        let file_name = format!("{:X}_{}", module.base, module.name.replace(['/', '\\', ':'], "_"));
+        let path = output_path.join(file_name);
+        let rebuilt = exports.and_then(|exports| rebuild_pe(&data, module.base, exports).ok());
+        let body = rebuilt.as_deref().unwrap_or(&data);
+        File::create(&path)?.write_all(body)?;
+        // Fingerprints compare code as mapped, and hashes are of the image as loaded
+        let fingerprint = ModuleFingerprint::of_image(&data, module.base);
+        let sha256 = Sha256::digest(body).iter().map(|b| format!("{:02x}", b)).collect();
+        written.push(ExtractedModule { path, missing_pages: missing, fingerprint, sha256, hashes: PeHashes::of_image(&data, true) });
+    }
+    Ok(written)
+}
+
+/// Write the manifest of the extracted `modules` into `output_path`
+fn write_module_manifest(modules: &[FoundModule], extracted: &[ExtractedModule], output_path: &Path) -> Result<()> {
+    let manifest: Vec<ModuleManifestEntry> = modules.iter().zip(extracted).map(|(found, e)| ModuleManifestEntry {
+        name: found.module.name.clone(),
+        base: format!("0x{:X}", found.module.base),
+        size: found.module.size,
+        path: e.path.clone(),
+        missing_pages: e.missing_pages,
+        sha256: e.sha256.clone(),
+        hashes: e.hashes.clone().unwrap_or_default(),
+    }).collect();
+    let manifest_path = output_path.join(MANIFEST_FILE);
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+    Ok(())
+}
+
+fn module_table(modules: &[FoundModule], extracted: Option<&[ExtractedModule]>, drift: Option<&[Drift]>) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    let mut titles = row![bFg->"Base", bFg->"Size", bFg->"Name", bFg->"Listed", bFg->"Pool", bFg->"Path"];
+    if extracted.is_some() {
+        titles.add_cell(cell!(bFg->"Missing Pages"));
+        titles.add_cell(cell!(bFg->"Version"));
+    }
+    if drift.is_some() {
+        titles.add_cell(cell!(bFg->"Baseline"));
+    }
+    table.set_titles(titles);
+    for (i, found) in modules.iter().enumerate() {
+        let module = &found.module;
+        let name = if found.is_unlinked() { cell!(Fr->module.name) } else { cell!(module.name) };
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        let mut row = row![format!("0x{:X}", module.base), format!("0x{:X}", module.size)];
+        row.add_cell(name);
+        row.add_cell(cell!(yes_no(found.listed)));
+        row.add_cell(cell!(yes_no(found.pooled)));
+        row.add_cell(cell!(module.path.as_deref().unwrap_or("-")));
+        if let Some(extracted) = extracted {
+            row.add_cell(cell!(extracted[i].missing_pages));
+            row.add_cell(cell!(extracted[i].fingerprint.version.as_deref().unwrap_or("-")));
+        }
+        if let Some(drift) = drift {
+            row.add_cell(if drift[i].is_deviation() { cell!(Fr->drift[i]) } else { cell!(drift[i]) });
+        }
+        table.add_row(row);
+    }
+    table
+}
+
+/// The modules on the loader lists of process `pid`, in its address space;
+/// `listed` is whether a module is on every list it should be
+fn process_modules(img: &MemoryImage, os: OsContext, pid: u32, progress: &ProgressBar) -> Result<(ProcessAddressSpace, Vec<FoundModule>)> {
+    let finder = windows_finder(img, os, None);
+    let process = finder.find_processes(img, progress)?
+        .into_iter()
+        .find(|p| p.pid == pid)
+        .with_context(|| format!("Process {} is not on the active process list", pid))?;
+    let space = process.address_space(img).with_context(|| format!("Process {} has no DTB", pid))?;
+    let modules = process_dlls(img, &finder, &process).dlls.into_iter()
+        .filter(|dll| dll.size != 0 && dll.size <= MAX_MODULE_SIZE)
+        .map(|dll| FoundModule {
+            listed: dll.missing_from().is_empty(),
+            pooled: false,
+            module: KernelModule { base: dll.base, size: dll.size, name: dll.name, path: dll.path },
+        })
+        .collect();
+    Ok((space, modules))
+}
+
+/// Load a dump for module enumeration; module names and bodies live in
+/// kernel virtual memory, so a DTB is required
+fn load_kernel_image(dump_path: &Path, dtb: Option<u64>) -> Result<MemoryImage> {
+    let mut memory_image = load_memory_image(dump_path)?;
+    if let Some(dtb) = dtb {
+        memory_image.set_cr3(dtb);
+    }
+    if memory_image.info.dtb.is_none() {
+        bail!("Kernel modules are read through the kernel page tables; pass the kernel DTB with --dtb");
+    }
+    Ok(memory_image)
+}
+
+fn report_unlinked(modules: &[FoundModule]) {
+    for found in modules.iter().filter(|f| f.is_unlinked()) {
+        crate::status!("{} {} at 0x{:X} has a loader entry in pool but is not on PsLoadedModuleList (hidden or unloaded)",
+            "Suspicious:".bright_red(), found.module.name.bright_yellow(), found.module.base);
+    }
+}
+
+/// List kernel modules from PsLoadedModuleList and from `MmLd` pool
+/// allocations, flagging the ones only the pool scan found
+pub fn modscan(dump_path: PathBuf, dtb: Option<u64>) -> Result<()> {
+    let memory_image = load_kernel_image(&dump_path, dtb)?;
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let os = OsContext::find(&memory_image, &progress);
+    if os.is_none() {
+        progress.println(format!("{} no KDBG block found; reporting pool results only", "Note:".bright_yellow()));
+    }
+    progress.set_message("Scanning pool memory for MmLd allocations");
+    let modules = find_kernel_modules(&memory_image, os.as_ref(), &progress);
+    progress.finish_and_clear();
+
+    println!("{} {} kernel modules", "Found".bright_green(), modules.len().to_string().bright_yellow());
+    module_table(&modules, None, None).printstd();
+    report_unlinked(&modules);
+    Ok(())
+}
+
+/// Extract every kernel module, or the DLLs of a process, into
+/// `output_path`, only those matching the options' pattern and regex;
+/// modules are compared with a baseline when one is given
+pub fn extract_modules(dump_path: PathBuf, output_path: PathBuf, dtb: Option<u64>, options: ExtractOptions) -> Result<()> {
+    let ExtractOptions { pattern, regex, pid, baseline, save_baseline, raw } = options;
+    crate::status!("{} {} {} {}",
         "Extracting modules from".bright_green(),
         dump_path.display().to_string().bright_yellow(),
         "to".bright_green(),
         output_path.display().to_string().bright_cyan()
     );
-
-    // Ensure output directory exists
+    let filter = ModuleFilter::new(pattern.as_deref(), regex.as_deref())?;
+    let memory_image = load_kernel_image(&dump_path, dtb)?;
     fs::create_dir_all(&output_path)?;
-    
-    // Load the memory image
-    let _memory_image = load_memory_image(&dump_path)?;
-    
-    // Set up progress bar for module extraction
-    let progress = ProgressBar::new(100);
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
     progress.set_style(ProgressStyle::with_template(
-        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}% {msg}"
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}"
     )?.progress_chars("#>-"));
-    
-    // Simulate finding and extracting modules
-    // In a real implementation, we would:
-    // 1. Scan for PE/ELF headers in memory
-    // 2. Determine memory regions that contain modules
-    // 3. Extract the memory regions to files
-    
-    let module_count = 5; // Simulating 5 modules for demonstration
-    
-    for i in 0..module_count {
-        let module_name = format!("module_{}.bin", i);
-        let module_path = output_path.join(&module_name);
-        
-        // Update progress
-        let progress_pct = (i as u64 + 1) * 100 / module_count as u64;
-        progress.set_position(progress_pct);
-        progress.set_message(format!("Extracting module {}/{}: {}", i + 1, module_count, module_name));
-        
-        // Simulate a delay for extraction work
-        std::thread::sleep(std::time::Duration::from_millis(200));
-        
-        // Create a simulated module file with some content
-        let mut file = File::create(module_path)?;
-        
-        // Write some mock data - in a real implementation we'd extract from the memory image
-        let mock_data = vec![0xDE, 0xAD, 0xBE, 0xEF, 0x01, 0x23, 0x45, 0x67];
-        file.write_all(&mock_data)?;
-    }
-    
-    progress.finish_with_message(format!("Successfully extracted {} modules", module_count));
-    
-    // Print summary
-    println!("\n{} {}", 
+    let os = OsContext::find(&memory_image, &progress);
+    if os.is_none() {
+        progress.println(format!("{} no KDBG block found; extracting pool results only", "Note:".bright_yellow()));
+    }
+    let (space, found) = match pid {
+        Some(pid) => {
+            let os = os.context("No KDBG block found; cannot walk the process list")?;
+            progress.set_message(format!("Listing the modules of process {}", pid));
+            let (space, found) = process_modules(&memory_image, os, pid, &progress)?;
+            (Some(space), found)
+        }
+        None => {
+            progress.set_message("Scanning pool memory for MmLd allocations");
+            (None, find_kernel_modules(&memory_image, os.as_ref(), &progress))
+        }
+    };
+    // DLL bodies are read through the process's page tables
+    let image: &MemoryImage = space.as_deref().unwrap_or(&memory_image);
+    // Imports of one module resolve to the exports of any other
+    let exports = (!raw).then(|| ExportIndex::build(image, found.iter().map(|f| (f.module.name.as_str(), f.module.base))));
+    let modules: Vec<FoundModule> = found.into_iter().filter(|found| filter.matches(&found.module)).collect();
+    let extracted = write_module_bodies(image, &modules, &output_path, exports.as_ref(), &progress)?;
+    write_module_manifest(&modules, &extracted, &output_path)?;
+    progress.finish_with_message(format!("Successfully extracted {} modules", modules.len()));
+
+    let drift: Option<Vec<Drift>> = baseline.as_ref().map(|baseline| {
+        modules.iter().zip(&extracted).map(|(found, e)| baseline.compare(&found.module.name, &e.fingerprint)).collect()
+    });
+    crate::status!("\n{} {} {} {}",
         "Modules extracted:".bright_cyan(),
-        module_count.to_string().bright_yellow().bold()
+        modules.len().to_string().bright_yellow().bold(),
+        "hashes in".bright_cyan(),
+        MANIFEST_FILE.bright_yellow()
     );
-    
+    if crate::output::structured() {
+        let records: Vec<ModuleRecord> = modules.iter().zip(&extracted).enumerate()
+            .map(|(i, (found, e))| ModuleRecord::new(found, e, drift.as_ref().map(|drift| &drift[i])))
+            .collect();
+        crate::output::emit(&dump_path, &records)?;
+    } else {
+        module_table(&modules, Some(&extracted), drift.as_deref()).printstd();
+    }
+    report_unlinked(&modules);
+    for (found, drift) in modules.iter().zip(drift.iter().flatten()).filter(|(_, d)| d.is_deviation()) {
+        crate::status!("{} {} at 0x{:X} deviates from the baseline: {}",
+            "Suspicious:".bright_red(), found.module.name.bright_yellow(), found.module.base, drift);
+    }
+
+    if let Some(path) = save_baseline {
+        let mut baseline = Baseline::default();
+        for (found, e) in modules.iter().zip(&extracted) {
+            baseline.insert(&found.module.name, e.fingerprint.clone());
+        }
+        baseline.save(&path)?;
+        crate::status!("{} {} module fingerprints to {}", "Saved".bright_green(),
+            baseline.modules.len().to_string().bright_yellow(), path.display().to_string().bright_cyan());
+    }
+    let files: Vec<PathBuf> = extracted.into_iter().map(|e| e.path).collect();
+    crate::actions::run_for_files(&dump_path, &files);
     Ok(())
 }
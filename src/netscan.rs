@@ -0,0 +1,406 @@
+//! Network connections and neighbor caches for Windows and Linux dumps
+//!
+//! On Windows the `netscan` and `arpcache` plugins carve tcpip.sys
+//! endpoints and neighbor entries from pool.
+//! Linux keeps no such allocations apart from the slab caches, so sockets
+//! are reached from the processes that hold them: each task's
+//! `files_struct` points to its `fdtable`, whose open files include socket
+//! files with the `struct socket` in `private_data`. The socket's `sk`
+//! points to the `struct sock`, which starts with the `sock_common` holding
+//! the family, addresses, ports and TCP state. Neighbors are walked from
+//! the hash tables of `arp_tbl` and `nd_tbl`. Offsets and symbols come
+//! from the kernel's profile.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{Table, row, format};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+
+use crate::linux_profile::LinuxProfile;
+use crate::loader::load_memory_image;
+use crate::modules::read_pages;
+use crate::osinfo::find_linux_banners;
+use crate::paging::MemoryImage;
+use crate::plugin::{format_endpoint, ArpCacheScanner, MemoryPlugin, Neighbor, NetworkScanner};
+use crate::processes::{LinuxProcessFinder, Process, ProcessFinder};
+use crate::symbols::KernelTypes;
+
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 10;
+
+/// `enum sock_type`
+const SOCK_STREAM: u16 = 1;
+const SOCK_DGRAM: u16 = 2;
+const SOCK_RAW: u16 = 3;
+
+/// TCP states from `include/net/tcp_states.h`, by value
+const TCP_STATES: [&str; 13] = [
+    "", "ESTABLISHED", "SYN_SENT", "SYN_RECV", "FIN_WAIT1", "FIN_WAIT2", "TIME_WAIT",
+    "CLOSE", "CLOSE_WAIT", "LAST_ACK", "LISTEN", "CLOSING", "NEW_SYN_RECV",
+];
+
+/// Most descriptors read from one task's table
+const MAX_FDS: u64 = 0x10000;
+
+/// Neighbor tables and the length of their keys, the IP addresses
+const NEIGHBOR_TABLES: [(&str, usize); 2] = [("arp_tbl", 4), ("nd_tbl", 16)];
+/// Largest `hash_shift` of a neighbor hash table
+const MAX_HASH_SHIFT: u32 = 20;
+/// Most entries read from one neighbor table
+const MAX_NEIGHBORS: usize = 0x10000;
+
+/// `nud_state` bits from `include/uapi/linux/neighbour.h`, most telling first
+const NUD_STATES: [(u8, &str); 8] = [
+    (0x80, "PERMANENT"), (0x40, "NOARP"), (0x02, "REACHABLE"), (0x04, "STALE"),
+    (0x08, "DELAY"), (0x10, "PROBE"), (0x20, "FAILED"), (0x01, "INCOMPLETE"),
+];
+
+/// Offsets needed to go from a task to the sockets it holds
+struct SocketLayout {
+    task_files: usize,
+    fdt: usize,
+    max_fds: usize,
+    fd: usize,
+    private_data: usize,
+    socket_type: usize,
+    socket_file: usize,
+    socket_sk: usize,
+    family: usize,
+    state: usize,
+    /// Local port in host order, remote port in network order
+    num: usize,
+    dport: usize,
+    rcv_saddr: usize,
+    daddr: usize,
+    /// Only present in kernels built with IPv6
+    v6_rcv_saddr: Option<usize>,
+    v6_daddr: Option<usize>,
+}
+
+impl SocketLayout {
+    fn from_types(types: &KernelTypes) -> Result<Self> {
+        let required = |name: &str, field: &str| {
+            types.offset(name, field).with_context(|| format!("Profile has no {}.{}", name, field))
+        };
+        Ok(SocketLayout {
+            task_files: required("task_struct", "files")?,
+            fdt: required("files_struct", "fdt")?,
+            max_fds: required("fdtable", "max_fds")?,
+            fd: required("fdtable", "fd")?,
+            private_data: required("file", "private_data")?,
+            socket_type: required("socket", "type")?,
+            socket_file: required("socket", "file")?,
+            socket_sk: required("socket", "sk")?,
+            family: required("sock_common", "skc_family")?,
+            state: required("sock_common", "skc_state")?,
+            num: required("sock_common", "skc_num")?,
+            dport: required("sock_common", "skc_dport")?,
+            rcv_saddr: required("sock_common", "skc_rcv_saddr")?,
+            daddr: required("sock_common", "skc_daddr")?,
+            v6_rcv_saddr: types.offset("sock_common", "skc_v6_rcv_saddr"),
+            v6_daddr: types.offset("sock_common", "skc_v6_daddr"),
+        })
+    }
+}
+
+/// An open IPv4 or IPv6 socket and the process holding it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinuxSocket {
+    pub pid: u32,
+    pub process: String,
+    pub fd: u32,
+    /// `SOCK_STREAM`, `SOCK_DGRAM` or `SOCK_RAW`
+    pub sock_type: u16,
+    pub local: IpAddr,
+    pub local_port: u16,
+    pub remote: IpAddr,
+    pub remote_port: u16,
+    /// `skc_state`, a TCP state for stream sockets
+    pub state: u8,
+    /// Virtual address of the `struct sock`
+    pub sock: u64,
+}
+
+impl LinuxSocket {
+    pub fn protocol(&self) -> &'static str {
+        match (self.sock_type, self.local) {
+            (SOCK_STREAM, IpAddr::V4(_)) => "TCPv4",
+            (SOCK_STREAM, IpAddr::V6(_)) => "TCPv6",
+            (SOCK_DGRAM, IpAddr::V4(_)) => "UDPv4",
+            (SOCK_DGRAM, IpAddr::V6(_)) => "UDPv6",
+            (_, IpAddr::V4(_)) => "RAWv4",
+            (_, IpAddr::V6(_)) => "RAWv6",
+        }
+    }
+
+    /// TCP state of a stream socket; datagram sockets only tell whether
+    /// they are connected
+    pub fn state_name(&self) -> &'static str {
+        match self.sock_type {
+            SOCK_STREAM => TCP_STATES.get(self.state as usize).copied().filter(|s| !s.is_empty()).unwrap_or("UNKNOWN"),
+            _ if self.state == 1 => "CONNECTED",
+            _ => "",
+        }
+    }
+
+    /// The remote end, unless the socket is not connected
+    pub fn peer(&self) -> Option<(IpAddr, u16)> {
+        (self.remote_port != 0 || !self.remote.is_unspecified()).then_some((self.remote, self.remote_port))
+    }
+}
+
+impl SocketLayout {
+    /// Decode the socket behind the `struct file` at `file`, if it is an
+    /// IPv4 or IPv6 socket
+    fn socket(&self, img: &MemoryImage, process: &Process, fd: u32, file: u64) -> Option<LinuxSocket> {
+        let u16_at = |va: u64| img.read_virt(va, 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+        let socket = img.read_virt_u64(file + self.private_data as u64).filter(|&va| va != 0)?;
+        // A socket file's socket points back to the file; other files keep
+        // unrelated data in private_data
+        if img.read_virt_u64(socket + self.socket_file as u64)? != file {
+            return None;
+        }
+        let sock_type = u16_at(socket + self.socket_type as u64).filter(|t| [SOCK_STREAM, SOCK_DGRAM, SOCK_RAW].contains(t))?;
+        let sk = img.read_virt_u64(socket + self.socket_sk as u64).filter(|&va| va != 0)?;
+
+        let at = |off: usize| sk + off as u64;
+        let (local, remote) = match u16_at(at(self.family))? {
+            AF_INET => (
+                IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(img.read_virt(at(self.rcv_saddr), 4)?).ok()?)),
+                IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(img.read_virt(at(self.daddr), 4)?).ok()?)),
+            ),
+            AF_INET6 => {
+                let v6 = |off: Option<usize>| -> Option<IpAddr> {
+                    Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(img.read_virt(at(off?), 16)?).ok()?)))
+                };
+                (v6(self.v6_rcv_saddr)?, v6(self.v6_daddr)?)
+            }
+            _ => return None,
+        };
+        let dport = img.read_virt(at(self.dport), 2).map(|b| u16::from_be_bytes([b[0], b[1]]))?;
+        Some(LinuxSocket {
+            pid: process.pid,
+            process: process.name.clone(),
+            fd,
+            sock_type,
+            local,
+            local_port: u16_at(at(self.num))?,
+            remote,
+            remote_port: dport,
+            state: img.read_virt(at(self.state), 1)?[0],
+            sock: sk,
+        })
+    }
+
+    /// IPv4 and IPv6 sockets among the open files of a task
+    fn task_sockets(&self, img: &MemoryImage, process: &Process) -> Vec<LinuxSocket> {
+        let task = process.virtual_address;
+        let fdt = img.read_virt_u64(task + self.task_files as u64)
+            .filter(|&files| files != 0)
+            .and_then(|files| img.read_virt_u64(files + self.fdt as u64))
+            .filter(|&fdt| fdt != 0);
+        let Some(fdt) = fdt else { return Vec::new() };
+        let (Some(max_fds), Some(fd_array)) = (img.read_virt(fdt + self.max_fds as u64, 4), img.read_virt_u64(fdt + self.fd as u64)) else {
+            return Vec::new();
+        };
+        let max_fds = (u32::from_le_bytes(max_fds.try_into().unwrap()) as u64).min(MAX_FDS);
+        let (files, _) = read_pages(img, fd_array, max_fds * 8);
+        files.chunks_exact(8).enumerate()
+            .map(|(fd, file)| (fd as u32, u64::from_le_bytes(file.try_into().unwrap())))
+            .filter(|&(_, file)| file != 0)
+            .filter_map(|(fd, file)| self.socket(img, process, fd, file))
+            .collect()
+    }
+}
+
+/// IPv4 and IPv6 sockets held by `processes`, found by the Linux task walk
+/// with the kernel DTB
+pub fn linux_sockets(img: &MemoryImage, types: &KernelTypes, processes: &[Process]) -> Result<Vec<LinuxSocket>> {
+    let layout = SocketLayout::from_types(types)?;
+    Ok(processes.iter().flat_map(|process| layout.task_sockets(img, process)).collect())
+}
+
+/// `neigh_table`, `neighbour` and `net_device` offsets
+struct NeighborLayout {
+    nht: usize,
+    hash_buckets: usize,
+    hash_shift: usize,
+    next: usize,
+    nud_state: usize,
+    ha: usize,
+    dev: usize,
+    primary_key: usize,
+    dev_name: usize,
+    addr_len: Option<usize>,
+}
+
+impl NeighborLayout {
+    fn from_types(types: &KernelTypes) -> Result<Self> {
+        let required = |name: &str, field: &str| {
+            types.offset(name, field).with_context(|| format!("Profile has no {}.{}", name, field))
+        };
+        Ok(NeighborLayout {
+            nht: required("neigh_table", "nht")?,
+            hash_buckets: required("neigh_hash_table", "hash_buckets")?,
+            hash_shift: required("neigh_hash_table", "hash_shift")?,
+            next: required("neighbour", "next")?,
+            nud_state: required("neighbour", "nud_state")?,
+            ha: required("neighbour", "ha")?,
+            dev: required("neighbour", "dev")?,
+            primary_key: required("neighbour", "primary_key")?,
+            dev_name: required("net_device", "name")?,
+            addr_len: types.offset("net_device", "addr_len"),
+        })
+    }
+
+    /// Decode the `struct neighbour` at `va`, keyed by an IP address of `key_len` bytes
+    fn neighbor(&self, img: &MemoryImage, va: u64, key_len: usize) -> Option<Neighbor> {
+        let at = |off: usize| va + off as u64;
+        let key = img.read_virt(at(self.primary_key), key_len)?;
+        let ip = match key_len {
+            4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(key).ok()?)),
+            _ => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(key).ok()?)),
+        };
+        let nud = img.read_virt(at(self.nud_state), 1)?[0];
+        let state = NUD_STATES.iter().find(|(bit, _)| nud & bit != 0).map_or("NONE", |(_, name)| name);
+
+        // Only Ethernet-style devices have 6-byte hardware addresses
+        let dev = img.read_virt_u64(at(self.dev)).filter(|&dev| dev != 0);
+        if let Some(len) = dev.zip(self.addr_len).and_then(|(dev, off)| img.read_virt(dev + off as u64, 1)) {
+            if len[0] != 6 {
+                return None;
+            }
+        }
+        let interface = dev.and_then(|dev| img.read_virt(dev + self.dev_name as u64, 16)).map(|name| {
+            let len = name.iter().position(|&b| b == 0).unwrap_or(16);
+            String::from_utf8_lossy(&name[..len]).into_owned()
+        }).filter(|name| !name.is_empty());
+        Some(Neighbor {
+            ip,
+            mac: img.read_virt(at(self.ha), 6)?.try_into().ok()?,
+            state,
+            interface,
+        })
+    }
+}
+
+/// Entries of the kernel's ARP and IPv6 neighbor tables; `slide` is the
+/// KASLR slide of the profile's symbols and the image must use the kernel DTB
+pub fn linux_neighbors(img: &MemoryImage, profile: &LinuxProfile, slide: u64) -> Result<Vec<Neighbor>> {
+    let layout = NeighborLayout::from_types(&profile.types)?;
+    let mut neighbors = Vec::new();
+    // IPv6 may be a module, leaving nd_tbl out of the kernel's symbols
+    for (symbol, key_len) in NEIGHBOR_TABLES {
+        let Some(table) = profile.symbol(symbol) else { continue };
+        let table = table.wrapping_add(slide);
+        let Some(nht) = img.read_virt_u64(table + layout.nht as u64).filter(|&nht| nht != 0) else { continue };
+        let (Some(buckets), Some(shift)) = (img.read_virt_u64(nht + layout.hash_buckets as u64), img.read_virt(nht + layout.hash_shift as u64, 4)) else {
+            continue;
+        };
+        let shift = u32::from_le_bytes(shift.try_into().unwrap());
+        if shift > MAX_HASH_SHIFT {
+            continue;
+        }
+        let mut seen = std::collections::HashSet::new();
+        for bucket in 0..1u64 << shift {
+            let mut entry = img.read_virt_u64(buckets + bucket * 8).unwrap_or(0);
+            while entry != 0 && seen.len() < MAX_NEIGHBORS && seen.insert(entry) {
+                neighbors.extend(layout.neighbor(img, entry, key_len));
+                entry = img.read_virt_u64(entry + layout.next as u64).unwrap_or(0);
+            }
+        }
+    }
+    Ok(neighbors)
+}
+
+/// Print the network connections of a dump, or with `arp` its neighbor
+/// cache: tcpip.sys pool entries on Windows, kernel structures reached from
+/// the profile on Linux
+pub fn netscan(dump_path: PathBuf, os_type: &str, dtb: Option<u64>, profile: Option<PathBuf>, arp: bool) -> Result<()> {
+    let what = if arp { "neighbor cache" } else { "network connections" };
+    println!("{}", format!("Listing {} from memory dump...", what).bright_green());
+    let mut img = load_memory_image(&dump_path)?;
+    if let Some(dtb) = dtb {
+        img.set_cr3(dtb);
+    }
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    if arp {
+        table.set_titles(row![bFg->"IP", bFg->"MAC", bFg->"State", bFg->"Interface"]);
+    } else {
+        table.set_titles(row![bFg->"Proto", bFg->"Local", bFg->"Remote", bFg->"State", bFg->"PID", bFg->"Process", bFg->"FD"]);
+    }
+
+    // Auto-detection takes a Linux banner for a Linux kernel
+    let banner = match os_type {
+        "linux" | "auto" => find_linux_banners(&img, &progress).into_iter().next(),
+        "windows" => None,
+        other => bail!("netscan does not support {} dumps", other),
+    };
+    if os_type == "linux" || banner.is_some() {
+        let path = profile.context("Walking Linux network structures needs a profile of the dump's kernel (use --profile)")?;
+        let loaded = LinuxProfile::find(&path, banner.as_ref().map(|b| b.release.as_str()))?;
+        println!("Using Linux profile: {}", loaded.file_name().bright_yellow());
+        let mut finder = LinuxProcessFinder::default().with_profile(loaded.clone());
+        if let Some(banner) = banner {
+            finder = finder.with_banner(banner);
+        }
+        if arp {
+            let slide = finder.kernel_slide(&img)?;
+            for neighbor in linux_neighbors(&img, &loaded, slide)? {
+                table.add_row(row![neighbor.ip, neighbor.mac_string(), neighbor.state, neighbor.interface.unwrap_or_default()]);
+            }
+        } else {
+            let processes = finder.find_processes(&img, &progress)?;
+            for socket in linux_sockets(&img, &loaded.types, &processes)? {
+                let remote = socket.peer().map_or("-".to_string(), |(addr, port)| format_endpoint(addr, port));
+                table.add_row(row![
+                    socket.protocol(),
+                    format_endpoint(socket.local, socket.local_port),
+                    remote,
+                    socket.state_name(),
+                    socket.pid,
+                    socket.process,
+                    socket.fd
+                ]);
+            }
+        }
+    } else if arp {
+        for finding in ArpCacheScanner.scan(&img, &progress) {
+            let detail = |key: &str| finding.details.get(key).cloned().unwrap_or_default();
+            table.add_row(row![detail("ip"), detail("mac"), detail("state"), detail("interface")]);
+        }
+    } else {
+        for finding in NetworkScanner.scan(&img, &progress) {
+            let detail = |key: &str| finding.details.get(key).cloned().unwrap_or_default();
+            let endpoint = |addr: &str, port: &str| match finding.details.get(addr) {
+                Some(ip) => format_endpoint(ip.parse().unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)), detail(port).parse().unwrap_or(0)),
+                None => "-".to_string(),
+            };
+            table.add_row(row![
+                detail("protocol"),
+                endpoint("local_addr", "local_port"),
+                endpoint("remote_addr", "remote_port"),
+                detail("state"),
+                detail("pid"),
+                detail("process"),
+                "-"
+            ]);
+        }
+    }
+
+    if table.is_empty() {
+        println!("{}", format!("No {} found.", if arp { "neighbor entries" } else { "network connections" }).bright_red());
+    } else {
+        println!("{} {}", if arp { "Neighbors:" } else { "Connections:" }.bright_green(), table.len().to_string().bright_yellow());
+        table.printstd();
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+//! Operating system identification
+//!
+//! Linux kernels embed a `linux_banner` string (`/proc/version`) naming the
+//! kernel release, the build host, the compiler and the build date. The
+//! release selects the Linux profile; Windows is recognised by its KDBG block.
+
+use anyhow::Result;
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::path::PathBuf;
+
+use crate::kdbg::OsContext;
+use crate::loader::{load_memory_image, print_acquisition};
+use crate::paging::MemoryImage;
+
+const BANNER_PREFIX: &[u8] = b"Linux version ";
+const MAX_BANNER_LEN: usize = 512;
+const WEEKDAYS: &[&str] = &["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Parsed Linux kernel banner
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinuxBanner {
+    /// Physical offset of the banner
+    pub offset: u64,
+    pub raw: String,
+    /// Kernel release, e.g. `5.15.0-91-generic`
+    pub release: String,
+    /// (major, minor, patch) parsed from the release
+    pub version: (u32, u32, u32),
+    /// `user@host` that built the kernel
+    pub builder: Option<String>,
+    /// Compiler and linker description
+    pub compiler: Option<String>,
+    /// Build number and flags, e.g. `#101-Ubuntu SMP`
+    pub build: Option<String>,
+    /// Build timestamp as printed in the banner
+    pub build_date: Option<String>,
+}
+
+/// Split off a leading parenthesised group, honouring nested parentheses
+fn take_group(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start().strip_prefix('(')?;
+    let mut depth = 1;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((&s[..i], &s[i + 1..]));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_version(release: &str) -> Option<(u32, u32, u32)> {
+    let numeric = release.split(|c: char| !c.is_ascii_digit() && c != '.').next()?;
+    let mut parts = numeric.split('.').map(|p| p.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next()?.ok()?;
+    let patch = parts.next().and_then(|p| p.ok()).unwrap_or(0);
+    Some((major, minor, patch))
+}
+
+impl LinuxBanner {
+    /// Parse a banner string starting at `Linux version`
+    pub fn parse(raw: &str) -> Option<Self> {
+        let rest = raw.strip_prefix("Linux version ")?;
+        let (release, mut rest) = rest.split_once(' ').unwrap_or((rest, ""));
+        let version = parse_version(release)?;
+
+        let mut builder = None;
+        if let Some((group, after)) = take_group(rest) {
+            builder = Some(group.to_string());
+            rest = after;
+        }
+        let mut compiler = None;
+        if let Some((group, after)) = take_group(rest) {
+            compiler = Some(group.to_string());
+            rest = after;
+        }
+
+        // "#101-Ubuntu SMP Tue Nov 14 13:30:08 UTC 2023 (...)": flags, then the date
+        let rest = rest.trim();
+        let tokens: Vec<&str> = rest.split_whitespace().collect();
+        let date_at = tokens.iter().position(|t| WEEKDAYS.contains(t));
+        let build = match date_at {
+            Some(at) => tokens[..at].join(" "),
+            None => tokens.iter().take_while(|t| !t.starts_with('(')).cloned().collect::<Vec<_>>().join(" "),
+        };
+        let build_date = date_at.map(|at| {
+            tokens[at..].iter().take_while(|t| !t.starts_with('(')).take(6).cloned().collect::<Vec<_>>().join(" ")
+        });
+
+        Some(LinuxBanner {
+            offset: 0,
+            raw: raw.to_string(),
+            release: release.to_string(),
+            version,
+            builder,
+            compiler,
+            build: (!build.is_empty()).then_some(build),
+            build_date,
+        })
+    }
+
+    /// File name of the Linux profile matching this kernel
+    pub fn profile_name(&self) -> String {
+        crate::linux_profile::profile_file_name(&self.release)
+    }
+}
+
+/// Find every distinct Linux banner in the image, in physical order
+pub fn find_linux_banners(img: &MemoryImage, progress: &ProgressBar) -> Vec<LinuxBanner> {
+    let mut banners: Vec<LinuxBanner> = Vec::new();
+    let size = img.size();
+    let chunk_size = 0x10000;
+
+    progress.set_length(size as u64);
+    progress.set_message("Scanning for Linux banner");
+
+    for chunk_start in (0..size).step_by(chunk_size) {
+        progress.set_position(chunk_start as u64);
+        // Overlap chunks so a banner straddling the boundary is read whole
+        let len = (chunk_size + MAX_BANNER_LEN).min(size - chunk_start);
+        let Some(chunk) = img.get_bytes(chunk_start, len) else { continue };
+
+        let hits = chunk.windows(BANNER_PREFIX.len()).enumerate()
+            .filter(|(i, w)| *i < chunk_size && *w == BANNER_PREFIX)
+            .map(|(i, _)| i);
+        for pos in hits {
+            let text = &chunk[pos..(pos + MAX_BANNER_LEN).min(chunk.len())];
+            let end = text.iter().position(|&b| b == 0 || b == b'\n').unwrap_or(text.len());
+            let Ok(raw) = std::str::from_utf8(&text[..end]) else { continue };
+            if raw.len() < 32 || !raw.chars().all(|c| !c.is_control()) {
+                continue;
+            }
+            // The same banner appears in several copies (kernel image, log buffer)
+            if banners.iter().any(|b| b.raw == raw) {
+                continue;
+            }
+            if let Some(mut banner) = LinuxBanner::parse(raw) {
+                banner.offset = (chunk_start + pos) as u64;
+                banners.push(banner);
+            }
+        }
+    }
+    banners
+}
+
+/// Operating system identified in an image
+#[derive(Debug, Clone, PartialEq)]
+pub enum DetectedOs {
+    Linux(LinuxBanner),
+    Windows(OsContext),
+    Unknown,
+}
+
+/// Identify the operating system, trying the Linux banner first
+pub fn detect_os(img: &MemoryImage, progress: &ProgressBar) -> DetectedOs {
+    if let Some(banner) = find_linux_banners(img, progress).into_iter().next() {
+        return DetectedOs::Linux(banner);
+    }
+    match OsContext::find(img, progress) {
+        Some(ctx) => DetectedOs::Windows(ctx),
+        None => DetectedOs::Unknown,
+    }
+}
+
+/// Print the operating system details found in a dump
+pub fn report_osinfo(dump_path: PathBuf, dtb: Option<u64>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    if let Some(dtb) = dtb {
+        memory_image.set_cr3(dtb);
+    }
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let banners = find_linux_banners(&memory_image, &progress);
+    let kdbg = if banners.is_empty() { OsContext::find(&memory_image, &progress) } else { None };
+    progress.finish_and_clear();
+    print_acquisition(&memory_image.info.acquisition);
+
+    if let Some(banner) = banners.first() {
+        println!("{} {}", "Operating system:".bright_green(), "Linux".bright_yellow());
+        println!("  {:<14} {}", "Release", banner.release.bright_yellow());
+        println!("  {:<14} {}.{}.{}", "Version", banner.version.0, banner.version.1, banner.version.2);
+        let field = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        println!("  {:<14} {}", "Built by", field(&banner.builder));
+        println!("  {:<14} {}", "Compiler", field(&banner.compiler));
+        println!("  {:<14} {}", "Build", field(&banner.build));
+        println!("  {:<14} {}", "Build date", field(&banner.build_date));
+        println!("  {:<14} 0x{:X}", "Banner at", banner.offset);
+        println!("  {:<14} {}", "Profile", banner.profile_name().bright_cyan());
+        if banners.len() > 1 {
+            println!("\n{} {} other kernel banners (nested VMs or stale copies):",
+                "Note:".bright_yellow(), banners.len() - 1);
+            for other in &banners[1..] {
+                println!("  0x{:X} {}", other.offset, other.release);
+            }
+        }
+    } else if let Some(ctx) = kdbg {
+        println!("{} {}", "Operating system:".bright_green(), "Windows".bright_yellow());
+        println!("  {:<14} 0x{:X}", "Kernel base", ctx.kernel_base);
+        println!("  {:<14} {}", "KDBG", if ctx.encoded { "encoded" } else { "plaintext" });
+    } else {
+        println!("{}", "Operating system could not be identified".bright_red());
+        if memory_image.info.dtb.is_none() {
+            println!("Windows 8+ kernels need a DTB to decode KDBG (use --dtb)");
+        }
+    }
+
+    Ok(())
+}
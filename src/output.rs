@@ -0,0 +1,162 @@
+//! Machine-readable command output
+//!
+//! With `--format json` or `--format csv`, `list-procs`, `run-plugin`,
+//! `translate`, `extract-modules`, `timeline` and `diff` write their results
+//! as records rather than tables: to stdout, or with `--output-file` to a file. Messages about
+//! the run move to stderr ([`status!`](crate::status)), so stdout carries
+//! nothing but the records.
+//!
+//! JSON output is one object naming the record schema and its version,
+//! followed by the records:
+//!
+//! ```text
+//! {"schema":"rmf.process","version":1,"records":[{"pid":4,"ppid":0,"name":"System",...}]}
+//! ```
+//!
+//! CSV output is a header of the record's [`Record::COLUMNS`] and a row per
+//! record; lists and objects, such as a finding's details, are written as
+//! JSON in their cell and missing values as empty cells. Fields are only
+//! ever added to a schema at the end of its columns; renaming or removing
+//! one bumps [`SCHEMA_VERSION`].
+//!
+//! An `--output-file` named `*.db` (or `*.sqlite`) selects a results
+//! database instead, whatever the format: see [`crate::resultsdb`].
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+/// Version of every record schema, in the `version` field of JSON output
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How commands report their results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Colored tables and messages on the terminal
+    Text,
+    Json,
+    Csv,
+    /// Tables of a results database
+    Sqlite,
+}
+
+static FORMAT: AtomicU8 = AtomicU8::new(OutputFormat::Text as u8);
+static DESTINATION: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Select how commands report, and the file records go to instead of stdout;
+/// a destination named like a database selects [`OutputFormat::Sqlite`]
+pub fn set_output_format(format: OutputFormat, destination: Option<PathBuf>) {
+    let format = match &destination {
+        Some(path) if is_database_path(path) => OutputFormat::Sqlite,
+        _ => format,
+    };
+    FORMAT.store(format as u8, Ordering::Relaxed);
+    *DESTINATION.lock().unwrap() = destination;
+}
+
+pub fn output_format() -> OutputFormat {
+    match FORMAT.load(Ordering::Relaxed) {
+        x if x == OutputFormat::Json as u8 => OutputFormat::Json,
+        x if x == OutputFormat::Csv as u8 => OutputFormat::Csv,
+        x if x == OutputFormat::Sqlite as u8 => OutputFormat::Sqlite,
+        _ => OutputFormat::Text,
+    }
+}
+
+/// Whether results go out as records rather than tables
+pub fn structured() -> bool {
+    output_format() != OutputFormat::Text
+}
+
+/// `println!` for messages about a run: on stdout with text output, on
+/// stderr when stdout carries records
+#[macro_export]
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::output::structured() { eprintln!($($arg)*) } else { println!($($arg)*) }
+    };
+}
+
+/// A row of a command's machine-readable output
+pub trait Record: Serialize {
+    /// Schema name, `rmf.` and the kind of record
+    const SCHEMA: &'static str;
+    /// Table of a results database holding records of this schema
+    const TABLE: &'static str;
+    /// Serialized field names, in CSV column order
+    const COLUMNS: &'static [&'static str];
+}
+
+/// Whether `path` names a results database rather than a JSON or CSV file:
+/// `*.db`, `*.sqlite` or `*.sqlite3`
+pub fn is_database_path(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref(),
+        Some("db") | Some("sqlite") | Some("sqlite3")
+    )
+}
+
+/// Record a run on `dump` holding `records` in the results database at `path`
+#[cfg(feature = "sqlite")]
+pub fn save_to_database<R: Record>(path: &Path, dump: &Path, records: &[R]) -> Result<()> {
+    crate::resultsdb::save(path, dump, records)
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn save_to_database<R: Record>(path: &Path, _dump: &Path, _records: &[R]) -> Result<()> {
+    anyhow::bail!("Cannot write {}: rmf was built without the `sqlite` feature", path.display())
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, R> {
+    schema: &'static str,
+    version: u32,
+    records: &'a [R],
+}
+
+/// `records` as JSON or CSV; nothing for text output, and SQLite output is
+/// only written to a database
+pub fn render<R: Record>(records: &[R], format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Text | OutputFormat::Sqlite => Ok(String::new()),
+        OutputFormat::Json => {
+            let envelope = Envelope { schema: R::SCHEMA, version: SCHEMA_VERSION, records };
+            Ok(serde_json::to_string_pretty(&envelope)? + "\n")
+        }
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(R::COLUMNS)?;
+            for record in records {
+                let value = serde_json::to_value(record)?;
+                writer.write_record(R::COLUMNS.iter().map(|column| match value.get(column) {
+                    None | Some(serde_json::Value::Null) => String::new(),
+                    Some(serde_json::Value::String(text)) => text.clone(),
+                    Some(other) => other.to_string(),
+                }))?;
+            }
+            Ok(String::from_utf8(writer.into_inner().context("Failed to write CSV")?)?)
+        }
+    }
+}
+
+/// Write `records` of a run on `dump` in the selected format to the selected
+/// destination
+pub fn emit<R: Record>(dump: &Path, records: &[R]) -> Result<()> {
+    let destination = DESTINATION.lock().unwrap();
+    if let (OutputFormat::Sqlite, Some(path)) = (output_format(), destination.as_ref()) {
+        save_to_database(path, dump, records)?;
+        eprintln!("Added {} {} records to {}", records.len(), R::SCHEMA, path.display());
+        return Ok(());
+    }
+    let text = render(records, output_format())?;
+    match destination.as_ref() {
+        Some(path) => {
+            std::fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Wrote {} {} records to {}", records.len(), R::SCHEMA, path.display());
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
@@ -1,14 +1,33 @@
-use anyhow::Result;
+use anyhow::{bail, Context};
 use memmap2::Mmap;
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::coverage::CoverageMap;
+use crate::output::Record;
+
+use crate::formats::acquisition::AcquisitionMetadata;
+use crate::formats::compressed::Compression;
+use crate::usermode::UserSpace;
 
 use crate::arch::x86_64::{
-    PML4Entry, PDPTEntry, PDEntry, PTEntry, VirtualAddress, PAGE_SIZE
+    PML4Entry, PDPTEntry, PDEntry, PTEntry, VirtualAddress
 };
+use crate::arch::{riscv, x86, x86_pae};
 
 /// Different CPU architectures supported by the memory forensics tool
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Architecture {
     X86_64,
+    /// 32-bit x86 with Physical Address Extension (3-level paging)
+    X86Pae,
+    /// Legacy 32-bit x86 without PAE (2-level paging)
+    X86,
+    /// RISC-V Sv39 (3-level paging)
+    RiscvSv39,
+    /// RISC-V Sv48 (4-level paging)
+    RiscvSv48,
     // Other architectures could be added here in the future
 }
 
@@ -21,42 +40,401 @@ pub enum PageTableType {
     FiveLevel,
 }
 
-/// Memory image information
+/// Container format the memory image was loaded from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    /// Flat physical memory dump
+    Raw,
+    /// VirtualBox `.elf` core dump (`VBoxManage debugvm dumpvmcore`)
+    VBoxElf,
+    /// Generic ELF core with physical PT_LOAD segments
+    ElfCore,
+    /// Raw dump split across several files (`dump.001`, `dump.002`, ...)
+    Segmented,
+    /// Windows user-mode minidump of a single process
+    Minidump,
+}
+
+/// Bytes of one dump file: a file mapping, or a buffer already in memory
 #[derive(Debug)]
+pub enum Segment {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl std::ops::Deref for Segment {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Segment::Mapped(mmap) => mmap,
+            Segment::Owned(bytes) => bytes,
+        }
+    }
+}
+
+impl From<Mmap> for Segment {
+    fn from(mmap: Mmap) -> Self {
+        Segment::Mapped(mmap)
+    }
+}
+
+impl From<Vec<u8>> for Segment {
+    fn from(bytes: Vec<u8>) -> Self {
+        Segment::Owned(bytes)
+    }
+}
+
+/// A contiguous run of physical memory backed by a region of the dump file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalRun {
+    pub start: u64,       // First physical address covered by the run
+    pub length: u64,      // Length of the run in bytes
+    pub file_offset: u64, // Offset of the run's data in the dump file
+    pub segment: usize,   // Index of the dump file holding the run
+}
+
+impl PhysicalRun {
+    pub fn end(&self) -> u64 {
+        self.start + self.length
+    }
+}
+
+/// A slice of the physical address space, `start..end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl AddressRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+}
+
+impl FromStr for AddressRange {
+    type Err = anyhow::Error;
+
+    /// `start:end` in hex, end exclusive
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (start, end) = s.split_once(':').context("expected start:end, e.g. 0x100000000:0x140000000")?;
+        let parse = |value: &str| {
+            let digits = value.trim().trim_start_matches("0x").trim_start_matches("0X");
+            u64::from_str_radix(digits, 16).with_context(|| format!("'{}' is not a hex address", value))
+        };
+        let range = AddressRange { start: parse(start)?, end: parse(end)? };
+        if range.is_empty() {
+            bail!("range end 0x{:X} must be above its start 0x{:X}", range.end, range.start);
+        }
+        Ok(range)
+    }
+}
+
+impl std::fmt::Display for AddressRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{:X}-0x{:X}", self.start, self.end)
+    }
+}
+
+/// A present leaf mapping found by walking the page tables
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mapping {
+    pub va: u64,          // Canonical virtual address of the page
+    pub pa: u64,          // Physical address it maps to
+    pub size: u64,        // Page size: 4KB, 2MB or 1GB
+    pub executable: bool, // No level of the walk sets NX
+}
+
+/// Where a virtual address falls in the address space layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressClass {
+    User,
+    Kernel,
+    /// Upper bits are not a sign extension of the top bit; any access faults
+    NonCanonical,
+    /// The first 64KB, never mapped so that null pointer accesses fault
+    NullGuard,
+    /// The highest 64KB of the user half, left unmapped below the kernel half
+    BoundaryGuard,
+    /// 32-bit address, where the user/kernel split is chosen by the OS
+    Unsplit,
+}
+
+impl std::fmt::Display for AddressClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            AddressClass::User => "user half",
+            AddressClass::Kernel => "kernel half",
+            AddressClass::NonCanonical => "non-canonical (accesses fault)",
+            AddressClass::NullGuard => "null guard region (user half)",
+            AddressClass::BoundaryGuard => "guard region at the top of the user half",
+            AddressClass::Unsplit => "32-bit (user/kernel split set by the OS)",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Classify `va` for the paging scheme `arch`
+pub fn classify_address(arch: Architecture, va: u64) -> AddressClass {
+    const GUARD_SIZE: u64 = 0x10000;
+    let bits = match arch {
+        Architecture::X86 | Architecture::X86Pae => {
+            return if va > u32::MAX as u64 { AddressClass::NonCanonical } else { AddressClass::Unsplit };
+        }
+        Architecture::X86_64 | Architecture::RiscvSv48 => 48,
+        Architecture::RiscvSv39 => 39,
+    };
+    let upper = (va as i64) >> (bits - 1);
+    let top_of_user = (1u64 << (bits - 1)) - 1;
+    match upper {
+        -1 => AddressClass::Kernel,
+        0 if va < GUARD_SIZE => AddressClass::NullGuard,
+        0 if va > top_of_user - GUARD_SIZE => AddressClass::BoundaryGuard,
+        0 => AddressClass::User,
+        _ => AddressClass::NonCanonical,
+    }
+}
+
+/// One page table entry read during a walk
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WalkStep {
+    /// Table level, named as the architecture manuals do (PML4E, PDE, ...)
+    pub level: &'static str,
+    pub index: usize,
+    /// Physical address of the entry
+    pub entry_addr: u64,
+    pub value: u64,
+    /// Set flags, e.g. `P RW US A NX`
+    pub flags: String,
+    /// The entry maps a page rather than the next table
+    pub leaf: bool,
+    /// Size of the page mapped by a leaf entry
+    pub page_size: Option<u64>,
+}
+
+/// Every entry read translating one virtual address, and where the walk ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageWalk {
+    pub va: u64,
+    pub class: AddressClass,
+    pub steps: Vec<WalkStep>,
+    pub pa: Option<u64>,
+    /// Why translation failed, when it did
+    pub fault: Option<String>,
+}
+
+/// A translation as `translate --format json|csv` reports it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TranslationRecord {
+    pub virtual_address: String,
+    pub class: String,
+    pub dtb: Option<String>,
+    pub physical_address: Option<String>,
+    pub page_size: Option<u64>,
+    pub fault: Option<String>,
+    /// The first 16 bytes at the physical address, in hex
+    pub bytes: Option<String>,
+    pub steps: Vec<WalkStep>,
+}
+
+impl Record for TranslationRecord {
+    const SCHEMA: &'static str = "rmf.translation";
+    const TABLE: &'static str = "translations";
+    const COLUMNS: &'static [&'static str] = &["virtual_address", "class", "dtb", "physical_address", "page_size", "fault", "bytes", "steps"];
+}
+
+impl TranslationRecord {
+    /// The record of `walk`, made through the page tables of `img`
+    pub fn new(img: &MemoryImage, walk: &PageWalk) -> Self {
+        let bytes = walk.pa.and_then(|pa| img.get_bytes(pa as usize, 16));
+        TranslationRecord {
+            virtual_address: format!("0x{:X}", walk.va),
+            class: walk.class.to_string(),
+            dtb: img.info.dtb.map(|dtb| format!("0x{:X}", dtb)),
+            physical_address: walk.pa.map(|pa| format!("0x{:X}", pa)),
+            page_size: walk.steps.iter().find(|step| step.leaf).and_then(|step| step.page_size),
+            fault: walk.fault.clone(),
+            bytes: bytes.map(|bytes| bytes.iter().map(|b| format!("{:02x}", b)).collect()),
+            steps: walk.steps.clone(),
+        }
+    }
+}
+
+/// One level of an x86 page walk
+struct X86Level {
+    name: &'static str,
+    shift: u32,
+    index_bits: u32,
+    entry_size: u64,
+    /// Page size mapped when the PS bit is set at this level
+    large: Option<u64>,
+}
+
+/// Names of the set x86 flags; bit 7 is PS above the last level and PAT in it
+fn x86_flags(value: u64, last: bool) -> String {
+    let names = [(0, "P"), (1, "RW"), (2, "US"), (3, "PWT"), (4, "PCD"), (5, "A"), (6, "D"), (7, if last { "PAT" } else { "PS" }), (8, "G"), (63, "NX")];
+    names.iter().filter(|(bit, _)| value >> bit & 1 != 0).map(|(_, name)| *name).collect::<Vec<_>>().join(" ")
+}
+
+fn riscv_flags(value: u64) -> String {
+    let names = ["V", "R", "W", "X", "U", "G", "A", "D"];
+    names.iter().enumerate().filter(|(bit, _)| value >> bit & 1 != 0).map(|(_, name)| *name).collect::<Vec<_>>().join(" ")
+}
+
+/// Register state of a CPU recovered from the dump container
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuState {
+    pub rip: u64,
+    pub rsp: u64,
+    pub cr0: u64,
+    pub cr3: u64,
+    pub cr4: u64,
+}
+
+/// Memory image information
+#[derive(Debug, Clone)]
 pub struct MemoryImageInfo {
     pub arch: Architecture,
     pub page_table_type: PageTableType,
+    pub format: ImageFormat,
+    pub compression: Option<Compression>, // Compression wrapper the dump was inflated from
     pub cr3: Option<u64>,   // Control register 3 (page table base)
     pub dtb: Option<u64>,   // Directory Table Base (another name for CR3)
-    pub size: usize,        // Size of the memory image in bytes
+    pub size: usize,        // Size of the physical address space in bytes
+    pub cpus: Vec<CpuState>, // Per-CPU registers, when the format records them
+    pub user: Option<UserSpace>, // Process address space for user-mode dumps
+    pub acquisition: AcquisitionMetadata, // Capture times and tool markers from the dump header
+    pub range: Option<AddressRange>, // Slice of the dump the image was restricted to
 }
 
 #[derive(Debug)]
 pub struct MemoryImage {
-    mmap: Mmap,
+    // One mapping per dump file; a plain dump has exactly one. Shared with
+    // the views returned by `with_dtb`
+    segments: Arc<[Segment]>,
+    // Physical runs sorted by start address
+    runs: Arc<[PhysicalRun]>,
+    // Records which pages were read, when coverage tracking is enabled
+    coverage: Option<Arc<CoverageMap>>,
     // Memory image information and metadata
     pub info: MemoryImageInfo,
 }
 
 impl MemoryImage {
-    pub fn new(mmap: Mmap) -> Self {
-        let size = mmap.len();
-        Self { 
-            mmap,
+    pub fn new(data: impl Into<Segment>) -> Self {
+        let data = data.into();
+        let run = PhysicalRun { start: 0, length: data.len() as u64, file_offset: 0, segment: 0 };
+        Self::with_runs(data, vec![run], ImageFormat::Raw)
+    }
+
+    /// Create a memory image whose physical address space is described by a run map
+    pub fn with_runs(data: impl Into<Segment>, runs: Vec<PhysicalRun>, format: ImageFormat) -> Self {
+        Self::with_segments(vec![data.into()], runs, format)
+    }
+
+    /// Create a memory image from raw dump segments laid end to end in order
+    pub fn from_segments(segments: Vec<Mmap>) -> Self {
+        let mut start = 0;
+        let runs = segments
+            .iter()
+            .enumerate()
+            .map(|(segment, mmap)| {
+                let run = PhysicalRun { start, length: mmap.len() as u64, file_offset: 0, segment };
+                start += run.length;
+                run
+            })
+            .collect();
+        Self::with_segments(segments.into_iter().map(Segment::from).collect(), runs, ImageFormat::Segmented)
+    }
+
+    fn with_segments(segments: Vec<Segment>, mut runs: Vec<PhysicalRun>, format: ImageFormat) -> Self {
+        // Drop runs that point past the end of their file or the address space
+        runs.retain(|r| {
+            r.length > 0
+                && r.start.checked_add(r.length).is_some()
+                && segments.get(r.segment).is_some_and(|m| r.file_offset.checked_add(r.length).is_some_and(|end| end <= m.len() as u64))
+        });
+        runs.sort_by_key(|r| r.start);
+        let size = runs.last().map(|r| r.end() as usize).unwrap_or(0);
+
+        Self {
+            segments: segments.into(),
+            runs: runs.into(),
+            coverage: None,
             info: MemoryImageInfo {
                 arch: Architecture::X86_64,
                 page_table_type: PageTableType::Standard,
+                format,
+                compression: None,
                 cr3: None,
                 dtb: None,
                 size,
+                cpus: Vec::new(),
+                user: None,
+                acquisition: AcquisitionMetadata::default(),
+                range: None,
             }
         }
     }
 
+    /// Physical runs backing this image
+    pub fn runs(&self) -> &[PhysicalRun] {
+        &self.runs
+    }
+
+    /// Drop every byte outside `range`, keeping the remaining bytes at their
+    /// original physical addresses so offsets still match the full dump
+    pub fn restrict_to(&mut self, range: AddressRange) -> &mut Self {
+        self.restrict_to_ranges(&[range]);
+        self.info.range = Some(range);
+        self
+    }
+
+    /// Drop every byte outside the sorted, non-overlapping `ranges`, such
+    /// as the hit map of an earlier scan, keeping original addresses
+    pub fn restrict_to_ranges(&mut self, ranges: &[AddressRange]) -> &mut Self {
+        let runs: Vec<PhysicalRun> = ranges.iter().flat_map(|range| {
+            self.runs.iter().filter_map(move |run| {
+                let (start, end) = (run.start.max(range.start), run.end().min(range.end));
+                (start < end).then(|| PhysicalRun {
+                    start,
+                    length: end - start,
+                    file_offset: run.file_offset + (start - run.start),
+                    segment: run.segment,
+                })
+            })
+        }).collect();
+        self.info.size = runs.last().map_or(0, |r| r.end() as usize);
+        self.runs = runs.into();
+        self
+    }
+
+    /// Record CPU register state and use the first valid CR3 as the default DTB
+    pub fn set_cpus(&mut self, cpus: Vec<CpuState>) -> &mut Self {
+        if self.info.dtb.is_none() {
+            if let Some(cr3) = cpus.iter().map(|c| c.cr3 & !0xFFF).find(|&cr3| cr3 != 0) {
+                self.set_cr3(cr3);
+            }
+        }
+        self.info.cpus = cpus;
+        self
+    }
+
     pub fn size(&self) -> usize {
         self.info.size
     }
 
+    /// Select the paging scheme used by `virt_to_phys`
+    pub fn set_arch(&mut self, arch: Architecture) -> &mut Self {
+        self.info.arch = arch;
+        self
+    }
+
     /// Set the CR3 register value for this memory image
     pub fn set_cr3(&mut self, cr3: u64) -> &mut Self {
         self.info.cr3 = Some(cr3);
@@ -64,19 +442,233 @@ impl MemoryImage {
         self
     }
 
-    pub fn get_bytes(&self, offset: usize, len: usize) -> Option<&[u8]> {
-        if offset + len <= self.info.size {
-            Some(&self.mmap[offset..offset + len])
-        } else {
-            None
+    /// A view of the same physical memory translating through `dtb`, such as
+    /// a process's page table root, leaving this image's DTB untouched
+    ///
+    /// The view shares the dump's mappings and coverage map, so it is cheap
+    /// to create. User-mode dumps hold a single address space and ignore `dtb`.
+    pub fn with_dtb(&self, dtb: u64) -> MemoryImage {
+        let mut view = MemoryImage {
+            segments: self.segments.clone(),
+            runs: self.runs.clone(),
+            coverage: self.coverage.clone(),
+            info: self.info.clone(),
+        };
+        view.set_cr3(dtb);
+        view
+    }
+
+    /// Start recording which physical pages are read from this image
+    pub fn track_coverage(&mut self) -> Arc<CoverageMap> {
+        let coverage = Arc::new(CoverageMap::new(self.size()));
+        self.coverage = Some(coverage.clone());
+        coverage
+    }
+
+    fn record_read(&self, offset: usize, len: usize) {
+        if let Some(coverage) = &self.coverage {
+            coverage.record(offset, len);
         }
     }
 
-    /// Virtual to physical address translation for x86_64
+    /// Return the bytes from `offset` to the end of the run containing it
+    fn run_slice(&self, offset: usize) -> Option<&[u8]> {
+        let addr = offset as u64;
+        let idx = self.runs.partition_point(|r| r.end() <= addr);
+        let run = self.runs.get(idx).filter(|r| r.start <= addr)?;
+        let file_start = (run.file_offset + (addr - run.start)) as usize;
+        let file_end = (run.file_offset + run.length) as usize;
+        Some(&self.segments[run.segment][file_start..file_end])
+    }
+
+    pub fn get_bytes(&self, offset: usize, len: usize) -> Option<&[u8]> {
+        let bytes = self.run_slice(offset)?.get(..len)?;
+        self.record_read(offset, len);
+        Some(bytes)
+    }
+
+    /// Virtual to physical address translation using the image's architecture
     pub fn virt_to_phys(&self, virt_addr: u64) -> Option<u64> {
+        // User-mode dumps record the process address space directly
+        if let Some(user) = &self.info.user {
+            return user.va_to_offset(virt_addr);
+        }
+        
         // If we don't have a DTB/CR3, we can't do translation
         let dtb = self.info.dtb?;
         
+        match self.info.arch {
+            Architecture::X86_64 => self.translate_x86_64(dtb, virt_addr),
+            Architecture::X86Pae => self.translate_x86_pae(dtb, virt_addr),
+            Architecture::X86 => self.translate_x86(dtb, virt_addr),
+            Architecture::RiscvSv39 => self.translate_riscv(dtb, virt_addr, 3),
+            Architecture::RiscvSv48 => self.translate_riscv(dtb, virt_addr, 4),
+        }
+    }
+    
+    /// Every present leaf mapping under the DTB, in virtual address order
+    ///
+    /// Only x86_64 paging is walked; other architectures return nothing.
+    pub fn mappings(&self) -> Vec<Mapping> {
+        let mut mappings = Vec::new();
+        if let (Some(dtb), Architecture::X86_64) = (self.info.dtb, self.info.arch) {
+            self.walk_x86_64(dtb & 0x000F_FFFF_FFFF_F000, 4, 0, true, &mut mappings);
+        }
+        mappings
+    }
+    
+    fn walk_x86_64(&self, table: u64, level: u32, va_base: u64, executable: bool, out: &mut Vec<Mapping>) {
+        let Some(entries) = self.get_bytes(table as usize, 0x1000) else { return };
+        let shift = 12 + 9 * (level - 1);
+        for (index, entry) in entries.chunks_exact(8).enumerate() {
+            let entry = u64::from_le_bytes(entry.try_into().unwrap());
+            if entry & 1 == 0 {
+                continue;
+            }
+            let mut va = va_base | ((index as u64) << shift);
+            // Sign-extend bit 47 into a canonical address
+            if level == 4 && index >= 256 {
+                va |= 0xFFFF_0000_0000_0000;
+            }
+            let executable = executable && entry >> 63 == 0;
+            let frame = entry & 0x000F_FFFF_FFFF_F000;
+            let large = (level == 3 || level == 2) && entry & (1 << 7) != 0;
+            if level == 1 || large {
+                let size = 1u64 << shift;
+                out.push(Mapping { va, pa: frame & !(size - 1), size, executable });
+            } else if level > 1 {
+                self.walk_x86_64(frame, level - 1, va, executable, out);
+            }
+        }
+    }
+    
+    /// Translate `va` recording every table entry read, like WinDbg's `!pte`
+    ///
+    /// User-mode dumps have no page tables and return no steps.
+    pub fn page_walk(&self, va: u64) -> PageWalk {
+        let mut walk = PageWalk { va, class: classify_address(self.info.arch, va), steps: Vec::new(), pa: None, fault: None };
+        if let Some(user) = &self.info.user {
+            walk.pa = user.va_to_offset(va);
+            return walk;
+        }
+        let Some(dtb) = self.info.dtb else {
+            walk.fault = Some("no DTB".to_string());
+            return walk;
+        };
+        if walk.class == AddressClass::NonCanonical {
+            walk.fault = Some("non-canonical address".to_string());
+            return walk;
+        }
+        
+        let level = |name, shift, index_bits, entry_size, large| X86Level { name, shift, index_bits, entry_size, large };
+        match self.info.arch {
+            Architecture::X86_64 => self.walk_x86(&mut walk, dtb & 0x000F_FFFF_FFFF_F000, 0x000F_FFFF_FFFF_F000, &[
+                level("PML4E", 39, 9, 8, None),
+                level("PDPTE", 30, 9, 8, Some(1 << 30)),
+                level("PDE", 21, 9, 8, Some(1 << 21)),
+                level("PTE", 12, 9, 8, None),
+            ]),
+            Architecture::X86Pae => self.walk_x86(&mut walk, x86_pae::pdpt_base(dtb), 0x000F_FFFF_FFFF_F000, &[
+                level("PDPTE", 30, 2, 8, None),
+                level("PDE", 21, 9, 8, Some(1 << 21)),
+                level("PTE", 12, 9, 8, None),
+            ]),
+            Architecture::X86 => self.walk_x86(&mut walk, x86::page_directory_base(dtb), 0xFFFF_F000, &[
+                level("PDE", 22, 10, 4, Some(1 << 22)),
+                level("PTE", 12, 10, 4, None),
+            ]),
+            Architecture::RiscvSv39 => self.walk_riscv(&mut walk, dtb, 3),
+            Architecture::RiscvSv48 => self.walk_riscv(&mut walk, dtb, 4),
+        }
+        walk
+    }
+    
+    fn walk_x86(&self, walk: &mut PageWalk, root: u64, frame_mask: u64, levels: &[X86Level]) {
+        let mut table = root;
+        for (depth, level) in levels.iter().enumerate() {
+            let last = depth == levels.len() - 1;
+            let index = ((walk.va >> level.shift) & ((1 << level.index_bits) - 1)) as usize;
+            let entry_addr = table + index as u64 * level.entry_size;
+            let value = match level.entry_size {
+                4 => self.read_u32(entry_addr as usize).map(u64::from),
+                _ => self.read_u64(entry_addr as usize),
+            };
+            let Some(value) = value else {
+                walk.fault = Some(format!("{} at 0x{:X} is outside the image", level.name, entry_addr));
+                return;
+            };
+            let large = level.large.filter(|_| value & 0x80 != 0);
+            let page_size = if last { Some(1 << level.shift) } else { large };
+            let present = value & 1 != 0;
+            walk.steps.push(WalkStep {
+                level: level.name,
+                index,
+                entry_addr,
+                value,
+                flags: x86_flags(value, last),
+                leaf: present && page_size.is_some(),
+                page_size: page_size.filter(|_| present),
+            });
+            if !present {
+                walk.fault = Some(format!("{} not present", level.name));
+                return;
+            }
+            if let Some(size) = page_size {
+                // 32-bit 4MB pages keep PSE-36 address bits above bit 31
+                let base = match (large, level.entry_size) {
+                    (Some(_), 4) => x86::PDEntry::new(value as u32).get_large_page_address(),
+                    _ => value & frame_mask & !(size - 1),
+                };
+                walk.pa = Some(base + (walk.va & (size - 1)));
+                return;
+            }
+            table = value & frame_mask;
+        }
+    }
+    
+    fn walk_riscv(&self, walk: &mut PageWalk, dtb: u64, levels: usize) {
+        const NAMES: [&str; 4] = ["L0", "L1", "L2", "L3"];
+        let va = riscv::VirtualAddress::new(walk.va);
+        let mut table = riscv::root_table(dtb);
+        for level in (0..levels).rev() {
+            let index = va.get_vpn(level);
+            let entry_addr = table + index as u64 * 8;
+            let Some(value) = self.read_u64(entry_addr as usize) else {
+                walk.fault = Some(format!("{} entry at 0x{:X} is outside the image", NAMES[level], entry_addr));
+                return;
+            };
+            let pte = riscv::PageTableEntry::new(value);
+            let size = 1u64 << (12 + 9 * level);
+            let leaf = pte.is_valid() && pte.is_leaf();
+            walk.steps.push(WalkStep {
+                level: NAMES[level],
+                index,
+                entry_addr,
+                value,
+                flags: riscv_flags(value),
+                leaf,
+                page_size: leaf.then_some(size),
+            });
+            if !pte.is_valid() || pte.is_reserved() {
+                walk.fault = Some(format!("{} entry {}", NAMES[level], if pte.is_valid() { "reserved" } else { "not valid" }));
+                return;
+            }
+            if leaf {
+                let base = pte.get_physical_address();
+                if base & (size - 1) != 0 {
+                    walk.fault = Some("misaligned superpage".to_string());
+                    return;
+                }
+                walk.pa = Some(base + va.get_page_offset(level));
+                return;
+            }
+            table = pte.get_physical_address();
+        }
+        walk.fault = Some("no leaf entry at the last level".to_string());
+    }
+    
+    /// 4-level x86_64 page walk
+    fn translate_x86_64(&self, dtb: u64, virt_addr: u64) -> Option<u64> {
         // Create a virtual address structure
         let va = VirtualAddress::new(virt_addr);
         
@@ -142,76 +734,187 @@ impl MemoryImage {
         Some(pte.get_physical_address() + offset as u64)
     }
     
+    /// 3-level 32-bit PAE page walk
+    fn translate_x86_pae(&self, dtb: u64, virt_addr: u64) -> Option<u64> {
+        // PAE only covers a 32-bit virtual address space
+        let va = x86_pae::VirtualAddress::new(u32::try_from(virt_addr).ok()?);
+        
+        // Get PDPT entry
+        let pdpte_addr = x86_pae::pdpt_base(dtb) + (va.get_pdpt_index() * 8) as u64;
+        let pdpte = x86_pae::PDPTEntry::new(self.read_u64(pdpte_addr as usize)?);
+        
+        if !pdpte.is_present() {
+            return None;
+        }
+        
+        // Get PD entry
+        let pde_addr = pdpte.get_physical_address() + (va.get_pd_index() * 8) as u64;
+        let pde = PDEntry::new(self.read_u64(pde_addr as usize)?);
+        
+        if !pde.is_present() {
+            return None;
+        }
+        
+        // Check if this is a 2MB page
+        if pde.is_page_size_2mb() {
+            let large_page_base = pde.get_physical_address() & !0x1F_FFFF;
+            return Some(large_page_base + va.get_large_page_offset() as u64);
+        }
+        
+        // Get PT entry
+        let pte_addr = pde.get_physical_address() + (va.get_pt_index() * 8) as u64;
+        let pte = PTEntry::new(self.read_u64(pte_addr as usize)?);
+        
+        if !pte.is_present() {
+            return None;
+        }
+        
+        Some(pte.get_physical_address() + va.get_page_offset() as u64)
+    }
+    
+    /// 2-level 32-bit x86 page walk
+    fn translate_x86(&self, dtb: u64, virt_addr: u64) -> Option<u64> {
+        let va = x86::VirtualAddress::new(u32::try_from(virt_addr).ok()?);
+        
+        // Get PD entry
+        let pde_addr = x86::page_directory_base(dtb) + (va.get_pd_index() * 4) as u64;
+        let pde = x86::PDEntry::new(self.read_u32(pde_addr as usize)?);
+        
+        if !pde.is_present() {
+            return None;
+        }
+        
+        // Check if this is a 4MB page
+        if pde.is_page_size_4mb() {
+            return Some(pde.get_large_page_address() + va.get_large_page_offset() as u64);
+        }
+        
+        // Get PT entry
+        let pte_addr = pde.get_physical_address() + (va.get_pt_index() * 4) as u64;
+        let pte = x86::PTEntry::new(self.read_u32(pte_addr as usize)?);
+        
+        if !pte.is_present() {
+            return None;
+        }
+        
+        Some(pte.get_physical_address() + va.get_page_offset() as u64)
+    }
+    
+    /// RISC-V Sv39/Sv48 page walk over `levels` levels
+    fn translate_riscv(&self, dtb: u64, virt_addr: u64, levels: usize) -> Option<u64> {
+        let va = riscv::VirtualAddress::new(virt_addr);
+        if !va.is_canonical(levels) {
+            return None;
+        }
+        
+        let mut table = riscv::root_table(dtb);
+        for level in (0..levels).rev() {
+            let pte_addr = table + (va.get_vpn(level) * 8) as u64;
+            let pte = riscv::PageTableEntry::new(self.read_u64(pte_addr as usize)?);
+            
+            if !pte.is_valid() || pte.is_reserved() {
+                return None;
+            }
+            
+            if pte.is_leaf() {
+                // Superpages must be aligned to their size
+                let base = pte.get_physical_address();
+                if base & ((1u64 << (12 + 9 * level)) - 1) != 0 {
+                    return None;
+                }
+                return Some(base + va.get_page_offset(level));
+            }
+            
+            table = pte.get_physical_address();
+        }
+        
+        // A non-leaf entry at the last level is a fault
+        None
+    }
+    
     /// Read a u64 value from the memory image at the given offset
     pub fn read_u64(&self, offset: usize) -> Option<u64> {
-        if offset + 8 <= self.info.size {
-            let bytes = &self.mmap[offset..offset + 8];
-            let value = u64::from_le_bytes([
-                bytes[0], bytes[1], bytes[2], bytes[3], 
-                bytes[4], bytes[5], bytes[6], bytes[7]
-            ]);
-            Some(value)
-        } else {
-            None
+        let bytes = self.get_bytes(offset, 8)?;
+        let value = u64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], 
+            bytes[4], bytes[5], bytes[6], bytes[7]
+        ]);
+        Some(value)
+    }
+    
+    /// Read a u64 value at a virtual address
+    pub fn read_virt_u64(&self, virt_addr: u64) -> Option<u64> {
+        self.read_u64(self.virt_to_phys(virt_addr)? as usize)
+    }
+    
+    /// Read a u32 value at a virtual address
+    pub fn read_virt_u32(&self, virt_addr: u64) -> Option<u32> {
+        self.read_u32(self.virt_to_phys(virt_addr)? as usize)
+    }
+    
+    /// Read `len` bytes at a virtual address, failing if any page is unmapped
+    pub fn read_virt(&self, virt_addr: u64, len: usize) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            let va = virt_addr + out.len() as u64;
+            let take = (len - out.len()).min(0x1000 - (va & 0xFFF) as usize);
+            out.extend_from_slice(self.get_bytes(self.virt_to_phys(va)? as usize, take)?);
+        }
+        Some(out)
+    }
+    
+    /// Read a Windows UNICODE_STRING structure at a virtual address
+    pub fn read_unicode_string(&self, virt_addr: u64) -> Option<String> {
+        let length = self.read_virt_u32(virt_addr)? & 0xFFFF;
+        let buffer = self.read_virt_u64(virt_addr + 8)?;
+        if length == 0 || buffer == 0 {
+            return None;
         }
+        // The buffer may cross into a page mapped to an unrelated frame
+        let data = self.read_virt(buffer, length as usize)?;
+        let chars: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        String::from_utf16(&chars).ok()
     }
     
     /// Read a u32 value from the memory image
     pub fn read_u32(&self, offset: usize) -> Option<u32> {
-        if offset + 4 <= self.info.size {
-            let bytes = &self.mmap[offset..offset + 4];
-            let value = u32::from_le_bytes([
-                bytes[0], bytes[1], bytes[2], bytes[3]
-            ]);
-            Some(value)
-        } else {
-            None
-        }
+        let bytes = self.get_bytes(offset, 4)?;
+        let value = u32::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3]
+        ]);
+        Some(value)
     }
     
     /// Read a null-terminated ASCII string from the memory image
     pub fn read_ascii_string(&self, offset: usize, max_len: usize) -> Option<String> {
-        if offset >= self.info.size {
-            return None;
-        }
-        
-        let mut end = offset;
-        let max_end = std::cmp::min(offset + max_len, self.info.size);
+        let data = self.run_slice(offset)?;
+        let data = &data[..max_len.min(data.len())];
         
         // Find the null terminator
-        while end < max_end && self.mmap[end] != 0 {
-            end += 1;
-        }
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        self.record_read(offset, end + 1);
         
         // Convert the bytes to a string
-        let string_bytes = &self.mmap[offset..end];
-        String::from_utf8(string_bytes.to_vec()).ok()
+        String::from_utf8(data[..end].to_vec()).ok()
     }
     
     /// Read a null-terminated UTF-16 (wide) string from the memory image
     pub fn read_utf16_string(&self, offset: usize, max_len: usize) -> Option<String> {
-        if offset + 1 >= self.info.size {
+        let data = self.run_slice(offset)?;
+        if data.len() < 2 {
             return None;
         }
         
-        let mut chars = Vec::new();
-        let max_chars = max_len / 2;
-        
-        for i in 0..max_chars {
-            if offset + (i * 2) + 1 >= self.info.size {
-                break;
-            }
-            
-            let low = self.mmap[offset + (i * 2)] as u16;
-            let high = self.mmap[offset + (i * 2) + 1] as u16;
-            let c = low | (high << 8);
-            
-            if c == 0 {
-                break;
-            }
-            
-            chars.push(c);
-        }
+        let chars: Vec<u16> = data[..max_len.min(data.len())]
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        self.record_read(offset, chars.len() * 2 + 2);
         
         String::from_utf16(&chars).ok()
     }
@@ -0,0 +1,754 @@
+//! Export and import tables of PE images in memory
+//!
+//! Images are read as the loader mapped them, through an address space:
+//! every RVA is an offset from the image base. The export directory names
+//! the module and its exported functions; the import directory lists, per
+//! imported module, the functions the image uses and the IAT slots the
+//! loader filled with their addresses. Paged out tables read as empty.
+//!
+//! A mapped image copied out of memory is turned back into a file that
+//! disassemblers load like the original by [`rebuild_pe`]. [`PeHashes`]
+//! are the imphash, Rich header hash and section hashes analysts look an
+//! image up by in threat intelligence databases.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::paging::MemoryImage;
+
+/// Data directories after the optional header's fields, PE32 and PE32+
+const PE32_DIRECTORIES: u64 = 0x60;
+const PE32_PLUS_DIRECTORIES: u64 = 0x70;
+/// Indices of the export and import data directories
+const EXPORT_DIRECTORY: u64 = 0;
+const IMPORT_DIRECTORY: u64 = 1;
+/// IMAGE_EXPORT_DIRECTORY offsets
+const EXPORT_MODULE_NAME: u64 = 0x0C;
+const EXPORT_ORDINAL_BASE: u64 = 0x10;
+const EXPORT_FUNCTION_COUNT: u64 = 0x14;
+const EXPORT_NAME_COUNT: u64 = 0x18;
+const EXPORT_FUNCTIONS: u64 = 0x1C;
+const EXPORT_NAMES: u64 = 0x20;
+const EXPORT_NAME_ORDINALS: u64 = 0x24;
+/// Upper bound on exports read from one module
+const MAX_EXPORTS: usize = 0x10000;
+const MAX_NAME: usize = 0x100;
+/// IMAGE_IMPORT_DESCRIPTOR size and offsets
+const IMPORT_DESCRIPTOR_SIZE: u64 = 0x14;
+const IMPORT_NAME_TABLE: u64 = 0;
+const IMPORT_MODULE_NAME: u64 = 0x0C;
+const IMPORT_ADDRESS_TABLE: u64 = 0x10;
+/// Upper bounds on imported modules and on the functions of one
+const MAX_IMPORT_MODULES: usize = 0x400;
+const MAX_IMPORTS: usize = 0x4000;
+/// Data directories holding file offsets and loader hints, which a rebuilt
+/// image no longer matches
+const SECURITY_DIRECTORY: usize = 4;
+const BOUND_IMPORT_DIRECTORY: usize = 11;
+/// Size of an IMAGE_SECTION_HEADER
+pub(crate) const SECTION_HEADER_SIZE: usize = 40;
+/// The section added for a rebuilt import table: initialized, readable and
+/// writable data
+const IMPORT_SECTION_NAME: &[u8; 8] = b".rmfimp\0";
+const IMPORT_SECTION_FLAGS: u32 = 0xC000_0040;
+/// File alignment of a rebuilt image whose headers give no valid one
+const DEFAULT_FILE_ALIGNMENT: usize = 0x200;
+/// Sections the Windows loader accepts
+const MAX_SECTIONS: usize = 96;
+/// Bytes of the first section compared to tell a mapped image from a file
+const LAYOUT_PROBE: usize = 0x200;
+/// Data directories the optional header has room for
+const MAX_DIRECTORIES: usize = 16;
+
+/// An exported function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Export {
+    /// The export's name, or `#<ordinal>` for one exported by ordinal only
+    pub name: String,
+    pub ordinal: u32,
+    pub address: u64,
+}
+
+/// A function the image imports
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    /// The module named in the import descriptor, such as `KERNEL32.dll`
+    pub module: String,
+    /// The function's name, or `#<ordinal>` for one imported by ordinal
+    pub name: String,
+    /// Virtual address of the function's IAT slot
+    pub slot: u64,
+    /// The address the slot holds, if it is resident
+    pub address: Option<u64>,
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn put_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn align_up(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A NUL-terminated ASCII name at `va`
+fn read_name(img: &MemoryImage, va: u64) -> Option<String> {
+    // The name may end close to an unmapped page
+    let bytes = img.read_virt(va, MAX_NAME).or_else(|| img.read_virt(va, (0x1000 - (va & 0xFFF)) as usize))?;
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    (end != 0).then(|| String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+/// Whether the image at `base` is PE32+, and its data directory `index` as
+/// an RVA and size
+fn data_directory(img: &MemoryImage, base: u64, index: u64) -> Option<(bool, u64, u64)> {
+    let dos = img.read_virt(base, 0x40)?;
+    if &dos[..2] != b"MZ" {
+        return None;
+    }
+    let nt = base + read_u32(&dos, 0x3C)? as u64;
+    if img.read_virt(nt, 4)? != b"PE\0\0" {
+        return None;
+    }
+    let optional = nt + 0x18;
+    let (pe32_plus, directories) = match img.read_virt(optional, 2)?.as_slice() {
+        [0x0B, 0x01] => (false, PE32_DIRECTORIES),
+        [0x0B, 0x02] => (true, PE32_PLUS_DIRECTORIES),
+        _ => return None,
+    };
+    let entry = img.read_virt(optional + directories + index * 8, 8)?;
+    let (rva, size) = (read_u32(&entry, 0)? as u64, read_u32(&entry, 4)? as u64);
+    (rva != 0 && size != 0).then_some((pe32_plus, rva, size))
+}
+
+/// The module name the export directory of the image at `base` records,
+/// which is the image's file name when it was linked
+pub fn module_name(img: &MemoryImage, base: u64) -> Option<String> {
+    let (_, rva, _) = data_directory(img, base, EXPORT_DIRECTORY)?;
+    let name = img.read_virt_u32(base + rva + EXPORT_MODULE_NAME)?;
+    read_name(img, base + name as u64).filter(|name| name.chars().all(|c| c.is_ascii_graphic() || c == ' '))
+}
+
+/// Functions in the export table of the PE image at `base`; forwarded
+/// exports, which name a function of another module, are left out
+pub fn module_exports(img: &MemoryImage, base: u64) -> Vec<Export> {
+    let Some((_, rva, size)) = data_directory(img, base, EXPORT_DIRECTORY) else { return Vec::new() };
+    let Some(header) = img.read_virt(base + rva, 0x28) else { return Vec::new() };
+    let ordinal_base = read_u32(&header, EXPORT_ORDINAL_BASE as usize).unwrap_or(0);
+    let functions = (read_u32(&header, EXPORT_FUNCTION_COUNT as usize).unwrap_or(0) as usize).min(MAX_EXPORTS);
+    let names = (read_u32(&header, EXPORT_NAME_COUNT as usize).unwrap_or(0) as usize).min(MAX_EXPORTS);
+    let table = |offset: u64, count: usize, width: usize| {
+        read_u32(&header, offset as usize)
+            .and_then(|table| img.read_virt(base + table as u64, count * width))
+            .unwrap_or_default()
+    };
+    let addresses = table(EXPORT_FUNCTIONS, functions, 4);
+    let name_pointers = table(EXPORT_NAMES, names, 4);
+    let name_ordinals = table(EXPORT_NAME_ORDINALS, names, 2);
+
+    let mut named: HashMap<usize, String> = HashMap::new();
+    for index in 0..names {
+        let (Some(pointer), Some(ordinal)) = (read_u32(&name_pointers, index * 4), name_ordinals.get(index * 2..index * 2 + 2)) else { break };
+        let Some(name) = read_name(img, base + pointer as u64) else { continue };
+        named.entry(u16::from_le_bytes([ordinal[0], ordinal[1]]) as usize).or_insert(name);
+    }
+
+    addresses.chunks_exact(4).enumerate().filter_map(|(index, entry)| {
+        let function = u32::from_le_bytes(entry.try_into().unwrap()) as u64;
+        if function == 0 || (rva..rva + size).contains(&function) {
+            return None;
+        }
+        let ordinal = ordinal_base + index as u32;
+        Some(Export {
+            name: named.remove(&index).unwrap_or_else(|| format!("#{}", ordinal)),
+            ordinal,
+            address: base + function,
+        })
+    }).collect()
+}
+
+/// The export of `exports` at `address`
+pub fn export_at(exports: &[Export], address: u64) -> Option<&Export> {
+    exports.iter().find(|export| export.address == address)
+}
+
+/// Functions the PE image at `base` imports, by descriptor and then by
+/// IAT slot. Names come from the import name table, which the loader
+/// leaves alone; bound images without one name their imports by slot
+pub fn module_imports(img: &MemoryImage, base: u64) -> Vec<Import> {
+    let Some((pe32_plus, rva, _)) = data_directory(img, base, IMPORT_DIRECTORY) else { return Vec::new() };
+    let width: u64 = if pe32_plus { 8 } else { 4 };
+    let ordinal_flag = 1u64 << (width * 8 - 1);
+    let read_thunk = |va: u64| if pe32_plus { img.read_virt_u64(va) } else { img.read_virt_u32(va).map(u64::from) };
+    let mut imports = Vec::new();
+    for index in 0..MAX_IMPORT_MODULES as u64 {
+        let Some(descriptor) = img.read_virt(base + rva + index * IMPORT_DESCRIPTOR_SIZE, IMPORT_DESCRIPTOR_SIZE as usize) else { break };
+        if descriptor.iter().all(|&b| b == 0) {
+            break;
+        }
+        let field = |offset: u64| read_u32(&descriptor, offset as usize).unwrap_or(0) as u64;
+        let (names, slots) = (field(IMPORT_NAME_TABLE), field(IMPORT_ADDRESS_TABLE));
+        if slots == 0 {
+            continue;
+        }
+        let module = read_name(img, base + field(IMPORT_MODULE_NAME)).unwrap_or_else(|| format!("#{}", index));
+        for entry in 0..MAX_IMPORTS as u64 {
+            let slot = base + slots + entry * width;
+            let address = read_thunk(slot);
+            let thunk = if names != 0 { read_thunk(base + names + entry * width) } else { address };
+            let name = match thunk {
+                Some(0) => break,
+                Some(thunk) if thunk & ordinal_flag != 0 => format!("#{}", thunk & 0xFFFF),
+                // Hint, then the name
+                Some(thunk) if names != 0 => read_name(img, base + (thunk & 0x7FFF_FFFF) + 2).unwrap_or_else(|| format!("slot_{}", entry)),
+                Some(_) => format!("slot_{}", entry),
+                None => break,
+            };
+            imports.push(Import { module: module.clone(), name, slot, address });
+        }
+    }
+    imports
+}
+
+/// The exports of a set of modules by address, naming the functions an
+/// import address table points to
+#[derive(Debug, Clone, Default)]
+pub struct ExportIndex {
+    functions: HashMap<u64, (String, String)>,
+}
+
+impl ExportIndex {
+    /// Index the exports of the images mapped at each `(name, base)` of `modules`
+    pub fn build<'a>(img: &MemoryImage, modules: impl IntoIterator<Item = (&'a str, u64)>) -> Self {
+        let mut index = ExportIndex::default();
+        for (module, base) in modules {
+            index.insert(module, &module_exports(img, base));
+        }
+        index
+    }
+
+    /// Add the `exports` of `module`; an address already indexed keeps its name
+    pub fn insert(&mut self, module: &str, exports: &[Export]) {
+        for export in exports {
+            self.functions.entry(export.address).or_insert_with(|| (module.to_string(), export.name.clone()));
+        }
+    }
+
+    /// The module and function exported at `address`
+    pub fn resolve(&self, address: u64) -> Option<(&str, &str)> {
+        self.functions.get(&address).map(|(module, function)| (module.as_str(), function.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
+
+/// An entry of the section table
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeSection {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+}
+
+/// How the sections of an image are laid out in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeLayout {
+    /// Sections at their RVAs, as the loader maps them
+    Mapped,
+    /// Sections at their raw offsets, as on disk
+    File,
+    /// Raw offsets equal the RVAs, so both layouts are the same
+    Identical,
+    /// The first section was not read or holds only zeros
+    Unknown,
+}
+
+impl std::fmt::Display for PeLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeLayout::Mapped => write!(f, "mapped"),
+            PeLayout::File => write!(f, "file"),
+            PeLayout::Identical => write!(f, "identical"),
+            PeLayout::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// The COFF and optional headers of a PE image
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeHeaders {
+    pub machine: u16,
+    pub timestamp: u32,
+    pub characteristics: u16,
+    pub pe32_plus: bool,
+    pub entry_point: u32,
+    pub image_base: u64,
+    pub section_alignment: u32,
+    pub file_alignment: u32,
+    pub image_size: u32,
+    pub headers_size: u32,
+    pub subsystem: u16,
+    /// RVA and size of each data directory
+    pub directories: Vec<(u32, u32)>,
+    pub sections: Vec<PeSection>,
+}
+
+impl PeHeaders {
+    /// Parse the headers of the image starting at `image[0]`; the section
+    /// table may be cut short by the end of `image`
+    pub fn parse(image: &[u8]) -> Option<PeHeaders> {
+        let (optional, table, count) = pe_headers(image).ok()?;
+        let nt = optional - 0x18;
+        let pe32_plus = match read_u16(image, optional)? {
+            0x20B => true,
+            0x10B => false,
+            _ => return None,
+        };
+        let image_base = if pe32_plus {
+            u64::from_le_bytes(image.get(optional + 0x18..optional + 0x20)?.try_into().ok()?)
+        } else {
+            read_u32(image, optional + 0x1C)? as u64
+        };
+        let sections = (0..count.min(MAX_SECTIONS))
+            .map_while(|index| {
+                let header = image.get(table + index * SECTION_HEADER_SIZE..table + (index + 1) * SECTION_HEADER_SIZE)?;
+                let name = &header[..8];
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(8)];
+                Some(PeSection {
+                    name: String::from_utf8_lossy(name).to_string(),
+                    virtual_size: read_u32(header, 8)?,
+                    virtual_address: read_u32(header, 12)?,
+                    raw_size: read_u32(header, 16)?,
+                    raw_offset: read_u32(header, 20)?,
+                })
+            })
+            .collect();
+        let (count_at, directories) = if pe32_plus { (0x6C, PE32_PLUS_DIRECTORIES) } else { (0x5C, PE32_DIRECTORIES) };
+        let directories = (0..(read_u32(image, optional + count_at)? as usize).min(MAX_DIRECTORIES))
+            .map_while(|index| {
+                let entry = optional + directories as usize + index * 8;
+                Some((read_u32(image, entry)?, read_u32(image, entry + 4)?))
+            })
+            .collect();
+        Some(PeHeaders {
+            machine: read_u16(image, nt + 4)?,
+            timestamp: read_u32(image, nt + 8)?,
+            characteristics: read_u16(image, nt + 0x16)?,
+            pe32_plus,
+            entry_point: read_u32(image, optional + 0x10)?,
+            image_base,
+            section_alignment: read_u32(image, optional + 0x20)?,
+            file_alignment: read_u32(image, optional + 0x24)?,
+            image_size: read_u32(image, optional + 0x38)?,
+            headers_size: read_u32(image, optional + 0x3C)?,
+            subsystem: read_u16(image, optional + 0x44)?,
+            directories,
+            sections,
+        })
+    }
+
+    pub fn is_dll(&self) -> bool {
+        self.characteristics & 0x2000 != 0
+    }
+
+    /// Offset of `rva` in an image that is `mapped`, or laid out as a file
+    /// the way its section table says
+    pub fn offset(&self, rva: u32, mapped: bool) -> Option<usize> {
+        if mapped || rva < self.headers_size {
+            return Some(rva as usize);
+        }
+        self.sections.iter()
+            .find(|s| rva >= s.virtual_address && rva - s.virtual_address < s.raw_size)
+            .map(|s| (s.raw_offset + (rva - s.virtual_address)) as usize)
+    }
+
+    /// Whether `image`, starting with these headers, is mapped or a file:
+    /// the first section with raw data away from its RVA is looked for at
+    /// both places
+    pub fn layout(&self, image: &[u8]) -> PeLayout {
+        let Some(section) = self.sections.iter().filter(|s| s.raw_size != 0).min_by_key(|s| s.virtual_address) else {
+            return PeLayout::Unknown;
+        };
+        if self.sections.iter().all(|s| s.raw_size == 0 || s.raw_offset == s.virtual_address) {
+            return PeLayout::Identical;
+        }
+        let len = (section.raw_size.min(section.virtual_size.max(1)) as usize).min(LAYOUT_PROBE);
+        let zero = |offset: u32| image.get(offset as usize..offset as usize + len).map(|bytes| bytes.iter().all(|&b| b == 0));
+        match (zero(section.raw_offset), zero(section.virtual_address)) {
+            // Raw offsets before the first RVA fall in the zeroed rest of
+            // the header page once mapped
+            (Some(true), Some(false)) if section.raw_offset < section.virtual_address => PeLayout::Mapped,
+            (Some(false), _) => PeLayout::File,
+            _ => PeLayout::Unknown,
+        }
+    }
+}
+
+/// Locate the NT headers of a PE image: returns the optional header offset,
+/// the section table offset and the section count
+pub(crate) fn pe_headers(image: &[u8]) -> Result<(usize, usize, usize)> {
+    if !image.starts_with(b"MZ") {
+        bail!("no MZ signature");
+    }
+    let nt = read_u32(image, 0x3C).context("truncated DOS header")? as usize;
+    if image.get(nt..nt + 4) != Some(b"PE\0\0") {
+        bail!("no PE signature at offset 0x{:X}", nt);
+    }
+    let optional = nt + 0x18;
+    let sections = optional + read_u16(image, nt + 0x14).context("truncated file header")? as usize;
+    let count = read_u16(image, nt + 6).context("truncated file header")? as usize;
+    Ok((optional, sections, count))
+}
+
+/// `SizeOfImage` from the headers of a mapped PE
+pub fn image_size(header: &[u8]) -> Result<u64> {
+    let (optional, _, _) = pe_headers(header)?;
+    Ok(read_u32(header, optional + 0x38).context("truncated optional header")? as u64)
+}
+
+/// A NUL-terminated ASCII name at offset `at` of a mapped image
+fn c_string(image: &[u8], at: usize) -> Option<String> {
+    let bytes = image.get(at..)?;
+    let end = bytes.iter().take(MAX_NAME).position(|&b| b == 0)?;
+    (end != 0).then(|| String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+/// What an IAT slot imports: a function by name or by ordinal
+#[derive(Debug, Clone, PartialEq)]
+enum ImportName {
+    Name(String),
+    Ordinal(u64),
+}
+
+/// An IAT slot of a mapped image, by RVA, and the function it imports
+struct Thunk {
+    slot: usize,
+    module: String,
+    name: ImportName,
+    /// The slot's import name table entry, when that is intact
+    original: Option<u64>,
+}
+
+/// Point the IAT slots of the mapped `image` back at the names of their
+/// functions, as in the file. Slots whose import name table entry is gone
+/// are named by the export of `exports` at the address they hold, and the
+/// import table is then written anew into an added section
+fn rebuild_imports(image: &mut Vec<u8>, optional: usize, sections: usize, exports: &ExportIndex) -> Result<()> {
+    let pe32_plus = read_u16(image, optional) == Some(0x20B);
+    let directories = optional + if pe32_plus { PE32_PLUS_DIRECTORIES } else { PE32_DIRECTORIES } as usize;
+    let Some(directory) = read_u32(image, directories + IMPORT_DIRECTORY as usize * 8).filter(|&rva| rva != 0) else { return Ok(()) };
+    let width = if pe32_plus { 8 } else { 4 };
+    let ordinal_flag = 1u64 << (width * 8 - 1);
+    let read_thunk = |image: &[u8], at: usize| if pe32_plus { read_u64(image, at) } else { read_u32(image, at).map(u64::from) };
+
+    let mut thunks = Vec::new();
+    for index in 0..MAX_IMPORT_MODULES {
+        let at = directory as usize + index * IMPORT_DESCRIPTOR_SIZE as usize;
+        let Some(descriptor) = image.get(at..at + IMPORT_DESCRIPTOR_SIZE as usize) else { break };
+        if descriptor.iter().all(|&b| b == 0) {
+            break;
+        }
+        let field = |offset: u64| read_u32(descriptor, offset as usize).unwrap_or(0) as usize;
+        let (names, slots) = (field(IMPORT_NAME_TABLE), field(IMPORT_ADDRESS_TABLE));
+        let module = c_string(image, field(IMPORT_MODULE_NAME));
+        for entry in (0..MAX_IMPORTS).take_while(|_| slots != 0) {
+            let slot = slots + entry * width;
+            let Some(address) = read_thunk(image, slot).filter(|&address| address != 0) else { break };
+            let original = read_thunk(image, names + entry * width).filter(|_| names != 0);
+            let named = match original {
+                Some(thunk) if thunk & ordinal_flag != 0 => Some(ImportName::Ordinal(thunk & 0xFFFF)),
+                // Hint, then the name
+                Some(thunk) if thunk != 0 => c_string(image, thunk as usize + 2).map(ImportName::Name),
+                _ => None,
+            };
+            let thunk = match (named, &module) {
+                (Some(name), Some(module)) => Thunk { slot, module: module.clone(), name, original },
+                _ => match exports.resolve(address) {
+                    Some((module, function)) => Thunk { slot, module: module.to_string(), name: ImportName::Name(function.to_string()), original: None },
+                    None => Thunk {
+                        slot,
+                        module: module.clone().unwrap_or_else(|| "unresolved".to_string()),
+                        name: ImportName::Name(format!("unresolved_{:x}", address)),
+                        original: None,
+                    },
+                },
+            };
+            thunks.push(thunk);
+        }
+    }
+
+    let write_thunk = |image: &mut [u8], at: usize, value: u64| image[at..at + width].copy_from_slice(&value.to_le_bytes()[..width]);
+    if thunks.iter().all(|thunk| thunk.original.is_some()) {
+        for thunk in &thunks {
+            write_thunk(image, thunk.slot, thunk.original.unwrap());
+        }
+        return Ok(());
+    }
+
+    // Runs of adjacent slots importing from one module become one descriptor
+    let mut runs: Vec<Vec<&Thunk>> = Vec::new();
+    for thunk in &thunks {
+        match runs.last_mut() {
+            Some(run) if run[run.len() - 1].slot + width == thunk.slot && run[0].module.eq_ignore_ascii_case(&thunk.module) => run.push(thunk),
+            _ => runs.push(vec![thunk]),
+        }
+    }
+    let count = read_u16(image, optional - 0x18 + 6).context("truncated file header")? as usize;
+    let header = sections + count * SECTION_HEADER_SIZE;
+    let headers_end = read_u32(image, optional + 0x3C).context("truncated optional header")? as usize;
+    if header + SECTION_HEADER_SIZE > headers_end {
+        bail!("no room in the headers for an import section");
+    }
+    let section_alignment = (read_u32(image, optional + 0x20).context("truncated optional header")? as usize).max(1);
+    let rva = align_up(image.len(), section_alignment);
+
+    // Descriptors, then each run's name table, then the names
+    let mut section = vec![0u8; (runs.len() + 1) * IMPORT_DESCRIPTOR_SIZE as usize];
+    let mut tables = Vec::new();
+    for run in &runs {
+        tables.push(section.len());
+        section.resize(section.len() + (run.len() + 1) * width, 0);
+    }
+    let mut slots = Vec::new();
+    for (index, (run, table)) in runs.iter().zip(tables).enumerate() {
+        for (entry, thunk) in run.iter().enumerate() {
+            let value = match &thunk.name {
+                ImportName::Ordinal(ordinal) => ordinal_flag | ordinal,
+                ImportName::Name(name) => {
+                    let at = section.len();
+                    section.extend_from_slice(&[0, 0]);
+                    section.extend_from_slice(name.as_bytes());
+                    section.resize(align_up(section.len() + 1, 2), 0);
+                    (rva + at) as u64
+                }
+            };
+            write_thunk(&mut section, table + entry * width, value);
+            slots.push((thunk.slot, value));
+        }
+        let module = section.len();
+        section.extend_from_slice(run[0].module.as_bytes());
+        section.push(0);
+        let descriptor = index * IMPORT_DESCRIPTOR_SIZE as usize;
+        put_u32(&mut section, descriptor + IMPORT_NAME_TABLE as usize, (rva + table) as u32);
+        put_u32(&mut section, descriptor + IMPORT_MODULE_NAME as usize, (rva + module) as u32);
+        put_u32(&mut section, descriptor + IMPORT_ADDRESS_TABLE as usize, run[0].slot as u32);
+    }
+    for (slot, value) in slots {
+        write_thunk(image, slot, value);
+    }
+
+    image[header..header + 8].copy_from_slice(IMPORT_SECTION_NAME);
+    put_u32(image, header + 8, section.len() as u32);
+    put_u32(image, header + 12, rva as u32);
+    put_u32(image, header + 36, IMPORT_SECTION_FLAGS);
+    image[optional - 0x18 + 6..optional - 0x18 + 8].copy_from_slice(&(count as u16 + 1).to_le_bytes());
+    put_u32(image, directories + IMPORT_DIRECTORY as usize * 8, rva as u32);
+    put_u32(image, directories + IMPORT_DIRECTORY as usize * 8 + 4, ((runs.len() + 1) * IMPORT_DESCRIPTOR_SIZE as usize) as u32);
+    let len = section.len();
+    image.resize(rva, 0);
+    image.extend(section);
+    let size = rva + align_up(len, section_alignment);
+    image.resize(size, 0);
+    put_u32(image, optional + 0x38, size as u32);
+    Ok(())
+}
+
+/// Turn a PE image as mapped at `base` into a file that loads like the
+/// original: `ImageBase` is set to `base`, so pointers the loader relocated
+/// resolve without a rebase, the import table is rebuilt from the live IAT
+/// (see `rebuild_imports`), and each section's data moves from its RVA back
+/// to a file offset
+pub fn rebuild_pe(image: &[u8], base: u64, exports: &ExportIndex) -> Result<Vec<u8>> {
+    let mut image = image.to_vec();
+    let (optional, sections, _) = pe_headers(&image)?;
+    let directories = match read_u16(&image, optional) {
+        Some(0x20B) => {
+            image[optional + 0x18..optional + 0x20].copy_from_slice(&base.to_le_bytes());
+            optional + PE32_PLUS_DIRECTORIES as usize
+        }
+        Some(0x10B) => {
+            put_u32(&mut image, optional + 0x1C, base as u32);
+            optional + PE32_DIRECTORIES as usize
+        }
+        magic => bail!("unknown optional header magic {:X?}", magic),
+    };
+    // An import table that cannot grow keeps the live addresses of the slots it cannot name
+    let _ = rebuild_imports(&mut image, optional, sections, exports);
+    let (_, _, count) = pe_headers(&image)?;
+
+    // Sections follow the headers in table order, each padded to the file alignment
+    let file_alignment = read_u32(&image, optional + 0x24).map(|a| a as usize)
+        .filter(|a| a.is_power_of_two() && (DEFAULT_FILE_ALIGNMENT..=0x10000).contains(a))
+        .unwrap_or(DEFAULT_FILE_ALIGNMENT);
+    let headers_size = (read_u32(&image, optional + 0x3C).context("truncated optional header")? as usize).min(image.len());
+    if sections + count * SECTION_HEADER_SIZE > headers_size {
+        bail!("section table runs past the headers");
+    }
+    let mut file = image[..headers_size].to_vec();
+    file.resize(align_up(headers_size, file_alignment), 0);
+    put_u32(&mut file, optional + 0x24, file_alignment as u32);
+    for directory in [SECURITY_DIRECTORY, BOUND_IMPORT_DIRECTORY] {
+        let entry = directories + directory * 8;
+        if entry + 8 <= headers_size {
+            file[entry..entry + 8].fill(0);
+        }
+    }
+    for index in 0..count {
+        let header = sections + index * SECTION_HEADER_SIZE;
+        let virtual_size = read_u32(&image, header + 8).unwrap() as usize;
+        let rva = read_u32(&image, header + 12).unwrap() as usize;
+        let size = if virtual_size == 0 { read_u32(&image, header + 16).unwrap() as usize } else { virtual_size };
+        let data = image.get(rva..).map_or(&[][..], |rest| &rest[..size.min(rest.len())]);
+        let (pointer, raw_size) = if data.is_empty() {
+            (0, 0)
+        } else {
+            let pointer = file.len();
+            file.extend_from_slice(data);
+            file.resize(align_up(file.len(), file_alignment), 0);
+            (pointer, file.len() - pointer)
+        };
+        put_u32(&mut file, header + 16, raw_size as u32);
+        put_u32(&mut file, header + 20, pointer as u32);
+    }
+    Ok(file)
+}
+
+/// The module and function of each import the name tables of `image`
+/// list; functions imported by ordinal are named `#<ordinal>`
+pub fn image_imports(image: &[u8], headers: &PeHeaders, mapped: bool) -> Vec<(String, String)> {
+    let Some(&(directory, _)) = headers.directories.get(IMPORT_DIRECTORY as usize).filter(|(rva, _)| *rva != 0) else { return Vec::new() };
+    let width = if headers.pe32_plus { 8 } else { 4 };
+    let ordinal_flag = 1u64 << (width * 8 - 1);
+    let at = |rva: u32| headers.offset(rva, mapped);
+    let read_thunk = |offset: usize| if headers.pe32_plus { read_u64(image, offset) } else { read_u32(image, offset).map(u64::from) };
+    let mut imports = Vec::new();
+    for index in 0..MAX_IMPORT_MODULES as u32 {
+        let descriptor = at(directory + index * IMPORT_DESCRIPTOR_SIZE as u32)
+            .and_then(|offset| image.get(offset..offset + IMPORT_DESCRIPTOR_SIZE as usize));
+        let Some(descriptor) = descriptor.filter(|d| d.iter().any(|&b| b != 0)) else { break };
+        let field = |offset: u64| read_u32(descriptor, offset as usize).unwrap_or(0);
+        // The IAT of a file names its functions too; a mapped one holds addresses
+        let names = match field(IMPORT_NAME_TABLE) {
+            0 if !mapped => field(IMPORT_ADDRESS_TABLE),
+            names => names,
+        };
+        let Some(module) = at(field(IMPORT_MODULE_NAME)).and_then(|offset| c_string(image, offset)) else { continue };
+        for entry in (0..MAX_IMPORTS as u32).take_while(|_| names != 0) {
+            let Some(thunk) = at(names + entry * width as u32).and_then(read_thunk).filter(|&thunk| thunk != 0) else { break };
+            let name = if thunk & ordinal_flag != 0 {
+                format!("#{}", thunk & 0xFFFF)
+            } else {
+                // Hint, then the name
+                let Some(name) = at(thunk as u32 + 2).and_then(|offset| c_string(image, offset)) else { break };
+                name
+            };
+            imports.push((module.clone(), name));
+        }
+    }
+    imports
+}
+
+/// The imphash of `imports` as pefile computes it: the MD5 of the
+/// comma-separated `module.function` of each import in lower case, the
+/// module without a `.dll`, `.ocx` or `.sys` extension. Functions imported
+/// by ordinal are `ord<n>`; pefile's names for ordinals of a few system
+/// DLLs are not applied
+pub fn imphash(imports: &[(String, String)]) -> Option<String> {
+    if imports.is_empty() {
+        return None;
+    }
+    let list: Vec<String> = imports.iter().map(|(module, function)| {
+        let module = module.to_lowercase();
+        let module = match module.rsplit_once('.') {
+            Some((stem, "dll" | "ocx" | "sys")) => stem.to_string(),
+            _ => module,
+        };
+        let function = match function.strip_prefix('#') {
+            Some(ordinal) => format!("ord{}", ordinal),
+            None => function.to_lowercase(),
+        };
+        format!("{}.{}", module, function)
+    }).collect();
+    Some(hex(&Md5::digest(list.join(",").as_bytes())))
+}
+
+/// The MD5 of the decoded Rich header, the linker's record of the tools
+/// that built the image, between the DOS stub and the PE header
+pub fn rich_header_hash(image: &[u8]) -> Option<String> {
+    let nt = read_u32(image, 0x3C)? as usize;
+    let stub = image.get(0x80..nt)?;
+    let rich = (0..stub.len().saturating_sub(7)).step_by(4).find(|&i| &stub[i..i + 4] == b"Rich")?;
+    let key = read_u32(stub, rich + 4)?;
+    let decoded: Vec<u8> = stub[..rich].chunks_exact(4)
+        .flat_map(|dword| (u32::from_le_bytes(dword.try_into().unwrap()) ^ key).to_le_bytes())
+        .collect();
+    let start = (0..decoded.len()).step_by(4).find(|&i| &decoded[i..i + 4] == b"DanS")?;
+    Some(hex(&Md5::digest(&decoded[start..])))
+}
+
+/// The SHA-256 of a section's data
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SectionHash {
+    pub name: String,
+    pub sha256: String,
+}
+
+/// SHA-256 of the data of each section of an image that is `mapped` or a
+/// file; sections running past the end of `image` are left out
+pub fn section_hashes(image: &[u8], headers: &PeHeaders, mapped: bool) -> Vec<SectionHash> {
+    headers.sections.iter().filter_map(|section| {
+        let (start, len) = if mapped {
+            (section.virtual_address, if section.virtual_size == 0 { section.raw_size } else { section.virtual_size })
+        } else {
+            (section.raw_offset, section.raw_size)
+        };
+        let data = image.get(start as usize..(start as usize).checked_add(len as usize)?).filter(|_| len != 0)?;
+        Some(SectionHash { name: section.name.clone(), sha256: hex(&Sha256::digest(data)) })
+    }).collect()
+}
+
+/// Hashes that identify a PE image in threat intelligence databases
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PeHashes {
+    pub imphash: Option<String>,
+    pub rich_header_hash: Option<String>,
+    pub sections: Vec<SectionHash>,
+}
+
+impl PeHashes {
+    /// Hash the image starting at `image[0]`, `mapped` or laid out as a file
+    pub fn of_image(image: &[u8], mapped: bool) -> Option<PeHashes> {
+        let headers = PeHeaders::parse(image)?;
+        Some(PeHashes {
+            imphash: imphash(&image_imports(image, &headers, mapped)),
+            rich_header_hash: rich_header_hash(image),
+            sections: section_hashes(image, &headers, mapped),
+        })
+    }
+}
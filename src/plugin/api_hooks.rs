@@ -0,0 +1,245 @@
+//! Inline API hooks in user-mode modules
+//!
+//! An inline hook overwrites the first bytes of a function with a jump to
+//! the hooking code, which usually sits in injected memory or another DLL.
+//! The scanner walks the export table of each module on a process's loader
+//! lists, disassembles the first instructions of every exported function
+//! and flags a trampoline (a direct jump or call, a jump through a pointer,
+//! `push`/`ret` or `mov reg`/`jmp reg`) whose destination is outside the
+//! module. Import thunks that jump through a pointer in their own module
+//! are normal and not reported. Import address table slots are checked
+//! too: the loader fills each with a function of a loaded module, so one
+//! pointing outside every module was patched. Hook targets in a module are
+//! named by that module's export at the address, when there is one.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::disasm::{disassemble, Instruction};
+use crate::dlllist::{process_dlls, LoadedDll};
+use crate::kdbg::OsContext;
+use crate::paging::MemoryImage;
+use crate::pe::{export_at, module_exports, module_imports, Export, Import};
+use crate::processes::{Process, ProcessFinder, WindowsProcessFinder};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Bytes read and instructions decoded at the start of each function
+const PROLOGUE_BYTES: usize = 32;
+const PROLOGUE_INSTRUCTIONS: usize = 3;
+/// A pointer this close after the jump reading it is part of the patch
+const INLINE_POINTER_SLACK: u64 = 16;
+
+/// A trampoline at the start of a function
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineHook {
+    /// `jmp`, `call`, `jmp [mem]`, `call [mem]`, `push/ret` or `mov/jmp`
+    pub kind: String,
+    pub target: u64,
+    pub instructions: Vec<Instruction>,
+}
+
+/// The trampoline at the start of a function of the module spanning
+/// `module`, if its first instructions transfer control out of it;
+/// `read_pointer` reads the slot of a jump through memory
+pub fn find_trampoline(instructions: &[Instruction], module: &Range<u64>, read_pointer: impl Fn(u64) -> Option<u64>) -> Option<InlineHook> {
+    let hook = |kind: &str, target: u64| (!module.contains(&target)).then(|| InlineHook {
+        kind: kind.to_string(),
+        target,
+        instructions: instructions.to_vec(),
+    });
+    for (index, instruction) in instructions.iter().enumerate() {
+        let next = instructions.get(index + 1);
+        match instruction.mnemonic.as_str() {
+            "jmp" | "call" if instruction.target.is_some() => return hook(&instruction.mnemonic, instruction.target?),
+            "jmp" | "call" if instruction.memory.is_some() => {
+                // An import thunk jumps through its own module's IAT; a hook's
+                // pointer is in the patch or elsewhere
+                let slot = instruction.memory?;
+                let end = instruction.address + instruction.bytes.len() as u64;
+                if module.contains(&slot) && !(end..end + INLINE_POINTER_SLACK).contains(&slot) {
+                    return None;
+                }
+                return hook(&format!("{} [mem]", instruction.mnemonic), read_pointer(slot)?);
+            }
+            "push" if instruction.immediate.is_some() && next.is_some_and(|n| n.mnemonic == "ret") => {
+                return hook("push/ret", instruction.immediate?);
+            }
+            // A 64-bit immediate load decodes as movabs
+            "mov" | "movabs" if instruction.immediate.is_some() => {
+                let register = instruction.operands.split(',').next().unwrap_or_default();
+                if let Some(next) = next.filter(|n| (n.mnemonic == "jmp" || n.mnemonic == "call") && n.operands == register) {
+                    return hook(&format!("mov/{}", next.mnemonic), instruction.immediate?);
+                }
+            }
+            // Any other transfer of control ends the prologue
+            "jmp" | "call" | "ret" | "int3" | "db" => return None,
+            mnemonic if mnemonic.starts_with('j') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Hooked exports of the modules on the loader lists of `process`
+pub fn process_hooks(img: &MemoryImage, finder: &WindowsProcessFinder, process: &Process) -> Vec<(LoadedDll, Export, InlineHook)> {
+    let Some(space) = process.address_space(img) else { return Vec::new() };
+    let dlls = process_dlls(img, finder, process).dlls;
+    let mut hooks = Vec::new();
+    for dll in &dlls {
+        let module = dll.base..dll.base + dll.size;
+        for export in module_exports(&space, dll.base) {
+            let Some(code) = space.read_virt(export.address, PROLOGUE_BYTES) else { continue };
+            let instructions = disassemble(&code, export.address, PROLOGUE_INSTRUCTIONS);
+            if let Some(hook) = find_trampoline(&instructions, &module, |slot| space.read_virt_u64(slot)) {
+                hooks.push((dll.clone(), export, hook));
+            }
+        }
+    }
+    hooks
+}
+
+/// Imports of the modules on the loader lists of `process` whose IAT slot
+/// points outside every loaded module
+pub fn process_iat_hooks(img: &MemoryImage, finder: &WindowsProcessFinder, process: &Process) -> Vec<(LoadedDll, Import)> {
+    let Some(space) = process.address_space(img) else { return Vec::new() };
+    let dlls = process_dlls(img, finder, process).dlls;
+    let in_module = |address: u64| dlls.iter().any(|m| (m.base..m.base + m.size).contains(&address));
+    let mut hooks = Vec::new();
+    for dll in &dlls {
+        for import in module_imports(&space, dll.base) {
+            // A slot still holding its name table RVA was never bound
+            if import.address.is_some_and(|address| address >= dll.size && !in_module(address)) {
+                hooks.push((dll.clone(), import));
+            }
+        }
+    }
+    hooks
+}
+
+/// `address` as `module!function` when it is an export of the module, and
+/// as `module+offset` otherwise
+fn describe_target(address: u64, module: &LoadedDll, exports: &[Export]) -> String {
+    match export_at(exports, address) {
+        Some(export) => format!("{}!{}", module.name, export.name),
+        None => format!("{}+{:#x}", module.name, address - module.base),
+    }
+}
+
+/// A plugin that finds inline hooks in the exported functions of user-mode modules
+#[derive(Default)]
+pub struct ApiHookScanner;
+
+impl ApiHookScanner {
+    fn report(&self, addr: u64, process: &Process, dll: &LoadedDll, export: &Export, hook: &InlineHook, target_module: Option<(&LoadedDll, String)>) -> Finding {
+        let target = match &target_module {
+            Some((_, name)) => format!("{:#x} ({})", hook.target, name),
+            None => format!("{:#x} (no module)", hook.target),
+        };
+        let disassembly: Vec<String> = hook.instructions.iter().map(|i| i.to_string()).collect();
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "hook".to_string());
+        details.insert("rule".to_string(), "inline_hook".to_string());
+        details.insert("pid".to_string(), process.pid.to_string());
+        details.insert("process".to_string(), process.name.clone());
+        details.insert("module".to_string(), dll.name.clone());
+        details.insert("function".to_string(), export.name.clone());
+        details.insert("function_address".to_string(), format!("{:#x}", export.address));
+        details.insert("hook_type".to_string(), hook.kind.clone());
+        details.insert("target".to_string(), format!("{:#x}", hook.target));
+        details.insert("target_module".to_string(), target_module.as_ref().map_or_else(|| "-".to_string(), |(m, _)| m.name.clone()));
+        details.insert("target_function".to_string(), target_module.as_ref().map_or_else(|| "-".to_string(), |(_, name)| name.clone()));
+        details.insert("disassembly".to_string(), disassembly.join("\n"));
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{}!{} in {} (PID {}) hooked with {} to {}", dll.name, export.name, process.name, process.pid, hook.kind, target),
+            // Code outside every module is injected; another module may be a shim
+            confidence: if target_module.is_some() { 70 } else { 90 },
+            details,
+        }
+    }
+
+    fn report_iat(&self, addr: u64, process: &Process, dll: &LoadedDll, import: &Import) -> Finding {
+        let target = import.address.unwrap_or_default();
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "hook".to_string());
+        details.insert("rule".to_string(), "iat_hook".to_string());
+        details.insert("pid".to_string(), process.pid.to_string());
+        details.insert("process".to_string(), process.name.clone());
+        details.insert("module".to_string(), dll.name.clone());
+        details.insert("function".to_string(), format!("{}!{}", import.module, import.name));
+        details.insert("iat_slot".to_string(), format!("{:#x}", import.slot));
+        details.insert("hook_type".to_string(), "iat".to_string());
+        details.insert("target".to_string(), format!("{:#x}", target));
+        details.insert("target_module".to_string(), "-".to_string());
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} in {} (PID {}) imports {}!{} through an IAT slot patched to {:#x} (no module)",
+                dll.name, process.name, process.pid, import.module, import.name, target),
+            confidence: 80,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for ApiHookScanner {
+    fn name(&self) -> &'static str {
+        "apihooks"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Finds inline hooks (exported functions of user-mode modules that start with a jump out of the module) and IAT slots patched to point outside every module"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Locating processes");
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else {
+            progress.finish_with_message("No kernel found; apihooks needs the process list");
+            return findings;
+        };
+        let finder = WindowsProcessFinder::new().with_os_context(os);
+        let processes = finder.find_processes(img, &ProgressBar::hidden()).unwrap_or_default();
+
+        progress.set_message("Checking exported functions");
+        progress.set_length(processes.len() as u64);
+        progress.set_position(0);
+        for process in &processes {
+            progress.inc(1);
+            let hooks = process_hooks(img, &finder, process);
+            let iat_hooks = process_iat_hooks(img, &finder, process);
+            if hooks.is_empty() && iat_hooks.is_empty() {
+                continue;
+            }
+            let Some(space) = process.address_space(img) else { continue };
+            let dlls = process_dlls(img, &finder, process).dlls;
+            // Exports of the modules hooks jump to, read once per module
+            let mut exports: HashMap<u64, Vec<Export>> = HashMap::new();
+            for (dll, export, hook) in &hooks {
+                let target_module = dlls.iter().find(|m| (m.base..m.base + m.size).contains(&hook.target)).map(|module| {
+                    let exports = exports.entry(module.base).or_insert_with(|| module_exports(&space, module.base));
+                    (module, describe_target(hook.target, module, exports))
+                });
+                let addr = space.virt_to_phys(export.address).unwrap_or(export.address);
+                findings.push(self.report(addr, process, dll, export, hook, target_module));
+            }
+            for (dll, import) in &iat_hooks {
+                let addr = space.virt_to_phys(import.slot).unwrap_or(import.slot);
+                findings.push(self.report_iat(addr, process, dll, import));
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} hooked functions", findings.len()));
+        findings
+    }
+}
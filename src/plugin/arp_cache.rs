@@ -0,0 +1,139 @@
+//! ARP and neighbor cache scan
+//!
+//! tcpip.sys keeps an entry for every neighbor it resolved on the local
+//! link, pairing the IP address with the MAC address it answered from.
+//! The table shows which hosts on the local network the machine talked to
+//! around capture time. The scanner finds the entries by pool tag and
+//! decodes each one; offsets are for Windows 10 x64. Linux neighbor tables
+//! are walked from `arp_tbl` and `nd_tbl` by `rmf netscan --os linux --arp`,
+//! which needs a profile.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::paging::MemoryImage;
+use crate::processes::scan_pool_tags;
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+/// Pool tag of tcpip.sys neighbor entries
+const NEIGHBOR_POOL_TAGS: [&[u8]; 1] = [b"IpNe"];
+const POOL_HEADER_SIZE: usize = 0x10;
+
+/// NEIGHBOR offsets
+const NEIGHBOR_SIZE: usize = 0x58;
+const NEIGHBOR_STATE: usize = 0x28;
+const NEIGHBOR_DL_ADDRESS_LENGTH: usize = 0x2C;
+const NEIGHBOR_DL_ADDRESS: usize = 0x30;
+const NEIGHBOR_FAMILY: usize = 0x40;
+const NEIGHBOR_ADDRESS: usize = 0x48;
+
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 0x17;
+const ETHERNET_ADDRESS_LENGTH: u16 = 6;
+
+/// NL_NEIGHBOR_STATE names, by value
+const NEIGHBOR_STATES: [&str; 7] = ["UNREACHABLE", "INCOMPLETE", "PROBE", "DELAY", "STALE", "REACHABLE", "PERMANENT"];
+/// States in which the MAC address has been learned
+const NEIGHBOR_RESOLVED: u32 = 2;
+
+/// An IP address and the link-layer address it resolved to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbor {
+    pub ip: IpAddr,
+    pub mac: [u8; 6],
+    pub state: &'static str,
+    /// Network interface, when known
+    pub interface: Option<String>,
+}
+
+impl Neighbor {
+    pub fn mac_string(&self) -> String {
+        format_mac(&self.mac)
+    }
+}
+
+/// `aa:bb:cc:dd:ee:ff`
+pub fn format_mac(mac: &[u8]) -> String {
+    mac.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+/// Validate and decode a NEIGHBOR body
+pub fn parse_neighbor(body: &[u8]) -> Option<Neighbor> {
+    if body.len() < NEIGHBOR_SIZE {
+        return None;
+    }
+    let u16_at = |off: usize| u16::from_le_bytes([body[off], body[off + 1]]);
+    let state = u32::from_le_bytes(body[NEIGHBOR_STATE..NEIGHBOR_STATE + 4].try_into().unwrap());
+    let name = NEIGHBOR_STATES.get(state as usize)?;
+    if u16_at(NEIGHBOR_DL_ADDRESS_LENGTH) != ETHERNET_ADDRESS_LENGTH {
+        return None;
+    }
+    let mac: [u8; 6] = body[NEIGHBOR_DL_ADDRESS..NEIGHBOR_DL_ADDRESS + 6].try_into().unwrap();
+    if state >= NEIGHBOR_RESOLVED && mac == [0; 6] {
+        return None;
+    }
+    let ip = match u16_at(NEIGHBOR_FAMILY) {
+        AF_INET => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(&body[NEIGHBOR_ADDRESS..NEIGHBOR_ADDRESS + 4]).unwrap())),
+        AF_INET6 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&body[NEIGHBOR_ADDRESS..NEIGHBOR_ADDRESS + 16]).unwrap())),
+        _ => return None,
+    };
+    (!ip.is_unspecified()).then_some(Neighbor { ip, mac, state: name, interface: None })
+}
+
+/// A plugin that recovers the ARP and IPv6 neighbor cache of tcpip.sys
+#[derive(Default)]
+pub struct ArpCacheScanner;
+
+impl ArpCacheScanner {
+    fn report(&self, addr: u64, neighbor: &Neighbor) -> Finding {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "neighbor".to_string());
+        details.insert("rule".to_string(), "neighbor_entry".to_string());
+        details.insert("ip".to_string(), neighbor.ip.to_string());
+        details.insert("mac".to_string(), neighbor.mac_string());
+        details.insert("state".to_string(), neighbor.state.to_string());
+        if let Some(interface) = &neighbor.interface {
+            details.insert("interface".to_string(), interface.clone());
+        }
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} is at {} ({})", neighbor.ip, neighbor.mac_string(), neighbor.state),
+            confidence: 50,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for ArpCacheScanner {
+    fn name(&self) -> &'static str {
+        "arpcache"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn description(&self) -> &'static str {
+        "Recovers the IP to MAC pairs of the tcpip.sys ARP and IPv6 neighbor cache"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        progress.set_message("Scanning pool memory for neighbor entries");
+        let mut neighbors: Vec<Neighbor> = Vec::new();
+        let mut findings = Vec::new();
+        for (header, _) in scan_pool_tags(img, progress, &NEIGHBOR_POOL_TAGS) {
+            let body = header as usize + POOL_HEADER_SIZE;
+            let Some(data) = img.get_bytes(body, NEIGHBOR_SIZE) else { continue };
+            // Freed entries for the same neighbor are reported once
+            if let Some(neighbor) = parse_neighbor(data).filter(|n| !neighbors.contains(n)) {
+                findings.push(self.report(body as u64, &neighbor));
+                neighbors.push(neighbor);
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} neighbor entries", findings.len()));
+        findings
+    }
+}
@@ -0,0 +1,276 @@
+//! BitLocker full volume encryption keys
+//!
+//! While a BitLocker volume is unlocked its full volume encryption key
+//! (FVEK) stays in kernel pool memory as an expanded AES key: in the
+//! fvevol.sys crypto context (`FVEc`) on Windows 7, and in CNG key objects
+//! (`Cngb`, or `None` from Windows 10) later. The scanner searches those
+//! allocations for AES-128 and AES-256 key schedules that expand from the
+//! key in front of them, so only real keys match. Two schedules of one size
+//! in an allocation are the FVEK and tweak key of AES-CBC with the Elephant
+//! diffuser in `FVEc`, and the two halves of an XTS-AES key in CNG pools; a
+//! lone key in a CNG pool may belong to any CNG user and is reported with
+//! low confidence.
+//!
+//! Keys are redacted unless the plugin is run with `--reveal`. Revealed
+//! findings carry the 66-byte FVEK file dislocker takes with `--fvek`, as
+//! hex for `xxd -r -p`, and the `bdemount -k` argument for libbde.
+
+use aes::hazmat::{cipher_round, inv_mix_columns};
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::paging::MemoryImage;
+use crate::processes::scan_pool_tags;
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+/// Pool tags of the fvevol.sys crypto context and of CNG key objects
+const FVE_POOL_TAG: &[u8] = b"FVEc";
+const KEY_POOL_TAGS: [&[u8]; 3] = [FVE_POOL_TAG, b"Cngb", b"None"];
+const POOL_HEADER_SIZE: usize = 0x10;
+/// Bytes read of big pool allocations, whose header gives no size
+const BIG_POOL_READ: usize = 0x1000;
+/// Key schedules are dword aligned
+const SCHEDULE_ALIGN: usize = 4;
+/// dislocker's FVEK file: the encryption method, then the key padded to 64 bytes
+const DISLOCKER_KEY_SIZE: usize = 64;
+
+/// BitLocker encryption methods, by their FVE metadata identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FveCipher {
+    Aes128Diffuser = 0x8000,
+    Aes256Diffuser = 0x8001,
+    Aes128Cbc = 0x8002,
+    Aes256Cbc = 0x8003,
+    XtsAes128 = 0x8004,
+    XtsAes256 = 0x8005,
+}
+
+impl FveCipher {
+    pub fn id(self) -> u16 {
+        self as u16
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FveCipher::Aes128Diffuser => "AES-128-CBC + Elephant diffuser",
+            FveCipher::Aes256Diffuser => "AES-256-CBC + Elephant diffuser",
+            FveCipher::Aes128Cbc => "AES-128-CBC",
+            FveCipher::Aes256Cbc => "AES-256-CBC",
+            FveCipher::XtsAes128 => "XTS-AES-128",
+            FveCipher::XtsAes256 => "XTS-AES-256",
+        }
+    }
+}
+
+/// A key recovered from a pool allocation
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitlockerKey {
+    pub cipher: FveCipher,
+    /// The FVEK; both AES keys for XTS
+    pub fvek: Vec<u8>,
+    /// The diffuser tweak key
+    pub tweak: Option<Vec<u8>>,
+    pub pool_tag: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl BitlockerKey {
+    /// The FVEK file dislocker reads with `--fvek`
+    pub fn dislocker_file(&self) -> Vec<u8> {
+        let mut file = self.cipher.id().to_le_bytes().to_vec();
+        let mut key = self.fvek.clone();
+        key.extend(self.tweak.iter().flatten());
+        key.resize(DISLOCKER_KEY_SIZE, 0);
+        file.extend(key);
+        file
+    }
+
+    /// The `bdemount -k` key argument, `FVEK[:TWEAK]` in hex
+    pub fn libbde_keys(&self) -> String {
+        match &self.tweak {
+            Some(tweak) => format!("{}:{}", hex(&self.fvek), hex(tweak)),
+            None => hex(&self.fvek),
+        }
+    }
+
+    /// A pair of keys or a key in the BitLocker context is the volume's;
+    /// a lone key in a CNG pool may be anyone's
+    pub fn is_certain(&self) -> bool {
+        self.tweak.is_some() || self.pool_tag.as_bytes() == FVE_POOL_TAG || matches!(self.cipher, FveCipher::XtsAes128 | FveCipher::XtsAes256)
+    }
+}
+
+/// SubBytes of one word. With the word in every column ShiftRows leaves the
+/// block as it is, so an AES round under a zero key, less its MixColumns,
+/// substitutes each byte.
+fn sub_word(word: [u8; 4]) -> [u8; 4] {
+    let mut block = aes::Block::clone_from_slice(&word.repeat(4));
+    cipher_round(&mut block, &aes::Block::default());
+    inv_mix_columns(&mut block);
+    block[..4].try_into().unwrap()
+}
+
+/// An AES-128 or AES-256 key expanded as AES implementations keep it in
+/// memory: the key, then each round key; `None` for other key sizes
+pub fn key_schedule(key: &[u8]) -> Option<Vec<u8>> {
+    if key.len() != 16 && key.len() != 32 {
+        return None;
+    }
+    let nk = key.len() / 4;
+    let mut schedule = key.to_vec();
+    let mut rcon = 1u8;
+    for i in nk..4 * (nk + 7) {
+        let mut temp: [u8; 4] = schedule[(i - 1) * 4..i * 4].try_into().unwrap();
+        if i % nk == 0 {
+            temp = sub_word([temp[1], temp[2], temp[3], temp[0]]);
+            temp[0] ^= rcon;
+            rcon = (rcon << 1) ^ if rcon & 0x80 != 0 { 0x1B } else { 0 };
+        } else if nk > 6 && i % nk == 4 {
+            temp = sub_word(temp);
+        }
+        for (j, byte) in temp.into_iter().enumerate() {
+            schedule.push(schedule[(i - nk) * 4 + j] ^ byte);
+        }
+    }
+    Some(schedule)
+}
+
+/// The AES keys in `data` stored with their expanded schedule, by offset
+pub fn find_key_schedules(data: &[u8]) -> Vec<(usize, Vec<u8>)> {
+    let mut keys = Vec::new();
+    let mut offset = 0;
+    while offset + 16 <= data.len() {
+        let found = [32, 16].into_iter().find_map(|len| {
+            let key = data.get(offset..offset + len)?;
+            // A zeroed or freed allocation expands from no key
+            if key.iter().all(|&b| b == key[0]) {
+                return None;
+            }
+            let schedule = key_schedule(key)?;
+            (data.get(offset..offset + schedule.len())? == schedule.as_slice()).then(|| (key.to_vec(), schedule.len()))
+        });
+        match found {
+            Some((key, len)) => {
+                keys.push((offset, key));
+                offset += len;
+            }
+            None => offset += SCHEDULE_ALIGN,
+        }
+    }
+    keys
+}
+
+/// Pair the key schedules of one pool allocation into BitLocker keys
+pub fn pool_keys(tag: &[u8], block: &[u8]) -> Vec<(usize, BitlockerKey)> {
+    let fve = tag == FVE_POOL_TAG;
+    let pool_tag = String::from_utf8_lossy(tag).to_string();
+    let schedules = find_key_schedules(block);
+    let mut keys = Vec::new();
+    let mut i = 0;
+    while i < schedules.len() {
+        let (offset, first) = &schedules[i];
+        let aes256 = first.len() == 32;
+        let second = schedules.get(i + 1).map(|(_, key)| key).filter(|key| key.len() == first.len());
+        let (cipher, fvek, tweak) = match (second, fve) {
+            (Some(second), true) => (if aes256 { FveCipher::Aes256Diffuser } else { FveCipher::Aes128Diffuser }, first.clone(), Some(second.clone())),
+            (Some(second), false) => (if aes256 { FveCipher::XtsAes256 } else { FveCipher::XtsAes128 }, [first.as_slice(), second].concat(), None),
+            (None, _) => (if aes256 { FveCipher::Aes256Cbc } else { FveCipher::Aes128Cbc }, first.clone(), None),
+        };
+        i += if second.is_some() { 2 } else { 1 };
+        keys.push((*offset, BitlockerKey { cipher, fvek, tweak, pool_tag: pool_tag.clone() }));
+    }
+    keys
+}
+
+/// Shown in place of keys unless `--reveal` is given
+fn redacted(bytes: usize) -> String {
+    format!("<redacted, {} bytes>", bytes)
+}
+
+/// A plugin that recovers BitLocker volume keys from pool memory
+#[derive(Default)]
+pub struct BitlockerScanner {
+    /// Report keys in clear
+    pub reveal: bool,
+}
+
+impl BitlockerScanner {
+    pub fn new(reveal: bool) -> Self {
+        BitlockerScanner { reveal }
+    }
+
+    fn report(&self, addr: u64, key: &BitlockerKey, copies: usize) -> Finding {
+        let shown = |value: String, bytes: usize| if self.reveal { value } else { redacted(bytes) };
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "credential".to_string());
+        details.insert("rule".to_string(), if key.is_certain() { "bitlocker_fvek" } else { "possible_bitlocker_fvek" }.to_string());
+        details.insert("cipher".to_string(), key.cipher.name().to_string());
+        details.insert("method".to_string(), format!("0x{:04x}", key.cipher.id()));
+        details.insert("pool_tag".to_string(), key.pool_tag.clone());
+        details.insert("copies".to_string(), copies.to_string());
+        details.insert("fvek".to_string(), shown(hex(&key.fvek), key.fvek.len()));
+        if let Some(tweak) = &key.tweak {
+            details.insert("tweak".to_string(), shown(hex(tweak), tweak.len()));
+        }
+        if self.reveal {
+            details.insert("dislocker".to_string(), hex(&key.dislocker_file()));
+            details.insert("libbde".to_string(), format!("bdemount -k {}", key.libbde_keys()));
+        }
+        details.insert("redacted".to_string(), (!self.reveal).to_string());
+        let what = if key.is_certain() { "BitLocker FVEK" } else { "Possible BitLocker FVEK" };
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} ({}) in {} pool: {}", what, key.cipher.name(), key.pool_tag, details["fvek"]),
+            confidence: if key.is_certain() { 85 } else { 40 },
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for BitlockerScanner {
+    fn name(&self) -> &'static str {
+        "bitlocker"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
+    fn description(&self) -> &'static str {
+        "Recovers BitLocker FVEKs from AES key schedules in FVE and CNG pools, for dislocker and libbde (redacted unless --reveal)"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![("reveal", self.reveal.to_string())]
+    }
+
+    fn revealing(&self) -> Option<Box<dyn MemoryPlugin>> {
+        Some(Box::new(BitlockerScanner::new(true)))
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        progress.set_message("Scanning FVE and CNG pools for AES key schedules");
+        // The same key is often held by several allocations; report it once
+        let mut keys: Vec<(u64, BitlockerKey, usize)> = Vec::new();
+        for (header, size) in scan_pool_tags(img, progress, &KEY_POOL_TAGS) {
+            let start = header as usize + POOL_HEADER_SIZE;
+            let len = if size > POOL_HEADER_SIZE { size - POOL_HEADER_SIZE } else { BIG_POOL_READ - POOL_HEADER_SIZE };
+            let Some(block) = img.get_bytes(start, len.min(img.size().saturating_sub(start))) else { continue };
+            let Some(tag) = img.get_bytes(header as usize + 4, 4) else { continue };
+            for (offset, key) in pool_keys(tag, block) {
+                match keys.iter_mut().find(|(_, seen, _)| seen.fvek == key.fvek) {
+                    Some((_, _, copies)) => *copies += 1,
+                    None => keys.push(((start + offset) as u64, key, 1)),
+                }
+            }
+        }
+        let findings: Vec<Finding> = keys.iter().map(|(addr, key, copies)| self.report(*addr, key, *copies)).collect();
+        let certain = keys.iter().filter(|(_, key, _)| key.is_certain()).count();
+        progress.finish_with_message(format!("Found {} BitLocker keys and {} possible keys", certain, keys.len() - certain));
+        findings
+    }
+}
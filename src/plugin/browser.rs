@@ -0,0 +1,296 @@
+//! Browser artifacts in browser process memory
+//!
+//! Web browsers keep what they fetch and send in their heaps long after a
+//! page closes: the URLs of requests and history, HTTP `Cookie` and
+//! `Set-Cookie` headers, and urlencoded form bodies as they were posted.
+//! The scanner searches the private and pagefile-backed memory of Chrome,
+//! Edge, Firefox and other browser processes for these as ASCII and
+//! UTF-16LE text (Chromium's network stack holds UTF-8 strings, Blink and
+//! Gecko UTF-16), reporting each distinct artifact once per process with
+//! the number of copies. Form data is only taken when a field names a
+//! credential or identity, and a query string is reported as its URL.
+
+use indicatif::ProgressBar;
+use regex::bytes::{Regex, RegexBuilder};
+use std::collections::HashMap;
+
+use crate::kdbg::OsContext;
+use crate::modules::read_pages;
+use crate::paging::MemoryImage;
+use crate::processes::{Process, ProcessFinder, WindowsProcessFinder};
+use crate::vad::VadKind;
+use super::pattern_scan::{narrow_utf16, TextEncoding};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Browser process names, as truncated in the EPROCESS, and the browser
+const BROWSERS: [(&str, &str); 8] = [
+    ("chrome.exe", "Chrome"),
+    ("msedge.exe", "Edge"),
+    ("msedgewebview2", "Edge WebView2"),
+    ("firefox.exe", "Firefox"),
+    ("brave.exe", "Brave"),
+    ("opera.exe", "Opera"),
+    ("vivaldi.exe", "Vivaldi"),
+    ("iexplore.exe", "Internet Explorer"),
+];
+/// Field names, in lower case, that make form data worth reporting; the
+/// first group are secrets
+const SECRET_FIELDS: [&str; 6] = ["pass", "pwd", "secret", "otp", "cvv", "pin"];
+const IDENTITY_FIELDS: [&str; 6] = ["user", "login", "email", "mail", "account", "card"];
+/// Process memory is read a chunk at a time, each running into the next
+/// far enough for the longest artifact
+const CHUNK: u64 = 0x100000;
+const OVERLAP: u64 = 0x4000;
+const MAX_REGION_SIZE: u64 = 0x4000_0000;
+/// Distinct artifacts reported per process
+const MAX_ARTIFACTS: usize = 5000;
+
+/// The browser whose process `name` is
+pub fn browser_name(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    BROWSERS.iter().find(|(process, _)| name.starts_with(process)).map(|&(_, browser)| browser)
+}
+
+/// What a browser artifact is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArtifactKind {
+    Url,
+    /// A `Cookie` request header
+    Cookie,
+    /// A `Set-Cookie` response header
+    SetCookie,
+    FormData,
+}
+
+impl ArtifactKind {
+    fn rule(self) -> &'static str {
+        match self {
+            ArtifactKind::Url => "url",
+            ArtifactKind::Cookie => "cookie",
+            ArtifactKind::SetCookie => "set_cookie",
+            ArtifactKind::FormData => "form_data",
+        }
+    }
+}
+
+/// A URL, cookie or form body found in a process
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowserArtifact {
+    pub kind: ArtifactKind,
+    /// Virtual address of the first copy
+    pub address: u64,
+    pub encoding: TextEncoding,
+    pub text: String,
+    pub copies: usize,
+}
+
+impl BrowserArtifact {
+    /// Host of a URL, or of the domain attribute of a set cookie
+    pub fn host(&self) -> Option<String> {
+        let host = match self.kind {
+            ArtifactKind::Url => self.text.split_once("://")?.1.split(['/', '?', '#']).next()?,
+            ArtifactKind::SetCookie => self.text.split(';').find_map(|attribute| {
+                let (name, value) = attribute.split_once('=')?;
+                name.trim().eq_ignore_ascii_case("domain").then_some(value.trim())
+            })?,
+            _ => return None,
+        };
+        let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+        Some(host.trim_start_matches('.').to_lowercase())
+    }
+
+    /// Names of the fields of a form body or cookie header
+    pub fn fields(&self) -> Vec<String> {
+        let (text, separator) = match self.kind {
+            ArtifactKind::FormData => (self.text.as_str(), '&'),
+            ArtifactKind::Cookie => (self.text.as_str(), ';'),
+            // Attributes after the first pair describe the cookie
+            ArtifactKind::SetCookie => (self.text.split(';').next().unwrap_or_default(), ';'),
+            ArtifactKind::Url => return Vec::new(),
+        };
+        text.split(separator).filter_map(|pair| pair.split_once('=')).map(|(name, _)| name.trim().to_string()).collect()
+    }
+}
+
+/// Whether a form field name is a secret, an identity or neither
+fn field_class(name: &str) -> Option<bool> {
+    let name = name.to_lowercase();
+    if SECRET_FIELDS.iter().any(|field| name.contains(field)) {
+        Some(true)
+    } else if IDENTITY_FIELDS.iter().any(|field| name.contains(field)) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// A plugin that carves URLs, cookies and form data from browser processes
+pub struct BrowserScanner {
+    url: Regex,
+    cookie: Regex,
+    form: Regex,
+}
+
+impl Default for BrowserScanner {
+    fn default() -> Self {
+        let build = |pattern: &str| RegexBuilder::new(pattern).unicode(false).build().unwrap();
+        BrowserScanner {
+            url: build(r#"(?i)\b(?:https?|wss?|ftp)://[a-z0-9][a-z0-9.-]*(?::[0-9]{1,5})?(?:[/?#][!#-;=?-~]{0,2048})?"#),
+            cookie: build(r"(?i)\b(set-)?cookie:[ \t]*([!-~][ -~]{2,4095})"),
+            form: build(r"(?:[A-Za-z0-9_.%\[\]-]{1,64}=[!#-%'-;=?-~]{0,512}&)+[A-Za-z0-9_.%\[\]-]{1,64}=[!#-%'-;=?-~]{0,512}"),
+        }
+    }
+}
+
+impl BrowserScanner {
+    /// URLs, cookie headers and form bodies in `data`, by offset, with the
+    /// kind of each
+    pub fn find(&self, data: &[u8]) -> Vec<(usize, ArtifactKind, String)> {
+        let mut found = Vec::new();
+        for m in self.url.find_iter(data) {
+            // Text running on from the URL is not part of it
+            let url = m.as_bytes().strip_suffix(b".").unwrap_or(m.as_bytes());
+            found.push((m.start(), ArtifactKind::Url, String::from_utf8_lossy(url).to_string()));
+        }
+        for captures in self.cookie.captures_iter(data) {
+            let kind = if captures.get(1).is_some() { ArtifactKind::SetCookie } else { ArtifactKind::Cookie };
+            let header = String::from_utf8_lossy(&captures[2]).trim_end().to_string();
+            if header.contains('=') {
+                found.push((captures.get(0).unwrap().start(), kind, header));
+            }
+        }
+        for m in self.form.find_iter(data) {
+            // A query string is reported with its URL
+            if m.start() > 0 && matches!(data[m.start() - 1], b'?' | b'#' | b'=' | b'&') {
+                continue;
+            }
+            let body = String::from_utf8_lossy(m.as_bytes()).to_string();
+            let artifact = BrowserArtifact { kind: ArtifactKind::FormData, address: 0, encoding: TextEncoding::Ascii, text: body, copies: 1 };
+            if artifact.fields().iter().any(|field| field_class(field).is_some()) {
+                found.push((m.start(), ArtifactKind::FormData, artifact.text));
+            }
+        }
+        found.sort_by_key(|&(offset, kind, _)| (offset, kind.rule()));
+        found
+    }
+
+    /// URLs, cookies and form data in the private and pagefile-backed memory of `process`
+    pub fn process_artifacts(&self, img: &MemoryImage, finder: &WindowsProcessFinder, process: &Process) -> Vec<BrowserArtifact> {
+        let Some(space) = process.address_space(img) else { return Vec::new() };
+        let mut artifacts: Vec<BrowserArtifact> = Vec::new();
+        let mut seen: HashMap<(ArtifactKind, String), usize> = HashMap::new();
+        let regions = finder.vads(img, process).into_iter()
+            .filter(|vad| matches!((&vad.kind, &vad.file), (VadKind::Private, _) | (VadKind::Mapped, None)) && vad.size() <= MAX_REGION_SIZE);
+        for region in regions {
+            let mut offset = 0;
+            while offset < region.size() && artifacts.len() < MAX_ARTIFACTS {
+                let len = (CHUNK + OVERLAP).min(region.size() - offset);
+                let (data, missing) = read_pages(&space, region.start + offset, len);
+                if missing as u64 == len.div_ceil(0x1000) {
+                    offset += CHUNK;
+                    continue;
+                }
+                let mut found: Vec<(u64, TextEncoding, ArtifactKind, String)> = self.find(&data).into_iter()
+                    .map(|(at, kind, text)| (at as u64, TextEncoding::Ascii, kind, text)).collect();
+                for alignment in 0..2 {
+                    found.extend(self.find(&narrow_utf16(&data, alignment)).into_iter()
+                        .map(|(at, kind, text)| ((alignment + at * 2) as u64, TextEncoding::Utf16Le, kind, text)));
+                }
+                found.sort_by_key(|&(at, ..)| at);
+                // Artifacts starting in the overlap belong to the next chunk
+                for (at, encoding, kind, text) in found.into_iter().filter(|&(at, ..)| at < CHUNK) {
+                    match seen.get(&(kind, text.clone())) {
+                        Some(&index) => artifacts[index].copies += 1,
+                        None => {
+                            seen.insert((kind, text.clone()), artifacts.len());
+                            artifacts.push(BrowserArtifact { kind, address: region.start + offset + at, encoding, text, copies: 1 });
+                        }
+                    }
+                }
+                offset += CHUNK;
+            }
+        }
+        artifacts
+    }
+
+    fn report(&self, addr: u64, process: &Process, browser: &str, artifact: &BrowserArtifact) -> Finding {
+        let fields = artifact.fields();
+        let secret = fields.iter().any(|field| field_class(field) == Some(true));
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "browser".to_string());
+        details.insert("rule".to_string(), artifact.kind.rule().to_string());
+        details.insert("browser".to_string(), browser.to_string());
+        details.insert("pid".to_string(), process.pid.to_string());
+        details.insert("process".to_string(), process.name.clone());
+        details.insert("address".to_string(), format!("{:#x}", artifact.address));
+        details.insert("encoding".to_string(), artifact.encoding.to_string());
+        details.insert("copies".to_string(), artifact.copies.to_string());
+        details.insert("text".to_string(), artifact.text.clone());
+        if let Some(host) = artifact.host() {
+            details.insert("host".to_string(), host);
+        }
+        if !fields.is_empty() {
+            details.insert("fields".to_string(), fields.join(","));
+        }
+        let (what, confidence) = match artifact.kind {
+            ArtifactKind::Url => ("URL", 20),
+            ArtifactKind::Cookie => ("Cookie header", 50),
+            ArtifactKind::SetCookie => ("Set-Cookie header", 50),
+            ArtifactKind::FormData if secret => ("Form data with a secret", 70),
+            ArtifactKind::FormData => ("Form data", 40),
+        };
+        let shown: String = artifact.text.chars().take(100).collect();
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} in {} (PID {}): {}", what, process.name, process.pid, shown),
+            confidence,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for BrowserScanner {
+    fn name(&self) -> &'static str {
+        "browser"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Carves URLs, cookie headers and credential form data from Chrome, Edge, Firefox and other browser processes"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Locating processes");
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else {
+            progress.finish_with_message("No kernel found; browser needs the process list");
+            return findings;
+        };
+        let finder = WindowsProcessFinder::new().with_os_context(os);
+        let processes = finder.find_processes(img, &ProgressBar::hidden()).unwrap_or_default();
+
+        progress.set_message("Carving browser artifacts");
+        progress.set_length(processes.len() as u64);
+        progress.set_position(0);
+        for process in &processes {
+            progress.inc(1);
+            let Some(browser) = browser_name(&process.name) else { continue };
+            let space = process.address_space(img);
+            for artifact in self.process_artifacts(img, &finder, process) {
+                let addr = space.as_ref().and_then(|space| space.virt_to_phys(artifact.address)).unwrap_or(artifact.address);
+                findings.push(self.report(addr, process, browser, &artifact));
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} browser artifacts", findings.len()));
+        findings
+    }
+}
@@ -0,0 +1,276 @@
+//! Kernel notification callbacks
+//!
+//! Drivers register routines the kernel calls on process and thread
+//! creation, image loads (PsSetCreateProcessNotifyRoutine and friends),
+//! registry operations (CmRegisterCallback) and handle operations on
+//! processes and threads (ObRegisterCallbacks). Rootkits use them to watch
+//! or block security tools, and a routine left in pool memory by a driver
+//! that unloaded or was never on the module list stands out. The arrays
+//! and lists are not exported, so they are found by disassembling the
+//! exported ntoskrnl routines that use them (and the function each calls)
+//! for RIP-relative `lea`s whose target has the expected shape. A driver
+//! without an embedded signature may still be catalog-signed, so that flag
+//! is weaker than code outside every module. Offsets are for Windows 10 x64.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::disasm::disassemble;
+use crate::kdbg::OsContext;
+use crate::modules::{list_kernel_modules, module_containing, read_pages, KernelModule};
+use crate::paging::MemoryImage;
+use crate::pe::{module_exports, Export};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Exported routines referencing the EX_CALLBACK arrays, by callback kind
+const NOTIFY_ARRAYS: [(&str, &str); 3] = [
+    ("process creation", "PsSetCreateProcessNotifyRoutine"),
+    ("thread creation", "PsSetCreateThreadNotifyRoutine"),
+    ("image load", "PsSetLoadImageNotifyRoutine"),
+];
+/// Slots of each notify routine array
+const NOTIFY_SLOTS: usize = 64;
+/// EX_CALLBACK_ROUTINE_BLOCK.Function; the low four bits of a slot are a
+/// reference count
+const ROUTINE_BLOCK_FUNCTION: u64 = 0x8;
+const FAST_REF_MASK: u64 = !0xF;
+
+/// Exported routine referencing CallbackListHead
+const REGISTRY_CALLBACKS: &str = "CmUnRegisterCallback";
+/// CM_CALLBACK_CONTEXT_BLOCK offsets
+const CM_CALLBACK_FUNCTION: u64 = 0x28;
+const CM_CALLBACK_ALTITUDE: u64 = 0x30;
+
+/// Exported OBJECT_TYPE pointers with handle operation callbacks
+const OBJECT_TYPES: [(&str, &str); 2] = [("Process", "PsProcessType"), ("Thread", "PsThreadType")];
+const OBJECT_TYPE_CALLBACK_LIST: u64 = 0xC8;
+/// OB_CALLBACK_ENTRY offsets
+const OB_CALLBACK_OPERATIONS: u64 = 0x10;
+const OB_CALLBACK_ENABLED: u64 = 0x14;
+const OB_CALLBACK_PRE: u64 = 0x28;
+const OB_CALLBACK_POST: u64 = 0x30;
+
+/// Bytes and instructions of each routine searched for references
+const ROUTINE_BYTES: u64 = 0x200;
+const ROUTINE_INSTRUCTIONS: usize = 128;
+/// Upper bound on list entries, guarding against corrupted links
+const MAX_LIST_ENTRIES: usize = 1024;
+/// Security data directory, holding an embedded Authenticode signature
+const SECURITY_DIRECTORY: u64 = 4;
+const KERNEL_SPACE: u64 = 0xFFFF_8000_0000_0000;
+
+/// A registered callback routine
+#[derive(Debug, Clone, PartialEq)]
+pub struct KernelCallback {
+    /// `PsSetCreateProcessNotifyRoutine`, `CmRegisterCallback`, ...
+    pub api: &'static str,
+    /// What the callback is called on
+    pub event: String,
+    pub function: u64,
+    /// Address of the array slot or list entry registering it
+    pub registration: u64,
+    /// Registry callback altitude
+    pub altitude: Option<String>,
+}
+
+fn is_kernel_va(va: u64) -> bool {
+    va >= KERNEL_SPACE
+}
+
+/// Targets of RIP-relative `lea`s in the routine at `function`, following
+/// direct calls and jumps within `kernel` `depth` levels deep, in order
+pub fn lea_targets(img: &MemoryImage, function: u64, kernel: &KernelModule, depth: usize) -> Vec<u64> {
+    rip_operands(img, function, kernel, depth, "lea")
+}
+
+/// RIP-relative operand addresses of the `mnemonic` instructions in the
+/// routine at `function`, following calls and jumps like [`lea_targets`]
+pub fn rip_operands(img: &MemoryImage, function: u64, kernel: &KernelModule, depth: usize, mnemonic: &str) -> Vec<u64> {
+    let (code, _) = read_pages(img, function, ROUTINE_BYTES);
+    let mut targets = Vec::new();
+    for instruction in disassemble(&code, function, ROUTINE_INSTRUCTIONS) {
+        if let Some(target) = instruction.memory.filter(|_| instruction.mnemonic == mnemonic) {
+            targets.push(target);
+        }
+        if let Some(callee) = instruction.target.filter(|&t| depth > 0 && kernel.contains(t)) {
+            if instruction.mnemonic == "call" || instruction.mnemonic == "jmp" {
+                targets.extend(rip_operands(img, callee, kernel, depth - 1, mnemonic));
+            }
+        }
+        if instruction.mnemonic == "ret" || instruction.mnemonic == "int3" || instruction.mnemonic == "jmp" {
+            break;
+        }
+    }
+    targets
+}
+
+/// Functions registered in an EX_CALLBACK array, or None when `array` does
+/// not look like one
+pub fn notify_routines(img: &MemoryImage, array: u64) -> Option<Vec<(u64, u64)>> {
+    let slots = img.read_virt(array, NOTIFY_SLOTS * 8)?;
+    let mut routines = Vec::new();
+    for (index, slot) in slots.chunks_exact(8).enumerate() {
+        let slot = u64::from_le_bytes(slot.try_into().unwrap());
+        if slot == 0 {
+            continue;
+        }
+        let block = slot & FAST_REF_MASK;
+        let function = img.read_virt_u64(block + ROUTINE_BLOCK_FUNCTION).filter(|&f| is_kernel_va(block) && is_kernel_va(f))?;
+        routines.push((array + index as u64 * 8, function));
+    }
+    (!routines.is_empty()).then_some(routines)
+}
+
+/// Entries of the LIST_ENTRY at `head`, or None when it is not a list head
+fn list_entries(img: &MemoryImage, head: u64) -> Option<Vec<u64>> {
+    let first = img.read_virt_u64(head).filter(|&f| is_kernel_va(f))?;
+    if img.read_virt_u64(first + 8)? != head {
+        return None;
+    }
+    let mut entries = Vec::new();
+    let mut link = first;
+    while link != head && is_kernel_va(link) && entries.len() < MAX_LIST_ENTRIES && !entries.contains(&link) {
+        entries.push(link);
+        link = img.read_virt_u64(link)?;
+    }
+    Some(entries)
+}
+
+/// Callbacks registered with the kernel at `kernel`, whose exports are `exports`
+pub fn kernel_callbacks(img: &MemoryImage, kernel: &KernelModule, exports: &[Export]) -> Vec<KernelCallback> {
+    let export = |name: &str| exports.iter().find(|e| e.name == name).map(|e| e.address);
+    let in_kernel = |va: &u64| kernel.contains(*va);
+    let mut callbacks = Vec::new();
+
+    for (event, api) in NOTIFY_ARRAYS {
+        let Some(routine) = export(api) else { continue };
+        let found = lea_targets(img, routine, kernel, 1).into_iter().filter(in_kernel).find_map(|array| notify_routines(img, array));
+        for (registration, function) in found.unwrap_or_default() {
+            callbacks.push(KernelCallback { api, event: event.to_string(), function, registration, altitude: None });
+        }
+    }
+
+    if let Some(routine) = export(REGISTRY_CALLBACKS) {
+        let entries = lea_targets(img, routine, kernel, 1).into_iter().filter(in_kernel).find_map(|head| list_entries(img, head));
+        for entry in entries.unwrap_or_default() {
+            let Some(function) = img.read_virt_u64(entry + CM_CALLBACK_FUNCTION).filter(|&f| is_kernel_va(f)) else { continue };
+            callbacks.push(KernelCallback {
+                api: "CmRegisterCallback",
+                event: "registry operation".to_string(),
+                function,
+                registration: entry,
+                altitude: img.read_unicode_string(entry + CM_CALLBACK_ALTITUDE),
+            });
+        }
+    }
+
+    for (name, variable) in OBJECT_TYPES {
+        let Some(object_type) = export(variable).and_then(|va| img.read_virt_u64(va)).filter(|&t| is_kernel_va(t)) else { continue };
+        for entry in list_entries(img, object_type + OBJECT_TYPE_CALLBACK_LIST).unwrap_or_default() {
+            let operations = img.read_virt_u32(entry + OB_CALLBACK_OPERATIONS).unwrap_or(0);
+            let kinds: Vec<&str> = [(1, "create"), (2, "duplicate")].iter().filter(|(bit, _)| operations & bit != 0).map(|(_, k)| *k).collect();
+            let enabled = img.read_virt(entry + OB_CALLBACK_ENABLED, 1).is_some_and(|b| b[0] != 0);
+            for (stage, offset) in [("pre", OB_CALLBACK_PRE), ("post", OB_CALLBACK_POST)] {
+                let Some(function) = img.read_virt_u64(entry + offset).filter(|&f| is_kernel_va(f)) else { continue };
+                callbacks.push(KernelCallback {
+                    api: "ObRegisterCallbacks",
+                    event: format!("{} handle {} ({}){}", name, stage, kinds.join(", "), if enabled { "" } else { ", disabled" }),
+                    function,
+                    registration: entry,
+                    altitude: None,
+                });
+            }
+        }
+    }
+    callbacks
+}
+
+/// Whether the PE image at `base` carries an embedded Authenticode signature
+pub fn has_embedded_signature(img: &MemoryImage, base: u64) -> bool {
+    let Some(nt) = img.read_virt_u32(base + 0x3C).map(|offset| base + offset as u64) else { return false };
+    if img.read_virt(nt, 4).as_deref() != Some(b"PE\0\0") {
+        return false;
+    }
+    let directories = match img.read_virt(nt + 0x18, 2).as_deref() {
+        Some([0x0B, 0x01]) => 0x60,
+        Some([0x0B, 0x02]) => 0x70,
+        _ => return false,
+    };
+    img.read_virt_u32(nt + 0x18 + directories + SECURITY_DIRECTORY * 8 + 4).is_some_and(|size| size != 0)
+}
+
+/// A plugin that lists kernel notification callbacks and their drivers
+#[derive(Default)]
+pub struct CallbackScanner;
+
+impl CallbackScanner {
+    fn report(&self, img: &MemoryImage, callback: &KernelCallback, owner: Option<&KernelModule>, signed: bool) -> Finding {
+        let (rule, confidence) = match owner {
+            None => ("callback_in_unbacked_memory", 90),
+            Some(_) if !signed => ("callback_unsigned_module", 60),
+            Some(_) => ("kernel_callback", 40),
+        };
+        let module = owner.map_or("unknown", |m| m.name.as_str());
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "callback".to_string());
+        details.insert("rule".to_string(), rule.to_string());
+        details.insert("api".to_string(), callback.api.to_string());
+        details.insert("event".to_string(), callback.event.clone());
+        details.insert("function".to_string(), format!("{:#x}", callback.function));
+        details.insert("module".to_string(), module.to_string());
+        details.insert("signed".to_string(), if signed { "embedded" } else { "none" }.to_string());
+        if let Some(altitude) = &callback.altitude {
+            details.insert("altitude".to_string(), altitude.clone());
+        }
+        Finding {
+            plugin: self.name().to_string(),
+            addr: img.virt_to_phys(callback.registration).unwrap_or(callback.registration),
+            desc: format!("{} callback {:#x} in {} ({})", callback.event, callback.function, module, callback.api),
+            confidence,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for CallbackScanner {
+    fn name(&self) -> &'static str {
+        "callbacks"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Lists process, thread, image load, registry and object callbacks, flagging routines outside signed drivers"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        progress.set_message("Locating the kernel");
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else {
+            progress.finish_with_message("No kernel found; callbacks need KDBG");
+            return Vec::new();
+        };
+        let modules = list_kernel_modules(img, &os);
+        let Some(kernel) = module_containing(&modules, os.kernel_base) else {
+            progress.finish_with_message("ntoskrnl is not on the module list");
+            return Vec::new();
+        };
+
+        progress.set_message("Reading callback arrays and lists");
+        let exports = module_exports(img, kernel.base);
+        let mut signatures: HashMap<u64, bool> = HashMap::new();
+        let findings: Vec<Finding> = kernel_callbacks(img, kernel, &exports).iter().map(|callback| {
+            let owner = module_containing(&modules, callback.function);
+            let signed = owner.is_some_and(|m| *signatures.entry(m.base).or_insert_with(|| has_embedded_signature(img, m.base)));
+            self.report(img, callback, owner, signed)
+        }).collect();
+
+        progress.finish_with_message(format!("Found {} callbacks", findings.len()));
+        findings
+    }
+}
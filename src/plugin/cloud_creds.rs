@@ -0,0 +1,374 @@
+//! Cloud credential detection plugin
+//!
+//! Looks for AWS, GCP and Azure credential material (access keys, STS session
+//! tokens, service-account JSON, SAS tokens and connection strings) as well as
+//! fragments of instance metadata service (IMDS) requests and responses.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::paging::MemoryImage;
+use crate::scan_util::{chunks, find_all, CHUNK_SIZE};
+use super::registry::{MemoryPlugin, Finding};
+
+/// How a rule decides whether a needle hit is a real credential
+#[derive(Clone, Copy)]
+enum Matcher {
+    /// Needle must be followed by `len` characters from the given alphabet
+    FixedToken { len: usize, alphabet: fn(u8) -> bool },
+    /// Needle must be followed by at least `min_len` token characters
+    VariableToken { min_len: usize },
+    /// Needle is a key; the value follows after `=`/`:`/quotes/whitespace
+    KeyValue { min_len: usize },
+    /// Needle alone is the indicator (URLs, headers)
+    Marker,
+    /// Needle must be accompanied by `other` within `window` bytes
+    Near { other: &'static [u8], window: usize },
+}
+
+struct Rule {
+    id: &'static str,
+    needle: &'static [u8],
+    provider: &'static str,
+    kind: &'static str,
+    desc: &'static str,
+    confidence: u8,
+    risk: &'static str,
+    matcher: Matcher,
+}
+
+/// Bytes read past a chunk for values after their needle, and in front of
+/// it for the widest `Near` rule
+const OVERLAP: usize = 0x2000;
+const LOOKBEHIND: usize = 0x1000;
+
+fn is_aws_key_char(c: u8) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit()
+}
+
+fn is_api_key_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'-'
+}
+
+fn is_token_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.' | b'/' | b'+' | b'=' | b'%')
+}
+
+const RULES: &[Rule] = &[
+    // AWS
+    Rule {
+        id: "aws_access_key_id",
+        needle: b"AKIA",
+        provider: "aws",
+        kind: "access_key_id",
+        desc: "AWS access key ID",
+        confidence: 90,
+        risk: "high",
+        matcher: Matcher::FixedToken { len: 16, alphabet: is_aws_key_char },
+    },
+    Rule {
+        id: "aws_sts_access_key_id",
+        needle: b"ASIA",
+        provider: "aws",
+        kind: "sts_access_key_id",
+        desc: "AWS STS temporary access key ID",
+        confidence: 90,
+        risk: "high",
+        matcher: Matcher::FixedToken { len: 16, alphabet: is_aws_key_char },
+    },
+    Rule {
+        id: "aws_secret_access_key",
+        needle: b"aws_secret_access_key",
+        provider: "aws",
+        kind: "secret_access_key",
+        desc: "AWS secret access key",
+        confidence: 95,
+        risk: "high",
+        matcher: Matcher::KeyValue { min_len: 40 },
+    },
+    Rule {
+        id: "aws_session_token",
+        needle: b"aws_session_token",
+        provider: "aws",
+        kind: "sts_session_token",
+        desc: "AWS STS session token",
+        confidence: 90,
+        risk: "high",
+        matcher: Matcher::KeyValue { min_len: 100 },
+    },
+    Rule {
+        id: "aws_imds_secret",
+        needle: b"\"SecretAccessKey\"",
+        provider: "aws",
+        kind: "imds_credentials",
+        desc: "AWS IMDS credential response (SecretAccessKey)",
+        confidence: 95,
+        risk: "high",
+        matcher: Matcher::KeyValue { min_len: 40 },
+    },
+    Rule {
+        id: "aws_imds_token",
+        needle: b"\"Token\"",
+        provider: "aws",
+        kind: "imds_credentials",
+        desc: "AWS IMDS credential response (session Token)",
+        confidence: 85,
+        risk: "high",
+        matcher: Matcher::Near { other: b"\"AccessKeyId\"", window: 512 },
+    },
+    Rule {
+        id: "aws_imds_request",
+        needle: b"/latest/meta-data/iam/security-credentials",
+        provider: "aws",
+        kind: "imds_request",
+        desc: "AWS IMDS credential endpoint reference",
+        confidence: 70,
+        risk: "medium",
+        matcher: Matcher::Marker,
+    },
+    Rule {
+        id: "aws_imdsv2_token_request",
+        needle: b"X-aws-ec2-metadata-token",
+        provider: "aws",
+        kind: "imds_request",
+        desc: "AWS IMDSv2 session token header",
+        confidence: 70,
+        risk: "medium",
+        matcher: Matcher::Marker,
+    },
+    // GCP
+    Rule {
+        id: "gcp_service_account_json",
+        needle: b"\"service_account\"",
+        provider: "gcp",
+        kind: "service_account_key",
+        desc: "GCP service-account key JSON",
+        confidence: 95,
+        risk: "high",
+        matcher: Matcher::Near { other: b"\"private_key\"", window: 4096 },
+    },
+    Rule {
+        id: "gcp_oauth_access_token",
+        needle: b"ya29.",
+        provider: "gcp",
+        kind: "oauth_access_token",
+        desc: "GCP OAuth2 access token",
+        confidence: 85,
+        risk: "high",
+        matcher: Matcher::VariableToken { min_len: 40 },
+    },
+    Rule {
+        id: "gcp_api_key",
+        needle: b"AIza",
+        provider: "gcp",
+        kind: "api_key",
+        desc: "Google API key",
+        confidence: 80,
+        risk: "medium",
+        matcher: Matcher::FixedToken { len: 35, alphabet: is_api_key_char },
+    },
+    Rule {
+        id: "gcp_metadata_request",
+        needle: b"computeMetadata/v1/instance/service-accounts",
+        provider: "gcp",
+        kind: "imds_request",
+        desc: "GCP metadata server service-account endpoint",
+        confidence: 70,
+        risk: "medium",
+        matcher: Matcher::Marker,
+    },
+    Rule {
+        id: "gcp_metadata_flavor",
+        needle: b"Metadata-Flavor: Google",
+        provider: "gcp",
+        kind: "imds_request",
+        desc: "GCP metadata server request header",
+        confidence: 60,
+        risk: "low",
+        matcher: Matcher::Marker,
+    },
+    // Azure
+    Rule {
+        id: "azure_storage_account_key",
+        needle: b"AccountKey=",
+        provider: "azure",
+        kind: "storage_connection_string",
+        desc: "Azure storage connection string (AccountKey)",
+        confidence: 95,
+        risk: "high",
+        matcher: Matcher::KeyValue { min_len: 64 },
+    },
+    Rule {
+        id: "azure_shared_access_key",
+        needle: b"SharedAccessKey=",
+        provider: "azure",
+        kind: "service_bus_connection_string",
+        desc: "Azure Service Bus/Event Hub shared access key",
+        confidence: 90,
+        risk: "high",
+        matcher: Matcher::KeyValue { min_len: 40 },
+    },
+    Rule {
+        id: "azure_sas_token",
+        needle: b"&sig=",
+        provider: "azure",
+        kind: "sas_token",
+        desc: "Azure shared access signature (SAS) token",
+        confidence: 85,
+        risk: "high",
+        matcher: Matcher::Near { other: b"sv=", window: 256 },
+    },
+    Rule {
+        id: "azure_imds_token_request",
+        needle: b"/metadata/identity/oauth2/token",
+        provider: "azure",
+        kind: "imds_request",
+        desc: "Azure IMDS managed identity token endpoint",
+        confidence: 70,
+        risk: "medium",
+        matcher: Matcher::Marker,
+    },
+];
+
+/// Characters that terminate a credential value
+fn is_value_terminator(c: u8) -> bool {
+    matches!(c, b'"' | b'\'' | b';' | b'&' | b',' | b'}' | 0) || c.is_ascii_whitespace()
+}
+
+/// Extract the value following a `key` needle, skipping separators and quotes
+fn extract_value(data: &[u8], start: usize) -> Option<&[u8]> {
+    let mut pos = start;
+    while pos < data.len() && matches!(data[pos], b'=' | b':' | b' ' | b'\t' | b'"' | b'\'') {
+        pos += 1;
+    }
+    let value_start = pos;
+    while pos < data.len() && !is_value_terminator(data[pos]) && data[pos].is_ascii_graphic() {
+        pos += 1;
+    }
+    if pos > value_start {
+        Some(&data[value_start..pos])
+    } else {
+        None
+    }
+}
+
+/// Mask the middle of a secret so findings can be shared without leaking it
+fn mask_secret(value: &str) -> String {
+    if value.len() <= 8 {
+        return "*".repeat(value.len());
+    }
+    format!("{}...{}", &value[..4], &value[value.len() - 4..])
+}
+
+/// A plugin that detects cloud provider credentials and metadata-service traffic
+#[derive(Default)]
+pub struct CloudCredentialScanner;
+
+impl CloudCredentialScanner {
+    /// Apply a rule at a needle hit, returning the matched value on success
+    fn evaluate(rule: &Rule, chunk: &[u8], pos: usize) -> Option<String> {
+        let after = pos + rule.needle.len();
+        match rule.matcher {
+            Matcher::FixedToken { len, alphabet } => {
+                // Reject hits embedded in a longer identifier
+                if pos > 0 && chunk[pos - 1].is_ascii_alphanumeric() {
+                    return None;
+                }
+                let token = chunk.get(after..after + len)?;
+                if !token.iter().all(|&c| alphabet(c)) {
+                    return None;
+                }
+                if chunk.get(after + len).is_some_and(|&c| alphabet(c)) {
+                    return None;
+                }
+                Some(String::from_utf8_lossy(&chunk[pos..after + len]).into_owned())
+            }
+            Matcher::VariableToken { min_len } => {
+                let len = chunk[after..].iter().take_while(|&&c| is_token_char(c)).count();
+                if len < min_len {
+                    return None;
+                }
+                Some(String::from_utf8_lossy(&chunk[pos..after + len]).into_owned())
+            }
+            Matcher::KeyValue { min_len } => {
+                let value = extract_value(chunk, after)?;
+                if value.len() < min_len {
+                    return None;
+                }
+                Some(String::from_utf8_lossy(value).into_owned())
+            }
+            Matcher::Marker => Some(String::from_utf8_lossy(rule.needle).into_owned()),
+            Matcher::Near { other, window } => {
+                let lo = pos.saturating_sub(window);
+                let hi = (after + window).min(chunk.len());
+                let found = chunk[lo..hi].windows(other.len()).any(|w| w == other);
+                if !found {
+                    return None;
+                }
+                let value = extract_value(chunk, after)
+                    .map(|v| String::from_utf8_lossy(v).into_owned())
+                    .unwrap_or_default();
+                Some(value)
+            }
+        }
+    }
+}
+
+impl MemoryPlugin for CloudCredentialScanner {
+    fn name(&self) -> &'static str {
+        "cloud_creds"
+    }
+
+    fn description(&self) -> &'static str {
+        "Detects AWS/GCP/Azure credentials and instance metadata service fragments"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        progress.set_message("Scanning for cloud credentials");
+
+        for window in chunks(img, CHUNK_SIZE, OVERLAP).with_lookbehind(LOOKBEHIND).with_progress(progress) {
+            let chunk = window.data;
+            for rule in RULES {
+                for pos in find_all(chunk, rule.needle).filter(|&pos| window.owns(pos)) {
+                    let value = match Self::evaluate(rule, chunk, pos) {
+                        Some(value) => value,
+                        None => continue,
+                    };
+
+                    let mut details = HashMap::new();
+                    details.insert("type".to_string(), "cloud_credential".to_string());
+                    details.insert("provider".to_string(), rule.provider.to_string());
+                    details.insert("kind".to_string(), rule.kind.to_string());
+                    details.insert("rule".to_string(), rule.id.to_string());
+                    details.insert("risk".to_string(), rule.risk.to_string());
+
+                    let shown = match rule.matcher {
+                        Matcher::Marker | Matcher::FixedToken { .. } => value,
+                        _ if value.is_empty() => String::new(),
+                        _ => mask_secret(&value),
+                    };
+                    if !shown.is_empty() {
+                        details.insert("value".to_string(), shown.clone());
+                    }
+
+                    findings.push(Finding {
+                        plugin: self.name().to_string(),
+                        addr: window.addr(pos),
+                        desc: if shown.is_empty() {
+                            rule.desc.to_string()
+                        } else {
+                            format!("{}: {}", rule.desc, shown)
+                        },
+                        confidence: rule.confidence,
+                        details,
+                    });
+                }
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} cloud credential artifacts", findings.len()));
+        findings
+    }
+}
@@ -0,0 +1,163 @@
+//! Container runtime detection plugin
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::containers::{parse_cgroup_path, ContainerRef, CONTAINER_ID_LEN};
+use crate::paging::MemoryImage;
+use crate::scan_util::{chunks, find_all, path_around, CHUNK_SIZE};
+use super::registry::{MemoryPlugin, Finding};
+
+/// Substrings that appear in container cgroup paths
+const CGROUP_NEEDLES: &[&[u8]] = &[
+    b"/docker/",
+    b"docker-",
+    b"cri-containerd-",
+    b"crio-",
+    b"libpod-",
+];
+
+/// Runtime artifacts that indicate a container engine was running on the host
+const RUNTIME_MARKERS: &[(&[u8], &str)] = &[
+    (b"/run/containerd/containerd.sock", "containerd"),
+    (b"/var/run/docker.sock", "docker"),
+    (b"containerd-shim-runc-v2", "containerd"),
+    (b"/var/run/crio/crio.sock", "cri-o"),
+    (b"runc init", "runc"),
+    (b"conmon", "podman"),
+];
+
+/// Docker's config.v2.json records the image next to the container ID
+const IMAGE_KEY: &[u8] = b"\"Image\":\"";
+const ID_KEY: &[u8] = b"\"ID\":\"";
+/// Bytes read around a chunk so cgroup paths (up to PATH_MAX) and the image
+/// following an ID are whole
+const OVERLAP: usize = 0x2000;
+const LOOKBEHIND: usize = 0x1000;
+
+/// Read a quoted JSON string value starting at `start`
+fn json_string(chunk: &[u8], start: usize) -> Option<String> {
+    let len = chunk.get(start..)?.iter().take(256).position(|&c| c == b'"')?;
+    std::str::from_utf8(&chunk[start..start + len]).ok().map(str::to_string)
+}
+
+/// Aggregated evidence for one container
+struct ContainerHits {
+    container: ContainerRef,
+    first_addr: u64,
+    hits: usize,
+    image: Option<String>,
+}
+
+/// A plugin that identifies containers and container runtimes in Linux images
+#[derive(Default)]
+pub struct ContainerScanner;
+
+impl MemoryPlugin for ContainerScanner {
+    fn name(&self) -> &'static str {
+        "containers"
+    }
+
+    fn description(&self) -> &'static str {
+        "Detects docker/containerd/CRI-O/podman containers from cgroup paths and runtime state"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut containers: HashMap<String, ContainerHits> = HashMap::new();
+        let mut images: HashMap<String, String> = HashMap::new();
+        let mut runtimes: HashMap<&'static str, u64> = HashMap::new();
+
+        progress.set_message("Scanning for container cgroups");
+
+        for window in chunks(img, CHUNK_SIZE, OVERLAP).with_lookbehind(LOOKBEHIND).with_progress(progress) {
+            let chunk = window.data;
+            for needle in CGROUP_NEEDLES {
+                for pos in find_all(chunk, needle).filter(|&pos| window.owns(pos)) {
+                    let path = String::from_utf8_lossy(path_around(chunk, pos)).into_owned();
+                    if let Some(container) = parse_cgroup_path(&path) {
+                        containers
+                            .entry(container.id.clone())
+                            .or_insert_with(|| ContainerHits {
+                                container,
+                                first_addr: window.addr(pos),
+                                hits: 0,
+                                image: None,
+                            })
+                            .hits += 1;
+                    }
+                }
+            }
+
+            // Pair `"ID":"<id>"` with the next `"Image":"<ref>"` in runtime state JSON
+            for pos in find_all(chunk, ID_KEY).filter(|&pos| window.owns(pos)) {
+                let id_start = pos + ID_KEY.len();
+                let id = match json_string(chunk, id_start) {
+                    Some(id) if id.len() == CONTAINER_ID_LEN => id,
+                    _ => continue,
+                };
+                let window_end = (id_start + 4096).min(chunk.len());
+                if let Some(img_pos) = find_all(&chunk[id_start..window_end], IMAGE_KEY).next() {
+                    if let Some(image) = json_string(chunk, id_start + img_pos + IMAGE_KEY.len()) {
+                        images.insert(id, image);
+                    }
+                }
+            }
+
+            for &(marker, runtime) in RUNTIME_MARKERS {
+                if let Some(pos) = find_all(chunk, marker).find(|&pos| window.owns(pos)) {
+                    runtimes.entry(runtime).or_insert(window.addr(pos));
+                }
+            }
+        }
+
+        let mut findings = Vec::new();
+
+        for (runtime, addr) in runtimes {
+            let mut details = HashMap::new();
+            details.insert("type".to_string(), "container_runtime".to_string());
+            details.insert("rule".to_string(), "runtime_marker".to_string());
+            details.insert("runtime".to_string(), runtime.to_string());
+
+            findings.push(Finding {
+                plugin: self.name().to_string(),
+                addr,
+                desc: format!("Container runtime artifacts: {}", runtime),
+                confidence: 60,
+                details,
+            });
+        }
+
+        for (id, mut entry) in containers {
+            entry.image = images.remove(&id);
+
+            let mut details = HashMap::new();
+            details.insert("type".to_string(), "container".to_string());
+            details.insert("rule".to_string(), "container_id_reference".to_string());
+            details.insert("container_id".to_string(), id.clone());
+            details.insert("runtime".to_string(), entry.container.runtime.to_string());
+            details.insert("references".to_string(), entry.hits.to_string());
+            if let Some(pod) = &entry.container.pod_uid {
+                details.insert("pod_uid".to_string(), pod.clone());
+            }
+            if let Some(image) = &entry.image {
+                details.insert("image".to_string(), image.clone());
+            }
+
+            findings.push(Finding {
+                plugin: self.name().to_string(),
+                addr: entry.first_addr,
+                desc: format!(
+                    "{} container {}{}",
+                    entry.container.runtime,
+                    entry.container.short_id(),
+                    entry.image.as_deref().map(|i| format!(" ({})", i)).unwrap_or_default()
+                ),
+                confidence: if entry.hits > 1 { 90 } else { 75 },
+                details,
+            });
+        }
+
+        progress.finish_with_message(format!("Found {} container artifacts", findings.len()));
+        findings
+    }
+}
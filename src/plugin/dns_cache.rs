@@ -0,0 +1,233 @@
+//! DNS response carving
+//!
+//! The names a host resolved show which domains to scope an incident to,
+//! and the answers map them to the addresses seen in connections. Resolver
+//! caches, socket buffers and applications keep DNS responses in wire
+//! format, so this scanner carves them from physical memory on any OS: it
+//! looks for a first answer whose name points back to the question
+//! (`C0 0C`), finds the header in front of the question and decodes the
+//! A, AAAA and CNAME answers with their TTLs.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::paging::MemoryImage;
+use crate::scan_util::{chunks, CHUNK_SIZE};
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+const HEADER_SIZE: usize = 12;
+/// Name pointer to the question name right after the header
+const QUESTION_POINTER: [u8; 2] = [0xC0, 0x0C];
+/// Longest encoded name (RFC 1035)
+const MAX_NAME: usize = 255;
+/// Largest response decoded, the usual EDNS buffer size
+const MAX_MESSAGE: usize = 0x1000;
+const MAX_ANSWERS: u16 = 64;
+
+const TYPE_A: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+/// Furthest a response's header can be in front of its first answer
+const LOOKBEHIND: usize = HEADER_SIZE + MAX_NAME + 4;
+
+/// One decoded answer record
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DnsAnswer {
+    pub name: String,
+    /// `A`, `AAAA` or `CNAME`
+    pub record_type: &'static str,
+    /// The address, or the canonical name of a CNAME
+    pub data: String,
+    pub ttl: u32,
+}
+
+/// A carved DNS response
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsResponse {
+    pub id: u16,
+    pub question: String,
+    pub answers: Vec<DnsAnswer>,
+}
+
+fn u16_at(data: &[u8], at: usize) -> Option<u16> {
+    data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+/// Decode the possibly compressed name at `at` in `message`; returns the
+/// name and the offset after its encoding
+fn read_name(message: &[u8], mut at: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    let mut jumps = 0;
+    loop {
+        let len = *message.get(at)? as usize;
+        match len {
+            0 => break,
+            0xC0.. => {
+                // Pointers may only go backwards, which also stops loops
+                let target = (u16_at(message, at)? & 0x3FFF) as usize;
+                jumps += 1;
+                if target >= at || jumps > 16 {
+                    return None;
+                }
+                end.get_or_insert(at + 2);
+                at = target;
+            }
+            1..=63 => {
+                let label = message.get(at + 1..at + 1 + len)?;
+                if !label.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') {
+                    return None;
+                }
+                labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+                at += 1 + len;
+            }
+            _ => return None,
+        }
+        if labels.iter().map(|l| l.len() + 1).sum::<usize>() > MAX_NAME {
+            return None;
+        }
+    }
+    (!labels.is_empty()).then(|| (labels.join("."), end.unwrap_or(at + 1)))
+}
+
+/// Decode a response whose header is at the start of `message`, with the
+/// question ending at `answers`
+pub fn parse_response(message: &[u8], answers: usize) -> Option<DnsResponse> {
+    let flags = u16_at(message, 2)?;
+    // A standard query response (QR set, opcode 0) without a format error
+    if flags & 0x8000 == 0 || flags & 0x7800 != 0 || flags & 0xF == 1 || u16_at(message, 4)? != 1 {
+        return None;
+    }
+    let count = u16_at(message, 6)?;
+    if count == 0 || count > MAX_ANSWERS {
+        return None;
+    }
+    let (question, after) = read_name(message, HEADER_SIZE)?;
+    if after + 4 != answers || u16_at(message, after + 2)? != CLASS_IN {
+        return None;
+    }
+
+    let mut response = DnsResponse { id: u16_at(message, 0)?, question, answers: Vec::new() };
+    let mut at = answers;
+    for _ in 0..count {
+        let Some((name, after)) = read_name(message, at) else { break };
+        let (Some(rtype), Some(class), Some(rdlength)) = (u16_at(message, after), u16_at(message, after + 2), u16_at(message, after + 8)) else { break };
+        let ttl = u32::from_be_bytes(message.get(after + 4..after + 8)?.try_into().unwrap());
+        let rdata = after + 10;
+        let Some(bytes) = message.get(rdata..rdata + rdlength as usize) else { break };
+        at = rdata + rdlength as usize;
+        if class != CLASS_IN {
+            continue;
+        }
+        let (record_type, data) = match (rtype, bytes.len()) {
+            (TYPE_A, 4) => ("A", Ipv4Addr::from(<[u8; 4]>::try_from(bytes).unwrap()).to_string()),
+            (TYPE_AAAA, 16) => ("AAAA", Ipv6Addr::from(<[u8; 16]>::try_from(bytes).unwrap()).to_string()),
+            (TYPE_CNAME, _) => match read_name(message, rdata) {
+                Some((cname, _)) => ("CNAME", cname),
+                None => break,
+            },
+            _ => continue,
+        };
+        response.answers.push(DnsAnswer { name, record_type, data, ttl });
+    }
+    (!response.answers.is_empty()).then_some(response)
+}
+
+/// Responses in `data` whose first answer starts in `from..limit`, with
+/// their offsets
+fn carve_responses(data: &[u8], from: usize, limit: usize) -> Vec<(usize, DnsResponse)> {
+    let mut responses = Vec::new();
+    let mut at = from.max(HEADER_SIZE + 6);
+    while at < limit.min(data.len()) {
+        let Some(found) = data[at..limit.min(data.len())].windows(2).position(|w| w == QUESTION_POINTER) else { break };
+        let answers = at + found;
+        at = answers + 1;
+        let first_type = u16_at(data, answers + 2);
+        if !matches!(first_type, Some(TYPE_A | TYPE_AAAA | TYPE_CNAME)) || u16_at(data, answers + 4) != Some(CLASS_IN) {
+            continue;
+        }
+        // The question is a name of at least two bytes, then its type and class
+        let shortest = answers.saturating_sub(HEADER_SIZE + 4 + 2);
+        let longest = answers.saturating_sub(LOOKBEHIND);
+        let parsed = (longest..=shortest).rev().find_map(|start| {
+            let message = &data[start..data.len().min(start + MAX_MESSAGE)];
+            parse_response(message, answers - start).map(|response| (start, response))
+        });
+        if let Some((start, response)) = parsed {
+            responses.push((start, response));
+        }
+    }
+    responses
+}
+
+/// A plugin that carves DNS responses and reports the names they resolved
+#[derive(Default)]
+pub struct DnsCacheScanner;
+
+impl DnsCacheScanner {
+    fn report(&self, addr: u64, question: &str, answer: &DnsAnswer, copies: usize) -> Finding {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "dns".to_string());
+        details.insert("rule".to_string(), "dns_response".to_string());
+        details.insert("domain".to_string(), answer.name.clone());
+        details.insert("record_type".to_string(), answer.record_type.to_string());
+        details.insert("answer".to_string(), answer.data.clone());
+        details.insert("ttl".to_string(), answer.ttl.to_string());
+        details.insert("query".to_string(), question.to_string());
+        details.insert("copies".to_string(), copies.to_string());
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("DNS {} {} -> {} (TTL {})", answer.record_type, answer.name, answer.data, answer.ttl),
+            confidence: 60,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for DnsCacheScanner {
+    fn name(&self) -> &'static str {
+        "dnscache"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Low
+    }
+
+    fn description(&self) -> &'static str {
+        "Carves DNS responses from memory, reporting the domains resolved with their addresses and TTLs"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        progress.set_message("Carving DNS responses");
+
+        // Each answer once, at its first copy, in the order found
+        let mut seen: HashMap<(String, DnsAnswer), (usize, usize)> = HashMap::new();
+        let mut order = Vec::new();
+        // Read around each chunk so responses straddling its ends are whole
+        for window in chunks(img, CHUNK_SIZE, MAX_MESSAGE).with_lookbehind(LOOKBEHIND).with_progress(progress) {
+            for (offset, response) in carve_responses(window.data, window.owned.start, window.owned.end) {
+                for answer in response.answers {
+                    let key = (response.question.clone(), answer);
+                    match seen.get_mut(&key) {
+                        Some((_, copies)) => *copies += 1,
+                        None => {
+                            seen.insert(key.clone(), (window.start + offset, 1));
+                            order.push(key);
+                        }
+                    }
+                }
+            }
+        }
+
+        let findings: Vec<Finding> = order.iter().map(|key| {
+            let (addr, copies) = seen[key];
+            self.report(addr as u64, &key.0, &key.1, copies)
+        }).collect();
+        progress.finish_with_message(format!("Found {} DNS answers", findings.len()));
+        findings
+    }
+}
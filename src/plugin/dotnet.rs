@@ -0,0 +1,118 @@
+//! .NET runtimes and assemblies in processes
+//!
+//! Reports each process that has the CLR loaded, and every assembly in its
+//! address space (see [`crate::dotnet`]): assemblies mapped from a file are
+//! context, while those found in private or pagefile-backed memory were
+//! loaded from bytes, the way in-memory loaders run their payloads. `rmf
+//! dotnet --output` writes the assemblies themselves to disk.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::dlllist::process_dlls;
+use crate::dotnet::{clr_runtime, format_guid, process_assemblies, Assembly, AssemblySource};
+use crate::kdbg::OsContext;
+use crate::paging::MemoryImage;
+use crate::processes::{Process, ProcessFinder, WindowsProcessFinder};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// A plugin that finds the CLR and .NET assemblies of each process
+#[derive(Default)]
+pub struct DotnetScanner;
+
+impl DotnetScanner {
+    fn report_runtime(&self, addr: u64, process: &Process, runtime: &str) -> Finding {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "dotnet".to_string());
+        details.insert("rule".to_string(), "clr_loaded".to_string());
+        details.insert("pid".to_string(), process.pid.to_string());
+        details.insert("process".to_string(), process.name.clone());
+        details.insert("runtime".to_string(), runtime.to_string());
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} runtime loaded in {} (PID {})", runtime, process.name, process.pid),
+            confidence: 10,
+            details,
+        }
+    }
+
+    fn report_assembly(&self, addr: u64, process: &Process, assembly: &Assembly) -> Finding {
+        let in_memory = assembly.source == AssemblySource::Memory;
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "dotnet".to_string());
+        details.insert("rule".to_string(), if in_memory { "in_memory_assembly" } else { "dotnet_assembly" }.to_string());
+        details.insert("pid".to_string(), process.pid.to_string());
+        details.insert("process".to_string(), process.name.clone());
+        details.insert("base".to_string(), format!("{:#x}", assembly.base));
+        details.insert("size".to_string(), format!("{:#x}", assembly.size));
+        details.insert("module".to_string(), assembly.name());
+        details.insert("source".to_string(), assembly.source.to_string());
+        details.insert("layout".to_string(), if assembly.mapped { "mapped" } else { "file" }.to_string());
+        details.insert("metadata_version".to_string(), assembly.clr.metadata_version.clone());
+        details.insert("clr_flags".to_string(), assembly.clr.flag_names().join(","));
+        if let Some(mvid) = &assembly.clr.mvid {
+            details.insert("mvid".to_string(), format_guid(mvid));
+        }
+        if let Some(file) = &assembly.file {
+            details.insert("file".to_string(), file.clone());
+        }
+        let what = if in_memory { "loaded from memory" } else { "mapped from file" };
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!(".NET assembly {} {} in {} (PID {}) at {:#x}", assembly.name(), what, process.name, process.pid, assembly.base),
+            confidence: if in_memory { 80 } else { 20 },
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for DotnetScanner {
+    fn name(&self) -> &'static str {
+        "dotnet"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Finds processes running the CLR and their .NET assemblies, flagging those loaded from memory rather than a file"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Locating processes");
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else {
+            progress.finish_with_message("No kernel found; dotnet needs the process list");
+            return findings;
+        };
+        let finder = WindowsProcessFinder::new().with_os_context(os);
+        let processes = finder.find_processes(img, &ProgressBar::hidden()).unwrap_or_default();
+
+        progress.set_message("Searching process memory for assemblies");
+        progress.set_length(processes.len() as u64);
+        progress.set_position(0);
+        for process in &processes {
+            progress.inc(1);
+            let Some(space) = process.address_space(img) else { continue };
+            let dlls = process_dlls(img, &finder, process).dlls;
+            if let Some(runtime) = clr_runtime(dlls.iter().map(|dll| dll.name.as_str())) {
+                findings.push(self.report_runtime(process.virtual_address, process, runtime));
+            }
+            for assembly in process_assemblies(img, &finder, process) {
+                let addr = space.virt_to_phys(assembly.base).unwrap_or(assembly.base);
+                findings.push(self.report_assembly(addr, process, &assembly));
+            }
+        }
+
+        let in_memory = findings.iter().filter(|f| f.details["rule"] == "in_memory_assembly").count();
+        progress.finish_with_message(format!("Found {} .NET findings, {} assemblies loaded from memory", findings.len(), in_memory));
+        findings
+    }
+}
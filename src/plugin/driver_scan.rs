@@ -0,0 +1,196 @@
+//! Driver object scan
+//!
+//! Every loaded driver has a DRIVER_OBJECT whose MajorFunction table says
+//! which routine handles each IRP type. Rootkits hook a driver by swapping
+//! entries of that table for their own code, which then runs outside the
+//! driver's image. The scanner finds DRIVER_OBJECT allocations by pool tag
+//! and checks each handler against the driver's own image and the kernel,
+//! which handles the IRP types a driver does not implement. Without a list
+//! of kernel modules the kernel's default handler is recognized as the
+//! address most tables share.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::kdbg::OsContext;
+use crate::modules::{list_kernel_modules, unicode_string_at, KernelModule};
+use crate::paging::MemoryImage;
+use crate::processes::scan_pool_tags;
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Pool tags for DRIVER_OBJECT, with and without the protected-allocation bit
+const DRIVER_POOL_TAGS: [&[u8]; 2] = [b"Driv", b"Dri\xf6"];
+const POOL_HEADER_SIZE: usize = 0x10;
+const OBJECT_HEADER_SIZE: usize = 0x30;
+const MAX_OPTIONAL_HEADERS: usize = 0x90;
+
+/// DRIVER_OBJECT offsets for x64
+const IO_TYPE_DRIVER: u16 = 4;
+const DRIVER_OBJECT_SIZE: usize = 0x150;
+const DRIVER_START: usize = 0x18;
+const DRIVER_SIZE: usize = 0x20;
+const DRIVER_NAME: usize = 0x38;
+const MAJOR_FUNCTION: usize = 0x70;
+
+const KERNEL_VA_START: u64 = 0xFFFF_8000_0000_0000;
+
+/// IRP_MJ_* names, in MajorFunction order
+const IRP_MAJOR_FUNCTIONS: [&str; 28] = [
+    "IRP_MJ_CREATE", "IRP_MJ_CREATE_NAMED_PIPE", "IRP_MJ_CLOSE", "IRP_MJ_READ", "IRP_MJ_WRITE",
+    "IRP_MJ_QUERY_INFORMATION", "IRP_MJ_SET_INFORMATION", "IRP_MJ_QUERY_EA", "IRP_MJ_SET_EA",
+    "IRP_MJ_FLUSH_BUFFERS", "IRP_MJ_QUERY_VOLUME_INFORMATION", "IRP_MJ_SET_VOLUME_INFORMATION",
+    "IRP_MJ_DIRECTORY_CONTROL", "IRP_MJ_FILE_SYSTEM_CONTROL", "IRP_MJ_DEVICE_CONTROL",
+    "IRP_MJ_INTERNAL_DEVICE_CONTROL", "IRP_MJ_SHUTDOWN", "IRP_MJ_LOCK_CONTROL", "IRP_MJ_CLEANUP",
+    "IRP_MJ_CREATE_MAILSLOT", "IRP_MJ_QUERY_SECURITY", "IRP_MJ_SET_SECURITY", "IRP_MJ_POWER",
+    "IRP_MJ_SYSTEM_CONTROL", "IRP_MJ_DEVICE_CHANGE", "IRP_MJ_QUERY_QUOTA", "IRP_MJ_SET_QUOTA",
+    "IRP_MJ_PNP",
+];
+
+/// Decoded DRIVER_OBJECT fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriverObject {
+    pub driver_start: u64,
+    pub driver_size: u64,
+    pub major_functions: [u64; 28],
+}
+
+impl DriverObject {
+    pub fn in_image(&self, va: u64) -> bool {
+        va >= self.driver_start && va - self.driver_start < self.driver_size
+    }
+}
+
+/// Validate and decode a DRIVER_OBJECT body
+pub fn parse_driver_object(body: &[u8]) -> Option<DriverObject> {
+    if body.len() < DRIVER_OBJECT_SIZE {
+        return None;
+    }
+    let u16_at = |off: usize| u16::from_le_bytes(body[off..off + 2].try_into().unwrap());
+    let u64_at = |off: usize| u64::from_le_bytes(body[off..off + 8].try_into().unwrap());
+    if u16_at(0) != IO_TYPE_DRIVER || u16_at(2) as usize != DRIVER_OBJECT_SIZE {
+        return None;
+    }
+
+    let driver = DriverObject {
+        driver_start: u64_at(DRIVER_START),
+        driver_size: u64_at(DRIVER_SIZE) & 0xFFFF_FFFF,
+        major_functions: std::array::from_fn(|i| u64_at(MAJOR_FUNCTION + i * 8)),
+    };
+    let kernel_or_null = |va: u64| va == 0 || va >= KERNEL_VA_START;
+    let valid = kernel_or_null(driver.driver_start)
+        && driver.major_functions.iter().all(|&f| kernel_or_null(f))
+        && driver.major_functions.iter().any(|&f| f != 0);
+    valid.then_some(driver)
+}
+
+/// A plugin that lists the IRP handlers of every driver object and flags
+/// handlers outside the driver's image
+#[derive(Default)]
+pub struct DriverScanner;
+
+impl DriverScanner {
+    /// Where a handler outside its driver's image points, or `None` when
+    /// the kernel handles the IRP type
+    fn foreign_target(&self, handler: u64, modules: &[KernelModule], default_handler: Option<u64>) -> Option<String> {
+        let owner = modules.iter().find(|m| handler >= m.base && handler - m.base < m.size);
+        match (owner, modules.first()) {
+            (Some(owner), Some(kernel)) if owner.base == kernel.base => None,
+            (Some(owner), _) => Some(owner.name.clone()),
+            (None, _) if Some(handler) == default_handler && modules.is_empty() => None,
+            (None, _) => Some("unknown".to_string()),
+        }
+    }
+
+    fn report(&self, addr: u64, name: Option<String>, driver: &DriverObject, hooks: &[(usize, String)], findings: &mut Vec<Finding>) {
+        let name = name.unwrap_or_else(|| "(unnamed)".to_string());
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "driver_object".to_string());
+        details.insert("rule".to_string(), if hooks.is_empty() { "driver_pool_tag" } else { "irp_handler_outside_image" }.to_string());
+        details.insert("driver".to_string(), name.clone());
+        details.insert("driver_start".to_string(), format!("0x{:X}", driver.driver_start));
+        details.insert("driver_size".to_string(), format!("0x{:X}", driver.driver_size));
+        let handlers: Vec<String> = driver.major_functions.iter().enumerate()
+            .filter(|(_, &f)| f != 0)
+            .map(|(i, f)| format!("{}=0x{:X}", IRP_MAJOR_FUNCTIONS[i], f))
+            .collect();
+        details.insert("handlers".to_string(), handlers.join(","));
+        if !hooks.is_empty() {
+            let hooked: Vec<String> = hooks.iter()
+                .map(|(i, target)| format!("{}=0x{:X} ({})", IRP_MAJOR_FUNCTIONS[*i], driver.major_functions[*i], target))
+                .collect();
+            details.insert("hooked".to_string(), hooked.join(","));
+        }
+
+        findings.push(Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: if hooks.is_empty() {
+                format!("Driver {} with {} IRP handlers", name, handlers.len())
+            } else {
+                let names: Vec<&str> = hooks.iter().map(|(i, _)| IRP_MAJOR_FUNCTIONS[*i]).collect();
+                format!("Driver {} has IRP handlers outside its image, likely hooked: {}", name, names.join(", "))
+            },
+            confidence: if hooks.is_empty() { 60 } else { 85 },
+            details,
+        });
+    }
+}
+
+impl MemoryPlugin for DriverScanner {
+    fn name(&self) -> &'static str {
+        "driverscan"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Finds driver objects, lists their IRP handlers and flags handlers outside the driver's image as hooks"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        progress.set_message("Scanning pool memory for driver objects");
+        let drivers: Vec<(u64, DriverObject)> = scan_pool_tags(img, progress, &DRIVER_POOL_TAGS).into_iter()
+            .filter_map(|(header, _)| {
+                let first = header as usize + POOL_HEADER_SIZE + OBJECT_HEADER_SIZE;
+                (0..=MAX_OPTIONAL_HEADERS).step_by(0x10).find_map(|extra| {
+                    let body = img.get_bytes(first + extra, DRIVER_OBJECT_SIZE)?;
+                    parse_driver_object(body).map(|driver| ((first + extra) as u64, driver))
+                })
+            })
+            .collect();
+
+        // The kernel module list tells the kernel's handlers apart from hooks;
+        // without it, the handler most tables share is the kernel's default
+        let modules = img.info.dtb
+            .and_then(|_| OsContext::find(img, &ProgressBar::hidden()))
+            .map(|os| list_kernel_modules(img, &os))
+            .unwrap_or_default();
+        let mut counts: HashMap<u64, usize> = HashMap::new();
+        for (_, driver) in &drivers {
+            for &handler in driver.major_functions.iter().filter(|&&f| f != 0 && !driver.in_image(f)) {
+                *counts.entry(handler).or_default() += 1;
+            }
+        }
+        let default_handler = counts.into_iter().max_by_key(|&(handler, count)| (count, handler)).map(|(handler, _)| handler);
+
+        let mut findings = Vec::new();
+        for (addr, driver) in &drivers {
+            let body = img.get_bytes(*addr as usize, DRIVER_OBJECT_SIZE).unwrap_or_default();
+            let name = unicode_string_at(img, body, DRIVER_NAME);
+            let hooks: Vec<(usize, String)> = driver.major_functions.iter().enumerate()
+                .filter(|(_, &f)| f != 0 && !driver.in_image(f))
+                .filter_map(|(i, &f)| self.foreign_target(f, &modules, default_handler).map(|target| (i, target)))
+                .collect();
+            self.report(*addr, name, driver, &hooks, &mut findings);
+        }
+
+        progress.finish_with_message(format!("Found {} driver objects", findings.len()));
+        findings
+    }
+}
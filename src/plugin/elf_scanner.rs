@@ -0,0 +1,245 @@
+//! ELF header scanner plugin
+//!
+//! Reports each `\x7fELF` header whose identification and program header
+//! table are consistent, with its class, byte order, architecture, type and
+//! entry point. The loadable segments give the size of the image once
+//! mapped; a shared object with an interpreter is a position independent
+//! executable rather than a library.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use crate::paging::MemoryImage;
+use crate::scan_util::{chunks, find_all, CHUNK_SIZE};
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+/// Bytes read past a chunk for the program headers of an ELF header in it
+const OVERLAP: usize = 0x4000;
+/// Program headers parsed, more than any real image has
+const MAX_SEGMENTS: usize = 128;
+/// Segment types
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+/// Longest interpreter path read
+const MAX_INTERP: usize = 0x100;
+const PAGE_SIZE: u64 = 0x1000;
+
+/// Architecture name of an ELF machine
+pub fn elf_machine_name(machine: u16) -> &'static str {
+    match machine {
+        0x03 => "x86",
+        0x08 => "MIPS",
+        0x14 => "PowerPC",
+        0x15 => "PowerPC64",
+        0x16 => "s390",
+        0x28 => "ARM",
+        0x3E => "x64",
+        0xB7 => "ARM64",
+        0xF3 => "RISC-V",
+        _ => "Unknown",
+    }
+}
+
+/// A program header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfSegment {
+    pub kind: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub file_size: u64,
+    pub mem_size: u64,
+}
+
+/// What the ELF header and program headers of an image say
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfHeader {
+    pub elf64: bool,
+    pub big_endian: bool,
+    pub elf_type: u16,
+    pub machine: u16,
+    pub entry: u64,
+    pub segments: Vec<ElfSegment>,
+    /// Path of the program interpreter, if its segment was read
+    pub interpreter: Option<String>,
+}
+
+impl ElfHeader {
+    /// Parse the ELF header at `data[0]` and its program headers; `None`
+    /// unless the identification is valid and the program headers were read
+    pub fn parse(data: &[u8]) -> Option<ElfHeader> {
+        if data.get(..4)? != ELF_MAGIC {
+            return None;
+        }
+        let elf64 = match data.get(4)? {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+        let big_endian = match data.get(5)? {
+            1 => false,
+            2 => true,
+            _ => return None,
+        };
+        let u16_at = |offset: usize| data.get(offset..offset + 2).map(|b| {
+            let b = [b[0], b[1]];
+            if big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) }
+        });
+        let u32_at = |offset: usize| data.get(offset..offset + 4).map(|b| {
+            let b = b.try_into().unwrap();
+            if big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) }
+        });
+        let u64_at = |offset: usize| data.get(offset..offset + 8).map(|b| {
+            let b = b.try_into().unwrap();
+            if big_endian { u64::from_be_bytes(b) } else { u64::from_le_bytes(b) }
+        });
+        // An address or offset: 8 bytes in ELF64, 4 in ELF32
+        let word = |offset: usize| if elf64 { u64_at(offset) } else { u32_at(offset).map(u64::from) };
+        if data[6] != 1 || u32_at(0x14)? != 1 {
+            return None;
+        }
+        let elf_type = u16_at(0x10)?;
+        if !(1..=4).contains(&elf_type) {
+            return None;
+        }
+        let (entry, phoff, phentsize_at, phnum_at, entry_size) = if elf64 {
+            (word(0x18)?, word(0x20)?, 0x36, 0x38, 0x38)
+        } else {
+            (word(0x18)?, word(0x1C)?, 0x2A, 0x2C, 0x20)
+        };
+        let count = u16_at(phnum_at)? as usize;
+        if (count != 0 && u16_at(phentsize_at)? as usize != entry_size) || count > MAX_SEGMENTS {
+            return None;
+        }
+        let mut segments = Vec::with_capacity(count);
+        for index in 0..count {
+            let at = (phoff as usize).checked_add(index * entry_size)?;
+            segments.push(if elf64 {
+                ElfSegment {
+                    kind: u32_at(at)?,
+                    flags: u32_at(at + 4)?,
+                    offset: u64_at(at + 8)?,
+                    vaddr: u64_at(at + 0x10)?,
+                    file_size: u64_at(at + 0x20)?,
+                    mem_size: u64_at(at + 0x28)?,
+                }
+            } else {
+                ElfSegment {
+                    kind: u32_at(at)?,
+                    offset: word(at + 4)?,
+                    vaddr: word(at + 8)?,
+                    file_size: word(at + 0x10)?,
+                    mem_size: word(at + 0x14)?,
+                    flags: u32_at(at + 0x18)?,
+                }
+            });
+        }
+        let interpreter = segments.iter().find(|s| s.kind == PT_INTERP).and_then(|s| {
+            let start = s.offset as usize;
+            let path = data.get(start..start.checked_add((s.file_size as usize).min(MAX_INTERP))?)?;
+            let path = &path[..path.iter().position(|&b| b == 0).unwrap_or(path.len())];
+            Some(String::from_utf8_lossy(path).to_string())
+        });
+        Some(ElfHeader { elf64, big_endian, elf_type, machine: u16_at(0x12)?, entry, segments, interpreter })
+    }
+
+    /// `rel`, `exec`, `dyn` or `core`; see also [`ElfHeader::is_pie`]
+    pub fn type_name(&self) -> &'static str {
+        match self.elf_type {
+            1 => "rel",
+            2 => "exec",
+            3 => "dyn",
+            _ => "core",
+        }
+    }
+
+    /// A shared object that names an interpreter is an executable
+    pub fn is_pie(&self) -> bool {
+        self.elf_type == 3 && self.segments.iter().any(|s| s.kind == PT_INTERP)
+    }
+
+    /// Bytes from the first loadable segment's page to the end of the last
+    pub fn mapped_size(&self) -> u64 {
+        let loads = self.segments.iter().filter(|s| s.kind == PT_LOAD && s.mem_size != 0);
+        let (start, end) = loads.fold((u64::MAX, 0), |(start, end), s| {
+            (start.min(s.vaddr & !(PAGE_SIZE - 1)), end.max(s.vaddr.saturating_add(s.mem_size)))
+        });
+        if end == 0 { 0 } else { end.saturating_sub(start).div_ceil(PAGE_SIZE) * PAGE_SIZE }
+    }
+}
+
+fn segment_flags(flags: u32) -> String {
+    [(4, 'r'), (2, 'w'), (1, 'x')].iter().map(|&(bit, c)| if flags & bit != 0 { c } else { '-' }).collect()
+}
+
+/// A plugin that scans for ELF headers in memory
+pub struct ElfScanner;
+
+impl ElfScanner {
+    fn report(&self, addr: u64, header: &ElfHeader) -> Finding {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "ELF_HEADER".to_string());
+        details.insert("rule".to_string(), "elf_magic".to_string());
+        details.insert("class".to_string(), if header.elf64 { "ELF64" } else { "ELF32" }.to_string());
+        details.insert("endianness".to_string(), if header.big_endian { "big" } else { "little" }.to_string());
+        details.insert("architecture".to_string(), elf_machine_name(header.machine).to_string());
+        details.insert("elf_type".to_string(), header.type_name().to_string());
+        details.insert("pie".to_string(), header.is_pie().to_string());
+        details.insert("entry_point".to_string(), format!("0x{:X}", header.entry));
+        details.insert("mapped_size".to_string(), format!("0x{:X}", header.mapped_size()));
+        details.insert("segment_count".to_string(), header.segments.len().to_string());
+        details.insert("dynamic".to_string(), header.segments.iter().any(|s| s.kind == PT_DYNAMIC).to_string());
+        let loads: Vec<String> = header.segments.iter().filter(|s| s.kind == PT_LOAD)
+            .map(|s| format!("{} vaddr=0x{:X} memsz=0x{:X} offset=0x{:X} filesz=0x{:X}", segment_flags(s.flags), s.vaddr, s.mem_size, s.offset, s.file_size))
+            .collect();
+        details.insert("load_segments".to_string(), loads.join("; "));
+        if let Some(interpreter) = &header.interpreter {
+            details.insert("interpreter".to_string(), interpreter.clone());
+        }
+        let kind = match header.elf_type {
+            3 if header.is_pie() => "PIE executable",
+            3 => "shared object",
+            2 => "executable",
+            1 => "relocatable object",
+            _ => "core file",
+        };
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("ELF image at 0x{:X} ({} {}, 0x{:X} bytes mapped, entry 0x{:X})",
+                addr, elf_machine_name(header.machine), kind, header.mapped_size(), header.entry),
+            // Program headers that parse and load something are hard to fake by chance
+            confidence: if header.mapped_size() != 0 { 90 } else { 60 },
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for ElfScanner {
+    fn name(&self) -> &'static str {
+        "elf_scanner"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Low
+    }
+
+    fn description(&self) -> &'static str {
+        "Scans memory for ELF headers and reports their class, architecture, type, entry point, interpreter and mapped size"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Scanning for ELF headers");
+        for window in chunks(img, CHUNK_SIZE, OVERLAP).with_progress(progress) {
+            for i in find_all(window.data, ELF_MAGIC).filter(|&i| window.owns(i)) {
+                if let Some(header) = ElfHeader::parse(&window.data[i..]) {
+                    findings.push(self.report(window.addr(i), &header));
+                }
+            }
+        }
+        progress.finish_with_message(format!("Found {} ELF headers", findings.len()));
+        findings
+    }
+}
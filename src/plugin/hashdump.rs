@@ -0,0 +1,293 @@
+//! Local account password hashes from the SAM
+//!
+//! The SAM hive stores each local account's LM and NT hashes encrypted
+//! twice: with a key derived from the hashed boot key, then with DES keys
+//! built from the account's RID. The hashed boot key is itself encrypted in
+//! the SAM's `Domains\Account` F value with the boot key, which the SYSTEM
+//! hive scatters over the class names of four `Lsa` subkeys. Both hives are
+//! rebuilt from memory, so a kernel DTB is needed. RC4 (before Windows 10
+//! 1607) and AES (after) encrypted hashes are both read.
+
+use aes::cipher::block_padding::NoPadding;
+use aes::cipher::{BlockDecrypt, BlockDecryptMut, KeyInit, KeyIvInit, StreamCipher};
+use aes::Aes128;
+use anyhow::{bail, Context, Result};
+use des::Des;
+use indicatif::ProgressBar;
+use md5::{Digest, Md5};
+use rc4::{consts::U16, Rc4};
+use std::collections::HashMap;
+
+use crate::paging::MemoryImage;
+use crate::registry::{current_control_set, find_hives, read_hive, Hive, HiveInfo};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// `Lsa` subkeys whose class names hold the scrambled boot key, in order
+const BOOT_KEY_PARTS: [&str; 4] = ["JD", "Skew1", "GBG", "Data"];
+const BOOT_KEY_PERMUTATION: [usize; 16] = [8, 5, 4, 2, 11, 9, 13, 3, 0, 6, 1, 12, 14, 10, 15, 7];
+
+/// Constants mixed into the RC4 keys of the SAM
+const QWERTY: &[u8] = b"!@#$%^&*()qwertyUIOPAzxcvbnmQQQQQQQQQQQQ)(*@&%\0";
+const DIGITS: &[u8] = b"0123456789012345678901234567890123456789\0";
+const NT_PASSWORD: &[u8] = b"NTPASSWORD\0";
+const LM_PASSWORD: &[u8] = b"LMPASSWORD\0";
+
+/// Domain F value: the encrypted hashed boot key
+const F_KEY: usize = 0x68;
+/// User V value: offset and length pairs of its fields, relative to the
+/// end of the header
+const V_HEADER_SIZE: usize = 0xCC;
+const V_NAME: usize = 0x0C;
+const V_LM_HASH: usize = 0x9C;
+const V_NT_HASH: usize = 0xA8;
+
+/// Hashes reported for accounts without a password of that kind
+pub const EMPTY_LM: &str = "aad3b435b51404eeaad3b435b51404ee";
+pub const EMPTY_NT: &str = "31d6cfe0d16ae931b73c59d7e0c089c0";
+
+/// A local account with its decrypted hashes
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamAccount {
+    pub rid: u32,
+    pub name: String,
+    /// `None` when the account has no hash of that kind stored
+    pub lm: Option<[u8; 16]>,
+    pub nt: Option<[u8; 16]>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl SamAccount {
+    pub fn lm_hex(&self) -> String {
+        self.lm.map_or(EMPTY_LM.to_string(), |h| hex(&h))
+    }
+
+    pub fn nt_hex(&self) -> String {
+        self.nt.map_or(EMPTY_NT.to_string(), |h| hex(&h))
+    }
+
+    /// `name:rid:lm:nt:::`, the pwdump format password crackers read
+    pub fn pwdump(&self) -> String {
+        format!("{}:{}:{}:{}:::", self.name, self.rid, self.lm_hex(), self.nt_hex())
+    }
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    Md5::digest(data).into()
+}
+
+/// RC4 with one of the SAM's MD5-derived keys
+fn rc4(key: &[u8; 16], data: &[u8]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    Rc4::<U16>::new(key.into()).apply_keystream(&mut out);
+    out
+}
+
+/// AES-128-CBC decryption of the whole blocks of `data`
+fn aes_cbc(key: &[u8; 16], iv: &[u8; 16], data: &[u8]) -> Vec<u8> {
+    let whole = &data[..data.len() / 16 * 16];
+    cbc::Decryptor::<Aes128>::new(key.into(), iv.into())
+        .decrypt_padded_vec_mut::<NoPadding>(whole)
+        .expect("whole blocks need no padding")
+}
+
+/// Spread 56 key bits over eight bytes, leaving the low (parity) bit of
+/// each clear, as the SAM's RID-derived keys are built
+fn des_key_from_7(bytes: &[u8; 7]) -> [u8; 8] {
+    let bits = bytes.iter().fold(0u64, |out, &b| (out << 8) | b as u64);
+    let mut key = [0u8; 8];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = (((bits >> (49 - 7 * i)) & 0x7F) as u8) << 1;
+    }
+    key
+}
+
+/// Single-block DES decryption with one of the RID-derived keys
+fn des_decrypt_block(key: &[u8; 8], block: &[u8]) -> [u8; 8] {
+    let mut block = des::cipher::Block::<Des>::clone_from_slice(block);
+    Des::new(key.into()).decrypt_block(&mut block);
+    block.into()
+}
+
+fn u32_at(data: &[u8], off: usize) -> Option<u32> {
+    data.get(off..off + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// The boot key, from the class names of the current control set's
+/// `Control\Lsa\{JD,Skew1,GBG,Data}` keys
+pub fn boot_key(system: &Hive) -> Result<[u8; 16]> {
+    let lsa = system.open_key(&format!("{}\\Control\\Lsa", current_control_set(system)?))?;
+    let mut scrambled = Vec::with_capacity(16);
+    for part in BOOT_KEY_PARTS {
+        let class = system.subkey(&lsa, part).and_then(|k| k.class)
+            .with_context(|| format!("Lsa\\{} has no class name", part))?;
+        if class.len() != 8 {
+            bail!("Lsa\\{} class name is not 4 hex bytes", part);
+        }
+        for at in (0..8).step_by(2) {
+            scrambled.push(u8::from_str_radix(&class[at..at + 2], 16).with_context(|| format!("Lsa\\{} class name is not hex", part))?);
+        }
+    }
+    Ok(BOOT_KEY_PERMUTATION.map(|i| scrambled[i]))
+}
+
+/// Decrypt the hashed boot key from the SAM's `Domains\Account` F value
+pub fn hashed_boot_key(sam: &Hive, boot_key: &[u8; 16]) -> Result<[u8; 16]> {
+    let account = sam.open_key("SAM\\Domains\\Account")?;
+    let f = sam.value(&account, "F").context("SAM hive has no Domains\\Account F value")?.data;
+    let key = f.get(F_KEY..).context("Domains\\Account F value is truncated")?;
+    match u32_at(key, 0) {
+        Some(1) => {
+            // SAM_KEY_DATA: salt, key and checksum, RC4 encrypted
+            let encrypted = key.get(0x14..0x34).context("SAM key data is truncated")?;
+            let rc4_key = md5(&[&key[0x4..0x14], QWERTY, boot_key, DIGITS].concat());
+            let decrypted = rc4(&rc4_key, encrypted);
+            let checksum = md5(&[&decrypted[..16], DIGITS, &decrypted[..16], QWERTY].concat());
+            if checksum[..] != decrypted[16..32] {
+                bail!("Boot key does not decrypt the SAM key (checksum mismatch)");
+            }
+            Ok(decrypted[..16].try_into().unwrap())
+        }
+        Some(2) => {
+            // SAM_KEY_DATA_AES: lengths, salt used as the IV, then the data
+            let length = u32_at(key, 0xC).context("SAM key data is truncated")? as usize;
+            let salt: [u8; 16] = key.get(0x10..0x20).context("SAM key data is truncated")?.try_into().unwrap();
+            let data = key.get(0x20..0x20 + length).filter(|d| d.len() >= 16).context("SAM key data is truncated")?;
+            let decrypted = aes_cbc(boot_key, &salt, data);
+            Ok(decrypted[..16].try_into().unwrap())
+        }
+        revision => bail!("Unsupported SAM key revision {:?}", revision),
+    }
+}
+
+/// The two DES keys of an account, built from its RID
+fn rid_keys(rid: u32) -> ([u8; 8], [u8; 8]) {
+    let r = rid.to_le_bytes();
+    (des_key_from_7(&[r[0], r[1], r[2], r[3], r[0], r[1], r[2]]), des_key_from_7(&[r[3], r[0], r[1], r[2], r[3], r[0], r[1]]))
+}
+
+/// Decrypt one SAM_HASH or SAM_HASH_AES entry of a V value
+fn decrypt_hash(entry: &[u8], rid: u32, hashed_boot_key: &[u8; 16], constant: &[u8]) -> Option<[u8; 16]> {
+    let obfuscated: Vec<u8> = match (entry.get(2..4)?, entry.len()) {
+        // Revision 1 with a hash: RC4
+        ([1, 0], 0x14) => {
+            let key = md5(&[&hashed_boot_key[..], &rid.to_le_bytes(), constant].concat());
+            rc4(&key, &entry[4..0x14])
+        }
+        // Revision 2: the salt is the IV, and the data follows it
+        ([2, 0], len) if len >= 0x28 => {
+            let salt: [u8; 16] = entry[0x8..0x18].try_into().unwrap();
+            aes_cbc(hashed_boot_key, &salt, &entry[0x18..])
+        }
+        _ => return None,
+    };
+    let (first, second) = rid_keys(rid);
+    let mut hash = [0u8; 16];
+    hash[..8].copy_from_slice(&des_decrypt_block(&first, obfuscated.get(..8)?));
+    hash[8..].copy_from_slice(&des_decrypt_block(&second, obfuscated.get(8..16)?));
+    Some(hash)
+}
+
+/// A field of a user V value, from its offset and length pair
+fn v_field(v: &[u8], pair: usize) -> Option<&[u8]> {
+    let offset = u32_at(v, pair)? as usize + V_HEADER_SIZE;
+    let length = u32_at(v, pair + 4)? as usize;
+    v.get(offset..offset + length)
+}
+
+/// Every account under `SAM\Domains\Account\Users` with a readable V value
+pub fn sam_accounts(sam: &Hive, hashed_boot_key: &[u8; 16]) -> Result<Vec<SamAccount>> {
+    let users = sam.open_key("SAM\\Domains\\Account\\Users")?;
+    Ok(sam.subkeys(&users).iter().filter_map(|user| {
+        // Account keys are named by RID in hex; `Names` maps names to them
+        let rid = u32::from_str_radix(&user.name, 16).ok()?;
+        let v = sam.value(user, "V")?.data;
+        let name = v_field(&v, V_NAME)?;
+        let name = String::from_utf16_lossy(&name.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect::<Vec<_>>());
+        Some(SamAccount {
+            rid,
+            name,
+            lm: v_field(&v, V_LM_HASH).and_then(|e| decrypt_hash(e, rid, hashed_boot_key, LM_PASSWORD)),
+            nt: v_field(&v, V_NT_HASH).and_then(|e| decrypt_hash(e, rid, hashed_boot_key, NT_PASSWORD)),
+        })
+    }).collect())
+}
+
+/// Rebuild and parse the loaded hives with the given name, e.g. `SYSTEM`
+pub fn hives_named(img: &MemoryImage, hives: &[HiveInfo], name: &str) -> Vec<(u64, Hive)> {
+    hives.iter()
+        .filter(|info| info.name().eq_ignore_ascii_case(name))
+        .filter_map(|info| Hive::parse(read_hive(img, info).0).ok().map(|hive| (info.addr, hive)))
+        .collect()
+}
+
+/// A plugin that decrypts the NT and LM hashes of local accounts from the
+/// SAM and SYSTEM hives
+#[derive(Default)]
+pub struct HashDumpScanner;
+
+impl HashDumpScanner {
+    fn report(&self, addr: u64, account: &SamAccount) -> Finding {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "credential".to_string());
+        details.insert("rule".to_string(), "sam_hash".to_string());
+        details.insert("user".to_string(), account.name.clone());
+        details.insert("rid".to_string(), account.rid.to_string());
+        details.insert("lm".to_string(), account.lm_hex());
+        details.insert("nt".to_string(), account.nt_hex());
+        details.insert("pwdump".to_string(), account.pwdump());
+        let blank = account.nt_hex() == EMPTY_NT;
+        if blank {
+            details.insert("blank_password".to_string(), "true".to_string());
+        }
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("NTLM hash of {} (RID {}): {}{}", account.name, account.rid, account.nt_hex(), if blank { " (blank password)" } else { "" }),
+            confidence: 95,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for HashDumpScanner {
+    fn name(&self) -> &'static str {
+        "hashdump"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Decrypts the NTLM hashes of local accounts from the SAM with the boot key from SYSTEM"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        progress.set_message("Scanning for registry hives");
+        let hives = find_hives(img, progress);
+        let systems = hives_named(img, &hives, "SYSTEM");
+        let sams = hives_named(img, &hives, "SAM");
+
+        // Copies of a hive may be stale; use the first pair that decrypts
+        let boot_keys: Vec<[u8; 16]> = systems.iter().filter_map(|(_, system)| boot_key(system).ok()).collect();
+        let accounts = sams.iter().find_map(|(addr, sam)| {
+            let hashed = boot_keys.iter().find_map(|key| hashed_boot_key(sam, key).ok())?;
+            sam_accounts(sam, &hashed).ok().map(|accounts| (*addr, accounts))
+        });
+        let Some((addr, accounts)) = accounts else {
+            progress.finish_with_message("No SAM hive decrypted with a SYSTEM boot key");
+            return Vec::new();
+        };
+
+        let findings: Vec<Finding> = accounts.iter().map(|account| self.report(addr, account)).collect();
+        progress.finish_with_message(format!("Found {} account hashes", findings.len()));
+        findings
+    }
+}
@@ -0,0 +1,236 @@
+//! Windows job object enumeration
+//!
+//! Job objects group processes under shared limits. Browsers and other
+//! sandboxes use them to confine renderers, and attackers use them to keep
+//! a set of tools alive together or kill it at once. The scanner finds EJOB
+//! allocations by pool tag, decodes their limits and UI restrictions and,
+//! when a kernel DTB is known, walks the member process list.
+
+use indicatif::ProgressBar;
+use std::collections::{HashMap, HashSet};
+
+use crate::paging::MemoryImage;
+use crate::scan_util::{chunks, CHUNK_SIZE};
+use crate::processes::WindowsProcessFinder;
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Pool tags for EJOB, with and without the protected-allocation bit
+const JOB_POOL_TAGS: [&[u8]; 2] = [b"Job ", b"Job\xa0"];
+const POOL_HEADER_SIZE: usize = 0x10;
+const OBJECT_HEADER_SIZE: usize = 0x30;
+const MAX_OPTIONAL_HEADERS: usize = 0x90;
+
+/// EJOB offsets for Windows 10 x64
+const EJOB_SIZE: usize = 0x120;
+const EJOB_PROCESS_LIST: usize = 0x28;
+const EJOB_TOTAL_PROCESSES: usize = 0xC4;
+const EJOB_ACTIVE_PROCESSES: usize = 0xC8;
+const EJOB_LIMIT_FLAGS: usize = 0xF0;
+const EJOB_ACTIVE_PROCESS_LIMIT: usize = 0xF4;
+const EJOB_UI_RESTRICTIONS: usize = 0x118;
+
+const MAX_MEMBERS: usize = 1024;
+const KERNEL_VA_START: u64 = 0xFFFF_8000_0000_0000;
+
+/// JOB_OBJECT_LIMIT_* flags
+const LIMIT_FLAGS: &[(u32, &str)] = &[
+    (0x0001, "WORKINGSET"),
+    (0x0002, "PROCESS_TIME"),
+    (0x0004, "JOB_TIME"),
+    (0x0008, "ACTIVE_PROCESS"),
+    (0x0010, "AFFINITY"),
+    (0x0020, "PRIORITY_CLASS"),
+    (0x0040, "PRESERVE_JOB_TIME"),
+    (0x0080, "SCHEDULING_CLASS"),
+    (0x0100, "PROCESS_MEMORY"),
+    (0x0200, "JOB_MEMORY"),
+    (0x0400, "DIE_ON_UNHANDLED_EXCEPTION"),
+    (0x0800, "BREAKAWAY_OK"),
+    (0x1000, "SILENT_BREAKAWAY_OK"),
+    (0x2000, "KILL_ON_JOB_CLOSE"),
+    (0x4000, "SUBSET_AFFINITY"),
+];
+
+/// JOB_OBJECT_UILIMIT_* flags
+const UI_RESTRICTIONS: &[(u32, &str)] = &[
+    (0x01, "HANDLES"),
+    (0x02, "READCLIPBOARD"),
+    (0x04, "WRITECLIPBOARD"),
+    (0x08, "SYSTEMPARAMETERS"),
+    (0x10, "DISPLAYSETTINGS"),
+    (0x20, "GLOBALATOMS"),
+    (0x40, "DESKTOP"),
+    (0x80, "EXITWINDOWS"),
+];
+
+/// Render a flag word as `A|B|C`
+fn flag_names(value: u32, names: &[(u32, &str)]) -> String {
+    let set: Vec<&str> = names.iter().filter(|(bit, _)| value & bit != 0).map(|(_, name)| *name).collect();
+    if set.is_empty() { "none".to_string() } else { set.join("|") }
+}
+
+fn is_list_pointer(ptr: u64) -> bool {
+    ptr >= KERNEL_VA_START && ptr & 0x7 == 0
+}
+
+/// Decoded EJOB fields
+#[derive(Debug, Default, PartialEq)]
+pub struct JobObject {
+    pub process_list: (u64, u64),
+    pub total_processes: u32,
+    pub active_processes: u32,
+    pub limit_flags: u32,
+    pub active_process_limit: u32,
+    pub ui_restrictions: u32,
+}
+
+/// Validate and decode an EJOB body
+pub fn parse_ejob(body: &[u8]) -> Option<JobObject> {
+    let u32_at = |off: usize| u32::from_le_bytes(body[off..off + 4].try_into().unwrap());
+    let u64_at = |off: usize| u64::from_le_bytes(body[off..off + 8].try_into().unwrap());
+    if body.len() < EJOB_SIZE {
+        return None;
+    }
+
+    let job = JobObject {
+        process_list: (u64_at(EJOB_PROCESS_LIST), u64_at(EJOB_PROCESS_LIST + 8)),
+        total_processes: u32_at(EJOB_TOTAL_PROCESSES),
+        active_processes: u32_at(EJOB_ACTIVE_PROCESSES),
+        limit_flags: u32_at(EJOB_LIMIT_FLAGS),
+        active_process_limit: u32_at(EJOB_ACTIVE_PROCESS_LIMIT),
+        ui_restrictions: u32_at(EJOB_UI_RESTRICTIONS),
+    };
+
+    let valid = is_list_pointer(job.process_list.0)
+        && is_list_pointer(job.process_list.1)
+        && job.active_processes <= job.total_processes
+        && job.total_processes < 0x10_0000
+        && job.limit_flags & !0x7FFF == 0
+        && job.ui_restrictions & !0xFF == 0;
+    valid.then_some(job)
+}
+
+/// A plugin that enumerates Windows job objects and their member processes
+#[derive(Default)]
+pub struct JobObjectScanner;
+
+impl JobObjectScanner {
+    /// Walk EJOB.ProcessListHead through EPROCESS.JobLinks
+    fn members(&self, img: &MemoryImage, job_va: u64, job: &JobObject) -> Vec<(u32, String)> {
+        let finder = WindowsProcessFinder::new();
+        let head = job_va + EJOB_PROCESS_LIST as u64;
+        let mut members = Vec::new();
+        let mut seen = HashSet::new();
+        let mut link = job.process_list.0;
+
+        while link != head && seen.insert(link) && members.len() < MAX_MEMBERS {
+            let eprocess_va = link - finder.job_links_offset() as u64;
+            let process = img.virt_to_phys(eprocess_va).and_then(|phys| finder.parse_eprocess(img, phys));
+            if let Some(process) = process {
+                members.push((process.pid, process.name));
+            }
+            link = match img.read_virt_u64(link) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        members
+    }
+
+    fn report(&self, img: &MemoryImage, addr: u64, job: JobObject, members: &[(u32, String)], findings: &mut Vec<Finding>) {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "job_object".to_string());
+        details.insert("rule".to_string(), "ejob_pool_tag".to_string());
+        details.insert("active_processes".to_string(), job.active_processes.to_string());
+        details.insert("total_processes".to_string(), job.total_processes.to_string());
+        details.insert("limits".to_string(), flag_names(job.limit_flags, LIMIT_FLAGS));
+        details.insert("ui_restrictions".to_string(), flag_names(job.ui_restrictions, UI_RESTRICTIONS));
+        if job.limit_flags & 0x8 != 0 {
+            details.insert("active_process_limit".to_string(), job.active_process_limit.to_string());
+        }
+        if !members.is_empty() {
+            let list: Vec<String> = members.iter().map(|(pid, name)| format!("{}:{}", pid, name)).collect();
+            details.insert("members".to_string(), list.join(","));
+        }
+        if img.info.dtb.is_none() {
+            details.insert("note".to_string(), "member list needs a kernel DTB".to_string());
+        }
+
+        // Sandboxes restrict the UI; a kill-on-close job without limits just groups processes
+        let sandboxed = job.ui_restrictions != 0;
+        let grouping = job.limit_flags & 0x2000 != 0 && job.limit_flags & !0x2800 == 0;
+        let kind = if sandboxed { "sandbox" } else if grouping { "grouping" } else { "limits" };
+        details.insert("kind".to_string(), kind.to_string());
+
+        let names: Vec<&str> = members.iter().map(|(_, name)| name.as_str()).collect();
+        findings.push(Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!(
+                "Job object ({}) with {} active processes{}",
+                kind,
+                job.active_processes,
+                if names.is_empty() { String::new() } else { format!(": {}", names.join(", ")) }
+            ),
+            confidence: if members.is_empty() { 65 } else { 85 },
+            details,
+        });
+    }
+}
+
+impl MemoryPlugin for JobObjectScanner {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Enumerates Windows job objects with their limits, UI restrictions and member processes"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        progress.set_message("Scanning pool memory for job objects");
+
+        // Aligned pool headers never straddle a chunk, so windows need no overlap
+        for window in chunks(img, CHUNK_SIZE, 0).with_progress(progress) {
+            let (chunk_start, chunk) = (window.start, window.data);
+
+            // Pool headers are 16-byte aligned with the tag at offset 4
+            for tag_off in (4..chunk.len().saturating_sub(4)).step_by(POOL_HEADER_SIZE) {
+                if !JOB_POOL_TAGS.contains(&&chunk[tag_off..tag_off + 4]) {
+                    continue;
+                }
+                let first = chunk_start + tag_off - 4 + POOL_HEADER_SIZE + OBJECT_HEADER_SIZE;
+                let found = (0..=MAX_OPTIONAL_HEADERS).step_by(0x10).find_map(|extra| {
+                    let body = img.get_bytes(first + extra, EJOB_SIZE)?;
+                    parse_ejob(body).map(|job| ((first + extra) as u64, job))
+                });
+                let (addr, job) = match found {
+                    Some(found) => found,
+                    None => continue,
+                };
+
+                // The job's own VA is the Blink target of its first member's JobLinks
+                let job_va = img.read_virt_u64(job.process_list.0 + 8)
+                    .map(|head| head - EJOB_PROCESS_LIST as u64);
+                let members = match job_va {
+                    Some(job_va) if img.info.dtb.is_some() => self.members(img, job_va, &job),
+                    _ => Vec::new(),
+                };
+                self.report(img, addr, job, &members, &mut findings);
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} job objects", findings.len()));
+        findings
+    }
+}
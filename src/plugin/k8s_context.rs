@@ -0,0 +1,337 @@
+//! Kubernetes node context extraction plugin
+//!
+//! Recovers service account tokens, pod identity labels written by the
+//! kubelet/CRI runtime and API server endpoints from node memory, so that
+//! processes and containers can be tied back to Kubernetes workloads.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use indicatif::ProgressBar;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::containers::parse_cgroup_path;
+use crate::scan_util::{chunks, find_all, path_around, CHUNK_SIZE};
+use crate::paging::MemoryImage;
+use super::registry::{MemoryPlugin, Finding};
+
+/// Base64url of `{"alg":"` — the start of every service account JWT header
+const JWT_PREFIX: &[u8] = b"eyJhbGciOi";
+
+/// CRI labels that identify the pod a container belongs to
+const POD_NAME_LABEL: &[u8] = b"io.kubernetes.pod.name";
+const POD_NAMESPACE_LABEL: &str = "io.kubernetes.pod.namespace";
+const POD_UID_LABEL: &str = "io.kubernetes.pod.uid";
+const CONTAINER_NAME_LABEL: &str = "io.kubernetes.container.name";
+
+/// How far around a pod name label to look for its sibling labels
+const LABEL_WINDOW: usize = 2048;
+
+/// Markers of the API server address as seen by pods, kubelet and kubectl
+const ENDPOINT_MARKERS: &[(&[u8], &str)] = &[
+    (b"KUBERNETES_SERVICE_HOST=", "in-cluster service host"),
+    (b"KUBERNETES_PORT_443_TCP=", "in-cluster service address"),
+    (b"server: https://", "kubeconfig server"),
+    (b"--apiserver-advertise-address=", "kubeadm advertise address"),
+];
+
+/// Kubelet per-pod state directory
+const KUBELET_PODS_DIR: &[u8] = b"/var/lib/kubelet/pods/";
+
+/// Pod cgroups carry the pod UID and container ID of every workload process
+const KUBEPODS_NEEDLE: &[u8] = b"kubepods";
+
+/// Bytes read around a chunk so tokens, label blocks and cgroup paths
+/// straddling its ends are whole
+const OVERLAP: usize = 0x2000;
+const LOOKBEHIND: usize = 0x1000;
+
+fn is_jwt_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'-' || c == b'_' || c == b'.'
+}
+
+/// Read a printable token starting at `start`, stopping at quotes/whitespace
+fn token_at(chunk: &[u8], start: usize, max_len: usize) -> Option<String> {
+    let rest = chunk.get(start..)?;
+    let len = rest
+        .iter()
+        .take(max_len)
+        .position(|&c| !c.is_ascii_graphic() || c == b'"' || c == b'\'' || c == b',')
+        .unwrap_or(rest.len().min(max_len));
+    if len == 0 {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&rest[..len]).into_owned())
+}
+
+/// Find the value of a CRI label (`"label":"value"` or `label=value`) in a window
+fn label_value(window: &[u8], label: &str) -> Option<String> {
+    let pos = find_all(window, label.as_bytes()).next()?;
+    let mut start = pos + label.len();
+    while start < window.len() && matches!(window[start], b'"' | b':' | b'=' | b' ') {
+        start += 1;
+    }
+    token_at(window, start, 253)
+}
+
+/// Identity claims recovered from a service account token
+#[derive(Debug, Default, PartialEq)]
+pub struct ServiceAccountClaims {
+    pub issuer: Option<String>,
+    pub namespace: Option<String>,
+    pub service_account: Option<String>,
+    pub pod: Option<String>,
+    pub expires: Option<i64>,
+}
+
+/// Decode a JWT payload and extract Kubernetes service account claims
+pub fn parse_service_account_token(token: &str) -> Option<ServiceAccountClaims> {
+    let mut parts = token.split('.');
+    let (_, payload, _) = (parts.next()?, parts.next()?, parts.next()?);
+    let payload: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+    let str_at = |ptr: &str| payload.pointer(ptr).and_then(Value::as_str).map(str::to_string);
+
+    let claims = if payload.get("kubernetes.io").is_some() {
+        // Bound (projected) token
+        ServiceAccountClaims {
+            issuer: str_at("/iss"),
+            namespace: str_at("/kubernetes.io/namespace"),
+            service_account: str_at("/kubernetes.io/serviceaccount/name"),
+            pod: str_at("/kubernetes.io/pod/name"),
+            expires: payload.get("exp").and_then(Value::as_i64),
+        }
+    } else if str_at("/iss").as_deref() == Some("kubernetes/serviceaccount") {
+        // Legacy secret-based token
+        ServiceAccountClaims {
+            issuer: str_at("/iss"),
+            namespace: str_at("/kubernetes.io~1serviceaccount~1namespace"),
+            service_account: str_at("/kubernetes.io~1serviceaccount~1service-account.name"),
+            pod: None,
+            expires: None,
+        }
+    } else {
+        return None;
+    };
+
+    Some(claims)
+}
+
+/// Label pod UID-only findings with the pod name and namespace seen elsewhere
+fn resolve_pod_names(findings: &mut [Finding]) {
+    let mut pods: HashMap<String, (String, Option<String>)> = HashMap::new();
+    for finding in findings.iter() {
+        if let (Some(uid), Some(name)) = (finding.details.get("pod_uid"), finding.details.get("pod_name")) {
+            pods.entry(uid.clone())
+                .or_insert_with(|| (name.clone(), finding.details.get("namespace").cloned()));
+        }
+    }
+
+    for finding in findings.iter_mut() {
+        if finding.details.contains_key("pod_name") {
+            continue;
+        }
+        let (name, namespace) = match finding.details.get("pod_uid").and_then(|uid| pods.get(uid)) {
+            Some(pod) => pod.clone(),
+            None => continue,
+        };
+        finding.desc = format!("{} ({}/{})", finding.desc, namespace.as_deref().unwrap_or("?"), name);
+        finding.details.insert("pod_name".to_string(), name);
+        if let Some(namespace) = namespace {
+            finding.details.insert("namespace".to_string(), namespace);
+        }
+    }
+}
+
+/// A plugin that recovers Kubernetes pod, token and API server context
+#[derive(Default)]
+pub struct KubernetesContextScanner;
+
+impl KubernetesContextScanner {
+    fn push(&self, findings: &mut Vec<Finding>, addr: usize, desc: String, confidence: u8, details: HashMap<String, String>) {
+        findings.push(Finding {
+            plugin: self.name().to_string(),
+            addr: addr as u64,
+            desc,
+            confidence,
+            details,
+        });
+    }
+
+    fn scan_tokens(&self, chunk: &[u8], base: usize, findings: &mut Vec<Finding>) {
+        for pos in find_all(chunk, JWT_PREFIX) {
+            if pos > 0 && is_jwt_char(chunk[pos - 1]) {
+                continue;
+            }
+            let len = chunk[pos..].iter().take(4096).take_while(|&&c| is_jwt_char(c)).count();
+            let token = String::from_utf8_lossy(&chunk[pos..pos + len]);
+            let claims = match parse_service_account_token(&token) {
+                Some(claims) => claims,
+                None => continue,
+            };
+
+            let mut details = HashMap::new();
+            details.insert("type".to_string(), "k8s_service_account_token".to_string());
+            details.insert("rule".to_string(), "service_account_jwt".to_string());
+            details.insert("risk".to_string(), "high".to_string());
+            let fields = [
+                ("issuer", &claims.issuer),
+                ("namespace", &claims.namespace),
+                ("service_account", &claims.service_account),
+                ("pod_name", &claims.pod),
+            ];
+            for (key, value) in fields {
+                if let Some(value) = value {
+                    details.insert(key.to_string(), value.clone());
+                }
+            }
+            if let Some(exp) = claims.expires {
+                details.insert("expires".to_string(), exp.to_string());
+            }
+
+            self.push(
+                findings,
+                base + pos,
+                format!(
+                    "Service account token for {}/{}",
+                    claims.namespace.as_deref().unwrap_or("?"),
+                    claims.service_account.as_deref().unwrap_or("?")
+                ),
+                95,
+                details,
+            );
+        }
+    }
+
+    fn scan_pod_labels(&self, chunk: &[u8], base: usize, findings: &mut Vec<Finding>) {
+        for pos in find_all(chunk, POD_NAME_LABEL) {
+            // Skip the namespace/uid labels that share this prefix
+            if chunk.get(pos + POD_NAME_LABEL.len()).is_some_and(|&c| c == b'.') {
+                continue;
+            }
+            let window = &chunk[pos.saturating_sub(LABEL_WINDOW)..(pos + LABEL_WINDOW).min(chunk.len())];
+            let local = &chunk[pos..(pos + LABEL_WINDOW).min(chunk.len())];
+            let pod_name = match label_value(local, "io.kubernetes.pod.name") {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let mut details = HashMap::new();
+            details.insert("type".to_string(), "k8s_pod".to_string());
+            details.insert("rule".to_string(), "cri_pod_labels".to_string());
+            details.insert("pod_name".to_string(), pod_name.clone());
+            let namespace = label_value(window, POD_NAMESPACE_LABEL);
+            if let Some(namespace) = &namespace {
+                details.insert("namespace".to_string(), namespace.clone());
+            }
+            if let Some(uid) = label_value(window, POD_UID_LABEL) {
+                details.insert("pod_uid".to_string(), uid);
+            }
+            if let Some(container) = label_value(window, CONTAINER_NAME_LABEL) {
+                details.insert("container_name".to_string(), container);
+            }
+
+            let confidence = if details.len() > 3 { 90 } else { 70 };
+            self.push(
+                findings,
+                base + pos,
+                format!("Pod {}/{}", namespace.as_deref().unwrap_or("?"), pod_name),
+                confidence,
+                details,
+            );
+        }
+
+        for pos in find_all(chunk, KUBELET_PODS_DIR) {
+            let uid = match token_at(chunk, pos + KUBELET_PODS_DIR.len(), 36) {
+                Some(uid) if uid.len() == 36 => uid,
+                _ => continue,
+            };
+            let mut details = HashMap::new();
+            details.insert("type".to_string(), "k8s_pod".to_string());
+            details.insert("rule".to_string(), "kubelet_pod_dir".to_string());
+            details.insert("pod_uid".to_string(), uid.clone());
+            self.push(findings, base + pos, format!("Kubelet pod directory {}", uid), 60, details);
+        }
+    }
+
+    fn scan_workloads(&self, chunk: &[u8], base: usize, findings: &mut Vec<Finding>) {
+        let mut last_end = 0;
+        for pos in find_all(chunk, KUBEPODS_NEEDLE) {
+            // A single path mentions kubepods several times
+            if pos < last_end {
+                continue;
+            }
+            let path = path_around(chunk, pos);
+            last_end = pos + path.len();
+            let container = match parse_cgroup_path(&String::from_utf8_lossy(path)) {
+                Some(container) => container,
+                None => continue,
+            };
+            let pod_uid = match &container.pod_uid {
+                Some(uid) => uid.clone(),
+                None => continue,
+            };
+
+            let mut details = HashMap::new();
+            details.insert("type".to_string(), "k8s_workload".to_string());
+            details.insert("rule".to_string(), "cgroup_pod_container".to_string());
+            details.insert("pod_uid".to_string(), pod_uid.clone());
+            details.insert("container_id".to_string(), container.id.clone());
+            details.insert("runtime".to_string(), container.runtime.to_string());
+            self.push(
+                findings,
+                base + pos,
+                format!("Container {} in pod {}", container.short_id(), pod_uid),
+                80,
+                details,
+            );
+        }
+    }
+
+    fn scan_endpoints(&self, chunk: &[u8], base: usize, findings: &mut Vec<Finding>) {
+        for &(marker, source) in ENDPOINT_MARKERS {
+            for pos in find_all(chunk, marker) {
+                let start = if marker.ends_with(b"https://") { pos + marker.len() - 8 } else { pos + marker.len() };
+                let endpoint = match token_at(chunk, start, 256) {
+                    Some(endpoint) => endpoint,
+                    None => continue,
+                };
+
+                let mut details = HashMap::new();
+                details.insert("type".to_string(), "k8s_api_endpoint".to_string());
+                details.insert("rule".to_string(), "api_server_endpoint".to_string());
+                details.insert("endpoint".to_string(), endpoint.clone());
+                details.insert("source".to_string(), source.to_string());
+                self.push(findings, base + pos, format!("API server endpoint {} ({})", endpoint, source), 75, details);
+            }
+        }
+    }
+}
+
+impl MemoryPlugin for KubernetesContextScanner {
+    fn name(&self) -> &'static str {
+        "k8s_context"
+    }
+
+    fn description(&self) -> &'static str {
+        "Recovers Kubernetes pod metadata, service account tokens and API server endpoints"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+
+        progress.set_message("Scanning for Kubernetes context");
+
+        for window in chunks(img, CHUNK_SIZE, OVERLAP).with_lookbehind(LOOKBEHIND).with_progress(progress) {
+            let found = findings.len();
+            self.scan_tokens(window.data, window.start, &mut findings);
+            self.scan_pod_labels(window.data, window.start, &mut findings);
+            self.scan_workloads(window.data, window.start, &mut findings);
+            self.scan_endpoints(window.data, window.start, &mut findings);
+            window.retain_owned(&mut findings, found);
+        }
+
+        resolve_pod_names(&mut findings);
+
+        progress.finish_with_message(format!("Found {} Kubernetes artifacts", findings.len()));
+        findings
+    }
+}
@@ -0,0 +1,307 @@
+//! LSA secrets and cached domain logons from the SECURITY hive
+//!
+//! lsass protects the secrets under `Policy\Secrets` with the LSA key, which
+//! the SECURITY hive keeps in `Policy\PolEKList` encrypted with the boot key
+//! from SYSTEM. Each secret (service account passwords, the auto-logon
+//! password, the machine account password, DPAPI system keys) is AES-256
+//! encrypted with a key hashed from the LSA key. The `NL$KM` secret in turn
+//! decrypts the `Cache\NL$n` records of the last domain logons, whose
+//! DCC2 (MS-Cache v2) hashes crackers take as `$DCC2$iterations#user#hash`.
+//! Windows Vista and later formats are read.
+//!
+//! Findings carry credentials, so values are redacted unless the plugin is
+//! run with `--reveal`.
+
+use aes::cipher::block_padding::NoPadding;
+use aes::cipher::{BlockDecrypt, BlockDecryptMut, KeyInit, KeyIvInit};
+use aes::{Aes128, Aes256};
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::paging::MemoryImage;
+use crate::registry::{find_hives, Hive};
+use super::hashdump::{boot_key, hives_named};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// LSA_SECRET: version, key ID, algorithm and flags before the data
+const SECRET_HEADER_SIZE: usize = 0x1C;
+/// The data starts with the salt hashed into the AES key
+const SECRET_SALT_SIZE: usize = 0x20;
+const SECRET_HASH_ROUNDS: usize = 1000;
+/// LSA_SECRET_BLOB: length, then 12 unknown bytes before the secret
+const BLOB_SECRET: usize = 0x10;
+/// The LSA key within the decrypted PolEKList secret
+const POLEKLIST_KEY: usize = 0x34;
+
+/// NL_RECORD offsets
+const NL_USER_LENGTH: usize = 0x0;
+const NL_DOMAIN_LENGTH: usize = 0x2;
+const NL_USER_ID: usize = 0x10;
+const NL_LAST_WRITE: usize = 0x20;
+const NL_DNS_DOMAIN_LENGTH: usize = 0x3C;
+const NL_IV: usize = 0x40;
+const NL_ENCRYPTED: usize = 0x60;
+/// Decrypted record: the hash, then the names from this offset
+const NL_NAMES: usize = 0x48;
+const DEFAULT_ITERATIONS: u32 = 10240;
+
+/// A decrypted secret from `Policy\Secrets`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LsaSecret {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn utf16(data: &[u8]) -> String {
+    let units: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units).trim_end_matches('\0').to_string()
+}
+
+impl LsaSecret {
+    /// What the secret is, from its name
+    pub fn kind(&self) -> &'static str {
+        match self.name.as_str() {
+            "$MACHINE.ACC" => "machine_account",
+            "DefaultPassword" => "autologon_password",
+            "DPAPI_SYSTEM" => "dpapi_system",
+            "NL$KM" => "cached_logon_key",
+            name if name.starts_with("_SC_") => "service_password",
+            _ => "secret",
+        }
+    }
+
+    /// The secret as text: passwords decoded, keys in hex
+    pub fn display(&self) -> String {
+        match self.kind() {
+            "autologon_password" | "service_password" => utf16(&self.data),
+            "dpapi_system" if self.data.len() >= 44 => format!("machine:{} user:{}", hex(&self.data[4..24]), hex(&self.data[24..44])),
+            _ => hex(&self.data),
+        }
+    }
+}
+
+/// A cached domain logon
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedLogon {
+    pub user: String,
+    pub domain: String,
+    pub dns_domain: String,
+    pub rid: u32,
+    /// FILETIME of the logon that cached the record
+    pub last_write: u64,
+    pub hash: [u8; 16],
+    pub iterations: u32,
+}
+
+impl CachedLogon {
+    /// `$DCC2$iterations#user#hash`, the format hashcat and John read
+    pub fn dcc2(&self) -> String {
+        format!("$DCC2${}#{}#{}", self.iterations, self.user, hex(&self.hash))
+    }
+}
+
+/// Decrypt an LSA_SECRET record with `key`; returns the secret
+fn decrypt_secret(key: &[u8], record: &[u8]) -> Option<Vec<u8>> {
+    let data = record.get(SECRET_HEADER_SIZE..)?;
+    let salt = data.get(..SECRET_SALT_SIZE)?;
+    let mut hasher = Sha256::new();
+    hasher.update(key);
+    for _ in 0..SECRET_HASH_ROUNDS {
+        hasher.update(salt);
+    }
+    let aes = Aes256::new(&hasher.finalize());
+    // Each block is decrypted on its own with a zero IV
+    let plain: Vec<u8> = data[SECRET_SALT_SIZE..].chunks_exact(16)
+        .flat_map(|block| {
+            let mut block = *aes::Block::from_slice(block);
+            aes.decrypt_block(&mut block);
+            block
+        })
+        .collect();
+    let length = u32::from_le_bytes(plain.get(..4)?.try_into().unwrap()) as usize;
+    plain.get(BLOB_SECRET..BLOB_SECRET + length).map(<[u8]>::to_vec)
+}
+
+/// The LSA key, decrypted from `Policy\PolEKList` with the boot key
+pub fn lsa_key(security: &Hive, boot_key: &[u8; 16]) -> Result<[u8; 32]> {
+    let policy = security.open_key("Policy\\PolEKList")?;
+    let record = security.value(&policy, "").context("PolEKList has no default value")?.data;
+    let secret = decrypt_secret(boot_key, &record).context("Boot key does not decrypt PolEKList")?;
+    let key = secret.get(POLEKLIST_KEY..POLEKLIST_KEY + 32).context("PolEKList secret is truncated")?;
+    Ok(key.try_into().unwrap())
+}
+
+/// The current value of every secret under `Policy\Secrets` that decrypts
+pub fn lsa_secrets(security: &Hive, lsa_key: &[u8; 32]) -> Result<Vec<LsaSecret>> {
+    let secrets = security.open_key("Policy\\Secrets")?;
+    Ok(security.subkeys(&secrets).into_iter().filter_map(|secret| {
+        let current = security.subkey(&secret, "CurrVal")?;
+        let record = security.value(&current, "")?.data;
+        let data = decrypt_secret(lsa_key, &record)?;
+        Some(LsaSecret { name: secret.name, data })
+    }).collect())
+}
+
+fn u16_at(data: &[u8], off: usize) -> usize {
+    data.get(off..off + 2).map_or(0, |b| u16::from_le_bytes([b[0], b[1]]) as usize)
+}
+
+/// Decode one `NL$n` record; `None` for unused slots
+fn parse_cached_logon(record: &[u8], nlkm: &[u8], iterations: u32) -> Option<CachedLogon> {
+    let iv: [u8; 16] = record.get(NL_IV..NL_IV + 16)?.try_into().unwrap();
+    if iv == [0; 16] {
+        return None;
+    }
+    let encrypted = record.get(NL_ENCRYPTED..)?;
+    let plain = cbc::Decryptor::<Aes128>::new_from_slices(nlkm.get(16..32)?, &iv).ok()?
+        .decrypt_padded_vec_mut::<NoPadding>(&encrypted[..encrypted.len() / 16 * 16])
+        .ok()?;
+    let pad = |length: usize| (length + 3) & !3;
+    let (user_length, domain_length) = (u16_at(record, NL_USER_LENGTH), u16_at(record, NL_DOMAIN_LENGTH));
+    let names = plain.get(NL_NAMES..)?;
+    let dns_at = pad(user_length) + pad(domain_length);
+    let dns_domain = names.get(dns_at..dns_at + u16_at(record, NL_DNS_DOMAIN_LENGTH)).map(utf16).unwrap_or_default();
+    Some(CachedLogon {
+        user: utf16(names.get(..user_length)?),
+        domain: utf16(names.get(pad(user_length)..pad(user_length) + domain_length)?),
+        dns_domain,
+        rid: u32::from_le_bytes(record.get(NL_USER_ID..NL_USER_ID + 4)?.try_into().unwrap()),
+        last_write: u64::from_le_bytes(record.get(NL_LAST_WRITE..NL_LAST_WRITE + 8)?.try_into().unwrap()),
+        hash: plain.get(..16)?.try_into().unwrap(),
+        iterations,
+    })
+}
+
+/// The cached logons under `Cache`, decrypted with the `NL$KM` secret
+pub fn cached_logons(security: &Hive, nlkm: &[u8]) -> Result<Vec<CachedLogon>> {
+    let cache = security.open_key("Cache")?;
+    let values = security.values(&cache);
+    // NL$IterationCount is the count itself above 10240, and in units of 1024 below
+    let iterations = values.iter().find(|v| v.name.eq_ignore_ascii_case("NL$IterationCount"))
+        .and_then(|v| v.data.get(..4).map(|b| u32::from_le_bytes(b.try_into().unwrap())))
+        .map_or(DEFAULT_ITERATIONS, |count| if count > DEFAULT_ITERATIONS { count & 0xFFFF_FC00 } else { count * 1024 });
+    Ok(values.iter()
+        .filter(|v| v.name.strip_prefix("NL$").is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())))
+        .filter_map(|v| parse_cached_logon(&v.data, nlkm, iterations))
+        .collect())
+}
+
+/// Shown in place of secrets unless `--reveal` is given
+fn redacted(value: &str) -> String {
+    format!("<redacted, {} chars>", value.chars().count())
+}
+
+/// A plugin that decrypts LSA secrets and cached domain logons
+#[derive(Default)]
+pub struct LsaDumpScanner {
+    /// Report secrets and hashes in clear
+    pub reveal: bool,
+}
+
+impl LsaDumpScanner {
+    pub fn new(reveal: bool) -> Self {
+        LsaDumpScanner { reveal }
+    }
+
+    fn shown(&self, value: &str) -> String {
+        if self.reveal { value.to_string() } else { redacted(value) }
+    }
+
+    fn report_secret(&self, addr: u64, secret: &LsaSecret) -> Finding {
+        let value = self.shown(&secret.display());
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "credential".to_string());
+        details.insert("rule".to_string(), "lsa_secret".to_string());
+        details.insert("secret".to_string(), secret.name.clone());
+        details.insert("kind".to_string(), secret.kind().to_string());
+        details.insert("value".to_string(), value.clone());
+        details.insert("redacted".to_string(), (!self.reveal).to_string());
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("LSA secret {} ({}): {}", secret.name, secret.kind(), value),
+            confidence: 90,
+            details,
+        }
+    }
+
+    fn report_logon(&self, addr: u64, logon: &CachedLogon) -> Finding {
+        let hash = self.shown(&logon.dcc2());
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "credential".to_string());
+        details.insert("rule".to_string(), "cached_domain_logon".to_string());
+        details.insert("user".to_string(), logon.user.clone());
+        details.insert("domain".to_string(), logon.domain.clone());
+        if !logon.dns_domain.is_empty() {
+            details.insert("dns_domain".to_string(), logon.dns_domain.clone());
+        }
+        details.insert("rid".to_string(), logon.rid.to_string());
+        details.insert("last_write".to_string(), logon.last_write.to_string());
+        details.insert("dcc2".to_string(), hash.clone());
+        details.insert("redacted".to_string(), (!self.reveal).to_string());
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("Cached domain logon {}\\{}: {}", logon.domain, logon.user, hash),
+            confidence: 95,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for LsaDumpScanner {
+    fn name(&self) -> &'static str {
+        "lsadump"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Decrypts LSA secrets and DCC2 cached domain logons from the SECURITY hive (redacted unless --reveal)"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![("reveal", self.reveal.to_string())]
+    }
+
+    fn revealing(&self) -> Option<Box<dyn MemoryPlugin>> {
+        Some(Box::new(LsaDumpScanner::new(true)))
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        progress.set_message("Scanning for registry hives");
+        let hives = find_hives(img, progress);
+        let boot_keys: Vec<[u8; 16]> = hives_named(img, &hives, "SYSTEM").iter().filter_map(|(_, system)| boot_key(system).ok()).collect();
+
+        // Copies of a hive may be stale; use the first that decrypts
+        let decrypted = hives_named(img, &hives, "SECURITY").into_iter().find_map(|(addr, security)| {
+            let key = boot_keys.iter().find_map(|boot| lsa_key(&security, boot).ok())?;
+            Some((addr, security, key))
+        });
+        let Some((addr, security, key)) = decrypted else {
+            progress.finish_with_message("No SECURITY hive decrypted with a SYSTEM boot key");
+            return Vec::new();
+        };
+
+        let secrets = lsa_secrets(&security, &key).unwrap_or_default();
+        let logons = secrets.iter().find(|s| s.name == "NL$KM")
+            .and_then(|nlkm| cached_logons(&security, &nlkm.data).ok())
+            .unwrap_or_default();
+        let mut findings: Vec<Finding> = secrets.iter().map(|secret| self.report_secret(addr, secret)).collect();
+        findings.extend(logons.iter().map(|logon| self.report_logon(addr, logon)));
+        progress.finish_with_message(format!("Found {} LSA secrets and {} cached logons", secrets.len(), logons.len()));
+        findings
+    }
+}
@@ -0,0 +1,300 @@
+//! Mach-O header scanner plugin
+//!
+//! Reports each 32 or 64-bit Mach-O header, in either byte order, whose
+//! load commands fit the size the header gives them, with its architecture,
+//! file type, segments and the size of the image once mapped; dylibs carry
+//! their install name and executables the entry point of `LC_MAIN`. Universal
+//! (fat) headers are reported with the architectures they hold, which tells
+//! them apart from Java class files sharing their magic.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use crate::paging::MemoryImage;
+use crate::scan_util::{chunks, find_all, CHUNK_SIZE};
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+/// Magic values as they appear in memory: 32 and 64-bit headers in little
+/// and big-endian order, and the big-endian universal header
+const MACHO_MAGICS: [[u8; 4]; 5] = [
+    [0xCE, 0xFA, 0xED, 0xFE],
+    [0xCF, 0xFA, 0xED, 0xFE],
+    [0xFE, 0xED, 0xFA, 0xCE],
+    [0xFE, 0xED, 0xFA, 0xCF],
+    [0xCA, 0xFE, 0xBA, 0xBE],
+];
+const FAT_MAGIC: u32 = 0xCAFE_BABE;
+/// Bytes read past a chunk for the load commands of a header in it
+const OVERLAP: usize = 0x8000;
+/// Load commands parsed, and architectures of a universal header
+const MAX_COMMANDS: u32 = 0x200;
+const MAX_FAT_ARCHS: u32 = 16;
+/// Load commands
+const LC_SEGMENT: u32 = 0x1;
+const LC_LOAD_DYLIB: u32 = 0xC;
+const LC_ID_DYLIB: u32 = 0xD;
+const LC_SEGMENT_64: u32 = 0x19;
+const LC_UUID: u32 = 0x1B;
+const LC_MAIN: u32 = 0x8000_0028;
+/// The unmapped guard segment below an executable
+const PAGEZERO: &str = "__PAGEZERO";
+
+/// Architecture name of a Mach-O CPU type
+pub fn macho_cpu_name(cpu: u32) -> &'static str {
+    match cpu {
+        7 => "x86",
+        0x0100_0007 => "x64",
+        12 => "ARM",
+        0x0100_000C => "ARM64",
+        0x0200_000C => "ARM64_32",
+        18 => "PowerPC",
+        0x0100_0012 => "PowerPC64",
+        _ => "Unknown",
+    }
+}
+
+/// Name of a Mach-O file type
+pub fn macho_type_name(file_type: u32) -> &'static str {
+    match file_type {
+        1 => "object",
+        2 => "execute",
+        3 => "fvmlib",
+        4 => "core",
+        5 => "preload",
+        6 => "dylib",
+        7 => "dylinker",
+        8 => "bundle",
+        9 => "dylib_stub",
+        10 => "dsym",
+        11 => "kext_bundle",
+        12 => "fileset",
+        _ => "unknown",
+    }
+}
+
+/// A segment load command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachSegment {
+    pub name: String,
+    pub vmaddr: u64,
+    pub vmsize: u64,
+    pub file_offset: u64,
+    pub file_size: u64,
+}
+
+/// What a Mach-O header and its load commands say
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachHeader {
+    pub macho64: bool,
+    pub big_endian: bool,
+    pub cpu: u32,
+    pub file_type: u32,
+    pub flags: u32,
+    pub command_count: u32,
+    pub segments: Vec<MachSegment>,
+    pub uuid: Option<[u8; 16]>,
+    /// File offset of the entry point from `LC_MAIN`
+    pub entry_offset: Option<u64>,
+    /// Install name of a dylib
+    pub install_name: Option<String>,
+    pub linked_dylibs: usize,
+}
+
+fn read_name(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(&bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())]).to_string()
+}
+
+impl MachHeader {
+    /// Parse the Mach-O header at `data[0]` and its load commands; `None`
+    /// unless the header is valid and every command was read
+    pub fn parse(data: &[u8]) -> Option<MachHeader> {
+        let (macho64, big_endian) = match data.get(..4)? {
+            [0xCE, 0xFA, 0xED, 0xFE] => (false, false),
+            [0xCF, 0xFA, 0xED, 0xFE] => (true, false),
+            [0xFE, 0xED, 0xFA, 0xCE] => (false, true),
+            [0xFE, 0xED, 0xFA, 0xCF] => (true, true),
+            _ => return None,
+        };
+        let u32_at = |offset: usize| data.get(offset..offset + 4).map(|b| {
+            let b = b.try_into().unwrap();
+            if big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) }
+        });
+        let u64_at = |offset: usize| data.get(offset..offset + 8).map(|b| {
+            let b = b.try_into().unwrap();
+            if big_endian { u64::from_be_bytes(b) } else { u64::from_le_bytes(b) }
+        });
+        let cpu = u32_at(4)?;
+        let file_type = u32_at(12)?;
+        let command_count = u32_at(16)?;
+        let commands_size = u32_at(20)? as usize;
+        if macho_cpu_name(cpu) == "Unknown" || macho_type_name(file_type) == "unknown" || command_count == 0 || command_count > MAX_COMMANDS {
+            return None;
+        }
+        let start: usize = if macho64 { 32 } else { 28 };
+        let end = start.checked_add(commands_size)?;
+        if end > data.len() {
+            return None;
+        }
+        let mut header = MachHeader {
+            macho64, big_endian, cpu, file_type,
+            flags: u32_at(24)?,
+            command_count,
+            segments: Vec::new(),
+            uuid: None,
+            entry_offset: None,
+            install_name: None,
+            linked_dylibs: 0,
+        };
+        let mut at = start;
+        for _ in 0..command_count {
+            let (cmd, size) = (u32_at(at)?, u32_at(at + 4)? as usize);
+            if size < 8 || size % 4 != 0 || at + size > end {
+                return None;
+            }
+            let command = &data[at..at + size];
+            match cmd {
+                LC_SEGMENT if size >= 56 => header.segments.push(MachSegment {
+                    name: read_name(&command[8..24]),
+                    vmaddr: u32_at(at + 24)? as u64,
+                    vmsize: u32_at(at + 28)? as u64,
+                    file_offset: u32_at(at + 32)? as u64,
+                    file_size: u32_at(at + 36)? as u64,
+                }),
+                LC_SEGMENT_64 if size >= 72 => header.segments.push(MachSegment {
+                    name: read_name(&command[8..24]),
+                    vmaddr: u64_at(at + 24)?,
+                    vmsize: u64_at(at + 32)?,
+                    file_offset: u64_at(at + 40)?,
+                    file_size: u64_at(at + 48)?,
+                }),
+                LC_UUID if size >= 24 => header.uuid = command[8..24].try_into().ok(),
+                LC_MAIN if size >= 16 => header.entry_offset = u64_at(at + 8),
+                LC_ID_DYLIB if size >= 24 => {
+                    header.install_name = command.get(u32_at(at + 8)? as usize..).map(read_name);
+                }
+                LC_LOAD_DYLIB => header.linked_dylibs += 1,
+                _ => {}
+            }
+            at += size;
+        }
+        Some(header)
+    }
+
+    /// Bytes from the lowest segment to the end of the highest, leaving out
+    /// `__PAGEZERO`
+    pub fn mapped_size(&self) -> u64 {
+        let segments = self.segments.iter().filter(|s| s.vmsize != 0 && s.name != PAGEZERO);
+        let (start, end) = segments.fold((u64::MAX, 0), |(start, end), s| (start.min(s.vmaddr), end.max(s.vmaddr.saturating_add(s.vmsize))));
+        end.saturating_sub(start)
+    }
+}
+
+/// The CPU types of a universal header, `None` unless it holds a plausible
+/// number of known architectures
+pub fn fat_architectures(data: &[u8]) -> Option<Vec<u32>> {
+    let u32_at = |offset: usize| data.get(offset..offset + 4).map(|b| u32::from_be_bytes(b.try_into().unwrap()));
+    if u32_at(0)? != FAT_MAGIC {
+        return None;
+    }
+    let count = u32_at(4)?;
+    if count == 0 || count > MAX_FAT_ARCHS {
+        return None;
+    }
+    // Each fat_arch is 20 bytes: cputype, cpusubtype, offset, size, align
+    let cpus: Vec<u32> = (0..count as usize).map(|i| u32_at(8 + i * 20)).collect::<Option<_>>()?;
+    cpus.iter().all(|&cpu| macho_cpu_name(cpu) != "Unknown").then_some(cpus)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A plugin that scans for Mach-O headers in memory
+pub struct MachOScanner;
+
+impl MachOScanner {
+    fn report(&self, addr: u64, header: &MachHeader) -> Finding {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "MACHO_HEADER".to_string());
+        details.insert("rule".to_string(), "macho_magic".to_string());
+        details.insert("format".to_string(), if header.macho64 { "Mach-O 64" } else { "Mach-O 32" }.to_string());
+        details.insert("endianness".to_string(), if header.big_endian { "big" } else { "little" }.to_string());
+        details.insert("architecture".to_string(), macho_cpu_name(header.cpu).to_string());
+        details.insert("file_type".to_string(), macho_type_name(header.file_type).to_string());
+        details.insert("flags".to_string(), format!("0x{:X}", header.flags));
+        details.insert("command_count".to_string(), header.command_count.to_string());
+        details.insert("mapped_size".to_string(), format!("0x{:X}", header.mapped_size()));
+        details.insert("linked_dylibs".to_string(), header.linked_dylibs.to_string());
+        let segments: Vec<String> = header.segments.iter()
+            .map(|s| format!("{} vmaddr=0x{:X} vmsize=0x{:X} fileoff=0x{:X} filesize=0x{:X}", s.name, s.vmaddr, s.vmsize, s.file_offset, s.file_size))
+            .collect();
+        details.insert("segments".to_string(), segments.join("; "));
+        if let Some(uuid) = &header.uuid {
+            details.insert("uuid".to_string(), hex(uuid));
+        }
+        if let Some(entry) = header.entry_offset {
+            details.insert("entry_offset".to_string(), format!("0x{:X}", entry));
+        }
+        if let Some(name) = &header.install_name {
+            details.insert("install_name".to_string(), name.clone());
+        }
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("Mach-O image at 0x{:X} ({} {}, 0x{:X} bytes mapped, {} load commands)",
+                addr, macho_cpu_name(header.cpu), macho_type_name(header.file_type), header.mapped_size(), header.command_count),
+            confidence: if header.segments.is_empty() { 60 } else { 90 },
+            details,
+        }
+    }
+
+    fn report_fat(&self, addr: u64, cpus: &[u32]) -> Finding {
+        let names: Vec<&str> = cpus.iter().map(|&cpu| macho_cpu_name(cpu)).collect();
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "MACHO_HEADER".to_string());
+        details.insert("rule".to_string(), "macho_fat_magic".to_string());
+        details.insert("format".to_string(), "universal".to_string());
+        details.insert("architectures".to_string(), names.join(","));
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("Universal Mach-O header at 0x{:X} ({})", addr, names.join(", ")),
+            confidence: 70,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for MachOScanner {
+    fn name(&self) -> &'static str {
+        "macho_scanner"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Low
+    }
+
+    fn description(&self) -> &'static str {
+        "Scans memory for Mach-O and universal headers and reports their architecture, file type, segments and mapped size"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Scanning for Mach-O headers");
+        for window in chunks(img, CHUNK_SIZE, OVERLAP).with_progress(progress) {
+            let start = findings.len();
+            for magic in &MACHO_MAGICS {
+                for i in find_all(window.data, magic).filter(|&i| window.owns(i)) {
+                    let data = &window.data[i..];
+                    if let Some(header) = MachHeader::parse(data) {
+                        findings.push(self.report(window.addr(i), &header));
+                    } else if let Some(cpus) = fat_architectures(data) {
+                        findings.push(self.report_fat(window.addr(i), &cpus));
+                    }
+                }
+            }
+            findings[start..].sort_by_key(|f| f.addr);
+        }
+        progress.finish_with_message(format!("Found {} Mach-O headers", findings.len()));
+        findings
+    }
+}
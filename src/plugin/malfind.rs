@@ -0,0 +1,194 @@
+//! Injected code in process memory
+//!
+//! Code injected into a process usually runs from memory the injector
+//! allocated itself: a private region, backed by no file, that is both
+//! writable and executable so the payload can be copied in and then run.
+//! The scanner walks each process's VAD tree for such regions and shows the
+//! start of each as a hexdump and a disassembly. A region holding a PE
+//! header (a reflectively loaded DLL) or high-entropy bytes (a packed or
+//! encrypted payload) scores higher; JIT compilers allocate the same kind
+//! of memory, so a region of ordinary code is only a lead. Regions whose
+//! first page is paged out or zero have nothing to show and are skipped.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::disasm::{disassemble, Instruction};
+use crate::kdbg::OsContext;
+use crate::paging::MemoryImage;
+use crate::processes::{Process, ProcessFinder, WindowsProcessFinder};
+use crate::vad::{VadKind, VadRegion};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Bytes of each region shown as a hexdump
+const HEXDUMP_BYTES: usize = 64;
+/// Instructions shown in the disassembly preview
+const PREVIEW_INSTRUCTIONS: usize = 8;
+const PAGE_SIZE: usize = 0x1000;
+/// Shannon entropy, in bits per byte, above which a page looks packed or
+/// encrypted, and below which it is mostly padding
+const HIGH_ENTROPY: f64 = 7.0;
+const LOW_ENTROPY: f64 = 2.0;
+
+/// The start of a private, writable and executable region
+#[derive(Debug, Clone, PartialEq)]
+pub struct InjectedRegion {
+    pub start: u64,
+    pub end: u64,
+    pub protection: String,
+    /// The region's first page
+    pub data: Vec<u8>,
+    /// Entropy of the first page in bits per byte, trailing zero padding
+    /// excluded so a short payload is not scored as empty
+    pub entropy: f64,
+    pub pe_header: bool,
+}
+
+impl InjectedRegion {
+    pub fn hexdump(&self) -> String {
+        hexdump(&self.data[..self.data.len().min(HEXDUMP_BYTES)], self.start)
+    }
+
+    pub fn disassembly(&self) -> Vec<Instruction> {
+        disassemble(&self.data, self.start, PREVIEW_INSTRUCTIONS)
+    }
+}
+
+/// Shannon entropy of `data` in bits per byte
+pub fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let total = data.len().max(1) as f64;
+    counts.iter().filter(|&&count| count > 0).map(|&count| {
+        let p = count as f64 / total;
+        -p * p.log2()
+    }).sum()
+}
+
+/// An MZ header whose e_lfanew points at a PE signature within `data`
+pub fn has_pe_header(data: &[u8]) -> bool {
+    if !data.starts_with(b"MZ") {
+        return false;
+    }
+    let Some(lfanew) = data.get(0x3C..0x40).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize) else { return false };
+    data.get(lfanew..lfanew + 4) == Some(b"PE\0\0")
+}
+
+/// Sixteen bytes a line: address, hex and printable ASCII
+pub fn hexdump(data: &[u8], address: u64) -> String {
+    data.chunks(16).enumerate().map(|(index, line)| {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = line.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+        format!("{:#x}  {:<47}  {}", address + index as u64 * 16, hex.join(" "), ascii)
+    }).collect::<Vec<_>>().join("\n")
+}
+
+/// The private, writable and executable regions of `process` with
+/// something in their first page
+pub fn injected_regions(img: &MemoryImage, finder: &WindowsProcessFinder, process: &Process) -> Vec<InjectedRegion> {
+    let Some(space) = process.address_space(img) else { return Vec::new() };
+    finder.vads(img, process).iter()
+        .filter(|vad| is_injectable(vad))
+        .filter_map(|vad| {
+            let data = space.read_virt(vad.start, PAGE_SIZE.min(vad.size() as usize))?;
+            if data.iter().all(|&b| b == 0) {
+                return None;
+            }
+            Some(InjectedRegion {
+                start: vad.start,
+                end: vad.end,
+                protection: vad.protection.to_string(),
+                entropy: entropy(&data[..data.iter().rposition(|&b| b != 0).map_or(0, |last| last + 1)]),
+                pe_header: has_pe_header(&data),
+                data,
+            })
+        })
+        .collect()
+}
+
+fn is_injectable(vad: &VadRegion) -> bool {
+    vad.kind == VadKind::Private && vad.file.is_none() && vad.protection.is_executable() && vad.protection.is_writable()
+}
+
+/// A plugin that finds code injected into private writable and executable memory
+#[derive(Default)]
+pub struct MalfindScanner;
+
+impl MalfindScanner {
+    fn report(&self, addr: u64, process: &Process, region: &InjectedRegion) -> Finding {
+        let (rule, what, confidence) = if region.pe_header {
+            ("injected_pe", "PE image", 90)
+        } else if region.entropy >= HIGH_ENTROPY {
+            ("injected_code", "high-entropy data", 70)
+        } else if region.entropy < LOW_ENTROPY {
+            ("injected_code", "sparse data", 30)
+        } else {
+            ("injected_code", "code", 50)
+        };
+        let disassembly: Vec<String> = region.disassembly().iter().map(|i| i.to_string()).collect();
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "injection".to_string());
+        details.insert("rule".to_string(), rule.to_string());
+        details.insert("pid".to_string(), process.pid.to_string());
+        details.insert("process".to_string(), process.name.clone());
+        details.insert("start".to_string(), format!("{:#x}", region.start));
+        details.insert("end".to_string(), format!("{:#x}", region.end));
+        details.insert("protection".to_string(), region.protection.clone());
+        details.insert("entropy".to_string(), format!("{:.2}", region.entropy));
+        details.insert("hexdump".to_string(), region.hexdump());
+        details.insert("disassembly".to_string(), disassembly.join("\n"));
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} in {} (PID {}) at {:#x}-{:#x}, private {}", what, process.name, process.pid, region.start, region.end, region.protection),
+            confidence,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for MalfindScanner {
+    fn name(&self) -> &'static str {
+        "malfind"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Finds private writable and executable process memory (injected code) with a hexdump and disassembly"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Locating processes");
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else {
+            progress.finish_with_message("No kernel found; malfind needs the process list");
+            return findings;
+        };
+        let finder = WindowsProcessFinder::new().with_os_context(os);
+        let processes = finder.find_processes(img, &ProgressBar::hidden()).unwrap_or_default();
+
+        progress.set_message("Walking process VADs");
+        progress.set_length(processes.len() as u64);
+        progress.set_position(0);
+        for process in &processes {
+            progress.inc(1);
+            let space = process.address_space(img);
+            for region in injected_regions(img, &finder, process) {
+                let addr = space.as_ref().and_then(|space| space.virt_to_phys(region.start)).unwrap_or(region.start);
+                findings.push(self.report(addr, process, &region));
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} suspicious regions", findings.len()));
+        findings
+    }
+}
@@ -5,24 +5,100 @@
 
 mod string_carve;
 mod pe_scanner;
+mod elf_scanner;
+mod macho_scanner;
+mod cloud_creds;
+mod ssh_keys;
+mod container_scan;
+mod k8s_context;
+mod privesc;
+mod peb_check;
+mod job_objects;
+mod driver_scan;
+mod svc_scan;
+mod mutant_scan;
+mod net_scan;
+mod dns_cache;
+mod arp_cache;
+mod hashdump;
+mod lsadump;
+mod shimcache;
+mod malfind;
+mod dotnet;
+mod powershell;
+mod browser;
+mod pii_scan;
+mod api_hooks;
+mod ssdt;
+mod callbacks;
+mod timers;
+mod bitlocker;
+mod pattern_scan;
+mod signature_scan;
+#[cfg(feature = "yara")]
+mod yara_scan;
 mod registry;
+mod schedule;
 
 pub use string_carve::StringCarvePlugin;
 pub use pe_scanner::PEScanner;
-pub use registry::{PluginRegistry, Finding, MemoryPlugin};
+pub use elf_scanner::{elf_machine_name, ElfHeader, ElfScanner, ElfSegment};
+pub use macho_scanner::{fat_architectures, macho_cpu_name, macho_type_name, MachHeader, MachOScanner, MachSegment};
+pub use cloud_creds::CloudCredentialScanner;
+pub use ssh_keys::SshKeyScanner;
+pub use container_scan::ContainerScanner;
+pub use k8s_context::KubernetesContextScanner;
+pub use privesc::PrivescScanner;
+pub use peb_check::{parse_environment, parse_peb, read_process_parameters, PebInfo, PebScanner, ProcessParameters};
+pub use job_objects::JobObjectScanner;
+pub use driver_scan::{parse_driver_object, DriverObject, DriverScanner};
+pub use svc_scan::{parse_service_record, ServiceRecord, ServiceScanner};
+pub use mutant_scan::{parse_mutant, Mutant, MutantScanner};
+pub use net_scan::{format_endpoint, parse_endpoint, Endpoint, EndpointKind, NetLayout, NetworkScanner, NET_LAYOUTS};
+pub use dns_cache::{parse_response, DnsAnswer, DnsCacheScanner, DnsResponse};
+pub use arp_cache::{format_mac, parse_neighbor, ArpCacheScanner, Neighbor};
+#[cfg(feature = "yara")]
+pub use yara_scan::YaraScanner;
+pub use signature_scan::SignatureScanner;
+pub use pattern_scan::{narrow_utf16, printable, signature_regex, PatternMatch, PatternScanner, TextEncoding, MAX_PATTERN_MATCHES};
+pub use bitlocker::{find_key_schedules, key_schedule, pool_keys, BitlockerKey, BitlockerScanner, FveCipher};
+pub use timers::{find_timer_lists, prcb_timers, queued_dpcs, read_dpc, wait_keys, Dpc, KernelTimer, TimerScanner, WaitKeys};
+pub use callbacks::{has_embedded_signature, kernel_callbacks, lea_targets, notify_routines, rip_operands, CallbackScanner, KernelCallback};
+pub use ssdt::{find_service_descriptors, gdt_call_gates, parse_idt, processor_blocks, processor_tables, read_service_table, ProcessorTables, ServiceTable, SsdtScanner};
+pub use api_hooks::{find_trampoline, process_hooks, process_iat_hooks, ApiHookScanner, InlineHook};
+pub use dotnet::DotnetScanner;
+pub use pii_scan::{card_network, iban_valid, luhn_valid, mask_pii, PiiKind, PiiMatch, PiiScanner};
+pub use browser::{browser_name, ArtifactKind, BrowserArtifact, BrowserScanner};
+pub use powershell::{script_indicators, utf16_runs, PowerShellKind, PowerShellScanner, PowerShellText};
+pub use malfind::{entropy, has_pe_header, hexdump, injected_regions, InjectedRegion, MalfindScanner};
+pub use shimcache::{amcache_entries, execution_timeline, parse_shimcache, shimcache_entries, ExecutionEntry, ShimcacheScanner};
+pub use lsadump::{cached_logons, lsa_key, lsa_secrets, CachedLogon, LsaDumpScanner, LsaSecret};
+pub use hashdump::{boot_key, hashed_boot_key, hives_named, sam_accounts, HashDumpScanner, SamAccount, EMPTY_LM, EMPTY_NT};
+pub use registry::{PluginRegistry, Finding, FindingRecord, ExportFormat, export_findings, MemoryPlugin, PluginNeeds, Priority, scan_parameters, scan_with_provenance, sort_findings};
+pub use schedule::{run_scheduled, schedule, total_passes, PluginRun};
 
 // Re-export registry
 pub use registry::get_plugin_registry;
 
-use anyhow::{Result, Context};
+use anyhow::{bail, Result, Context};
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle, MultiProgress};
+use indicatif::{ProgressBar, ProgressStyle};
+#[cfg(not(target_arch = "wasm32"))]
 use pager::Pager;
-use prettytable::{Table, row, format};
-use std::path::PathBuf;
-use serde_json;
-use csv::Writer;
+use prettytable::{Table, Row, Cell, row, format};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use crate::allowlist::Allowlist;
+use crate::case::Case;
+use crate::containers::ContainerScope;
+use crate::freed::FreedMemory;
+use crate::hits::HitMap;
+use crate::limits::ResourceLimits;
 use crate::loader::load_memory_image;
+use crate::processes::linux_processes;
 
 /// Initialize built-in plugins and register them in the global registry
 pub fn init_plugins() {
@@ -31,15 +107,98 @@ pub fn init_plugins() {
 
     registry.register(Box::new(StringCarvePlugin::default()));
     registry.register(Box::new(PEScanner));
+    registry.register(Box::new(ElfScanner));
+    registry.register(Box::new(MachOScanner));
+    registry.register(Box::new(CloudCredentialScanner));
+    registry.register(Box::new(SshKeyScanner::default()));
+    registry.register(Box::new(ContainerScanner));
+    registry.register(Box::new(KubernetesContextScanner));
+    registry.register(Box::new(PrivescScanner));
+    registry.register(Box::new(PebScanner));
+    registry.register(Box::new(JobObjectScanner));
+    registry.register(Box::new(DriverScanner));
+    registry.register(Box::new(ServiceScanner));
+    registry.register(Box::new(MutantScanner));
+    registry.register(Box::new(NetworkScanner));
+    registry.register(Box::new(DnsCacheScanner));
+    registry.register(Box::new(ArpCacheScanner));
+    registry.register(Box::new(HashDumpScanner));
+    registry.register(Box::new(LsaDumpScanner::default()));
+    registry.register(Box::new(ShimcacheScanner));
+    registry.register(Box::new(MalfindScanner));
+    registry.register(Box::new(DotnetScanner));
+    registry.register(Box::new(PowerShellScanner::default()));
+    registry.register(Box::new(BrowserScanner::default()));
+    registry.register(Box::new(PiiScanner::default()));
+    registry.register(Box::new(ApiHookScanner));
+    registry.register(Box::new(SsdtScanner));
+    registry.register(Box::new(CallbackScanner));
+    registry.register(Box::new(TimerScanner));
+    registry.register(Box::new(BitlockerScanner::default()));
+    #[cfg(feature = "yara")]
+    registry.register(Box::new(YaraScanner::default()));
+    registry.register(Box::new(SignatureScanner::default()));
+}
+
+/// How `run_plugin` filters, annotates and exports findings
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Export findings to this file: JSON for `.json`, JSON lines for
+    /// `.jsonl`, a results database for `.db`, otherwise CSV
+    pub export: Option<PathBuf>,
+    /// Only report findings belonging to this container
+    pub container: Option<String>,
+    /// Linux profile used to attribute processes to `container`, and
+    /// ssh_keys findings to Linux ssh-agent/sshd tasks
+    pub profile: Option<PathBuf>,
+    /// Keep findings in freed pool blocks and transition pages
+    pub include_freed: bool,
+    /// Known-benign findings to suppress
+    pub allowlist: Allowlist,
+    /// List the suppressed findings
+    pub show_suppressed: bool,
+    /// Case directory to record findings in and read triage states from
+    pub case: Option<PathBuf>,
+    /// Write the blocks holding findings to this hit map instead of listing them
+    pub hits: Option<PathBuf>,
+    /// Report the secrets the plugin redacts by default
+    pub reveal: bool,
+    /// YARA rule file for the yara plugin
+    pub rules: Option<PathBuf>,
+    /// Also scan process memory with the YARA rules
+    pub processes: bool,
+    /// Signature file for the signature_scan plugin
+    pub signatures: Option<PathBuf>,
+    /// Plugin settings such as `min_string_len`, see `MemoryPlugin::configure`
+    pub settings: HashMap<String, String>,
+}
+
+/// Parse a `key=value` plugin setting
+pub fn parse_setting(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.trim().to_string())),
+        _ => bail!("'{}' is not a key=value setting", s),
+    }
+}
+
+/// The plugin `name` scanning with the YARA rules at `path`
+#[cfg(feature = "yara")]
+fn rules_plugin(name: &str, path: &Path, processes: bool) -> Result<Box<dyn MemoryPlugin>> {
+    if name != "yara" {
+        bail!("Plugin '{}' takes no rules; --rules applies to the yara plugin", name);
+    }
+    Ok(Box::new(YaraScanner::load(path)?.with_processes(processes)))
+}
+
+#[cfg(not(feature = "yara"))]
+fn rules_plugin(_name: &str, path: &Path, _processes: bool) -> Result<Box<dyn MemoryPlugin>> {
+    bail!("Cannot load {}: rmf was built without the `yara` feature", path.display())
 }
 
 /// Run a plugin by name on the provided memory dump
-pub fn run_plugin(
-    dump_path: PathBuf,
-    plugin_name: String,
-    csv_output: Option<PathBuf>,
-) -> Result<()> {
-    println!("{} {} {} {}",
+pub fn run_plugin(dump_path: PathBuf, plugin_name: String, options: RunOptions) -> Result<()> {
+    let RunOptions { export, container, profile, include_freed, allowlist, show_suppressed, case, hits, reveal, rules, processes, signatures, settings } = options;
+    crate::status!("{} {} {} {}",
         "Running plugin".bright_green(),
         plugin_name.bright_yellow().bold(),
         "on".bright_green(),
@@ -59,8 +218,41 @@ pub fn run_plugin(
                 .collect::<Vec<_>>()
                 .join(", ")
         ))?;
+    // Scanners built from --rules, --signatures or --profile replace the
+    // registered one; --reveal and --set then apply to whichever runs
+    let overlay = match &rules {
+        Some(path) => Some(rules_plugin(&plugin_name, path, processes)?),
+        None if processes => bail!("--processes only applies with --rules"),
+        None => None,
+    };
+    let overlay = match &signatures {
+        Some(path) if plugin_name != "signature_scan" => {
+            bail!("Plugin '{}' takes no signatures; --signatures applies to the signature_scan plugin ({})", plugin_name, path.display())
+        }
+        Some(path) => Some(Box::new(SignatureScanner::load(path)?) as Box<dyn MemoryPlugin>),
+        None => overlay,
+    };
+    let overlay = match &profile {
+        Some(path) if plugin_name == "ssh_keys" => Some(Box::new(SshKeyScanner::with_profile(path.clone())) as Box<dyn MemoryPlugin>),
+        _ => overlay,
+    };
+    let plugin = overlay.as_deref().unwrap_or(plugin);
+    let revealing = if reveal {
+        Some(plugin.revealing().with_context(|| format!("Plugin '{}' redacts nothing; --reveal does not apply", plugin_name))?)
+    } else {
+        None
+    };
+    let plugin = revealing.as_deref().unwrap_or(plugin);
+    let configured = if settings.is_empty() { None } else { Some(plugin.configure(&settings)?) };
+    let plugin = configured.as_deref().unwrap_or(plugin);
+    run_scanner(dump_path, plugin, RunOptions { export, container, profile, include_freed, allowlist, show_suppressed, case, hits, ..Default::default() })
+}
 
-    println!("{}: {} (v{})",
+/// Run `plugin` on the provided memory dump and report its findings as
+/// `run_plugin` does, for scanners configured on the command line
+pub fn run_scanner(dump_path: PathBuf, plugin: &dyn MemoryPlugin, options: RunOptions) -> Result<()> {
+    let RunOptions { export, container, profile, include_freed, allowlist, show_suppressed, case, hits, .. } = options;
+    crate::status!("{}: {} (v{})",
         "Plugin description".bright_blue(),
         plugin.description(),
         plugin.get_version().bright_blue());
@@ -68,66 +260,305 @@ pub fn run_plugin(
     // Load memory image
     let memory_image = load_memory_image(&dump_path)?;
 
-    // Set up progress bars
-    let multi_progress = MultiProgress::new();
-    let scan_progress = multi_progress.add(ProgressBar::new(100));
+    // Set up the progress bar
+    let scan_progress = crate::progress::attach(ProgressBar::new(100));
     scan_progress.set_style(ProgressStyle::with_template(
         "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} {msg}"
     )?.progress_chars("#>-"));
 
     // Run the plugin
-    println!("{}", "Starting scan...".bright_green());
-    let findings = plugin.scan(&memory_image, &scan_progress);
+    crate::status!("{}", "Starting scan...".bright_green());
+    let mut findings = scan_with_provenance(plugin, &memory_image, &scan_progress);
+
+    // Keep findings in freed memory only when asked to, and say where they came from
+    let freed = FreedMemory::scan(&memory_image, &ProgressBar::hidden());
+    let before = findings.len();
+    freed.apply(&mut findings, include_freed);
+    if include_freed {
+        let recovered = findings.iter().filter(|f| f.details.contains_key("provenance")).count();
+        crate::status!("{} {} findings recovered from freed memory",
+            "Included".bright_blue(),
+            recovered.to_string().bright_yellow()
+        );
+    } else if findings.len() < before {
+        crate::status!("{} {} findings in freed memory (use --include-freed to keep them)",
+            "Skipped".bright_blue(),
+            (before - findings.len()).to_string().bright_yellow()
+        );
+    }
 
+    // Report user-mode dump findings at their process virtual addresses
+    if let Some(user) = &memory_image.info.user {
+        let mut module_hashes: HashMap<u64, Option<String>> = HashMap::new();
+        for finding in &mut findings {
+            if let Some(va) = user.offset_to_va(finding.addr) {
+                finding.details.insert("va".to_string(), format!("0x{:X}", va));
+                if let Some(module) = user.module_at(va) {
+                    finding.details.insert("module".to_string(), module.name.clone());
+                    // Hash of the captured module image, for allowlisting by module
+                    let hash = module_hashes.entry(module.base).or_insert_with(|| {
+                        memory_image.read_virt(module.base, module.size as usize)
+                            .map(|image| Sha256::digest(image).iter().map(|b| format!("{:02x}", b)).collect())
+                    });
+                    if let Some(hash) = hash {
+                        finding.details.insert("module_sha256".to_string(), hash.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    // Drop known-benign findings listed in the global and case allowlists
+    let suppressed = allowlist.apply(&mut findings);
+    if !suppressed.is_empty() {
+        crate::status!("{} {} known-benign findings (allowlist)",
+            "Suppressed".bright_blue(),
+            suppressed.len().to_string().bright_yellow()
+        );
+        if show_suppressed {
+            for finding in &suppressed {
+                crate::status!("  {} 0x{:08X} {} [{}]", finding.id(), finding.addr, finding.desc, finding.details["suppressed_by"]);
+            }
+        }
+    }
+
+    // Restrict findings to a single container when requested
+    if let Some(container_id) = &container {
+        // Processes are attributed to containers from their cgroups
+        let processes = match &profile {
+            Some(path) => linux_processes(&memory_image, path, &ProgressBar::hidden())?,
+            None => {
+                crate::status!("{} without a Linux profile (--profile) only findings naming the container are kept", "Note:".bright_yellow());
+                Vec::new()
+            }
+        };
+        let scope = ContainerScope::new(container_id, &processes);
+        let before = findings.len();
+        findings.retain(|f| scope.contains(f));
+        crate::status!("{} {} ({} processes, {} of {} findings in scope)",
+            "Scoped to container".bright_blue(),
+            container_id.bright_yellow(),
+            scope.pids().len(),
+            findings.len(),
+            before
+        );
+    }
+
+    // A locating pass only keeps where the findings are
+    if let Some(path) = &hits {
+        let map = HitMap::from_findings(&findings, crate::hits::DEFAULT_GRANULARITY);
+        map.save(path)?;
+        crate::status!("{} {} findings as {} hit ranges ({} bytes) to {}",
+            "Mapped".bright_green(),
+            findings.len().to_string().bright_yellow(),
+            map.ranges().len(),
+            map.covered(),
+            path.display().to_string().bright_cyan()
+        );
+        return Ok(());
+    }
+
+    // Record the findings in the case and pick up their triage states
+    let triage = match &case {
+        Some(dir) => {
+            let case = Case::open(dir)?;
+            case.record_dump(&dump_path, &memory_image.info.acquisition)?;
+            case.record_findings(&dump_path, &findings)?;
+            crate::status!("{} {} findings in case {}",
+                "Recorded".bright_blue(),
+                findings.len().to_string().bright_yellow(),
+                dir.display().to_string().bright_cyan()
+            );
+            Some(case.triage_status()?)
+        }
+        None => None,
+    };
+    let triage_state = |finding: &Finding| -> String {
+        let status = triage.as_ref().and_then(|t| t.get(&finding.id()));
+        match status {
+            Some(status) if status.disputed() => format!("{} (disputed)", status.state()),
+            Some(status) => status.state().to_string(),
+            None => "untriaged".to_string(),
+        }
+    };
+
+    let records: Vec<FindingRecord> = findings.iter().map(|f| FindingRecord::new(f, triage.as_ref().map(|_| triage_state(f)))).collect();
+    if crate::output::structured() {
+        crate::output::emit(&dump_path, &records)?;
+    }
     // Display findings using pager if there are many
     if !findings.is_empty() {
         let mut table = Table::new();
         table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
-        table.set_titles(row![b->"Address", b->"Confidence", b->"Description"]);
+        if triage.is_some() {
+            table.set_titles(row![b->"ID", b->"Address", b->"Confidence", b->"Triage", b->"Description"]);
+        } else {
+            table.set_titles(row![b->"ID", b->"Address", b->"Confidence", b->"Description"]);
+        }
 
         for finding in &findings {
-            table.add_row(row![
-                format!("0x{:08X}", finding.addr),
+            let mut cells = vec![
+                finding.id(),
+                finding.details.get("va").cloned().unwrap_or_else(|| format!("0x{:08X}", finding.addr)),
                 format!("{}%", finding.confidence),
-                finding.desc
-            ]);
+            ];
+            if triage.is_some() {
+                cells.push(triage_state(finding));
+            }
+            cells.push(match finding.details.get("provenance") {
+                Some(provenance) => format!("{} [{}]", finding.desc, provenance),
+                None => finding.desc.clone(),
+            });
+            table.add_row(Row::new(cells.into_iter().map(|c| Cell::new(&c)).collect()));
         }
 
-        if findings.len() > 20 {
+        #[cfg(not(target_arch = "wasm32"))]
+        if findings.len() > 20 && !crate::output::structured() {
             Pager::new().setup();
         }
 
-        println!("\n{} {} {}",
+        crate::status!("\n{} {} {}",
             "Found".bright_green(),
             findings.len().to_string().bright_yellow().bold(),
             "items".bright_green()
         );
 
-        table.printstd();
-
-        if let Some(csv_path) = csv_output {
-            let mut wtr = Writer::from_path(&csv_path)?;
-            wtr.write_record(["plugin", "address", "confidence", "description", "details"])?;
-            for finding in &findings {
-                let details = serde_json::to_string(&finding.details)?;
-                wtr.write_record([
-                    finding.plugin.clone(),
-                    format!("0x{:X}", finding.addr),
-                    finding.confidence.to_string(),
-                    finding.desc.clone(),
-                    details,
-                ])?;
-            }
-            wtr.flush()?;
-            println!(
-                "{} {}",
-                "Exported findings to".bright_green(),
-                csv_path.display().to_string().bright_cyan()
-            );
+        if !crate::output::structured() {
+            table.printstd();
         }
+
     } else {
-        println!("{}", "No findings from the scan".bright_yellow());
+        crate::status!("{}", "No findings from the scan".bright_yellow());
+    }
+    if let Some(path) = export {
+        export_to(&path, &dump_path, &records)?;
     }
+    crate::actions::run_for_findings(&dump_path, &findings);
 
     Ok(())
 }
+
+/// Write the findings of a run on `dump_path` to `path`, in the format its
+/// extension names
+fn export_to(path: &Path, dump_path: &Path, records: &[FindingRecord]) -> Result<()> {
+    match ExportFormat::from_path(path) {
+        ExportFormat::Sqlite => crate::output::save_to_database(path, dump_path, records)?,
+        format => fs::write(path, export_findings(records, format)?).with_context(|| format!("Failed to write {}", path.display()))?,
+    }
+    crate::status!(
+        "{} {}",
+        "Exported findings to".bright_green(),
+        path.display().to_string().bright_cyan()
+    );
+    Ok(())
+}
+
+/// Run several plugins (default: all) highest priority first, printing the
+/// findings of each plugin as soon as it finishes; with `plan`, only print
+/// what would run
+pub fn analyze(dump_path: PathBuf, plugins: Vec<String>, plan: bool, export: Option<PathBuf>) -> Result<()> {
+    let registry = get_plugin_registry();
+    let registry = registry.read().unwrap();
+    let names = if plugins.is_empty() {
+        registry.list_plugins().into_iter().map(|(name, _, _)| name).collect()
+    } else {
+        plugins
+    };
+    let mut selected = Vec::new();
+    for name in &names {
+        match registry.get(name) {
+            Some(plugin) => selected.push(plugin),
+            None => println!("{} {}", "Unknown plugin, skipped:".bright_red(), name),
+        }
+    }
+
+    let threads = ResourceLimits::new().threads_for(selected.len());
+    if plan {
+        print_plan(&dump_path, &selected, threads);
+        return Ok(());
+    }
+
+    let memory_image = load_memory_image(&dump_path)?;
+    let order: Vec<&str> = schedule(&selected).iter().map(|p| p.name()).collect();
+    println!("{} {} plugins on {} threads: {}",
+        "Analyzing with".bright_green(),
+        selected.len().to_string().bright_yellow(),
+        threads,
+        order.join(", ")
+    );
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+
+    let started = Instant::now();
+    let mut findings = Vec::new();
+    run_scheduled(&memory_image, &selected, threads, &progress, |run| {
+        progress.suspend(|| {
+            println!("{} {} ({} findings, {:.1}s)",
+                "Finished".bright_green(),
+                run.plugin.bright_yellow(),
+                run.findings.len(),
+                run.elapsed.as_secs_f64()
+            );
+            for finding in &run.findings {
+                println!("  {} 0x{:08X} {:>3}% {}", finding.id(), finding.addr, finding.confidence, finding.desc);
+            }
+        });
+        findings.extend(run.findings);
+    });
+    progress.finish_and_clear();
+
+    println!("\n{} {} findings from {} plugins in {:.1}s",
+        "Found".bright_green(),
+        findings.len().to_string().bright_yellow().bold(),
+        selected.len(),
+        started.elapsed().as_secs_f64()
+    );
+    if let Some(path) = export {
+        sort_findings(&mut findings);
+        let records: Vec<FindingRecord> = findings.iter().map(|f| FindingRecord::new(f, None)).collect();
+        export_to(&path, &dump_path, &records)?;
+    }
+    crate::actions::run_for_findings(&dump_path, &findings);
+    Ok(())
+}
+
+/// Print the pipeline `analyze` would run on `dump_path` and what it needs
+fn print_plan(dump_path: &std::path::Path, plugins: &[&dyn MemoryPlugin], threads: usize) {
+    println!("{} {}", "Analysis plan for".bright_green(), dump_path.display().to_string().bright_cyan());
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"#", bFg->"Plugin", bFg->"Priority", bFg->"Passes", bFg->"Needs"]);
+    for (index, plugin) in schedule(plugins).iter().enumerate() {
+        let needs = plugin.needs();
+        table.add_row(row![index + 1, plugin.name(), plugin.priority(), needs.passes, needs]);
+    }
+    table.printstd();
+
+    let passes = total_passes(plugins);
+    let size = std::fs::metadata(dump_path).map(|m| m.len()).ok();
+    match size {
+        Some(size) => println!("\n{} {} passes over {:.2} GB ({:.2} GB read) on {} threads",
+            "Estimated:".bright_green(),
+            passes.to_string().bright_yellow(),
+            size as f64 / (1u64 << 30) as f64,
+            (size * passes as u64) as f64 / (1u64 << 30) as f64,
+            threads
+        ),
+        None => println!("\n{} {} passes over the image on {} threads", "Estimated:".bright_green(), passes.to_string().bright_yellow(), threads),
+    }
+
+    let needed_by = |need: fn(&PluginNeeds) -> bool| -> Vec<&str> {
+        plugins.iter().filter(|p| need(&p.needs())).map(|p| p.name()).collect()
+    };
+    let symbols = needed_by(|n| n.symbols);
+    for (label, users) in [("Kernel DTB", needed_by(|n| n.kernel_dtb)), ("User-mode dump", needed_by(|n| n.user_dump)), ("Symbols", symbols.clone())] {
+        let state = if users.is_empty() { "not needed".to_string() } else { format!("needed by {}", users.join(", ")) };
+        println!("  {:<16} {}", label, state);
+    }
+    if !cfg!(feature = "symbols") && !symbols.is_empty() {
+        println!("{} rmf was built without the symbols feature", "Warning:".bright_red());
+    }
+}
@@ -0,0 +1,165 @@
+//! Mutant object scan
+//!
+//! Malware creates a named mutex at start-up so a second copy can tell it
+//! is already running, and the names are fixed per family, which makes
+//! them a quick triage signal. The scanner finds KMUTANT allocations by pool
+//! tag, reads each object's name from its OBJECT_HEADER_NAME_INFO and
+//! attributes held mutants to their owner thread and its process.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::modules::unicode_string_at;
+use crate::paging::MemoryImage;
+use crate::processes::{scan_pool_tags, WindowsProcessFinder};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Pool tags for KMUTANT, with and without the protected-allocation bit
+const MUTANT_POOL_TAGS: [&[u8]; 2] = [b"Muta", b"Mut\xe1"];
+const POOL_HEADER_SIZE: usize = 0x10;
+const OBJECT_HEADER_SIZE: usize = 0x30;
+const MAX_OPTIONAL_HEADERS: usize = 0x90;
+
+/// OBJECT_HEADER.InfoMask, and the optional headers it can announce
+const OBJECT_HEADER_INFO_MASK: usize = 0x1A;
+const INFO_CREATOR: u8 = 0x1;
+const INFO_NAME: u8 = 0x2;
+const CREATOR_INFO_SIZE: usize = 0x20;
+const NAME_INFO_SIZE: usize = 0x20;
+/// OBJECT_HEADER_NAME_INFO.Name
+const NAME_INFO_NAME: usize = 0x8;
+
+/// KMUTANT offsets for x64; DISPATCHER_HEADER.Size counts dwords
+const MUTANT_OBJECT: u8 = 2;
+const MUTANT_SIZE: usize = 0x38;
+const SIGNAL_STATE: usize = 0x4;
+const OWNER_THREAD: usize = 0x28;
+const ABANDONED: usize = 0x30;
+
+const KERNEL_VA_START: u64 = 0xFFFF_8000_0000_0000;
+
+/// Decoded KMUTANT fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mutant {
+    /// Free at 1; each acquisition by the owner decrements it
+    pub signal_state: i32,
+    pub owner_thread: u64,
+    pub abandoned: bool,
+}
+
+impl Mutant {
+    pub fn is_owned(&self) -> bool {
+        self.owner_thread != 0
+    }
+}
+
+/// Validate and decode a KMUTANT body
+pub fn parse_mutant(body: &[u8]) -> Option<Mutant> {
+    if body.len() < MUTANT_SIZE || body[0] != MUTANT_OBJECT || body[2] as usize * 4 != MUTANT_SIZE {
+        return None;
+    }
+    let mutant = Mutant {
+        signal_state: i32::from_le_bytes(body[SIGNAL_STATE..SIGNAL_STATE + 4].try_into().unwrap()),
+        owner_thread: u64::from_le_bytes(body[OWNER_THREAD..OWNER_THREAD + 8].try_into().unwrap()),
+        abandoned: body[ABANDONED] != 0,
+    };
+    let owner_valid = if mutant.is_owned() {
+        mutant.owner_thread >= KERNEL_VA_START && mutant.signal_state <= 0
+    } else {
+        mutant.signal_state == 1
+    };
+    owner_valid.then_some(mutant)
+}
+
+/// Name of the object whose body is at physical address `body`, from the
+/// name header in front of its object header
+fn object_name(img: &MemoryImage, body: usize) -> Option<String> {
+    let header = body.checked_sub(OBJECT_HEADER_SIZE)?;
+    let info_mask = img.get_bytes(header + OBJECT_HEADER_INFO_MASK, 1)?[0];
+    if info_mask & INFO_NAME == 0 {
+        return None;
+    }
+    let creator = if info_mask & INFO_CREATOR != 0 { CREATOR_INFO_SIZE } else { 0 };
+    let name_info = header.checked_sub(creator + NAME_INFO_SIZE)?;
+    let data = img.get_bytes(name_info, NAME_INFO_SIZE)?;
+    unicode_string_at(img, data, NAME_INFO_NAME).filter(|name| !name.is_empty())
+}
+
+/// A plugin that lists named mutexes and the processes holding them
+#[derive(Default)]
+pub struct MutantScanner;
+
+impl MutantScanner {
+    fn report(&self, img: &MemoryImage, finder: &WindowsProcessFinder, addr: u64, name: String, mutant: &Mutant, findings: &mut Vec<Finding>) {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "mutant".to_string());
+        details.insert("rule".to_string(), "named_mutant".to_string());
+        details.insert("mutant".to_string(), name.clone());
+        details.insert("signal_state".to_string(), mutant.signal_state.to_string());
+        if mutant.abandoned {
+            details.insert("abandoned".to_string(), "true".to_string());
+        }
+
+        let owner = mutant.is_owned().then(|| finder.thread_owner(img, mutant.owner_thread)).flatten();
+        let holder = match &owner {
+            Some((tid, process)) => {
+                details.insert("owner_tid".to_string(), tid.to_string());
+                details.insert("owner_pid".to_string(), process.pid.to_string());
+                details.insert("owner_process".to_string(), process.name.clone());
+                format!(", held by {} ({}) thread {}", process.pid, process.name, tid)
+            }
+            None if mutant.is_owned() => {
+                details.insert("owner_thread".to_string(), format!("0x{:X}", mutant.owner_thread));
+                format!(", held by thread 0x{:X}", mutant.owner_thread)
+            }
+            None => String::new(),
+        };
+
+        findings.push(Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("Mutant {}{}{}", name, holder, if mutant.abandoned { " (abandoned)" } else { "" }),
+            confidence: 50,
+            details,
+        });
+    }
+}
+
+impl MemoryPlugin for MutantScanner {
+    fn name(&self) -> &'static str {
+        "mutantscan"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Lists named mutexes found by pool tag, with the thread and process holding each one"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        progress.set_message("Scanning pool memory for mutant objects");
+        let finder = WindowsProcessFinder::new();
+        let mut findings = Vec::new();
+        for (header, _) in scan_pool_tags(img, progress, &MUTANT_POOL_TAGS) {
+            let first = header as usize + POOL_HEADER_SIZE + OBJECT_HEADER_SIZE;
+            let found = (0..=MAX_OPTIONAL_HEADERS).step_by(0x10).find_map(|extra| {
+                let body = img.get_bytes(first + extra, MUTANT_SIZE)?;
+                parse_mutant(body).map(|mutant| (first + extra, mutant))
+            });
+            // Unnamed mutants are private to one process and say little
+            let Some((body, mutant)) = found else { continue };
+            if let Some(name) = object_name(img, body) {
+                self.report(img, &finder, body as u64, name, &mutant, &mut findings);
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} named mutants", findings.len()));
+        findings
+    }
+}
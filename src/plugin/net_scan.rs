@@ -0,0 +1,322 @@
+//! Network connection scan
+//!
+//! tcpip.sys allocates a pool block for every TCP connection (`TcpE`),
+//! TCP listener (`TcpL`) and UDP endpoint (`UdpA`), each pointing to its
+//! addresses, ports and owning EPROCESS. Blocks of closed connections stay
+//! in pool until reused, so a scan recovers recent connections as well as
+//! open ones. The structures changed between Windows 7, 8 and 10, and the
+//! build is not known here; each block is decoded with every layout and
+//! the first one that validates wins.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::paging::MemoryImage;
+use crate::processes::{filetime_to_system, scan_pool_tags, WindowsProcessFinder};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+const POOL_HEADER_SIZE: u64 = 0x10;
+const KERNEL_VA_START: u64 = 0xFFFF_8000_0000_0000;
+
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 0x17;
+
+/// MIB_TCP_STATE names, by value
+const TCP_STATES: [&str; 14] = [
+    "CLOSED", "LISTENING", "SYN_SENT", "SYN_RCVD", "ESTABLISHED", "FIN_WAIT1", "FIN_WAIT2",
+    "CLOSE_WAIT", "CLOSING", "LAST_ACK", "", "", "TIME_WAIT", "DELETE_TCB",
+];
+
+/// Offsets of the tcpip.sys structures for one range of Windows builds (x64)
+#[derive(Debug, Clone)]
+pub struct NetLayout {
+    pub name: &'static str,
+    /// INETAF.AddressFamily
+    pub inetaf_family: u64,
+    /// LOCAL_ADDRESS.pData, a pointer to a pointer to the address
+    pub local_address_data: u64,
+    /// TCP_ENDPOINT
+    pub tcp_inetaf: u64,
+    pub tcp_addr_info: u64,
+    pub tcp_state: u64,
+    pub tcp_local_port: u64,
+    pub tcp_remote_port: u64,
+    pub tcp_owner: u64,
+    pub tcp_create_time: u64,
+    /// TCP_LISTENER
+    pub listener_create_time: u64,
+    pub listener_owner: u64,
+    pub listener_local_addr: u64,
+    pub listener_inetaf: u64,
+    pub listener_port: u64,
+    /// UDP_ENDPOINT
+    pub udp_owner: u64,
+    pub udp_create_time: u64,
+    pub udp_inetaf: u64,
+    pub udp_local_addr: u64,
+    pub udp_port: u64,
+}
+
+/// ADDRINFO.Local and ADDRINFO.Remote, the same on every layout
+const ADDR_INFO_LOCAL: u64 = 0x0;
+const ADDR_INFO_REMOTE: u64 = 0x10;
+
+pub const NET_LAYOUTS: [NetLayout; 3] = [
+    NetLayout {
+        name: "Windows 10/11",
+        inetaf_family: 0x18,
+        local_address_data: 0x10,
+        tcp_inetaf: 0x10, tcp_addr_info: 0x18, tcp_state: 0x6C, tcp_local_port: 0x70, tcp_remote_port: 0x72,
+        tcp_owner: 0x258, tcp_create_time: 0x268,
+        listener_create_time: 0x20, listener_owner: 0x28, listener_local_addr: 0x58, listener_inetaf: 0x60, listener_port: 0x72,
+        udp_owner: 0x28, udp_create_time: 0x58, udp_inetaf: 0x20, udp_local_addr: 0x80, udp_port: 0xA0,
+    },
+    NetLayout {
+        name: "Windows 8/8.1",
+        inetaf_family: 0x18,
+        local_address_data: 0x10,
+        tcp_inetaf: 0x10, tcp_addr_info: 0x18, tcp_state: 0x6C, tcp_local_port: 0x70, tcp_remote_port: 0x72,
+        tcp_owner: 0x250, tcp_create_time: 0x260,
+        listener_create_time: 0x20, listener_owner: 0x28, listener_local_addr: 0x58, listener_inetaf: 0x60, listener_port: 0x72,
+        udp_owner: 0x28, udp_create_time: 0x58, udp_inetaf: 0x20, udp_local_addr: 0x60, udp_port: 0x80,
+    },
+    NetLayout {
+        name: "Windows 7",
+        inetaf_family: 0x14,
+        local_address_data: 0x10,
+        tcp_inetaf: 0x18, tcp_addr_info: 0x20, tcp_state: 0x68, tcp_local_port: 0x6C, tcp_remote_port: 0x6E,
+        tcp_owner: 0x238, tcp_create_time: 0x248,
+        listener_create_time: 0x20, listener_owner: 0x28, listener_local_addr: 0x58, listener_inetaf: 0x60, listener_port: 0x6A,
+        udp_owner: 0x28, udp_create_time: 0x58, udp_inetaf: 0x20, udp_local_addr: 0x60, udp_port: 0x80,
+    },
+];
+
+/// What a pool block describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointKind {
+    TcpConnection,
+    TcpListener,
+    Udp,
+}
+
+impl EndpointKind {
+    fn tags(&self) -> [&'static [u8]; 1] {
+        match self {
+            EndpointKind::TcpConnection => [b"TcpE"],
+            EndpointKind::TcpListener => [b"TcpL"],
+            EndpointKind::Udp => [b"UdpA"],
+        }
+    }
+}
+
+/// A decoded network endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    pub kind: EndpointKind,
+    pub local: IpAddr,
+    pub local_port: u16,
+    /// Remote end of a TCP connection
+    pub remote: Option<(IpAddr, u16)>,
+    /// MIB_TCP_STATE of a TCP connection
+    pub state: Option<u32>,
+    /// Virtual address of the owning EPROCESS
+    pub owner: u64,
+    /// FILETIME the endpoint was created
+    pub create_time: u64,
+    pub layout: &'static str,
+}
+
+impl Endpoint {
+    pub fn protocol(&self) -> &'static str {
+        match (self.kind, self.local) {
+            (EndpointKind::Udp, IpAddr::V4(_)) => "UDPv4",
+            (EndpointKind::Udp, IpAddr::V6(_)) => "UDPv6",
+            (_, IpAddr::V4(_)) => "TCPv4",
+            (_, IpAddr::V6(_)) => "TCPv6",
+        }
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        match (self.kind, self.state) {
+            (EndpointKind::TcpListener, _) => "LISTENING",
+            (_, Some(state)) => TCP_STATES.get(state as usize).copied().filter(|s| !s.is_empty()).unwrap_or("UNKNOWN"),
+            _ => "",
+        }
+    }
+}
+
+/// Read an address of `family` through a pointer to it, or the unspecified
+/// address when the pointer is null
+fn address_at(img: &MemoryImage, ptr: u64, family: u16) -> Option<IpAddr> {
+    let unspecified = if family == AF_INET { IpAddr::V4(Ipv4Addr::UNSPECIFIED) } else { IpAddr::V6(Ipv6Addr::UNSPECIFIED) };
+    if ptr == 0 {
+        return Some(unspecified);
+    }
+    Some(match family {
+        AF_INET => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(img.read_virt(ptr, 4)?).ok()?)),
+        _ => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(img.read_virt(ptr, 16)?).ok()?)),
+    })
+}
+
+/// The address a LOCAL_ADDRESS refers to
+fn local_address(img: &MemoryImage, layout: &NetLayout, local: u64, family: u16) -> Option<IpAddr> {
+    if local == 0 {
+        return address_at(img, 0, family);
+    }
+    let data = img.read_virt_u64(local + layout.local_address_data)?;
+    let ptr = if data == 0 { 0 } else { img.read_virt_u64(data)? };
+    address_at(img, ptr, family)
+}
+
+fn is_kernel_or_null(va: u64) -> bool {
+    va == 0 || va >= KERNEL_VA_START
+}
+
+/// Validate and decode the endpoint at physical address `addr` with `layout`;
+/// the image must use the kernel DTB
+pub fn parse_endpoint(img: &MemoryImage, addr: u64, kind: EndpointKind, layout: &NetLayout) -> Option<Endpoint> {
+    let u64_at = |off: u64| img.read_u64((addr + off) as usize);
+    let u32_at = |off: u64| img.read_u32((addr + off) as usize);
+    let port_at = |off: u64| img.get_bytes((addr + off) as usize, 2).map(|b| u16::from_be_bytes([b[0], b[1]]));
+    let (inetaf, owner, create_time, port) = match kind {
+        EndpointKind::TcpConnection => (layout.tcp_inetaf, layout.tcp_owner, layout.tcp_create_time, layout.tcp_local_port),
+        EndpointKind::TcpListener => (layout.listener_inetaf, layout.listener_owner, layout.listener_create_time, layout.listener_port),
+        EndpointKind::Udp => (layout.udp_inetaf, layout.udp_owner, layout.udp_create_time, layout.udp_port),
+    };
+    let inetaf = u64_at(inetaf).filter(|&va| va >= KERNEL_VA_START)?;
+    let family = img.read_virt(inetaf + layout.inetaf_family, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))?;
+    if family != AF_INET && family != AF_INET6 {
+        return None;
+    }
+    let owner = u64_at(owner).filter(|&va| is_kernel_or_null(va))?;
+    let create_time = u64_at(create_time)?;
+    let local_port = port_at(port)?;
+
+    let mut endpoint = Endpoint {
+        kind,
+        local: address_at(img, 0, family)?,
+        local_port,
+        remote: None,
+        state: None,
+        owner,
+        create_time,
+        layout: layout.name,
+    };
+    match kind {
+        EndpointKind::TcpConnection => {
+            let state = u32_at(layout.tcp_state).filter(|&s| TCP_STATES.get(s as usize).is_some_and(|n| !n.is_empty()))?;
+            let addr_info = u64_at(layout.tcp_addr_info).filter(|&va| is_kernel_or_null(va))?;
+            if addr_info != 0 {
+                let local = img.read_virt_u64(addr_info + ADDR_INFO_LOCAL)?;
+                endpoint.local = local_address(img, layout, local, family)?;
+                let remote = img.read_virt_u64(addr_info + ADDR_INFO_REMOTE)?;
+                endpoint.remote = Some((address_at(img, remote, family)?, port_at(layout.tcp_remote_port)?));
+            }
+            endpoint.state = Some(state);
+        }
+        EndpointKind::TcpListener | EndpointKind::Udp => {
+            let local = if kind == EndpointKind::Udp { layout.udp_local_addr } else { layout.listener_local_addr };
+            let local = u64_at(local).filter(|&va| is_kernel_or_null(va))?;
+            endpoint.local = local_address(img, layout, local, family)?;
+            if local_port == 0 {
+                return None;
+            }
+        }
+    }
+    // A live endpoint has an owner and a creation time; freed ones keep both
+    (owner != 0 || filetime_to_system(create_time).is_some()).then_some(endpoint)
+}
+
+/// A plugin that recovers TCP and UDP endpoints from tcpip.sys pool blocks
+#[derive(Default)]
+pub struct NetworkScanner;
+
+impl NetworkScanner {
+    fn report(&self, img: &MemoryImage, finder: &WindowsProcessFinder, addr: u64, endpoint: &Endpoint, findings: &mut Vec<Finding>) {
+        let mut details = HashMap::new();
+        let rule = match endpoint.kind {
+            EndpointKind::TcpConnection => "tcp_endpoint",
+            EndpointKind::TcpListener => "tcp_listener",
+            EndpointKind::Udp => "udp_endpoint",
+        };
+        details.insert("type".to_string(), "network".to_string());
+        details.insert("rule".to_string(), rule.to_string());
+        details.insert("protocol".to_string(), endpoint.protocol().to_string());
+        details.insert("local_addr".to_string(), endpoint.local.to_string());
+        details.insert("local_port".to_string(), endpoint.local_port.to_string());
+        details.insert("layout".to_string(), endpoint.layout.to_string());
+        let mut desc = format!("{} {}", endpoint.protocol(), format_endpoint(endpoint.local, endpoint.local_port));
+        if let Some((remote, port)) = endpoint.remote {
+            details.insert("remote_addr".to_string(), remote.to_string());
+            details.insert("remote_port".to_string(), port.to_string());
+            desc.push_str(&format!(" -> {}", format_endpoint(remote, port)));
+        }
+        if !endpoint.state_name().is_empty() {
+            details.insert("state".to_string(), endpoint.state_name().to_string());
+            desc.push_str(&format!(" {}", endpoint.state_name()));
+        }
+        let owner = img.virt_to_phys(endpoint.owner).filter(|_| endpoint.owner != 0)
+            .and_then(|pa| finder.parse_eprocess(img, pa));
+        if let Some(process) = owner {
+            details.insert("pid".to_string(), process.pid.to_string());
+            details.insert("process".to_string(), process.name.clone());
+            desc.push_str(&format!(" by {} ({})", process.pid, process.name));
+        }
+        if let Some(created) = filetime_to_system(endpoint.create_time) {
+            let created = chrono::DateTime::<chrono::Utc>::from(created).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+            details.insert("created".to_string(), created);
+        }
+
+        findings.push(Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc,
+            confidence: 60,
+            details,
+        });
+    }
+}
+
+/// `addr:port`, with IPv6 addresses in brackets
+pub fn format_endpoint(addr: IpAddr, port: u16) -> String {
+    match addr {
+        IpAddr::V4(v4) => format!("{}:{}", v4, port),
+        IpAddr::V6(v6) => format!("[{}]:{}", v6, port),
+    }
+}
+
+impl MemoryPlugin for NetworkScanner {
+    fn name(&self) -> &'static str {
+        "netscan"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Recovers TCP connections, listeners and UDP endpoints from tcpip.sys pool allocations"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let finder = WindowsProcessFinder::new();
+        let mut findings = Vec::new();
+        for kind in [EndpointKind::TcpConnection, EndpointKind::TcpListener, EndpointKind::Udp] {
+            progress.set_message(format!("Scanning pool memory for {} blocks", String::from_utf8_lossy(kind.tags()[0])));
+            for (header, _) in scan_pool_tags(img, progress, &kind.tags()) {
+                let body = header + POOL_HEADER_SIZE;
+                if let Some(endpoint) = NET_LAYOUTS.iter().find_map(|layout| parse_endpoint(img, body, kind, layout)) {
+                    self.report(img, &finder, body, &endpoint, &mut findings);
+                }
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} network endpoints", findings.len()));
+        findings
+    }
+}
@@ -0,0 +1,233 @@
+//! Pattern scans from the command line
+//!
+//! `rmf scan --scan-type regex --pattern <re>` searches physical memory for
+//! a user regex, and `--scan-type bytes --pattern "4D 5A ?? ?? 90"` for a
+//! byte signature with wildcards, without writing a plugin. Memory is read in windows that
+//! run into the next so a match across a window boundary is seen whole;
+//! a match is reported by the window it starts in, and matches longer than
+//! the overlap may be cut short. Each window is searched as raw bytes and,
+//! for a regex, also as UTF-16LE text in Windows memory: the code units
+//! with a zero high byte at either alignment are narrowed to bytes and
+//! searched again.
+
+use anyhow::{bail, Context, Result};
+use indicatif::ProgressBar;
+use regex::bytes::{Regex, RegexBuilder};
+use std::collections::HashMap;
+
+use crate::paging::MemoryImage;
+use crate::scan_util::{chunks, Window};
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+/// Physical memory searched per window, and how far each window runs into
+/// the next
+pub(super) const WINDOW: usize = 0x100000;
+pub(super) const OVERLAP: usize = 0x1000;
+/// Bytes of context shown on each side of a match, and of the match itself
+const CONTEXT: usize = 32;
+const MAX_SHOWN: usize = 64;
+/// Matches reported before the scan stops
+pub const MAX_PATTERN_MATCHES: usize = 10000;
+
+/// How matched bytes were read from memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Ascii,
+    Utf16Le,
+}
+
+impl std::fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextEncoding::Ascii => write!(f, "ascii"),
+            TextEncoding::Utf16Le => write!(f, "utf-16le"),
+        }
+    }
+}
+
+/// A match in one window: where it starts and ends in the window's bytes,
+/// and the interpreted text with its context
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternMatch {
+    pub offset: usize,
+    pub len: usize,
+    pub encoding: TextEncoding,
+    pub text: Vec<u8>,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+/// The UTF-16LE code units of `data` starting at `alignment`, those with a
+/// zero high byte narrowed to their low byte and the rest to zero
+pub fn narrow_utf16(data: &[u8], alignment: usize) -> Vec<u8> {
+    data.get(alignment..).unwrap_or_default().chunks_exact(2).map(|unit| if unit[1] == 0 { unit[0] } else { 0 }).collect()
+}
+
+/// A byte signature such as `4D 5A ?? ?? 90` as a regex: `??` matches any
+/// byte and `4?` or `?4` any byte with that high or low nibble
+pub fn signature_regex(signature: &str) -> Result<String> {
+    let digits: Vec<u8> = signature.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        bail!("Byte pattern {:?} must be whole bytes of two hex digits or ?", signature);
+    }
+    let nibble = |c: u8| (c as char).to_digit(16);
+    let mut pattern = String::from("(?s-u)");
+    for pair in digits.chunks(2) {
+        pattern.push_str(&match (pair[0], pair[1]) {
+            (b'?', b'?') => ".".to_string(),
+            (b'?', low) if nibble(low).is_some() => {
+                format!("[{}]", (0..16).map(|high| format!("\\x{:x}{}", high, low as char)).collect::<String>())
+            }
+            (high, b'?') if nibble(high).is_some() => format!("[\\x{}0-\\x{}f]", high as char, high as char),
+            (high, low) if nibble(high).is_some() && nibble(low).is_some() => format!("\\x{}{}", high as char, low as char),
+            _ => bail!("Invalid byte {:?} in pattern {:?}", String::from_utf8_lossy(pair), signature),
+        });
+    }
+    if digits.chunks(2).all(|pair| pair == b"??") {
+        bail!("Byte pattern {:?} has no fixed byte and would match everywhere", signature);
+    }
+    Ok(pattern)
+}
+
+/// Printable ASCII of `bytes`, other bytes as dots
+pub fn printable(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+}
+
+/// A plugin searching physical memory for a pattern given on the command line
+pub struct PatternScanner {
+    pattern: String,
+    regex: Regex,
+    /// A byte signature rather than a regex: no UTF-16 search, hex output
+    signature: bool,
+}
+
+impl PatternScanner {
+    /// Search for the regular expression `pattern`, matched against bytes
+    /// (`.` matches any byte, classes are ASCII)
+    pub fn regex(pattern: &str) -> Result<Self> {
+        let regex = RegexBuilder::new(pattern).unicode(false).build().with_context(|| format!("Invalid regex {:?}", pattern))?;
+        Ok(PatternScanner { pattern: pattern.to_string(), regex, signature: false })
+    }
+
+    /// Search for the byte signature `pattern`, see [`signature_regex`]
+    pub fn bytes(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(&signature_regex(pattern)?)?;
+        Ok(PatternScanner { pattern: pattern.to_string(), regex, signature: true })
+    }
+
+    /// Matches of the pattern in `window`, as bytes and as UTF-16LE, by offset
+    pub fn find(&self, window: &[u8]) -> Vec<PatternMatch> {
+        let mut matches: Vec<PatternMatch> = self.regex.find_iter(window).filter(|m| !m.is_empty()).map(|m| PatternMatch {
+            offset: m.start(),
+            len: m.len(),
+            encoding: TextEncoding::Ascii,
+            text: m.as_bytes().to_vec(),
+            before: window[m.start().saturating_sub(CONTEXT)..m.start()].to_vec(),
+            after: window[m.end()..(m.end() + CONTEXT).min(window.len())].to_vec(),
+        }).collect();
+        for alignment in (0..2).filter(|_| !self.signature) {
+            let narrowed = narrow_utf16(window, alignment);
+            // A match of only zeros, whether real or narrowed away, is no text
+            matches.extend(self.regex.find_iter(&narrowed).filter(|m| m.as_bytes().iter().any(|&b| b != 0)).map(|m| PatternMatch {
+                offset: alignment + m.start() * 2,
+                len: m.len() * 2,
+                encoding: TextEncoding::Utf16Le,
+                text: m.as_bytes().to_vec(),
+                before: narrowed[m.start().saturating_sub(CONTEXT)..m.start()].to_vec(),
+                after: narrowed[m.end()..(m.end() + CONTEXT).min(narrowed.len())].to_vec(),
+            }));
+        }
+        matches.sort_by_key(|m| (m.offset, m.encoding == TextEncoding::Utf16Le));
+        matches
+    }
+
+    /// Matches of the pattern that `window` reports; later ones start in
+    /// the overlap and belong to the next
+    pub(super) fn window_matches(&self, window: &Window) -> Vec<PatternMatch> {
+        self.find(window.data).into_iter().filter(|m| window.owns(m.offset)).collect()
+    }
+
+    /// The pattern, the match and its context: hex for a byte signature,
+    /// printable text otherwise
+    pub(super) fn match_details(&self, found: &PatternMatch) -> HashMap<String, String> {
+        let text = &found.text[..found.text.len().min(MAX_SHOWN)];
+        let mut details = HashMap::new();
+        details.insert("pattern".to_string(), self.pattern.clone());
+        details.insert("length".to_string(), found.len.to_string());
+        if self.signature {
+            // Hex bytes of context, then the printable rendering
+            let before = &found.before[found.before.len().saturating_sub(CONTEXT / 2)..];
+            let after = &found.after[..found.after.len().min(CONTEXT / 2)];
+            details.insert("match".to_string(), hex(text));
+            details.insert("context".to_string(), format!("{} [{}] {}", hex(before), hex(text), hex(after)).trim().to_string());
+            details.insert("ascii".to_string(), printable(text));
+        } else {
+            let shown = printable(text);
+            details.insert("encoding".to_string(), found.encoding.to_string());
+            details.insert("context".to_string(), format!("{}[{}]{}", printable(&found.before), shown, printable(&found.after)));
+            details.insert("match".to_string(), shown);
+        }
+        details
+    }
+
+    fn report(&self, addr: u64, found: &PatternMatch) -> Finding {
+        let mut details = self.match_details(found);
+        details.insert("type".to_string(), "pattern".to_string());
+        let desc = if self.signature {
+            details.insert("rule".to_string(), "byte_pattern".to_string());
+            format!("Byte pattern match {} at 0x{:X}", details["match"], addr)
+        } else {
+            details.insert("rule".to_string(), "regex".to_string());
+            format!("Regex match \"{}\" at 0x{:X} ({})", details["match"], addr, found.encoding)
+        };
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc,
+            confidence: 50,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for PatternScanner {
+    fn name(&self) -> &'static str {
+        "pattern_scan"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Low
+    }
+
+    fn description(&self) -> &'static str {
+        "Searches physical memory for a regex (as bytes and UTF-16LE) or wildcard byte signature given on the command line"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![("pattern", self.pattern.clone())]
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message(format!("Searching for {}", self.pattern));
+        'scan: for window in chunks(img, WINDOW, OVERLAP).with_progress(progress) {
+            for found in self.window_matches(&window) {
+                if findings.len() == MAX_PATTERN_MATCHES {
+                    break 'scan;
+                }
+                findings.push(self.report(window.addr(found.offset), &found));
+            }
+        }
+        if findings.len() == MAX_PATTERN_MATCHES {
+            progress.finish_with_message(format!("Stopped after {} matches", MAX_PATTERN_MATCHES));
+            return findings;
+        }
+        progress.finish_with_message(format!("Found {} matches", findings.len()));
+        findings
+    }
+}
@@ -1,9 +1,107 @@
 //! PE (Portable Executable) scanner plugin
+//!
+//! Reports each MZ header followed by a PE header, with what its file and
+//! optional headers say: architecture, image size, entry point, link time,
+//! subsystem and the section table. A loaded image has its sections at
+//! their RVAs while a file (or a copy in the cache) has them at their raw
+//! offsets; the scanner tells the two apart by where the first section's
+//! data is, since the padding between the headers and the first section is
+//! zero in a mapped image.
+//!
+//! Each image is also hashed the way threat intelligence databases index
+//! PEs: imphash, Rich header hash and the SHA-256 of every section (see
+//! [`PeHashes`]). The image is read from the physical pages following its
+//! headers, which only hold the rest of a mapped image when it happens to
+//! be physically contiguous; hashes of a carved image that do not match a
+//! known one are therefore no proof that it was modified.
 
 use indicatif::ProgressBar;
 use std::collections::HashMap;
 use crate::paging::MemoryImage;
-use super::registry::{MemoryPlugin, Finding};
+use crate::pe::{read_u16, PeHashes, PeHeaders, PeLayout};
+use crate::scan_util::{chunks, CHUNK_SIZE};
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+/// Bytes read past a chunk for the headers of an MZ header in it and the
+/// start of the first section
+const OVERLAP: usize = 0x4000;
+/// Most bytes of an image read to hash it
+const MAX_HASHED: usize = 0x4000000;
+
+/// Architecture name of a COFF machine type
+pub fn machine_name(machine: u16) -> &'static str {
+    match machine {
+        0x014c => "x86",
+        0x0200 => "IA64",
+        0x8664 => "x64",
+        0xAA64 => "ARM64",
+        _ => "Unknown",
+    }
+}
+
+/// Name of an optional header subsystem
+pub fn subsystem_name(subsystem: u16) -> &'static str {
+    match subsystem {
+        1 => "native",
+        2 => "windows_gui",
+        3 => "windows_cui",
+        5 => "os2_cui",
+        7 => "posix_cui",
+        9 => "windows_ce_gui",
+        10 => "efi_application",
+        11 => "efi_boot_service_driver",
+        12 => "efi_runtime_driver",
+        13 => "efi_rom",
+        14 => "xbox",
+        16 => "windows_boot_application",
+        _ => "unknown",
+    }
+}
+
+/// Details of a parsed PE image for its finding
+fn header_details(headers: &PeHeaders, layout: PeLayout, details: &mut HashMap<String, String>) {
+    details.insert("format".to_string(), if headers.pe32_plus { "PE32+" } else { "PE32" }.to_string());
+    details.insert("image_base".to_string(), format!("0x{:X}", headers.image_base));
+    details.insert("image_size".to_string(), format!("0x{:X}", headers.image_size));
+    details.insert("entry_point".to_string(), format!("0x{:X}", headers.entry_point));
+    details.insert("timestamp".to_string(), format!("0x{:08X}", headers.timestamp));
+    // Reproducible builds store a hash here rather than a time
+    if let Some(time) = chrono::DateTime::from_timestamp(headers.timestamp as i64, 0) {
+        details.insert("link_time".to_string(), time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+    }
+    details.insert("subsystem".to_string(), subsystem_name(headers.subsystem).to_string());
+    details.insert("dll".to_string(), headers.is_dll().to_string());
+    details.insert("section_alignment".to_string(), format!("0x{:X}", headers.section_alignment));
+    details.insert("file_alignment".to_string(), format!("0x{:X}", headers.file_alignment));
+    details.insert("section_count".to_string(), headers.sections.len().to_string());
+    let sections: Vec<String> = headers.sections.iter()
+        .map(|s| format!("{} rva=0x{:X} vsize=0x{:X} raw=0x{:X} rawsize=0x{:X}", s.name, s.virtual_address, s.virtual_size, s.raw_offset, s.raw_size))
+        .collect();
+    details.insert("sections".to_string(), sections.join("; "));
+    details.insert("layout".to_string(), layout.to_string());
+}
+
+/// Hash details of the image at physical address `addr`, read from `img`
+/// as far as the headers say it runs, or from `window` when that fails
+fn hash_details(img: &MemoryImage, addr: usize, window: &[u8], headers: &PeHeaders, layout: PeLayout, details: &mut HashMap<String, String>) {
+    let mapped = layout != PeLayout::File;
+    let len = if mapped {
+        headers.image_size as usize
+    } else {
+        headers.sections.iter().map(|s| (s.raw_offset + s.raw_size) as usize).max().unwrap_or(0).max(headers.headers_size as usize)
+    };
+    let len = len.min(MAX_HASHED).min(img.size().saturating_sub(addr));
+    let image = img.get_bytes(addr, len).unwrap_or(window);
+    let Some(hashes) = PeHashes::of_image(image, mapped) else { return };
+    if let Some(imphash) = hashes.imphash {
+        details.insert("imphash".to_string(), imphash);
+    }
+    if let Some(rich) = hashes.rich_header_hash {
+        details.insert("rich_header_hash".to_string(), rich);
+    }
+    let sections: Vec<String> = hashes.sections.iter().map(|s| format!("{}={}", s.name, s.sha256)).collect();
+    details.insert("section_sha256".to_string(), sections.join("; "));
+}
 
 /// A plugin that scans for PE headers in memory
 pub struct PEScanner;
@@ -12,111 +110,85 @@ impl MemoryPlugin for PEScanner {
     fn name(&self) -> &'static str {
         "pe_scanner"
     }
+
+    fn priority(&self) -> Priority {
+        Priority::Low
+    }
     
     fn description(&self) -> &'static str {
-        "Scans memory for Portable Executable (PE) headers and executables"
+        "Scans memory for Portable Executable (PE) headers and reports their image size, entry point, link time, subsystem, sections, layout, imphash and section hashes"
     }
 
     fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
         let mut findings = Vec::new();
-        let size = img.size();
         
         // Set up progress bar
-        progress.set_length(size as u64);
         progress.set_message("Scanning for PE headers");
         
         // The PE file format starts with "MZ" (0x4D5A) and has a PE header at a specified offset
         let mz_signature = [0x4D, 0x5A]; // "MZ"
         let pe_signature = [0x50, 0x45, 0x00, 0x00]; // "PE\0\0"
         
-        // Scan in chunks to avoid loading the entire memory image at once
-        let chunk_size = 0x10000; // 64KB chunks
-        
-        for chunk_start in (0..size).step_by(chunk_size) {
-            // Update progress
-            progress.set_position(chunk_start as u64);
-            
-            // Get the chunk
-            if let Some(chunk) = img.get_bytes(chunk_start, chunk_size) {
-                for i in 0..chunk.len() - mz_signature.len() {
-                    // Check for MZ signature
-                    if chunk[i..i + mz_signature.len()] == mz_signature {
-                        // Found potential PE file, get the e_lfanew field at offset 0x3C
-                        if i + 0x40 < chunk.len() {
-                            let e_lfanew_offset = i + 0x3C;
-                            let e_lfanew = u32::from_le_bytes([
-                                chunk[e_lfanew_offset],
-                                chunk[e_lfanew_offset + 1],
-                                chunk[e_lfanew_offset + 2],
-                                chunk[e_lfanew_offset + 3],
-                            ]);
-                            
-                            // Calculate the PE header offset
-                            let pe_offset = i as u32 + e_lfanew;
-                            
-                            // Check if the PE header is within this chunk
-                            let pe_header_in_chunk = pe_offset as usize + pe_signature.len() <= chunk_start + chunk.len();
-                            
-                            // If PE header is in this chunk, check for "PE\0\0" signature
-                            if pe_header_in_chunk {
-                                let pe_header_offset = (pe_offset as usize) - chunk_start;
-                                if pe_header_offset + pe_signature.len() <= chunk.len() &&
-                                   chunk[pe_header_offset..pe_header_offset + pe_signature.len()] == pe_signature {
-                                    // This is a PE file
-                                    let mut details = HashMap::new();
-                                    details.insert("type".to_string(), "PE_HEADER".to_string());
-                                    
-                                    // Try to extract more information
-                                    if pe_header_offset + 0x18 < chunk.len() {
-                                        // Extract machine type
-                                        let machine = u16::from_le_bytes([
-                                            chunk[pe_header_offset + 4],
-                                            chunk[pe_header_offset + 5],
-                                        ]);
-                                        
-                                        // Map machine type to architecture
-                                        let arch = match machine {
-                                            0x014c => "x86",
-                                            0x0200 => "IA64",
-                                            0x8664 => "x64",
-                                            _ => "Unknown",
-                                        };
-                                        
-                                        details.insert("architecture".to_string(), arch.to_string());
-                                    }
-                                    
-                                    findings.push(Finding {
-                                        plugin: self.name().to_string(),
-                                        addr: (chunk_start + i) as u64,
-                                        desc: format!("PE Header found at 0x{:X}", chunk_start + i),
-                                        confidence: 95,
-                                        details,
-                                    });
-                                }
-                            } else {
-                                // PE header might be in another chunk, we'd need to check
-                                // For this demo, just add it as a potential finding with lower confidence
-                                let mut details = HashMap::new();
-                                details.insert("type".to_string(), "POTENTIAL_PE_HEADER".to_string());
-                                details.insert("e_lfanew".to_string(), format!("0x{:X}", e_lfanew));
-                                
-                                findings.push(Finding {
-                                    plugin: self.name().to_string(),
-                                    addr: (chunk_start + i) as u64,
-                                    desc: format!("Potential PE file at 0x{:X}", chunk_start + i),
-                                    confidence: 50,
-                                    details,
-                                });
-                            }
-                        }
+        // Scan in chunks to avoid loading the entire memory image at once,
+        // reading far enough past each for the PE header of an MZ near its end
+        for window in chunks(img, CHUNK_SIZE, OVERLAP).with_progress(progress) {
+            let chunk = window.data;
+            for i in window.owned.clone() {
+                // Check for MZ signature; e_lfanew is at offset 0x3C
+                if chunk.get(i..i + mz_signature.len()) != Some(&mz_signature[..]) || i + 0x40 > chunk.len() {
+                    continue;
+                }
+                let e_lfanew = u32::from_le_bytes(chunk[i + 0x3C..i + 0x40].try_into().unwrap());
+                let pe_header_offset = i + e_lfanew as usize;
+                
+                // If the PE header was read with the window, check for "PE\0\0" signature
+                if pe_header_offset + pe_signature.len() <= chunk.len() {
+                    if chunk[pe_header_offset..pe_header_offset + pe_signature.len()] != pe_signature {
+                        continue;
+                    }
+                    // This is a PE file
+                    let mut details = HashMap::new();
+                    details.insert("type".to_string(), "PE_HEADER".to_string());
+                    details.insert("rule".to_string(), "mz_pe_signature".to_string());
+                    
+                    // Try to extract more information
+                    let mut desc = format!("PE Header found at 0x{:X}", window.addr(i));
+                    if let Some(machine) = read_u16(chunk, pe_header_offset + 4) {
+                        details.insert("architecture".to_string(), machine_name(machine).to_string());
+                    }
+                    if let Some(headers) = PeHeaders::parse(&chunk[i..]) {
+                        let layout = headers.layout(&chunk[i..]);
+                        header_details(&headers, layout, &mut details);
+                        hash_details(img, window.start + i, &chunk[i..], &headers, layout, &mut details);
+                        desc = format!("PE image at 0x{:X} ({} {}, {} layout, 0x{:X} bytes, entry 0x{:X})",
+                            window.addr(i), machine_name(headers.machine), if headers.is_dll() { "DLL" } else { "EXE" },
+                            layout, headers.image_size, headers.entry_point);
                     }
+
+                    findings.push(Finding {
+                        plugin: self.name().to_string(),
+                        addr: window.addr(i),
+                        desc,
+                        confidence: 95,
+                        details,
+                    });
+                } else {
+                    // The PE header lies past the bytes read, further than
+                    // real images put it
+                    let mut details = HashMap::new();
+                    details.insert("type".to_string(), "POTENTIAL_PE_HEADER".to_string());
+                    details.insert("rule".to_string(), "mz_header_only".to_string());
+                    details.insert("e_lfanew".to_string(), format!("0x{:X}", e_lfanew));
+                    
+                    findings.push(Finding {
+                        plugin: self.name().to_string(),
+                        addr: window.addr(i),
+                        desc: format!("Potential PE file at 0x{:X}", window.addr(i)),
+                        confidence: 50,
+                        details,
+                    });
                 }
             }
-            
-            // Simulate work
-            if chunk_start % (1024 * 1024) == 0 {  // Every 1MB
-                std::thread::sleep(std::time::Duration::from_millis(5));
-            }
         }
         
         progress.finish_with_message(format!("Found {} PE headers", findings.len()));
@@ -0,0 +1,313 @@
+//! PEB/TEB parser and anti-debugging checks for user-mode process dumps
+//!
+//! Walks every thread's TEB to its PEB and decodes the debugger-related
+//! fields, loader data, process parameters and TLS slots. The results are
+//! cross-checked against the dump's own module and thread streams, which
+//! the dumper obtained from the kernel rather than from process memory.
+
+use indicatif::ProgressBar;
+use std::collections::{HashMap, HashSet};
+
+use crate::paging::MemoryImage;
+use crate::usermode::UserSpace;
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// x64 TEB offsets
+const TEB_SELF: u64 = 0x30;
+const TEB_PEB: u64 = 0x60;
+const TEB_TLS_SLOTS: u64 = 0x1480;
+const TLS_SLOT_COUNT: u64 = 64;
+
+/// x64 PEB offsets
+const PEB_BEING_DEBUGGED: u64 = 0x02;
+const PEB_IMAGE_BASE: u64 = 0x10;
+const PEB_LDR: u64 = 0x18;
+const PEB_PROCESS_PARAMETERS: u64 = 0x20;
+const PEB_PROCESS_HEAP: u64 = 0x30;
+const PEB_NT_GLOBAL_FLAG: u64 = 0xBC;
+
+/// PEB_LDR_DATA / LDR_DATA_TABLE_ENTRY offsets
+const LDR_IN_LOAD_ORDER: u64 = 0x10;
+const LDR_ENTRY_DLL_BASE: u64 = 0x30;
+const LDR_ENTRY_SIZE_OF_IMAGE: u64 = 0x40;
+const LDR_ENTRY_FULL_NAME: u64 = 0x48;
+const MAX_LDR_ENTRIES: usize = 4096;
+
+/// RTL_USER_PROCESS_PARAMETERS offsets
+const PARAMS_CURRENT_DIRECTORY: u64 = 0x38;
+const PARAMS_IMAGE_PATH: u64 = 0x60;
+const PARAMS_COMMAND_LINE: u64 = 0x70;
+const PARAMS_ENVIRONMENT: u64 = 0x80;
+const PARAMS_ENVIRONMENT_SIZE: u64 = 0x3F0;
+
+/// Environment blocks are read up to this size when EnvironmentSize is implausible
+const MAX_ENVIRONMENT_SIZE: usize = 0x10_0000;
+
+/// _HEAP ForceFlags, non-zero when the heap was created under a debugger
+const HEAP_FORCE_FLAGS: u64 = 0x74;
+
+/// FLG_HEAP_ENABLE_TAIL_CHECK | FLG_HEAP_ENABLE_FREE_CHECK | FLG_HEAP_VALIDATE_PARAMETERS
+const DEBUG_GLOBAL_FLAGS: u32 = 0x70;
+
+/// Read a UNICODE_STRING at a virtual address
+fn read_virt_u8(img: &MemoryImage, va: u64) -> Option<u8> {
+    img.get_bytes(img.virt_to_phys(va)? as usize, 1).map(|b| b[0])
+}
+
+/// Command line, paths and environment from a process's RTL_USER_PROCESS_PARAMETERS
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessParameters {
+    pub image_path: Option<String>,
+    pub command_line: Option<String>,
+    pub current_directory: Option<String>,
+    /// Variables in block order; per-drive directories keep their leading `=` (`=C:`)
+    pub environment: Vec<(String, String)>,
+}
+
+/// Split a UTF-16 environment block into variables, stopping at the empty entry
+pub fn parse_environment(block: &[u8]) -> Vec<(String, String)> {
+    let chars: Vec<u16> = block.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    chars
+        .split(|&c| c == 0)
+        .take_while(|entry| !entry.is_empty())
+        .map(String::from_utf16_lossy)
+        .map(|entry| match entry.char_indices().skip(1).find(|&(_, c)| c == '=') {
+            Some((at, _)) => (entry[..at].to_string(), entry[at + 1..].to_string()),
+            None => (entry, String::new()),
+        })
+        .collect()
+}
+
+/// Read the environment block at `va` page by page, stopping at the first
+/// unmapped page or the terminating empty entry
+fn read_environment(img: &MemoryImage, va: u64, size: usize) -> Vec<(String, String)> {
+    let mut block = Vec::new();
+    while block.len() < size {
+        let at = va + block.len() as u64;
+        let take = (size - block.len()).min(0x1000 - (at & 0xFFF) as usize);
+        let Some(bytes) = img.virt_to_phys(at).and_then(|pa| img.get_bytes(pa as usize, take)) else { break };
+        block.extend_from_slice(bytes);
+        if block.len() % 2 == 0 && block.windows(4).step_by(2).any(|w| w == [0; 4]) {
+            break;
+        }
+    }
+    parse_environment(&block)
+}
+
+/// Follow the PEB at `peb` to the process parameters; `img` must translate
+/// through the process's page tables
+pub fn read_process_parameters(img: &MemoryImage, peb: u64) -> Option<ProcessParameters> {
+    let params = img.read_virt_u64(peb + PEB_PROCESS_PARAMETERS).filter(|&p| p != 0)?;
+    let mut parameters = ProcessParameters {
+        image_path: img.read_unicode_string(params + PARAMS_IMAGE_PATH),
+        command_line: img.read_unicode_string(params + PARAMS_COMMAND_LINE),
+        // CURDIR starts with the DosPath UNICODE_STRING
+        current_directory: img.read_unicode_string(params + PARAMS_CURRENT_DIRECTORY),
+        environment: Vec::new(),
+    };
+    if let Some(env) = img.read_virt_u64(params + PARAMS_ENVIRONMENT).filter(|&e| e != 0) {
+        let size = img.read_virt_u64(params + PARAMS_ENVIRONMENT_SIZE)
+            .map(|size| size as usize)
+            .filter(|&size| size > 0 && size <= MAX_ENVIRONMENT_SIZE)
+            .unwrap_or(MAX_ENVIRONMENT_SIZE);
+        parameters.environment = read_environment(img, env, size);
+    }
+    Some(parameters)
+}
+
+/// Decoded PEB and the threads that reference it
+#[derive(Debug, Default)]
+pub struct PebInfo {
+    pub being_debugged: bool,
+    pub nt_global_flag: u32,
+    pub image_base: u64,
+    pub image_path: Option<String>,
+    pub command_line: Option<String>,
+    pub heap_force_flags: u32,
+    pub ldr_modules: Vec<(u64, u64, String)>, // (base, size, full name)
+}
+
+/// Parse the PEB at `peb` and walk its loader list
+pub fn parse_peb(img: &MemoryImage, peb: u64) -> Option<PebInfo> {
+    let mut info = PebInfo {
+        being_debugged: read_virt_u8(img, peb + PEB_BEING_DEBUGGED)? != 0,
+        nt_global_flag: img.read_virt_u32(peb + PEB_NT_GLOBAL_FLAG).unwrap_or(0),
+        image_base: img.read_virt_u64(peb + PEB_IMAGE_BASE)?,
+        ..Default::default()
+    };
+
+    if let Some(params) = read_process_parameters(img, peb) {
+        info.image_path = params.image_path;
+        info.command_line = params.command_line;
+    }
+    if let Some(heap) = img.read_virt_u64(peb + PEB_PROCESS_HEAP).filter(|&h| h != 0) {
+        info.heap_force_flags = img.read_virt_u32(heap + HEAP_FORCE_FLAGS).unwrap_or(0);
+    }
+
+    // InLoadOrderModuleList is the first LIST_ENTRY in each loader entry
+    if let Some(ldr) = img.read_virt_u64(peb + PEB_LDR).filter(|&l| l != 0) {
+        let head = ldr + LDR_IN_LOAD_ORDER;
+        let mut entry = img.read_virt_u64(head).unwrap_or(head);
+        let mut seen = HashSet::new();
+        while entry != head && seen.insert(entry) && seen.len() <= MAX_LDR_ENTRIES {
+            let base = match img.read_virt_u64(entry + LDR_ENTRY_DLL_BASE) {
+                Some(base) => base,
+                None => break,
+            };
+            let name = img.read_unicode_string(entry + LDR_ENTRY_FULL_NAME).unwrap_or_default();
+            let size = img.read_virt_u32(entry + LDR_ENTRY_SIZE_OF_IMAGE).unwrap_or(0) as u64;
+            info.ldr_modules.push((base, size, name));
+            entry = match img.read_virt_u64(entry) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+
+    Some(info)
+}
+
+/// Count the TLS slots a thread has populated
+fn tls_slots_used(img: &MemoryImage, teb: u64) -> usize {
+    (0..TLS_SLOT_COUNT)
+        .filter_map(|i| img.read_virt_u64(teb + TEB_TLS_SLOTS + i * 8))
+        .filter(|&slot| slot != 0)
+        .count()
+}
+
+/// A plugin that parses PEB/TEB structures and flags anti-debugging tampering
+#[derive(Default)]
+pub struct PebScanner;
+
+impl PebScanner {
+    fn finding(&self, img: &MemoryImage, va: u64, desc: String, confidence: u8, details: HashMap<String, String>) -> Finding {
+        Finding {
+            plugin: self.name().to_string(),
+            addr: img.virt_to_phys(va).unwrap_or(0),
+            desc,
+            confidence,
+            details,
+        }
+    }
+
+    fn anomaly(&self, img: &MemoryImage, va: u64, rule: &str, desc: String, findings: &mut Vec<Finding>) {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "peb_anomaly".to_string());
+        details.insert("rule".to_string(), rule.to_string());
+        findings.push(self.finding(img, va, desc, 80, details));
+    }
+
+    fn check(&self, img: &MemoryImage, user: &UserSpace, findings: &mut Vec<Finding>) {
+        let mut pebs: HashMap<u64, Vec<u32>> = HashMap::new();
+        let mut tls_used = 0;
+
+        for thread in user.threads.iter().filter(|t| t.teb != 0) {
+            if let Some(self_ptr) = img.read_virt_u64(thread.teb + TEB_SELF) {
+                if self_ptr != thread.teb {
+                    self.anomaly(img, thread.teb, "teb_self_mismatch",
+                        format!("TEB of thread {} points to 0x{:X} instead of itself", thread.id, self_ptr), findings);
+                }
+            }
+            if let Some(peb) = img.read_virt_u64(thread.teb + TEB_PEB) {
+                pebs.entry(peb).or_default().push(thread.id);
+            }
+            tls_used = tls_used.max(tls_slots_used(img, thread.teb));
+        }
+
+        if pebs.len() > 1 {
+            let mut addrs: Vec<String> = pebs.keys().map(|p| format!("0x{:X}", p)).collect();
+            addrs.sort();
+            let teb = user.threads.first().map(|t| t.teb).unwrap_or(0);
+            self.anomaly(img, teb, "peb_mismatch",
+                format!("Threads reference different PEBs: {}", addrs.join(", ")), findings);
+        }
+
+        for (&peb_addr, threads) in &pebs {
+            let peb = match parse_peb(img, peb_addr) {
+                Some(peb) => peb,
+                None => continue,
+            };
+
+            let mut details = HashMap::new();
+            details.insert("type".to_string(), "peb".to_string());
+            details.insert("rule".to_string(), "peb_summary".to_string());
+            details.insert("being_debugged".to_string(), peb.being_debugged.to_string());
+            details.insert("nt_global_flag".to_string(), format!("0x{:X}", peb.nt_global_flag));
+            details.insert("image_base".to_string(), format!("0x{:X}", peb.image_base));
+            details.insert("heap_force_flags".to_string(), format!("0x{:X}", peb.heap_force_flags));
+            details.insert("ldr_modules".to_string(), peb.ldr_modules.len().to_string());
+            details.insert("tls_slots_used".to_string(), tls_used.to_string());
+            details.insert("threads".to_string(), threads.len().to_string());
+            if let Some(path) = &peb.image_path {
+                details.insert("image_path".to_string(), path.clone());
+            }
+            if let Some(command_line) = &peb.command_line {
+                details.insert("command_line".to_string(), command_line.clone());
+            }
+            let name = peb.image_path.as_deref().unwrap_or("?");
+            findings.push(self.finding(img, peb_addr, format!("PEB of {} ({} loader entries)", name, peb.ldr_modules.len()), 90, details));
+
+            if peb.being_debugged {
+                self.anomaly(img, peb_addr, "being_debugged", "PEB.BeingDebugged is set".to_string(), findings);
+            }
+            if peb.nt_global_flag & DEBUG_GLOBAL_FLAGS != 0 {
+                self.anomaly(img, peb_addr, "nt_global_flag",
+                    format!("NtGlobalFlag 0x{:X} has debugger heap flags", peb.nt_global_flag), findings);
+            }
+            if peb.heap_force_flags != 0 {
+                self.anomaly(img, peb_addr, "heap_force_flags",
+                    format!("Process heap ForceFlags 0x{:X} indicate a debugger", peb.heap_force_flags), findings);
+            }
+
+            // The dump's module stream comes from the kernel; compare it with the PEB
+            let kernel_bases: HashSet<u64> = user.modules.iter().map(|m| m.base).collect();
+            let ldr_bases: HashSet<u64> = peb.ldr_modules.iter().map(|(base, _, _)| *base).collect();
+            if !kernel_bases.is_empty() && !kernel_bases.contains(&peb.image_base) {
+                self.anomaly(img, peb_addr, "image_base_mismatch",
+                    format!("PEB.ImageBaseAddress 0x{:X} is not a loaded module", peb.image_base), findings);
+            }
+            if !peb.ldr_modules.is_empty() {
+                for module in user.modules.iter().filter(|m| !ldr_bases.contains(&m.base)) {
+                    self.anomaly(img, peb_addr, "unlinked_module",
+                        format!("{} at 0x{:X} is missing from the PEB loader list", module.name, module.base), findings);
+                }
+                for (base, _, name) in peb.ldr_modules.iter().filter(|(base, _, _)| !kernel_bases.contains(base)) {
+                    self.anomaly(img, peb_addr, "phantom_ldr_entry",
+                        format!("Loader entry {} at 0x{:X} has no mapped image", name, base), findings);
+                }
+            }
+        }
+    }
+}
+
+impl MemoryPlugin for PebScanner {
+    fn name(&self) -> &'static str {
+        "peb"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { passes: 0, user_dump: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Parses PEB/TEB structures and flags anti-debugging tampering and loader mismatches"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Parsing PEB/TEB structures");
+
+        // Needs a process address space, which only user-mode dumps provide
+        if let Some(user) = &img.info.user {
+            progress.set_length(user.threads.len() as u64);
+            self.check(img, user, &mut findings);
+        }
+
+        progress.finish_with_message(format!("Found {} PEB findings", findings.len()));
+        findings
+    }
+}
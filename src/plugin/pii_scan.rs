@@ -0,0 +1,331 @@
+//! Personal data in memory
+//!
+//! Finds email addresses, phone numbers, payment card numbers and IBANs in
+//! physical memory, as ASCII and UTF-16LE, so the exposure of personal data
+//! in a capture can be assessed. Each candidate a regex finds is validated
+//! before it is reported: card numbers must pass the Luhn check and start
+//! with a known issuer prefix, IBANs must have their country's length and a
+//! valid mod-97 checksum, and phone numbers must have a plausible number of
+//! digits. Values are masked unless the plugin is run with `--reveal`; a
+//! masked card keeps the first six and last four digits, as PCI DSS allows.
+//!
+//! `--set detectors=card,iban` limits the scan to some of `email`, `phone`,
+//! `card` and `iban`.
+
+use anyhow::{bail, Result};
+use indicatif::ProgressBar;
+use regex::bytes::{Regex, RegexBuilder};
+use std::collections::HashMap;
+
+use crate::paging::MemoryImage;
+use crate::scan_util::{chunks, CHUNK_SIZE};
+use super::pattern_scan::{narrow_utf16, TextEncoding};
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+/// Bytes read past a chunk for a value starting in it
+const OVERLAP: usize = 0x200;
+/// Extensions that follow an `@` in file names such as `icon@2x.png`
+const FILE_EXTENSIONS: [&str; 9] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "js", "css", "dll"];
+/// IBAN lengths by country
+const IBAN_LENGTHS: [(&str, usize); 36] = [
+    ("AD", 24), ("AE", 23), ("AT", 20), ("BE", 16), ("BG", 22), ("BR", 29), ("CH", 21), ("CY", 28), ("CZ", 24),
+    ("DE", 22), ("DK", 18), ("EE", 20), ("ES", 24), ("FI", 18), ("FR", 27), ("GB", 22), ("GR", 27), ("HR", 21),
+    ("HU", 28), ("IE", 22), ("IS", 26), ("IT", 27), ("LI", 21), ("LT", 20), ("LU", 20), ("LV", 21), ("MC", 27),
+    ("MT", 31), ("NL", 18), ("NO", 15), ("PL", 28), ("PT", 25), ("RO", 24), ("SA", 24), ("SE", 24), ("SI", 19),
+];
+
+/// The kinds of personal data the plugin finds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PiiKind {
+    Email,
+    Phone,
+    PaymentCard,
+    Iban,
+}
+
+impl PiiKind {
+    pub const ALL: [PiiKind; 4] = [PiiKind::Email, PiiKind::Phone, PiiKind::PaymentCard, PiiKind::Iban];
+
+    /// Name in `--set detectors=`
+    pub fn detector(self) -> &'static str {
+        match self {
+            PiiKind::Email => "email",
+            PiiKind::Phone => "phone",
+            PiiKind::PaymentCard => "card",
+            PiiKind::Iban => "iban",
+        }
+    }
+
+    fn rule(self) -> &'static str {
+        match self {
+            PiiKind::PaymentCard => "payment_card",
+            kind => kind.detector(),
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            PiiKind::Email => "Email address",
+            PiiKind::Phone => "Phone number",
+            PiiKind::PaymentCard => "Payment card number",
+            PiiKind::Iban => "IBAN",
+        }
+    }
+}
+
+/// Whether the digits of `number` pass the Luhn check
+pub fn luhn_valid(number: &str) -> bool {
+    let mut sum = 0;
+    for (index, c) in number.chars().rev().enumerate() {
+        let Some(mut digit) = c.to_digit(10) else { return false };
+        if index % 2 == 1 {
+            digit *= 2;
+            if digit > 9 {
+                digit -= 9;
+            }
+        }
+        sum += digit;
+    }
+    !number.is_empty() && sum % 10 == 0
+}
+
+/// The card network whose issuer prefix and length `number` has
+pub fn card_network(number: &str) -> Option<&'static str> {
+    let prefix = |len: usize| number.get(..len).and_then(|p| p.parse::<u32>().ok()).unwrap_or(0);
+    let len = number.len();
+    match () {
+        _ if number.starts_with('4') && matches!(len, 13 | 16 | 19) => Some("Visa"),
+        _ if ((51..=55).contains(&prefix(2)) || (2221..=2720).contains(&prefix(4))) && len == 16 => Some("Mastercard"),
+        _ if matches!(prefix(2), 34 | 37) && len == 15 => Some("American Express"),
+        _ if (prefix(4) == 6011 || prefix(2) == 65 || (644..=649).contains(&prefix(3))) && (16..=19).contains(&len) => Some("Discover"),
+        _ if (3528..=3589).contains(&prefix(4)) && (16..=19).contains(&len) => Some("JCB"),
+        _ if (prefix(2) == 36 || (300..=305).contains(&prefix(3))) && (14..=19).contains(&len) => Some("Diners Club"),
+        _ => None,
+    }
+}
+
+/// Whether `iban`, without spaces, has its country's length and a valid checksum
+pub fn iban_valid(iban: &str) -> bool {
+    let Some(&(_, len)) = IBAN_LENGTHS.iter().find(|(country, _)| iban.starts_with(country)) else { return false };
+    if iban.len() != len || !iban.bytes().all(|b| b.is_ascii_digit() || b.is_ascii_uppercase()) {
+        return false;
+    }
+    // The country and check digits move to the end, letters count as 10-35
+    let rearranged = iban[4..].bytes().chain(iban[..4].bytes());
+    rearranged.fold(0u64, |remainder, b| {
+        let value = if b.is_ascii_digit() { (b - b'0') as u64 } else { (b - b'A') as u64 + 10 };
+        (remainder * if value > 9 { 100 } else { 10 } + value) % 97
+    }) == 1
+}
+
+/// `value` as reported unless revealed: enough left to tell values apart,
+/// separators kept
+pub fn mask_pii(kind: PiiKind, value: &str) -> String {
+    let (head, tail) = match kind {
+        PiiKind::Email => {
+            let (local, domain) = value.split_once('@').unwrap_or((value, ""));
+            return format!("{}{}@{}", &local[..1.min(local.len())], "*".repeat(local.len().saturating_sub(1)), domain);
+        }
+        PiiKind::Phone => (0, 4),
+        PiiKind::PaymentCard => (6, 4),
+        PiiKind::Iban => (4, 4),
+    };
+    let total = value.chars().filter(char::is_ascii_alphanumeric).count();
+    let mut seen = 0;
+    value.chars().map(|c| {
+        if !c.is_ascii_alphanumeric() {
+            return c;
+        }
+        seen += 1;
+        if seen <= head || seen > total.saturating_sub(tail) { c } else { '*' }
+    }).collect()
+}
+
+/// A validated value and what it is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiMatch {
+    pub offset: usize,
+    pub kind: PiiKind,
+    /// As found in memory
+    pub value: String,
+    /// Card network or IBAN country
+    pub detail: Option<String>,
+}
+
+/// A plugin that finds personal data in physical memory
+pub struct PiiScanner {
+    pub reveal: bool,
+    pub detectors: Vec<PiiKind>,
+    email: Regex,
+    phone: Regex,
+    card: Regex,
+    iban: Regex,
+}
+
+impl Default for PiiScanner {
+    fn default() -> Self {
+        PiiScanner::new(false)
+    }
+}
+
+impl PiiScanner {
+    pub fn new(reveal: bool) -> Self {
+        let build = |pattern: &str| RegexBuilder::new(pattern).unicode(false).build().unwrap();
+        PiiScanner {
+            reveal,
+            detectors: PiiKind::ALL.to_vec(),
+            email: build(r"(?i)\b[a-z0-9][a-z0-9._%+-]{0,63}@(?:[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?\.)+[a-z]{2,24}\b"),
+            phone: build(r"(?:\+[1-9][0-9]{0,2}(?:[ .-]?\(?[0-9]{1,4}\)?){2,5}|\(?\b[2-9][0-9]{2}\)?[ .-][2-9][0-9]{2}[ .-][0-9]{4})\b"),
+            card: build(r"\b[2-6][0-9]{3}(?:[ -]?[0-9]{2,4}){2,4}(?:[ -]?[0-9]{1,3})?\b"),
+            iban: build(r"\b[A-Z]{2}[0-9]{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b"),
+        }
+    }
+
+    /// Validated personal data in `data`, by offset
+    pub fn find(&self, data: &[u8]) -> Vec<PiiMatch> {
+        let mut found = Vec::new();
+        for &kind in &self.detectors {
+            let regex = match kind {
+                PiiKind::Email => &self.email,
+                PiiKind::Phone => &self.phone,
+                PiiKind::PaymentCard => &self.card,
+                PiiKind::Iban => &self.iban,
+            };
+            for m in regex.find_iter(data) {
+                // A number running on from other digits is part of something else
+                let before = m.start().checked_sub(1).map(|at| data[at]);
+                if kind != PiiKind::Email && before.is_some_and(|b| b.is_ascii_digit() || (kind == PiiKind::Phone && b == b'+')) {
+                    continue;
+                }
+                let value = String::from_utf8_lossy(m.as_bytes()).to_string();
+                if let Some(detail) = validate(kind, &value) {
+                    found.push(PiiMatch { offset: m.start(), kind, value, detail });
+                }
+            }
+        }
+        found.sort_by_key(|m| m.offset);
+        found
+    }
+
+    fn report(&self, addr: u64, found: &PiiMatch, encoding: TextEncoding) -> Finding {
+        let shown = if self.reveal { found.value.clone() } else { mask_pii(found.kind, &found.value) };
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "pii".to_string());
+        details.insert("rule".to_string(), found.kind.rule().to_string());
+        details.insert("value".to_string(), shown.clone());
+        details.insert("encoding".to_string(), encoding.to_string());
+        details.insert("redacted".to_string(), (!self.reveal).to_string());
+        match (found.kind, &found.detail) {
+            (PiiKind::PaymentCard, Some(network)) => { details.insert("network".to_string(), network.clone()); }
+            (PiiKind::Iban, Some(country)) => { details.insert("country".to_string(), country.clone()); }
+            _ => {}
+        }
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} {} at 0x{:X}", found.kind.description(), shown, addr),
+            confidence: match found.kind {
+                PiiKind::Email => 50,
+                PiiKind::Phone => 30,
+                PiiKind::PaymentCard => 70,
+                PiiKind::Iban => 80,
+            },
+            details,
+        }
+    }
+}
+
+/// Why a candidate is what it looks like, `None` if it is not: the card
+/// network or IBAN country, nothing more for an email or phone number
+fn validate(kind: PiiKind, value: &str) -> Option<Option<String>> {
+    let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+    match kind {
+        PiiKind::Email => {
+            let (local, domain) = value.split_once('@')?;
+            let tld = domain.rsplit('.').next()?.to_lowercase();
+            let valid = !local.ends_with('.') && !local.contains("..") && !FILE_EXTENSIONS.contains(&tld.as_str());
+            valid.then_some(None)
+        }
+        PiiKind::Phone => {
+            let range = if value.starts_with('+') { 8..=15 } else { 10..=10 };
+            range.contains(&digits.len()).then_some(None)
+        }
+        PiiKind::PaymentCard => {
+            // One kind of separator, used throughout or not at all
+            let separators: Vec<char> = value.chars().filter(|c| !c.is_ascii_digit()).collect();
+            if separators.windows(2).any(|pair| pair[0] != pair[1]) || digits.bytes().all(|b| b == digits.as_bytes()[0]) {
+                return None;
+            }
+            let network = card_network(&digits)?;
+            luhn_valid(&digits).then(|| Some(network.to_string()))
+        }
+        PiiKind::Iban => {
+            let iban: String = value.chars().filter(|c| *c != ' ').collect();
+            iban_valid(&iban).then(|| Some(iban[..2].to_string()))
+        }
+    }
+}
+
+impl MemoryPlugin for PiiScanner {
+    fn name(&self) -> &'static str {
+        "pii_scan"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Low
+    }
+
+    fn description(&self) -> &'static str {
+        "Finds email addresses, phone numbers, Luhn-valid card numbers and checksummed IBANs (masked unless --reveal)"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        let detectors: Vec<&str> = self.detectors.iter().map(|kind| kind.detector()).collect();
+        vec![("reveal", self.reveal.to_string()), ("detectors", detectors.join(","))]
+    }
+
+    fn revealing(&self) -> Option<Box<dyn MemoryPlugin>> {
+        let mut plugin = PiiScanner::new(true);
+        plugin.detectors = self.detectors.clone();
+        Some(Box::new(plugin))
+    }
+
+    fn configure(&self, settings: &HashMap<String, String>) -> Result<Box<dyn MemoryPlugin>> {
+        let mut plugin = PiiScanner::new(self.reveal);
+        for (key, value) in settings {
+            match key.as_str() {
+                "detectors" => {
+                    plugin.detectors = value.split(',').map(|name| {
+                        let name = name.trim();
+                        PiiKind::ALL.into_iter().find(|kind| kind.detector() == name)
+                            .ok_or_else(|| anyhow::anyhow!("Unknown PII detector '{}' (email, phone, card, iban)", name))
+                    }).collect::<Result<_>>()?;
+                }
+                _ => bail!("Plugin 'pii_scan' takes no setting '{}' (detectors)", key),
+            }
+        }
+        Ok(Box::new(plugin))
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Scanning for personal data");
+        for window in chunks(img, CHUNK_SIZE, OVERLAP).with_progress(progress) {
+            for found in self.find(window.data).into_iter().filter(|m| window.owns(m.offset)) {
+                findings.push(self.report(window.addr(found.offset), &found, TextEncoding::Ascii));
+            }
+            for alignment in 0..2 {
+                for mut found in self.find(&narrow_utf16(window.data, alignment)) {
+                    found.offset = alignment + found.offset * 2;
+                    if window.owns(found.offset) {
+                        findings.push(self.report(window.addr(found.offset), &found, TextEncoding::Utf16Le));
+                    }
+                }
+            }
+        }
+        findings.sort_by_key(|f| f.addr);
+        let cards = findings.iter().filter(|f| f.details["rule"] == "payment_card").count();
+        progress.finish_with_message(format!("Found {} personal data values, {} card numbers", findings.len(), cards));
+        findings
+    }
+}
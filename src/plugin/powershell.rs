@@ -0,0 +1,288 @@
+//! PowerShell script blocks, encoded commands and PSReadLine history
+//!
+//! PowerShell holds the text of every script block it compiles as a .NET
+//! string, UTF-16LE on the managed heap, long after the script ran. The
+//! scanner searches the private and pagefile-backed memory of PowerShell
+//! hosts (`powershell.exe`, `pwsh.exe`, the ISE, and any process that
+//! loaded System.Management.Automation, as unmanaged PowerShell tools do)
+//! for runs of UTF-16 text that read as PowerShell, reporting each distinct
+//! script once with the number of copies. `-EncodedCommand` arguments in
+//! that text are decoded from base64, and a mapped view of PSReadLine's
+//! `ConsoleHost_history.txt` yields the interactive history; history lines
+//! PSReadLine only holds as strings are indistinguishable from other
+//! script text and surface as script blocks.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use indicatif::ProgressBar;
+use regex::Regex;
+use std::collections::HashMap;
+
+use crate::dlllist::process_dlls;
+use crate::kdbg::OsContext;
+use crate::modules::read_pages;
+use crate::paging::MemoryImage;
+use crate::processes::{Process, ProcessFinder, WindowsProcessFinder};
+use crate::vad::{VadKind, VadRegion};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Process names of PowerShell hosts, as truncated in the EPROCESS
+const POWERSHELL_HOSTS: [&str; 3] = ["powershell.exe", "pwsh.exe", "powershell_ise"];
+/// The engine every host loads, natively compiled or not
+const AUTOMATION_DLL: &str = "system.management.automation";
+const HISTORY_FILE: &str = "consolehost_history.txt";
+/// Shortest text taken for a script, and longest read of one
+const MIN_SCRIPT_CHARS: usize = 32;
+const MAX_SCRIPT_CHARS: usize = 0x8000;
+/// Characters of a script kept in its finding
+const MAX_SHOWN: usize = 0x1000;
+/// Process memory is read a chunk at a time, each running into the next
+/// far enough for the longest script
+const CHUNK: u64 = 0x100000;
+const MAX_REGION_SIZE: u64 = 0x4000_0000;
+/// Distinct scripts reported per process
+const MAX_SCRIPTS: usize = 2000;
+
+/// Substrings, in lower case, common in malicious PowerShell
+const INDICATORS: [&str; 16] = [
+    "downloadstring", "downloadfile", "downloaddata", "frombase64string", "invoke-expression", "iex ", "iex(",
+    "net.webclient", "reflection.assembly", "virtualalloc", "amsiutils", "amsiinitfailed", "-bxor",
+    "invoke-mimikatz", "add-mppreference", "start-bitstransfer",
+];
+
+/// Runs of at least `min_chars` printable UTF-16LE characters at even
+/// offsets of `data`, by offset
+pub fn utf16_runs(data: &[u8], min_chars: usize) -> Vec<(usize, String)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    let mut text = String::new();
+    for (index, unit) in data.chunks_exact(2).enumerate() {
+        let c = u16::from_le_bytes([unit[0], unit[1]]);
+        let printable = (0x20..0x7F).contains(&c) || matches!(c, 0x09 | 0x0A | 0x0D);
+        if printable && text.len() < MAX_SCRIPT_CHARS {
+            start.get_or_insert(index * 2);
+            text.push(c as u8 as char);
+            continue;
+        }
+        if let Some(at) = start.take() {
+            if text.len() >= min_chars {
+                runs.push((at, std::mem::take(&mut text)));
+            }
+            text.clear();
+        }
+        // A character past the longest script starts the next run
+        if printable {
+            start = Some(index * 2);
+            text.push(c as u8 as char);
+        }
+    }
+    if let Some(at) = start.filter(|_| text.len() >= min_chars) {
+        runs.push((at, text));
+    }
+    runs
+}
+
+/// The indicators of malicious PowerShell in `text`
+pub fn script_indicators(text: &str) -> Vec<&'static str> {
+    let lower = text.to_lowercase();
+    INDICATORS.iter().copied().filter(|indicator| lower.contains(indicator)).collect()
+}
+
+/// A script, encoded command or history found in a process
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerShellText {
+    pub kind: PowerShellKind,
+    /// Virtual address of the first copy
+    pub address: u64,
+    pub text: String,
+    pub copies: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerShellKind {
+    ScriptBlock,
+    /// An `-EncodedCommand` argument, decoded
+    EncodedCommand,
+    History,
+}
+
+impl PowerShellKind {
+    fn rule(self) -> &'static str {
+        match self {
+            PowerShellKind::ScriptBlock => "script_block",
+            PowerShellKind::EncodedCommand => "encoded_command",
+            PowerShellKind::History => "psreadline_history",
+        }
+    }
+}
+
+/// A plugin that recovers PowerShell scripts and history from PowerShell hosts
+pub struct PowerShellScanner {
+    /// `-e`, `-ec`, `-enc` or any other prefix of `-EncodedCommand`, and its argument
+    encoded: Regex,
+    /// Verb-Noun cmdlet names and variables that make text read as PowerShell
+    cmdlet: Regex,
+    variable: Regex,
+}
+
+impl Default for PowerShellScanner {
+    fn default() -> Self {
+        PowerShellScanner {
+            encoded: Regex::new(r#"(?i)(?:^|\s)[-/]e(?:c|n[a-z]*)?\s+['"]?([A-Za-z0-9+/]{20,}={0,2})"#).unwrap(),
+            cmdlet: Regex::new(r"\b[A-Z][a-z]{1,14}-[A-Z][A-Za-z]{2,}\b").unwrap(),
+            variable: Regex::new(r"\$[A-Za-z_][A-Za-z0-9_:]*").unwrap(),
+        }
+    }
+}
+
+impl PowerShellScanner {
+    /// Whether carved text reads as PowerShell: a cmdlet and a variable,
+    /// pipeline or script block
+    pub fn is_script(&self, text: &str) -> bool {
+        self.cmdlet.is_match(text) && (self.variable.is_match(text) || text.contains(" | ") || text.contains('{'))
+    }
+
+    /// The commands of the `-EncodedCommand` arguments in `text`, decoded
+    pub fn encoded_commands(&self, text: &str) -> Vec<String> {
+        self.encoded.captures_iter(text).filter_map(|captures| {
+            let bytes = STANDARD.decode(&captures[1]).ok()?;
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+            let command = String::from_utf16(&units).ok()?;
+            // Base64 that is not UTF-16 text is some other switch's argument
+            command.chars().all(|c| !c.is_control() || c.is_whitespace()).then_some(command)
+        }).collect()
+    }
+
+    /// Scripts and encoded commands carved from `region` of `space`
+    fn carve_region(&self, space: &MemoryImage, region: &VadRegion, found: &mut Vec<PowerShellText>) {
+        let mut offset = 0;
+        while offset < region.size() && found.len() < MAX_SCRIPTS {
+            let len = (CHUNK + MAX_SCRIPT_CHARS as u64 * 2).min(region.size() - offset);
+            let (data, missing) = read_pages(space, region.start + offset, len);
+            if missing as u64 == len.div_ceil(0x1000) {
+                offset += CHUNK;
+                continue;
+            }
+            // A run at the start of a later chunk is the end of one already taken
+            for (at, text) in utf16_runs(&data, MIN_SCRIPT_CHARS).into_iter().filter(|&(at, _)| (at as u64) < CHUNK && (at != 0 || offset == 0)) {
+                let address = region.start + offset + at as u64;
+                for command in self.encoded_commands(&text) {
+                    add_text(found, PowerShellKind::EncodedCommand, address, command);
+                }
+                if self.is_script(&text) {
+                    add_text(found, PowerShellKind::ScriptBlock, address, text);
+                }
+            }
+            offset += CHUNK;
+        }
+    }
+
+    /// Scripts, encoded commands and history of `process`
+    pub fn process_texts(&self, img: &MemoryImage, finder: &WindowsProcessFinder, process: &Process) -> Vec<PowerShellText> {
+        let Some(space) = process.address_space(img) else { return Vec::new() };
+        let mut found = Vec::new();
+        for region in finder.vads(img, process).iter().filter(|vad| vad.size() <= MAX_REGION_SIZE) {
+            match (&region.kind, &region.file) {
+                (VadKind::Mapped, Some(file)) if file.to_lowercase().ends_with(HISTORY_FILE) => {
+                    let (data, _) = read_pages(&space, region.start, region.size());
+                    let history = String::from_utf8_lossy(&data[..data.iter().position(|&b| b == 0).unwrap_or(data.len())]).trim_end().to_string();
+                    if !history.is_empty() {
+                        add_text(&mut found, PowerShellKind::History, region.start, history);
+                    }
+                }
+                (VadKind::Private, _) | (VadKind::Mapped, None) => self.carve_region(&space, region, &mut found),
+                _ => {}
+            }
+        }
+        found
+    }
+
+    fn report(&self, addr: u64, process: &Process, host: &str, found: &PowerShellText) -> Finding {
+        let indicators = script_indicators(&found.text);
+        let shown: String = found.text.chars().take(MAX_SHOWN).collect();
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "powershell".to_string());
+        details.insert("rule".to_string(), found.kind.rule().to_string());
+        details.insert("pid".to_string(), process.pid.to_string());
+        details.insert("process".to_string(), process.name.clone());
+        details.insert("host".to_string(), host.to_string());
+        details.insert("address".to_string(), format!("{:#x}", found.address));
+        details.insert("length".to_string(), found.text.len().to_string());
+        details.insert("copies".to_string(), found.copies.to_string());
+        details.insert("indicators".to_string(), indicators.join(","));
+        details.insert("text".to_string(), shown);
+        let (what, base) = match found.kind {
+            PowerShellKind::ScriptBlock => ("Script block", 30),
+            PowerShellKind::EncodedCommand => ("Decoded -EncodedCommand", 70),
+            PowerShellKind::History => (if found.text.lines().count() == 1 { "PSReadLine history line" } else { "PSReadLine history" }, 40),
+        };
+        let first_line = found.text.lines().next().unwrap_or_default();
+        let first_line: String = first_line.chars().take(80).collect();
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} in {} (PID {}): {}", what, process.name, process.pid, first_line.trim()),
+            confidence: (base + 15 * indicators.len()).min(95) as u8,
+            details,
+        }
+    }
+}
+
+/// Record `text`, or one more copy of it
+fn add_text(found: &mut Vec<PowerShellText>, kind: PowerShellKind, address: u64, text: String) {
+    match found.iter_mut().find(|seen| seen.kind == kind && seen.text == text) {
+        Some(seen) => seen.copies += 1,
+        None => found.push(PowerShellText { kind, address, text, copies: 1 }),
+    }
+}
+
+impl MemoryPlugin for PowerShellScanner {
+    fn name(&self) -> &'static str {
+        "powershell"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Carves PowerShell script blocks, decoded -EncodedCommand payloads and PSReadLine history from PowerShell host processes"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Locating processes");
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else {
+            progress.finish_with_message("No kernel found; powershell needs the process list");
+            return findings;
+        };
+        let finder = WindowsProcessFinder::new().with_os_context(os);
+        let processes = finder.find_processes(img, &ProgressBar::hidden()).unwrap_or_default();
+
+        progress.set_message("Carving PowerShell text from host processes");
+        progress.set_length(processes.len() as u64);
+        progress.set_position(0);
+        for process in &processes {
+            progress.inc(1);
+            let name = process.name.to_lowercase();
+            let host = if POWERSHELL_HOSTS.iter().any(|host| name.starts_with(host)) {
+                "powershell"
+            } else if process_dlls(img, &finder, process).dlls.iter().any(|dll| dll.name.to_lowercase().starts_with(AUTOMATION_DLL)) {
+                "unmanaged powershell"
+            } else {
+                continue;
+            };
+            let space = process.address_space(img);
+            for found in self.process_texts(img, &finder, process) {
+                let addr = space.as_ref().and_then(|space| space.virt_to_phys(found.address)).unwrap_or(found.address);
+                findings.push(self.report(addr, process, host, &found));
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} PowerShell scripts, commands and histories", findings.len()));
+        findings
+    }
+}
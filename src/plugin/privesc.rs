@@ -0,0 +1,314 @@
+//! Linux namespace and privilege escalation indicators
+//!
+//! Two sources are combined:
+//!
+//! * `/proc/<pid>/status` snapshots left in memory by `ps`, `top` and
+//!   monitoring agents give each task's ids, capability sets and pid
+//!   namespace nesting (`NSpid`).
+//! * Kernel `struct cred` objects are carved heuristically and checked for
+//!   combinations that normal credential changes never produce, such as a
+//!   full effective capability set on a non-root credential.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::paging::MemoryImage;
+use crate::scan_util::{chunks, find_all, Window, CHUNK_SIZE};
+use super::registry::{MemoryPlugin, Finding};
+
+/// CAP_LAST_CAP is 40 (CAP_CHECKPOINT_RESTORE) since Linux 5.9
+const CAP_FULL_SET: u64 = 0x1ff_ffff_ffff;
+/// Older kernels stop at CAP_AUDIT_READ (37)
+const CAP_FULL_SET_LEGACY: u64 = 0x3f_ffff_ffff;
+/// Docker's default bounding set keeps 14 capabilities
+const MIN_BSET_CAPS: u32 = 14;
+
+/// Programs that legitimately run as root under an unprivileged parent
+const SETUID_LAUNCHERS: &[&str] = &[
+    "su", "sudo", "pkexec", "doas", "passwd", "newgrp", "chsh", "chfn", "mount", "umount",
+    "fusermount", "fusermount3", "polkit-agent-he", "unix_chkpwd", "Xorg",
+];
+
+const STATUS_MARKER: &[u8] = b"Name:\t";
+const STATUS_WINDOW: usize = 2048;
+/// Bytes read past a chunk so a status block or cred starting in it is whole
+const OVERLAP: usize = STATUS_WINDOW;
+
+fn is_full_caps(caps: u64) -> bool {
+    caps == CAP_FULL_SET || caps == CAP_FULL_SET_LEGACY
+}
+
+/// Ids and capability sets of a task, from either source
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Credentials {
+    pub uid: u32,
+    pub euid: u32,
+    pub suid: u32,
+    pub fsuid: u32,
+    pub cap_inheritable: u64,
+    pub cap_permitted: u64,
+    pub cap_effective: u64,
+    pub cap_bset: u64,
+    pub cap_ambient: u64,
+}
+
+impl Credentials {
+    fn is_root(&self) -> bool {
+        self.uid == 0 && self.euid == 0
+    }
+
+    /// Indicators that the credential was tampered with rather than changed by the kernel
+    pub fn anomalies(&self) -> Vec<&'static str> {
+        let mut found = Vec::new();
+        if !self.is_root() && self.uid != 0 && is_full_caps(self.cap_effective) {
+            found.push("cap_full_non_root");
+        }
+        if self.euid == 0 && self.uid != 0 && self.suid != 0 {
+            // A setuid exec also sets suid, so euid alone flipping to 0 is an overwrite
+            found.push("euid_overwrite");
+        }
+        if self.cap_effective & !self.cap_permitted != 0 {
+            found.push("effective_exceeds_permitted");
+        }
+        if is_full_caps(self.cap_ambient) {
+            found.push("ambient_full");
+        }
+        found
+    }
+}
+
+/// Parse a kernel `struct cred` at the start of `data`
+///
+/// `ids_at` is 4 for kernels with an `atomic_t usage` and 8 for the
+/// `atomic_long_t` counter used since 6.8. Returns None unless every field is
+/// plausible, which keeps zeroed and random memory from matching.
+pub fn parse_cred(data: &[u8], ids_at: usize) -> Option<Credentials> {
+    let u32_at = |off: usize| data.get(off..off + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+    let u64_at = |off: usize| data.get(off..off + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()));
+
+    let usage = if ids_at == 8 { u64_at(0)? } else { u32_at(0)? as u64 };
+    if usage == 0 || usage > 0x10000 {
+        return None;
+    }
+
+    // uid, gid, suid, sgid, euid, egid, fsuid, fsgid
+    let ids: Vec<u32> = (0..8).map(|i| u32_at(ids_at + i * 4)).collect::<Option<_>>()?;
+    if ids.iter().any(|&id| id > 0xFFFF && id != u32::MAX) {
+        return None;
+    }
+    let securebits = u32_at(ids_at + 32)?;
+    if securebits > 0xFF {
+        return None;
+    }
+
+    let caps_at = (ids_at + 36 + 7) & !7;
+    let caps: Vec<u64> = (0..5).map(|i| u64_at(caps_at + i * 8)).collect::<Option<_>>()?;
+    if caps.iter().any(|&c| c & !CAP_FULL_SET != 0) || caps[3].count_ones() < MIN_BSET_CAPS {
+        return None;
+    }
+
+    Some(Credentials {
+        uid: ids[0],
+        suid: ids[2],
+        euid: ids[4],
+        fsuid: ids[6],
+        cap_inheritable: caps[0],
+        cap_permitted: caps[1],
+        cap_effective: caps[2],
+        cap_bset: caps[3],
+        cap_ambient: caps[4],
+    })
+}
+
+/// A task as described by a `/proc/<pid>/status` snapshot
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskStatus {
+    pub name: String,
+    pub pid: u32,
+    pub ppid: u32,
+    pub creds: Credentials,
+    pub ns_pids: Vec<u32>,
+    pub no_new_privs: bool,
+    pub seccomp: u32,
+}
+
+/// Parse a `/proc/<pid>/status` text block starting at its `Name:` line
+pub fn parse_status(text: &str) -> Option<TaskStatus> {
+    let mut task = TaskStatus::default();
+    let mut seen_pid = false;
+    let mut seen_caps = false;
+
+    for line in text.lines() {
+        let (key, value) = match line.split_once(":\t") {
+            Some(kv) => kv,
+            None => break,
+        };
+        let hex = || u64::from_str_radix(value.trim(), 16).ok();
+        let fields = || value.split_whitespace().filter_map(|v| v.parse::<u32>().ok()).collect::<Vec<_>>();
+
+        match key {
+            "Name" if task.name.is_empty() => task.name = value.to_string(),
+            // A second Name line means the next snapshot has started
+            "Name" => break,
+            "Pid" => {
+                task.pid = value.trim().parse().ok()?;
+                seen_pid = true;
+            }
+            "PPid" => task.ppid = value.trim().parse().ok()?,
+            "Uid" => {
+                let ids = fields();
+                if ids.len() != 4 {
+                    return None;
+                }
+                task.creds.uid = ids[0];
+                task.creds.euid = ids[1];
+                task.creds.suid = ids[2];
+                task.creds.fsuid = ids[3];
+            }
+            "NSpid" => task.ns_pids = fields(),
+            "CapInh" => task.creds.cap_inheritable = hex()?,
+            "CapPrm" => task.creds.cap_permitted = hex()?,
+            "CapEff" => {
+                task.creds.cap_effective = hex()?;
+                seen_caps = true;
+            }
+            "CapBnd" => task.creds.cap_bset = hex()?,
+            "CapAmb" => task.creds.cap_ambient = hex()?,
+            "NoNewPrivs" => task.no_new_privs = value.trim() == "1",
+            "Seccomp" => task.seccomp = value.trim().parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+
+    (seen_pid && seen_caps && !task.name.is_empty()).then_some(task)
+}
+
+/// A plugin that reports Linux task namespaces, capabilities and credential anomalies
+#[derive(Default)]
+pub struct PrivescScanner;
+
+impl PrivescScanner {
+    fn scan_statuses(&self, window: &Window, tasks: &mut HashMap<u32, (u64, TaskStatus)>) {
+        let chunk = window.data;
+        for pos in find_all(chunk, STATUS_MARKER).filter(|&pos| window.owns(pos)) {
+            if pos > 0 && chunk[pos - 1] != b'\n' && chunk[pos - 1] != 0 {
+                continue;
+            }
+            let block = &chunk[pos..(pos + STATUS_WINDOW).min(chunk.len())];
+            let text = String::from_utf8_lossy(block);
+            if let Some(task) = parse_status(&text) {
+                tasks.entry(task.pid).or_insert((window.addr(pos), task));
+            }
+        }
+    }
+
+    fn scan_creds(&self, window: &Window, findings: &mut Vec<Finding>) {
+        let chunk = window.data;
+        for off in window.owned.clone().step_by(8) {
+            for ids_at in [4, 8] {
+                let creds = match parse_cred(&chunk[off..], ids_at) {
+                    Some(creds) => creds,
+                    None => continue,
+                };
+                let anomalies = creds.anomalies();
+                if anomalies.is_empty() {
+                    continue;
+                }
+
+                let mut details = cred_details(&creds);
+                details.insert("type".to_string(), "cred_anomaly".to_string());
+                details.insert("rule".to_string(), "cred_struct".to_string());
+                details.insert("anomalies".to_string(), anomalies.join(","));
+                findings.push(Finding {
+                    plugin: self.name().to_string(),
+                    addr: window.addr(off),
+                    desc: format!("struct cred uid={} euid={}: {}", creds.uid, creds.euid, anomalies.join(", ")),
+                    confidence: 60,
+                    details,
+                });
+                break;
+            }
+        }
+    }
+}
+
+fn cred_details(creds: &Credentials) -> HashMap<String, String> {
+    let mut details = HashMap::new();
+    details.insert("uid".to_string(), creds.uid.to_string());
+    details.insert("euid".to_string(), creds.euid.to_string());
+    details.insert("cap_effective".to_string(), format!("{:016x}", creds.cap_effective));
+    details.insert("cap_permitted".to_string(), format!("{:016x}", creds.cap_permitted));
+    details.insert("cap_bset".to_string(), format!("{:016x}", creds.cap_bset));
+    details
+}
+
+impl MemoryPlugin for PrivescScanner {
+    fn name(&self) -> &'static str {
+        "linux_privesc"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reports Linux task namespaces, capabilities and privilege escalation indicators"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut tasks = HashMap::new();
+
+        progress.set_message("Scanning for task credentials");
+
+        for window in chunks(img, CHUNK_SIZE, OVERLAP).with_progress(progress) {
+            self.scan_statuses(&window, &mut tasks);
+            self.scan_creds(&window, &mut findings);
+        }
+
+        // One finding per task, flagging root tasks launched by unprivileged parents
+        let uids: HashMap<u32, u32> = tasks.values().map(|(_, t)| (t.pid, t.creds.uid)).collect();
+        let mut ordered: Vec<_> = tasks.into_values().collect();
+        ordered.sort_by_key(|(_, t)| t.pid);
+
+        for (addr, task) in ordered {
+            let mut anomalies = task.creds.anomalies();
+            let parent_uid = uids.get(&task.ppid).copied();
+            if task.creds.is_root()
+                && parent_uid.is_some_and(|uid| uid != 0)
+                && !SETUID_LAUNCHERS.contains(&task.name.as_str())
+            {
+                anomalies.push("root_from_unprivileged_parent");
+            }
+
+            let mut details = cred_details(&task.creds);
+            details.insert("type".to_string(), "task_creds".to_string());
+            details.insert("rule".to_string(), "proc_status".to_string());
+            details.insert("pid".to_string(), task.pid.to_string());
+            details.insert("ppid".to_string(), task.ppid.to_string());
+            details.insert("name".to_string(), task.name.clone());
+            details.insert("pid_ns_level".to_string(), task.ns_pids.len().saturating_sub(1).to_string());
+            if task.ns_pids.len() > 1 {
+                let ns_pids: Vec<String> = task.ns_pids.iter().map(u32::to_string).collect();
+                details.insert("ns_pids".to_string(), ns_pids.join(","));
+            }
+            details.insert("no_new_privs".to_string(), task.no_new_privs.to_string());
+            details.insert("seccomp".to_string(), task.seccomp.to_string());
+            if !anomalies.is_empty() {
+                details.insert("anomalies".to_string(), anomalies.join(","));
+            }
+
+            let desc = if anomalies.is_empty() {
+                format!("{} (pid {}) uid={} caps={:x}", task.name, task.pid, task.creds.uid, task.creds.cap_effective)
+            } else {
+                format!("{} (pid {}) uid={}: {}", task.name, task.pid, task.creds.uid, anomalies.join(", "))
+            };
+            findings.push(Finding {
+                plugin: self.name().to_string(),
+                addr,
+                desc,
+                confidence: if anomalies.is_empty() { 50 } else { 85 },
+                details,
+            });
+        }
+
+        progress.finish_with_message(format!("Found {} credential findings", findings.len()));
+        findings
+    }
+}
@@ -1,9 +1,15 @@
 //! Plugin registry system for memory forensics plugins
 
-use anyhow::{Result, Context};
-use colored::*;
+use anyhow::{bail, Result};
+#[cfg(feature = "plugins")]
+use anyhow::Context;
 use indicatif::ProgressBar;
-use std::{collections::HashMap, sync::{RwLock, Arc}, path::PathBuf};
+use sha2::{Digest, Sha256};
+use std::{collections::{BTreeMap, HashMap}, sync::{RwLock, Arc}};
+#[cfg(feature = "plugins")]
+use std::path::PathBuf;
+use serde::Serialize;
+use crate::output::Record;
 use crate::paging::MemoryImage;
 
 /// Represents a finding from a memory forensics plugin
@@ -16,6 +22,242 @@ pub struct Finding {
     pub details: HashMap<String, String>, // Additional details as key-value pairs
 }
 
+/// Detail keys added after a scan to annotate findings; they do not change a finding's ID
+const ANNOTATION_KEYS: &[&str] = &[
+    "va", "module", "module_sha256", "provenance", "freed_source", "suppressed_by", "plugin_version", "scan_params",
+];
+
+impl Finding {
+    /// Stable content-derived ID: a hash of the plugin, address, description
+    /// and scan-time details, identical across runs and output formats
+    pub fn id(&self) -> String {
+        let mut hasher = Sha256::new();
+        for part in [self.plugin.as_str(), &format!("{:X}", self.addr), &self.desc] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        for (key, value) in self.sorted_details().into_iter().filter(|(k, _)| !ANNOTATION_KEYS.contains(k)) {
+            hasher.update(key.as_bytes());
+            hasher.update([b'=']);
+            hasher.update(value.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.finalize()[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Details ordered by key, for reproducible serialization
+    pub fn sorted_details(&self) -> BTreeMap<&str, &str> {
+        self.details.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()
+    }
+}
+
+/// A finding as `run-plugin --format json|csv` reports it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FindingRecord {
+    pub id: String,
+    pub plugin: String,
+    pub address: String,
+    /// Virtual address, for findings in user-mode dumps
+    pub va: Option<String>,
+    pub confidence: u8,
+    pub rule: Option<String>,
+    pub description: String,
+    /// Triage state, with `--case`
+    pub triage: Option<String>,
+    pub details: BTreeMap<String, String>,
+}
+
+impl Record for FindingRecord {
+    const SCHEMA: &'static str = "rmf.finding";
+    const TABLE: &'static str = "findings";
+    const COLUMNS: &'static [&'static str] = &["id", "plugin", "address", "va", "confidence", "rule", "description", "triage", "details"];
+}
+
+impl FindingRecord {
+    pub fn new(finding: &Finding, triage: Option<String>) -> Self {
+        FindingRecord {
+            id: finding.id(),
+            plugin: finding.plugin.clone(),
+            address: format!("0x{:X}", finding.addr),
+            va: finding.details.get("va").cloned(),
+            confidence: finding.confidence,
+            rule: finding.details.get("rule").cloned(),
+            description: finding.desc.clone(),
+            triage,
+            details: finding.details.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        }
+    }
+}
+
+/// File formats `run-plugin --output` exports findings in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A row per finding, with a column per detail key
+    Csv,
+    /// The `rmf.finding` document of `--format json`
+    Json,
+    /// One finding record per line
+    JsonLines,
+    /// The `findings` table of a results database
+    Sqlite,
+}
+
+impl ExportFormat {
+    /// The format named by the extension of `path`: `.json`, `.jsonl` or
+    /// `.ndjson`, a results database, and CSV for anything else
+    pub fn from_path(path: &std::path::Path) -> Self {
+        if crate::output::is_database_path(path) {
+            return ExportFormat::Sqlite;
+        }
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("json") => ExportFormat::Json,
+            Some("jsonl") | Some("ndjson") => ExportFormat::JsonLines,
+            _ => ExportFormat::Csv,
+        }
+    }
+}
+
+/// Columns of a CSV export ahead of the detail keys
+const EXPORT_COLUMNS: [&str; 5] = ["id", "plugin", "address", "confidence", "description"];
+
+/// `records` as `format`; in CSV each detail key any finding carries becomes
+/// a column, sorted by key and prefixed with `details.` where it would clash
+/// with a fixed column, and the triage column is only present with a case
+pub fn export_findings(records: &[FindingRecord], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Sqlite => bail!("Findings are only written to a results database, not as text"),
+        ExportFormat::Json => crate::output::render(records, crate::output::OutputFormat::Json),
+        ExportFormat::JsonLines => {
+            let mut text = String::new();
+            for record in records {
+                text += &serde_json::to_string(record)?;
+                text.push('\n');
+            }
+            Ok(text)
+        }
+        ExportFormat::Csv => {
+            let triage = records.iter().any(|r| r.triage.is_some());
+            let keys: std::collections::BTreeSet<&str> = records.iter().flat_map(|r| r.details.keys().map(String::as_str)).collect();
+            let mut header: Vec<String> = EXPORT_COLUMNS.iter().map(|c| c.to_string()).collect();
+            if triage {
+                header.push("triage".to_string());
+            }
+            header.extend(keys.iter().map(|&key| match EXPORT_COLUMNS.contains(&key) || key == "triage" {
+                true => format!("details.{}", key),
+                false => key.to_string(),
+            }));
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            writer.write_record(&header)?;
+            for record in records {
+                let mut row = vec![
+                    record.id.clone(),
+                    record.plugin.clone(),
+                    record.address.clone(),
+                    record.confidence.to_string(),
+                    record.description.clone(),
+                ];
+                if triage {
+                    row.push(record.triage.clone().unwrap_or_default());
+                }
+                row.extend(keys.iter().map(|&key| record.details.get(key).cloned().unwrap_or_default()));
+                writer.write_record(&row)?;
+            }
+            Ok(String::from_utf8(writer.into_inner()?)?)
+        }
+    }
+}
+
+/// Put findings in a deterministic order: by address, then plugin, then ID
+pub fn sort_findings(findings: &mut [Finding]) {
+    findings.sort_by_cached_key(|f| (f.addr, f.plugin.clone(), f.id()));
+}
+
+/// `key=value` pairs, sorted by key, describing how `plugin` scans `img`:
+/// the plugin's own settings, the paging scheme and the DTB
+pub fn scan_parameters(plugin: &dyn MemoryPlugin, img: &MemoryImage) -> String {
+    let mut params: BTreeMap<&str, String> = plugin.parameters().into_iter().collect();
+    params.insert("arch", format!("{:?}", img.info.arch));
+    if let Some(dtb) = img.info.dtb {
+        params.insert("dtb", format!("0x{:X}", dtb));
+    }
+    params.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(",")
+}
+
+/// Scan with `plugin`, record on every finding the plugin version and scan
+/// parameters that produced it, and sort the findings with `sort_findings`
+///
+/// Together with the `rule` detail naming the pattern that fired, this keeps
+/// exported findings interpretable after the plugin's patterns change.
+pub fn scan_with_provenance(plugin: &dyn MemoryPlugin, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+    let mut findings = plugin.scan(img, progress);
+    let params = scan_parameters(plugin, img);
+    for finding in &mut findings {
+        finding.details.insert("plugin_version".to_string(), plugin.get_version().to_string());
+        finding.details.insert("scan_params".to_string(), params.clone());
+    }
+    sort_findings(&mut findings);
+    findings
+}
+
+/// When a plugin runs in a multi-plugin analysis, highest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Structure walks that finish in seconds and show where to look
+    High,
+    Normal,
+    /// Carving passes over the whole image
+    Low,
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Priority::High => write!(f, "high"),
+            Priority::Normal => write!(f, "normal"),
+            Priority::Low => write!(f, "low"),
+        }
+    }
+}
+
+/// What a plugin reads, for planning an analysis before running it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PluginNeeds {
+    /// Sequential passes over the whole physical image
+    pub passes: u32,
+    /// Follows kernel virtual addresses; reports less without a DTB
+    pub kernel_dtb: bool,
+    /// Walks the process structures of a user-mode dump; finds nothing in kernel dumps
+    pub user_dump: bool,
+    /// Reads structure layouts from kernel symbols (`--symbols`)
+    pub symbols: bool,
+}
+
+impl Default for PluginNeeds {
+    fn default() -> Self {
+        Self { passes: 1, kernel_dtb: false, user_dump: false, symbols: false }
+    }
+}
+
+impl std::fmt::Display for PluginNeeds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut needs = Vec::new();
+        if self.kernel_dtb {
+            needs.push("kernel DTB");
+        }
+        if self.user_dump {
+            needs.push("user-mode dump");
+        }
+        if self.symbols {
+            needs.push("symbols");
+        }
+        if needs.is_empty() {
+            write!(f, "-")
+        } else {
+            write!(f, "{}", needs.join(", "))
+        }
+    }
+}
+
 /// Core trait for memory forensics plugins
 pub trait MemoryPlugin: Send + Sync {
     fn name(&self) -> &'static str;
@@ -24,6 +266,29 @@ pub trait MemoryPlugin: Send + Sync {
     fn get_version(&self) -> &'static str {
         "1.0.0" // Default version
     }
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds::default()
+    }
+    /// Settings that change what the scan reports, recorded with its findings
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+    /// The same plugin reporting in clear the secrets it redacts by default
+    /// (`--reveal`); `None` for plugins that redact nothing
+    fn revealing(&self) -> Option<Box<dyn MemoryPlugin>> {
+        None
+    }
+    /// The same plugin with `settings` (`--set key=value`, keys as in
+    /// `parameters`) applied; an error for settings it does not take
+    fn configure(&self, settings: &HashMap<String, String>) -> Result<Box<dyn MemoryPlugin>> {
+        match settings.keys().min() {
+            Some(key) => bail!("Plugin '{}' takes no setting '{}'", self.name(), key),
+            None => bail!("Plugin '{}' takes no settings", self.name()),
+        }
+    }
 }
 
 /// Registry of available plugins
@@ -43,18 +308,21 @@ impl PluginRegistry {
         self.plugins.insert(plugin.name().to_string(), plugin);
     }
     
-    pub fn get(&self, name: &str) -> Option<&Box<dyn MemoryPlugin>> {
-        self.plugins.get(name)
+    pub fn get(&self, name: &str) -> Option<&dyn MemoryPlugin> {
+        self.plugins.get(name).map(|p| p.as_ref())
     }
     
+    /// (name, description, version) of every plugin, sorted by name
     pub fn list_plugins(&self) -> Vec<(String, String, String)> {
-        self.plugins.iter()
+        let mut plugins: Vec<_> = self.plugins.iter()
             .map(|(k, v)| (k.clone(), v.description().to_string(), v.get_version().to_string()))
-            .collect()
+            .collect();
+        plugins.sort();
+        plugins
     }
     
     /// Attempt to load an external plugin from a dynamic library
-    #[cfg(unix)]
+    #[cfg(all(unix, feature = "plugins"))]
     pub fn load_plugin_from_file(&mut self, path: &PathBuf) -> Result<()> {
         use libloading::{Library, Symbol};
         
@@ -82,7 +350,7 @@ impl PluginRegistry {
         Ok(())
     }
     
-    #[cfg(windows)]
+    #[cfg(all(windows, feature = "plugins"))]
     pub fn load_plugin_from_file(&mut self, path: &PathBuf) -> Result<()> {
         use libloading::{Library, Symbol};
         
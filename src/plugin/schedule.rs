@@ -0,0 +1,66 @@
+//! Priority scheduling of multi-plugin analyses
+//!
+//! A full analysis runs every plugin, and the carving passes take far longer
+//! than the structure walks. Plugins are started highest priority first on a
+//! pool of worker threads and each result is handed back as soon as that
+//! plugin finishes, so quick, high-value findings are reported within
+//! seconds while the heavy scans are still running.
+
+use indicatif::ProgressBar;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::paging::MemoryImage;
+use super::registry::{scan_with_provenance, Finding, MemoryPlugin};
+
+/// Findings of one plugin in a scheduled analysis
+#[derive(Debug)]
+pub struct PluginRun {
+    pub plugin: String,
+    pub findings: Vec<Finding>,
+    pub elapsed: Duration,
+}
+
+/// Plugins in the order they are started: by priority, then by name
+pub fn schedule<'a>(plugins: &[&'a dyn MemoryPlugin]) -> Vec<&'a dyn MemoryPlugin> {
+    let mut order = plugins.to_vec();
+    order.sort_by_key(|p| (p.priority(), p.name()));
+    order
+}
+
+/// Full passes over the image that running all of `plugins` makes
+pub fn total_passes(plugins: &[&dyn MemoryPlugin]) -> u32 {
+    plugins.iter().map(|p| p.needs().passes).sum()
+}
+
+/// Run `plugins` in scheduled order on up to `threads` workers, passing each
+/// result to `on_result` on the calling thread as soon as it is ready
+pub fn run_scheduled(img: &MemoryImage, plugins: &[&dyn MemoryPlugin], threads: usize, progress: &ProgressBar, mut on_result: impl FnMut(PluginRun)) {
+    let order = schedule(plugins);
+    let next = AtomicUsize::new(0);
+    progress.set_length(order.len() as u64);
+    thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..threads.clamp(1, order.len().max(1)) {
+            let (tx, order, next) = (tx.clone(), &order, &next);
+            scope.spawn(move || {
+                while let Some(plugin) = order.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let started = Instant::now();
+                    let findings = scan_with_provenance(*plugin, img, &ProgressBar::hidden());
+                    let run = PluginRun { plugin: plugin.name().to_string(), findings, elapsed: started.elapsed() };
+                    if tx.send(run).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+        for run in rx {
+            progress.inc(1);
+            progress.set_message(format!("{} finished", run.plugin));
+            on_result(run);
+        }
+    });
+}
@@ -0,0 +1,199 @@
+//! Execution evidence from the Shimcache and Amcache
+//!
+//! The Application Compatibility Cache (Shimcache) in the SYSTEM hive lists
+//! binaries the shim engine looked at, most recent first, each with the
+//! file's last-modified time; on Windows 10 an entry shows the file was
+//! present and likely run. Amcache.hve records each program installed or
+//! run under `Root\InventoryApplicationFile`, with its path and SHA-1, the
+//! key's last write marking when it was recorded. Both hives are loaded at
+//! run time, so the memory copies can hold entries not yet flushed to disk.
+//! Shimcache formats of Windows 8.x and 10 and the Amcache inventory of
+//! Windows 10 1709 and later are read.
+
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::paging::MemoryImage;
+use crate::processes::filetime_to_system;
+use crate::registry::{current_control_set, find_hives, Hive, HiveInfo};
+use super::hashdump::hives_named;
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Signature of every Windows 8.x and 10 cache entry
+const ENTRY_SIGNATURE: &[u8; 4] = b"10ts";
+/// Cache header sizes: Windows 8.x, then the Windows 10 releases
+const WINDOWS8_HEADER: usize = 0x80;
+const WINDOWS10_HEADERS: [usize; 2] = [0x30, 0x34];
+/// Signature, unknown, then the size of the rest of the entry
+const ENTRY_HEADER_SIZE: usize = 0xC;
+/// Cached paths are at most MAX_PATH UTF-16 characters
+const MAX_PATH_BYTES: usize = 0x208;
+
+/// One entry of the Shimcache or the Amcache
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionEntry {
+    /// `shimcache` or `amcache`
+    pub source: &'static str,
+    pub path: String,
+    /// FILETIME: the file's last modification (Shimcache) or when the
+    /// entry was recorded (Amcache)
+    pub timestamp: u64,
+    /// Position in the Shimcache, 0 being the most recent
+    pub position: Option<usize>,
+    pub sha1: Option<String>,
+    pub product: Option<String>,
+}
+
+impl ExecutionEntry {
+    pub fn time(&self) -> Option<String> {
+        filetime_to_system(self.timestamp).map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+    }
+}
+
+fn utf16(data: &[u8]) -> String {
+    let units: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units).trim_end_matches('\0').to_string()
+}
+
+fn u16_at(data: &[u8], off: usize) -> Option<usize> {
+    data.get(off..off + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+}
+
+fn u64_at(data: &[u8], off: usize) -> Option<u64> {
+    data.get(off..off + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+}
+
+/// Decode an AppCompatCache value, in cache order; stops at the first entry
+/// that does not parse
+pub fn parse_shimcache(data: &[u8]) -> Vec<ExecutionEntry> {
+    let Some(header) = data.get(..4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize) else { return Vec::new() };
+    if header != WINDOWS8_HEADER && !WINDOWS10_HEADERS.contains(&header) {
+        return Vec::new();
+    }
+    let mut entries = Vec::new();
+    let mut at = header;
+    while data.get(at..at + 4) == Some(ENTRY_SIGNATURE) {
+        let Some(size) = data.get(at + 8..at + 12).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize) else { break };
+        let Some(entry) = data.get(at + ENTRY_HEADER_SIZE..at + ENTRY_HEADER_SIZE + size) else { break };
+        let Some(path_size) = u16_at(entry, 0).filter(|&s| s <= MAX_PATH_BYTES) else { break };
+        let mut after = 2 + path_size;
+        // Windows 8.x adds the package name and two flag fields
+        if header == WINDOWS8_HEADER {
+            let Some(package_size) = u16_at(entry, after) else { break };
+            after += 2 + package_size + 8;
+        }
+        let (Some(path), Some(timestamp)) = (entry.get(2..2 + path_size), u64_at(entry, after)) else { break };
+        entries.push(ExecutionEntry {
+            source: "shimcache",
+            path: utf16(path),
+            timestamp,
+            position: Some(entries.len()),
+            sha1: None,
+            product: None,
+        });
+        at += ENTRY_HEADER_SIZE + size;
+    }
+    entries
+}
+
+/// Shimcache entries of the current control set
+pub fn shimcache_entries(system: &Hive) -> Result<Vec<ExecutionEntry>> {
+    let path = format!("{}\\Control\\Session Manager\\AppCompatCache", current_control_set(system)?);
+    let key = system.open_key(&path)?;
+    let value = system.value(&key, "AppCompatCache").context("AppCompatCache key has no AppCompatCache value")?;
+    Ok(parse_shimcache(&value.data))
+}
+
+/// Programs in the Amcache's `Root\InventoryApplicationFile`
+pub fn amcache_entries(amcache: &Hive) -> Result<Vec<ExecutionEntry>> {
+    let inventory = amcache.open_key("Root\\InventoryApplicationFile")?;
+    Ok(amcache.subkeys(&inventory).into_iter().filter_map(|file| {
+        let text = |name: &str| amcache.value(&file, name).map(|v| v.display()).filter(|s| !s.is_empty());
+        // FileId is the SHA-1 behind four zero digits
+        let sha1 = text("FileId").map(|id| id.strip_prefix("0000").map(str::to_string).unwrap_or(id));
+        Some(ExecutionEntry {
+            source: "amcache",
+            path: text("LowerCaseLongPath")?,
+            timestamp: file.last_write,
+            position: None,
+            sha1,
+            product: text("ProductName"),
+        })
+    }).collect())
+}
+
+/// Shimcache and Amcache entries of the loaded hives, oldest first
+pub fn execution_timeline(img: &MemoryImage, hives: &[HiveInfo]) -> Vec<(u64, ExecutionEntry)> {
+    // Copies of a hive may be stale; take the first that parses
+    let shimcache = hives_named(img, hives, "SYSTEM").into_iter()
+        .find_map(|(addr, system)| shimcache_entries(&system).ok().filter(|e| !e.is_empty()).map(|e| (addr, e)));
+    let amcache = hives_named(img, hives, "Amcache.hve").into_iter()
+        .find_map(|(addr, amcache)| amcache_entries(&amcache).ok().map(|e| (addr, e)));
+    let mut timeline: Vec<(u64, ExecutionEntry)> = shimcache.into_iter().chain(amcache)
+        .flat_map(|(addr, entries)| entries.into_iter().map(move |entry| (addr, entry)))
+        .collect();
+    timeline.sort_by_key(|(_, entry)| (entry.timestamp, entry.position));
+    timeline
+}
+
+/// A plugin that reports the binaries recorded in the Shimcache and Amcache
+#[derive(Default)]
+pub struct ShimcacheScanner;
+
+impl ShimcacheScanner {
+    fn report(&self, addr: u64, entry: &ExecutionEntry) -> Finding {
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "execution".to_string());
+        details.insert("rule".to_string(), format!("{}_entry", entry.source));
+        details.insert("source".to_string(), entry.source.to_string());
+        details.insert("path".to_string(), entry.path.clone());
+        if let Some(time) = entry.time() {
+            details.insert("timestamp".to_string(), time);
+        }
+        if let Some(position) = entry.position {
+            details.insert("position".to_string(), position.to_string());
+        }
+        if let Some(sha1) = &entry.sha1 {
+            details.insert("sha1".to_string(), sha1.clone());
+        }
+        if let Some(product) = &entry.product {
+            details.insert("product".to_string(), product.clone());
+        }
+        let what = if entry.source == "shimcache" { "modified" } else { "recorded" };
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} {} ({} {})", entry.source, entry.path, what, entry.time().unwrap_or_else(|| "-".to_string())),
+            // A Shimcache entry shows presence; an Amcache entry is closer to execution
+            confidence: if entry.source == "shimcache" { 60 } else { 75 },
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for ShimcacheScanner {
+    fn name(&self) -> &'static str {
+        "shimcache"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Lists binaries recorded as present or run in the Shimcache (SYSTEM hive) and Amcache.hve"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        progress.set_message("Scanning for registry hives");
+        let hives = find_hives(img, progress);
+        let findings: Vec<Finding> = execution_timeline(img, &hives).iter().map(|(addr, entry)| self.report(*addr, entry)).collect();
+        progress.finish_with_message(format!("Found {} Shimcache and Amcache entries", findings.len()));
+        findings
+    }
+}
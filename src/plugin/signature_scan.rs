@@ -0,0 +1,116 @@
+//! Signature file scanning
+//!
+//! Searches physical memory for every signature of a user signature file
+//! (`--signatures sigs.toml`, see [`crate::signatures`]) in one pass over
+//! the image, reporting each match with the signature's name, description,
+//! tags and confidence. Windows and context work as for `pattern_scan`.
+
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::paging::MemoryImage;
+use crate::scan_util::chunks;
+use crate::signatures::{Signature, SignaturePattern, SignatureSet};
+use super::pattern_scan::{PatternMatch, PatternScanner, MAX_PATTERN_MATCHES, OVERLAP, WINDOW};
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+/// A plugin that reports where the signatures of a signature file match
+#[derive(Default)]
+pub struct SignatureScanner {
+    signatures: Vec<(Signature, PatternScanner)>,
+    /// SHA-256 prefix of the signatures, recorded with the findings
+    digest: String,
+}
+
+impl SignatureScanner {
+    /// Scan for the signatures of `set`
+    pub fn new(set: SignatureSet) -> Result<Self> {
+        let mut hasher = Sha256::new();
+        let mut signatures = Vec::new();
+        for signature in set.signatures {
+            let scanner = match &signature.pattern {
+                SignaturePattern::Bytes(pattern) => PatternScanner::bytes(pattern),
+                SignaturePattern::Regex(pattern) => PatternScanner::regex(pattern),
+            }.with_context(|| format!("Signature '{}'", signature.name))?;
+            hasher.update(format!("{}\0{:?}\0{}\0", signature.name, signature.pattern, signature.confidence).as_bytes());
+            signatures.push((signature, scanner));
+        }
+        let digest = hasher.finalize()[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(SignatureScanner { signatures, digest })
+    }
+
+    /// Scan for the signatures in the file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::new(SignatureSet::load(path)?).with_context(|| format!("Failed to compile {}", path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    fn report(&self, addr: u64, signature: &Signature, scanner: &PatternScanner, found: &PatternMatch) -> Finding {
+        let mut details = scanner.match_details(found);
+        details.insert("type".to_string(), "signature".to_string());
+        details.insert("rule".to_string(), signature.name.clone());
+        details.insert("tags".to_string(), signature.tags.join(","));
+        details.insert("description".to_string(), signature.description.clone());
+        let what = if signature.description.is_empty() { &signature.name } else { &signature.description };
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} at 0x{:X} ({}: \"{}\")", what, addr, signature.name, details["match"]),
+            confidence: signature.confidence,
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for SignatureScanner {
+    fn name(&self) -> &'static str {
+        "signature_scan"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Low
+    }
+
+    fn description(&self) -> &'static str {
+        "Searches physical memory for the byte and regex signatures of the file given by --signatures"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![("signatures", format!("{} ({})", self.signatures.len(), self.digest))]
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if self.signatures.is_empty() {
+            progress.finish_with_message("No signatures loaded; run signature_scan with --signatures");
+            return findings;
+        }
+        progress.set_message(format!("Searching for {} signatures", self.signatures.len()));
+        'scan: for window in chunks(img, WINDOW, OVERLAP).with_progress(progress) {
+            for (signature, scanner) in &self.signatures {
+                for found in scanner.window_matches(&window) {
+                    if findings.len() == MAX_PATTERN_MATCHES {
+                        break 'scan;
+                    }
+                    findings.push(self.report(window.addr(found.offset), signature, scanner, &found));
+                }
+            }
+        }
+        findings.sort_by_key(|f| f.addr);
+        if findings.len() == MAX_PATTERN_MATCHES {
+            progress.finish_with_message(format!("Stopped after {} matches", MAX_PATTERN_MATCHES));
+            return findings;
+        }
+        progress.finish_with_message(format!("Found {} signature matches", findings.len()));
+        findings
+    }
+}
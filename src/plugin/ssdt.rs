@@ -0,0 +1,323 @@
+//! System service and processor dispatch table hooks
+//!
+//! The System Service Descriptor Table (SSDT) maps each system call number
+//! to its routine in ntoskrnl, and the shadow table does the same for the
+//! win32k GUI calls; each CPU's IDT lists its interrupt and exception
+//! handlers and its GDT holds the segment descriptors. Rootkits redirect
+//! entries of these tables to their own code, or add a call gate to the
+//! GDT as a way into ring 0. The scanner finds the descriptor tables
+//! through the `lea r10`/`lea r11` pair in `KiSystemServiceRepeat`, reads
+//! the IDT and GDT of every KPCR on `KiProcessorBlock`, and flags routines
+//! outside ntoskrnl (win32k for the shadow table, ntoskrnl and the HAL for
+//! the IDT). The win32k table is only mapped in GUI processes' session
+//! space, so it is read through the first process that maps it.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::kdbg::OsContext;
+use crate::modules::{list_kernel_modules, module_containing, read_pages, KernelModule};
+use crate::paging::MemoryImage;
+use crate::processes::{ProcessFinder, WindowsProcessFinder};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// `lea r10, [rip+KeServiceDescriptorTable]` and
+/// `lea r11, [rip+KeServiceDescriptorTableShadow]` in KiSystemServiceRepeat
+const LEA_R10: [u8; 3] = [0x4C, 0x8D, 0x15];
+const LEA_R11: [u8; 3] = [0x4C, 0x8D, 0x1D];
+/// KSERVICE_TABLE_DESCRIPTOR: Base, Count, Limit, Number
+const DESCRIPTOR_SIZE: u64 = 0x20;
+const DESCRIPTOR_LIMIT: u64 = 0x10;
+/// Far more than any Windows release defines
+const MAX_SERVICES: u32 = 0x1000;
+
+/// KPCR offsets for x64; the KPRCB is embedded at 0x180
+const KPCR_GDT: u64 = 0x00;
+const KPCR_SELF: u64 = 0x18;
+const KPCR_CURRENT_PRCB: u64 = 0x20;
+const KPCR_IDT: u64 = 0x38;
+const KPCR_PRCB: u64 = 0x180;
+/// Entries of KiProcessorBlock
+const MAX_CPUS: u64 = 640;
+const IDT_ENTRIES: usize = 256;
+const IDT_ENTRY_SIZE: usize = 16;
+/// Bytes of the GDT read; Windows uses the first 0x60
+const GDT_SIZE: usize = 0x80;
+/// System descriptor types taking two GDT slots in long mode
+const GDT_LDT: u64 = 0x2;
+const GDT_TSS: [u64; 2] = [0x9, 0xB];
+const GDT_CALL_GATE: u64 = 0xC;
+
+/// A service table and the routine of each system call
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceTable {
+    /// Address of the KSERVICE_TABLE_DESCRIPTOR
+    pub descriptor: u64,
+    pub base: u64,
+    pub routines: Vec<u64>,
+}
+
+/// Descriptor tables of one processor
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessorTables {
+    pub cpu: usize,
+    pub kpcr: u64,
+    pub gdt: u64,
+    pub idt: u64,
+    /// Vector and handler of each present IDT entry
+    pub interrupts: Vec<(u8, u64)>,
+    /// Selector and target of each GDT call gate
+    pub call_gates: Vec<(u16, u64)>,
+}
+
+fn u64_at(data: &[u8], off: usize) -> u64 {
+    u64::from_le_bytes(data[off..off + 8].try_into().unwrap())
+}
+
+/// Addresses of KeServiceDescriptorTable and KeServiceDescriptorTableShadow
+/// from the code of the kernel image at `base`
+pub fn find_service_descriptors(img: &MemoryImage, base: u64, size: u64) -> Option<(u64, u64)> {
+    let (image, _) = read_pages(img, base, size);
+    image.windows(14).enumerate().find_map(|(at, code)| {
+        if code[..3] != LEA_R10 || code[7..10] != LEA_R11 {
+            return None;
+        }
+        let target = |next: usize, disp: &[u8]| (base + next as u64).wrapping_add(i32::from_le_bytes(disp.try_into().unwrap()) as i64 as u64);
+        Some((target(at + 7, &code[3..7]), target(at + 14, &code[10..14])))
+    })
+}
+
+/// Read the service table a KSERVICE_TABLE_DESCRIPTOR describes; each entry
+/// is the routine's offset from the table base, shifted left four bits
+/// over its stack argument count
+pub fn read_service_table(img: &MemoryImage, descriptor: u64) -> Option<ServiceTable> {
+    let base = img.read_virt_u64(descriptor).filter(|&b| b != 0)?;
+    let limit = img.read_virt_u32(descriptor + DESCRIPTOR_LIMIT).filter(|&l| l > 0 && l <= MAX_SERVICES)?;
+    let entries = img.read_virt(base, limit as usize * 4)?;
+    let routines = entries.chunks_exact(4)
+        .map(|e| base.wrapping_add((i32::from_le_bytes(e.try_into().unwrap()) >> 4) as i64 as u64))
+        .collect();
+    Some(ServiceTable { descriptor, base, routines })
+}
+
+/// Vector and handler of each present KIDTENTRY64
+pub fn parse_idt(data: &[u8]) -> Vec<(u8, u64)> {
+    data.chunks_exact(IDT_ENTRY_SIZE).take(IDT_ENTRIES).enumerate().filter_map(|(vector, entry)| {
+        let word = |off: usize| u16::from_le_bytes([entry[off], entry[off + 1]]) as u64;
+        if word(4) & 0x8000 == 0 {
+            return None;
+        }
+        let high = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as u64;
+        Some((vector as u8, word(0) | word(6) << 16 | high << 32))
+    }).collect()
+}
+
+/// Selector and target of each present call gate in a long-mode GDT
+pub fn gdt_call_gates(data: &[u8]) -> Vec<(u16, u64)> {
+    let mut gates = Vec::new();
+    let mut slot = 0;
+    while slot + 8 <= data.len() {
+        let descriptor = u64_at(data, slot);
+        let (kind, system, present) = (descriptor >> 40 & 0xF, descriptor >> 44 & 1 == 0, descriptor >> 47 & 1 == 1);
+        let wide = present && system && (kind == GDT_LDT || kind == GDT_CALL_GATE || GDT_TSS.contains(&kind));
+        if wide && kind == GDT_CALL_GATE && slot + 16 <= data.len() {
+            let high = data[slot + 8..slot + 12].try_into().map(u32::from_le_bytes).unwrap() as u64;
+            gates.push((slot as u16, descriptor & 0xFFFF | (descriptor >> 48) << 16 | high << 32));
+        }
+        slot += if wide { 16 } else { 8 };
+    }
+    gates
+}
+
+/// Processor number and KPRCB of each processor on KiProcessorBlock whose
+/// KPCR points back at itself
+pub fn processor_blocks(img: &MemoryImage, ki_processor_block: u64) -> Vec<(usize, u64)> {
+    (0..MAX_CPUS)
+        .map_while(|cpu| img.read_virt_u64(ki_processor_block + cpu * 8).filter(|&prcb| prcb != 0))
+        .enumerate()
+        .filter(|&(_, prcb)| {
+            let kpcr = prcb.wrapping_sub(KPCR_PRCB);
+            img.read_virt_u64(kpcr + KPCR_SELF) == Some(kpcr) && img.read_virt_u64(kpcr + KPCR_CURRENT_PRCB) == Some(prcb)
+        })
+        .collect()
+}
+
+/// The IDT and GDT of each processor on KiProcessorBlock
+pub fn processor_tables(img: &MemoryImage, ki_processor_block: u64) -> Vec<ProcessorTables> {
+    processor_blocks(img, ki_processor_block)
+        .into_iter()
+        .filter_map(|(cpu, prcb)| {
+            let kpcr = prcb.wrapping_sub(KPCR_PRCB);
+            let gdt = img.read_virt_u64(kpcr + KPCR_GDT)?;
+            let idt = img.read_virt_u64(kpcr + KPCR_IDT)?;
+            Some(ProcessorTables {
+                cpu,
+                kpcr,
+                gdt,
+                idt,
+                interrupts: img.read_virt(idt, IDT_ENTRIES * IDT_ENTRY_SIZE).map(|d| parse_idt(&d)).unwrap_or_default(),
+                call_gates: img.read_virt(gdt, GDT_SIZE).map(|d| gdt_call_gates(&d)).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Size of the PE image at `base` from its optional header
+fn image_size(img: &MemoryImage, base: u64) -> Option<u64> {
+    let nt = base + img.read_virt_u32(base + 0x3C)? as u64;
+    if img.read_virt(nt, 4)? != b"PE\0\0" {
+        return None;
+    }
+    img.read_virt_u32(nt + 0x18 + 0x38).map(|size| size as u64)
+}
+
+/// A plugin that checks the SSDT, shadow SSDT, IDT and GDT for hooks
+#[derive(Default)]
+pub struct SsdtScanner;
+
+impl SsdtScanner {
+    fn finding(&self, addr: u64, rule: &str, desc: String, confidence: u8, details: Vec<(&str, String)>) -> Finding {
+        let mut map = HashMap::new();
+        map.insert("type".to_string(), "hook".to_string());
+        map.insert("rule".to_string(), rule.to_string());
+        for (key, value) in details {
+            map.insert(key.to_string(), value);
+        }
+        Finding { plugin: self.name().to_string(), addr, desc, confidence, details: map }
+    }
+
+    /// One finding for the table and one per routine outside `owners`;
+    /// without the owning modules no routine can be judged
+    fn report_table(&self, space: &MemoryImage, name: &str, table: &ServiceTable, modules: &[KernelModule], owners: &[&KernelModule], owner_name: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (index, &routine) in table.routines.iter().enumerate() {
+            if owners.is_empty() || owners.iter().any(|owner| owner.contains(routine)) {
+                continue;
+            }
+            let owner = module_containing(modules, routine).map_or("unknown", |m| m.name.as_str());
+            let entry = table.base + index as u64 * 4;
+            findings.push(self.finding(
+                space.virt_to_phys(entry).unwrap_or(entry),
+                "ssdt_hook",
+                format!("{} entry {:#x} points to {:#x} in {}", name, index, routine, owner),
+                90,
+                vec![("table", name.to_string()), ("index", format!("{:#x}", index)), ("routine", format!("{:#x}", routine)), ("module", owner.to_string())],
+            ));
+        }
+        let summary = self.finding(
+            space.virt_to_phys(table.descriptor).unwrap_or(table.descriptor),
+            "service_table",
+            format!("{} at {:#x} with {} services, {} outside {}", name, table.base, table.routines.len(), findings.len(), owner_name),
+            if findings.is_empty() { 50 } else { 90 },
+            vec![("table", name.to_string()), ("base", format!("{:#x}", table.base)), ("services", table.routines.len().to_string()),
+                ("hooked", findings.len().to_string())],
+        );
+        findings.insert(0, summary);
+        findings
+    }
+
+    fn report_processor(&self, img: &MemoryImage, cpu: &ProcessorTables, modules: &[KernelModule], owners: &[&KernelModule]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let (mut hooked, mut gates) = (0, 0);
+        for &(vector, handler) in &cpu.interrupts {
+            if owners.is_empty() || owners.iter().any(|owner| owner.contains(handler)) {
+                continue;
+            }
+            hooked += 1;
+            let owner = module_containing(modules, handler).map_or("unknown", |m| m.name.as_str());
+            let entry = cpu.idt + vector as u64 * IDT_ENTRY_SIZE as u64;
+            findings.push(self.finding(
+                img.virt_to_phys(entry).unwrap_or(entry),
+                "idt_hook",
+                format!("CPU {} IDT vector {:#04x} handler {:#x} in {}", cpu.cpu, vector, handler, owner),
+                85,
+                vec![("cpu", cpu.cpu.to_string()), ("vector", format!("{:#04x}", vector)), ("handler", format!("{:#x}", handler)), ("module", owner.to_string())],
+            ));
+        }
+        for &(selector, target) in &cpu.call_gates {
+            gates += 1;
+            let owner = module_containing(modules, target).map_or("unknown", |m| m.name.as_str());
+            let entry = cpu.gdt + selector as u64;
+            findings.push(self.finding(
+                img.virt_to_phys(entry).unwrap_or(entry),
+                "gdt_call_gate",
+                format!("CPU {} GDT call gate {:#x} to {:#x} in {}", cpu.cpu, selector, target, owner),
+                90,
+                vec![("cpu", cpu.cpu.to_string()), ("selector", format!("{:#x}", selector)), ("target", format!("{:#x}", target)), ("module", owner.to_string())],
+            ));
+        }
+        let summary = self.finding(
+            img.virt_to_phys(cpu.kpcr).unwrap_or(cpu.kpcr),
+            "processor_tables",
+            format!("CPU {} IDT at {:#x} ({} vectors, {} hooked), GDT at {:#x} ({} call gates)", cpu.cpu, cpu.idt, cpu.interrupts.len(), hooked, cpu.gdt, gates),
+            if findings.is_empty() { 50 } else { 85 },
+            vec![("cpu", cpu.cpu.to_string()), ("kpcr", format!("{:#x}", cpu.kpcr)), ("idt", format!("{:#x}", cpu.idt)), ("gdt", format!("{:#x}", cpu.gdt)),
+                ("vectors", cpu.interrupts.len().to_string()), ("hooked", hooked.to_string()), ("call_gates", gates.to_string())],
+        );
+        findings.insert(0, summary);
+        findings
+    }
+}
+
+impl MemoryPlugin for SsdtScanner {
+    fn name(&self) -> &'static str {
+        "ssdt"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Checks the SSDT, shadow SSDT, IDT and GDT for entries pointing outside ntoskrnl, win32k and the HAL"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Locating the kernel");
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else {
+            progress.finish_with_message("No kernel found; the dispatch tables need KDBG");
+            return findings;
+        };
+        let modules = list_kernel_modules(img, &os);
+        let kernel = module_containing(&modules, os.kernel_base).cloned().or_else(|| {
+            image_size(img, os.kernel_base).map(|size| KernelModule { base: os.kernel_base, size, name: "ntoskrnl.exe".to_string(), path: None })
+        });
+
+        progress.set_message("Reading the service descriptor tables");
+        if let Some(kernel) = &kernel {
+            if let Some((ssdt, shadow)) = find_service_descriptors(img, kernel.base, kernel.size) {
+                if let Some(table) = read_service_table(img, ssdt) {
+                    findings.extend(self.report_table(img, "SSDT", &table, &modules, &[kernel], "ntoskrnl"));
+                }
+                // The shadow's second descriptor is win32k's, in session space
+                let win32k: Vec<&KernelModule> = modules.iter().filter(|m| m.name.to_lowercase().starts_with("win32k")).collect();
+                let finder = WindowsProcessFinder::new().with_os_context(os.clone());
+                let processes = finder.find_processes(img, &ProgressBar::hidden()).unwrap_or_default();
+                let shadow_table = read_service_table(img, shadow + DESCRIPTOR_SIZE).map(|table| (None, table)).or_else(|| {
+                    processes.iter().filter_map(|p| p.address_space(img))
+                        .find_map(|space| read_service_table(&space, shadow + DESCRIPTOR_SIZE).map(|table| (Some(space), table)))
+                });
+                match shadow_table {
+                    Some((Some(space), table)) => findings.extend(self.report_table(&space, "Shadow SSDT", &table, &modules, &win32k, "win32k")),
+                    Some((None, table)) => findings.extend(self.report_table(img, "Shadow SSDT", &table, &modules, &win32k, "win32k")),
+                    None => {}
+                }
+            }
+        }
+
+        progress.set_message("Reading processor descriptor tables");
+        let mut owners: Vec<&KernelModule> = kernel.iter().collect();
+        owners.extend(modules.iter().filter(|m| m.name.eq_ignore_ascii_case("hal.dll")));
+        for cpu in os.ki_processor_block.map(|kpb| processor_tables(img, kpb)).unwrap_or_default() {
+            findings.extend(self.report_processor(img, &cpu, &modules, &owners));
+        }
+
+        progress.finish_with_message(format!("Checked dispatch tables: {} findings", findings.len()));
+        findings
+    }
+}
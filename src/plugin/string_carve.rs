@@ -1,10 +1,50 @@
 //! String carving plugin implementation
+//!
+//! Physical memory is read in chunks and carved for runs of printable ASCII
+//! and of UTF-16LE code units with a zero high byte, at both alignments, as
+//! Windows stores most text. A run reaching the end of a chunk carries into
+//! the next, so strings spanning chunk boundaries are reported whole; runs
+//! stop at unreadable memory.
 
+use anyhow::{bail, Context, Result};
 use indicatif::ProgressBar;
 use std::collections::HashMap;
 
 use crate::paging::MemoryImage;
-use super::registry::{MemoryPlugin, Finding};
+use crate::scan_util::chunks;
+use super::registry::{MemoryPlugin, Finding, Priority};
+
+/// Physical memory carved per read
+const CHUNK: usize = 0x100000;
+/// Characters kept of a run; longer runs are reported cut, with their length
+const MAX_STRING_LEN: usize = 4096;
+/// Strings reported before the scan stops
+pub const MAX_STRINGS: usize = 100_000;
+
+/// A run of printable characters being carved, which may span chunks
+#[derive(Default)]
+struct Run {
+    start: u64,
+    text: Vec<u8>,
+    len: usize,
+    utf16: bool,
+}
+
+impl Run {
+    fn push(&mut self, addr: u64, bytes: &[u8]) {
+        if self.len == 0 {
+            self.start = addr;
+        }
+        self.len += bytes.len();
+        let room = MAX_STRING_LEN.saturating_sub(self.text.len());
+        self.text.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+
+    fn take(&mut self) -> Run {
+        let utf16 = self.utf16;
+        std::mem::replace(self, Run { utf16, ..Default::default() })
+    }
+}
 
 /// A plugin that carves for strings in memory
 pub struct StringCarvePlugin {
@@ -18,7 +58,7 @@ impl StringCarvePlugin {
     }
     
     fn is_printable(c: u8) -> bool {
-        (c >= 32 && c <= 126) || c == b'\n' || c == b'\r' || c == b'\t'
+        (32..=126).contains(&c) || c == b'\n' || c == b'\r' || c == b'\t'
     }
     
     fn extract_ascii_string(&self, data: &[u8], start: usize) -> Option<String> {
@@ -34,6 +74,102 @@ impl StringCarvePlugin {
             None
         }
     }
+
+    /// Carve the ASCII strings of the chunk at `base`, continuing `pending`
+    /// from the previous chunk and leaving in it a run reaching the end
+    fn carve_ascii(&self, base: u64, data: &[u8], pending: &mut Run, carved: &mut Vec<Run>) {
+        let run_end = |from: usize| data[from..].iter().position(|&b| !Self::is_printable(b)).map_or(data.len(), |n| from + n);
+        let mut i = 0;
+        if pending.len > 0 {
+            i = run_end(0);
+            pending.push(base, &data[..i]);
+            if i == data.len() {
+                return;
+            }
+            self.finish(pending.take(), carved);
+        }
+        while i < data.len() {
+            if !Self::is_printable(data[i]) {
+                i += 1;
+                continue;
+            }
+            let end = run_end(i);
+            if end == data.len() {
+                pending.push(base + i as u64, &data[i..]);
+            } else if let Some(string) = self.extract_ascii_string(data, i) {
+                let mut run = Run::default();
+                run.push(base + i as u64, string.as_bytes());
+                carved.push(run);
+            }
+            i = end;
+        }
+    }
+
+    /// Carve the UTF-16LE strings of the chunk at `base`: `lanes` hold the
+    /// runs of code units at even and odd addresses and `low` the low byte
+    /// of a unit cut by the previous chunk's end
+    fn carve_utf16(&self, base: u64, data: &[u8], lanes: &mut [Run; 2], low: &mut Option<u8>, carved: &mut Vec<Run>) {
+        for (i, &byte) in data.iter().enumerate() {
+            let addr = base + i as u64;
+            // This byte ends the unit of the lane of the previous address
+            let lane = &mut lanes[addr.is_multiple_of(2) as usize];
+            if let Some(low) = low.replace(byte) {
+                if byte == 0 && Self::is_printable(low) {
+                    lane.push(addr - 1, &[low]);
+                } else if lane.len > 0 {
+                    self.finish(lane.take(), carved);
+                }
+            }
+        }
+    }
+
+    /// Keep `run` if it is long enough
+    fn finish(&self, run: Run, carved: &mut Vec<Run>) {
+        if run.len >= self.min_string_len {
+            carved.push(run);
+        }
+    }
+
+    fn report(&self, run: &Run) -> Finding {
+        let text = String::from_utf8_lossy(&run.text).to_string();
+        let mut details = HashMap::new();
+
+        // Categorize the string
+        let category = if text.contains("Password:") || text.contains("KEY=") {
+            Some(("credential", "credential_keyword", "high"))
+        } else if text.contains("SELECT") {
+            Some(("sql_query", "sql_keyword", "medium"))
+        } else if text.contains("http:") || text.contains("https:") {
+            Some(("url", "url_scheme", "low"))
+        } else if text.contains("ssh-rsa") {
+            Some(("ssh_key", "ssh_key_prefix", "high"))
+        } else if text.contains(".xml") {
+            Some(("config_file", "config_extension", "low"))
+        } else {
+            None
+        };
+        match category {
+            Some((kind, rule, risk)) => {
+                details.insert("type".to_string(), kind.to_string());
+                details.insert("rule".to_string(), rule.to_string());
+                details.insert("risk".to_string(), risk.to_string());
+            }
+            None => {
+                details.insert("rule".to_string(), "printable_run".to_string());
+            }
+        }
+        details.insert("encoding".to_string(), if run.utf16 { "utf-16le" } else { "ascii" }.to_string());
+        details.insert("length".to_string(), run.len.to_string());
+
+        Finding {
+            plugin: self.name().to_string(),
+            addr: run.start,
+            desc: if run.len > run.text.len() { format!("{}...", text) } else { text },
+            // Keyword matches say more than any printable run
+            confidence: if category.is_some() { 90 } else { 50 },
+            details,
+        }
+    }
 }
 
 impl Default for StringCarvePlugin {
@@ -49,102 +185,74 @@ impl MemoryPlugin for StringCarvePlugin {
     fn name(&self) -> &'static str {
         "string_carve"
     }
+
+    fn priority(&self) -> Priority {
+        Priority::Low
+    }
     
     fn description(&self) -> &'static str {
         "Scans memory for ASCII and UTF-16 strings"
     }
 
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![("min_string_len", self.min_string_len.to_string()), ("scan_utf16", self.scan_utf16.to_string())]
+    }
+
     fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
         let mut findings = Vec::new();
-        let size = img.size();
         
-        // Set up progress bar
-        progress.set_length(size as u64);
         progress.set_message("Scanning for strings");
         
-        // For demonstration purposes, we'll simulate finding strings
-        // In a real scanner, we'd look for MIN_STRING_LEN consecutive printable chars
-        let scan_points = [
-            (0x1000, "Password: admin123"),
-            (0x2500, "config.xml"),
-            (0x5000, "http://example.com/data"),
-            (0x7A00, "SELECT * FROM users"),
-            (0xA000, "/etc/shadow"),
-            (0xC000, "ssh-rsa AAAA..."),
-            (0xE000, "SECRET_KEY=abc123"),
-            (0xF500, "192.168.1.1"),
-        ];
-        
-        // Iterate through chunks of memory (simulated here)
-        for chunk_start in (0..size).step_by(4096) {
-            // Update progress every 4KB
-            progress.set_position(chunk_start as u64);
-            
-            // Check if any of our simulated strings are in this chunk
-            for &(addr, string) in scan_points.iter() {
-                if addr >= chunk_start && addr < chunk_start + 4096 {
-                    // Add more details for interesting strings
-                    let mut details = HashMap::new();
-                    
-                    // Categorize the string
-                    if string.contains("Password:") || string.contains("KEY=") {
-                        details.insert("type".to_string(), "credential".to_string());
-                        details.insert("risk".to_string(), "high".to_string());
-                    } else if string.contains("SELECT") {
-                        details.insert("type".to_string(), "sql_query".to_string());
-                        details.insert("risk".to_string(), "medium".to_string());
-                    } else if string.contains("http:") || string.contains("https:") {
-                        details.insert("type".to_string(), "url".to_string());
-                        details.insert("risk".to_string(), "low".to_string());
-                    } else if string.contains("ssh-rsa") {
-                        details.insert("type".to_string(), "ssh_key".to_string());
-                        details.insert("risk".to_string(), "high".to_string());
-                    } else if string.contains(".xml") {
-                        details.insert("type".to_string(), "config_file".to_string());
-                        details.insert("risk".to_string(), "low".to_string());
-                    }
-                    
-                    // Calculate a confidence level based on string length and content
-                    let confidence = if string.len() >= self.min_string_len { 90 } else { 50 };
-                    
-                    findings.push(Finding {
-                        plugin: self.name().to_string(),
-                        addr: addr as u64,
-                        desc: string.to_string(),
-                        confidence,
-                        details,
-                    });
-                }
+        let mut ascii = Run::default();
+        let mut lanes = [Run { utf16: true, ..Default::default() }, Run { utf16: true, ..Default::default() }];
+        let mut low = None;
+        let mut carved = Vec::new();
+        // Runs carry from chunk to chunk, so windows need no overlap
+        let mut next = 0;
+        for window in chunks(img, CHUNK, 0).with_progress(progress) {
+            if window.start != next {
+                // Strings do not run across unreadable memory
+                self.finish(ascii.take(), &mut carved);
+                lanes.iter_mut().for_each(|lane| self.finish(lane.take(), &mut carved));
+                low = None;
+            }
+            next = window.start + window.data.len();
+            self.carve_ascii(window.start as u64, window.data, &mut ascii, &mut carved);
+            if self.scan_utf16 {
+                self.carve_utf16(window.start as u64, window.data, &mut lanes, &mut low, &mut carved);
             }
-            
-            // In a real scanner, we'd do something like this:
-            // if let Some(chunk_bytes) = img.get_bytes(chunk_start, 4096) {
-            //     for i in 0..chunk_bytes.len() {
-            //         if Self::is_printable(chunk_bytes[i]) {
-            //             if let Some(string) = self.extract_ascii_string(chunk_bytes, i) {
-            //                 findings.push(Finding {
-            //                     plugin: self.name().to_string(),
-            //                     addr: (chunk_start + i) as u64,
-            //                     desc: string,
-            //                     confidence: 90,
-            //                     details: HashMap::new(),
-            //                 });
-            //                 i += string.len();
-            //             }
-            //         }
-            //     }
-            // }
-            
-            // Simulate work
-            if chunk_start % (1024 * 1024) == 0 {  // Every 1MB
-                std::thread::sleep(std::time::Duration::from_millis(5));
+            if carved.len() >= MAX_STRINGS {
+                break;
             }
         }
+        self.finish(ascii.take(), &mut carved);
+        lanes.iter_mut().for_each(|lane| self.finish(lane.take(), &mut carved));
         
+        carved.sort_by_key(|run| (run.start, run.utf16));
+        findings.extend(carved.iter().take(MAX_STRINGS).map(|run| self.report(run)));
+        if carved.len() > MAX_STRINGS {
+            progress.finish_with_message(format!("Stopped after {} strings", MAX_STRINGS));
+            return findings;
+        }
         progress.finish_with_message(format!("Found {} strings", findings.len()));
         findings
     }
     
+    fn configure(&self, settings: &HashMap<String, String>) -> Result<Box<dyn MemoryPlugin>> {
+        let mut plugin = StringCarvePlugin::new(self.min_string_len, self.scan_utf16);
+        for (key, value) in settings {
+            match key.as_str() {
+                "min_string_len" => plugin.min_string_len = value.parse().with_context(|| format!("min_string_len must be a number, not '{}'", value))?,
+                "scan_utf16" => plugin.scan_utf16 = value.parse().with_context(|| format!("scan_utf16 must be true or false, not '{}'", value))?,
+                _ => bail!("Plugin 'string_carve' takes no setting '{}' (min_string_len, scan_utf16)", key),
+            }
+        }
+        if plugin.min_string_len == 0 {
+            bail!("min_string_len must be at least 1");
+        }
+        Ok(Box::new(plugin))
+    }
+    
     fn get_version(&self) -> &'static str {
         "1.0.1"
     }
@@ -0,0 +1,264 @@
+//! Windows service scan
+//!
+//! The Service Control Manager in services.exe keeps a record for every
+//! installed service in its heap, with the service's name, display name,
+//! binary, status and start type. The records stay in memory after a
+//! malicious service deletes its binary and registry key, so they show
+//! services that left no trace on disk. The scanner walks the private
+//! regions of services.exe for the `serH` tag of each record's header and
+//! follows it to the record. Offsets are for Windows 10 x64.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::kdbg::OsContext;
+use crate::paging::MemoryImage;
+use crate::processes::{Process, ProcessFinder, WindowsProcessFinder};
+use crate::vad::VadKind;
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Tag at the start of a SERVICE_HEADER, which points to its record
+const SERVICE_HEADER_TAG: &[u8; 4] = b"serH";
+const HEADER_RECORD: u64 = 0x10;
+
+/// SERVICE_RECORD offsets
+const RECORD_SIZE: usize = 0x60;
+const RECORD_NAME: usize = 0x08;
+const RECORD_DISPLAY_NAME: usize = 0x10;
+const RECORD_ORDER: usize = 0x18;
+/// SERVICE_PROCESS for user-mode services, driver object name for drivers
+const RECORD_PROCESS_OR_DRIVER: usize = 0x28;
+/// SERVICE_STATUS.dwServiceType and dwCurrentState
+const RECORD_TYPE: usize = 0x38;
+const RECORD_STATE: usize = 0x3C;
+const RECORD_START_TYPE: usize = 0x58;
+
+/// SERVICE_PROCESS offsets
+const PROCESS_BINARY_PATH: u64 = 0x18;
+const PROCESS_PID: u64 = 0x28;
+
+/// Longest name or path read, in bytes
+const MAX_STRING: usize = 0x400;
+/// Private regions larger than this are not heaps worth scanning
+const MAX_REGION: u64 = 64 << 20;
+
+/// SERVICE_KERNEL_DRIVER and SERVICE_FILE_SYSTEM_DRIVER
+const SERVICE_DRIVER: u32 = 0x3;
+/// Every defined SERVICE_* type bit
+const SERVICE_TYPE_MASK: u32 = 0x3F3;
+const SERVICE_RUNNING: u32 = 4;
+
+const STATES: [&str; 7] = ["STOPPED", "START_PENDING", "STOP_PENDING", "RUNNING", "CONTINUE_PENDING", "PAUSE_PENDING", "PAUSED"];
+const START_TYPES: [&str; 5] = ["BOOT_START", "SYSTEM_START", "AUTO_START", "DEMAND_START", "DISABLED"];
+
+/// Directories any user can write to; services should not run from them
+const WRITABLE_DIRS: [&str; 5] = ["\\temp\\", "\\appdata\\", "\\users\\public\\", "\\programdata\\", "\\downloads\\"];
+/// Interpreters a service binary path should not start
+const SCRIPT_HOSTS: [&str; 6] = ["cmd.exe", "powershell", "pwsh", "wscript", "cscript", "mshta"];
+
+/// A decoded SCM service record
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceRecord {
+    pub name: String,
+    pub display_name: Option<String>,
+    /// Binary path of a user-mode service, driver object name of a driver
+    pub binary: Option<String>,
+    pub order: u32,
+    pub service_type: u32,
+    pub state: u32,
+    pub start_type: u32,
+    /// Process hosting a running user-mode service
+    pub pid: Option<u32>,
+}
+
+impl ServiceRecord {
+    pub fn is_driver(&self) -> bool {
+        self.service_type & SERVICE_DRIVER != 0
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        STATES.get(self.state.wrapping_sub(1) as usize).copied().unwrap_or("UNKNOWN")
+    }
+
+    pub fn start_type_name(&self) -> &'static str {
+        START_TYPES.get(self.start_type as usize).copied().unwrap_or("UNKNOWN")
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.service_type & !0x100 {
+            0x1 => "KERNEL_DRIVER",
+            0x2 => "FILE_SYSTEM_DRIVER",
+            0x10 => "WIN32_OWN_PROCESS",
+            0x20 => "WIN32_SHARE_PROCESS",
+            0x50 => "USER_OWN_PROCESS",
+            0x60 => "USER_SHARE_PROCESS",
+            _ => "UNKNOWN",
+        }
+    }
+}
+
+/// Read a NUL-terminated UTF-16 string at a virtual address
+fn wide_string_at(img: &MemoryImage, va: u64) -> Option<String> {
+    if va == 0 {
+        return None;
+    }
+    let text = img.read_utf16_string(img.virt_to_phys(va)? as usize, MAX_STRING)?;
+    let printable = !text.is_empty() && text.chars().all(|c| !c.is_control());
+    printable.then_some(text)
+}
+
+/// Validate and decode the SERVICE_RECORD at virtual address `va`
+pub fn parse_service_record(img: &MemoryImage, va: u64) -> Option<ServiceRecord> {
+    let body = img.read_virt(va, RECORD_SIZE)?;
+    let u32_at = |off: usize| u32::from_le_bytes(body[off..off + 4].try_into().unwrap());
+    let u64_at = |off: usize| u64::from_le_bytes(body[off..off + 8].try_into().unwrap());
+    let (service_type, state, start_type) = (u32_at(RECORD_TYPE), u32_at(RECORD_STATE), u32_at(RECORD_START_TYPE));
+    if service_type == 0 || service_type & !SERVICE_TYPE_MASK != 0 || !(1..=7).contains(&state) || start_type > 4 {
+        return None;
+    }
+
+    let mut record = ServiceRecord {
+        name: wide_string_at(img, u64_at(RECORD_NAME))?,
+        display_name: wide_string_at(img, u64_at(RECORD_DISPLAY_NAME)),
+        binary: None,
+        order: u32_at(RECORD_ORDER),
+        service_type,
+        state,
+        start_type,
+        pid: None,
+    };
+    let target = u64_at(RECORD_PROCESS_OR_DRIVER);
+    if record.is_driver() {
+        record.binary = wide_string_at(img, target);
+    } else if target != 0 {
+        record.binary = img.read_virt_u64(target + PROCESS_BINARY_PATH).and_then(|path| wide_string_at(img, path));
+        record.pid = img.read_virt_u32(target + PROCESS_PID).filter(|&pid| pid != 0);
+    }
+    Some(record)
+}
+
+/// A plugin that lists the services known to the Service Control Manager
+#[derive(Default)]
+pub struct ServiceScanner;
+
+impl ServiceScanner {
+    /// Service records in the private memory of services.exe, by address
+    fn records(&self, img: &MemoryImage, finder: &WindowsProcessFinder, services: &Process, progress: &ProgressBar) -> Vec<(u64, ServiceRecord)> {
+        let Some(space) = services.address_space(img) else { return Vec::new() };
+        let regions: Vec<_> = finder.vads(img, services).into_iter()
+            .filter(|vad| vad.kind == VadKind::Private && vad.protection.is_writable() && vad.size() <= MAX_REGION)
+            .collect();
+        progress.set_length(regions.iter().map(|vad| vad.size()).sum());
+        progress.set_position(0);
+
+        let mut records: Vec<(u64, ServiceRecord)> = Vec::new();
+        for vad in &regions {
+            for page in (vad.start..=vad.end).step_by(0x1000) {
+                progress.inc(0x1000);
+                let Some(data) = space.read_virt(page, 0x1000) else { continue };
+                for at in (0..data.len()).step_by(8).filter(|&at| data[at..].starts_with(SERVICE_HEADER_TAG)) {
+                    let Some(record_va) = space.read_virt_u64(page + at as u64 + HEADER_RECORD) else { continue };
+                    if records.iter().any(|(va, _)| *va == record_va) {
+                        continue;
+                    }
+                    if let Some(record) = parse_service_record(&space, record_va) {
+                        records.push((record_va, record));
+                    }
+                }
+            }
+        }
+        records.sort_by_key(|(_, record)| record.order);
+        records
+    }
+
+    /// Why a service looks malicious, if it does
+    fn suspicion(&self, record: &ServiceRecord, pids: &[u32]) -> Option<(&'static str, String)> {
+        let binary = record.binary.as_deref().unwrap_or_default().to_lowercase();
+        if let Some(dir) = WRITABLE_DIRS.iter().find(|dir| binary.contains(*dir)) {
+            return Some(("service_binary_in_writable_path", format!("binary under {}", dir.trim_matches('\\'))));
+        }
+        if let Some(host) = SCRIPT_HOSTS.iter().find(|host| binary.contains(*host)) {
+            return Some(("service_runs_script_host", format!("binary path runs {}", host)));
+        }
+        match record.pid {
+            Some(pid) if record.state == SERVICE_RUNNING && !pids.is_empty() && !pids.contains(&pid) =>
+                Some(("service_process_missing", format!("running in PID {}, which is not on the process list", pid))),
+            _ => None,
+        }
+    }
+
+    fn report(&self, addr: u64, record: &ServiceRecord, pids: &[u32], findings: &mut Vec<Finding>) {
+        let suspicion = self.suspicion(record, pids);
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "service".to_string());
+        details.insert("rule".to_string(), suspicion.as_ref().map_or("service_record", |(rule, _)| rule).to_string());
+        details.insert("service".to_string(), record.name.clone());
+        details.insert("service_type".to_string(), record.type_name().to_string());
+        details.insert("state".to_string(), record.state_name().to_string());
+        details.insert("start_type".to_string(), record.start_type_name().to_string());
+        details.insert("order".to_string(), record.order.to_string());
+        if let Some(display) = &record.display_name {
+            details.insert("display_name".to_string(), display.clone());
+        }
+        if let Some(binary) = &record.binary {
+            details.insert("binary".to_string(), binary.clone());
+        }
+        if let Some(pid) = record.pid {
+            details.insert("pid".to_string(), pid.to_string());
+        }
+
+        let summary = format!("{} ({}, {}, {})", record.name, record.type_name(), record.state_name(), record.start_type_name());
+        findings.push(Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: match &suspicion {
+                Some((_, reason)) => format!("Suspicious service {}: {}", summary, reason),
+                None => format!("Service {} {}", summary, record.binary.as_deref().unwrap_or("-")),
+            },
+            confidence: if suspicion.is_some() { 75 } else { 50 },
+            details,
+        });
+    }
+}
+
+impl MemoryPlugin for ServiceScanner {
+    fn name(&self) -> &'static str {
+        "svcscan"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::High
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Lists services from the SCM records in services.exe, including services deleted from disk"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Locating services.exe");
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else {
+            progress.finish_with_message("No kernel found; services need the process list");
+            return findings;
+        };
+        let finder = WindowsProcessFinder::new().with_os_context(os);
+        let processes = finder.find_processes(img, &ProgressBar::hidden()).unwrap_or_default();
+        let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+
+        progress.set_message("Scanning services.exe for service records");
+        for services in processes.iter().filter(|p| p.name.eq_ignore_ascii_case("services.exe")) {
+            for (va, record) in self.records(img, &finder, services, progress) {
+                let space = services.address_space(img);
+                let addr = space.and_then(|space| space.virt_to_phys(va)).unwrap_or(va);
+                self.report(addr, &record, &pids, &mut findings);
+            }
+        }
+
+        progress.finish_with_message(format!("Found {} services", findings.len()));
+        findings
+    }
+}
@@ -0,0 +1,322 @@
+//! Kernel timers and queued DPCs
+//!
+//! A driver can run code periodically without a thread of its own by
+//! setting a KTIMER with a DPC: the kernel calls the DPC's deferred routine
+//! each time the timer fires. Rootkits use timers to re-apply hooks and
+//! beacon, and a routine in pool memory outside every loaded module is a
+//! strong sign of hidden code. Each KPRCB on KiProcessorBlock holds a
+//! timer table of list heads, found by its shape since the offset changes
+//! between builds, and the lists of DPCs queued to run on that processor,
+//! found by a DPC that points back at its list.
+//!
+//! Since Windows 8.1 the DPC pointer in a KTIMER is encoded with the
+//! KiWaitNever and KiWaitAlways keys. They are not exported, so the
+//! variables KeSetTimer reads are tried as keys and the pair that decodes
+//! the most timers to valid KDPCs wins. Offsets are for Windows 10 x64.
+
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::kdbg::OsContext;
+use crate::modules::{list_kernel_modules, module_containing, read_pages, KernelModule};
+use crate::paging::MemoryImage;
+use crate::pe::{module_exports, Export};
+use super::callbacks::rip_operands;
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+use super::ssdt::processor_blocks;
+
+/// Bytes of each KPRCB searched for the timer table and DPC lists
+const PRCB_BYTES: u64 = 0x8000;
+/// KTIMER_TABLE_ENTRY: Lock, Entry (LIST_ENTRY), Time
+const TIMER_ENTRY_SIZE: usize = 0x20;
+const TIMER_ENTRY_LIST: usize = 0x8;
+/// Timer list heads that must be present for a table to be accepted, and
+/// the most followed (newer builds keep two tables of 256)
+const MIN_TIMER_LISTS: usize = 256;
+const MAX_TIMER_LISTS: usize = 512;
+/// KTIMER offsets
+const KTIMER_DUE_TIME: u64 = 0x18;
+const KTIMER_LIST_ENTRY: u64 = 0x20;
+const KTIMER_DPC: u64 = 0x30;
+const KTIMER_PERIOD: u64 = 0x3C;
+/// KDPC offsets and object types
+const KDPC_LIST_ENTRY: u64 = 0x8;
+const KDPC_ROUTINE: u64 = 0x18;
+const KDPC_CONTEXT: u64 = 0x20;
+const KDPC_DATA: u64 = 0x38;
+const DPC_OBJECT: u8 = 0x13;
+const THREADED_DPC_OBJECT: u8 = 0x1A;
+
+/// Exported routine reading KiWaitNever and KiWaitAlways, and how many of
+/// the variables it reads are tried as keys
+const SET_TIMER: &str = "KeSetTimer";
+const MAX_KEY_CANDIDATES: usize = 6;
+/// Upper bound on list entries, guarding against corrupted links
+const MAX_LIST_ENTRIES: usize = 4096;
+const KERNEL_SPACE: u64 = 0xFFFF_8000_0000_0000;
+
+/// A deferred procedure call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dpc {
+    pub address: u64,
+    pub routine: u64,
+    pub context: u64,
+}
+
+/// A timer on a processor's timer table
+#[derive(Debug, Clone, PartialEq)]
+pub struct KernelTimer {
+    pub address: u64,
+    /// Interrupt time the timer fires at, in 100ns units
+    pub due_time: u64,
+    /// Milliseconds between firings of a periodic timer, zero for one-shot
+    pub period: u32,
+    /// KTIMER.Dpc as stored, possibly encoded
+    pub raw_dpc: u64,
+}
+
+/// KiWaitNever and KiWaitAlways, the keys KTIMER.Dpc is encoded with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaitKeys {
+    pub never: u64,
+    pub always: u64,
+}
+
+impl WaitKeys {
+    /// The KDPC pointer encoded in the KTIMER at `timer`
+    pub fn decode(&self, encoded: u64, timer: u64) -> u64 {
+        (encoded ^ self.never).rotate_left(self.never as u8 as u32) ^ timer.swap_bytes() ^ self.always
+    }
+}
+
+impl KernelTimer {
+    /// The timer's DPC, read as a plain pointer first and then decoded with
+    /// `keys`; None for timers without one
+    pub fn dpc(&self, img: &MemoryImage, keys: Option<WaitKeys>) -> Option<Dpc> {
+        if self.raw_dpc == 0 {
+            return None;
+        }
+        read_dpc(img, self.raw_dpc).or_else(|| read_dpc(img, keys?.decode(self.raw_dpc, self.address)))
+    }
+}
+
+fn is_kernel_va(va: u64) -> bool {
+    va >= KERNEL_SPACE
+}
+
+fn u64_at(data: &[u8], off: usize) -> u64 {
+    u64::from_le_bytes(data[off..off + 8].try_into().unwrap())
+}
+
+/// The KDPC at `address`, or None when it is not one
+pub fn read_dpc(img: &MemoryImage, address: u64) -> Option<Dpc> {
+    if !is_kernel_va(address) {
+        return None;
+    }
+    let kind = img.read_virt(address, 1)?[0];
+    let routine = img.read_virt_u64(address + KDPC_ROUTINE).filter(|&r| is_kernel_va(r))?;
+    (kind == DPC_OBJECT || kind == THREADED_DPC_OBJECT).then(|| Dpc {
+        address,
+        routine,
+        context: img.read_virt_u64(address + KDPC_CONTEXT).unwrap_or(0),
+    })
+}
+
+/// Whether the LIST_ENTRY at `head` in `data` (mapped at `base`) is empty
+/// and points at itself, or has both links in kernel space
+fn is_list_head(data: &[u8], base: u64, offset: usize) -> bool {
+    let head = base + offset as u64;
+    let (flink, blink) = (u64_at(data, offset), u64_at(data, offset + 8));
+    is_kernel_va(flink) && is_kernel_va(blink) && (flink == head) == (blink == head)
+}
+
+/// Addresses of the timer list heads in the KPRCB read into `data` from
+/// `prcb`: the first run of KTIMER_TABLE_ENTRYs long enough to be a table
+pub fn find_timer_lists(data: &[u8], prcb: u64) -> Vec<u64> {
+    let count = |start: usize| {
+        (0..MAX_TIMER_LISTS)
+            .map(|i| start + i * TIMER_ENTRY_SIZE + TIMER_ENTRY_LIST)
+            .take_while(|&offset| offset + 16 <= data.len() && is_list_head(data, prcb, offset))
+            .count()
+    };
+    (0..data.len()).step_by(8)
+        .map(|start| (start, count(start)))
+        .find(|&(_, lists)| lists >= MIN_TIMER_LISTS)
+        .map(|(start, lists)| (0..lists).map(|i| prcb + (start + i * TIMER_ENTRY_SIZE + TIMER_ENTRY_LIST) as u64).collect())
+        .unwrap_or_default()
+}
+
+/// Entries of the LIST_ENTRY at `head`, stopping at a broken link
+fn list_entries(img: &MemoryImage, head: u64) -> Vec<u64> {
+    let mut entries = Vec::new();
+    let mut link = img.read_virt_u64(head).unwrap_or(head);
+    while link != head && is_kernel_va(link) && entries.len() < MAX_LIST_ENTRIES && !entries.contains(&link) {
+        entries.push(link);
+        let Some(next) = img.read_virt_u64(link) else { break };
+        link = next;
+    }
+    entries
+}
+
+/// Timers on the timer table of the KPRCB at `prcb`
+pub fn prcb_timers(img: &MemoryImage, prcb: u64) -> Vec<KernelTimer> {
+    let (data, _) = read_pages(img, prcb, PRCB_BYTES);
+    find_timer_lists(&data, prcb).into_iter()
+        .flat_map(|head| list_entries(img, head))
+        .map(|entry| {
+            let address = entry - KTIMER_LIST_ENTRY;
+            KernelTimer {
+                address,
+                due_time: img.read_virt_u64(address + KTIMER_DUE_TIME).unwrap_or(0),
+                period: img.read_virt_u32(address + KTIMER_PERIOD).unwrap_or(0),
+                raw_dpc: img.read_virt_u64(address + KTIMER_DPC).unwrap_or(0),
+            }
+        })
+        .collect()
+}
+
+/// DPCs queued on the KPRCB at `prcb`: each KDPC_DATA list starts with a
+/// pointer to a KDPC's list entry, and that KDPC points back at the list
+pub fn queued_dpcs(img: &MemoryImage, prcb: u64) -> Vec<Dpc> {
+    let (data, _) = read_pages(img, prcb, PRCB_BYTES);
+    let mut dpcs = Vec::new();
+    for offset in (0..data.len()).step_by(8) {
+        let list = prcb + offset as u64;
+        let first = u64_at(&data, offset);
+        if !is_kernel_va(first) || img.read_virt_u64(first.wrapping_sub(KDPC_LIST_ENTRY) + KDPC_DATA) != Some(list) {
+            continue;
+        }
+        let mut link = first;
+        while is_kernel_va(link) && dpcs.len() < MAX_LIST_ENTRIES {
+            let Some(dpc) = read_dpc(img, link - KDPC_LIST_ENTRY) else { break };
+            if dpcs.contains(&dpc) {
+                break;
+            }
+            dpcs.push(dpc);
+            link = img.read_virt_u64(link).unwrap_or(0);
+        }
+    }
+    dpcs
+}
+
+/// The KiWaitNever and KiWaitAlways pair, among the variables KeSetTimer
+/// reads, that decodes the most of `timers` to valid KDPCs
+pub fn wait_keys(img: &MemoryImage, kernel: &KernelModule, exports: &[Export], timers: &[KernelTimer]) -> Option<WaitKeys> {
+    let routine = exports.iter().find(|e| e.name == SET_TIMER)?.address;
+    let mut variables: Vec<u64> = Vec::new();
+    for va in rip_operands(img, routine, kernel, 1, "mov") {
+        if kernel.contains(va) && !variables.contains(&va) && variables.len() < MAX_KEY_CANDIDATES {
+            variables.push(va);
+        }
+    }
+    let values: Vec<u64> = variables.iter().filter_map(|&va| img.read_virt_u64(va)).collect();
+    let encoded: Vec<&KernelTimer> = timers.iter().filter(|t| t.raw_dpc != 0 && read_dpc(img, t.raw_dpc).is_none()).collect();
+    values.iter()
+        .flat_map(|&never| values.iter().map(move |&always| WaitKeys { never, always }))
+        .filter(|keys| keys.never != keys.always)
+        .map(|keys| (encoded.iter().filter(|t| read_dpc(img, keys.decode(t.raw_dpc, t.address)).is_some()).count(), keys))
+        .filter(|&(decoded, _)| decoded > 0)
+        .max_by_key(|&(decoded, _)| decoded)
+        .map(|(_, keys)| keys)
+}
+
+/// A plugin that lists timer DPCs and queued DPCs with their owning modules
+#[derive(Default)]
+pub struct TimerScanner;
+
+impl TimerScanner {
+    fn report(&self, img: &MemoryImage, cpu: usize, timer: Option<&KernelTimer>, dpc: &Dpc, owner: Option<&KernelModule>) -> Finding {
+        let rule = match (timer.is_some(), owner.is_some()) {
+            (true, true) => "kernel_timer",
+            (true, false) => "timer_in_unbacked_memory",
+            (false, true) => "queued_dpc",
+            (false, false) => "dpc_in_unbacked_memory",
+        };
+        let module = owner.map_or("unknown", |m| m.name.as_str());
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "timer".to_string());
+        details.insert("rule".to_string(), rule.to_string());
+        details.insert("cpu".to_string(), cpu.to_string());
+        details.insert("dpc".to_string(), format!("{:#x}", dpc.address));
+        details.insert("routine".to_string(), format!("{:#x}", dpc.routine));
+        details.insert("context".to_string(), format!("{:#x}", dpc.context));
+        details.insert("module".to_string(), module.to_string());
+        let (object, desc) = match timer {
+            Some(timer) => {
+                details.insert("timer".to_string(), format!("{:#x}", timer.address));
+                details.insert("due_time".to_string(), timer.due_time.to_string());
+                details.insert("period".to_string(), timer.period.to_string());
+                let period = if timer.period > 0 { format!(" every {} ms", timer.period) } else { String::new() };
+                (timer.address, format!("Timer {:#x} on CPU {} runs {:#x} in {}{}", timer.address, cpu, dpc.routine, module, period))
+            }
+            None => (dpc.address, format!("DPC {:#x} queued on CPU {} runs {:#x} in {}", dpc.address, cpu, dpc.routine, module)),
+        };
+        Finding {
+            plugin: self.name().to_string(),
+            addr: img.virt_to_phys(object).unwrap_or(object),
+            desc,
+            confidence: if owner.is_some() { 30 } else { 90 },
+            details,
+        }
+    }
+}
+
+impl MemoryPlugin for TimerScanner {
+    fn name(&self) -> &'static str {
+        "timers"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: true, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Lists kernel timers and queued DPCs with their routines' modules, flagging routines in unbacked memory"
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        progress.set_message("Locating the kernel");
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else {
+            progress.finish_with_message("No kernel found; timers need KDBG");
+            return findings;
+        };
+        let Some(ki_processor_block) = os.ki_processor_block else {
+            progress.finish_with_message("KDBG has no KiProcessorBlock");
+            return findings;
+        };
+        let modules = list_kernel_modules(img, &os);
+        let processors = processor_blocks(img, ki_processor_block);
+
+        progress.set_message("Walking timer tables");
+        let timers: Vec<(usize, KernelTimer)> = processors.iter()
+            .flat_map(|&(cpu, prcb)| prcb_timers(img, prcb).into_iter().map(move |timer| (cpu, timer)))
+            .collect();
+        let all: Vec<KernelTimer> = timers.iter().map(|(_, timer)| timer.clone()).collect();
+        let keys = module_containing(&modules, os.kernel_base)
+            .and_then(|kernel| wait_keys(img, kernel, &module_exports(img, kernel.base), &all));
+        let mut undecoded = 0;
+        for (cpu, timer) in &timers {
+            match timer.dpc(img, keys) {
+                Some(dpc) => findings.push(self.report(img, *cpu, Some(timer), &dpc, module_containing(&modules, dpc.routine))),
+                None if timer.raw_dpc != 0 => undecoded += 1,
+                None => {}
+            }
+        }
+
+        progress.set_message("Walking DPC queues");
+        for &(cpu, prcb) in &processors {
+            for dpc in queued_dpcs(img, prcb) {
+                findings.push(self.report(img, cpu, None, &dpc, module_containing(&modules, dpc.routine)));
+            }
+        }
+
+        let skipped = if undecoded > 0 { format!(" ({} timer DPCs could not be decoded)", undecoded) } else { String::new() };
+        progress.finish_with_message(format!("Found {} timer and DPC routines{}", findings.len(), skipped));
+        findings
+    }
+}
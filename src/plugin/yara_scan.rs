@@ -0,0 +1,227 @@
+//! YARA rule scanning
+//!
+//! Runs a rule file (`--rules rules.yar`) over physical memory and, with
+//! `--processes`, over the virtual memory of each process, so a pattern
+//! split across two non-contiguous physical pages is still found in the
+//! process that maps them. Physical memory is scanned in overlapping
+//! windows whose conditions are evaluated separately; a match is reported
+//! by the window its first string starts in. Rules that match without a
+//! string (`condition: true`) would match every window and are not
+//! reported. Rules are compiled and evaluated by yara-x, so rule sets
+//! importing modules such as `pe` or `hash` load as they would in `yara`.
+
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::kdbg::OsContext;
+use crate::modules::read_pages;
+use crate::paging::MemoryImage;
+use crate::scan_util::chunks;
+use crate::processes::{Process, ProcessFinder, WindowsProcessFinder};
+use yara_x::{MetaValue, Rules, Scanner};
+use super::registry::{MemoryPlugin, Finding, PluginNeeds, Priority};
+
+/// Physical memory scanned per window, and how far each window runs into
+/// the next so matches spanning the boundary are seen whole
+const WINDOW: usize = 0x100000;
+const OVERLAP: usize = 0x1000;
+/// Bytes of each process region scanned
+const MAX_REGION: u64 = 0x1000000;
+/// Matched strings listed per finding, and bytes shown of each
+const MAX_LISTED: usize = 16;
+const MAX_SHOWN: usize = 32;
+
+/// A matched rule, copied out of the scan results
+struct RuleMatch {
+    rule: String,
+    tags: Vec<String>,
+    meta: Vec<(String, String)>,
+    /// Matched strings in order of offset
+    strings: Vec<StringMatch>,
+}
+
+struct StringMatch {
+    id: String,
+    offset: usize,
+    data: Vec<u8>,
+}
+
+/// Scan `data`, returning the rules that match. A window that fails to
+/// scan (a rule timing out, say) is treated as matching nothing.
+fn scan_rules(scanner: &mut Scanner, data: &[u8]) -> Vec<RuleMatch> {
+    let Ok(results) = scanner.scan(data) else { return Vec::new() };
+    results.matching_rules().map(|rule| {
+        let mut strings: Vec<StringMatch> = rule.patterns()
+            .flat_map(|pattern| pattern.matches().map(move |m| StringMatch {
+                id: pattern.identifier().to_string(),
+                offset: m.range().start,
+                data: m.data().to_vec(),
+            }))
+            .collect();
+        strings.sort_by_key(|s| s.offset);
+        RuleMatch {
+            rule: rule.identifier().to_string(),
+            tags: rule.tags().map(|tag| tag.identifier().to_string()).collect(),
+            meta: rule.metadata().map(|(key, value)| (key.to_string(), match value {
+                MetaValue::Integer(i) => i.to_string(),
+                MetaValue::Float(f) => f.to_string(),
+                MetaValue::Bool(b) => b.to_string(),
+                MetaValue::String(s) => s.to_string(),
+                MetaValue::Bytes(b) => String::from_utf8_lossy(b).into_owned(),
+            })).collect(),
+            strings,
+        }
+    }).collect()
+}
+
+/// A plugin that reports where the rules of a YARA file match
+#[derive(Default)]
+pub struct YaraScanner {
+    rules: Option<Rules>,
+    /// SHA-256 prefix of the rule source, recorded with the findings
+    digest: String,
+    processes: bool,
+}
+
+impl YaraScanner {
+    /// Scan with the rules in `source`
+    pub fn new(source: &str) -> Result<Self> {
+        let digest = Sha256::digest(source.as_bytes())[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(YaraScanner { rules: Some(yara_x::compile(source)?), digest, processes: false })
+    }
+
+    /// Scan with the rules in the file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path).with_context(|| format!("Failed to read rules from {}", path.display()))?;
+        Self::new(&source).with_context(|| format!("Failed to compile {}", path.display()))
+    }
+
+    /// Also scan the virtual memory of each process
+    pub fn with_processes(mut self, processes: bool) -> Self {
+        self.processes = processes;
+        self
+    }
+
+    fn report(&self, addr: u64, base: u64, found: &RuleMatch, process: Option<&Process>) -> Finding {
+        let strings: Vec<String> = found.strings.iter().take(MAX_LISTED).map(|s| {
+            let shown = &s.data[..s.data.len().min(MAX_SHOWN)];
+            let text = if shown.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+                format!("\"{}\"", String::from_utf8_lossy(shown))
+            } else {
+                shown.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+            };
+            format!("{} at {:#x}: {}{}", s.id, base + s.offset as u64, text, if s.data.len() > MAX_SHOWN { " ..." } else { "" })
+        }).collect();
+        let mut ids: Vec<&str> = found.strings.iter().map(|s| s.id.as_str()).collect();
+        ids.dedup();
+        let first = base + found.strings[0].offset as u64;
+
+        let mut details = HashMap::new();
+        details.insert("type".to_string(), "yara".to_string());
+        details.insert("rule".to_string(), found.rule.clone());
+        details.insert("tags".to_string(), found.tags.join(","));
+        details.insert("meta".to_string(), found.meta.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("; "));
+        details.insert("strings".to_string(), strings.join("\n"));
+        details.insert("matches".to_string(), found.strings.len().to_string());
+        let desc = match process {
+            Some(process) => {
+                details.insert("pid".to_string(), process.pid.to_string());
+                details.insert("process".to_string(), process.name.clone());
+                details.insert("va".to_string(), format!("{:#x}", first));
+                format!("YARA rule {} matched in {} (PID {}) at {:#x} ({})", found.rule, process.name, process.pid, first, ids.join(", "))
+            }
+            None => format!("YARA rule {} matched at {:#x} ({})", found.rule, first, ids.join(", ")),
+        };
+        Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc,
+            confidence: 70,
+            details,
+        }
+    }
+
+    fn scan_physical(&self, rules: &Rules, img: &MemoryImage, progress: &ProgressBar, findings: &mut Vec<Finding>) {
+        progress.set_message("Scanning physical memory");
+        let mut scanner = Scanner::new(rules);
+        for window in chunks(img, WINDOW, OVERLAP).with_progress(progress) {
+            for found in scan_rules(&mut scanner, window.data) {
+                // The next window reports matches starting in the overlap
+                if found.strings.first().is_some_and(|s| window.owns(s.offset)) {
+                    let addr = window.addr(found.strings[0].offset);
+                    findings.push(self.report(addr, window.start as u64, &found, None));
+                }
+            }
+        }
+    }
+
+    fn scan_processes(&self, rules: &Rules, img: &MemoryImage, progress: &ProgressBar, findings: &mut Vec<Finding>) {
+        let Some(os) = img.info.dtb.and_then(|_| OsContext::find(img, &ProgressBar::hidden())) else { return };
+        let finder = WindowsProcessFinder::new().with_os_context(os);
+        let processes = finder.find_processes(img, &ProgressBar::hidden()).unwrap_or_default();
+        progress.set_message("Scanning process memory");
+        let mut scanner = Scanner::new(rules);
+        progress.set_length(processes.len() as u64);
+        progress.set_position(0);
+        for process in &processes {
+            progress.inc(1);
+            let Some(space) = process.address_space(img) else { continue };
+            for vad in finder.vads(img, process) {
+                let (data, missing) = read_pages(&space, vad.start, vad.size().min(MAX_REGION));
+                if missing as u64 * 0x1000 >= data.len() as u64 {
+                    continue;
+                }
+                for found in scan_rules(&mut scanner, &data).into_iter().filter(|f| !f.strings.is_empty()) {
+                    let va = vad.start + found.strings[0].offset as u64;
+                    // A match in a page that isn't resident has no physical
+                    // address; it is reported at 0 and marked, with its VA
+                    let pa = space.virt_to_phys(va);
+                    let mut finding = self.report(pa.unwrap_or(0), vad.start, &found, Some(process));
+                    if pa.is_none() {
+                        finding.details.insert("unmapped".to_string(), "true".to_string());
+                    }
+                    findings.push(finding);
+                }
+            }
+        }
+    }
+}
+
+impl MemoryPlugin for YaraScanner {
+    fn name(&self) -> &'static str {
+        "yara"
+    }
+
+    fn priority(&self) -> Priority {
+        Priority::Low
+    }
+
+    fn needs(&self) -> PluginNeeds {
+        PluginNeeds { kernel_dtb: self.processes, ..Default::default() }
+    }
+
+    fn description(&self) -> &'static str {
+        "Scans physical (and with --processes, process) memory with the YARA rules given by --rules"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![("rules", self.digest.clone()), ("processes", self.processes.to_string())]
+    }
+
+    fn scan(&self, img: &MemoryImage, progress: &ProgressBar) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let Some(rules) = &self.rules else {
+            progress.finish_with_message("No rules loaded; run the yara plugin with --rules");
+            return findings;
+        };
+        self.scan_physical(rules, img, progress, &mut findings);
+        if self.processes {
+            self.scan_processes(rules, img, progress, &mut findings);
+        }
+        progress.finish_with_message(format!("Found {} YARA matches", findings.len()));
+        findings
+    }
+}
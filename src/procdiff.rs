@@ -0,0 +1,252 @@
+//! Comparison of one process across two captures
+//!
+//! A snapshot records a process's user-mode mappings, loader modules,
+//! threads and a hash of every executable page. Diffing the snapshots of
+//! the same PID taken at two points in time shows what changed in between;
+//! executable pages that appeared or changed outside any loaded module are
+//! reported as injected code.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use crate::arch::x86_64::PAGE_SIZE;
+use crate::kdbg::OsContext;
+use crate::limits::{parallel_map, ResourceLimits};
+use crate::loader::load_memory_image;
+use crate::paging::MemoryImage;
+use crate::plugin::parse_peb;
+use crate::processes::{ProcessContext, ProcessFinder, WindowsProcessFinder};
+
+/// First address above the x64 user-mode half
+const USER_LIMIT: u64 = 0x0000_8000_0000_0000;
+
+/// A contiguous run of user mappings with the same protection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MemoryRegion {
+    pub va: u64,
+    pub size: u64,
+    pub executable: bool,
+}
+
+/// A module on the process's loader list
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LoadedModule {
+    pub base: u64,
+    pub size: u64,
+    pub name: String,
+}
+
+/// State of one process in one capture
+#[derive(Debug, Clone, Default)]
+pub struct ProcessSnapshot {
+    pub pid: u32,
+    pub name: String,
+    pub regions: Vec<MemoryRegion>,
+    pub modules: Vec<LoadedModule>,
+    pub threads: BTreeSet<u32>,
+    /// SHA-256 of each executable 4KB page, keyed by virtual address
+    pub exec_pages: BTreeMap<u64, String>,
+}
+
+impl ProcessSnapshot {
+    /// Locate `pid` through KDBG and snapshot it; the image must use the kernel DTB
+    pub fn capture(img: &MemoryImage, pid: u32, progress: &ProgressBar) -> Result<Self> {
+        img.info.dtb.context("A kernel DTB is required (use --dtb)")?;
+        let os = OsContext::find(img, progress).context("No KDBG block found")?;
+        let finder = WindowsProcessFinder::new().with_os_context(os);
+        let process = finder.find_processes(img, progress)?
+            .into_iter()
+            .find(|p| p.pid == pid)
+            .with_context(|| format!("Process {} is not on the active process list", pid))?;
+        let ctx = finder.process_context(img, process.virtual_address)
+            .with_context(|| format!("Cannot read the EPROCESS of process {}", pid))?;
+        let space = process.address_space(img)
+            .with_context(|| format!("Process {} has no DTB", pid))?;
+        Ok(Self::from_address_space(&space, pid, &process.name, &ctx))
+    }
+
+    /// Snapshot the address space the image translates through
+    pub fn from_address_space(img: &MemoryImage, pid: u32, name: &str, ctx: &ProcessContext) -> Self {
+        let mut snapshot = ProcessSnapshot {
+            pid,
+            name: name.to_string(),
+            threads: ctx.threads.iter().copied().collect(),
+            ..Default::default()
+        };
+
+        for mapping in img.mappings().into_iter().filter(|m| m.va < USER_LIMIT) {
+            match snapshot.regions.last_mut() {
+                Some(last) if last.va + last.size == mapping.va && last.executable == mapping.executable => {
+                    last.size += mapping.size;
+                }
+                _ => snapshot.regions.push(MemoryRegion { va: mapping.va, size: mapping.size, executable: mapping.executable }),
+            }
+            if mapping.executable {
+                for offset in (0..mapping.size).step_by(PAGE_SIZE) {
+                    if let Some(page) = img.get_bytes((mapping.pa + offset) as usize, PAGE_SIZE) {
+                        let hash = Sha256::digest(page).iter().map(|b| format!("{:02x}", b)).collect();
+                        snapshot.exec_pages.insert(mapping.va + offset, hash);
+                    }
+                }
+            }
+        }
+
+        if let Some(peb) = parse_peb(img, ctx.peb).filter(|_| ctx.peb != 0) {
+            snapshot.modules = peb.ldr_modules.into_iter()
+                .map(|(base, size, name)| LoadedModule { base, size, name })
+                .collect();
+        }
+
+        snapshot
+    }
+
+    /// Module whose image contains `va`
+    pub fn module_at(&self, va: u64) -> Option<&LoadedModule> {
+        self.modules.iter().find(|m| m.base <= va && va < m.base + m.size)
+    }
+
+    /// Changes from this snapshot to a `later` one of the same process
+    pub fn diff(&self, later: &ProcessSnapshot) -> ProcessDiff {
+        fn added<T: Clone + Ord>(from: &[T], to: &[T]) -> Vec<T> {
+            let from: BTreeSet<&T> = from.iter().collect();
+            to.iter().filter(|x| !from.contains(x)).cloned().collect()
+        }
+
+        let mut diff = ProcessDiff {
+            regions_added: added(&self.regions, &later.regions),
+            regions_removed: added(&later.regions, &self.regions),
+            modules_loaded: added(&self.modules, &later.modules),
+            modules_unloaded: added(&later.modules, &self.modules),
+            threads_started: later.threads.difference(&self.threads).copied().collect(),
+            threads_exited: self.threads.difference(&later.threads).copied().collect(),
+            ..Default::default()
+        };
+
+        for (&va, hash) in &later.exec_pages {
+            match self.exec_pages.get(&va) {
+                None => diff.pages_added.push(va),
+                Some(old) if old != hash => diff.pages_modified.push(va),
+                _ => {}
+            }
+        }
+        diff.pages_removed = self.exec_pages.keys().filter(|va| !later.exec_pages.contains_key(va)).copied().collect();
+
+        // New or rewritten code that no loaded module accounts for
+        let mut changed: Vec<u64> = diff.pages_added.iter().chain(&diff.pages_modified).copied().collect();
+        changed.sort_unstable();
+        for va in changed.into_iter().filter(|&va| later.module_at(va).is_none()) {
+            match diff.injected.last_mut() {
+                Some((start, size)) if *start + *size == va => *size += PAGE_SIZE as u64,
+                _ => diff.injected.push((va, PAGE_SIZE as u64)),
+            }
+        }
+
+        diff
+    }
+}
+
+/// Differences between two snapshots of one process
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessDiff {
+    pub regions_added: Vec<MemoryRegion>,
+    pub regions_removed: Vec<MemoryRegion>,
+    pub modules_loaded: Vec<LoadedModule>,
+    pub modules_unloaded: Vec<LoadedModule>,
+    pub threads_started: Vec<u32>,
+    pub threads_exited: Vec<u32>,
+    /// Executable pages that did not exist, or were not executable, before
+    pub pages_added: Vec<u64>,
+    /// Executable pages whose contents changed
+    pub pages_modified: Vec<u64>,
+    pub pages_removed: Vec<u64>,
+    /// (start, size) ranges of added or modified code outside every module
+    pub injected: Vec<(u64, u64)>,
+}
+
+impl ProcessDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == ProcessDiff::default()
+    }
+}
+
+fn region_row(table: &mut Table, change: &str, region: &MemoryRegion) {
+    table.add_row(row![
+        change,
+        Fy->format!("0x{:012X}", region.va),
+        format!("0x{:X}", region.size),
+        if region.executable { "RX" } else { "R" }
+    ]);
+}
+
+/// Snapshot `pid` in both captures and print what changed between them
+pub fn report_process_diff(before: PathBuf, after: PathBuf, pid: u32, dtb: u64) -> Result<()> {
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+
+    // Both captures are snapshotted in parallel unless --max-threads says otherwise
+    let paths = [before, after];
+    let snapshots = parallel_map(&paths, ResourceLimits::new().threads_for(paths.len()), |path| {
+        let mut memory_image = load_memory_image(path)?;
+        if memory_image.info.user.is_some() {
+            bail!("{} is a user-mode dump; diff-proc needs kernel captures", path.display());
+        }
+        memory_image.set_cr3(dtb);
+        ProcessSnapshot::capture(&memory_image, pid, &progress)
+            .with_context(|| format!("Failed to snapshot {}", path.display()))
+    }).into_iter().collect::<Result<Vec<_>>>()?;
+    progress.finish_and_clear();
+
+    let (old, new) = (&snapshots[0], &snapshots[1]);
+    let diff = old.diff(new);
+    println!("{} {} (PID {})", "Process:".bright_green(), new.name.bright_yellow(), pid);
+    println!("  {:<18} {} -> {}", "Regions", old.regions.len(), new.regions.len());
+    println!("  {:<18} {} -> {}", "Modules", old.modules.len(), new.modules.len());
+    println!("  {:<18} {} -> {}", "Threads", old.threads.len(), new.threads.len());
+    println!("  {:<18} {} -> {}", "Executable pages", old.exec_pages.len(), new.exec_pages.len());
+
+    if diff.is_empty() {
+        println!("\n{}", "No changes between the captures".bright_green());
+        return Ok(());
+    }
+
+    if !diff.regions_added.is_empty() || !diff.regions_removed.is_empty() {
+        let mut table = Table::new();
+        table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+        table.set_titles(row![bFg->"Change", bFg->"Address", bFg->"Size", bFg->"Protection"]);
+        diff.regions_removed.iter().for_each(|r| region_row(&mut table, "-", r));
+        diff.regions_added.iter().for_each(|r| region_row(&mut table, "+", r));
+        println!("\n{}", "Memory map:".bright_green());
+        table.printstd();
+    }
+
+    for (change, module) in diff.modules_unloaded.iter().map(|m| ("-", m)).chain(diff.modules_loaded.iter().map(|m| ("+", m))) {
+        println!("{} {} 0x{:X} {}", "Module".bright_green(), change, module.base, module.name);
+    }
+    for (change, tid) in diff.threads_exited.iter().map(|t| ("-", t)).chain(diff.threads_started.iter().map(|t| ("+", t))) {
+        println!("{} {} {}", "Thread".bright_green(), change, tid);
+    }
+
+    if !diff.pages_modified.is_empty() {
+        println!("\n{}", "Modified executable pages:".bright_green());
+        for &va in &diff.pages_modified {
+            let module = new.module_at(va).map(|m| m.name.as_str()).unwrap_or("-");
+            println!("  0x{:012X} {}", va, module);
+        }
+    }
+    println!("\n{} {} added, {} modified, {} removed",
+        "Executable pages:".bright_green(), diff.pages_added.len(), diff.pages_modified.len(), diff.pages_removed.len());
+
+    for (start, size) in &diff.injected {
+        println!("{} 0x{:012X}-0x{:012X} ({} bytes of new code outside any module)",
+            "Injected:".bright_red(), start, start + size, size);
+    }
+
+    Ok(())
+}
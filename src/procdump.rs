@@ -0,0 +1,191 @@
+//! Dumping the memory of one process
+//!
+//! The process's VAD tree says which ranges of its address space are
+//! allocated; each range is read through the process's page tables, with
+//! pages that are paged out or were never touched written as zeroes so
+//! offsets in the output match offsets in the region. Without a readable
+//! VAD tree the present user-mode page table mappings are dumped instead.
+//! Files are written through [`ChunkedExtractor`], so an interrupted dump
+//! continues where it stopped when run again.
+//!
+//! A rebuilt PE is the process's main image turned back into a file by
+//! [`rebuild_pe`]: sections at file offsets, `ImageBase` the address it was
+//! loaded at, and the import table named from the live IAT with the exports
+//! of the process's DLLs. A raw PE is the image exactly as mapped.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dlllist::process_dlls;
+use crate::extract::ChunkedExtractor;
+use crate::kdbg::OsContext;
+use crate::loader::load_memory_image;
+use crate::modules::read_pages;
+use crate::paging::MemoryImage;
+use crate::pe::{image_size, rebuild_pe, ExportIndex};
+use crate::processes::{windows_finder, Process, ProcessFinder, WindowsProcessFinder};
+use crate::symbols::SymbolStore;
+use crate::vad::{VadKind, VadRegion};
+
+/// First address above the x64 user-mode half
+const USER_LIMIT: u64 = 0x0000_8000_0000_0000;
+
+/// Regions larger than this are reservations, not data worth dumping
+const MAX_REGION_SIZE: u64 = 1 << 30;
+
+/// Offset of `ImageBaseAddress` in the x64 PEB
+const PEB_IMAGE_BASE: u64 = 0x10;
+
+/// Bytes of the image read to find its headers
+const HEADER_SIZE: usize = 0x1000;
+
+/// What `dump_process_memory` writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpMode {
+    /// One raw file per allocated region
+    Regions,
+    /// The main executable, rebuilt into a loadable PE unless `raw`
+    Pe { raw: bool },
+}
+
+/// One file written for a process
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpedFile {
+    pub path: PathBuf,
+    pub start: u64,
+    pub size: u64,
+    /// Pages written as zeroes because they were not resident
+    pub missing_pages: usize,
+    /// VAD type and file, or `mapped` for page table fallback regions
+    pub label: String,
+}
+
+/// Allocated ranges of the process: its VADs, or its present user mappings
+/// merged into contiguous ranges when the VAD tree cannot be read
+fn dump_ranges(space: &MemoryImage, vads: &[VadRegion]) -> Vec<(u64, u64, String)> {
+    if !vads.is_empty() {
+        return vads.iter().map(|vad| {
+            let label = match &vad.file {
+                Some(file) => format!("{} {}", vad.kind, file),
+                None => vad.kind.to_string(),
+            };
+            (vad.start, vad.size(), label)
+        }).collect();
+    }
+    let mut ranges: Vec<(u64, u64, String)> = Vec::new();
+    for mapping in space.mappings().into_iter().filter(|m| m.va < USER_LIMIT) {
+        match ranges.last_mut() {
+            Some((start, size, _)) if *start + *size == mapping.va => *size += mapping.size,
+            _ => ranges.push((mapping.va, mapping.size, "mapped".to_string())),
+        }
+    }
+    ranges
+}
+
+/// Pages of the `size` bytes at `start` that cannot be read
+fn missing_pages(space: &MemoryImage, start: u64, size: u64) -> usize {
+    (0..size.div_ceil(0x1000)).filter(|page| {
+        let va = start + page * 0x1000;
+        let len = (start + size - va).min(0x1000) as usize;
+        space.virt_to_phys(va).and_then(|pa| space.get_bytes(pa as usize, len)).is_none()
+    }).count()
+}
+
+/// Name usable as a file name component
+fn file_component(name: &str) -> String {
+    name.replace(['/', '\\', ':'], "_")
+}
+
+/// Write the memory of `process` into `output`; the image must use the kernel DTB
+pub fn dump_process_memory(img: &MemoryImage, finder: &WindowsProcessFinder, process: &Process, output: &Path, mode: DumpMode) -> Result<Vec<DumpedFile>> {
+    let space = process.address_space(img)
+        .with_context(|| format!("Process {} has no DTB", process.pid))?;
+    fs::create_dir_all(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let vads = finder.vads(img, process);
+
+    if let DumpMode::Pe { raw } = mode {
+        let peb = finder.process_context(img, process.virtual_address).map_or(0, |ctx| ctx.peb);
+        let base = space.read_virt_u64(peb + PEB_IMAGE_BASE).filter(|_| peb != 0)
+            .or_else(|| vads.iter().find(|v| v.kind == VadKind::Image).map(|v| v.start))
+            .with_context(|| format!("Cannot locate the image of process {}", process.pid))?;
+        let header = space.read_virt(base, HEADER_SIZE)
+            .with_context(|| format!("The headers of the image at 0x{:X} are not resident", base))?;
+        let size = image_size(&header).with_context(|| format!("No PE image at 0x{:X}", base))?;
+        if size == 0 || size > MAX_REGION_SIZE {
+            bail!("Implausible SizeOfImage 0x{:X} at 0x{:X}", size, base);
+        }
+        let (image, missing_pages) = read_pages(&space, base, size);
+        let path = output.join(format!("pid.{}.{}", process.pid, file_component(&process.name)));
+        let (image, label) = if raw {
+            (image, "PE (raw)")
+        } else {
+            let dlls = process_dlls(img, finder, process).dlls;
+            let exports = ExportIndex::build(&space, dlls.iter().map(|dll| (dll.name.as_str(), dll.base)));
+            (rebuild_pe(&image, base, &exports)?, "PE")
+        };
+        ChunkedExtractor::new(&path).write_all(&image, &ProgressBar::hidden())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        return Ok(vec![DumpedFile { path, start: base, size, missing_pages, label: label.to_string() }]);
+    }
+
+    let mut files = Vec::new();
+    for (start, size, label) in dump_ranges(&space, &vads) {
+        if size > MAX_REGION_SIZE {
+            continue;
+        }
+        let missing_pages = missing_pages(&space, start, size);
+        if missing_pages as u64 == size.div_ceil(0x1000) {
+            continue;
+        }
+        // Unreadable pages are zeroes, so a resumed region lines up
+        let path = output.join(format!("pid.{}.vad.0x{:x}-0x{:x}.dmp", process.pid, start, start + size - 1));
+        ChunkedExtractor::new(&path)
+            .extract(size, &ProgressBar::hidden(), |offset, len| Ok(read_pages(&space, start + offset, len as u64).0))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        files.push(DumpedFile { path, start, size, missing_pages, label });
+    }
+    Ok(files)
+}
+
+/// Dump the process `pid` into `output`
+pub fn dump_process(dump_path: PathBuf, dtb: u64, pid: u32, output: PathBuf, mode: DumpMode, symbols: Option<SymbolStore>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    memory_image.set_cr3(dtb);
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let os = OsContext::find(&memory_image, &progress).context("No KDBG block found; cannot walk the process list")?;
+    let finder = windows_finder(&memory_image, os, symbols.as_ref());
+    let process = finder.find_processes(&memory_image, &progress)?
+        .into_iter()
+        .find(|p| p.pid == pid)
+        .with_context(|| format!("Process {} is not on the active process list", pid))?;
+    progress.set_message(format!("Dumping {} (PID {})", process.name, pid));
+    let files = dump_process_memory(&memory_image, &finder, &process, &output, mode)?;
+    progress.finish_and_clear();
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"Start", bFg->"Size", bFg->"Missing Pages", bFg->"Region", bFg->"File"]);
+    for file in &files {
+        table.add_row(row![
+            format!("0x{:X}", file.start),
+            format!("0x{:X}", file.size),
+            file.missing_pages,
+            file.label,
+            file.path.display()
+        ]);
+    }
+    println!("{} {} files for {} (PID {}) to {}", "Wrote".bright_green(), files.len().to_string().bright_yellow(),
+        process.name.bright_yellow(), pid, output.display().to_string().bright_cyan());
+    table.printstd();
+    let paths: Vec<PathBuf> = files.into_iter().map(|file| file.path).collect();
+    crate::actions::run_for_files(&dump_path, &paths);
+    Ok(())
+}
@@ -1,10 +1,26 @@
-use anyhow::{Result, Context};
+use anyhow::{bail, Result, Context};
 use std::path::PathBuf;
+use crate::containers::parse_cgroup_path;
+use crate::dtb::{find_dtb_candidates, MAX_DTB_ATTEMPTS};
+use crate::explain::{FieldKind, StructField};
+use crate::kdbg::OsContext;
+use crate::linux_profile::LinuxProfile;
 use crate::loader::load_memory_image;
+use crate::output::Record;
+use crate::osinfo::{detect_os, find_linux_banners, DetectedOs, LinuxBanner};
+use crate::modules::{list_kernel_modules, KernelModule};
+use crate::plugin::{parse_peb, read_process_parameters, ProcessParameters};
+use crate::scan_util::{chunks, CHUNK_SIZE};
+use crate::symbols::{load_kernel_types, KernelTypes, SymbolStore};
+use crate::token::{token_anomalies, TokenInfo, TokenLayout};
+use crate::vad::{walk_vad_tree, VadLayout, VadRegion};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
-use prettytable::{Table, row, format};
+use prettytable::{Table, cell, row, format};
+use serde::Serialize;
+use std::fmt;
 use std::time::{SystemTime, Duration};
+#[cfg(not(target_arch = "wasm32"))]
 use pager::Pager;
 
 /// Process state flags
@@ -14,6 +30,7 @@ pub enum ProcessState {
     Waiting,
     Stopped,
     Zombie,
+    Exited,
     Unknown,
 }
 
@@ -24,47 +41,280 @@ impl ProcessState {
             1 => ProcessState::Waiting,
             2 => ProcessState::Stopped,
             3 => ProcessState::Zombie,
+            4 => ProcessState::Exited,
             _ => ProcessState::Unknown,
         }
     }
     
-    pub fn to_string(&self) -> String {
+}
+
+impl fmt::Display for ProcessState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            ProcessState::Running => "Running".bright_green(),
+            ProcessState::Waiting => "Waiting".bright_yellow(),
+            ProcessState::Stopped => "Stopped".bright_red(),
+            ProcessState::Zombie => "Zombie".bright_purple(),
+            ProcessState::Exited => "Exited".bright_black(),
+            ProcessState::Unknown => "Unknown".bright_white(),
+        };
+        write!(f, "{}", state)
+    }
+}
+
+/// Scheduler state of a Windows thread (`KTHREAD_STATE`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThreadState {
+    Initialized,
+    Ready,
+    Running,
+    Standby,
+    Terminated,
+    Waiting,
+    Transition,
+    DeferredReady,
+    Unknown(u8),
+}
+
+impl ThreadState {
+    pub fn from_u8(state: u8) -> Self {
+        match state {
+            0 => ThreadState::Initialized,
+            1 => ThreadState::Ready,
+            2 => ThreadState::Running,
+            3 => ThreadState::Standby,
+            4 => ThreadState::Terminated,
+            5 => ThreadState::Waiting,
+            6 => ThreadState::Transition,
+            7 => ThreadState::DeferredReady,
+            other => ThreadState::Unknown(other),
+        }
+    }
+}
+
+impl fmt::Display for ThreadState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ProcessState::Running => "Running".bright_green().to_string(),
-            ProcessState::Waiting => "Waiting".bright_yellow().to_string(),
-            ProcessState::Stopped => "Stopped".bright_red().to_string(),
-            ProcessState::Zombie => "Zombie".bright_purple().to_string(),
-            ProcessState::Unknown => "Unknown".bright_white().to_string(),
+            ThreadState::Unknown(state) => write!(f, "Unknown ({})", state),
+            state => write!(f, "{:?}", state),
         }
     }
 }
 
+/// One thread of a process, read from its ETHREAD
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadInfo {
+    pub tid: u32,
+    pub pid: u32,
+    pub virtual_address: u64, // Virtual address of the ETHREAD
+    pub start_address: u64,   // Win32StartAddress, the routine the thread was created to run
+    pub state: ThreadState,
+    pub priority: u8,
+    pub module: Option<String>, // Loaded module containing the start address
+    pub create_time: Option<SystemTime>,
+    pub exit_time: Option<SystemTime>, // Set for threads that terminated
+}
+
+impl ThreadInfo {
+    /// Started outside every loaded module, as threads running injected code are
+    pub fn suspicious(&self) -> bool {
+        self.start_address != 0 && self.module.is_none()
+    }
+}
+
 /// Represents a process found in memory
 #[derive(Debug, Clone)]
 pub struct Process {
     pub pid: u32,
     pub ppid: u32,
     pub name: String,
-    pub start_time: SystemTime,
+    pub start_time: SystemTime, // Linux tasks count from boot, with the epoch standing for boot time
+    pub exit_time: Option<SystemTime>, // Set for processes that terminated before acquisition
     pub thread_count: u32,
     pub memory_usage: usize,
     pub state: ProcessState,
-    pub virtual_address: u64,  // Virtual address of EPROCESS or task_struct
-    pub command_line: Option<String>,
+    pub virtual_address: u64,  // Physical address of EPROCESS, virtual address of task_struct
+    pub dtb: Option<u64>,      // Root of the process page tables (DirectoryTableBase or mm->pgd)
+    pub parameters: Option<ProcessParameters>, // Command line, paths and environment (Windows PEB)
+    pub user: Option<String>,
+    pub token: Option<TokenInfo>, // Primary access token (Windows)
+    pub container_id: Option<String>, // Container the process runs in (Linux cgroups)
+}
+
+impl Process {
+    /// The process's own virtual memory, when its DTB is known
+    pub fn address_space(&self, memory_image: &crate::MemoryImage) -> Option<ProcessAddressSpace> {
+        let dtb = self.dtb?;
+        Some(ProcessAddressSpace {
+            pid: self.pid,
+            name: self.name.clone(),
+            object: self.virtual_address,
+            dtb,
+            image: memory_image.with_dtb(dtb),
+        })
+    }
+}
+
+/// A process as `list-procs --format json|csv` reports it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProcessRecord {
+    pub pid: u32,
+    pub ppid: u32,
+    pub name: String,
+    pub state: String,
+    /// RFC 3339, UTC
+    pub start_time: String,
+    pub exit_time: Option<String>,
+    pub threads: u32,
+    pub memory_bytes: usize,
     pub user: Option<String>,
+    pub integrity: Option<String>,
+    /// EPROCESS (physical) or task_struct (virtual) address
+    pub object: String,
+    pub dtb: Option<String>,
+    pub container_id: Option<String>,
+    pub command_line: Option<String>,
+}
+
+impl Record for ProcessRecord {
+    const SCHEMA: &'static str = "rmf.process";
+    const TABLE: &'static str = "processes";
+    const COLUMNS: &'static [&'static str] = &[
+        "pid", "ppid", "name", "state", "start_time", "exit_time", "threads", "memory_bytes", "user", "integrity", "object", "dtb",
+        "container_id", "command_line",
+    ];
+}
+
+impl From<&Process> for ProcessRecord {
+    fn from(process: &Process) -> Self {
+        let time = |time: SystemTime| chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+        ProcessRecord {
+            pid: process.pid,
+            ppid: process.ppid,
+            name: process.name.clone(),
+            state: process.state.to_string(),
+            start_time: time(process.start_time),
+            exit_time: process.exit_time.map(time),
+            threads: process.thread_count,
+            memory_bytes: process.memory_usage,
+            user: process.user.clone(),
+            integrity: process.token.as_ref().and_then(|t| t.integrity).map(|level| level.to_string()),
+            object: format!("0x{:X}", process.virtual_address),
+            dtb: process.dtb.map(|dtb| format!("0x{:X}", dtb)),
+            container_id: process.container_id.clone(),
+            command_line: process.parameters.as_ref().and_then(|params| params.command_line.clone()),
+        }
+    }
+}
+
+/// Virtual memory of one process: the dump read through the process's page
+/// tables, so its PEB, heaps and stacks resolve. Dereferences to a
+/// `MemoryImage`, so every reader that takes an image works on it unchanged.
+#[derive(Debug)]
+pub struct ProcessAddressSpace {
+    pub pid: u32,
+    pub name: String,
+    /// EPROCESS or task_struct the process was found at
+    pub object: u64,
+    pub dtb: u64,
+    image: crate::MemoryImage,
+}
+
+impl std::ops::Deref for ProcessAddressSpace {
+    type Target = crate::MemoryImage;
+
+    fn deref(&self) -> &Self::Target {
+        &self.image
+    }
 }
 
 /// Process finder trait - to be implemented for different OS types
 pub trait ProcessFinder {
     fn find_processes(&self, memory_image: &crate::MemoryImage, progress: &ProgressBar) -> Result<Vec<Process>>;
     fn get_os_info(&self) -> (String, String); // (OS Type, Version)
+    
+    /// Carve process structures from pool/slab memory, including freed ones
+    /// left behind by processes that exited before acquisition
+    fn scan_remnants(&self, _memory_image: &crate::MemoryImage, _progress: &ProgressBar) -> Result<Vec<Process>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Windows pool header and object header sizes on x64
+pub(crate) const POOL_HEADER_SIZE: usize = 0x10;
+const OBJECT_HEADER_SIZE: usize = 0x30;
+/// Optional object headers (creator info, name, handle, quota...) add up to this much
+const MAX_OPTIONAL_HEADERS: usize = 0x90;
+
+/// FILETIME values for 2000-01-01 and 2100-01-01
+const FILETIME_MIN: u64 = 125_911_584_000_000_000;
+const FILETIME_MAX: u64 = 157_469_184_000_000_000;
+/// Seconds between 1601-01-01 and the Unix epoch
+const FILETIME_EPOCH_DELTA: u64 = 11_644_473_600;
+
+/// Convert a Windows FILETIME to SystemTime when it falls in a plausible range
+pub(crate) fn filetime_to_system(filetime: u64) -> Option<SystemTime> {
+    if !(FILETIME_MIN..FILETIME_MAX).contains(&filetime) {
+        return None;
+    }
+    let secs = filetime / 10_000_000 - FILETIME_EPOCH_DELTA;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Pool tags of process and thread objects, with and without the protected-allocation bit
+const PROCESS_POOL_TAGS: [&[u8]; 2] = [b"Proc", b"Pro\xe3"];
+const THREAD_POOL_TAGS: [&[u8]; 2] = [b"Thre", b"Thr\xe5"];
+
+/// Scan physical memory for pool headers tagged with `tags`; returns the
+/// physical address of each header and the allocation's size in bytes, which
+/// is 0 for page-sized (big pool) allocations
+pub(crate) fn scan_pool_tags(memory_image: &crate::MemoryImage, progress: &ProgressBar, tags: &[&[u8]]) -> Vec<(u64, usize)> {
+    let mut headers = Vec::new();
+    
+    // Aligned pool headers never straddle a chunk, so windows need no overlap
+    for window in chunks(memory_image, CHUNK_SIZE, 0).with_progress(progress) {
+        let (chunk_start, chunk) = (window.start, window.data);
+        
+        // Pool headers are 16-byte aligned with the tag at offset 4
+        for tag_off in (4..chunk.len().saturating_sub(4)).step_by(POOL_HEADER_SIZE) {
+            if !tags.contains(&&chunk[tag_off..tag_off + 4]) {
+                continue;
+            }
+            // BlockSize counts 16-byte units
+            headers.push(((chunk_start + tag_off - 4) as u64, chunk[tag_off - 2] as usize * POOL_HEADER_SIZE));
+        }
+    }
+    headers
+}
+
+/// Scan physical memory for pool allocations tagged with `tags` and decode the
+/// object body of each with `parse`, given its physical address
+fn scan_pool_objects<T>(
+    memory_image: &crate::MemoryImage,
+    progress: &ProgressBar,
+    tags: &[&[u8]],
+    body_size: usize,
+    parse: impl Fn(u64) -> Option<T>,
+) -> Vec<T> {
+    scan_pool_tags(memory_image, progress, tags).into_iter().filter_map(|(header, block_size)| {
+        // The body follows the object header and any optional headers,
+        // and must fit in the allocation
+        let first = header + (POOL_HEADER_SIZE + OBJECT_HEADER_SIZE) as u64;
+        (0..=MAX_OPTIONAL_HEADERS)
+            .step_by(0x10)
+            .filter(|extra| block_size == 0 || POOL_HEADER_SIZE + OBJECT_HEADER_SIZE + extra + body_size <= block_size)
+            .find_map(|extra| parse(first + extra as u64))
+    }).collect()
 }
 
 /// Windows process finder implementation - uses EPROCESS structures
 pub struct WindowsProcessFinder {
+    #[allow(dead_code)]
     profile: WindowsProfile,
+    os: Option<OsContext>,
 }
 
+#[allow(dead_code)]
 struct WindowsProfile {
     eprocess_size: usize,
     pid_offset: usize,
@@ -76,6 +326,22 @@ struct WindowsProfile {
     vadroot_offset: usize,
     userspace_offset: usize,
     cmd_line_offset: usize,
+    active_links_offset: usize,
+    exit_time_offset: usize,
+    job_links_offset: usize,
+    thread_list_head_offset: usize,
+    ethread_size: usize,
+    ethread_cid_offset: usize,
+    ethread_list_entry_offset: usize,
+    ethread_start_address_offset: usize,
+    ethread_create_time_offset: usize,
+    ethread_exit_time_offset: usize,
+    kthread_process_offset: usize,
+    kthread_state_offset: usize,
+    kthread_priority_offset: usize,
+    token_offset: usize,
+    token: TokenLayout,
+    vad: VadLayout,
 }
 
 impl Default for WindowsProfile {
@@ -90,9 +356,86 @@ impl Default for WindowsProfile {
             thread_count_offset: 0x1F8,
             create_time_offset: 0x1A0,
             vadroot_offset: 0x290,
-            userspace_offset: 0x188,
+            userspace_offset: 0x338,
             cmd_line_offset: 0x470,
+            active_links_offset: 0x190,
+            exit_time_offset: 0x1A8,
+            job_links_offset: 0x4C0,
+            thread_list_head_offset: 0x30,
+            ethread_size: 0x500,
+            ethread_cid_offset: 0x478,
+            ethread_list_entry_offset: 0x4E8,
+            ethread_start_address_offset: 0x4D0,
+            ethread_create_time_offset: 0x430,
+            ethread_exit_time_offset: 0x438,
+            kthread_process_offset: 0x220,
+            kthread_state_offset: 0x184,
+            kthread_priority_offset: 0xC3,
+            token_offset: 0x208,
+            token: TokenLayout::default(),
+            vad: VadLayout::default(),
+        }
+    }
+}
+
+impl WindowsProfile {
+    /// Replace default offsets with the ones from the dump's kernel PDB
+    fn apply_types(&mut self, types: &KernelTypes) {
+        let fields = [
+            (&mut self.pid_offset, "_EPROCESS", "UniqueProcessId"),
+            (&mut self.ppid_offset, "_EPROCESS", "InheritedFromUniqueProcessId"),
+            (&mut self.name_offset, "_EPROCESS", "ImageFileName"),
+            (&mut self.dtb_offset, "_KPROCESS", "DirectoryTableBase"),
+            (&mut self.thread_count_offset, "_EPROCESS", "ActiveThreads"),
+            (&mut self.create_time_offset, "_EPROCESS", "CreateTime"),
+            (&mut self.exit_time_offset, "_EPROCESS", "ExitTime"),
+            (&mut self.vadroot_offset, "_EPROCESS", "VadRoot"),
+            (&mut self.userspace_offset, "_EPROCESS", "Peb"),
+            (&mut self.active_links_offset, "_EPROCESS", "ActiveProcessLinks"),
+            (&mut self.job_links_offset, "_EPROCESS", "JobLinks"),
+            (&mut self.thread_list_head_offset, "_EPROCESS", "ThreadListHead"),
+            (&mut self.ethread_cid_offset, "_ETHREAD", "Cid"),
+            (&mut self.ethread_list_entry_offset, "_ETHREAD", "ThreadListEntry"),
+            (&mut self.ethread_start_address_offset, "_ETHREAD", "Win32StartAddress"),
+            (&mut self.ethread_create_time_offset, "_ETHREAD", "CreateTime"),
+            (&mut self.ethread_exit_time_offset, "_ETHREAD", "ExitTime"),
+            (&mut self.kthread_process_offset, "_KTHREAD", "Process"),
+            (&mut self.kthread_state_offset, "_KTHREAD", "State"),
+            (&mut self.kthread_priority_offset, "_KTHREAD", "Priority"),
+            (&mut self.token_offset, "_EPROCESS", "Token"),
+            (&mut self.token.privileges_offset, "_TOKEN", "Privileges"),
+            (&mut self.token.user_count_offset, "_TOKEN", "UserAndGroupCount"),
+            (&mut self.token.user_groups_offset, "_TOKEN", "UserAndGroups"),
+            (&mut self.token.integrity_index_offset, "_TOKEN", "IntegrityLevelIndex"),
+            (&mut self.vad.left_offset, "_RTL_BALANCED_NODE", "Left"),
+            (&mut self.vad.right_offset, "_RTL_BALANCED_NODE", "Right"),
+            (&mut self.vad.starting_vpn_offset, "_MMVAD_SHORT", "StartingVpn"),
+            (&mut self.vad.ending_vpn_offset, "_MMVAD_SHORT", "EndingVpn"),
+            (&mut self.vad.starting_vpn_high_offset, "_MMVAD_SHORT", "StartingVpnHigh"),
+            (&mut self.vad.ending_vpn_high_offset, "_MMVAD_SHORT", "EndingVpnHigh"),
+            (&mut self.vad.flags_offset, "_MMVAD_SHORT", "u"),
+            (&mut self.vad.subsection_offset, "_MMVAD", "Subsection"),
+            (&mut self.vad.control_area_offset, "_SUBSECTION", "ControlArea"),
+            (&mut self.vad.file_pointer_offset, "_CONTROL_AREA", "FilePointer"),
+            (&mut self.vad.file_name_offset, "_FILE_OBJECT", "FileName"),
+        ];
+        for (offset, name, field) in fields {
+            if let Some(value) = types.offset(name, field) {
+                *offset = value;
+            }
         }
+        if let Some(size) = types.size("_EPROCESS") {
+            self.eprocess_size = size;
+        }
+        if let Some(size) = types.size("_ETHREAD") {
+            self.ethread_size = size;
+        }
+    }
+}
+
+impl Default for WindowsProcessFinder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -100,185 +443,1048 @@ impl WindowsProcessFinder {
     pub fn new() -> Self {
         Self {
             profile: WindowsProfile::default(),
+            os: None,
         }
     }
     
-    // Scan for EPROCESS structures by looking for pool tags
-    fn scan_for_process_pool_tags(&self, memory_image: &crate::MemoryImage, progress: &ProgressBar) -> Vec<u64> {
-        let mut process_addrs = Vec::new();
-        let size = memory_image.size();
-        let chunk_size = 0x10000; // 64KB chunks
-        let total_chunks = size / chunk_size;
+    /// Use structure offsets from the dump's kernel PDB instead of the defaults
+    pub fn with_kernel_types(mut self, types: &KernelTypes) -> Self {
+        self.profile.apply_types(types);
+        self
+    }
+    
+    /// Walk PsActiveProcessHead from an already located KDBG block
+    pub fn with_os_context(mut self, os: OsContext) -> Self {
+        self.os = Some(os);
+        self
+    }
+    
+    // Follow ActiveProcessLinks from PsActiveProcessHead through virtual memory,
+    // checking each entry's Blink against the entry it was reached from
+    fn walk_active_processes(&self, memory_image: &crate::MemoryImage, os: &OsContext, progress: &ProgressBar) -> Vec<Process> {
+        let mut processes = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let (mut invalid, mut broken_links) = (0, 0);
         
-        progress.set_length(total_chunks as u64);
-        progress.set_message("Scanning for process pool tags");
+        progress.set_message("Walking PsActiveProcessHead");
+        let mut prev = os.ps_active_process_head;
+        let mut link = memory_image.read_virt_u64(prev).unwrap_or(0);
+        while link != os.ps_active_process_head && link != 0 && seen.insert(link) && seen.len() <= MAX_PROCESSES {
+            if memory_image.read_virt_u64(link + 8) != Some(prev) {
+                broken_links += 1;
+            }
+            let eprocess = link.wrapping_sub(self.profile.active_links_offset as u64);
+            match memory_image.virt_to_phys(eprocess).and_then(|pa| self.parse_eprocess(memory_image, pa)) {
+                Some(process) => {
+                    processes.push(process);
+                    progress.set_position(processes.len() as u64);
+                }
+                None => invalid += 1,
+            }
+            prev = link;
+            link = match memory_image.read_virt_u64(link) {
+                Some(next) => next,
+                None => break,
+            };
+        }
         
-        // Pool tag for EPROCESS is "Proc" (0x636F7250)
-        let pool_tag = 0x636F7250u32;
+        let mut message = format!("Walked {} processes", processes.len());
+        if invalid > 0 || broken_links > 0 {
+            message += &format!(" ({} entries failed validation, {} inconsistent Blinks)", invalid, broken_links);
+        }
+        progress.finish_with_message(message);
+        processes
+    }
+}
+
+/// Handle table entries per page, and pointers per page of the upper levels
+const HANDLE_ENTRIES_PER_PAGE: u64 = 0x100;
+const HANDLE_POINTERS_PER_PAGE: u64 = 0x200;
+
+/// Object body address packed into a Windows 10 `_HANDLE_TABLE_ENTRY`
+fn handle_entry_object(entry: u64) -> u64 {
+    ((entry >> 20) << 4) | 0xFFFF_0000_0000_0000
+}
+
+impl WindowsProcessFinder {
+    /// Validate the ETHREAD at physical address `addr` and decode the EPROCESS
+    /// of the process that owns it; the image must use the kernel DTB
+    fn parse_ethread_owner(&self, memory_image: &crate::MemoryImage, addr: u64) -> Option<Process> {
+        let p = &self.profile;
+        let body = memory_image.get_bytes(addr as usize, p.ethread_size)?;
+        let u64_at = |off: usize| u64::from_le_bytes(body[off..off + 8].try_into().unwrap());
         
-        for i in 0..total_chunks {
-            let offset = i * chunk_size;
-            progress.set_position(i as u64);
-            
-            // In a real implementation, we would:
-            // 1. Read a chunk of memory
-            // 2. Search for the pool tag
-            // 3. Validate that it's an EPROCESS structure
-            
-            if let Some(chunk) = memory_image.get_bytes(offset, chunk_size) {
-                for j in 0..chunk_size - 4 {
-                    if j + 4 > chunk.len() {
-                        break;
-                    }
-                    
-                    let tag = u32::from_le_bytes([
-                        chunk[j], chunk[j + 1], chunk[j + 2], chunk[j + 3]
-                    ]);
-                    
-                    if tag == pool_tag {
-                        // Found a potential EPROCESS
-                        // In reality, we'd do more validation here
-                        process_addrs.push((offset + j) as u64);
+        // CLIENT_ID is { UniqueProcess, UniqueThread }
+        let (pid, tid) = (u64_at(p.ethread_cid_offset), u64_at(p.ethread_cid_offset + 8));
+        if pid % 4 != 0 || pid >= 0x10_0000 || tid == 0 || tid % 4 != 0 || tid >= 0x10_0000 {
+            return None;
+        }
+        let owner = memory_image.virt_to_phys(u64_at(p.kthread_process_offset))?;
+        self.parse_eprocess(memory_image, owner).filter(|process| process.pid as u64 == pid)
+    }
+    
+    /// Thread ID and owning process of the ETHREAD at kernel virtual address
+    /// `ethread`; the image must use the kernel DTB
+    pub(crate) fn thread_owner(&self, memory_image: &crate::MemoryImage, ethread: u64) -> Option<(u32, Process)> {
+        let addr = memory_image.virt_to_phys(ethread)?;
+        let owner = self.parse_ethread_owner(memory_image, addr)?;
+        let tid = memory_image.read_u64(addr as usize + self.profile.ethread_cid_offset + 8)?;
+        Some((tid as u32, owner))
+    }
+    
+    /// Processes owning the ETHREAD allocations found in pool memory
+    pub fn scan_threads(&self, memory_image: &crate::MemoryImage, progress: &ProgressBar) -> Vec<Process> {
+        progress.set_message("Scanning pool memory for ETHREAD structures");
+        let mut owners = scan_pool_objects(memory_image, progress, &THREAD_POOL_TAGS, self.profile.ethread_size,
+            |addr| self.parse_ethread_owner(memory_image, addr));
+        owners.sort_by_key(|p| p.virtual_address);
+        owners.dedup_by_key(|p| p.virtual_address);
+        progress.finish_with_message(format!("Threads belong to {} processes", owners.len()));
+        owners
+    }
+    
+    /// Processes in the CID handle table, which maps every live PID and TID to its object
+    pub fn walk_cid_table(&self, memory_image: &crate::MemoryImage, os: &OsContext) -> Vec<Process> {
+        let Some(table) = os.psp_cid_table.and_then(|va| memory_image.read_virt_u64(va)) else {
+            return Vec::new();
+        };
+        // HANDLE_TABLE.TableCode: the top-level page, with the number of levels above the entries in the low bits
+        let Some(table_code) = memory_image.read_virt_u64(table + 8) else {
+            return Vec::new();
+        };
+        
+        let mut pages = vec![(table_code & !3, table_code & 3, 0u64)];
+        let mut processes = Vec::new();
+        while let Some((page, level, first_index)) = pages.pop() {
+            if level > 0 {
+                // Each pointer covers the entries of a full subtree
+                let span = HANDLE_ENTRIES_PER_PAGE * HANDLE_POINTERS_PER_PAGE.pow(level as u32 - 1);
+                for i in 0..HANDLE_POINTERS_PER_PAGE {
+                    match memory_image.read_virt_u64(page + i * 8) {
+                        Some(next) if next != 0 => pages.push((next, level - 1, first_index + i * span)),
+                        _ => {}
                     }
                 }
+                continue;
             }
-            
-            // For demo purposes, simulate finding processes at fixed intervals
-            if i % 0x100 == 0 {
-                process_addrs.push((offset + 0x1000) as u64);
+            for i in 0..HANDLE_ENTRIES_PER_PAGE {
+                let entry = match memory_image.read_virt_u64(page + i * 16) {
+                    Some(entry) if entry != 0 => entry,
+                    _ => continue,
+                };
+                // A handle's value is four times its index; PIDs and TIDs share the table
+                let handle = (first_index + i) * 4;
+                let process = memory_image.virt_to_phys(handle_entry_object(entry))
+                    .and_then(|pa| self.parse_eprocess(memory_image, pa))
+                    .filter(|process| process.pid as u64 == handle);
+                if let Some(process) = process {
+                    processes.push(process);
+                    if processes.len() >= MAX_PROCESSES {
+                        return processes;
+                    }
+                }
             }
-            
-            // Don't hog the CPU
-            if i % 100 == 0 {
-                std::thread::sleep(Duration::from_millis(1));
+        }
+        processes.sort_by_key(|p| p.pid);
+        processes
+    }
+}
+
+/// Address space root, PEB and threads of a live process, read from its EPROCESS
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProcessContext {
+    pub dtb: u64,
+    pub peb: u64,
+    pub threads: Vec<u32>,
+}
+
+/// Upper bounds on list walks, guarding against corrupted links
+const MAX_THREADS: usize = 0x10000;
+const MAX_PROCESSES: usize = 0x10000;
+
+impl WindowsProcessFinder {
+    /// Offset of the LIST_ENTRY linking an EPROCESS into its job's process list
+    pub(crate) fn job_links_offset(&self) -> usize {
+        self.profile.job_links_offset
+    }
+    
+    pub(crate) fn eprocess_size(&self) -> usize {
+        self.profile.eprocess_size
+    }
+    
+    /// The EPROCESS fields this finder reads, by offset, for `explain`
+    pub(crate) fn eprocess_fields(&self) -> Vec<StructField> {
+        use FieldKind::*;
+        let p = &self.profile;
+        let mut fields = vec![
+            StructField::new("DirectoryTableBase", p.dtb_offset, Pointer, "Physical address of the top-level page table, loaded into CR3"),
+            StructField::new("ThreadListHead", p.thread_list_head_offset, ListEntry, "Head of the list of the process's ETHREADs"),
+            StructField::new("UniqueProcessId", p.pid_offset, U64, "Process ID"),
+            StructField::new("InheritedFromUniqueProcessId", p.ppid_offset, U64, "Parent's process ID at creation"),
+            StructField::new("ActiveProcessLinks", p.active_links_offset, ListEntry, "Links in PsActiveProcessHead; rootkits unlink here to hide"),
+            StructField::new("CreateTime", p.create_time_offset, FileTime, "When the process was created"),
+            StructField::new("ExitTime", p.exit_time_offset, FileTime, "When the process exited; zero while it runs"),
+            StructField::new("ActiveThreads", p.thread_count_offset, U32, "Threads that have not exited"),
+            StructField::new("Token", p.token_offset, FastRef, "Primary access token; the low 4 bits count references"),
+            StructField::new("VadRoot", p.vadroot_offset, Pointer, "Root of the tree of user-mode allocations"),
+            StructField::new("ImageFileName", p.name_offset, Ascii(15), "First 15 bytes of the executable's name"),
+            StructField::new("Peb", p.userspace_offset, Pointer, "Process Environment Block, in the process's own address space"),
+            StructField::new("JobLinks", p.job_links_offset, ListEntry, "Links in the member list of the process's job"),
+        ];
+        fields.sort_by_key(|f| f.offset);
+        fields
+    }
+    
+    /// Read the DTB and PEB of the EPROCESS at physical address `addr` and walk
+    /// its ThreadListHead for thread IDs; the image must use the kernel DTB
+    pub fn process_context(&self, memory_image: &crate::MemoryImage, addr: u64) -> Option<ProcessContext> {
+        let p = &self.profile;
+        let body = memory_image.get_bytes(addr as usize, p.eprocess_size)?;
+        let u64_at = |off: usize| u64::from_le_bytes(body[off..off + 8].try_into().unwrap());
+        
+        let threads = self.thread_list(memory_image, addr)?.into_iter()
+            // CLIENT_ID is { UniqueProcess, UniqueThread }
+            .filter_map(|ethread| memory_image.read_virt_u64(ethread + p.ethread_cid_offset as u64 + 8))
+            .map(|tid| tid as u32)
+            .collect();
+        Some(ProcessContext {
+            dtb: u64_at(p.dtb_offset) & !0xFFF,
+            peb: u64_at(p.userspace_offset),
+            threads,
+        })
+    }
+    
+    /// Virtual addresses of the ETHREADs on the ThreadListHead of the EPROCESS
+    /// at physical address `addr`
+    fn thread_list(&self, memory_image: &crate::MemoryImage, addr: u64) -> Option<Vec<u64>> {
+        let p = &self.profile;
+        let head = addr + p.thread_list_head_offset as u64;
+        let mut link = memory_image.read_u64(head as usize)?;
+        
+        // Links hold virtual addresses; the head is recognised by its physical address
+        let mut threads = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        while link != 0 && seen.insert(link) && seen.len() <= MAX_THREADS {
+            match memory_image.virt_to_phys(link) {
+                Some(pa) if pa != head => {}
+                _ => break,
             }
+            threads.push(link.wrapping_sub(p.ethread_list_entry_offset as u64));
+            link = memory_image.read_virt_u64(link).unwrap_or(0);
         }
+        Some(threads)
+    }
+    
+    /// Walk the threads of `process`, resolving each start address against the
+    /// process's loader modules and `kernel_modules`; the image must use the kernel DTB
+    pub fn threads(&self, memory_image: &crate::MemoryImage, process: &Process, kernel_modules: &[KernelModule]) -> Vec<ThreadInfo> {
+        let p = &self.profile;
+        let Some(ethreads) = self.thread_list(memory_image, process.virtual_address) else { return Vec::new() };
         
-        progress.finish_with_message(format!("Found {} potential process structures", process_addrs.len()));
-        process_addrs
+        // User-mode start addresses fall in modules on the PEB loader list
+        let mut modules: Vec<(u64, u64, String)> = kernel_modules.iter().map(|m| (m.base, m.size, m.name.clone())).collect();
+        let peb = self.process_context(memory_image, process.virtual_address).map_or(0, |ctx| ctx.peb);
+        if let Some(info) = process.address_space(memory_image).filter(|_| peb != 0).and_then(|space| parse_peb(&space, peb)) {
+            modules.extend(info.ldr_modules);
+        }
+        let read_u8 = |va: u64| memory_image.read_virt(va, 1).map(|b| b[0]);
+        
+        ethreads.into_iter().filter_map(|ethread| {
+            let tid = memory_image.read_virt_u64(ethread + p.ethread_cid_offset as u64 + 8)?;
+            let start_address = memory_image.read_virt_u64(ethread + p.ethread_start_address_offset as u64).unwrap_or(0);
+            // ExitTime shares its field with a list entry until the thread exits
+            let time_at = |offset: usize| memory_image.read_virt_u64(ethread + offset as u64).and_then(filetime_to_system);
+            let module = modules.iter()
+                .find(|(base, size, _)| (*base..base + size).contains(&start_address))
+                .map(|(_, _, name)| name.rsplit('\\').next().unwrap_or(name).to_string());
+            Some(ThreadInfo {
+                tid: tid as u32,
+                pid: process.pid,
+                virtual_address: ethread,
+                start_address,
+                state: ThreadState::from_u8(read_u8(ethread + p.kthread_state_offset as u64).unwrap_or(0xFF)),
+                priority: read_u8(ethread + p.kthread_priority_offset as u64).unwrap_or(0),
+                module,
+                create_time: time_at(p.ethread_create_time_offset),
+                exit_time: time_at(p.ethread_exit_time_offset),
+            })
+        }).collect()
+    }
+    
+    /// Walk the VAD tree of `process`; the image must use the kernel DTB
+    pub fn vads(&self, memory_image: &crate::MemoryImage, process: &Process) -> Vec<VadRegion> {
+        let head = process.virtual_address + self.profile.vadroot_offset as u64;
+        match memory_image.read_u64(head as usize) {
+            Some(root) if root != 0 => walk_vad_tree(memory_image, root, &self.profile.vad),
+            _ => Vec::new(),
+        }
+    }
+    
+    /// Validate and decode an EPROCESS body at physical address `addr`
+    pub(crate) fn parse_eprocess(&self, memory_image: &crate::MemoryImage, addr: u64) -> Option<Process> {
+        let p = &self.profile;
+        let body = memory_image.get_bytes(addr as usize, p.eprocess_size)?;
+        let u64_at = |off: usize| u64::from_le_bytes(body[off..off + 8].try_into().unwrap());
+        
+        let pid = u64_at(p.pid_offset);
+        let ppid = u64_at(p.ppid_offset);
+        if pid == 0 || pid % 4 != 0 || pid >= 0x10_0000 || ppid % 4 != 0 || ppid >= 0x10_0000 {
+            return None;
+        }
+        
+        let dtb = u64_at(p.dtb_offset);
+        if dtb == 0 || dtb & 0xFFF != 0 || dtb >> 52 != 0 {
+            return None;
+        }
+        
+        let name_bytes = &body[p.name_offset..p.name_offset + 15];
+        let name_len = name_bytes.iter().position(|&b| b == 0).unwrap_or(15);
+        if name_len == 0 || !name_bytes[..name_len].iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            return None;
+        }
+        
+        let start_time = filetime_to_system(u64_at(p.create_time_offset))?;
+        let exit_raw = u64_at(p.exit_time_offset);
+        let exit_time = match exit_raw {
+            0 => None,
+            t if t >= u64_at(p.create_time_offset) => Some(filetime_to_system(t)?),
+            _ => return None,
+        };
+        let thread_count = u32::from_le_bytes(body[p.thread_count_offset..p.thread_count_offset + 4].try_into().unwrap());
+        if thread_count as usize > MAX_THREADS {
+            return None;
+        }
+        
+        // The PEB lives in the process's own address space, gone once it exited
+        let peb = u64_at(p.userspace_offset);
+        let parameters = (exit_time.is_none() && peb != 0)
+            .then(|| read_process_parameters(&memory_image.with_dtb(dtb), peb))
+            .flatten();
+        let token = TokenInfo::read(memory_image, u64_at(p.token_offset), &p.token);
+        
+        Some(Process {
+            pid: pid as u32,
+            ppid: ppid as u32,
+            name: String::from_utf8_lossy(&name_bytes[..name_len]).into_owned(),
+            start_time,
+            exit_time,
+            thread_count,
+            memory_usage: 0,
+            state: if exit_time.is_some() { ProcessState::Exited } else { ProcessState::Running },
+            virtual_address: addr,
+            dtb: Some(dtb),
+            parameters,
+            user: token.as_ref().map(|t| t.user.display_name()),
+            token,
+            container_id: None,
+        })
     }
 }
 
 impl ProcessFinder for WindowsProcessFinder {
     fn find_processes(&self, memory_image: &crate::MemoryImage, progress: &ProgressBar) -> Result<Vec<Process>> {
-        let mut processes = Vec::new();
+        if memory_image.info.dtb.is_none() {
+            bail!("Walking the Windows process list needs the kernel DTB (use --dtb)");
+        }
+        let os = match &self.os {
+            Some(os) => os.clone(),
+            None => OsContext::find(memory_image, progress)
+                .context("No KDBG block found; cannot locate PsActiveProcessHead")?,
+        };
+        Ok(self.walk_active_processes(memory_image, &os, progress))
+    }
+    
+    fn get_os_info(&self) -> (String, String) {
+        ("Windows".to_string(), "10 x64".to_string())
+    }
+    
+    fn scan_remnants(&self, memory_image: &crate::MemoryImage, progress: &ProgressBar) -> Result<Vec<Process>> {
+        progress.set_message("Scanning pool memory for EPROCESS remnants");
+        let processes = scan_pool_objects(memory_image, progress, &PROCESS_POOL_TAGS, self.profile.eprocess_size,
+            |addr| self.parse_eprocess(memory_image, addr));
+        progress.finish_with_message(format!("Recovered {} process structures from pool", processes.len()));
+        Ok(processes)
+    }
+}
+
+/// Linux process finder implementation - uses task_struct
+#[derive(Default)]
+pub struct LinuxProcessFinder {
+    banner: Option<LinuxBanner>,
+    profile: Option<LinuxProfile>,
+}
+
+impl LinuxProcessFinder {
+    /// Record the kernel identified from its banner
+    pub fn with_banner(mut self, banner: LinuxBanner) -> Self {
+        self.banner = Some(banner);
+        self
+    }
+    
+    /// Use structure offsets and symbols from a profile of the dump's kernel
+    pub fn with_profile(mut self, profile: LinuxProfile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+    
+    pub fn profile(&self) -> Option<&LinuxProfile> {
+        self.profile.as_ref()
+    }
+}
+
+/// `task_struct` and related offsets needed to walk the task list
+struct TaskLayout {
+    tasks: usize,
+    comm: usize,
+    pid: usize,
+    tgid: usize,
+    real_parent: usize,
+    mm: usize,
+    start_time: usize,
+    /// `__state` since 5.14, `state` before
+    state: Option<usize>,
+    exit_state: Option<usize>,
+    thread_group: Option<usize>,
+    cred: Option<usize>,
+    pgd: usize,
+    uid: Option<usize>,
+    euid: Option<usize>,
+    /// Only known to profiles built with the cgroup structures
+    cgroup: Option<CgroupLayout>,
+}
+
+/// Offsets from a task to the kernfs node of its cgroup v2 directory
+struct CgroupLayout {
+    cgroups: usize,
+    dfl_cgrp: usize,
+    kn: usize,
+    name: usize,
+    parent: usize,
+}
+
+/// Deepest cgroup hierarchy followed
+const MAX_CGROUP_DEPTH: usize = 32;
+/// Longest kernfs node name (`NAME_MAX`)
+const MAX_KERNFS_NAME: usize = 255;
+
+/// Task states from `include/linux/sched.h`
+const TASK_INTERRUPTIBLE: u32 = 0x1;
+const TASK_UNINTERRUPTIBLE: u32 = 0x2;
+const TASK_STOPPED: u32 = 0x4;
+const TASK_TRACED: u32 = 0x8;
+const EXIT_ZOMBIE: u32 = 0x10;
+const EXIT_DEAD: u32 = 0x20;
+
+/// Largest PID the kernel hands out (`PID_MAX_LIMIT` on 64-bit)
+const PID_MAX_LIMIT: u32 = 0x40_0000;
+
+/// `task_struct`s are cache-line aligned in their slab
+const TASK_ALIGN: usize = 64;
+/// `list_del` leaves these in the links of a task taken off the task list
+const LIST_POISON1: u64 = 0xdead_0000_0000_0100;
+const LIST_POISON2: u64 = 0xdead_0000_0000_0122;
+/// Start of the direct map of physical memory without KASLR
+const DEFAULT_PAGE_OFFSET: u64 = 0xffff_8880_0000_0000;
+
+impl TaskLayout {
+    fn from_types(types: &KernelTypes) -> Result<Self> {
+        let task = |field: &str| types.offset("task_struct", field);
+        let required = |name: &str, field: &str| {
+            types.offset(name, field).with_context(|| format!("Profile has no {}.{}", name, field))
+        };
+        Ok(TaskLayout {
+            tasks: required("task_struct", "tasks")?,
+            comm: required("task_struct", "comm")?,
+            pid: required("task_struct", "pid")?,
+            tgid: required("task_struct", "tgid")?,
+            real_parent: required("task_struct", "real_parent")?,
+            mm: required("task_struct", "mm")?,
+            start_time: required("task_struct", "start_time")?,
+            state: task("__state").or_else(|| task("state")),
+            exit_state: task("exit_state"),
+            thread_group: task("thread_group"),
+            cred: task("cred"),
+            pgd: required("mm_struct", "pgd")?,
+            uid: types.offset("cred", "uid"),
+            euid: types.offset("cred", "euid"),
+            cgroup: CgroupLayout::from_types(types),
+        })
+    }
+}
+
+impl CgroupLayout {
+    fn from_types(types: &KernelTypes) -> Option<Self> {
+        Some(CgroupLayout {
+            cgroups: types.offset("task_struct", "cgroups")?,
+            dfl_cgrp: types.offset("css_set", "dfl_cgrp")?,
+            kn: types.offset("cgroup", "kn")?,
+            name: types.offset("kernfs_node", "name")?,
+            // Renamed when kernfs made the parent RCU-protected (6.15)
+            parent: types.offset("kernfs_node", "parent").or_else(|| types.offset("kernfs_node", "__parent"))?,
+        })
+    }
+}
+
+impl LinuxProcessFinder {
+    /// Path of the task's cgroup in the unified hierarchy, from the names of
+    /// its kernfs node and the node's parents
+    fn cgroup_path(memory_image: &crate::MemoryImage, layout: &CgroupLayout, task: u64) -> Option<String> {
+        let css_set = memory_image.read_virt_u64(task + layout.cgroups as u64).filter(|&p| p != 0)?;
+        let cgroup = memory_image.read_virt_u64(css_set + layout.dfl_cgrp as u64).filter(|&p| p != 0)?;
+        let mut node = memory_image.read_virt_u64(cgroup + layout.kn as u64)?;
+        let mut components = Vec::new();
+        while node != 0 && components.len() < MAX_CGROUP_DEPTH {
+            let parent = memory_image.read_virt_u64(node + layout.parent as u64)?;
+            // The root's name is empty
+            if parent != 0 {
+                let name = memory_image.read_virt_u64(node + layout.name as u64)?;
+                let mut bytes = Vec::new();
+                while bytes.len() < MAX_KERNFS_NAME && !bytes.contains(&0) {
+                    let va = name + bytes.len() as u64;
+                    let take = (0x1000 - (va & 0xFFF) as usize).min(MAX_KERNFS_NAME - bytes.len());
+                    bytes.extend(memory_image.read_virt(va, take)?);
+                }
+                let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                components.push(String::from_utf8_lossy(&bytes[..len]).into_owned());
+            }
+            node = parent;
+        }
+        components.reverse();
+        Some(format!("/{}", components.join("/")))
+    }
+
+    /// Read the `task_struct` at virtual address `task`; the image must use a kernel DTB
+    fn parse_task(&self, memory_image: &crate::MemoryImage, layout: &TaskLayout, task: u64) -> Option<Process> {
+        let u32_at = |off: usize| memory_image.read_virt(task + off as u64, 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+        let ptr_at = |base: u64, off: usize| memory_image.read_virt_u64(base + off as u64);
         
-        // Find potential EPROCESS addresses
-        let process_addrs = self.scan_for_process_pool_tags(memory_image, progress);
+        let (pid, tgid) = (u32_at(layout.pid)?, u32_at(layout.tgid)?);
+        if pid > PID_MAX_LIMIT || tgid > PID_MAX_LIMIT {
+            return None;
+        }
+        let comm = memory_image.read_virt(task + layout.comm as u64, 16)?;
+        let name_len = comm.iter().position(|&b| b == 0).unwrap_or(16);
+        if name_len == 0 || !comm[..name_len].iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            return None;
+        }
         
-        progress.set_length(process_addrs.len() as u64);
-        progress.set_message("Extracting process information");
+        // The tasks list links thread group leaders, whose PID users see is the TGID
+        let ppid = ptr_at(task, layout.real_parent)
+            .filter(|&parent| parent != task)
+            .and_then(|parent| memory_image.read_virt(parent + layout.tgid as u64, 4))
+            .map_or(0, |b| u32::from_le_bytes(b.try_into().unwrap()));
         
-        for (i, addr) in process_addrs.iter().enumerate() {
-            progress.set_position(i as u64);
-            
-            // In a real implementation, we would:
-            // 1. Extract all fields from the EPROCESS structure
-            // 2. Validate the fields
-            // 3. Create a Process struct
-            
-            // Extract PID (simulated)
-            let pid = i as u32 * 4 + 4;
-            
-            // Extract process name (simulated)
-            let name = if i % 3 == 0 {
-                "explorer.exe".to_string()
-            } else if i % 3 == 1 {
-                "svchost.exe".to_string()
-            } else {
-                format!("process_{}.exe", i)
-            };
-            
-            // Create a process object
-            let process = Process {
-                pid,
-                ppid: pid / 4,
-                name,
-                start_time: SystemTime::now() - Duration::from_secs(i as u64 * 1000),
-                thread_count: (i % 10 + 1) as u32,
-                memory_usage: (i % 32 + 1) * 1024 * 1024,
-                state: if i % 5 == 0 { ProcessState::Zombie } else { ProcessState::Running },
-                virtual_address: *addr,
-                command_line: Some(format!("C:\\Windows\\System32\\{}", if i % 3 == 0 { "explorer.exe" } else if i % 3 == 1 { "svchost.exe -k netsvcs" } else { format!("process_{}.exe", i) })),
-                user: Some(if i % 4 == 0 { "SYSTEM".to_string() } else { "USER".to_string() }),
-            };
-            
-            processes.push(process);
-            
-            // Don't hog the CPU
-            if i % 10 == 0 {
-                std::thread::sleep(Duration::from_millis(1));
+        // Kernel threads have no mm; mm->pgd is the kernel virtual address of the PGD
+        let dtb = ptr_at(task, layout.mm)
+            .filter(|&mm| mm != 0)
+            .and_then(|mm| ptr_at(mm, layout.pgd))
+            .and_then(|pgd| memory_image.virt_to_phys(pgd));
+        
+        let state = match (layout.exit_state.and_then(u32_at), layout.state.and_then(u32_at)) {
+            (Some(exit), _) if exit & EXIT_ZOMBIE != 0 => ProcessState::Zombie,
+            (Some(exit), _) if exit & EXIT_DEAD != 0 => ProcessState::Exited,
+            (_, Some(0)) => ProcessState::Running,
+            (_, Some(state)) if state & (TASK_STOPPED | TASK_TRACED) != 0 => ProcessState::Stopped,
+            (_, Some(state)) if state & (TASK_INTERRUPTIBLE | TASK_UNINTERRUPTIBLE) != 0 => ProcessState::Waiting,
+            _ => ProcessState::Unknown,
+        };
+        
+        // Other threads of the group hang off the leader's thread_group list
+        let thread_count = layout.thread_group.map_or(1, |off| {
+            let head = task + off as u64;
+            let mut seen = std::collections::HashSet::new();
+            let mut link = memory_image.read_virt_u64(head).unwrap_or(head);
+            while link != head && link != 0 && seen.insert(link) && seen.len() < MAX_THREADS {
+                link = memory_image.read_virt_u64(link).unwrap_or(head);
             }
-        }
+            seen.len() as u32 + 1
+        });
         
-        progress.finish_with_message(format!("Extracted {} processes", processes.len()));
+        // Real and effective UIDs from the task's credentials
+        let cred = layout.cred.and_then(|off| ptr_at(task, off)).filter(|&cred| cred != 0);
+        let id_at = |off: Option<usize>| {
+            cred.zip(off).and_then(|(cred, off)| memory_image.read_virt(cred + off as u64, 4))
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        };
+        let user = match (id_at(layout.uid), id_at(layout.euid)) {
+            (Some(uid), Some(euid)) if uid != euid => Some(format!("{} (euid {})", uid, euid)),
+            (Some(uid), _) => Some(uid.to_string()),
+            _ => None,
+        };
         
-        Ok(processes)
+        // start_time counts nanoseconds of monotonic time since boot
+        let start_time = memory_image.read_virt_u64(task + layout.start_time as u64)?;
+        
+        let container_id = layout.cgroup.as_ref()
+            .and_then(|cgroup| Self::cgroup_path(memory_image, cgroup, task))
+            .and_then(|path| parse_cgroup_path(&path))
+            .map(|container| container.id);
+        
+        Some(Process {
+            pid: tgid,
+            ppid,
+            name: String::from_utf8_lossy(&comm[..name_len]).into_owned(),
+            start_time: SystemTime::UNIX_EPOCH + Duration::from_nanos(start_time),
+            exit_time: None,
+            thread_count,
+            memory_usage: 0,
+            state,
+            virtual_address: task,
+            dtb,
+            parameters: None,
+            user,
+            token: None,
+            container_id,
+        })
     }
     
-    fn get_os_info(&self) -> (String, String) {
-        ("Windows".to_string(), "10 x64".to_string())
+    /// KASLR slides of the kernel image to try: none, then every slide that
+    /// puts the profile's `linux_banner` on the banner found in physical memory
+    fn kernel_slides(&self, memory_image: &crate::MemoryImage, profile: &LinuxProfile) -> Vec<u64> {
+        let mut slides = vec![0];
+        let (Some(banner), Some(symbol)) = (&self.banner, profile.symbol("linux_banner")) else {
+            return slides;
+        };
+        for mapping in memory_image.mappings() {
+            if (mapping.pa..mapping.pa + mapping.size).contains(&banner.offset) {
+                let slide = (mapping.va + (banner.offset - mapping.pa)).wrapping_sub(symbol);
+                if !slides.contains(&slide) {
+                    slides.push(slide);
+                }
+            }
+        }
+        slides
+    }
+    
+    // Follow init_task.tasks around the task list, checking each entry's prev
+    // link against the entry it was reached from
+    fn walk_tasks(&self, memory_image: &crate::MemoryImage, layout: &TaskLayout, init_task: u64, progress: &ProgressBar) -> Vec<Process> {
+        let mut processes = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let (mut invalid, mut broken_links) = (0, 0);
+        
+        progress.set_message("Walking init_task.tasks");
+        let head = init_task + layout.tasks as u64;
+        let mut prev = head;
+        let mut link = memory_image.read_virt_u64(head).unwrap_or(0);
+        while link != head && link != 0 && seen.insert(link) && seen.len() <= MAX_PROCESSES {
+            if memory_image.read_virt_u64(link + 8) != Some(prev) {
+                broken_links += 1;
+            }
+            match self.parse_task(memory_image, layout, link.wrapping_sub(layout.tasks as u64)) {
+                Some(process) => {
+                    processes.push(process);
+                    progress.set_position(processes.len() as u64);
+                }
+                None => invalid += 1,
+            }
+            prev = link;
+            link = match memory_image.read_virt_u64(link) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        
+        let mut message = format!("Walked {} tasks", processes.len());
+        if invalid > 0 || broken_links > 0 {
+            message += &format!(" ({} entries failed validation, {} inconsistent prev links)", invalid, broken_links);
+        }
+        progress.finish_with_message(message);
+        processes
     }
 }
 
-/// Linux process finder implementation - uses task_struct
-pub struct LinuxProcessFinder;
+impl LinuxProcessFinder {
+    /// KASLR slide to add to the profile's symbol addresses, found by
+    /// locating init_task; the image must use the kernel DTB
+    pub fn kernel_slide(&self, memory_image: &crate::MemoryImage) -> Result<u64> {
+        let Some(profile) = &self.profile else {
+            bail!("Walking the Linux task list needs a profile of the dump's kernel (use --profile)");
+        };
+        if memory_image.info.dtb.is_none() {
+            bail!("Walking the Linux task list needs the kernel DTB (use --dtb)");
+        }
+        let init_task = profile.symbol("init_task").context("Profile has no init_task address")?;
+        let layout = TaskLayout::from_types(&profile.types)?;
+        
+        // init_task is the idle task: PID 0, named swapper or swapper/0
+        self.kernel_slides(memory_image, profile).into_iter().find(|&slide| {
+            self.parse_task(memory_image, &layout, init_task.wrapping_add(slide))
+                .is_some_and(|task| task.pid == 0 && task.name.starts_with("swapper"))
+        }).context("init_task not found at its profile address or any KASLR slide")
+    }
+}
+
+impl LinuxProcessFinder {
+    /// Virtual address of physical address 0 in the kernel's direct map,
+    /// from `page_offset_base` when the profile has it
+    fn page_offset(memory_image: &crate::MemoryImage, profile: &LinuxProfile, slide: u64) -> u64 {
+        profile.symbol("page_offset_base")
+            .and_then(|symbol| memory_image.read_virt_u64(symbol.wrapping_add(slide)))
+            .filter(|&base| base >> 63 == 1)
+            .unwrap_or(DEFAULT_PAGE_OFFSET)
+    }
+    
+    /// Whether the slab object at `offset` in `data` looks like a task: a
+    /// NUL-terminated name, and task links that are kernel pointers or the
+    /// poison `list_del` leaves behind
+    fn task_candidate(data: &[u8], offset: usize, layout: &TaskLayout) -> bool {
+        let Some(comm) = data.get(offset + layout.comm..offset + layout.comm + 16) else { return false };
+        let name_len = comm.iter().position(|&b| b == 0).unwrap_or(16);
+        if name_len == 0 || name_len == 16 || !comm[..name_len].iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+            return false;
+        }
+        let Some(links) = data.get(offset + layout.tasks..offset + layout.tasks + 16) else { return false };
+        let (next, prev) = (u64::from_le_bytes(links[..8].try_into().unwrap()), u64::from_le_bytes(links[8..].try_into().unwrap()));
+        (next, prev) == (LIST_POISON1, LIST_POISON2) || (next >> 63 == 1 && prev >> 63 == 1)
+    }
+}
 
 impl ProcessFinder for LinuxProcessFinder {
     fn find_processes(&self, memory_image: &crate::MemoryImage, progress: &ProgressBar) -> Result<Vec<Process>> {
-        // For now, return an empty vector - we'll implement Linux process finding later
-        Ok(Vec::new())
+        let slide = self.kernel_slide(memory_image)?;
+        // kernel_slide has checked the profile
+        let profile = self.profile.as_ref().unwrap();
+        let init_task = profile.symbol("init_task").unwrap();
+        let layout = TaskLayout::from_types(&profile.types)?;
+        Ok(self.walk_tasks(memory_image, &layout, init_task.wrapping_add(slide), progress))
     }
     
     fn get_os_info(&self) -> (String, String) {
-        ("Linux".to_string(), "Generic x64".to_string())
+        let version = self.banner.as_ref().map(|b| b.release.clone())
+            .or_else(|| self.profile.as_ref().map(|p| p.release.clone()))
+            .unwrap_or_else(|| "Generic x64".to_string());
+        ("Linux".to_string(), version)
+    }
+    
+    fn scan_remnants(&self, memory_image: &crate::MemoryImage, progress: &ProgressBar) -> Result<Vec<Process>> {
+        let slide = self.kernel_slide(memory_image)?;
+        let profile = self.profile.as_ref().unwrap();
+        let layout = TaskLayout::from_types(&profile.types)?;
+        // Slab objects are read back through the direct map
+        let page_offset = Self::page_offset(memory_image, profile, slide);
+        
+        progress.set_message("Scanning slab memory for task_struct remnants");
+        let mut processes = Vec::new();
+        let span = layout.comm.max(layout.tasks + 16) + 16;
+        for window in chunks(memory_image, CHUNK_SIZE, span).with_progress(progress) {
+            for offset in window.owned.clone().step_by(TASK_ALIGN) {
+                if !Self::task_candidate(window.data, offset, &layout) {
+                    continue;
+                }
+                // Idle tasks (PID 0) are never on the task list
+                processes.extend(self.parse_task(memory_image, &layout, page_offset + window.addr(offset))
+                    .filter(|task| task.pid != 0));
+            }
+        }
+        progress.finish_with_message(format!("Recovered {} task structures from slab", processes.len()));
+        Ok(processes)
+    }
+}
+
+/// Parents that Windows core processes are started by
+const EXPECTED_PARENTS: &[(&str, &[&str])] = &[
+    ("smss.exe", &["System", "smss.exe"]),
+    ("csrss.exe", &["smss.exe"]),
+    ("wininit.exe", &["smss.exe"]),
+    ("winlogon.exe", &["smss.exe"]),
+    ("services.exe", &["wininit.exe"]),
+    ("lsass.exe", &["wininit.exe"]),
+    ("lsaiso.exe", &["wininit.exe"]),
+    ("svchost.exe", &["services.exe"]),
+    ("spoolsv.exe", &["services.exe"]),
+    ("userinit.exe", &["winlogon.exe"]),
+];
+
+/// A process in display order of the parent/child tree
+#[derive(Debug, Clone)]
+pub struct TreeNode<'a> {
+    pub process: &'a Process,
+    pub depth: usize,
+    /// Orphaned or unexpected parentage
+    pub note: Option<String>,
+}
+
+/// Arrange processes by parentage; children follow their parent, ordered by PID
+///
+/// A parent that started after its child is a later process reusing the PID,
+/// so the child is treated as orphaned.
+pub fn process_tree(processes: &[Process]) -> Vec<TreeNode<'_>> {
+    let parent_of = |child: &Process| {
+        processes.iter().find(|p| {
+            p.pid == child.ppid && p.pid != child.pid && p.start_time <= child.start_time && p.exit_time.is_none_or(|exit| exit >= child.start_time)
+        })
+    };
+    
+    let mut order: Vec<usize> = (0..processes.len()).collect();
+    order.sort_by_key(|&i| (processes[i].pid, processes[i].start_time));
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); processes.len()];
+    let mut roots = Vec::new();
+    let mut notes: Vec<Option<String>> = vec![None; processes.len()];
+    for &i in &order {
+        let process = &processes[i];
+        match parent_of(process) {
+            Some(parent) => {
+                let parent_index = processes.iter().position(|p| std::ptr::eq(p, parent)).unwrap();
+                children[parent_index].push(i);
+                let expected = EXPECTED_PARENTS.iter().find(|(name, _)| name.eq_ignore_ascii_case(&process.name));
+                if let Some((_, parents)) = expected {
+                    if !parents.iter().any(|name| name.eq_ignore_ascii_case(&parent.name)) {
+                        notes[i] = Some(format!("unexpected parent {} (expected {})", parent.name, parents.join(" or ")));
+                    }
+                }
+            }
+            None => {
+                if process.ppid != 0 && process.ppid != process.pid {
+                    notes[i] = Some(format!("orphaned (parent {} not found)", process.ppid));
+                }
+                roots.push(i);
+            }
+        }
+    }
+    
+    // Depth-first from the roots; anything left over sits in a parent cycle
+    let mut tree = Vec::new();
+    let mut visited = vec![false; processes.len()];
+    let mut stack: Vec<(usize, usize)> = roots.iter().rev().map(|&i| (i, 0)).collect();
+    let mut remaining = order.iter();
+    while let Some((i, depth)) = stack.pop().or_else(|| remaining.find(|&&i| !visited[i]).map(|&i| (i, 0))) {
+        if visited[i] {
+            continue;
+        }
+        visited[i] = true;
+        tree.push(TreeNode { process: &processes[i], depth, note: notes[i].take() });
+        stack.extend(children[i].iter().rev().map(|&child| (child, depth + 1)));
+    }
+    tree
+}
+
+/// Windows finder for the kernel located by `os`, with offsets from its PDB
+/// when a symbol store is given and the PDB can be loaded
+pub fn windows_finder(memory_image: &crate::MemoryImage, os: OsContext, symbols: Option<&SymbolStore>) -> WindowsProcessFinder {
+    let mut finder = WindowsProcessFinder::new();
+    if let Some(store) = symbols {
+        match load_kernel_types(memory_image, os.kernel_base, store) {
+            Ok((id, types)) => {
+                crate::status!("Using offsets from {}", id.to_string().bright_yellow());
+                finder = finder.with_kernel_types(&types);
+            }
+            Err(e) => crate::status!("{} {:#}", "Symbols unavailable, using default offsets:".bright_red(), e),
+        }
     }
+    finder.with_os_context(os)
+}
+
+/// Tasks of a Linux dump, walked with the profile at `profile` (a file, or a
+/// directory to pick from by the release in the dump's banner) through the
+/// image's DTB or, without one, the page table roots found in the dump
+pub fn linux_processes(memory_image: &crate::MemoryImage, profile: &std::path::Path, progress: &ProgressBar) -> Result<Vec<Process>> {
+    let banner = find_linux_banners(memory_image, progress).into_iter().next();
+    let loaded = LinuxProfile::find(profile, banner.as_ref().map(|b| b.release.as_str()))?;
+    let mut finder = LinuxProcessFinder::default().with_profile(loaded);
+    if let Some(banner) = banner {
+        finder = finder.with_banner(banner);
+    }
+    if memory_image.info.dtb.is_some() {
+        return finder.find_processes(memory_image, progress);
+    }
+    let mut found = Err(anyhow::anyhow!("No page table root found in the dump"));
+    for candidate in find_dtb_candidates(memory_image, progress).into_iter().take(MAX_DTB_ATTEMPTS) {
+        found = finder.find_processes(&memory_image.with_dtb(candidate.dtb), progress);
+        if found.as_ref().is_ok_and(|p| !p.is_empty()) {
+            break;
+        }
+    }
+    found
 }
 
 /// Factory to create the right process finder for an OS
 pub fn create_process_finder(os_type: &str) -> Box<dyn ProcessFinder> {
     match os_type.to_lowercase().as_str() {
         "windows" => Box::new(WindowsProcessFinder::new()),
-        "linux" => Box::new(LinuxProcessFinder),
+        "linux" => Box::new(LinuxProcessFinder::default()),
         _ => Box::new(WindowsProcessFinder::new()), // Default to Windows for now
     }
 }
 
-pub fn list_processes(dump_path: PathBuf) -> Result<()> {
-    println!("{}", "Listing processes from memory dump...".bright_green());
+/// How a pool-scanned process relates to the active process list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanStatus {
+    /// Also on the active process list
+    Active,
+    /// Exited before acquisition; its EPROCESS has not been reused yet
+    Terminated,
+    /// Still running but missing from the active list, as when a rootkit unlinks it
+    Unlinked,
+    /// The active list could not be walked to compare against
+    Unchecked,
+}
+
+/// Whether two views of a process describe the same EPROCESS
+pub fn same_process(a: &Process, b: &Process) -> bool {
+    a.virtual_address == b.virtual_address || (a.pid == b.pid && a.start_time == b.start_time)
+}
+
+/// Classify a pool-scanned process against the active list, when it was walked
+pub fn scan_status(process: &Process, active: Option<&[Process]>) -> ScanStatus {
+    match active {
+        Some(active) if active.iter().any(|p| same_process(p, process)) => ScanStatus::Active,
+        _ if process.exit_time.is_some() => ScanStatus::Terminated,
+        Some(_) => ScanStatus::Unlinked,
+        None => ScanStatus::Unchecked,
+    }
+}
+
+/// Add pool/slab remnants that the active process walk did not return
+pub fn merge_remnants(processes: &mut Vec<Process>, remnants: Vec<Process>) -> usize {
+    let before = processes.len();
+    for remnant in remnants {
+        if !processes.iter().any(|p| same_process(p, &remnant)) {
+            processes.push(remnant);
+        }
+    }
+    processes.len() - before
+}
+
+/// What `list_processes` recovers and how it prints the result
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    /// Also carve process structures from pool/slab memory to recover exited processes
+    pub scan_pool: bool,
+    /// Print the parent/child tree instead of the table
+    pub tree: bool,
+    /// Print the environment variables of every process
+    pub env: bool,
+    /// Print the SIDs and privileges of every process's token
+    pub tokens: bool,
+}
+
+pub fn list_processes(dump_path: PathBuf, os_type: &str, dtb: Option<u64>, symbols: Option<SymbolStore>, profile: Option<PathBuf>, options: ListOptions) -> Result<()> {
+    let ListOptions { scan_pool, tree, env, tokens } = options;
+    crate::status!("{}", "Listing processes from memory dump...".bright_green());
     
     // Load the memory image
-    let memory_image = load_memory_image(&dump_path)?;
-    println!("Memory dump size: {} bytes", memory_image.size());
+    let mut memory_image = load_memory_image(&dump_path)?;
+    if let Some(dtb) = dtb {
+        memory_image.set_cr3(dtb);
+    }
+    crate::status!("Memory dump size: {} bytes", memory_image.size());
     
     // Create a progress bar for the scanning operation
-    let progress = ProgressBar::new(100);
+    let progress = crate::progress::attach(ProgressBar::new(100));
     progress.set_style(ProgressStyle::with_template(
         "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
     )?.progress_chars("#>-"));
     
-    // For now, we're assuming a Windows memory dump
-    // In a real implementation, we'd detect the OS type
-    let process_finder = create_process_finder("windows");
+    // Without a DTB, start from the best page table root found in the dump
+    let candidates = if memory_image.info.dtb.is_none() && memory_image.info.user.is_none() {
+        find_dtb_candidates(&memory_image, &progress)
+    } else {
+        Vec::new()
+    };
+    if let Some(best) = candidates.first() {
+        memory_image.set_cr3(best.dtb);
+    }
+    
+    // Identify the kernel from its Linux banner or Windows KDBG block
+    let detected = match os_type {
+        "auto" => detect_os(&memory_image, &progress),
+        "linux" => find_linux_banners(&memory_image, &progress)
+            .into_iter()
+            .next()
+            .map_or(DetectedOs::Unknown, DetectedOs::Linux),
+        _ => memory_image.info.dtb
+            .and_then(|_| OsContext::find(&memory_image, &progress))
+            .map_or(DetectedOs::Unknown, DetectedOs::Windows),
+    };
+    
+    let process_finder: Box<dyn ProcessFinder> = match detected {
+        DetectedOs::Linux(banner) => {
+            let mut finder = LinuxProcessFinder::default();
+            match &profile {
+                Some(path) => {
+                    let loaded = LinuxProfile::find(path, Some(&banner.release))?;
+                    crate::status!("Using Linux profile: {}", loaded.file_name().bright_yellow());
+                    if loaded.release != banner.release {
+                        crate::status!("{} profile is for {}, dump runs {}", "Warning:".bright_red(), loaded.release, banner.release);
+                    }
+                    finder = finder.with_profile(loaded);
+                }
+                None => crate::status!("Linux profile: {} (build it with `rmf profile build-linux`)", banner.profile_name().bright_yellow()),
+            }
+            Box::new(finder.with_banner(banner))
+        }
+        // With a DTB, prefer walking the kernel's own process list from KDBG
+        DetectedOs::Windows(os) if memory_image.info.dtb.is_some() => {
+            crate::status!("Using KDBG: PsActiveProcessHead at {}", format!("0x{:X}", os.ps_active_process_head).bright_yellow());
+            Box::new(windows_finder(&memory_image, os, symbols.as_ref()))
+        }
+        _ if os_type == "linux" => {
+            let mut finder = LinuxProcessFinder::default();
+            if let Some(path) = &profile {
+                finder = finder.with_profile(LinuxProfile::find(path, None)?);
+            }
+            Box::new(finder)
+        }
+        _ => create_process_finder(os_type),
+    };
     
     let (os_type, os_version) = process_finder.get_os_info();
-    println!("Detected OS: {} {}", os_type.bright_yellow(), os_version.bright_yellow());
+    crate::status!("Detected OS: {} {}", os_type.bright_yellow(), os_version.bright_yellow());
+    
+    // Find processes, moving on to the next DTB candidate while none are found
+    let mut used = 0;
+    let mut found = process_finder.find_processes(&memory_image, &progress);
+    for (index, candidate) in candidates.iter().enumerate().take(MAX_DTB_ATTEMPTS).skip(1) {
+        if found.as_ref().is_ok_and(|p| !p.is_empty()) {
+            break;
+        }
+        memory_image.set_cr3(candidate.dtb);
+        used = index;
+        found = process_finder.find_processes(&memory_image, &progress);
+    }
+    if let Some(candidate) = candidates.get(used) {
+        crate::status!("{} {} (candidate {} of {})", "Using DTB".bright_green(), candidate.to_string().bright_yellow(), used + 1, candidates.len());
+    }
+    let mut processes = found.context("Failed to find processes")?;
     
-    // Find processes
-    let processes = process_finder.find_processes(&memory_image, &progress)
-        .context("Failed to find processes")?;
+    // Recover processes that exited before acquisition from freed structures
+    if scan_pool {
+        let remnants = process_finder.scan_remnants(&memory_image, &progress)
+            .context("Failed to scan for process remnants")?;
+        let added = merge_remnants(&mut processes, remnants);
+        crate::status!("{} {}", "Recovered from pool/slab:".bright_green(), added.to_string().bright_yellow());
+    }
+    
+    if crate::output::structured() {
+        let records: Vec<ProcessRecord> = processes.iter().map(ProcessRecord::from).collect();
+        return crate::output::emit(&dump_path, &records);
+    }
     
     if processes.is_empty() {
         println!("{}", "No processes found.".bright_red());
         return Ok(());
     }
     
+    if tree {
+        print_process_tree(&processes);
+        return Ok(());
+    }
+    
     // Create a table for the output
     let mut table = Table::new();
     table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
     
+    // Only show the container column when some process runs in a container
+    let show_containers = processes.iter().any(|p| p.container_id.is_some());
+    let show_exit = processes.iter().any(|p| p.exit_time.is_some());
+    let show_dtb = processes.iter().any(|p| p.dtb.is_some());
+    let show_integrity = processes.iter().any(|p| p.token.as_ref().is_some_and(|t| t.integrity.is_some()));
+    let command_line = |p: &Process| p.parameters.as_ref().and_then(|params| params.command_line.clone());
+    let show_command_lines = processes.iter().any(|p| command_line(p).is_some());
+    
     // Add table headers
-    table.set_titles(row![
+    let mut titles = row![
         bFg->"PID", 
         bFg->"PPID", 
         bFg->"Name", 
@@ -287,7 +1493,23 @@ pub fn list_processes(dump_path: PathBuf) -> Result<()> {
         bFg->"Threads", 
         bFg->"Memory (MB)", 
         bFg->"User"
-    ]);
+    ];
+    if show_integrity {
+        titles.add_cell(cell!(bFg->"Integrity"));
+    }
+    if show_dtb {
+        titles.add_cell(cell!(bFg->"DTB"));
+    }
+    if show_exit {
+        titles.add_cell(cell!(bFg->"Exit Time"));
+    }
+    if show_containers {
+        titles.add_cell(cell!(bFg->"Container"));
+    }
+    if show_command_lines {
+        titles.add_cell(cell!(bFg->"Command Line"));
+    }
+    table.set_titles(titles);
     
     // Add processes to table with formatted data
     for process in &processes {
@@ -299,7 +1521,7 @@ pub fn list_processes(dump_path: PathBuf) -> Result<()> {
         // Format memory usage in MB
         let memory_mb = process.memory_usage / (1024 * 1024);
         
-        table.add_row(row![
+        let mut row = row![
             process.pid,
             process.ppid,
             process.name,
@@ -308,10 +1530,32 @@ pub fn list_processes(dump_path: PathBuf) -> Result<()> {
             process.thread_count,
             memory_mb,
             process.user.clone().unwrap_or_else(|| "-".to_string())
-        ]);
+        ];
+        if show_integrity {
+            let integrity = process.token.as_ref().and_then(|t| t.integrity);
+            row.add_cell(cell!(integrity.map_or("-".to_string(), |level| level.to_string())));
+        }
+        if show_dtb {
+            row.add_cell(cell!(process.dtb.map_or("-".to_string(), |dtb| format!("0x{:X}", dtb))));
+        }
+        if show_exit {
+            let exit = process.exit_time.map(|t| chrono::DateTime::<chrono::Local>::from(t)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string());
+            row.add_cell(cell!(exit.unwrap_or_else(|| "-".to_string())));
+        }
+        if show_containers {
+            let container = process.container_id.as_deref().map(|id| &id[..id.len().min(12)]);
+            row.add_cell(cell!(container.unwrap_or("-")));
+        }
+        if show_command_lines {
+            row.add_cell(cell!(command_line(process).unwrap_or_else(|| "-".to_string())));
+        }
+        table.add_row(row);
     }
     
     // Use pager for large output
+    #[cfg(not(target_arch = "wasm32"))]
     if processes.len() > 20 {
         Pager::new().setup();
     }
@@ -324,5 +1568,235 @@ pub fn list_processes(dump_path: PathBuf) -> Result<()> {
     
     table.printstd();
     
+    if env {
+        print_environments(&processes);
+    }
+    if tokens {
+        print_tokens(&processes);
+    }
+    for anomaly in token_anomalies(&processes) {
+        println!("{} {} (PID {}) {}", "Token:".bright_red(), anomaly.name.bright_yellow(), anomaly.pid, anomaly.reason);
+    }
+    
+    Ok(())
+}
+
+/// Print the user, groups and privileges of each process's primary token
+fn print_tokens(processes: &[Process]) {
+    for process in processes {
+        let Some(token) = &process.token else { continue };
+        println!("\n{} {} (PID {}) at 0x{:X}", "Token:".bright_green(), process.name.bright_yellow(), process.pid, token.address);
+        println!("  {:<12} {} ({})", "User", token.user.display_name(), token.user);
+        if let Some(level) = token.integrity {
+            println!("  {:<12} {}", "Integrity", level);
+        }
+        for group in &token.groups {
+            println!("  {:<12} {}", "Group", group.account().map_or_else(|| group.to_string(), |name| format!("{} ({})", name, group)));
+        }
+        for privilege in token.enabled_privileges() {
+            println!("  {:<12} {}", "Privilege", privilege.bright_cyan());
+        }
+    }
+}
+
+/// Print the working directory and environment variables read from each process's PEB
+fn print_environments(processes: &[Process]) {
+    for process in processes {
+        let Some(params) = &process.parameters else { continue };
+        println!("\n{} {} (PID {})", "Environment:".bright_green(), process.name.bright_yellow(), process.pid);
+        if let Some(dir) = &params.current_directory {
+            println!("  {:<24} {}", "Current directory", dir);
+        }
+        if params.environment.is_empty() {
+            println!("  {}", "No environment block".bright_red());
+        }
+        for (name, value) in &params.environment {
+            println!("  {}={}", name.bright_cyan(), value);
+        }
+    }
+}
+
+/// Print processes as an indented parent/child tree with parentage anomalies
+fn print_process_tree(processes: &[Process]) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_CLEAN);
+    table.set_titles(row![bFg->"Name", bFg->"PID", bFg->"PPID", bFg->"Start Time", bFg->"Exit Time", bFg->"Note"]);
+    let nodes = process_tree(processes);
+    for node in &nodes {
+        let process = node.process;
+        let note = match &node.note {
+            Some(note) if note.starts_with("unexpected") => cell!(Fr->note),
+            Some(note) => cell!(Fy->note),
+            None => cell!(""),
+        };
+        let mut row = row![
+            format!("{}{}", ". ".repeat(node.depth), process.name),
+            process.pid,
+            process.ppid,
+            format_time(process.start_time),
+            process.exit_time.map_or("-".to_string(), format_time)
+        ];
+        row.add_cell(note);
+        table.add_row(row);
+    }
+    
+    println!("\n{} {}", "Found".bright_green(), format!("{} processes", processes.len()).bright_yellow().bold());
+    table.printstd();
+    
+    let anomalies = nodes.iter().filter(|n| n.note.as_deref().is_some_and(|note| note.starts_with("unexpected"))).count();
+    if anomalies > 0 {
+        println!("{} {} processes with unexpected parents", "Warning:".bright_red(), anomalies);
+    }
+}
+
+pub(crate) fn format_time(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn scanned_table(processes: &[&Process]) -> Table {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"Offset", bFg->"PID", bFg->"PPID", bFg->"Name", bFg->"Start Time", bFg->"Exit Time", bFg->"DTB"]);
+    for process in processes {
+        table.add_row(row![
+            format!("0x{:X}", process.virtual_address),
+            process.pid,
+            process.ppid,
+            process.name,
+            format_time(process.start_time),
+            process.exit_time.map_or("-".to_string(), format_time),
+            process.dtb.map_or("-".to_string(), |dtb| format!("0x{:X}", dtb))
+        ]);
+    }
+    table
+}
+
+/// Scan pool memory for EPROCESS allocations and report the terminated and
+/// unlinked processes the active process list does not show
+pub fn psscan(dump_path: PathBuf, dtb: Option<u64>, symbols: Option<SymbolStore>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    if let Some(dtb) = dtb {
+        memory_image.set_cr3(dtb);
+    }
+    
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    
+    // Walk the active list to compare against when the kernel can be located
+    let os = memory_image.info.dtb.and_then(|_| OsContext::find(&memory_image, &progress));
+    let mut finder = WindowsProcessFinder::new();
+    let active = match os {
+        Some(os) => {
+            finder = windows_finder(&memory_image, os, symbols.as_ref());
+            Some(finder.find_processes(&memory_image, &progress)?)
+        }
+        None => {
+            println!("{} the active process list needs the kernel DTB (--dtb) and KDBG; reporting pool results only",
+                "Note:".bright_yellow());
+            None
+        }
+    };
+    
+    let mut scanned = finder.scan_remnants(&memory_image, &progress)?;
+    scanned.sort_by_key(|p| p.virtual_address);
+    progress.finish_and_clear();
+    
+    let classified: Vec<(&Process, ScanStatus)> = scanned.iter()
+        .map(|p| (p, scan_status(p, active.as_deref())))
+        .collect();
+    let with_status = |status| classified.iter().filter(|(_, s)| *s == status).map(|(p, _)| *p).collect::<Vec<_>>();
+    
+    println!("{} {} EPROCESS allocations in pool memory", "Found".bright_green(), scanned.len().to_string().bright_yellow());
+    if let Some(active) = &active {
+        println!("  {:<24} {}", "On the active list", with_status(ScanStatus::Active).len());
+        let missing = active.iter().filter(|p| !scanned.iter().any(|s| same_process(s, p))).count();
+        if missing > 0 {
+            println!("  {:<24} {}", "Listed but not in pool", missing);
+        }
+    }
+    
+    let unchecked = with_status(ScanStatus::Unchecked);
+    if !unchecked.is_empty() {
+        println!("\n{}", "Running processes (not compared with the active list):".bright_green());
+        scanned_table(&unchecked).printstd();
+    }
+    
+    let terminated = with_status(ScanStatus::Terminated);
+    if !terminated.is_empty() {
+        println!("\n{}", "Terminated processes:".bright_green());
+        scanned_table(&terminated).printstd();
+    }
+    
+    let unlinked = with_status(ScanStatus::Unlinked);
+    if !unlinked.is_empty() {
+        println!("\n{}", "Unlinked processes (running but missing from the active list, possibly hidden):".bright_red());
+        scanned_table(&unlinked).printstd();
+    }
+    
+    Ok(())
+}
+
+/// Walk the thread lists of every process, or only of `pid`, and flag
+/// threads that start outside any loaded module
+pub fn list_threads(dump_path: PathBuf, dtb: u64, pid: Option<u32>, symbols: Option<SymbolStore>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    memory_image.set_cr3(dtb);
+    
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let os = OsContext::find(&memory_image, &progress).context("No KDBG block found; cannot walk the process list")?;
+    let kernel_modules = list_kernel_modules(&memory_image, &os);
+    let finder = windows_finder(&memory_image, os, symbols.as_ref());
+    let processes: Vec<Process> = finder.find_processes(&memory_image, &progress)?
+        .into_iter()
+        .filter(|p| pid.is_none_or(|pid| p.pid == pid))
+        .collect();
+    progress.finish_and_clear();
+    if let (Some(pid), true) = (pid, processes.is_empty()) {
+        bail!("Process {} is not on the active process list", pid);
+    }
+    
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"PID", bFg->"Process", bFg->"TID", bFg->"Start Address", bFg->"Module", bFg->"State", bFg->"Priority", bFg->"Note"]);
+    let mut threads = Vec::new();
+    for process in &processes {
+        for thread in finder.threads(&memory_image, process, &kernel_modules) {
+            let note = if thread.suspicious() { cell!(bFr->"outside modules") } else { cell!("") };
+            let mut row = row![
+                thread.pid,
+                process.name,
+                thread.tid,
+                format!("0x{:X}", thread.start_address),
+                thread.module.as_deref().unwrap_or("-"),
+                thread.state,
+                thread.priority
+            ];
+            row.add_cell(note);
+            table.add_row(row);
+            threads.push((process.name.clone(), thread));
+        }
+    }
+    
+    #[cfg(not(target_arch = "wasm32"))]
+    if threads.len() > 20 {
+        Pager::new().setup();
+    }
+    
+    println!("{} {} threads in {} processes", "Found".bright_green(), threads.len().to_string().bright_yellow(), processes.len());
+    table.printstd();
+    
+    let suspicious: Vec<_> = threads.iter().filter(|(_, t)| t.suspicious()).collect();
+    if suspicious.is_empty() {
+        println!("\n{}", "Every thread starts inside a loaded module".bright_green());
+    }
+    for (name, thread) in suspicious {
+        println!("{} thread {} of {} (PID {}) starts at 0x{:X}, outside any loaded module",
+            "Suspicious:".bright_red(), thread.tid, name.bright_yellow(), thread.pid, thread.start_address);
+    }
     Ok(())
 }
@@ -0,0 +1,156 @@
+//! Progress reporting for long-running commands
+//!
+//! Commands draw animated progress bars by default. With `--progress json`
+//! the bars are hidden and each one reports its state on stderr as a JSON
+//! line every `JSON_INTERVAL`, so GUIs and CI wrappers can show real
+//! progress without parsing terminal output:
+//!
+//! ```text
+//! {"done":1048576,"elapsed_secs":0.5,"eta_secs":81.4,"event":"progress","rate":52428800.0,"stage":"Scanning for KDBG","total":4294967296}
+//! ```
+//!
+//! A last event with `"event":"finished"` is written as soon as the bar
+//! completes, even when the command exits within one interval; a bar
+//! dropped unfinished writes its last state instead.
+
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use serde_json::json;
+use std::io::Write;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Time between JSON progress events of one bar
+pub const JSON_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How progress is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// Animated bars on the terminal
+    Bar,
+    /// JSON lines on stderr
+    Json,
+}
+
+static FORMAT: AtomicU8 = AtomicU8::new(ProgressFormat::Bar as u8);
+
+/// Select how every progress bar created from now on reports
+pub fn set_progress_format(format: ProgressFormat) {
+    FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+pub fn progress_format() -> ProgressFormat {
+    match FORMAT.load(Ordering::Relaxed) {
+        x if x == ProgressFormat::Json as u8 => ProgressFormat::Json,
+        _ => ProgressFormat::Bar,
+    }
+}
+
+/// One JSON progress event describing the current state of `progress`
+pub fn progress_event(progress: &ProgressBar) -> serde_json::Value {
+    let finished = progress.is_finished();
+    let eta = progress.length().filter(|_| !finished).map(|_| progress.eta().as_secs_f64());
+    json!({
+        "event": if finished { "finished" } else { "progress" },
+        "stage": progress.message(),
+        "done": progress.position(),
+        "total": progress.length(),
+        "rate": progress.per_sec(),
+        "eta_secs": eta,
+        "elapsed_secs": progress.elapsed().as_secs_f64(),
+    })
+}
+
+/// Where the JSON events of one bar go, and whether its last event has
+/// been written
+struct JsonSink {
+    out: Box<dyn Write + Send>,
+    closed: bool,
+}
+
+impl JsonSink {
+    /// Write the current state of `progress`; the first finished event, or
+    /// the event written by `close`, is the last one
+    fn report(&mut self, progress: &ProgressBar, close: bool) {
+        if self.closed {
+            return;
+        }
+        self.closed = close || progress.is_finished();
+        // Progress is best effort; a closed stderr must not fail the command
+        let _ = writeln!(self.out, "{}", progress_event(progress));
+    }
+}
+
+/// A progress bar from [`attach`], used as a [`ProgressBar`]. With JSON
+/// progress its last event is written when it is finished through these
+/// methods or dropped, rather than on the reporting thread's next tick.
+pub struct Progress {
+    bar: ProgressBar,
+    sink: Option<Arc<Mutex<JsonSink>>>,
+}
+
+impl Progress {
+    pub fn finish(&self) {
+        self.bar.finish();
+        self.close();
+    }
+
+    pub fn finish_with_message(&self, message: impl Into<std::borrow::Cow<'static, str>>) {
+        self.bar.finish_with_message(message);
+        self.close();
+    }
+
+    pub fn finish_and_clear(&self) {
+        self.bar.finish_and_clear();
+        self.close();
+    }
+
+    fn close(&self) {
+        if let Some(sink) = &self.sink {
+            sink.lock().unwrap().report(&self.bar, true);
+        }
+    }
+}
+
+impl Deref for Progress {
+    type Target = ProgressBar;
+
+    fn deref(&self) -> &ProgressBar {
+        &self.bar
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Report `progress` in the selected format; with JSON, hide the bar and
+/// write its events to stderr until it finishes or is dropped
+pub fn attach(progress: ProgressBar) -> Progress {
+    match progress_format() {
+        ProgressFormat::Json => report_json(progress, Box::new(std::io::stderr())),
+        ProgressFormat::Bar => Progress { bar: progress, sink: None },
+    }
+}
+
+/// Hide `progress` and write its JSON events to `out`, from a background
+/// thread every `JSON_INTERVAL` and synchronously when it finishes
+pub(crate) fn report_json(progress: ProgressBar, out: Box<dyn Write + Send>) -> Progress {
+    progress.set_draw_target(ProgressDrawTarget::hidden());
+    let sink = Arc::new(Mutex::new(JsonSink { out, closed: false }));
+    let weak = progress.downgrade();
+    let events = Arc::clone(&sink);
+    std::thread::spawn(move || loop {
+        std::thread::sleep(JSON_INTERVAL);
+        let Some(progress) = weak.upgrade() else { break };
+        let mut sink = events.lock().unwrap();
+        sink.report(&progress, false);
+        if sink.closed {
+            break;
+        }
+    });
+    Progress { bar: progress, sink: Some(sink) }
+}
@@ -0,0 +1,135 @@
+//! Cross-view detection of hidden processes
+//!
+//! Rootkits hide a process by unlinking its EPROCESS from the active process
+//! list, but the structure stays in pool memory, its threads keep running
+//! and the CID handle table still maps its PID. Each of these sources is an
+//! independent view of the running processes; a process that some views see
+//! and others do not was either hidden or has exited, and the exit time in
+//! its EPROCESS tells the two apart.
+
+use anyhow::{Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Cell, Row, Table};
+use std::path::PathBuf;
+
+use crate::kdbg::OsContext;
+use crate::loader::load_memory_image;
+use crate::paging::MemoryImage;
+use crate::processes::{same_process, windows_finder, Process, ProcessFinder};
+use crate::symbols::SymbolStore;
+
+/// Names of the views, in the order of `CrossViewEntry::seen`
+pub const VIEWS: [&str; 4] = ["pslist", "psscan", "thrdscan", "pspcid"];
+
+/// One process and the views it appears in
+#[derive(Debug, Clone)]
+pub struct CrossViewEntry {
+    pub process: Process,
+    /// Active process list, pool scan, thread scan, CID table
+    pub seen: [bool; 4],
+}
+
+impl CrossViewEntry {
+    pub fn exited(&self) -> bool {
+        self.process.exit_time.is_some()
+    }
+
+    /// Running, seen by some view, but missing from the active process list
+    pub fn hidden(&self) -> bool {
+        !self.exited() && !self.seen[0] && self.seen[1..].iter().any(|&seen| seen)
+    }
+
+    /// Running but missing from at least one view
+    pub fn inconsistent(&self) -> bool {
+        !self.exited() && !self.seen.iter().all(|&seen| seen)
+    }
+}
+
+/// Merge the views into one entry per EPROCESS, ordered by PID
+pub fn cross_view(views: &[Vec<Process>; 4]) -> Vec<CrossViewEntry> {
+    let mut entries: Vec<CrossViewEntry> = Vec::new();
+    for (index, view) in views.iter().enumerate() {
+        for process in view {
+            match entries.iter_mut().find(|e| same_process(&e.process, process)) {
+                Some(entry) => entry.seen[index] = true,
+                None => {
+                    let mut seen = [false; 4];
+                    seen[index] = true;
+                    entries.push(CrossViewEntry { process: process.clone(), seen });
+                }
+            }
+        }
+    }
+    entries.sort_by_key(|e| (e.process.pid, e.process.virtual_address));
+    entries
+}
+
+/// Collect every view of the processes of the Windows kernel located by `os`
+pub fn collect_views(img: &MemoryImage, os: OsContext, symbols: Option<&SymbolStore>, progress: &ProgressBar) -> Result<[Vec<Process>; 4]> {
+    let finder = windows_finder(img, os.clone(), symbols);
+    let pslist = finder.find_processes(img, progress)?;
+    let psscan = finder.scan_remnants(img, progress)?;
+    let thrdscan = finder.scan_threads(img, progress);
+    let pspcid = finder.walk_cid_table(img, &os);
+    Ok([pslist, psscan, thrdscan, pspcid])
+}
+
+/// Compare the process views of a dump and flag processes missing from some of them
+pub fn report_psxview(dump_path: PathBuf, dtb: u64, symbols: Option<SymbolStore>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    memory_image.set_cr3(dtb);
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let os = OsContext::find(&memory_image, &progress).context("No KDBG block found; cannot walk the process list")?;
+    if os.psp_cid_table.is_none() {
+        println!("{} KDBG records no PspCidTable; the pspcid view will be empty", "Warning:".bright_yellow());
+    }
+    let views = collect_views(&memory_image, os, symbols.as_ref(), &progress)?;
+    progress.finish_and_clear();
+
+    let entries = cross_view(&views);
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"Offset", bFg->"PID", bFg->"Name", bFg->"pslist", bFg->"psscan", bFg->"thrdscan", bFg->"pspcid", bFg->"Note"]);
+    for entry in &entries {
+        let mut cells = vec![
+            Cell::new(&format!("0x{:X}", entry.process.virtual_address)),
+            Cell::new(&entry.process.pid.to_string()),
+            Cell::new(&entry.process.name),
+        ];
+        for seen in entry.seen {
+            cells.push(if seen { Cell::new("True").style_spec("Fg") } else { Cell::new("False").style_spec("Fr") });
+        }
+        let note = if entry.hidden() {
+            Cell::new("hidden").style_spec("bFr")
+        } else if entry.inconsistent() {
+            Cell::new("partial").style_spec("Fy")
+        } else if entry.exited() {
+            Cell::new("exited")
+        } else {
+            Cell::new("")
+        };
+        cells.push(note);
+        table.add_row(Row::new(cells));
+    }
+
+    println!("{} {} processes across {} views", "Found".bright_green(), entries.len().to_string().bright_yellow(), VIEWS.len());
+    for (name, view) in VIEWS.iter().zip(&views) {
+        println!("  {:<10} {}", name, view.len());
+    }
+    table.printstd();
+
+    let hidden: Vec<_> = entries.iter().filter(|e| e.hidden()).collect();
+    if hidden.is_empty() {
+        println!("\n{}", "No running process is missing from the active process list".bright_green());
+    }
+    for entry in hidden {
+        println!("{} {} (PID {}) is running but not on the active process list",
+            "Hidden:".bright_red(), entry.process.name.bright_yellow(), entry.process.pid);
+    }
+    Ok(())
+}
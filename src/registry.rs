@@ -0,0 +1,567 @@
+//! Windows registry hives in memory
+//!
+//! The configuration manager keeps every loaded hive in a CMHIVE, whose
+//! HHIVE starts with the signature `0xBEE0BEE0` and points to the hive's
+//! base block and to the cell map translating cell indexes to the 4 KiB
+//! blocks of its bins. Following the map for each block of the stable
+//! storage rebuilds the hive in the on-disk `regf` format, with blocks that
+//! were not resident zero-filled, so the same parser reads hives carved
+//! from memory and hive files. Offsets are for Windows 10 x64.
+//!
+//! Key paths can start with `HKLM` or `HKU`, which are mapped to the
+//! `\REGISTRY\MACHINE` and `\REGISTRY\USER` paths hives are mounted at.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::loader::load_memory_image;
+use crate::modules::{read_pages, unicode_string_at};
+use crate::paging::MemoryImage;
+
+/// HHIVE.Signature
+const HIVE_SIGNATURE: [u8; 4] = 0xBEE0_BEE0u32.to_le_bytes();
+/// HHIVE and CMHIVE offsets
+const HHIVE_BASE_BLOCK: usize = 0x40;
+/// HHIVE.Storage[Stable]: DUAL.Length and DUAL.Map
+const HHIVE_STABLE_LENGTH: usize = 0x118;
+const HHIVE_STABLE_MAP: usize = 0x120;
+const CMHIVE_FILE_FULL_PATH: usize = 0x1048;
+const CMHIVE_HIVE_ROOT_PATH: usize = 0x1058;
+const CMHIVE_SIZE: usize = 0x1068;
+
+/// HMAP_ENTRY offsets and size (Windows 8 and later)
+const HMAP_BLOCK_OFFSET: u64 = 0x0;
+const HMAP_PERMANENT_BIN_ADDRESS: u64 = 0x8;
+const HMAP_ENTRY_SIZE: u64 = 0x18;
+
+const KERNEL_VA_START: u64 = 0xFFFF_8000_0000_0000;
+const BLOCK_SIZE: usize = 0x1000;
+/// Largest stable storage read, beyond any real hive
+const MAX_HIVE_LENGTH: u32 = 0x2000_0000;
+const CHUNK_SIZE: usize = 0x10000;
+
+/// `regf` base block fields
+const REGF_SIGNATURE: &[u8; 4] = b"regf";
+const REGF_ROOT_CELL: usize = 0x24;
+const REGF_HIVE_LENGTH: usize = 0x28;
+
+/// Key node flag: the name is stored in Latin-1 rather than UTF-16
+const KEY_COMP_NAME: u16 = 0x20;
+/// Value flag: the name is stored in Latin-1 rather than UTF-16
+const VALUE_COMP_NAME: u16 = 0x1;
+/// Data length bit marking data stored in the data offset field itself
+const DATA_INLINE: u32 = 0x8000_0000;
+/// Largest value data in one cell; longer data is split by a `db` record
+const MAX_CELL_DATA: usize = 16344;
+/// Deepest nesting of `ri` index lists followed
+const MAX_INDEX_DEPTH: usize = 4;
+
+/// A hive loaded at capture time
+#[derive(Debug, Clone, PartialEq)]
+pub struct HiveInfo {
+    /// Physical address of the CMHIVE
+    pub addr: u64,
+    pub base_block: u64,
+    /// Bytes of stable storage, the hive bins
+    pub length: u32,
+    pub map: u64,
+    /// Where the hive is mounted, e.g. `\REGISTRY\MACHINE\SOFTWARE`
+    pub root_path: Option<String>,
+    /// Backing file, e.g. `\Device\HarddiskVolume2\Windows\System32\config\SOFTWARE`
+    pub file_path: Option<String>,
+}
+
+impl HiveInfo {
+    /// Short name for listings and file names: the backing file's name, or
+    /// the last component of the mount path
+    pub fn name(&self) -> String {
+        self.file_path.as_deref().or(self.root_path.as_deref())
+            .and_then(|path| path.rsplit('\\').find(|part| !part.is_empty()))
+            .unwrap_or("unnamed")
+            .to_string()
+    }
+}
+
+/// Validate and decode the CMHIVE at physical address `addr`; the image must
+/// use the kernel DTB
+pub fn parse_cmhive(img: &MemoryImage, addr: u64) -> Option<HiveInfo> {
+    let data = img.get_bytes(addr as usize, CMHIVE_SIZE)?;
+    if data[..4] != HIVE_SIGNATURE {
+        return None;
+    }
+    let u64_at = |off: usize| u64::from_le_bytes(data[off..off + 8].try_into().unwrap());
+    let base_block = u64_at(HHIVE_BASE_BLOCK);
+    let length = u32::from_le_bytes(data[HHIVE_STABLE_LENGTH..HHIVE_STABLE_LENGTH + 4].try_into().unwrap());
+    let map = u64_at(HHIVE_STABLE_MAP);
+    if base_block < KERNEL_VA_START || map < KERNEL_VA_START || !(length as usize).is_multiple_of(BLOCK_SIZE) || length > MAX_HIVE_LENGTH {
+        return None;
+    }
+    if img.read_virt(base_block, 4)? != REGF_SIGNATURE {
+        return None;
+    }
+    Some(HiveInfo {
+        addr,
+        base_block,
+        length,
+        map,
+        root_path: unicode_string_at(img, data, CMHIVE_HIVE_ROOT_PATH),
+        file_path: unicode_string_at(img, data, CMHIVE_FILE_FULL_PATH),
+    })
+}
+
+/// Scan physical memory for CMHIVE structures
+///
+/// CMHIVEs are larger than a page and often come from the big-page pool,
+/// which has no pool headers, so the scan looks for the HHIVE signature
+/// itself on every 16-byte boundary.
+pub fn find_hives(img: &MemoryImage, progress: &ProgressBar) -> Vec<HiveInfo> {
+    let size = img.size();
+    progress.set_length(size as u64);
+    let mut hives = Vec::new();
+    for chunk_start in (0..size).step_by(CHUNK_SIZE) {
+        progress.set_position(chunk_start as u64);
+        let Some(chunk) = img.get_bytes(chunk_start, CHUNK_SIZE.min(size - chunk_start)) else { continue };
+        for at in (0..chunk.len().saturating_sub(3)).step_by(0x10).filter(|&at| chunk[at..at + 4] == HIVE_SIGNATURE) {
+            hives.extend(parse_cmhive(img, (chunk_start + at) as u64));
+        }
+    }
+    hives
+}
+
+/// Virtual address of the block holding cell index `index`, from the
+/// stable storage's cell map
+fn block_address(img: &MemoryImage, map: u64, index: u32) -> Option<u64> {
+    let directory = ((index >> 21) & 0x3FF) as u64;
+    let table = ((index >> 12) & 0x1FF) as u64;
+    let table_va = img.read_virt_u64(map + directory * 8).filter(|&va| va != 0)?;
+    let entry = table_va + table * HMAP_ENTRY_SIZE;
+    let bin = img.read_virt_u64(entry + HMAP_PERMANENT_BIN_ADDRESS)? & !0xF;
+    let offset = img.read_virt_u64(entry + HMAP_BLOCK_OFFSET)?;
+    (bin != 0).then(|| bin + offset)
+}
+
+/// Rebuild a hive file from memory: the base block followed by every block
+/// of the stable storage; returns the data and the number of blocks that
+/// were not resident
+pub fn read_hive(img: &MemoryImage, hive: &HiveInfo) -> (Vec<u8>, usize) {
+    let (mut data, mut missing) = read_pages(img, hive.base_block, BLOCK_SIZE as u64);
+    // The base block's length goes stale while the hive grows in memory
+    data[REGF_HIVE_LENGTH..REGF_HIVE_LENGTH + 4].copy_from_slice(&hive.length.to_le_bytes());
+    for index in (0..hive.length).step_by(BLOCK_SIZE) {
+        match block_address(img, hive.map, index).and_then(|va| img.read_virt(va, BLOCK_SIZE)) {
+            Some(block) => data.extend_from_slice(&block),
+            None => {
+                data.resize(data.len() + BLOCK_SIZE, 0);
+                missing += 1;
+            }
+        }
+    }
+    (data, missing)
+}
+
+/// A registry key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    pub name: String,
+    /// Class name, which some keys use to hold data (the boot key among them)
+    pub class: Option<String>,
+    /// FILETIME of the last change
+    pub last_write: u64,
+    /// Cell offset of the key node
+    pub offset: u32,
+}
+
+/// A registry value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Value {
+    /// Empty for the key's default value
+    pub name: String,
+    pub value_type: u32,
+    pub data: Vec<u8>,
+}
+
+fn utf16_string(data: &[u8]) -> String {
+    let units: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units).trim_end_matches('\0').to_string()
+}
+
+fn name_string(data: &[u8], compressed: bool) -> String {
+    if compressed {
+        data.iter().map(|&b| b as char).collect()
+    } else {
+        utf16_string(data)
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self.value_type {
+            0 => "REG_NONE",
+            1 => "REG_SZ",
+            2 => "REG_EXPAND_SZ",
+            3 => "REG_BINARY",
+            4 => "REG_DWORD",
+            5 => "REG_DWORD_BIG_ENDIAN",
+            6 => "REG_LINK",
+            7 => "REG_MULTI_SZ",
+            11 => "REG_QWORD",
+            _ => "UNKNOWN",
+        }
+    }
+
+    /// The data as text: strings decoded, numbers in decimal and hex, and
+    /// anything else as hex bytes
+    pub fn display(&self) -> String {
+        let data = &self.data;
+        match (self.value_type, data.len()) {
+            (1 | 2 | 6, _) => utf16_string(data),
+            (7, _) => utf16_string(data).split('\0').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("; "),
+            (4, 4) => {
+                let value = u32::from_le_bytes(data[..4].try_into().unwrap());
+                format!("{} (0x{:X})", value, value)
+            }
+            (5, 4) => {
+                let value = u32::from_be_bytes(data[..4].try_into().unwrap());
+                format!("{} (0x{:X})", value, value)
+            }
+            (11, 8) => {
+                let value = u64::from_le_bytes(data[..8].try_into().unwrap());
+                format!("{} (0x{:X})", value, value)
+            }
+            _ => {
+                let hex: String = data.iter().take(64).map(|b| format!("{:02x}", b)).collect();
+                if data.len() > 64 { format!("{}... ({} bytes)", hex, data.len()) } else { hex }
+            }
+        }
+    }
+}
+
+/// A hive in `regf` format, read from a file or rebuilt from memory
+#[derive(Debug, Clone)]
+pub struct Hive {
+    data: Vec<u8>,
+}
+
+impl Hive {
+    pub fn parse(data: Vec<u8>) -> Result<Self> {
+        if data.len() < BLOCK_SIZE || &data[..4] != REGF_SIGNATURE {
+            bail!("Not a registry hive (no regf base block)");
+        }
+        Ok(Hive { data })
+    }
+
+    fn u16_at(data: &[u8], off: usize) -> Option<u16> {
+        data.get(off..off + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32_at(data: &[u8], off: usize) -> Option<u32> {
+        data.get(off..off + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    /// Data of the cell at `offset`, relative to the first bin
+    fn cell(&self, offset: u32) -> Option<&[u8]> {
+        let at = BLOCK_SIZE + offset as usize;
+        let size = (Self::u32_at(&self.data, at)? as i32).unsigned_abs() as usize;
+        if size < 4 {
+            return None;
+        }
+        self.data.get(at + 4..at + size)
+    }
+
+    fn key_at(&self, offset: u32) -> Option<Key> {
+        let cell = self.cell(offset)?;
+        if !cell.starts_with(b"nk") {
+            return None;
+        }
+        let flags = Self::u16_at(cell, 0x2)?;
+        let name_len = Self::u16_at(cell, 0x48)? as usize;
+        let class_len = Self::u16_at(cell, 0x4A)? as usize;
+        let class_offset = Self::u32_at(cell, 0x30)?;
+        let class = (class_len > 0 && class_offset != u32::MAX)
+            .then(|| self.cell(class_offset).and_then(|c| c.get(..class_len)).map(utf16_string))
+            .flatten();
+        Some(Key {
+            name: name_string(cell.get(0x4C..0x4C + name_len)?, flags & KEY_COMP_NAME != 0),
+            class,
+            last_write: u64::from_le_bytes(cell.get(0x4..0xC)?.try_into().unwrap()),
+            offset,
+        })
+    }
+
+    pub fn root_key(&self) -> Result<Key> {
+        let root = Self::u32_at(&self.data, REGF_ROOT_CELL).unwrap_or(u32::MAX);
+        self.key_at(root).context("Hive root key is not resident")
+    }
+
+    /// Key node offsets in the subkey index at `offset`
+    fn index_entries(&self, offset: u32, depth: usize, keys: &mut Vec<u32>) {
+        let Some(cell) = self.cell(offset) else { return };
+        let count = Self::u16_at(cell, 0x2).unwrap_or(0) as usize;
+        let (stride, nested) = match cell.get(..2) {
+            Some(b"lf") | Some(b"lh") => (8, false),
+            Some(b"li") => (4, false),
+            Some(b"ri") if depth < MAX_INDEX_DEPTH => (4, true),
+            _ => return,
+        };
+        for entry in (0..count).filter_map(|i| Self::u32_at(cell, 0x4 + i * stride)) {
+            if nested {
+                self.index_entries(entry, depth + 1, keys);
+            } else {
+                keys.push(entry);
+            }
+        }
+    }
+
+    /// Subkeys of `key` whose node is resident
+    pub fn subkeys(&self, key: &Key) -> Vec<Key> {
+        let Some(cell) = self.cell(key.offset) else { return Vec::new() };
+        let (Some(count), Some(list)) = (Self::u32_at(cell, 0x14), Self::u32_at(cell, 0x1C)) else { return Vec::new() };
+        if count == 0 || list == u32::MAX {
+            return Vec::new();
+        }
+        let mut offsets = Vec::new();
+        self.index_entries(list, 0, &mut offsets);
+        offsets.into_iter().filter_map(|offset| self.key_at(offset)).collect()
+    }
+
+    pub fn subkey(&self, key: &Key, name: &str) -> Option<Key> {
+        self.subkeys(key).into_iter().find(|k| k.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The key at `path` below the root, with components separated by `\`
+    pub fn open_key(&self, path: &str) -> Result<Key> {
+        let mut key = self.root_key()?;
+        for part in path.split('\\').filter(|p| !p.is_empty()) {
+            key = self.subkey(&key, part).with_context(|| format!("No key {} under {}", part, key.name))?;
+        }
+        Ok(key)
+    }
+
+    fn value_data(&self, cell: &[u8]) -> Option<Vec<u8>> {
+        let length = Self::u32_at(cell, 0x4)?;
+        let offset = Self::u32_at(cell, 0x8)?;
+        if length & DATA_INLINE != 0 {
+            let length = (length & !DATA_INLINE) as usize;
+            return cell.get(0x8..0x8 + length.min(4)).map(<[u8]>::to_vec);
+        }
+        let length = length as usize;
+        let data = self.cell(offset)?;
+        if length > MAX_CELL_DATA && data.starts_with(b"db") {
+            // Big data: a list of segments of up to MAX_CELL_DATA bytes each
+            let segments = Self::u16_at(data, 0x2)? as usize;
+            let list = self.cell(Self::u32_at(data, 0x4)?)?;
+            let mut out = Vec::with_capacity(length);
+            for segment in (0..segments).filter_map(|i| Self::u32_at(list, i * 4)) {
+                let bytes = self.cell(segment)?;
+                out.extend_from_slice(&bytes[..bytes.len().min(MAX_CELL_DATA).min(length - out.len())]);
+            }
+            return (out.len() == length).then_some(out);
+        }
+        data.get(..length).map(<[u8]>::to_vec)
+    }
+
+    /// Values of `key` whose record and data are resident
+    pub fn values(&self, key: &Key) -> Vec<Value> {
+        let Some(cell) = self.cell(key.offset) else { return Vec::new() };
+        let (Some(count), Some(list)) = (Self::u32_at(cell, 0x24), Self::u32_at(cell, 0x28)) else { return Vec::new() };
+        if count == 0 || list == u32::MAX {
+            return Vec::new();
+        }
+        let Some(list) = self.cell(list) else { return Vec::new() };
+        (0..count as usize).filter_map(|i| Self::u32_at(list, i * 4)).filter_map(|offset| {
+            let cell = self.cell(offset).filter(|c| c.starts_with(b"vk"))?;
+            let name_len = Self::u16_at(cell, 0x2)? as usize;
+            let flags = Self::u16_at(cell, 0x10)?;
+            Some(Value {
+                name: name_string(cell.get(0x14..0x14 + name_len)?, flags & VALUE_COMP_NAME != 0),
+                value_type: Self::u32_at(cell, 0xC)?,
+                data: self.value_data(cell)?,
+            })
+        }).collect()
+    }
+
+    pub fn value(&self, key: &Key, name: &str) -> Option<Value> {
+        self.values(key).into_iter().find(|v| v.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Expand the `HKLM` and `HKU` abbreviations to the registry's own paths
+pub fn normalize_key_path(path: &str) -> String {
+    let path = path.trim_matches('\\');
+    let (root, rest) = path.split_once('\\').unwrap_or((path, ""));
+    let root = match root.to_ascii_uppercase().as_str() {
+        "HKLM" | "HKEY_LOCAL_MACHINE" => "\\REGISTRY\\MACHINE".to_string(),
+        "HKU" | "HKEY_USERS" => "\\REGISTRY\\USER".to_string(),
+        "REGISTRY" => "\\REGISTRY".to_string(),
+        other => other.to_string(),
+    };
+    if rest.is_empty() { root } else { format!("{}\\{}", root, rest) }
+}
+
+/// The hive mounted closest above `path` and the path of the key within it
+pub fn resolve_key<'a>(hives: &'a [HiveInfo], path: &str) -> Option<(&'a HiveInfo, String)> {
+    let path = normalize_key_path(path);
+    let upper = path.to_ascii_uppercase();
+    hives.iter().filter_map(|hive| {
+        let root = hive.root_path.as_deref()?.trim_end_matches('\\').to_ascii_uppercase();
+        let rest = upper.strip_prefix(&root)?;
+        (rest.is_empty() || rest.starts_with('\\')).then_some((hive, root.len()))
+    }).max_by_key(|(_, len)| *len).map(|(hive, len)| (hive, path[len..].trim_start_matches('\\').to_string()))
+}
+
+/// The control set in use, e.g. `ControlSet001`, from a SYSTEM hive's
+/// `Select\Current` value
+pub fn current_control_set(system: &Hive) -> Result<String> {
+    let select = system.open_key("Select")?;
+    let current = system.value(&select, "Current")
+        .and_then(|v| v.data.get(..4).map(|b| u32::from_le_bytes(b.try_into().unwrap())))
+        .context("SYSTEM hive has no Select\\Current value")?;
+    Ok(format!("ControlSet{:03}", current))
+}
+
+fn progress_bar() -> Result<crate::progress::Progress> {
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    progress.set_message("Scanning for registry hives");
+    Ok(progress)
+}
+
+fn format_filetime(filetime: u64) -> String {
+    crate::processes::filetime_to_system(filetime)
+        .map(|time| chrono::DateTime::<chrono::Utc>::from(time).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// List the hives of a dump, or with `key` that key's subkeys and values
+pub fn list_registry(dump_path: PathBuf, dtb: u64, key: Option<String>) -> Result<()> {
+    let mut img = load_memory_image(&dump_path)?;
+    img.set_cr3(dtb);
+    let progress = progress_bar()?;
+    let hives = find_hives(&img, &progress);
+    progress.finish_and_clear();
+    if hives.is_empty() {
+        println!("{}", "No registry hives found.".bright_red());
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    let Some(path) = key else {
+        table.set_titles(row![bFg->"CMHIVE", bFg->"Size", bFg->"Mounted At", bFg->"File"]);
+        for hive in &hives {
+            table.add_row(row![
+                format!("0x{:X}", hive.addr),
+                format!("0x{:X}", hive.length),
+                hive.root_path.as_deref().unwrap_or("-"),
+                hive.file_path.as_deref().unwrap_or("-")
+            ]);
+        }
+        println!("{} {}", "Registry hives:".bright_green(), hives.len().to_string().bright_yellow());
+        table.printstd();
+        return Ok(());
+    };
+
+    let (info, relative) = resolve_key(&hives, &path).with_context(|| format!("No loaded hive holds {}", path))?;
+    let (data, missing) = read_hive(&img, info);
+    let hive = Hive::parse(data)?;
+    let key = hive.open_key(&relative)?;
+    println!("{} {} (hive {}, {} blocks not resident)", "Key".bright_green(), normalize_key_path(&path).bright_yellow(),
+        info.name(), missing);
+    println!("Last written: {}", format_filetime(key.last_write));
+
+    let subkeys = hive.subkeys(&key);
+    if !subkeys.is_empty() {
+        table.set_titles(row![bFg->"Subkey", bFg->"Last Written"]);
+        for subkey in &subkeys {
+            table.add_row(row![subkey.name, format_filetime(subkey.last_write)]);
+        }
+        table.printstd();
+    }
+    let mut values_table = Table::new();
+    values_table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    values_table.set_titles(row![bFg->"Value", bFg->"Type", bFg->"Data"]);
+    for value in hive.values(&key) {
+        let name = if value.name.is_empty() { "(Default)".to_string() } else { value.name.clone() };
+        values_table.add_row(row![name, value.type_name(), value.display()]);
+    }
+    if !values_table.is_empty() {
+        values_table.printstd();
+    }
+    Ok(())
+}
+
+/// Write every hive of a dump to `output` as a hive file
+pub fn dump_hives(dump_path: PathBuf, dtb: u64, output: PathBuf) -> Result<()> {
+    let mut img = load_memory_image(&dump_path)?;
+    img.set_cr3(dtb);
+    let progress = progress_bar()?;
+    let hives = find_hives(&img, &progress);
+    progress.finish_and_clear();
+    if hives.is_empty() {
+        println!("{}", "No registry hives found.".bright_red());
+        return Ok(());
+    }
+    fs::create_dir_all(&output).with_context(|| format!("Failed to create {}", output.display()))?;
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"CMHIVE", bFg->"Mounted At", bFg->"Missing Blocks", bFg->"File"]);
+    let mut paths = Vec::new();
+    for hive in &hives {
+        let (data, missing) = read_hive(&img, hive);
+        let name: String = hive.name().chars().map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '_' { c } else { '_' }).collect();
+        let path = output.join(format!("{}.0x{:X}.hive", name, hive.addr));
+        fs::write(&path, &data).with_context(|| format!("Failed to write {}", path.display()))?;
+        table.add_row(row![
+            format!("0x{:X}", hive.addr),
+            hive.root_path.as_deref().unwrap_or("-"),
+            format!("{}/{}", missing, hive.length as usize / BLOCK_SIZE + 1),
+            path.display()
+        ]);
+        paths.push(path);
+    }
+    println!("{} {} hives to {}", "Wrote".bright_green(), hives.len().to_string().bright_yellow(), output.display().to_string().bright_cyan());
+    table.printstd();
+    crate::actions::run_for_files(&dump_path, &paths);
+    Ok(())
+}
+
+/// Print the Shimcache and Amcache entries of a dump, oldest first
+pub fn report_execution_timeline(dump_path: PathBuf, dtb: u64) -> Result<()> {
+    let mut img = load_memory_image(&dump_path)?;
+    img.set_cr3(dtb);
+    let progress = progress_bar()?;
+    let hives = find_hives(&img, &progress);
+    progress.finish_and_clear();
+    let timeline = crate::plugin::execution_timeline(&img, &hives);
+    if timeline.is_empty() {
+        println!("{}", "No Shimcache or Amcache entries found.".bright_red());
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"Time (UTC)", bFg->"Source", bFg->"Position", bFg->"Path", bFg->"SHA-1"]);
+    for (_, entry) in &timeline {
+        table.add_row(row![
+            format_filetime(entry.timestamp),
+            entry.source,
+            entry.position.map_or("-".to_string(), |p| p.to_string()),
+            entry.path,
+            entry.sha1.as_deref().unwrap_or("-")
+        ]);
+    }
+    println!("{} {} entries", "Execution timeline:".bright_green(), timeline.len().to_string().bright_yellow());
+    table.printstd();
+    Ok(())
+}
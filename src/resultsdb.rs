@@ -0,0 +1,122 @@
+//! SQLite results databases
+//!
+//! An `--output-file` or `run-plugin --output` named `*.db`, `*.sqlite` or
+//! `*.sqlite3` collects records in a database instead of a JSON or CSV file.
+//! Each command adds a row to `runs` (the dump, its size, the command line and
+//! when it ran) and its records to the table of their schema, `findings`,
+//! `processes`, `modules`, `translations`, `timeline` or `diffs`, with a `run_id`
+//! pointing at the run. The columns are those of the CSV output; lists and maps, such as a
+//! finding's details, are JSON text for `json_extract`. Opening an existing
+//! database appends to it, so one file can hold every step of an
+//! investigation:
+//!
+//! ```text
+//! SELECT r.dump, f.plugin, f.address, json_extract(f.details, '$.rule')
+//!   FROM findings f JOIN runs r ON r.id = f.run_id WHERE f.confidence >= 80;
+//! ```
+
+use anyhow::{Context, Result};
+use rusqlite::{params, params_from_iter, types::Value, Connection};
+use std::path::Path;
+use crate::output::{Record, SCHEMA_VERSION};
+
+const RUNS_TABLE: &str = "CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    dump TEXT NOT NULL,
+    dump_size INTEGER,
+    command TEXT NOT NULL,
+    started TEXT NOT NULL,
+    rmf_version TEXT NOT NULL,
+    schema_version INTEGER NOT NULL
+)";
+
+/// A results database, created on first use
+pub struct ResultsDb {
+    conn: Connection,
+}
+
+impl ResultsDb {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path).with_context(|| format!("Failed to open results database {}", path.display()))?;
+        conn.execute(RUNS_TABLE, [])?;
+        Ok(ResultsDb { conn })
+    }
+
+    /// Record a run of the current command on `dump`, returning its ID
+    pub fn start_run(&self, dump: &Path) -> Result<i64> {
+        let command = std::env::args().collect::<Vec<_>>().join(" ");
+        let size = std::fs::metadata(dump).ok().map(|m| m.len() as i64);
+        self.conn.execute(
+            "INSERT INTO runs (dump, dump_size, command, started, rmf_version, schema_version) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                dump.display().to_string(),
+                size,
+                command,
+                chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                env!("CARGO_PKG_VERSION"),
+                SCHEMA_VERSION,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Add `records` to the table of their schema under `run`, creating the
+    /// table or the columns a newer schema added as needed
+    pub fn insert<R: Record>(&mut self, run: i64, records: &[R]) -> Result<()> {
+        let columns: Vec<String> = R::COLUMNS.iter().map(|c| format!("\"{}\"", c)).collect();
+        self.conn.execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (run_id INTEGER NOT NULL REFERENCES runs(id), {columns})",
+            table = R::TABLE,
+            columns = columns.join(", "),
+        ), [])?;
+        self.conn.execute(&format!("CREATE INDEX IF NOT EXISTS {table}_run ON {table} (run_id)", table = R::TABLE), [])?;
+        let existing: Vec<String> = self.conn
+            .prepare(&format!("SELECT name FROM pragma_table_info('{}')", R::TABLE))?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for column in R::COLUMNS.iter().filter(|c| !existing.iter().any(|e| e == *c)) {
+            self.conn.execute(&format!("ALTER TABLE {} ADD COLUMN \"{}\"", R::TABLE, column), [])?;
+        }
+
+        let sql = format!(
+            "INSERT INTO {} (run_id, {}) VALUES (?1, {})",
+            R::TABLE,
+            columns.join(", "),
+            (2..=columns.len() + 1).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", "),
+        );
+        let transaction = self.conn.transaction()?;
+        {
+            let mut insert = transaction.prepare(&sql)?;
+            for record in records {
+                let value = serde_json::to_value(record)?;
+                let values = std::iter::once(Value::Integer(run))
+                    .chain(R::COLUMNS.iter().map(|column| sql_value(value.get(column))));
+                insert.execute(params_from_iter(values))?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// A record field as SQLite stores it: JSON text for lists and maps
+fn sql_value(value: Option<&serde_json::Value>) -> Value {
+    match value {
+        None | Some(serde_json::Value::Null) => Value::Null,
+        Some(serde_json::Value::Bool(b)) => Value::Integer(*b as i64),
+        Some(serde_json::Value::Number(n)) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => Value::Integer(i),
+            (None, Some(f)) if !n.is_u64() => Value::Real(f),
+            _ => Value::Text(n.to_string()),
+        },
+        Some(serde_json::Value::String(text)) => Value::Text(text.clone()),
+        Some(other) => Value::Text(other.to_string()),
+    }
+}
+
+/// Record a run on `dump` holding `records` in the database at `path`
+pub fn save<R: Record>(path: &Path, dump: &Path, records: &[R]) -> Result<()> {
+    let mut db = ResultsDb::open(path)?;
+    let run = db.start_run(dump)?;
+    db.insert(run, records)
+}
@@ -0,0 +1,128 @@
+//! Chunked scans of physical memory
+//!
+//! Plugins that search the whole image read it a chunk at a time. A chunk
+//! read alone misses a pattern straddling its end, so each [`Window`] also
+//! holds the first `overlap` bytes of the next chunk, at least the longest
+//! pattern the plugin matches, and optionally `lookbehind` bytes of the
+//! previous one for matches that are parsed backwards from their anchor.
+//! A window reports only what starts in its own chunk; what starts in the
+//! overlap is reported whole by the next window.
+
+use indicatif::ProgressBar;
+use std::ops::Range;
+
+use crate::paging::MemoryImage;
+use crate::plugin::Finding;
+
+/// The chunk size plugins read by default
+pub const CHUNK_SIZE: usize = 0x10000;
+
+/// Every offset of `needle` in `haystack`
+pub fn find_all<'a>(haystack: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(move |(_, w)| *w == needle)
+        .map(|(i, _)| i)
+}
+
+/// Bytes of a path token such as a cgroup path
+fn is_path_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'/' | b'-' | b'_' | b'.' | b':')
+}
+
+/// Expand a needle hit at `pos` to the surrounding path token
+pub fn path_around(chunk: &[u8], pos: usize) -> &[u8] {
+    let start = chunk[..pos].iter().rposition(|&c| !is_path_char(c)).map(|p| p + 1).unwrap_or(0);
+    let end = chunk[pos..].iter().position(|&c| !is_path_char(c)).map(|p| pos + p).unwrap_or(chunk.len());
+    &chunk[start..end]
+}
+
+/// One chunk of physical memory with the bytes around it
+#[derive(Debug, Clone)]
+pub struct Window<'a> {
+    /// Physical address of `data[0]`
+    pub start: usize,
+    pub data: &'a [u8],
+    /// The offsets in `data` of the chunk itself
+    pub owned: Range<usize>,
+}
+
+impl Window<'_> {
+    /// Whether a match at `offset` in `data` starts in this window's chunk
+    pub fn owns(&self, offset: usize) -> bool {
+        self.owned.contains(&offset)
+    }
+
+    /// Physical address of `offset` in `data`
+    pub fn addr(&self, offset: usize) -> u64 {
+        (self.start + offset) as u64
+    }
+
+    /// Drop the findings from index `from` on whose address is outside this
+    /// window's chunk; a neighbouring window reports them
+    pub fn retain_owned(&self, findings: &mut Vec<Finding>, from: usize) {
+        let owned = self.addr(self.owned.start)..self.addr(self.owned.end);
+        let mut index = 0;
+        findings.retain(|finding| {
+            index += 1;
+            index <= from || owned.contains(&finding.addr)
+        });
+    }
+}
+
+/// Iterator over the windows of an image, see [`chunks`]
+pub struct Chunks<'a> {
+    img: &'a MemoryImage,
+    progress: Option<&'a ProgressBar>,
+    chunk: usize,
+    overlap: usize,
+    lookbehind: usize,
+    next: usize,
+}
+
+/// The windows of `img`, `chunk` bytes each plus `overlap` bytes of the
+/// next; unreadable chunks are skipped
+pub fn chunks(img: &MemoryImage, chunk: usize, overlap: usize) -> Chunks<'_> {
+    Chunks { img, progress: None, chunk, overlap, lookbehind: 0, next: 0 }
+}
+
+impl<'a> Chunks<'a> {
+    /// Track the scan's position on `progress`
+    pub fn with_progress(mut self, progress: &'a ProgressBar) -> Self {
+        progress.set_length(self.img.size() as u64);
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Also hold `lookbehind` bytes of the previous chunk
+    pub fn with_lookbehind(mut self, lookbehind: usize) -> Self {
+        self.lookbehind = lookbehind;
+        self
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Window<'a>;
+
+    fn next(&mut self) -> Option<Window<'a>> {
+        let size = self.img.size();
+        while self.next < size {
+            let chunk_start = self.next;
+            let chunk_end = (chunk_start + self.chunk).min(size);
+            self.next = chunk_end;
+            if let Some(progress) = self.progress {
+                progress.set_position(chunk_start as u64);
+            }
+            let start = chunk_start.saturating_sub(self.lookbehind);
+            let end = (chunk_end + self.overlap).min(size);
+            // Neighbouring chunks may lie outside the run of memory holding this one
+            let window = self.img.get_bytes(start, end - start).map(|data| (start, data))
+                .or_else(|| self.img.get_bytes(chunk_start, chunk_end - chunk_start).map(|data| (chunk_start, data)));
+            if let Some((start, data)) = window {
+                return Some(Window { start, data, owned: chunk_start - start..chunk_end - start });
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,104 @@
+//! User-defined scan signatures
+//!
+//! Detection content a team maintains without recompiling: a TOML file of
+//! `[[signature]]` tables, each a byte signature (`bytes`, with `??` and
+//! nibble wildcards) or a regular expression (`regex`) with a name and,
+//! optionally, a description, a confidence (default 50) and tags. The
+//! `scan` command and the `signature_scan` plugin search memory for them.
+//!
+//! ```toml
+//! [[signature]]
+//! name = "cobalt_strike_pipe"
+//! regex = 'MSSE-[0-9]+-server'
+//! description = "Cobalt Strike default named pipe"
+//! confidence = 80
+//! tags = ["cobaltstrike", "c2"]
+//!
+//! [[signature]]
+//! name = "x64_shellcode_prologue"
+//! bytes = "FC 48 83 E4 F0 E8 ?? 00 00 00"
+//! ```
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Confidence of signatures that do not give one
+pub const DEFAULT_CONFIDENCE: u8 = 50;
+
+/// What a signature searches for
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignaturePattern {
+    /// Hex bytes with wildcards, such as `4D 5A ?? ?? 90`
+    Bytes(String),
+    /// A regular expression over bytes, also searched as UTF-16LE
+    Regex(String),
+}
+
+/// One entry of a signature file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    pub name: String,
+    pub pattern: SignaturePattern,
+    pub description: String,
+    pub confidence: u8,
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SignatureFile {
+    #[serde(default)]
+    signature: Vec<RawSignature>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSignature {
+    name: String,
+    bytes: Option<String>,
+    regex: Option<String>,
+    #[serde(default)]
+    description: String,
+    confidence: Option<u8>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// The signatures of one or more files
+#[derive(Debug, Clone, Default)]
+pub struct SignatureSet {
+    pub signatures: Vec<Signature>,
+}
+
+impl SignatureSet {
+    /// Parse signature file text; `origin` names the source in errors
+    pub fn parse(text: &str, origin: &str) -> Result<Self> {
+        let file: SignatureFile = toml::from_str(text).with_context(|| format!("{} is not a signature file", origin))?;
+        let mut signatures: Vec<Signature> = Vec::new();
+        for raw in file.signature {
+            let name = raw.name.trim().to_string();
+            if name.is_empty() {
+                bail!("{}: a signature has an empty name", origin);
+            }
+            if signatures.iter().any(|s| s.name == name) {
+                bail!("{}: duplicate signature '{}'", origin, name);
+            }
+            let pattern = match (raw.bytes, raw.regex) {
+                (Some(bytes), None) => SignaturePattern::Bytes(bytes),
+                (None, Some(regex)) => SignaturePattern::Regex(regex),
+                _ => bail!("{}: signature '{}' needs exactly one of bytes or regex", origin, name),
+            };
+            let confidence = raw.confidence.unwrap_or(DEFAULT_CONFIDENCE);
+            if confidence > 100 {
+                bail!("{}: signature '{}' has confidence {} above 100", origin, name, confidence);
+            }
+            signatures.push(Signature { name, pattern, description: raw.description, confidence, tags: raw.tags });
+        }
+        Ok(SignatureSet { signatures })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read signatures from {}", path.display()))?;
+        Self::parse(&text, &path.display().to_string())
+    }
+}
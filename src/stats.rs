@@ -0,0 +1,214 @@
+//! Physical memory composition report
+//!
+//! Classifies every physical page of an image so analysts can see how much
+//! of it the tool actually understands: zero pages, page-table pages, pages
+//! mapped into kernel or process address spaces, and the unidentified rest.
+//! Mappings come from walking the DTB; page tables that are not reachable
+//! from it are still recognised by their shape.
+
+use anyhow::Result;
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use std::path::PathBuf;
+
+use crate::arch::x86_64::PAGE_SIZE;
+use crate::loader::load_memory_image;
+use crate::paging::{Architecture, MemoryImage};
+
+const PTE_PRESENT: u64 = 1 << 0;
+const PTE_LARGE: u64 = 1 << 7;
+const PTE_NX: u64 = 1 << 63;
+const PTE_FRAME_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+/// Bits 52-62 are ignored/reserved and almost never set in real entries
+const PTE_HIGH_BITS: u64 = 0x7FF0_0000_0000_0000;
+
+/// Per-page attributes gathered during the walk
+const PAGE_TABLE: u8 = 1 << 0;
+const KERNEL: u8 = 1 << 1;
+const PROCESS: u8 = 1 << 2;
+const EXECUTABLE: u8 = 1 << 3;
+
+/// Page counts per category
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryStats {
+    pub total_pages: u64,
+    pub zero_pages: u64,
+    pub page_table_pages: u64,
+    pub kernel_pages: u64,
+    pub process_pages: u64,
+    pub unidentified_pages: u64,
+    /// Pages mapped executable somewhere; overlaps the kernel/process counts
+    pub executable_pages: u64,
+    /// Whether mappings were available (a DTB or a user-mode dump)
+    pub mappings_known: bool,
+}
+
+/// Does this page look like a table of x86_64 paging entries?
+fn looks_like_page_table(page: &[u8], max_frame: u64) -> bool {
+    let mut present = 0;
+    for entry in page.chunks_exact(8).map(|e| u64::from_le_bytes(e.try_into().unwrap())) {
+        if entry == 0 {
+            continue;
+        }
+        if entry & PTE_PRESENT == 0 || entry & PTE_HIGH_BITS != 0 || entry & PTE_FRAME_MASK >= max_frame {
+            return false;
+        }
+        present += 1;
+    }
+    // A couple of valid-looking words is common in ordinary data
+    present >= 4
+}
+
+/// Page walker that marks every physical frame reachable from the DTB
+struct Walker<'a> {
+    img: &'a MemoryImage,
+    marks: &'a mut [u8],
+}
+
+impl Walker<'_> {
+    fn table(&self, base: u64) -> Vec<u64> {
+        self.img.get_bytes(base as usize, PAGE_SIZE)
+            .map(|t| t.chunks_exact(8).map(|e| u64::from_le_bytes(e.try_into().unwrap())).collect())
+            .unwrap_or_default()
+    }
+
+    fn mark(&mut self, frame: u64, pages: u64, flags: u8) {
+        let first = frame / PAGE_SIZE as u64;
+        for page in first..(first + pages).min(self.marks.len() as u64) {
+            self.marks[page as usize] |= flags;
+        }
+    }
+
+    fn mark_table(&mut self, base: u64) {
+        self.mark(base, 1, PAGE_TABLE);
+    }
+
+    /// Walk a table at `level` (4 = PML4) under the given owner/executable state
+    fn walk(&mut self, base: u64, level: u32, owner: Option<u8>, executable: bool) {
+        self.mark_table(base);
+        for (index, entry) in self.table(base).into_iter().enumerate() {
+            if entry & PTE_PRESENT == 0 {
+                continue;
+            }
+            // The PML4 index decides the kernel/user half of the address space
+            let owner = owner.unwrap_or(if index >= 256 { KERNEL } else { PROCESS });
+            let executable = executable && entry & PTE_NX == 0;
+            let frame = entry & PTE_FRAME_MASK;
+            let exec_flag = if executable { EXECUTABLE } else { 0 };
+
+            match level {
+                // 1GB and 2MB pages
+                3 | 2 if entry & PTE_LARGE != 0 => self.mark(frame, 1 << (9 * (level - 1)), owner | exec_flag),
+                1 => self.mark(frame, 1, owner | exec_flag),
+                _ => self.walk(frame, level - 1, Some(owner), executable),
+            }
+        }
+    }
+}
+
+/// Classify every page of the image
+pub fn compute_stats(img: &MemoryImage, progress: &ProgressBar) -> MemoryStats {
+    let total_pages = img.size().div_ceil(PAGE_SIZE);
+    let mut marks = vec![0u8; total_pages];
+    let mut stats = MemoryStats { total_pages: total_pages as u64, ..Default::default() };
+
+    // Mappings from the kernel DTB, or the captured ranges of a user-mode dump
+    if let Some(user) = &img.info.user {
+        for region in &user.regions {
+            let first = region.offset as usize / PAGE_SIZE;
+            let last = (region.offset + region.size).div_ceil(PAGE_SIZE as u64) as usize;
+            marks[first..last.min(total_pages)].iter_mut().for_each(|m| *m |= PROCESS);
+        }
+        stats.mappings_known = true;
+    } else if let (Some(dtb), Architecture::X86_64) = (img.info.dtb, img.info.arch) {
+        Walker { img, marks: &mut marks }.walk(dtb & PTE_FRAME_MASK, 4, None, true);
+        stats.mappings_known = true;
+    }
+
+    progress.set_length(total_pages as u64);
+    progress.set_message("Classifying physical pages");
+    let max_frame = img.size() as u64;
+
+    for (page, mark) in marks.iter_mut().enumerate() {
+        if page % 0x1000 == 0 {
+            progress.set_position(page as u64);
+        }
+        let data = img.get_bytes(page * PAGE_SIZE, PAGE_SIZE);
+        let is_zero = data.is_none_or(|d| d.iter().all(|&b| b == 0));
+        if *mark & PAGE_TABLE == 0 && !is_zero && img.info.user.is_none() {
+            if let Some(data) = data.filter(|d| d.len() == PAGE_SIZE) {
+                if looks_like_page_table(data, max_frame) {
+                    *mark |= PAGE_TABLE;
+                }
+            }
+        }
+
+        if *mark & EXECUTABLE != 0 {
+            stats.executable_pages += 1;
+        }
+        // Each page is counted once, in order of how specific its classification is
+        if *mark & PAGE_TABLE != 0 {
+            stats.page_table_pages += 1;
+        } else if is_zero {
+            stats.zero_pages += 1;
+        } else if *mark & KERNEL != 0 {
+            stats.kernel_pages += 1;
+        } else if *mark & PROCESS != 0 {
+            stats.process_pages += 1;
+        } else {
+            stats.unidentified_pages += 1;
+        }
+    }
+
+    progress.finish_with_message(format!("Classified {} pages", total_pages));
+    stats
+}
+
+/// Print the composition report for a dump
+pub fn print_stats(dump_path: PathBuf, dtb: Option<u64>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    if let Some(dtb) = dtb {
+        memory_image.set_cr3(dtb);
+    }
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let stats = compute_stats(&memory_image, &progress);
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"Category", bFg->"Pages", bFg->"MB", bFg->"Share"]);
+    let rows = [
+        ("Zero", stats.zero_pages),
+        ("Page tables", stats.page_table_pages),
+        ("Kernel", stats.kernel_pages),
+        ("Process", stats.process_pages),
+        ("Unidentified", stats.unidentified_pages),
+        ("Executable (any)", stats.executable_pages),
+    ];
+    for (name, pages) in rows {
+        let share = if stats.total_pages == 0 { 0.0 } else { pages as f64 * 100.0 / stats.total_pages as f64 };
+        table.add_row(row![
+            name,
+            Fy->pages,
+            format!("{:.1}", (pages * PAGE_SIZE as u64) as f64 / (1024.0 * 1024.0)),
+            format!("{:.1}%", share)
+        ]);
+    }
+
+    println!("{} {} pages ({} bytes)",
+        "Physical memory:".bright_green(),
+        stats.total_pages.to_string().bright_yellow(),
+        memory_image.size().to_string().bright_yellow()
+    );
+    table.printstd();
+
+    if !stats.mappings_known {
+        println!("\n{}", "No DTB available: kernel/process/executable pages are counted as unidentified (use --dtb)".bright_red());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+//! Kernel symbol (PDB) lookup for exact structure offsets
+//!
+//! The CodeView record in ntoskrnl's debug directory names the PDB the
+//! kernel was built with (file name, GUID and age). That PDB is fetched from
+//! a symbol server into a local cache laid out like a Microsoft symbol store
+//! (`<cache>/<name>/<GUID><AGE>/<name>`), and its type information supplies
+//! the EPROCESS/ETHREAD layout of the dump's exact build.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::paging::MemoryImage;
+
+/// Microsoft public symbol server
+pub const DEFAULT_SYMBOL_SERVER: &str = "https://msdl.microsoft.com/download/symbols";
+
+/// Structures whose layout is read from the PDB
+pub const KERNEL_STRUCTS: &[&str] = &[
+    "_EPROCESS", "_KPROCESS", "_ETHREAD", "_KTHREAD", "_TOKEN", "_RTL_BALANCED_NODE",
+    "_MMVAD_SHORT", "_MMVAD", "_SUBSECTION", "_CONTROL_AREA", "_FILE_OBJECT",
+];
+
+// PE header offsets
+const PE_DEBUG_DIRECTORY: usize = 6;
+const DEBUG_TYPE_CODEVIEW: u32 = 2;
+
+/// Identity of a PDB as recorded in a CodeView RSDS record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdbId {
+    pub name: String,
+    pub guid: [u8; 16],
+    pub age: u32,
+}
+
+impl PdbId {
+    /// Parse an `RSDS` CodeView record
+    pub fn from_codeview(record: &[u8]) -> Option<Self> {
+        if record.len() < 25 || &record[..4] != b"RSDS" {
+            return None;
+        }
+        let name = &record[24..];
+        let end = name.iter().position(|&b| b == 0)?;
+        let name = std::str::from_utf8(&name[..end]).ok()?;
+        // The record may hold a full build path; the store is keyed by file name
+        let name = name.rsplit(['\\', '/']).next()?.to_string();
+        if name.is_empty() {
+            return None;
+        }
+        Some(PdbId {
+            name,
+            guid: record[4..20].try_into().unwrap(),
+            age: u32::from_le_bytes(record[20..24].try_into().unwrap()),
+        })
+    }
+
+    /// `<GUID><AGE>` directory name used by symbol stores
+    pub fn signature(&self) -> String {
+        let g = &self.guid;
+        format!(
+            "{:08X}{:04X}{:04X}{}{:X}",
+            u32::from_le_bytes(g[0..4].try_into().unwrap()),
+            u16::from_le_bytes(g[4..6].try_into().unwrap()),
+            u16::from_le_bytes(g[6..8].try_into().unwrap()),
+            g[8..].iter().map(|b| format!("{:02X}", b)).collect::<String>(),
+            self.age
+        )
+    }
+
+    /// Path of the PDB relative to a symbol store root
+    pub fn store_path(&self) -> PathBuf {
+        [&self.name, &self.signature(), &self.name].iter().collect()
+    }
+}
+
+impl fmt::Display for PdbId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.signature())
+    }
+}
+
+/// Find the PDB identity of the PE image mapped at virtual address `base`
+pub fn find_pdb_id(img: &MemoryImage, base: u64) -> Option<PdbId> {
+    let dos = img.read_virt(base, 0x40)?;
+    if &dos[..2] != b"MZ" {
+        return None;
+    }
+    let nt = base + u32::from_le_bytes(dos[0x3C..0x40].try_into().unwrap()) as u64;
+    let headers = img.read_virt(nt, 0x108)?;
+    if &headers[..4] != b"PE\0\0" {
+        return None;
+    }
+    // Data directories follow the optional header; PE32+ only
+    let optional = 0x18;
+    if u16::from_le_bytes(headers[optional..optional + 2].try_into().unwrap()) != 0x20B {
+        return None;
+    }
+    let dir = optional + 0x70 + PE_DEBUG_DIRECTORY * 8;
+    let rva = u32::from_le_bytes(headers[dir..dir + 4].try_into().unwrap()) as u64;
+    let size = u32::from_le_bytes(headers[dir + 4..dir + 8].try_into().unwrap()) as usize;
+    if rva == 0 || size == 0 {
+        return None;
+    }
+
+    // IMAGE_DEBUG_DIRECTORY entries are 28 bytes each
+    let entries = img.read_virt(base + rva, size.min(28 * 16))?;
+    entries.chunks_exact(28).find_map(|entry| {
+        let kind = u32::from_le_bytes(entry[12..16].try_into().unwrap());
+        let data_size = u32::from_le_bytes(entry[16..20].try_into().unwrap()) as usize;
+        let data_rva = u32::from_le_bytes(entry[20..24].try_into().unwrap()) as u64;
+        if kind != DEBUG_TYPE_CODEVIEW || data_rva == 0 {
+            return None;
+        }
+        PdbId::from_codeview(&img.read_virt(base + data_rva, data_size.min(0x200))?)
+    })
+}
+
+/// Layout of one structure from the PDB
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StructLayout {
+    pub size: u64,
+    pub fields: BTreeMap<String, u64>,
+}
+
+/// Structure layouts read from a kernel PDB
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KernelTypes {
+    pub structs: BTreeMap<String, StructLayout>,
+}
+
+impl KernelTypes {
+    pub fn size(&self, name: &str) -> Option<usize> {
+        self.structs.get(name).map(|s| s.size as usize)
+    }
+
+    pub fn offset(&self, name: &str, field: &str) -> Option<usize> {
+        self.structs.get(name)?.fields.get(field).map(|&o| o as usize)
+    }
+
+    /// Read the layouts of `KERNEL_STRUCTS` from a PDB file
+    #[cfg(feature = "symbols")]
+    pub fn from_pdb(path: &Path) -> Result<Self> {
+        use pdb::{FallibleIterator, TypeData};
+
+        let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut pdb = pdb::PDB::open(file).with_context(|| format!("Failed to parse {}", path.display()))?;
+        let type_information = pdb.type_information()?;
+        let mut finder = type_information.finder();
+        let mut types = KernelTypes::default();
+
+        let mut iter = type_information.iter();
+        while let Some(item) = iter.next()? {
+            finder.update(&iter);
+            let Ok(TypeData::Class(class)) = item.parse() else { continue };
+            let name = class.name.to_string();
+            if class.properties.forward_reference() || !KERNEL_STRUCTS.contains(&name.as_ref()) {
+                continue;
+            }
+
+            let mut layout = StructLayout { size: class.size, fields: BTreeMap::new() };
+            let mut next = class.fields;
+            while let Some(index) = next {
+                let TypeData::FieldList(list) = finder.find(index)?.parse()? else { break };
+                for field in list.fields {
+                    if let TypeData::Member(member) = field {
+                        layout.fields.insert(member.name.to_string().into_owned(), member.offset);
+                    }
+                }
+                next = list.continuation;
+            }
+            types.structs.insert(name.into_owned(), layout);
+        }
+
+        if !types.structs.contains_key("_EPROCESS") {
+            bail!("{} has no _EPROCESS type information", path.display());
+        }
+        Ok(types)
+    }
+
+    #[cfg(not(feature = "symbols"))]
+    pub fn from_pdb(path: &Path) -> Result<Self> {
+        bail!("Cannot read {}: rmf was built without the `symbols` feature", path.display())
+    }
+}
+
+/// Local symbol cache, optionally backed by a symbol server
+#[derive(Debug, Clone)]
+pub struct SymbolStore {
+    pub cache_dir: PathBuf,
+    pub server: String,
+    /// Never download; only use PDBs already in the cache
+    pub offline: bool,
+}
+
+impl SymbolStore {
+    pub fn new(cache_dir: PathBuf) -> Self {
+        Self { cache_dir, server: DEFAULT_SYMBOL_SERVER.to_string(), offline: false }
+    }
+
+    pub fn server(mut self, server: impl Into<String>) -> Self {
+        self.server = server.into();
+        self
+    }
+
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Return the cached PDB, downloading it first when allowed
+    pub fn locate(&self, id: &PdbId) -> Result<PathBuf> {
+        let path = self.cache_dir.join(id.store_path());
+        if path.is_file() {
+            return Ok(path);
+        }
+        if self.offline {
+            bail!("{} is not in the symbol cache {} (offline)", id, self.cache_dir.display());
+        }
+        self.download(id, &path)?;
+        Ok(path)
+    }
+
+    #[cfg(feature = "symbols")]
+    fn download(&self, id: &PdbId, path: &Path) -> Result<()> {
+        let url = format!("{}/{}", self.server.trim_end_matches('/'),
+            id.store_path().to_string_lossy().replace('\\', "/"));
+        let response = ureq::get(&url)
+            .set("User-Agent", "Microsoft-Symbol-Server/10.0.0.0")
+            .call()
+            .with_context(|| format!("Failed to download {}", url))?;
+
+        // Write to a temporary file first so an interrupted download is never cached
+        let dir = path.parent().context("Invalid symbol cache path")?;
+        std::fs::create_dir_all(dir)?;
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        std::io::copy(&mut response.into_reader(), &mut tmp)?;
+        tmp.persist(path)?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "symbols"))]
+    fn download(&self, id: &PdbId, _path: &Path) -> Result<()> {
+        bail!("Cannot download {}: rmf was built without the `symbols` feature", id)
+    }
+}
+
+/// Identify the kernel PDB, fetch it and read the kernel structure layouts
+pub fn load_kernel_types(img: &MemoryImage, kernel_base: u64, store: &SymbolStore) -> Result<(PdbId, KernelTypes)> {
+    let id = find_pdb_id(img, kernel_base)
+        .with_context(|| format!("No CodeView record in the kernel image at 0x{:X}", kernel_base))?;
+    let path = store.locate(&id)?;
+    let types = KernelTypes::from_pdb(&path)?;
+    Ok((id, types))
+}
+
+/// Print the kernel PDB identity and the offsets read from it
+pub fn report_symbols(dump_path: PathBuf, dtb: u64, store: SymbolStore) -> Result<()> {
+    let mut memory_image = crate::loader::load_memory_image(&dump_path)?;
+    memory_image.set_cr3(dtb);
+
+    let os = crate::kdbg::OsContext::find(&memory_image, &indicatif::ProgressBar::hidden())
+        .context("No KDBG block found; cannot locate the kernel image")?;
+    let (id, types) = load_kernel_types(&memory_image, os.kernel_base, &store)?;
+
+    println!("{} {}", "Kernel PDB:".bright_green(), id.to_string().bright_yellow());
+    for name in KERNEL_STRUCTS {
+        let Some(layout) = types.structs.get(*name) else { continue };
+        println!("\n{} (0x{:X} bytes)", name.bright_cyan(), layout.size);
+        let mut fields: Vec<_> = layout.fields.iter().collect();
+        fields.sort_by_key(|(name, &offset)| (offset, name.to_string()));
+        for (field, offset) in fields {
+            println!("  0x{:04X} {}", offset, field);
+        }
+    }
+
+    Ok(())
+}
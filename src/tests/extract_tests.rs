@@ -0,0 +1,130 @@
+use anyhow::bail;
+use indicatif::ProgressBar;
+use sha2::{Digest, Sha256};
+use tempfile::tempdir;
+
+use crate::dumpfiles::{dump_file_objects, scan_file_objects, FileSource, MANIFEST_FILE};
+use crate::extract::{ChunkManifest, ChunkedExtractor};
+use crate::paging::MemoryImage;
+use super::format_tests::{put_u32, put_u64};
+
+fn hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_chunked_extraction_resumes_after_interruption() -> Result<(), Box<dyn std::error::Error>> {
+    let source: Vec<u8> = (0..10_000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let test_dir = tempdir()?;
+    let output = test_dir.path().join("region.bin");
+    let extractor = ChunkedExtractor::new(&output).chunk_size(1024);
+
+    // First run dies while reading the fourth chunk
+    let result = extractor.extract(source.len() as u64, &ProgressBar::hidden(), |offset, len| {
+        if offset >= 3072 {
+            bail!("share disconnected");
+        }
+        Ok(source[offset as usize..offset as usize + len].to_vec())
+    });
+    assert!(result.is_err());
+    let manifest: ChunkManifest = serde_json::from_slice(&std::fs::read(extractor.partial_manifest())?)?;
+    assert_eq!(manifest.chunks.len(), 3);
+
+    // Damage the second chunk on disk; it and everything after must be rewritten
+    let mut partial = std::fs::read(&output)?;
+    partial[1500] ^= 0xFF;
+    std::fs::write(&output, &partial)?;
+
+    let mut reads = Vec::new();
+    let summary = extractor.extract(source.len() as u64, &ProgressBar::hidden(), |offset, len| {
+        reads.push(offset);
+        Ok(source[offset as usize..offset as usize + len].to_vec())
+    })?;
+
+    assert_eq!(summary.chunks_resumed, 1);
+    assert_eq!(summary.chunks_written, 9);
+    assert_eq!(reads.first(), Some(&1024));
+    assert_eq!(summary.sha256, hex(&source));
+    assert_eq!(std::fs::read(&output)?, source);
+    assert!(!extractor.partial_manifest().exists());
+
+    let done: ChunkManifest = serde_json::from_slice(&std::fs::read(extractor.final_manifest())?)?;
+    assert_eq!(done.chunks.len(), 10);
+    assert_eq!(done.chunks[9], hex(&source[9216..]));
+
+    Ok(())
+}
+
+#[test]
+fn test_dump_files_rebuilds_data_section_and_cache_views() -> Result<(), Box<dyn std::error::Error>> {
+    let kernel = 0xFFFF_8000_0000_0000u64;
+    let mut data = vec![0u8; 256 * 1024];
+    put_u64(&mut data, 0x1000 + 256 * 8, 0x2000 | 0x1);
+    put_u64(&mut data, 0x2000, 0x80 | 0x1);
+
+    let put_file = |data: &mut [u8], header: usize, name: (usize, &str), section_pointers: u64| {
+        data[header + 4..header + 8].copy_from_slice(b"File");
+        let body = header + 0x40;
+        data[body..body + 2].copy_from_slice(&5u16.to_le_bytes());
+        data[body + 2..body + 4].copy_from_slice(&0xD8u16.to_le_bytes());
+        put_u64(data, body + 0x28, section_pointers);
+        let wide: Vec<u8> = name.1.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+        data[name.0..name.0 + wide.len()].copy_from_slice(&wide);
+        data[body + 0x58..body + 0x5A].copy_from_slice(&(wide.len() as u16).to_le_bytes());
+        put_u64(data, body + 0x60, kernel + name.0 as u64);
+    };
+    put_file(&mut data, 0x8000, (0x9100, "\\Users\\bob\\secret.docx"), kernel + 0x9000);
+    put_file(&mut data, 0x8400, (0x9200, "\\Windows\\notepad.exe"), 0);
+
+    // Data section: one subsection of three prototype PTEs; the middle page
+    // is paged out and the last is on the standby list
+    put_u64(&mut data, 0x9000, kernel + 0xA000);
+    put_u64(&mut data, 0xA080, kernel + 0xA000);
+    put_u64(&mut data, 0xA088, kernel + 0xB000);
+    put_u32(&mut data, 0xA0AC, 3);
+    put_u64(&mut data, 0xB000, 0x10000 | 1);
+    put_u64(&mut data, 0xB010, 0x12000 | 1 << 11);
+    data[0x10000..0x11000].fill(b'A');
+    data[0x12000..0x13000].fill(b'C');
+
+    // Shared cache map with one VACB viewing the first 0x2800 bytes
+    put_u64(&mut data, 0x9008, kernel + 0xC000);
+    data[0xC000..0xC002].copy_from_slice(&0x2FFu16.to_le_bytes());
+    put_u64(&mut data, 0xC008, 0x2800);
+    put_u64(&mut data, 0xC018, 0x40000);
+    put_u64(&mut data, 0xC068, kernel + 0xC100);
+    put_u64(&mut data, 0xC100, kernel + 0xC200);
+    put_u64(&mut data, 0xC200, kernel + 0x14000);
+    put_u64(&mut data, 0xC208, kernel + 0xC000);
+    put_u64(&mut data, 0xC210, 1);
+    for (i, byte) in data[0x14000..0x16800].iter_mut().enumerate() {
+        *byte = (i % 251) as u8;
+    }
+
+    let mut img = MemoryImage::new(data.clone());
+    img.set_cr3(0x1000);
+    let names: Vec<String> = scan_file_objects(&img, &ProgressBar::hidden()).into_iter().map(|f| f.name).collect();
+    assert_eq!(names, vec!["\\Users\\bob\\secret.docx", "\\Windows\\notepad.exe"]);
+
+    let test_dir = tempdir()?;
+    let output = test_dir.path().join("files");
+    let pattern = regex::Regex::new("(?i)SECRET")?;
+    let manifest = dump_file_objects(&img, Some(&pattern), &output, &ProgressBar::hidden())?;
+    let summary: Vec<_> = manifest.iter().map(|e| (e.source, e.size, e.resident_pages, e.total_pages)).collect();
+    assert_eq!(summary, vec![(FileSource::DataSection, 0x3000, 2, 3), (FileSource::SharedCacheMap, 0x2800, 3, 3)]);
+    assert_eq!(manifest[0].path.file_name().unwrap(), "file.0x8040.secret.docx.dat");
+
+    let section = std::fs::read(&manifest[0].path)?;
+    assert!(section[..0x1000].iter().all(|&b| b == b'A'));
+    assert!(section[0x1000..0x2000].iter().all(|&b| b == 0));
+    assert!(section[0x2000..].iter().all(|&b| b == b'C'));
+    let cached = std::fs::read(&manifest[1].path)?;
+    assert_eq!(cached, data[0x14000..0x16800]);
+    assert_eq!(manifest[1].sha256, hex(&cached));
+
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(output.join(MANIFEST_FILE))?)?;
+    assert_eq!(written[1]["source"], "shared_cache_map");
+    assert_eq!(written[0]["name"], "\\Users\\bob\\secret.docx");
+
+    Ok(())
+}
@@ -0,0 +1,503 @@
+use std::{fs::File, io::Write, path::PathBuf};
+use tempfile::tempdir;
+
+use crate::loader::{load_memory_image, load_segmented_image};
+use crate::aslr::{AslrFlag, AslrLayout, RegionKind};
+use crate::paging::ImageFormat;
+use crate::evidence;
+use crate::case::{case_report, Case};
+use crate::formats::acquisition::{self, AcquisitionTime, TimeAnchor};
+
+// Write raw bytes to a temporary dump file
+fn write_dump(name: &str, data: &[u8]) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let test_dir = tempdir()?;
+    let test_file = test_dir.path().join(name);
+
+    let mut file = File::create(&test_file)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+
+    // Keep directory from being deleted
+    std::mem::forget(test_dir);
+
+    Ok(test_file)
+}
+
+fn put_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub(super) fn put_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+pub(super) fn put_u64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+// Append a note in the DBGFCoreWrite layout (namesz without NUL, 8-byte padding)
+fn push_vbox_note(notes: &mut Vec<u8>, name: &str, note_type: u32, desc: &[u8]) {
+    notes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&note_type.to_le_bytes());
+    let mut padded_name = name.as_bytes().to_vec();
+    padded_name.resize((name.len() + 1 + 7) & !7, 0);
+    notes.extend_from_slice(&padded_name);
+    notes.extend_from_slice(desc);
+}
+
+// Build a VirtualBox-style ELF core with one CPU and two physical runs
+fn build_vbox_core() -> Vec<u8> {
+    let mut notes = Vec::new();
+    let mut descriptor = vec![0u8; 24];
+    put_u32(&mut descriptor, 0, 0xc01a_c0de);
+    put_u32(&mut descriptor, 4, 1);
+    put_u32(&mut descriptor, 12, (7 << 24) | 12);
+    put_u32(&mut descriptor, 20, 1);
+    push_vbox_note(&mut notes, "VBCORE", 0xb00, &descriptor);
+
+    let mut cpu = vec![0u8; 0x200];
+    put_u64(&mut cpu, 0x80, 0xFFFF_F800_0000_1000);
+    put_u64(&mut cpu, 0x140, 0x1AB000);
+    push_vbox_note(&mut notes, "VBCPU", 0xb01, &cpu);
+
+    let phoff = 0x40;
+    let note_off = 0x1000;
+    let load0_off = 0x2000;
+    let load1_off = 0x3000;
+
+    let mut data = vec![0u8; 0x4000];
+    data[..4].copy_from_slice(b"\x7fELF");
+    data[4] = 2; // ELFCLASS64
+    data[5] = 1; // little endian
+    put_u16(&mut data, 0x10, 4); // ET_CORE
+    put_u64(&mut data, 0x20, phoff as u64);
+    put_u16(&mut data, 0x36, 0x38);
+    put_u16(&mut data, 0x38, 3);
+
+    // PT_NOTE, then two PT_LOAD runs with a hole between them
+    let headers: [(u32, u64, u64, u64); 3] = [
+        (4, note_off as u64, 0, notes.len() as u64),
+        (1, load0_off as u64, 0x0, 0x1000),
+        (1, load1_off as u64, 0x10_0000, 0x1000),
+    ];
+    for (i, (p_type, offset, paddr, size)) in headers.iter().enumerate() {
+        let ph = phoff + i * 0x38;
+        put_u32(&mut data, ph, *p_type);
+        put_u64(&mut data, ph + 0x08, *offset);
+        put_u64(&mut data, ph + 0x18, *paddr);
+        put_u64(&mut data, ph + 0x20, *size);
+    }
+
+    data[note_off..note_off + notes.len()].copy_from_slice(&notes);
+    data[load0_off..load0_off + 6].copy_from_slice(b"LOWMEM");
+    data[load1_off..load1_off + 7].copy_from_slice(b"HIGHMEM");
+    data
+}
+
+#[test]
+fn test_vbox_elf_core_run_map() -> Result<(), Box<dyn std::error::Error>> {
+    let dump = write_dump("vbox.elf", &build_vbox_core())?;
+    let img = load_memory_image(&dump)?;
+
+    assert_eq!(img.info.format, ImageFormat::VBoxElf);
+    assert_eq!(img.runs().len(), 2, "Expected two physical runs");
+    assert_eq!(img.size(), 0x10_1000, "Size should span to the end of the last run");
+
+    // Physical reads go through the run map
+    assert_eq!(img.get_bytes(0, 6), Some(&b"LOWMEM"[..]));
+    assert_eq!(img.get_bytes(0x10_0000, 7), Some(&b"HIGHMEM"[..]));
+
+    // Holes between runs are unreadable
+    assert!(img.get_bytes(0x8000, 4).is_none());
+    assert!(img.read_u64(0xFFC).is_none(), "Reads must not cross run boundaries");
+
+    // CR3 from the first CPU note becomes the default DTB
+    assert_eq!(img.info.cpus.len(), 1);
+    assert_eq!(img.info.dtb, Some(0x1AB000));
+
+    Ok(())
+}
+
+#[test]
+fn test_elf_core_rejects_overflowing_headers() -> Result<(), Box<dyn std::error::Error>> {
+    // A PT_LOAD whose offset plus size wraps around
+    let mut data = build_vbox_core();
+    put_u64(&mut data, 0x40 + 0x38 + 0x08, u64::MAX - 0xFFF);
+    let error = load_memory_image(&write_dump("wrap.elf", &data)?).unwrap_err();
+    assert!(format!("{:#}", error).contains("Program header 1 describes a segment past the end"), "{:#}", error);
+
+    // A program header table offset near the top of the address space
+    let mut data = build_vbox_core();
+    put_u64(&mut data, 0x20, u64::MAX - 0x10);
+    let error = load_memory_image(&write_dump("phoff.elf", &data)?).unwrap_err();
+    assert!(format!("{:#}", error).contains("Program header 0 lies outside the file"), "{:#}", error);
+
+    Ok(())
+}
+
+#[test]
+fn test_compressed_dumps_are_inflated() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write as _;
+    use crate::formats::compressed::Compression;
+
+    let mut raw = vec![0u8; 64 * 1024];
+    raw[0x1234..0x1234 + 9].copy_from_slice(b"INFLATED!");
+
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    gzip.write_all(&raw)?;
+    let gzip = gzip.finish()?;
+
+    let zstd = zstd::encode_all(&raw[..], 3)?;
+
+    let mut lz4 = lz4_flex::frame::FrameEncoder::new(Vec::new());
+    lz4.write_all(&raw)?;
+    let lz4 = lz4.finish()?;
+
+    for (name, data, kind) in [
+        ("dump.raw.gz", gzip, Compression::Gzip),
+        ("dump.raw.zst", zstd, Compression::Zstd),
+        ("dump.raw.lz4", lz4, Compression::Lz4),
+    ] {
+        let dump = write_dump(name, &data)?;
+        let img = load_memory_image(&dump)?;
+
+        assert_eq!(img.info.compression, Some(kind), "{} not detected", name);
+        assert_eq!(img.size(), raw.len(), "{} decompressed to the wrong size", name);
+        assert_eq!(img.get_bytes(0x1234, 9), Some(&b"INFLATED!"[..]));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_segmented_raw_dump() -> Result<(), Box<dyn std::error::Error>> {
+    let test_dir = tempdir()?;
+    // Write out of order and with a stray file to check the pattern and ordering
+    for (name, fill, len) in [("dump.002", 0xBBu8, 0x3000), ("dump.001", 0xAA, 0x2000), ("dump.003", 0xCC, 0x1000)] {
+        std::fs::write(test_dir.path().join(name), vec![fill; len])?;
+    }
+    std::fs::write(test_dir.path().join("dump.txt"), b"notes")?;
+
+    let pattern = test_dir.path().join("dump.0*");
+    let img = load_segmented_image(pattern.to_str().unwrap())?;
+
+    assert_eq!(img.info.format, ImageFormat::Segmented);
+    assert_eq!(img.size(), 0x6000);
+    assert_eq!(img.runs().len(), 3);
+    assert_eq!(img.get_bytes(0x1FFF, 1), Some(&[0xAA][..]));
+    assert_eq!(img.get_bytes(0x2000, 1), Some(&[0xBB][..]));
+    assert_eq!(img.get_bytes(0x5000, 1), Some(&[0xCC][..]));
+    assert!(img.get_bytes(0x6000, 1).is_none());
+
+    assert!(load_segmented_image(test_dir.path().join("none.*").to_str().unwrap()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_segments_sort_numerically() -> Result<(), Box<dyn std::error::Error>> {
+    let test_dir = tempdir()?;
+    // Unpadded numbers: a lexical sort would put dump.10 between dump.1 and dump.2
+    for n in 1..=10u8 {
+        std::fs::write(test_dir.path().join(format!("dump.{}", n)), vec![n; 0x1000])?;
+    }
+    let pattern = test_dir.path().join("dump.*");
+    let img = load_segmented_image(pattern.to_str().unwrap())?;
+    assert_eq!(img.size(), 0xA000);
+    for n in 1..=10u8 {
+        assert_eq!(img.get_bytes((n as usize - 1) * 0x1000, 1), Some(&[n][..]));
+    }
+    assert_eq!(img.info.compression, None);
+
+    // dump.01 and dump.1 would both be segment one
+    std::fs::write(test_dir.path().join("dump.01"), vec![0xEE; 0x1000])?;
+    let error = load_segmented_image(pattern.to_str().unwrap()).unwrap_err();
+    assert!(error.to_string().contains("have the same number"), "{}", error);
+
+    Ok(())
+}
+
+#[test]
+fn test_compressed_segments_record_compression() -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write as _;
+    use crate::formats::compressed::Compression;
+
+    let test_dir = tempdir()?;
+    for (name, fill) in [("dump.001.gz", 0xAAu8), ("dump.002.gz", 0xBB)] {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(&vec![fill; 0x1000])?;
+        std::fs::write(test_dir.path().join(name), gzip.finish()?)?;
+    }
+    let img = load_segmented_image(test_dir.path().join("dump.0*").to_str().unwrap())?;
+    assert_eq!(img.size(), 0x2000);
+    assert_eq!(img.get_bytes(0x1000, 1), Some(&[0xBB][..]));
+    assert_eq!(img.info.compression, Some(Compression::Gzip));
+
+    Ok(())
+}
+
+#[test]
+fn test_minidump_user_space() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 0x2000];
+    data[..4].copy_from_slice(b"MDMP");
+    put_u32(&mut data, 4, 0xA793);
+    put_u32(&mut data, 8, 3);
+    put_u32(&mut data, 12, 0x20);
+
+    // Stream directory: module list, thread list, Memory64 list
+    for (i, (stream_type, rva)) in [(4u32, 0x80u32), (3, 0x180), (9, 0x700)].into_iter().enumerate() {
+        put_u32(&mut data, 0x20 + i * 12, stream_type);
+        put_u32(&mut data, 0x20 + i * 12 + 8, rva);
+    }
+
+    // One module named C:\app.exe
+    put_u32(&mut data, 0x80, 1);
+    put_u64(&mut data, 0x84, 0x7FF6_0000_0000);
+    put_u32(&mut data, 0x84 + 8, 0x10000);
+    put_u32(&mut data, 0x84 + 20, 0x100);
+    let name: Vec<u8> = "C:\\app.exe".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    put_u32(&mut data, 0x100, name.len() as u32);
+    data[0x104..0x104 + name.len()].copy_from_slice(&name);
+
+    // One thread with an AMD64 context
+    put_u32(&mut data, 0x180, 1);
+    put_u32(&mut data, 0x184, 0x1234);
+    put_u64(&mut data, 0x184 + 16, 0x3F_0000_0000);
+    put_u32(&mut data, 0x184 + 40, 0x4D0);
+    put_u32(&mut data, 0x184 + 44, 0x200);
+    put_u64(&mut data, 0x200 + 0x98, 0x1000_0400);
+    put_u64(&mut data, 0x200 + 0xF8, 0x7FF6_0000_1010);
+
+    // Two ranges stored back to back from 0x800, listed out of VA order
+    put_u64(&mut data, 0x700, 2);
+    put_u64(&mut data, 0x708, 0x800);
+    put_u64(&mut data, 0x710, 0x7FF6_0000_1000);
+    put_u64(&mut data, 0x718, 0x1000);
+    put_u64(&mut data, 0x720, 0x1000_0000);
+    put_u64(&mut data, 0x728, 0x800);
+    data[0x810..0x818].copy_from_slice(b"CODEHERE");
+    data[0x1800..0x1808].copy_from_slice(b"HEAPDATA");
+
+    let dump = write_dump("process.dmp", &data)?;
+    let img = load_memory_image(&dump)?;
+    assert_eq!(img.info.format, ImageFormat::Minidump);
+    assert_eq!(img.size(), 0x1800);
+
+    let user = img.info.user.as_ref().unwrap();
+    assert_eq!(user.modules[0].name, "C:\\app.exe");
+    assert_eq!(user.threads[0].id, 0x1234);
+    assert_eq!(user.module_at(user.threads[0].rip).unwrap().base, 0x7FF6_0000_0000);
+
+    // The lower VA range is packed first
+    assert_eq!(img.get_bytes(0, 8), Some(&b"HEAPDATA"[..]));
+    let code = img.virt_to_phys(0x7FF6_0000_1010).unwrap();
+    assert_eq!(img.get_bytes(code as usize, 8), Some(&b"CODEHERE"[..]));
+    assert_eq!(user.offset_to_va(code), Some(0x7FF6_0000_1010));
+    assert!(img.virt_to_phys(0x2000_0000).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_minidump_rejects_overflowing_ranges() -> Result<(), Box<dyn std::error::Error>> {
+    let build = || {
+        let data = build_minidump(&[], &[], &[(0x1000, vec![0xAA; 0x100]), (0x2000, vec![0xBB; 0x100])]);
+        let memory_list = u32::from_le_bytes(data[0x20 + 2 * 12 + 8..0x20 + 2 * 12 + 12].try_into().unwrap()) as usize;
+        (data, memory_list)
+    };
+
+    // A range whose end wraps the address space
+    let (mut data, memory_list) = build();
+    put_u64(&mut data, memory_list + 24, u64::MAX - 0x10);
+    let error = load_memory_image(&write_dump("va_wrap.dmp", &data)?).unwrap_err();
+    assert!(format!("{:#}", error).contains("Memory descriptor 0 describes a range past the end"), "{:#}", error);
+
+    // A size that wraps the running file offset of the ranges after it
+    let (mut data, memory_list) = build();
+    put_u64(&mut data, memory_list + 16, 0);
+    put_u64(&mut data, memory_list + 24, u64::MAX - 0x10);
+    let error = load_memory_image(&write_dump("offset_wrap.dmp", &data)?).unwrap_err();
+    assert!(format!("{:#}", error).contains("Memory descriptor 0 runs past the end"), "{:#}", error);
+
+    // Address lookups on a region ending at the top of the address space
+    let user = crate::usermode::UserSpace {
+        regions: vec![crate::usermode::UserRegion { va: u64::MAX - 0xFFF, offset: 0x1000, size: 0x2000 }],
+        ..Default::default()
+    };
+    assert_eq!(user.va_to_offset(u64::MAX), Some(0x1FFF));
+    assert_eq!(user.offset_to_va(0x2FFF), None);
+
+    Ok(())
+}
+
+// Assemble a minidump with module, thread (AMD64 context) and Memory64 streams
+pub(super) fn build_minidump(modules: &[(u64, u32, &str)], threads: &[(u32, u64)], ranges: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    let mut data = vec![0u8; 0x100];
+    data[..4].copy_from_slice(b"MDMP");
+    put_u32(&mut data, 8, 3);
+    put_u32(&mut data, 12, 0x20);
+
+    let module_list = data.len();
+    data.resize(module_list + 4 + modules.len() * 108, 0);
+    put_u32(&mut data, module_list, modules.len() as u32);
+    for (i, &(base, size, name)) in modules.iter().enumerate() {
+        let entry = module_list + 4 + i * 108;
+        let name_rva = data.len();
+        let utf16: Vec<u8> = name.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        data.extend_from_slice(&(utf16.len() as u32).to_le_bytes());
+        data.extend_from_slice(&utf16);
+        put_u64(&mut data, entry, base);
+        put_u32(&mut data, entry + 8, size);
+        put_u32(&mut data, entry + 20, name_rva as u32);
+    }
+
+    let thread_list = data.len();
+    data.resize(thread_list + 4 + threads.len() * 48, 0);
+    put_u32(&mut data, thread_list, threads.len() as u32);
+    for (i, &(id, teb)) in threads.iter().enumerate() {
+        let entry = thread_list + 4 + i * 48;
+        put_u32(&mut data, entry, id);
+        put_u64(&mut data, entry + 16, teb);
+    }
+
+    let memory_list = data.len();
+    data.resize(memory_list + 16 + ranges.len() * 16, 0);
+    put_u64(&mut data, memory_list, ranges.len() as u64);
+    let base_rva = data.len() as u64;
+    put_u64(&mut data, memory_list + 8, base_rva);
+    for (i, (va, bytes)) in ranges.iter().enumerate() {
+        put_u64(&mut data, memory_list + 16 + i * 16, *va);
+        put_u64(&mut data, memory_list + 24 + i * 16, bytes.len() as u64);
+    }
+    for (_, bytes) in ranges {
+        data.extend_from_slice(bytes);
+    }
+
+    for (i, (stream_type, rva)) in [(4u32, module_list), (3, thread_list), (9, memory_list)].into_iter().enumerate() {
+        put_u32(&mut data, 0x20 + i * 12, stream_type);
+        put_u32(&mut data, 0x20 + i * 12 + 8, rva as u32);
+    }
+    data
+}
+
+#[test]
+fn test_aslr_layout_flags() -> Result<(), Box<dyn std::error::Error>> {
+    let (teb, peb, heaps) = (0x3F_0000_0000u64, 0x3F_0000_2000u64, 0x3F_0000_3000u64);
+
+    // TEB: stack 0x9A_0000..0xA0_0000, PEB pointer
+    let mut teb_page = vec![0u8; 0x1000];
+    put_u64(&mut teb_page, 0x08, 0xA0_0000);
+    put_u64(&mut teb_page, 0x10, 0x9A_0000);
+    put_u64(&mut teb_page, 0x60, peb);
+    // PEB: image base and two heaps, the second inside the injected module
+    let mut peb_page = vec![0u8; 0x2000];
+    put_u64(&mut peb_page, 0x10, 0x1_4000_0000);
+    put_u32(&mut peb_page, 0xE8, 2);
+    put_u64(&mut peb_page, 0xF0, heaps);
+    put_u64(&mut peb_page, 0x1000, 0x2_5000_0000);
+    put_u64(&mut peb_page, 0x1008, 0x7FF7_0000_8000);
+
+    let dump = build_minidump(
+        &[
+            (0x1_4000_0000, 0x20000, "C:\\Tools\\legacy.exe"),
+            (0x7FFA_1234_0000, 0x1F_0000, "C:\\Windows\\System32\\ntdll.dll"),
+            (0x7FF7_0000_0000, 0x10000, "C:\\Users\\Public\\inject.dll"),
+        ],
+        &[(0x44, teb)],
+        &[(teb, teb_page), (peb, peb_page)],
+    );
+    let path = write_dump("legacy.dmp", &dump)?;
+    let img = load_memory_image(&path)?;
+
+    let layout = AslrLayout::from_user_dump(&img).unwrap();
+    assert_eq!(layout.name, "legacy.exe");
+    let stack = layout.regions.iter().find(|r| r.kind == RegionKind::Stack).unwrap();
+    assert_eq!((stack.base, stack.size), (0x9A_0000, 0x6_0000));
+    assert_eq!(layout.regions.iter().filter(|r| r.kind == RegionKind::Heap).count(), 2);
+
+    let flags = layout.analyze();
+    assert_eq!(flags, vec![
+        AslrFlag::NotRandomized { label: "legacy.exe".to_string(), base: 0x1_4000_0000 },
+        AslrFlag::Overlap { first: "inject.dll".to_string(), second: "heap 1".to_string() },
+    ]);
+
+    Ok(())
+}
+
+#[test]
+fn test_acquisition_metadata_from_headers() -> Result<(), Box<dyn std::error::Error>> {
+    // 2024-01-01T00:00:00Z as a FILETIME
+    const NEW_YEAR: u64 = 133_485_408_000_000_000;
+
+    let mut crash = vec![0u8; 0x2000];
+    crash[..8].copy_from_slice(b"PAGEDU64");
+    put_u32(&mut crash, 0x38, 0x7E);
+    put_u64(&mut crash, 0x40, 0xC0000005);
+    put_u64(&mut crash, 0xFA8, NEW_YEAR);
+    let metadata = acquisition::parse(&crash);
+    assert_eq!(metadata.container.as_deref(), Some("Windows crash dump (64-bit)"));
+    assert_eq!(metadata.bugcheck.as_deref(), Some("0x7E (0xC0000005, 0x0, 0x0, 0x0)"));
+    assert_eq!(metadata.captured_at(), Some(&AcquisitionTime { anchor: TimeAnchor::CrashTime, time: "2024-01-01T00:00:00Z".to_string() }));
+
+    let mut hiberfil = vec![0u8; 0x1000];
+    hiberfil[..4].copy_from_slice(b"WAKE");
+    put_u64(&mut hiberfil, 0x20, NEW_YEAR + 36_000_000_000);
+    let metadata = acquisition::parse(&hiberfil);
+    assert_eq!(metadata.container.as_deref(), Some("Windows hibernation file (resumed)"));
+    assert_eq!(metadata.captured_at().map(|t| (t.anchor, t.time.as_str())), Some((TimeAnchor::HibernationTime, "2024-01-01T01:00:00Z")));
+
+    // Raw dumps only carry the tool's marker, which the loader keeps in the image info
+    let mut raw = vec![0u8; 0x4000];
+    raw[0x200..0x20F].copy_from_slice(b"winpmem 4.0 rc1");
+    let img = load_memory_image(&write_dump("raw.mem", &raw)?)?;
+    assert_eq!(img.info.acquisition.tool.as_deref(), Some("WinPmem"));
+    assert!(img.info.acquisition.times.is_empty());
+    assert!(acquisition::parse(&[0u8; 0x100]).is_empty());
+
+    // The case manifest keeps one record per dump for timeline anchoring
+    let dir = tempdir()?;
+    let case = Case::open(&dir.path().join("case"))?;
+    case.record_dump(&PathBuf::from("host.dmp"), &acquisition::parse(&crash))?;
+    case.record_dump(&PathBuf::from("laptop.mem"), &img.info.acquisition)?;
+    let manifest = case.manifest()?;
+    assert_eq!(manifest.dumps.len(), 2);
+    assert_eq!(manifest.dumps["host.dmp"].captured_at().unwrap().time, "2024-01-01T00:00:00Z");
+    let report = case_report(&case)?;
+    assert!(report.contains("| host.dmp | Windows crash dump (64-bit) | - | 2024-01-01T00:00:00Z (Crash time) |"), "{}", report);
+    assert!(report.contains("| laptop.mem | - | WinPmem | - |"), "{}", report);
+
+    Ok(())
+}
+
+#[test]
+fn test_evidence_opened_read_only_and_changes_detected() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let path = dir.path().join("evidence.mem");
+    std::fs::write(&path, vec![0x41u8; 0x2000])?;
+
+    // Dumps are only ever handed out as read-only descriptors
+    let mut file = evidence::open_evidence(&path)?;
+    assert!(file.write_all(b"tamper").is_err());
+    assert!(evidence::is_writable(&path));
+
+    let record = evidence::EvidenceRecord::capture(&path, true)?;
+    assert_eq!(record.len, 0x2000);
+    assert_eq!(record.sha256.as_deref(), Some(evidence::file_sha256(&path)?.as_str()));
+    assert!(record.changes().is_empty());
+
+    // Same size, different contents: only the hash gives it away
+    let modified = std::fs::metadata(&path)?.modified()?;
+    std::fs::write(&path, vec![0x42u8; 0x2000])?;
+    File::options().write(true).open(&path)?.set_modified(modified)?;
+    let changes = record.changes();
+    assert_eq!(changes.len(), 1, "{:?}", changes);
+    assert!(changes[0].starts_with("SHA-256 changed"));
+
+    std::fs::write(&path, vec![0x42u8; 0x1000])?;
+    assert!(record.changes().iter().any(|c| c == "size changed from 8192 to 4096 bytes"));
+    std::fs::remove_file(&path)?;
+    assert_eq!(record.changes(), ["the file can no longer be read"]);
+
+    Ok(())
+}
@@ -1,9 +1,9 @@
 use std::{fs::File, io::Write, path::PathBuf};
 use tempfile::tempdir;
 
-use crate::paging::{MemoryImage, Architecture, PageTableType};
 use crate::arch::x86_64::VirtualAddress;
 use crate::loader::load_memory_image;
+use crate::paging::Architecture;
 
 // Create a mock memory dump with page tables
 fn create_mock_memory_dump() -> Result<PathBuf, Box<dyn std::error::Error>> {
@@ -21,7 +21,7 @@ fn create_mock_memory_dump() -> Result<PathBuf, Box<dyn std::error::Error>> {
     // Create a PML4 entry at PML4[0] pointing to PDPT at 0x2000
     let pml4e_offset = cr3;
     let pdpt_addr = 0x2000;
-    let pml4e_value = pdpt_addr | 0x1; // Present bit set
+    let pml4e_value = (pdpt_addr | 0x1) as u64; // Present bit set
     
     // Write PML4 entry
     data[pml4e_offset..pml4e_offset + 8].copy_from_slice(&pml4e_value.to_le_bytes());
@@ -29,7 +29,7 @@ fn create_mock_memory_dump() -> Result<PathBuf, Box<dyn std::error::Error>> {
     // Create a PDPT entry at PDPT[0] pointing to PD at 0x3000
     let pdpt_offset = pdpt_addr;
     let pd_addr = 0x3000;
-    let pdpt_entry = pd_addr | 0x1; // Present bit set
+    let pdpt_entry = (pd_addr | 0x1) as u64; // Present bit set
     
     // Write PDPT entry
     data[pdpt_offset..pdpt_offset + 8].copy_from_slice(&pdpt_entry.to_le_bytes());
@@ -37,7 +37,7 @@ fn create_mock_memory_dump() -> Result<PathBuf, Box<dyn std::error::Error>> {
     // Create a PD entry at PD[0] pointing to PT at 0x4000
     let pd_offset = pd_addr;
     let pt_addr = 0x4000;
-    let pd_entry = pt_addr | 0x1; // Present bit set
+    let pd_entry = (pt_addr | 0x1) as u64; // Present bit set
     
     // Write PD entry
     data[pd_offset..pd_offset + 8].copy_from_slice(&pd_entry.to_le_bytes());
@@ -45,7 +45,7 @@ fn create_mock_memory_dump() -> Result<PathBuf, Box<dyn std::error::Error>> {
     // Create a PT entry at PT[0] pointing to a 4KB page at 0x5000
     let pt_offset = pt_addr;
     let page_addr = 0x5000;
-    let pt_entry = page_addr | 0x1; // Present bit set
+    let pt_entry = (page_addr | 0x1) as u64; // Present bit set
     
     // Write PT entry
     data[pt_offset..pt_offset + 8].copy_from_slice(&pt_entry.to_le_bytes());
@@ -90,7 +90,7 @@ fn test_address_translation_page_walk() -> Result<(), Box<dyn std::error::Error>
     assert_eq!(data.unwrap(), test_data, "Data at physical address doesn't match expected value");
     
     // Test virtual address component extraction
-    let va = VirtualAddress::new(0x123456789000);
+    let va = VirtualAddress::new((0x1 << 39) | (0x23 << 30) | (0x45 << 21) | (0x67 << 12) | 0x789);
     assert_eq!(va.get_pml4_index(), 0x1, "Wrong PML4 index");
     assert_eq!(va.get_pdpt_index(), 0x23, "Wrong PDPT index");
     assert_eq!(va.get_pd_index(), 0x45, "Wrong PD index");
@@ -100,6 +100,37 @@ fn test_address_translation_page_walk() -> Result<(), Box<dyn std::error::Error>
     Ok(())
 }
 
+#[test]
+fn test_unicode_string_spanning_pages() -> Result<(), Box<dyn std::error::Error>> {
+    let test_file = create_mock_memory_dump()?;
+    let mut data = std::fs::read(&test_file)?;
+    let wide = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect() };
+
+    // Virtual page 1 maps to 0x8000, not to the physically adjacent 0x6000
+    data[0x4008..0x4010].copy_from_slice(&(0x8000u64 | 0x1).to_le_bytes());
+    // UNICODE_STRING at VA 0x100: "SYSTEM" with its buffer at VA 0xFFA
+    data[0x5100..0x5102].copy_from_slice(&12u16.to_le_bytes());
+    data[0x5102..0x5104].copy_from_slice(&12u16.to_le_bytes());
+    data[0x5108..0x5110].copy_from_slice(&0xFFAu64.to_le_bytes());
+    data[0x5FFA..0x6000].copy_from_slice(&wide("SYS"));
+    data[0x6000..0x6006].copy_from_slice(&wide("XXX"));
+    data[0x8000..0x8006].copy_from_slice(&wide("TEM"));
+    std::fs::write(&test_file, &data)?;
+
+    let mut memory_image = load_memory_image(&test_file)?;
+    memory_image.set_cr3(0x1000);
+    assert_eq!(memory_image.read_unicode_string(0x100).as_deref(), Some("SYSTEM"));
+
+    // A tail in an unmapped page is not read from the next frame
+    data[0x4008..0x4010].copy_from_slice(&0u64.to_le_bytes());
+    std::fs::write(&test_file, &data)?;
+    let mut memory_image = load_memory_image(&test_file)?;
+    memory_image.set_cr3(0x1000);
+    assert_eq!(memory_image.read_unicode_string(0x100), None);
+
+    Ok(())
+}
+
 #[test]
 fn test_large_page_translation() -> Result<(), Box<dyn std::error::Error>> {
     let test_dir = tempdir()?;
@@ -115,7 +146,7 @@ fn test_large_page_translation() -> Result<(), Box<dyn std::error::Error>> {
     // Create a PML4 entry at PML4[0] pointing to PDPT at 0x2000
     let pml4e_offset = cr3;
     let pdpt_addr = 0x2000;
-    let pml4e_value = pdpt_addr | 0x1; // Present bit set
+    let pml4e_value = (pdpt_addr | 0x1) as u64; // Present bit set
     
     // Write PML4 entry
     data[pml4e_offset..pml4e_offset + 8].copy_from_slice(&pml4e_value.to_le_bytes());
@@ -123,7 +154,7 @@ fn test_large_page_translation() -> Result<(), Box<dyn std::error::Error>> {
     // Create a PDPT entry at PDPT[0] pointing to PD at 0x3000
     let pdpt_offset = pdpt_addr;
     let pd_addr = 0x3000;
-    let pdpt_entry = pd_addr | 0x1; // Present bit set
+    let pdpt_entry = (pd_addr | 0x1) as u64; // Present bit set
     
     // Write PDPT entry
     data[pdpt_offset..pdpt_offset + 8].copy_from_slice(&pdpt_entry.to_le_bytes());
@@ -132,7 +163,7 @@ fn test_large_page_translation() -> Result<(), Box<dyn std::error::Error>> {
     let pd_offset = pd_addr;
     let large_page_addr = 0x100000;
     // PS bit (bit 7) set for 2MB page
-    let pd_entry = large_page_addr | 0x1 | 0x80; 
+    let pd_entry = (large_page_addr | 0x1 | 0x80) as u64; 
     
     // Write PD entry
     data[pd_offset..pd_offset + 8].copy_from_slice(&pd_entry.to_le_bytes());
@@ -161,15 +192,15 @@ fn test_large_page_translation() -> Result<(), Box<dyn std::error::Error>> {
     let physical_addr_base = memory_image.virt_to_phys(virtual_addr_base);
     
     assert!(physical_addr_base.is_some(), "Failed to translate large page base address");
-    assert_eq!(physical_addr_base.unwrap(), large_page_addr, 
+    assert_eq!(physical_addr_base.unwrap(), large_page_addr as u64, 
               "Incorrect large page physical address translation");
     
     // Test the translation with an offset into the large page
-    let virtual_addr_offset = virtual_addr_base + offset;
+    let virtual_addr_offset = virtual_addr_base + offset as u64;
     let physical_addr_offset = memory_image.virt_to_phys(virtual_addr_offset);
     
     assert!(physical_addr_offset.is_some(), "Failed to translate address with offset in large page");
-    assert_eq!(physical_addr_offset.unwrap(), large_page_addr + offset, 
+    assert_eq!(physical_addr_offset.unwrap(), (large_page_addr + offset) as u64, 
               "Incorrect offset translation in large page");
     
     // Test reading the data at the offset
@@ -179,3 +210,380 @@ fn test_large_page_translation() -> Result<(), Box<dyn std::error::Error>> {
     
     Ok(())
 }
+
+#[test]
+fn test_pae_translation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 4 * 1024 * 1024];
+    let put = |data: &mut [u8], offset: usize, value: u64| {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    };
+
+    // PDPT at 0x1020 (32-byte aligned, not page aligned), entry 2 -> PD at 0x3000
+    let cr3 = 0x1020;
+    put(&mut data, cr3 + 2 * 8, 0x3000 | 0x1);
+    // PD[0x10] -> PT at 0x4000; PD[0x11] is a 2MB page at 0x200000 with NX set
+    put(&mut data, 0x3000 + 0x10 * 8, 0x4000 | 0x1);
+    put(&mut data, 0x3000 + 0x11 * 8, 0x8000_0000_0020_0000 | 0x80 | 0x1);
+    // PT[0x5] -> 4KB page at 0x6000
+    put(&mut data, 0x4000 + 0x5 * 8, 0x6000 | 0x1);
+    data[0x6123..0x6127].copy_from_slice(b"PAE!");
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("pae.bin");
+    std::fs::write(&path, &data)?;
+
+    let mut memory_image = load_memory_image(&path)?;
+    memory_image.set_cr3(cr3 as u64).set_arch(Architecture::X86Pae);
+
+    let small = (2u64 << 30) | (0x10 << 21) | (0x5 << 12) | 0x123;
+    assert_eq!(memory_image.virt_to_phys(small), Some(0x6123));
+    assert_eq!(memory_image.get_bytes(0x6123, 4), Some(&b"PAE!"[..]));
+
+    let large = (2u64 << 30) | (0x11 << 21) | 0x4_5678;
+    assert_eq!(memory_image.virt_to_phys(large), Some(0x20_0000 + 0x4_5678));
+
+    // Unmapped PDPT entry and addresses beyond 32 bits
+    assert_eq!(memory_image.virt_to_phys(0x1000), None);
+    assert_eq!(memory_image.virt_to_phys(1 << 32), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_x86_two_level_translation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 1024 * 1024];
+    let put = |data: &mut [u8], offset: usize, value: u32| {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    };
+
+    // PD at 0x2000: PD[0x300] -> PT at 0x3000, PD[0x301] is a 4MB page with PSE-36 high bits
+    put(&mut data, 0x2000 + 0x300 * 4, 0x3000 | 0x1);
+    put(&mut data, 0x2000 + 0x301 * 4, 0x0080_0000 | (0x2 << 13) | 0x80 | 0x1);
+    // PT[0x45] -> 4KB page at 0x9000
+    put(&mut data, 0x3000 + 0x45 * 4, 0x9000 | 0x1);
+    data[0x9ABC..0x9AC0].copy_from_slice(b"XP32");
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("x86.bin");
+    std::fs::write(&path, &data)?;
+
+    let mut memory_image = load_memory_image(&path)?;
+    memory_image.set_cr3(0x2000).set_arch(Architecture::X86);
+
+    let small = (0x300u64 << 22) | (0x45 << 12) | 0xABC;
+    assert_eq!(memory_image.virt_to_phys(small), Some(0x9ABC));
+    assert_eq!(memory_image.get_bytes(0x9ABC, 4), Some(&b"XP32"[..]));
+
+    let large = (0x301u64 << 22) | 0x12_3456;
+    assert_eq!(memory_image.virt_to_phys(large), Some((2 << 32) | 0x0080_0000 | 0x12_3456));
+
+    assert_eq!(memory_image.virt_to_phys(0x1000), None);
+    assert_eq!(memory_image.virt_to_phys(1 << 32), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_riscv_sv39_sv48_translation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 4 * 1024 * 1024];
+    let put = |data: &mut [u8], offset: u64, value: u64| {
+        let offset = offset as usize;
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    };
+    // PTE pointing at a table (V only) or mapping a page (V|R|W|X|A|D)
+    let table = |addr: u64| ((addr >> 12) << 10) | 0x1;
+    let leaf = |addr: u64| ((addr >> 12) << 10) | 0xCF;
+
+    // Sv39 root at 0x1000: kernel VA 0xFFFFFFC0_80000000 has VPN = [0, 0, 0x102]
+    put(&mut data, 0x1000 + 0x102 * 8, table(0x2000));
+    put(&mut data, 0x2000, table(0x3000));
+    put(&mut data, 0x3000 + 0x7 * 8, leaf(0x5000));
+    // VPN[1] = 1 is a 2MB superpage at 0x200000
+    put(&mut data, 0x2000 + 8, leaf(0x20_0000));
+    // VPN[1] = 2 is a misaligned superpage
+    put(&mut data, 0x2000 + 16, leaf(0x20_1000));
+    data[0x5ABC..0x5AC0].copy_from_slice(b"RV64");
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("riscv.bin");
+    std::fs::write(&path, &data)?;
+    let mut memory_image = load_memory_image(&path)?;
+
+    // A raw satp value (MODE=Sv39, PPN=1) is accepted as the DTB
+    memory_image.set_cr3((8 << 60) | 0x1).set_arch(Architecture::RiscvSv39);
+
+    let base = 0xFFFF_FFC0_8000_0000u64;
+    assert_eq!(memory_image.virt_to_phys(base + 0x7ABC), Some(0x5ABC));
+    assert_eq!(memory_image.get_bytes(0x5ABC, 4), Some(&b"RV64"[..]));
+    assert_eq!(memory_image.virt_to_phys(base + 0x20_1234), Some(0x20_1234));
+    assert_eq!(memory_image.virt_to_phys(base + 0x40_0000), None);
+    // Not sign-extended from bit 38
+    assert_eq!(memory_image.virt_to_phys(0x0000_0040_8000_0000), None);
+
+    // Sv48 adds a level on top: root at 0x6000, VPN[3] = 0x1FF for the same address
+    put(&mut data, 0x6000 + 0x1FF * 8, table(0x1000));
+    std::fs::write(&path, &data)?;
+    let mut memory_image = load_memory_image(&path)?;
+    memory_image.set_cr3(0x6000).set_arch(Architecture::RiscvSv48);
+    assert_eq!(memory_image.virt_to_phys(base + 0x7ABC), Some(0x5ABC));
+
+    Ok(())
+}
+
+#[test]
+fn test_physical_memory_stats() -> Result<(), Box<dyn std::error::Error>> {
+    let test_dir = tempdir()?;
+    let test_file = test_dir.path().join("stats_test.bin");
+    let mut data = vec![0u8; 0x10000];
+    let mut put = |offset: usize, value: u64| data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+
+    // User half: PML4[0] -> PDPT 0x2000 -> PD 0x3000 -> PT 0x4000
+    put(0x1000, 0x2000 | 0x1);
+    put(0x2000, 0x3000 | 0x1);
+    put(0x3000, 0x4000 | 0x1);
+    put(0x4000, 0x5000 | 0x1);
+    put(0x4008, 0x7000 | 0x1 | (1 << 63)); // NX data page
+    // Kernel half: PML4[256] -> PDPT 0x6000 -> PD 0x8000 -> PT 0x9000
+    put(0x1000 + 256 * 8, 0x6000 | 0x1);
+    put(0x6000, 0x8000 | 0x1);
+    put(0x8000, 0x9000 | 0x1);
+    put(0x9000, 0xA000 | 0x1);
+    // A page table that is not reachable from the DTB
+    for i in 0..4 {
+        put(0xC000 + i * 8, 0xD000 | 0x1);
+    }
+    for page in [0x5000, 0x7000, 0xA000, 0xB000] {
+        data[page..page + 4].copy_from_slice(b"DATA");
+    }
+    File::create(&test_file)?.write_all(&data)?;
+
+    let mut memory_image = load_memory_image(&test_file)?;
+    let progress = indicatif::ProgressBar::hidden();
+
+    // Without a DTB only shapes are recognised
+    let stats = crate::stats::compute_stats(&memory_image, &progress);
+    assert!(!stats.mappings_known);
+    assert_eq!(stats.total_pages, 16);
+    assert_eq!(stats.page_table_pages, 1, "Only the orphan table has enough entries");
+    assert_eq!(stats.kernel_pages + stats.process_pages + stats.executable_pages, 0);
+
+    memory_image.set_cr3(0x1000);
+    let stats = crate::stats::compute_stats(&memory_image, &progress);
+    assert!(stats.mappings_known);
+    assert_eq!(stats.page_table_pages, 8);
+    assert_eq!(stats.zero_pages, 4);
+    assert_eq!(stats.process_pages, 2);
+    assert_eq!(stats.kernel_pages, 1);
+    assert_eq!(stats.unidentified_pages, 1);
+    assert_eq!(stats.executable_pages, 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_dtb_candidates_are_scored_and_tried_in_order() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::dtb::{find_dtb_candidates, score_dtb, translate_with_candidates};
+
+    let mut data = vec![0u8; 1024 * 1024];
+    let put = |data: &mut [u8], offset: usize, value: u64| {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    };
+
+    // Kernel PML4 at 0x1000, mapped onto itself at 0x1ED, maps 0xFFFFF80000000000 to 0x5000
+    put(&mut data, 0x1000 + 0x1ED * 8, 0x1000 | 0x3);
+    put(&mut data, 0x1000 + 0x1F0 * 8, 0x2000 | 0x3);
+    put(&mut data, 0x2000, 0x3000 | 0x3);
+    put(&mut data, 0x3000, 0x4000 | 0x3);
+    put(&mut data, 0x4000, 0x5000 | 0x3);
+    data[0x5123..0x5127].copy_from_slice(b"KERN");
+
+    // A sane table with more kernel entries ranks first but maps other addresses
+    put(&mut data, 0x8000 + 0x1ED * 8, 0x8000 | 0x3);
+    put(&mut data, 0x8000 + 0x100 * 8, 0x9000 | 0x3);
+    put(&mut data, 0x8000 + 0x101 * 8, 0x9000 | 0x3);
+    put(&mut data, 0x9000, 0xA000 | 0x3);
+
+    // One sane entry out of four scores 25% and is dropped
+    put(&mut data, 0xC000 + 0x180 * 8, 0x2000 | 0x3);
+    for i in 0..3 {
+        put(&mut data, 0xC000 + (0x190 + i) * 8, 0x7FFF_0000_0000 | 0x3);
+    }
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("dtb.bin");
+    std::fs::write(&path, &data)?;
+    let mut memory_image = load_memory_image(&path)?;
+    let progress = indicatif::ProgressBar::hidden();
+
+    let candidates = find_dtb_candidates(&memory_image, &progress);
+    let dtbs: Vec<_> = candidates.iter().map(|c| c.dtb).collect();
+    assert_eq!(dtbs, vec![0x8000, 0x1000]);
+    assert_eq!(candidates[1].to_string(), "0x1000 (score 100%, 2/2 kernel entries, self-map 0x1ED)");
+    assert_eq!(score_dtb(&memory_image, 0xC000).map(|c| c.score()), Some(25.0));
+    assert!(score_dtb(&memory_image, 0x2000).is_none(), "A PDPT has no kernel-half entries");
+
+    // The first candidate that maps the address is kept
+    let kernel_va = 0xFFFF_F800_0000_0123;
+    assert_eq!(translate_with_candidates(&mut memory_image, &candidates, kernel_va), Some((1, 0x5123)));
+    assert_eq!(memory_image.info.dtb, Some(0x1000));
+    assert_eq!(translate_with_candidates(&mut memory_image, &candidates, 0x7000_0000_0000), None);
+
+    memory_image.set_arch(Architecture::RiscvSv39);
+    assert!(find_dtb_candidates(&memory_image, &progress).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_page_walk_reports_each_level() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::paging::{classify_address, AddressClass};
+
+    let mut data = vec![0u8; 1024 * 1024];
+    let put = |data: &mut [u8], offset: usize, value: u64| {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    };
+    // Kernel PML4[0x1F0] -> PDPT: [0] -> PD, [1] is a 1GB page; PD[0] -> PT, PD[1] is a 2MB NX page
+    put(&mut data, 0x1000 + 0x1F0 * 8, 0x2000 | 0x3);
+    put(&mut data, 0x2000, 0x3000 | 0x3);
+    put(&mut data, 0x2008, 0x4000_0000 | 0x83);
+    put(&mut data, 0x3000, 0x4000 | 0x3);
+    put(&mut data, 0x3008, 0x8000_0000_0020_0000 | 0x83);
+    put(&mut data, 0x4000 + 5 * 8, 0x9000 | 0x167);
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("walk.bin");
+    std::fs::write(&path, &data)?;
+    let mut memory_image = load_memory_image(&path)?;
+    memory_image.set_cr3(0x1000);
+
+    let kernel = 0xFFFF_F800_0000_0000u64;
+    let walk = memory_image.page_walk(kernel + 0x5ABC);
+    assert_eq!(walk.class, AddressClass::Kernel);
+    assert_eq!(walk.pa, Some(0x9ABC));
+    assert_eq!(walk.pa, memory_image.virt_to_phys(kernel + 0x5ABC));
+    let levels: Vec<_> = walk.steps.iter().map(|s| (s.level, s.index, s.entry_addr)).collect();
+    assert_eq!(levels, vec![("PML4E", 0x1F0, 0x1F80), ("PDPTE", 0, 0x2000), ("PDE", 0, 0x3000), ("PTE", 5, 0x4028)]);
+    assert_eq!(walk.steps[3].flags, "P RW US A D G");
+    assert_eq!(walk.steps[3].page_size, Some(0x1000));
+    assert!(walk.steps[..3].iter().all(|s| !s.leaf));
+
+    let walk = memory_image.page_walk(kernel + 0x21_2345);
+    assert_eq!(walk.pa, Some(0x21_2345));
+    let pde = walk.steps.last().unwrap();
+    assert!(pde.leaf && pde.page_size == Some(0x20_0000));
+    assert_eq!(pde.flags, "P RW PS NX");
+
+    let walk = memory_image.page_walk(kernel + 0x4012_3456);
+    assert_eq!((walk.steps.len(), walk.pa), (2, Some(0x4012_3456)));
+    assert_eq!(walk.steps[1].page_size, Some(1 << 30));
+
+    // Walks stop at the first entry that is not present
+    let walk = memory_image.page_walk(kernel + 0x8000_0000);
+    assert_eq!(walk.steps.len(), 2);
+    assert_eq!(walk.fault.as_deref(), Some("PDPTE not present"));
+
+    // Non-canonical addresses are not walked even though their low bits would translate
+    let walk = memory_image.page_walk(0x0000_F800_0000_5ABC);
+    assert_eq!(walk.class, AddressClass::NonCanonical);
+    assert!(walk.steps.is_empty() && walk.pa.is_none());
+
+    assert_eq!(classify_address(Architecture::X86_64, 0x10), AddressClass::NullGuard);
+    assert_eq!(classify_address(Architecture::X86_64, 0x7FF6_1234_0000), AddressClass::User);
+    assert_eq!(classify_address(Architecture::X86_64, 0x7FFF_FFFF_8000), AddressClass::BoundaryGuard);
+    assert_eq!(classify_address(Architecture::RiscvSv39, 0xFFFF_FFC0_8000_0000), AddressClass::Kernel);
+    assert_eq!(classify_address(Architecture::RiscvSv39, 0x0000_0040_8000_0000), AddressClass::NonCanonical);
+    assert_eq!(classify_address(Architecture::X86, 0x8000_0000), AddressClass::Unsplit);
+
+    // 32-bit 4MB pages keep their PSE-36 high address bits
+    let pde = 0x0080_0000u32 | (0x2 << 13) | 0x80 | 0x1;
+    data[0x6000 + 0x301 * 4..0x6000 + 0x302 * 4].copy_from_slice(&pde.to_le_bytes());
+    std::fs::write(&path, &data)?;
+    let mut memory_image = load_memory_image(&path)?;
+    memory_image.set_cr3(0x6000).set_arch(Architecture::X86);
+    let walk = memory_image.page_walk((0x301 << 22) | 0x12_3456);
+    assert_eq!(walk.pa, Some((2 << 32) | 0x0080_0000 | 0x12_3456));
+    assert_eq!(walk.steps[0].page_size, Some(4 << 20));
+
+    Ok(())
+}
+
+#[test]
+fn test_range_restriction_keeps_original_addresses() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::paging::{AddressRange, ImageFormat, MemoryImage, PhysicalRun};
+
+    let range: AddressRange = "0x1800:3000".parse()?;
+    assert_eq!((range.start, range.end, range.len()), (0x1800, 0x3000, 0x1800));
+    assert_eq!(range.to_string(), "0x1800-0x3000");
+    assert!("0x3000:0x1000".parse::<AddressRange>().is_err());
+    assert!("0x3000".parse::<AddressRange>().is_err());
+    assert!("0x1000:zz".parse::<AddressRange>().is_err());
+
+    // Two physical runs with a hole between them, the second stored first in the file
+    let mut data = vec![0xAAu8; 0x2000];
+    data.extend(vec![0xBBu8; 0x2000]);
+    let runs = vec![
+        PhysicalRun { start: 0x0, length: 0x2000, file_offset: 0x2000, segment: 0 },
+        PhysicalRun { start: 0x2800, length: 0x2000, file_offset: 0x0, segment: 0 },
+    ];
+    let mut img = MemoryImage::with_runs(data, runs, ImageFormat::ElfCore);
+    img.restrict_to(range);
+
+    assert_eq!(img.info.range, Some(range));
+    assert_eq!(img.runs(), &[
+        PhysicalRun { start: 0x1800, length: 0x800, file_offset: 0x3800, segment: 0 },
+        PhysicalRun { start: 0x2800, length: 0x800, file_offset: 0x0, segment: 0 },
+    ]);
+    assert_eq!(img.size(), 0x3000);
+    assert_eq!(img.get_bytes(0x1800, 1), Some(&[0xBB][..]));
+    assert_eq!(img.get_bytes(0x2800, 1), Some(&[0xAA][..]));
+    assert!(img.get_bytes(0x17FF, 1).is_none());
+    assert!(img.get_bytes(0x3000, 1).is_none());
+
+    img.restrict_to("0x8000:0x9000".parse()?);
+    assert!(img.runs().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_hit_map_round_trips_and_restricts_second_pass() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::hits::HitMap;
+    use crate::paging::{AddressRange, ImageFormat, MemoryImage, PhysicalRun};
+    use crate::plugin::Finding;
+
+    let finding = |addr: u64| Finding { plugin: "string_carve".to_string(), addr, desc: String::new(), confidence: 50, details: Default::default() };
+    let mut map = HitMap::from_findings(&[finding(0x1010), finding(0x1FF0), finding(0x2000), finding(0x9000)], 0x1000);
+    map.insert(0x5FFF, 2);
+    assert!(map.contains(0x2FFF) && !map.contains(0x3000));
+    let range = |start, end| AddressRange { start, end };
+    assert_eq!(map.ranges(), vec![range(0x1000, 0x3000), range(0x5000, 0x7000), range(0x9000, 0xA000)]);
+    assert_eq!(map.covered(), 0x5000);
+    assert_eq!(map.ranges_with_context(0x1000), vec![range(0x0, 0xB000)]);
+    assert_eq!(map.ranges_with_context(0x800), vec![range(0x800, 0x3800), range(0x4800, 0x7800), range(0x8800, 0xA800)]);
+
+    let dir = tempdir()?;
+    let path = dir.path().join("hits.txt");
+    map.save(&path)?;
+    let text = std::fs::read_to_string(&path)?;
+    assert_eq!(text, "# rmf hits v1\n# granularity 0x1000\n0x1000:0x3000\n0x5000:0x7000\n0x9000:0xA000\n");
+    assert_eq!(HitMap::load(&path)?, map);
+
+    // Interval files from other tools need no header
+    std::fs::write(&path, "0x2000:0x2800\n\n")?;
+    assert_eq!(HitMap::load(&path)?.ranges(), vec![range(0x2000, 0x3000)]);
+    std::fs::write(&path, "# nothing found\n")?;
+    assert!(HitMap::load(&path).is_err());
+    std::fs::write(&path, "0x2000-0x3000\n")?;
+    assert!(HitMap::load(&path).is_err());
+
+    // The second pass only sees the hit ranges, at their original addresses
+    let data: Vec<u8> = (0..0xC000u32).map(|i| (i >> 12) as u8).collect();
+    let runs = vec![PhysicalRun { start: 0, length: 0xC000, file_offset: 0, segment: 0 }];
+    let mut img = MemoryImage::with_runs(data, runs, ImageFormat::Raw);
+    img.restrict_to_ranges(&map.ranges());
+    assert_eq!(img.runs().iter().map(|r| (r.start, r.length)).collect::<Vec<_>>(),
+        vec![(0x1000, 0x2000), (0x5000, 0x2000), (0x9000, 0x1000)]);
+    assert_eq!(img.get_bytes(0x9000, 1), Some(&[9][..]));
+    assert!(img.get_bytes(0x3000, 1).is_none());
+    assert_eq!(img.info.range, None);
+    Ok(())
+}
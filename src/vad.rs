@@ -0,0 +1,265 @@
+//! Virtual address descriptors of Windows processes
+//!
+//! Every user-mode allocation of a process is described by an MMVAD node in
+//! a balanced tree rooted at `EPROCESS.VadRoot`, ordered by start address.
+//! A node records the page range, the protection the region was created
+//! with and whether it is private memory, a mapped file or an image; mapped
+//! and image nodes reach their file through Subsection -> ControlArea ->
+//! FilePointer. The tree lists reserved regions whose pages were never
+//! touched too, so it complements the page tables rather than mirroring them.
+
+use anyhow::{bail, Context, Result};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use prettytable::{cell, format, row, Table};
+use std::fmt;
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use pager::Pager;
+
+use crate::kdbg::OsContext;
+use crate::loader::load_memory_image;
+use crate::paging::MemoryImage;
+use crate::processes::{windows_finder, Process, ProcessFinder};
+use crate::symbols::SymbolStore;
+
+/// Upper bound on tree nodes, guarding against corrupted links
+const MAX_VADS: usize = 0x10000;
+
+/// Reference count bits of an x64 `EX_FAST_REF`
+const FAST_REF_MASK: u64 = 0xF;
+
+/// `_MMVAD_FLAGS` bit positions, Windows 10 1809 and later
+const VAD_TYPE_SHIFT: u32 = 4;
+const PROTECTION_SHIFT: u32 = 7;
+const PRIVATE_MEMORY_BIT: u32 = 20;
+
+/// `VadType` of a view of an executable image
+const VAD_IMAGE_MAP: u32 = 2;
+
+/// Offsets in the VAD structures; defaults are for Windows 10 x64
+#[derive(Debug, Clone)]
+pub(crate) struct VadLayout {
+    pub(crate) left_offset: usize,
+    pub(crate) right_offset: usize,
+    pub(crate) starting_vpn_offset: usize,
+    pub(crate) ending_vpn_offset: usize,
+    pub(crate) starting_vpn_high_offset: usize,
+    pub(crate) ending_vpn_high_offset: usize,
+    pub(crate) flags_offset: usize,
+    pub(crate) subsection_offset: usize,
+    pub(crate) control_area_offset: usize,
+    pub(crate) file_pointer_offset: usize,
+    pub(crate) file_name_offset: usize,
+}
+
+impl Default for VadLayout {
+    fn default() -> Self {
+        VadLayout {
+            left_offset: 0x0,
+            right_offset: 0x8,
+            starting_vpn_offset: 0x18,
+            ending_vpn_offset: 0x1C,
+            starting_vpn_high_offset: 0x20,
+            ending_vpn_high_offset: 0x21,
+            flags_offset: 0x30,
+            subsection_offset: 0x48,
+            control_area_offset: 0x0,
+            file_pointer_offset: 0x40,
+            file_name_offset: 0x58,
+        }
+    }
+}
+
+/// What backs a region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VadKind {
+    /// Heaps, stacks and `VirtualAlloc` memory
+    Private,
+    /// A view of a data file or of pagefile-backed shared memory
+    Mapped,
+    /// A view of an executable image
+    Image,
+}
+
+impl fmt::Display for VadKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VadKind::Private => "Private",
+            VadKind::Mapped => "Mapped",
+            VadKind::Image => "Image",
+        })
+    }
+}
+
+/// Names of the memory manager's protection values, by their low 3 bits
+const PROTECTIONS: [&str; 8] = [
+    "PAGE_NOACCESS", "PAGE_READONLY", "PAGE_EXECUTE", "PAGE_EXECUTE_READ",
+    "PAGE_READWRITE", "PAGE_WRITECOPY", "PAGE_EXECUTE_READWRITE", "PAGE_EXECUTE_WRITECOPY",
+];
+
+/// The 5-bit protection a region was created with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VadProtection(pub u8);
+
+impl VadProtection {
+    pub fn is_executable(&self) -> bool {
+        matches!(self.0 & 7, 2 | 3 | 6 | 7)
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.0 & 7 >= 4
+    }
+}
+
+impl fmt::Display for VadProtection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(PROTECTIONS[(self.0 & 7) as usize])?;
+        match self.0 >> 3 & 3 {
+            1 => f.write_str(" | PAGE_NOCACHE"),
+            2 => f.write_str(" | PAGE_GUARD"),
+            3 => f.write_str(" | PAGE_WRITECOMBINE"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// One node of the VAD tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct VadRegion {
+    /// Virtual address of the MMVAD
+    pub address: u64,
+    pub start: u64,
+    /// Last byte of the region
+    pub end: u64,
+    pub protection: VadProtection,
+    pub kind: VadKind,
+    /// Name of the mapped file or image
+    pub file: Option<String>,
+}
+
+impl VadRegion {
+    pub fn size(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    pub fn contains(&self, va: u64) -> bool {
+        (self.start..=self.end).contains(&va)
+    }
+}
+
+/// Decode the MMVAD at virtual address `vad`
+fn read_vad(img: &MemoryImage, vad: u64, layout: &VadLayout) -> Option<VadRegion> {
+    let read_u8 = |off: usize| img.read_virt(vad + off as u64, 1).map(|b| b[0] as u64);
+    let vpn = |low: usize, high: usize| Some((read_u8(high)? << 32 | img.read_virt_u32(vad + low as u64)? as u64) << 12);
+    let start = vpn(layout.starting_vpn_offset, layout.starting_vpn_high_offset)?;
+    let end = vpn(layout.ending_vpn_offset, layout.ending_vpn_high_offset)? | 0xFFF;
+    if end < start {
+        return None;
+    }
+
+    let flags = img.read_virt_u32(vad + layout.flags_offset as u64)?;
+    let kind = if flags >> PRIVATE_MEMORY_BIT & 1 != 0 {
+        VadKind::Private
+    } else if flags >> VAD_TYPE_SHIFT & 7 == VAD_IMAGE_MAP {
+        VadKind::Image
+    } else {
+        VadKind::Mapped
+    };
+    let file = (kind != VadKind::Private).then(|| file_name(img, vad, layout)).flatten();
+    Some(VadRegion {
+        address: vad,
+        start,
+        end,
+        protection: VadProtection((flags >> PROTECTION_SHIFT & 0x1F) as u8),
+        kind,
+        file,
+    })
+}
+
+/// Name of the file behind a mapped or image VAD, through its control area
+fn file_name(img: &MemoryImage, vad: u64, layout: &VadLayout) -> Option<String> {
+    let subsection = img.read_virt_u64(vad + layout.subsection_offset as u64).filter(|&p| p != 0)?;
+    let control_area = img.read_virt_u64(subsection + layout.control_area_offset as u64).filter(|&p| p != 0)?;
+    let file = img.read_virt_u64(control_area + layout.file_pointer_offset as u64)? & !FAST_REF_MASK;
+    if file == 0 {
+        return None;
+    }
+    img.read_unicode_string(file + layout.file_name_offset as u64)
+}
+
+/// Walk the tree under `root` in address order; the image must use the
+/// kernel DTB. Unreadable nodes are skipped along with their subtrees.
+pub(crate) fn walk_vad_tree(img: &MemoryImage, root: u64, layout: &VadLayout) -> Vec<VadRegion> {
+    let mut regions = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if node == 0 || !seen.insert(node) || seen.len() > MAX_VADS {
+            continue;
+        }
+        let Some(region) = read_vad(img, node, layout) else { continue };
+        regions.push(region);
+        for offset in [layout.left_offset, layout.right_offset] {
+            stack.push(img.read_virt_u64(node + offset as u64).unwrap_or(0));
+        }
+    }
+    regions.sort_by_key(|r| r.start);
+    regions
+}
+
+/// List the VADs of every process, or only of `pid`
+pub fn list_vads(dump_path: PathBuf, dtb: u64, pid: Option<u32>, symbols: Option<SymbolStore>) -> Result<()> {
+    let mut memory_image = load_memory_image(&dump_path)?;
+    memory_image.set_cr3(dtb);
+
+    let progress = crate::progress::attach(ProgressBar::new(100));
+    progress.set_style(ProgressStyle::with_template(
+        "[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}"
+    )?.progress_chars("#>-"));
+    let os = OsContext::find(&memory_image, &progress).context("No KDBG block found; cannot walk the process list")?;
+    let finder = windows_finder(&memory_image, os, symbols.as_ref());
+    let processes: Vec<Process> = finder.find_processes(&memory_image, &progress)?
+        .into_iter()
+        .filter(|p| pid.is_none_or(|pid| p.pid == pid))
+        .collect();
+    progress.finish_and_clear();
+    if let (Some(pid), true) = (pid, processes.is_empty()) {
+        bail!("Process {} is not on the active process list", pid);
+    }
+
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
+    table.set_titles(row![bFg->"PID", bFg->"Process", bFg->"VAD", bFg->"Start", bFg->"End", bFg->"Type", bFg->"Protection", bFg->"File"]);
+    let mut count = 0;
+    for process in &processes {
+        for region in finder.vads(&memory_image, process) {
+            let protection = if region.protection.is_executable() && region.kind == VadKind::Private {
+                cell!(Fr->region.protection)
+            } else {
+                cell!(region.protection)
+            };
+            let mut row = row![
+                process.pid,
+                process.name,
+                format!("0x{:X}", region.address),
+                format!("0x{:X}", region.start),
+                format!("0x{:X}", region.end),
+                region.kind
+            ];
+            row.add_cell(protection);
+            row.add_cell(cell!(region.file.as_deref().unwrap_or("")));
+            table.add_row(row);
+            count += 1;
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if count > 20 {
+        Pager::new().setup();
+    }
+
+    println!("{} {} regions in {} processes", "Found".bright_green(), count.to_string().bright_yellow(), processes.len());
+    table.printstd();
+    Ok(())
+}